@@ -69,6 +69,7 @@ async fn main() -> std::io::Result<()> {
         Commands::Group(command) => command.exec(client).await,
         Commands::Queue(command) => command.exec(client).await,
         Commands::Report(command) => command.exec(client).await,
+        Commands::Audit(command) => command.exec(client).await,
     }
 
     Ok(())
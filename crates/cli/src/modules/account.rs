@@ -27,6 +27,7 @@ impl AccountCommands {
                 is_admin,
                 addresses,
                 member_of,
+                roles,
             } => {
                 let principal = Principal {
                     typ: if is_admin.unwrap_or_default() {
@@ -41,6 +42,7 @@ impl AccountCommands {
                     emails: addresses.unwrap_or_default(),
                     member_of: member_of.unwrap_or_default(),
                     description,
+                    roles: roles.unwrap_or_default(),
                     ..Default::default()
                 };
                 let account_id = client
@@ -57,6 +59,7 @@ impl AccountCommands {
                 is_admin,
                 addresses,
                 member_of,
+                roles,
             } => {
                 let mut changes = Vec::new();
                 if let Some(new_name) = new_name {
@@ -109,6 +112,12 @@ impl AccountCommands {
                         PrincipalValue::StringList(member_of),
                     ));
                 }
+                if let Some(roles) = roles {
+                    changes.push(PrincipalUpdate::set(
+                        PrincipalField::Roles,
+                        PrincipalValue::StringList(roles),
+                    ));
+                }
 
                 if !changes.is_empty() {
                     client
@@ -203,6 +212,46 @@ impl AccountCommands {
                     .await;
                 eprintln!("Successfully updated account {name:?}.");
             }
+            AccountCommands::AddRole { name, roles } => {
+                client
+                    .http_request::<Value, _>(
+                        Method::PATCH,
+                        &format!("/api/principal/{name}"),
+                        Some(
+                            roles
+                                .into_iter()
+                                .map(|role| {
+                                    PrincipalUpdate::add_item(
+                                        PrincipalField::Roles,
+                                        PrincipalValue::String(role),
+                                    )
+                                })
+                                .collect::<Vec<_>>(),
+                        ),
+                    )
+                    .await;
+                eprintln!("Successfully updated account {name:?}.");
+            }
+            AccountCommands::RemoveRole { name, roles } => {
+                client
+                    .http_request::<Value, _>(
+                        Method::PATCH,
+                        &format!("/api/principal/{name}"),
+                        Some(
+                            roles
+                                .into_iter()
+                                .map(|role| {
+                                    PrincipalUpdate::remove_item(
+                                        PrincipalField::Roles,
+                                        PrincipalValue::String(role),
+                                    )
+                                })
+                                .collect::<Vec<_>>(),
+                        ),
+                    )
+                    .await;
+                eprintln!("Successfully updated account {name:?}.");
+            }
             AccountCommands::Delete { name } => {
                 client
                     .http_request::<Value, String>(
@@ -292,6 +341,12 @@ impl Client {
                 Cell::new(&principal.emails.join(", ")),
             ]));
         }
+        if !principal.roles.is_empty() {
+            table.add_row(Row::new(vec![
+                Cell::new("Roles").with_style(Attr::Bold),
+                Cell::new(&principal.roles.join(", ")),
+            ]));
+        }
         eprintln!();
         table.printstd();
         eprintln!();
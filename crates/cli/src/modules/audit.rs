@@ -0,0 +1,123 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use mail_parser::DateTime;
+use prettytable::{Attr, Cell, Row, Table};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::modules::List;
+
+use super::cli::{AuditCommands, Client};
+
+#[derive(Debug, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub actor: String,
+    #[serde(rename = "remoteIp")]
+    pub remote_ip: String,
+    pub action: String,
+    pub target: String,
+    #[serde(default)]
+    pub request: Option<Value>,
+    #[serde(default)]
+    pub before: Option<Value>,
+    #[serde(default)]
+    pub after: Option<Value>,
+    pub outcome: String,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl AuditCommands {
+    pub async fn exec(self, client: Client) {
+        match self {
+            AuditCommands::List {
+                actor,
+                text,
+                page,
+                limit,
+                details,
+            } => {
+                let mut query = form_urlencoded::Serializer::new("/api/audit?".to_string());
+                if let Some(actor) = &actor {
+                    query.append_pair("actor", actor);
+                }
+                if let Some(text) = &text {
+                    query.append_pair("text", text);
+                }
+                query.append_pair("page", &page.unwrap_or(1).to_string());
+                query.append_pair("limit", &limit.unwrap_or(20).to_string());
+
+                let entries = client
+                    .http_request::<List<AuditEntry>, String>(Method::GET, &query.finish(), None)
+                    .await;
+                if !entries.items.is_empty() {
+                    let mut table = Table::new();
+                    table.add_row(Row::new(
+                        ["Date", "Actor", "Remote IP", "Action", "Target", "Outcome"]
+                            .iter()
+                            .map(|p| Cell::new(p).with_style(Attr::Bold))
+                            .collect(),
+                    ));
+
+                    for entry in &entries.items {
+                        table.add_row(Row::new(vec![
+                            Cell::new(
+                                &DateTime::from_timestamp(entry.timestamp as i64).to_rfc822(),
+                            ),
+                            Cell::new(&entry.actor),
+                            Cell::new(&entry.remote_ip),
+                            Cell::new(&entry.action),
+                            Cell::new(&entry.target),
+                            Cell::new(&match &entry.error {
+                                Some(error) => format!("{} ({error})", entry.outcome),
+                                None => entry.outcome.clone(),
+                            }),
+                        ]));
+                    }
+
+                    eprintln!();
+                    table.printstd();
+                    eprintln!();
+                }
+
+                if details {
+                    for entry in &entries.items {
+                        eprintln!(
+                            "{} {} {}",
+                            DateTime::from_timestamp(entry.timestamp as i64).to_rfc822(),
+                            entry.action,
+                            entry.target
+                        );
+                        for (name, value) in [
+                            ("Request", &entry.request),
+                            ("Before", &entry.before),
+                            ("After", &entry.after),
+                        ] {
+                            if let Some(value) = value {
+                                eprintln!(
+                                    "  {name}: {}",
+                                    serde_json::to_string_pretty(value)
+                                        .unwrap_or_default()
+                                        .replace('\n', "\n  ")
+                                );
+                            }
+                        }
+                        eprintln!();
+                    }
+                }
+
+                eprintln!(
+                    "\n\n{} audit log entr{} found.\n",
+                    entries.total,
+                    if entries.total == 1 { "y" } else { "ies" }
+                );
+            }
+        }
+    }
+}
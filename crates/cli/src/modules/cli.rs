@@ -63,6 +63,10 @@ pub enum Commands {
     /// Manage SMTP DMARC/TLS report queue
     #[clap(subcommand)]
     Report(ReportCommands),
+
+    /// View the audit log
+    #[clap(subcommand)]
+    Audit(AuditCommands),
 }
 
 pub struct Client {
@@ -94,6 +98,9 @@ pub enum AccountCommands {
         /// Groups this account is a member of
         #[clap(short, long)]
         member_of: Option<Vec<String>>,
+        /// Administrative roles (domain-admin, helpdesk, auditor), optionally limited to a domain as role:domain
+        #[clap(short, long)]
+        roles: Option<Vec<String>>,
     },
 
     /// Update an existing user account
@@ -121,6 +128,9 @@ pub enum AccountCommands {
         /// Update groups this account is a member of
         #[clap(short, long)]
         member_of: Option<Vec<String>>,
+        /// Update administrative roles
+        #[clap(short, long)]
+        roles: Option<Vec<String>>,
     },
 
     /// Add e-mail aliases to a user account
@@ -159,6 +169,24 @@ pub enum AccountCommands {
         member_of: Vec<String>,
     },
 
+    /// Grant administrative roles to a user account
+    AddRole {
+        /// Account login
+        name: String,
+        /// Roles to add (domain-admin, helpdesk, auditor), optionally as role:domain
+        #[clap(required = true)]
+        roles: Vec<String>,
+    },
+
+    /// Revoke administrative roles from a user account
+    RemoveRole {
+        /// Account login
+        name: String,
+        /// Roles to remove
+        #[clap(required = true)]
+        roles: Vec<String>,
+    },
+
     /// Delete an existing user account
     Delete {
         /// Account name to delete
@@ -501,6 +529,58 @@ pub enum QueueCommands {
         // Cancel one or multiple message ids
         ids: Vec<String>,
     },
+
+    /// Shows the delivery history of completed messages, newest first
+    History {
+        /// Filter by sender address
+        #[clap(short, long)]
+        sender: Option<String>,
+        /// Filter by recipient
+        #[clap(short, long)]
+        rcpt: Option<String>,
+        /// Filter by queue id
+        #[clap(short, long)]
+        queue_id: Option<String>,
+        /// Filter by Message-ID header
+        #[clap(short, long)]
+        message_id: Option<String>,
+        /// Page number to display
+        #[clap(short, long)]
+        page: Option<usize>,
+        /// Number of entries per page
+        #[clap(short, long)]
+        limit: Option<usize>,
+        /// Display the recipients and transport details of each entry
+        #[clap(short, long)]
+        details: bool,
+    },
+
+    /// Lists recorded SMTP session transcripts or displays a single one
+    Transcripts {
+        /// Transcript id to display
+        id: Option<String>,
+        /// Filter by direction (inbound or outbound)
+        #[clap(short, long)]
+        direction: Option<String>,
+        /// Filter by remote IP address
+        #[clap(short, long)]
+        ip: Option<String>,
+        /// Filter by remote hostname
+        #[clap(long)]
+        host: Option<String>,
+        /// Filter by queue id
+        #[clap(short, long)]
+        queue_id: Option<String>,
+        /// Filter by text contained in the transcript
+        #[clap(short, long)]
+        text: Option<String>,
+        /// Page number to display
+        #[clap(short, long)]
+        page: Option<usize>,
+        /// Number of entries per page
+        #[clap(short, long)]
+        limit: Option<usize>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -532,6 +612,28 @@ pub enum ReportCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum AuditCommands {
+    /// Lists audit log entries, newest first
+    List {
+        /// Filter by actor login name
+        #[clap(short, long)]
+        actor: Option<String>,
+        /// Filter by text contained in the entry
+        #[clap(short, long)]
+        text: Option<String>,
+        /// Page number to display
+        #[clap(short, long)]
+        page: Option<usize>,
+        /// Number of entries per page
+        #[clap(short, long)]
+        limit: Option<usize>,
+        /// Display the changes made on each entry
+        #[clap(short, long)]
+        details: bool,
+    },
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Deserialize)]
 pub enum ReportFormat {
     /// DMARC report
@@ -13,6 +13,7 @@ use jmap_client::{
 use serde::{Deserialize, Serialize};
 
 pub mod account;
+pub mod audit;
 pub mod cli;
 pub mod database;
 pub mod domain;
@@ -59,6 +60,9 @@ pub struct Principal {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -98,6 +102,8 @@ pub enum PrincipalField {
     MemberOf,
     #[serde(rename = "members")]
     Members,
+    #[serde(rename = "roles")]
+    Roles,
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize, Default)]
@@ -49,6 +49,78 @@ pub struct Recipient {
     pub orcpt: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeliveryRecord {
+    #[serde(rename = "queueId")]
+    pub queue_id: u64,
+    #[serde(rename = "messageId")]
+    #[serde(default)]
+    pub message_id: Option<String>,
+    pub sender: String,
+    pub domain: String,
+    pub recipients: Vec<DeliveryRecipient>,
+    #[serde(rename = "remoteHost")]
+    #[serde(default)]
+    pub remote_host: Option<String>,
+    #[serde(rename = "remoteIp")]
+    #[serde(default)]
+    pub remote_ip: Option<String>,
+    #[serde(default)]
+    pub tls: Option<DeliveryTls>,
+    #[serde(default)]
+    pub dane: Option<String>,
+    #[serde(rename = "mtaSts")]
+    #[serde(default)]
+    pub mta_sts: Option<String>,
+    pub status: String,
+    pub response: String,
+    pub created: u64,
+    pub completed: u64,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryRecipient {
+    pub address: String,
+    pub status: String,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    pub response: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryTls {
+    pub protocol: String,
+    pub cipher: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptRecord {
+    pub id: u64,
+    pub direction: String,
+    #[serde(rename = "remoteIp")]
+    pub remote_ip: String,
+    #[serde(rename = "remoteHost")]
+    #[serde(default)]
+    pub remote_host: Option<String>,
+    #[serde(default)]
+    pub listener: Option<String>,
+    #[serde(rename = "queueId")]
+    #[serde(default)]
+    pub queue_id: Option<u64>,
+    pub created: u64,
+    pub truncated: bool,
+    #[serde(default)]
+    pub lines: Vec<TranscriptLine>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptLine {
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub text: String,
+}
+
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub enum Status {
     #[serde(rename = "scheduled")]
@@ -385,6 +457,276 @@ impl QueueCommands {
                 }
                 eprintln!();
             }
+            QueueCommands::History {
+                sender,
+                rcpt,
+                queue_id,
+                message_id,
+                page,
+                limit,
+                details,
+            } => {
+                let mut query = form_urlencoded::Serializer::new("/api/queue/history?".to_string());
+                if let Some(sender) = &sender {
+                    query.append_pair("from", sender);
+                }
+                if let Some(rcpt) = &rcpt {
+                    query.append_pair("to", rcpt);
+                }
+                if let Some(queue_id) = &queue_id {
+                    let queue_id = parse_ids(std::slice::from_ref(queue_id));
+                    query.append_pair("queue-id", &queue_id[0].to_string());
+                }
+                if let Some(message_id) = &message_id {
+                    query.append_pair("message-id", message_id);
+                }
+                query.append_pair("page", &page.unwrap_or(1).to_string());
+                query.append_pair("limit", &limit.unwrap_or(20).to_string());
+
+                let records = client
+                    .http_request::<List<DeliveryRecord>, String>(
+                        Method::GET,
+                        &query.finish(),
+                        None,
+                    )
+                    .await;
+                if !records.items.is_empty() {
+                    let mut table = Table::new();
+                    table.add_row(Row::new(
+                        [
+                            "ID",
+                            "Completed",
+                            "Sender",
+                            "Domain",
+                            "Remote Host",
+                            "Status",
+                            "Response",
+                        ]
+                        .iter()
+                        .map(|p| Cell::new(p).with_style(Attr::Bold))
+                        .collect(),
+                    ));
+
+                    for record in &records.items {
+                        table.add_row(Row::new(vec![
+                            Cell::new(&format!("{:X}", record.queue_id)),
+                            Cell::new(
+                                &DateTime::from_timestamp(record.completed as i64).to_rfc822(),
+                            ),
+                            Cell::new(if !record.sender.is_empty() {
+                                &record.sender
+                            } else {
+                                "<>"
+                            }),
+                            Cell::new(&record.domain),
+                            Cell::new(record.remote_host.as_deref().unwrap_or("-")),
+                            Cell::new(&record.status),
+                            Cell::new(&record.response),
+                        ]));
+                    }
+
+                    eprintln!();
+                    table.printstd();
+                    eprintln!();
+                }
+
+                if details {
+                    for record in &records.items {
+                        let mut table = Table::new();
+                        for (name, value) in [
+                            ("ID", format!("{:X}", record.queue_id)),
+                            ("Message-ID", record.message_id.clone().unwrap_or_default()),
+                            (
+                                "Created",
+                                DateTime::from_timestamp(record.created as i64).to_rfc822(),
+                            ),
+                            (
+                                "Completed",
+                                DateTime::from_timestamp(record.completed as i64).to_rfc822(),
+                            ),
+                            ("Attempts", record.attempts.to_string()),
+                            (
+                                "Remote Host",
+                                record.remote_host.clone().unwrap_or_default(),
+                            ),
+                            ("Remote IP", record.remote_ip.clone().unwrap_or_default()),
+                            (
+                                "TLS",
+                                record
+                                    .tls
+                                    .as_ref()
+                                    .map(|tls| format!("{} ({})", tls.protocol, tls.cipher))
+                                    .unwrap_or_else(|| "none".to_string()),
+                            ),
+                            (
+                                "DANE",
+                                record.dane.clone().unwrap_or_else(|| "none".to_string()),
+                            ),
+                            (
+                                "MTA-STS",
+                                record.mta_sts.clone().unwrap_or_else(|| "none".to_string()),
+                            ),
+                        ] {
+                            table.add_row(Row::new(vec![
+                                Cell::new(name).with_style(Attr::Bold),
+                                Cell::new(&value),
+                            ]));
+                        }
+                        for rcpt in &record.recipients {
+                            table.add_row(Row::new(vec![
+                                Cell::new(&rcpt.address).with_style(Attr::Bold),
+                                Cell::new(&match &rcpt.hostname {
+                                    Some(hostname) => {
+                                        format!("{} ({}): {}", rcpt.status, hostname, rcpt.response)
+                                    }
+                                    None => format!("{}: {}", rcpt.status, rcpt.response),
+                                }),
+                            ]));
+                        }
+
+                        eprintln!();
+                        table.printstd();
+                    }
+                    eprintln!();
+                }
+
+                eprintln!("\n{} delivery record(s) found.\n", records.total);
+            }
+            QueueCommands::Transcripts { id: Some(id), .. } => {
+                let id = parse_ids(&[id])[0];
+                let record = client
+                    .try_http_request::<TranscriptRecord, String>(
+                        Method::GET,
+                        &format!("/api/queue/transcripts/{id}"),
+                        None,
+                    )
+                    .await
+                    .unwrap_or_else(|| {
+                        eprintln!("Transcript {id:X} not found.");
+                        std::process::exit(1);
+                    });
+
+                let mut table = Table::new();
+                for (name, value) in [
+                    ("ID", format!("{:X}", record.id)),
+                    ("Direction", record.direction.clone()),
+                    ("Remote IP", record.remote_ip.clone()),
+                    (
+                        "Remote Host",
+                        record.remote_host.clone().unwrap_or_default(),
+                    ),
+                    ("Listener", record.listener.clone().unwrap_or_default()),
+                    (
+                        "Queue ID",
+                        record
+                            .queue_id
+                            .map(|id| format!("{id:X}"))
+                            .unwrap_or_default(),
+                    ),
+                    (
+                        "Created",
+                        DateTime::from_timestamp(record.created as i64).to_rfc822(),
+                    ),
+                ] {
+                    table.add_row(Row::new(vec![
+                        Cell::new(name).with_style(Attr::Bold),
+                        Cell::new(&value),
+                    ]));
+                }
+                eprintln!();
+                table.printstd();
+                eprintln!();
+
+                for line in &record.lines {
+                    let prefix = match line.typ.as_str() {
+                        "client" => "C:",
+                        "server" => "S:",
+                        _ => "*",
+                    };
+                    println!("{prefix} {}", line.text);
+                }
+                if record.truncated {
+                    println!("* [transcript truncated]");
+                }
+                eprintln!();
+            }
+            QueueCommands::Transcripts {
+                id: None,
+                direction,
+                ip,
+                host,
+                queue_id,
+                text,
+                page,
+                limit,
+            } => {
+                let mut query =
+                    form_urlencoded::Serializer::new("/api/queue/transcripts?".to_string());
+                if let Some(direction) = &direction {
+                    query.append_pair("direction", direction);
+                }
+                if let Some(ip) = &ip {
+                    query.append_pair("remote-ip", ip);
+                }
+                if let Some(host) = &host {
+                    query.append_pair("remote-host", host);
+                }
+                if let Some(queue_id) = &queue_id {
+                    let queue_id = parse_ids(std::slice::from_ref(queue_id));
+                    query.append_pair("queue-id", &queue_id[0].to_string());
+                }
+                if let Some(text) = &text {
+                    query.append_pair("text", text);
+                }
+                query.append_pair("page", &page.unwrap_or(1).to_string());
+                query.append_pair("limit", &limit.unwrap_or(20).to_string());
+
+                let records = client
+                    .http_request::<List<TranscriptRecord>, String>(
+                        Method::GET,
+                        &query.finish(),
+                        None,
+                    )
+                    .await;
+                if !records.items.is_empty() {
+                    let mut table = Table::new();
+                    table.add_row(Row::new(
+                        [
+                            "ID",
+                            "Created",
+                            "Direction",
+                            "Remote IP",
+                            "Remote Host",
+                            "Queue ID",
+                        ]
+                        .iter()
+                        .map(|p| Cell::new(p).with_style(Attr::Bold))
+                        .collect(),
+                    ));
+
+                    for record in &records.items {
+                        table.add_row(Row::new(vec![
+                            Cell::new(&format!("{:X}", record.id)),
+                            Cell::new(&DateTime::from_timestamp(record.created as i64).to_rfc822()),
+                            Cell::new(&record.direction),
+                            Cell::new(&record.remote_ip),
+                            Cell::new(record.remote_host.as_deref().unwrap_or("-")),
+                            Cell::new(
+                                &record
+                                    .queue_id
+                                    .map(|id| format!("{id:X}"))
+                                    .unwrap_or_else(|| "-".to_string()),
+                            ),
+                        ]));
+                    }
+
+                    eprintln!();
+                    table.printstd();
+                    eprintln!();
+                }
+
+                eprintln!("\n{} transcript(s) found.\n", records.total);
+            }
         }
     }
 }
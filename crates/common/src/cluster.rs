@@ -0,0 +1,37 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::Ipc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClusterEvent {
+    BlockIp(IpAddr),
+    InvalidatePrincipal(u32),
+    ReloadSettings,
+    ReloadBlockedIps,
+}
+
+impl Ipc {
+    // Replicates an event to all cluster peers, events are dropped when
+    // clustering is disabled.
+    pub fn broadcast_cluster_event(&self, event: ClusterEvent) {
+        match self.cluster_tx.try_send(event) {
+            Ok(_) | Err(TrySendError::Closed(_)) => (),
+            Err(TrySendError::Full(event)) => {
+                tracing::warn!(
+                    context = "cluster",
+                    event = "error",
+                    "Failed to broadcast cluster event {:?}: channel full",
+                    event
+                );
+            }
+        }
+    }
+}
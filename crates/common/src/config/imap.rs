@@ -20,6 +20,22 @@ pub struct ImapConfig {
 
     pub rate_requests: Option<Rate>,
     pub rate_concurrent: Option<u64>,
+
+    pub metadata_max_size: usize,
+    pub metadata_max_entries: usize,
+
+    pub pop3_view: Pop3View,
+    pub pop3_download_keyword: Option<String>,
+
+    pub undelete_folder: Option<String>,
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub enum Pop3View {
+    #[default]
+    Inbox,
+    All,
+    Folder(String),
 }
 
 impl ImapConfig {
@@ -49,6 +65,34 @@ impl ImapConfig {
             allow_plain_auth: config
                 .property_or_default("imap.auth.allow-plain-text", "false")
                 .unwrap_or(false),
+            metadata_max_size: config
+                .property_or_default("imap.metadata.max-size", "4096")
+                .unwrap_or(4096),
+            metadata_max_entries: config
+                .property_or_default("imap.metadata.max-entries", "100")
+                .unwrap_or(100),
+            pop3_view: match config.value("pop3.folder").map(|v| v.trim()) {
+                Some(folder) if !folder.is_empty() && !folder.eq_ignore_ascii_case("inbox") => {
+                    if folder == "*" {
+                        Pop3View::All
+                    } else {
+                        Pop3View::Folder(folder.to_string())
+                    }
+                }
+                _ => Pop3View::Inbox,
+            },
+            pop3_download_keyword: config
+                .value("pop3.download-keyword")
+                .filter(|keyword| !keyword.is_empty())
+                .map(|keyword| keyword.to_string()),
+            undelete_folder: Some(
+                config
+                    .value("imap.undelete.folder")
+                    .unwrap_or("Recently Deleted")
+                    .trim(),
+            )
+            .filter(|folder| !folder.is_empty())
+            .map(|folder| folder.to_string()),
         }
     }
 }
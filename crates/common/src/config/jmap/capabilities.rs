@@ -7,9 +7,9 @@
 use ahash::AHashSet;
 use jmap_proto::{
     request::capability::{
-        BlobCapabilities, Capabilities, Capability, CoreCapabilities, EmptyCapabilities,
-        MailCapabilities, SieveAccountCapabilities, SieveSessionCapabilities,
-        SubmissionCapabilities,
+        BlobCapabilities, CalendarsCapabilities, Capabilities, Capability, ContactsCapabilities,
+        CoreCapabilities, EmptyCapabilities, MailCapabilities, SieveAccountCapabilities,
+        SieveSessionCapabilities, SubmissionCapabilities,
     },
     types::type_state::DataType,
 };
@@ -165,6 +165,37 @@ impl JmapConfig {
             }),
         );
 
+        // Add Contacts capabilities
+        self.capabilities.session.append(
+            Capability::Contacts,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+        self.capabilities.account.append(
+            Capability::Contacts,
+            Capabilities::Contacts(ContactsCapabilities {
+                max_address_books_per_card: None,
+                may_create_address_book: true,
+            }),
+        );
+
+        // Add Calendars capabilities
+        self.capabilities.session.append(
+            Capability::Calendars,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+        self.capabilities.account.append(
+            Capability::Calendars,
+            Capabilities::Calendars(CalendarsCapabilities {
+                max_calendars_per_event: None,
+                max_expanded_query_duration: format!(
+                    "P{}D",
+                    self.calendar_max_expanded_duration.as_secs() / 86400
+                ),
+                max_participants_per_event: None,
+                may_create_calendar: true,
+            }),
+        );
+
         // Add Quota capabilities
         self.capabilities.session.append(
             Capability::Quota,
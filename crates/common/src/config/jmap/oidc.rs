@@ -0,0 +1,118 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts, Pkcs1v15Sign,
+    RsaPrivateKey,
+};
+use sha2::{Digest, Sha256};
+use utils::config::Config;
+
+#[derive(Default, Clone)]
+pub struct OidcConfig {
+    pub signature_key: Option<Arc<OidcKey>>,
+    pub keys: Vec<Arc<OidcKey>>,
+    pub expiry_id_token: u64,
+}
+
+pub struct OidcKey {
+    pub id: String,
+    pub key: RsaPrivateKey,
+}
+
+impl OidcConfig {
+    pub fn parse(config: &mut Config) -> Self {
+        // Parse key set, all keys are published but only one is used for signing
+        let mut keys = Vec::new();
+        for id in config
+            .sub_keys("oauth.oidc.key", ".private-key")
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>()
+        {
+            let Some(pem) = config
+                .value_require(("oauth.oidc.key", id.as_str(), "private-key"))
+                .map(|v| v.to_string())
+            else {
+                continue;
+            };
+            match RsaPrivateKey::from_pkcs1_pem(&pem)
+                .or_else(|_| RsaPrivateKey::from_pkcs8_pem(&pem))
+            {
+                Ok(key) => keys.push(Arc::new(OidcKey { id, key })),
+                Err(err) => config.new_build_error(
+                    ("oauth.oidc.key", id.as_str(), "private-key"),
+                    format!("Failed to build RSA key: {err}"),
+                ),
+            }
+        }
+
+        let signature_key = match config.value("oauth.oidc.signature-key") {
+            Some(id) => {
+                let key = keys.iter().find(|key| key.id == id).cloned();
+                if key.is_none() {
+                    let id = id.to_string();
+                    config.new_build_error(
+                        "oauth.oidc.signature-key",
+                        format!("Signing key {id:?} not found in \"oauth.oidc.key\""),
+                    );
+                }
+                key
+            }
+            None if keys.len() > 1 => {
+                config.new_build_error(
+                    "oauth.oidc.signature-key",
+                    "Multiple keys found, please specify which one to use for signing",
+                );
+                None
+            }
+            None => keys.first().cloned(),
+        };
+
+        OidcConfig {
+            signature_key,
+            keys,
+            expiry_id_token: config
+                .property_or_default::<Duration>("oauth.expiry.id-token", "15m")
+                .unwrap_or_else(|| Duration::from_secs(15 * 60))
+                .as_secs(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.signature_key.is_some()
+    }
+}
+
+impl OidcKey {
+    // Returns the RS256 signature of the message
+    pub fn sign(&self, message: &[u8]) -> Option<Vec<u8>> {
+        self.key
+            .sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(message))
+            .map_err(|err| {
+                tracing::error!(
+                    context = "oidc",
+                    event = "error",
+                    reason = %err,
+                    "Failed to sign OIDC token."
+                )
+            })
+            .ok()
+    }
+
+    pub fn to_jwk(&self) -> serde_json::Value {
+        serde_json::json!({
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": self.id,
+            "n": URL_SAFE_NO_PAD.encode(self.key.n().to_bytes_be()),
+            "e": URL_SAFE_NO_PAD.encode(self.key.e().to_bytes_be()),
+        })
+    }
+}
@@ -12,6 +12,8 @@ use nlp::language::Language;
 use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
 use utils::config::{cron::SimpleCron, utils::ParseValue, Config, Rate};
 
+use super::oidc::OidcConfig;
+
 #[derive(Default, Clone)]
 pub struct JmapConfig {
     pub default_language: Language,
@@ -40,8 +42,19 @@ pub struct JmapConfig {
     pub mail_attachments_max_size: usize,
     pub mail_parse_max_items: usize,
     pub mail_max_size: usize,
+    pub mail_max_messages: u64,
     pub mail_autoexpunge_after: Option<Duration>,
 
+    pub address_book_name_max_len: usize,
+    pub address_book_default_name: String,
+    pub contact_max_size: usize,
+
+    pub calendar_name_max_len: usize,
+    pub calendar_default_name: String,
+    pub calendar_event_max_size: usize,
+    pub calendar_max_expanded_duration: Duration,
+    pub calendar_max_instances: usize,
+
     pub sieve_max_script_name: usize,
     pub sieve_max_scripts: usize,
 
@@ -70,8 +83,10 @@ pub struct JmapConfig {
     pub oauth_expiry_refresh_token: u64,
     pub oauth_expiry_refresh_token_renew: u64,
     pub oauth_max_auth_attempts: u32,
+    pub oidc: OidcConfig,
     pub fallback_admin: Option<(String, String)>,
     pub master_user: Option<(String, String)>,
+    pub impersonation_group: Option<String>,
 
     pub spam_header: Option<(HeaderName<'static>, String)>,
     pub default_folders: Vec<DefaultFolder>,
@@ -80,6 +95,12 @@ pub struct JmapConfig {
     pub http_headers: Vec<(hyper::header::HeaderName, hyper::header::HeaderValue)>,
     pub http_use_forwarded: bool,
 
+    pub metrics_prometheus: bool,
+    pub metrics_prometheus_auth: Option<(String, String)>,
+
+    pub audit_enable: bool,
+    pub audit_retention: Duration,
+
     pub encrypt: bool,
     pub encrypt_append: bool,
 
@@ -300,10 +321,35 @@ impl JmapConfig {
                 .property("jmap.email.max-attachment-size")
                 .unwrap_or(50000000),
             mail_max_size: config.property("jmap.email.max-size").unwrap_or(75000000),
+            mail_max_messages: config.property("jmap.email.max-messages").unwrap_or(0),
             mail_parse_max_items: config.property("jmap.email.parse.max-items").unwrap_or(10),
             mail_autoexpunge_after: config
                 .property_or_default::<Option<Duration>>("jmap.email.auto-expunge", "30d")
                 .unwrap_or_default(),
+            address_book_name_max_len: config
+                .property("jmap.contacts.max-name-length")
+                .unwrap_or(255),
+            address_book_default_name: config
+                .value("jmap.contacts.default-address-book")
+                .unwrap_or("Personal")
+                .to_string(),
+            contact_max_size: config.property("jmap.contacts.max-size").unwrap_or(512000),
+            calendar_name_max_len: config
+                .property("jmap.calendars.max-name-length")
+                .unwrap_or(255),
+            calendar_default_name: config
+                .value("jmap.calendars.default-calendar")
+                .unwrap_or("Calendar")
+                .to_string(),
+            calendar_event_max_size: config
+                .property("jmap.calendars.max-event-size")
+                .unwrap_or(512000),
+            calendar_max_expanded_duration: config
+                .property_or_default("jmap.calendars.max-expanded-duration", "366d")
+                .unwrap_or(Duration::from_secs(366 * 86400)),
+            calendar_max_instances: config
+                .property("jmap.calendars.max-instances")
+                .unwrap_or(1000),
             sieve_max_script_name: config
                 .property("sieve.untrusted.limits.name-length")
                 .unwrap_or(512),
@@ -356,6 +402,7 @@ impl JmapConfig {
             oauth_max_auth_attempts: config
                 .property_or_default("oauth.auth.max-attempts", "3")
                 .unwrap_or(10),
+            oidc: OidcConfig::parse(config),
             event_source_throttle: config
                 .property_or_default("jmap.event-source.throttle", "1s")
                 .unwrap_or_else(|| Duration::from_secs(1)),
@@ -394,6 +441,22 @@ impl JmapConfig {
             http_use_forwarded: config
                 .property("server.http.use-x-forwarded")
                 .unwrap_or(false),
+            metrics_prometheus: config
+                .property_or_default("metrics.prometheus.enable", "false")
+                .unwrap_or(false),
+            audit_enable: config
+                .property_or_default("audit.enable", "false")
+                .unwrap_or(false),
+            audit_retention: config
+                .property_or_default("audit.retention", "90d")
+                .unwrap_or_else(|| Duration::from_secs(90 * 86400)),
+            metrics_prometheus_auth: config.value("metrics.prometheus.auth.username").and_then(
+                |u| {
+                    config
+                        .value("metrics.prometheus.auth.secret")
+                        .map(|p| (u.to_string(), p.to_string()))
+                },
+            ),
             http_headers,
             push_attempt_interval: config
                 .property_or_default("jmap.push.attempts.interval", "1m")
@@ -431,6 +494,9 @@ impl JmapConfig {
                     .value("authentication.master.secret")
                     .map(|p| (u.to_string(), p.to_string()))
             }),
+            impersonation_group: config
+                .value("authentication.impersonation.group")
+                .map(|g| g.to_string()),
             default_folders,
             shared_folder,
         };
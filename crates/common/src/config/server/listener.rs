@@ -189,6 +189,13 @@ impl Servers {
                     "8192",
                 )
                 .unwrap_or(8192),
+            compress: config
+                .property_or_else(
+                    ("server.listener", id, "compress.enable"),
+                    "server.compress.enable",
+                    "false",
+                )
+                .unwrap_or(false),
             id: id_,
             protocol,
             listeners,
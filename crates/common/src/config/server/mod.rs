@@ -29,6 +29,7 @@ pub struct Server {
     pub listeners: Vec<Listener>,
     pub proxy_networks: Vec<IpAddrMask>,
     pub max_connections: u64,
+    pub compress: bool,
 }
 
 #[derive(Debug)]
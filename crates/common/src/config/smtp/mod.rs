@@ -11,6 +11,7 @@ pub mod queue;
 pub mod report;
 pub mod resolver;
 pub mod session;
+pub mod srs;
 pub mod throttle;
 
 use crate::expr::{tokenizer::TokenMap, Expression};
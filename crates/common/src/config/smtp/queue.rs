@@ -17,7 +17,11 @@ use crate::{
     expr::{if_block::IfBlock, *},
 };
 
-use self::throttle::{parse_throttle, parse_throttle_key};
+use self::{
+    session::{parse_hooks, MTAHook, Transcript, QUEUE_STAGES},
+    srs::Srs,
+    throttle::{parse_throttle, parse_throttle_key},
+};
 
 use super::*;
 
@@ -41,12 +45,27 @@ pub struct QueueConfig {
     // Timeouts
     pub timeout: QueueOutboundTimeout,
 
+    // Connection reuse
+    pub pool: QueueOutboundPool,
+
     // Throttle and Quotas
     pub throttle: QueueThrottle,
     pub quota: QueueQuotas,
 
     // Relay hosts
     pub relay_hosts: AHashMap<String, RelayHost>,
+
+    // Sender rewriting
+    pub srs: Srs,
+
+    // Delivery history
+    pub history_retention: Option<Duration>,
+
+    // Outbound hooks
+    pub hooks: Vec<MTAHook>,
+
+    // Session transcripts
+    pub transcript: Transcript,
 }
 
 #[derive(Clone)]
@@ -82,6 +101,13 @@ pub struct QueueOutboundTimeout {
     pub mta_sts: IfBlock,
 }
 
+#[derive(Clone)]
+pub struct QueueOutboundPool {
+    pub enable: IfBlock,
+    pub idle_timeout: IfBlock,
+    pub max_messages: IfBlock,
+}
+
 #[derive(Debug, Clone)]
 pub struct QueueThrottle {
     pub sender: Vec<Throttle>,
@@ -114,7 +140,7 @@ pub struct RelayHost {
     pub tls_allow_invalid_certs: bool,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum RequireOptional {
     #[default]
     Optional,
@@ -203,6 +229,11 @@ impl Default for QueueConfig {
                 data: IfBlock::new::<()>("queue.outbound.timeouts.data", [], "10m"),
                 mta_sts: IfBlock::new::<()>("queue.outbound.timeouts.mta-sts", [], "10m"),
             },
+            pool: QueueOutboundPool {
+                enable: IfBlock::new::<()>("queue.outbound.pool.enable", [], "false"),
+                idle_timeout: IfBlock::new::<()>("queue.outbound.pool.idle-timeout", [], "30s"),
+                max_messages: IfBlock::new::<()>("queue.outbound.pool.max-messages", [], "100"),
+            },
             throttle: QueueThrottle {
                 sender: Default::default(),
                 rcpt: Default::default(),
@@ -214,6 +245,10 @@ impl Default for QueueConfig {
                 rcpt_domain: Default::default(),
             },
             relay_hosts: Default::default(),
+            srs: Default::default(),
+            history_retention: None,
+            hooks: Default::default(),
+            transcript: Transcript::new("queue.outbound.transcript"),
         }
     }
 }
@@ -312,6 +347,21 @@ impl QueueConfig {
                 "queue.outbound.timeouts.mta-sts",
                 &host_vars,
             ),
+            (
+                &mut queue.pool.enable,
+                "queue.outbound.pool.enable",
+                &host_vars,
+            ),
+            (
+                &mut queue.pool.idle_timeout,
+                "queue.outbound.pool.idle-timeout",
+                &host_vars,
+            ),
+            (
+                &mut queue.pool.max_messages,
+                "queue.outbound.pool.max-messages",
+                &host_vars,
+            ),
             (&mut queue.dsn.name, "report.dsn.from-name", &sender_vars),
             (
                 &mut queue.dsn.address,
@@ -328,6 +378,18 @@ impl QueueConfig {
         // Parse queue quotas and throttles
         queue.throttle = parse_queue_throttle(config);
         queue.quota = parse_queue_quota(config);
+        queue.srs = Srs::parse(config);
+        queue.history_retention = config
+            .property_or_default::<Option<Duration>>("queue.history.retention", "false")
+            .unwrap_or_default();
+        queue.hooks = config
+            .sub_keys("queue.hook", ".url")
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|id| parse_hooks(config, "queue.hook", &id, &rcpt_vars, QUEUE_STAGES))
+            .collect();
+        queue.transcript = Transcript::parse(config, "queue.outbound.transcript", &host_vars);
 
         // Parse relay hosts
         queue.relay_hosts = config
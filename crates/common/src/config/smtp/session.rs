@@ -46,6 +46,15 @@ pub struct SessionConfig {
 
     pub milters: Vec<Milter>,
     pub hooks: Vec<MTAHook>,
+
+    pub transcript: Transcript,
+}
+
+#[derive(Clone)]
+pub struct Transcript {
+    pub enable: IfBlock,
+    pub max_size: usize,
+    pub retention: Duration,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -198,8 +207,20 @@ pub enum Stage {
     Mail,
     Rcpt,
     Data,
+    Delivery,
+    Result,
 }
 
+pub(crate) const SESSION_STAGES: &[Stage] = &[
+    Stage::Connect,
+    Stage::Ehlo,
+    Stage::Auth,
+    Stage::Mail,
+    Stage::Rcpt,
+    Stage::Data,
+];
+pub(crate) const QUEUE_STAGES: &[Stage] = &[Stage::Delivery, Stage::Result];
+
 impl SessionConfig {
     pub fn parse(config: &mut Config) -> Self {
         let has_conn_vars = TokenMap::default().with_variables(CONNECTION_VARS);
@@ -224,8 +245,11 @@ impl SessionConfig {
             .map(|s| s.to_string())
             .collect::<Vec<_>>()
             .into_iter()
-            .filter_map(|id| parse_hooks(config, &id, &has_rcpt_vars))
+            .filter_map(|id| {
+                parse_hooks(config, "session.hook", &id, &has_rcpt_vars, SESSION_STAGES)
+            })
             .collect();
+        session.transcript = Transcript::parse(config, "session.transcript", &has_rcpt_vars);
         session.data.pipe_commands = config
             .sub_keys("session.data.pipe", "")
             .map(|s| s.to_string())
@@ -584,46 +608,53 @@ fn parse_milter(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<M
         },
         flags_actions: config.property(("session.milter", id, "options.flags.actions")),
         flags_protocol: config.property(("session.milter", id, "options.flags.protocol")),
-        run_on_stage: parse_stages(config, "session.milter", id),
+        run_on_stage: parse_stages(config, "session.milter", id, SESSION_STAGES),
     })
 }
 
-fn parse_hooks(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<MTAHook> {
+pub(crate) fn parse_hooks(
+    config: &mut Config,
+    prefix: &str,
+    id: &str,
+    token_map: &TokenMap,
+    stages: &[Stage],
+) -> Option<MTAHook> {
     let mut headers = HeaderMap::new();
 
-    for (header, value) in config
-        .values(("session.hook", id, "headers"))
-        .map(|(_, v)| {
-            if let Some((k, v)) = v.split_once(':') {
-                Ok((
-                    HeaderName::from_str(k.trim()).map_err(|err| {
-                        format!(
-                            "Invalid header found in property \"session.hook.{id}.headers\": {err}",
-                        )
-                    })?,
-                    HeaderValue::from_str(v.trim()).map_err(|err| {
-                        format!(
-                            "Invalid header found in property \"session.hook.{id}.headers\": {err}",
-                        )
-                    })?,
-                ))
-            } else {
-                Err(format!(
-                    "Invalid header found in property \"session.hook.{id}.headers\": {v}",
-                ))
-            }
-        })
-        .collect::<Result<Vec<(HeaderName, HeaderValue)>, String>>()
-        .map_err(|e| config.new_parse_error(("session.hook", id, "headers"), e))
-        .unwrap_or_default()
+    for (header, value) in
+        config
+            .values((prefix, id, "headers"))
+            .map(|(_, v)| {
+                if let Some((k, v)) = v.split_once(':') {
+                    Ok((
+                        HeaderName::from_str(k.trim()).map_err(|err| {
+                            format!(
+                                "Invalid header found in property \"{prefix}.{id}.headers\": {err}",
+                            )
+                        })?,
+                        HeaderValue::from_str(v.trim()).map_err(|err| {
+                            format!(
+                                "Invalid header found in property \"{prefix}.{id}.headers\": {err}",
+                            )
+                        })?,
+                    ))
+                } else {
+                    Err(format!(
+                        "Invalid header found in property \"{prefix}.{id}.headers\": {v}",
+                    ))
+                }
+            })
+            .collect::<Result<Vec<(HeaderName, HeaderValue)>, String>>()
+            .map_err(|e| config.new_parse_error((prefix, id, "headers"), e))
+            .unwrap_or_default()
     {
         headers.insert(header, value);
     }
 
     headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
     if let (Some(name), Some(secret)) = (
-        config.value(("session.hook", id, "auth.username")),
-        config.value(("session.hook", id, "auth.secret")),
+        config.value((prefix, id, "auth.username")),
+        config.value((prefix, id, "auth.secret")),
     ) {
         headers.insert(
             AUTHORIZATION,
@@ -634,34 +665,51 @@ fn parse_hooks(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<MT
     }
 
     Some(MTAHook {
-        enable: IfBlock::try_parse(config, ("session.hook", id, "enable"), token_map)
-            .unwrap_or_else(|| {
-                IfBlock::new::<()>(format!("session.hook.{id}.enable"), [], "false")
-            }),
-        url: config
-            .value_require(("session.hook", id, "url"))?
-            .to_string(),
+        enable: IfBlock::try_parse(config, (prefix, id, "enable"), token_map)
+            .unwrap_or_else(|| IfBlock::new::<()>(format!("{prefix}.{id}.enable"), [], "false")),
+        url: config.value_require((prefix, id, "url"))?.to_string(),
         timeout: config
-            .property_or_default(("session.hook", id, "timeout"), "30s")
+            .property_or_default((prefix, id, "timeout"), "30s")
             .unwrap_or_else(|| Duration::from_secs(30)),
         tls_allow_invalid_certs: config
-            .property_or_default(("session.hook", id, "allow-invalid-certs"), "false")
+            .property_or_default((prefix, id, "allow-invalid-certs"), "false")
             .unwrap_or_default(),
         tempfail_on_error: config
-            .property_or_default(("session.hook", id, "options.tempfail-on-error"), "true")
+            .property_or_default((prefix, id, "options.tempfail-on-error"), "true")
             .unwrap_or(true),
-        run_on_stage: parse_stages(config, "session.hook", id),
+        run_on_stage: parse_stages(config, prefix, id, stages),
         max_response_size: config
-            .property_or_default(
-                ("session.hook", id, "options.max-response-size"),
-                "52428800",
-            )
+            .property_or_default((prefix, id, "options.max-response-size"), "52428800")
             .unwrap_or(52428800),
         headers,
     })
 }
 
-fn parse_stages(config: &mut Config, prefix: &str, id: &str) -> AHashSet<Stage> {
+impl Transcript {
+    pub fn new(prefix: &str) -> Self {
+        Transcript {
+            enable: IfBlock::empty(format!("{prefix}.enable")),
+            max_size: 65536,
+            retention: Duration::from_secs(7 * 86400),
+        }
+    }
+
+    pub(crate) fn parse(config: &mut Config, prefix: &str, token_map: &TokenMap) -> Self {
+        let mut transcript = Transcript::new(prefix);
+        if let Some(enable) = IfBlock::try_parse(config, (prefix, "enable"), token_map) {
+            transcript.enable = enable;
+        }
+        transcript.max_size = config
+            .property_or_default((prefix, "max-size"), "65536")
+            .unwrap_or(transcript.max_size);
+        transcript.retention = config
+            .property_or_default((prefix, "retention"), "7d")
+            .unwrap_or(transcript.retention);
+        transcript
+    }
+}
+
+fn parse_stages(config: &mut Config, prefix: &str, id: &str, valid: &[Stage]) -> AHashSet<Stage> {
     let mut stages = AHashSet::default();
     let mut invalid = Vec::new();
     for (_, value) in config.values((prefix, id, "stages")) {
@@ -673,12 +721,18 @@ fn parse_stages(config: &mut Config, prefix: &str, id: &str) -> AHashSet<Stage>
             "mail" => Stage::Mail,
             "rcpt" => Stage::Rcpt,
             "data" => Stage::Data,
+            "delivery" => Stage::Delivery,
+            "result" => Stage::Result,
             _ => {
                 invalid.push(value);
                 continue;
             }
         };
-        stages.insert(state);
+        if valid.contains(&state) {
+            stages.insert(state);
+        } else {
+            invalid.push(value);
+        }
     }
 
     if !invalid.is_empty() {
@@ -689,7 +743,11 @@ fn parse_stages(config: &mut Config, prefix: &str, id: &str) -> AHashSet<Stage>
     }
 
     if stages.is_empty() {
-        stages.insert(Stage::Data);
+        if valid.contains(&Stage::Data) {
+            stages.insert(Stage::Data);
+        } else {
+            stages.extend(valid.iter().copied());
+        }
     }
 
     stages
@@ -867,6 +925,7 @@ impl Default for SessionConfig {
             mta_sts_policy: None,
             milters: Default::default(),
             hooks: Default::default(),
+            transcript: Transcript::new("session.transcript"),
         }
     }
 }
@@ -881,11 +940,11 @@ impl ParseValue for Mechanism {
             "PLAIN" => AUTH_PLAIN,
             "XOAUTH2" => AUTH_XOAUTH2,
             "OAUTHBEARER" => AUTH_OAUTHBEARER,
-            /*"SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
+            "SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
             "SCRAM-SHA-256" => AUTH_SCRAM_SHA_256,
             "SCRAM-SHA-1-PLUS" => AUTH_SCRAM_SHA_1_PLUS,
             "SCRAM-SHA-1" => AUTH_SCRAM_SHA_1,
-            "XOAUTH" => AUTH_XOAUTH,
+            /*"XOAUTH" => AUTH_XOAUTH,
             "9798-M-DSA-SHA1" => AUTH_9798_M_DSA_SHA1,
             "9798-M-ECDSA-SHA1" => AUTH_9798_M_ECDSA_SHA1,
             "9798-M-RSA-SHA1-ENC" => AUTH_9798_M_RSA_SHA1_ENC,
@@ -959,7 +1018,11 @@ impl ConstantValue for Mechanism {
             .add_constant("login", Mechanism(AUTH_LOGIN))
             .add_constant("plain", Mechanism(AUTH_PLAIN))
             .add_constant("xoauth2", Mechanism(AUTH_XOAUTH2))
-            .add_constant("oauthbearer", Mechanism(AUTH_OAUTHBEARER));
+            .add_constant("oauthbearer", Mechanism(AUTH_OAUTHBEARER))
+            .add_constant("scram_sha_256", Mechanism(AUTH_SCRAM_SHA_256))
+            .add_constant("scram_sha_256_plus", Mechanism(AUTH_SCRAM_SHA_256_PLUS))
+            .add_constant("scram_sha_1", Mechanism(AUTH_SCRAM_SHA_1))
+            .add_constant("scram_sha_1_plus", Mechanism(AUTH_SCRAM_SHA_1_PLUS));
     }
 }
 
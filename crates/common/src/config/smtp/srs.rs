@@ -0,0 +1,201 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, SystemTime};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::hmac;
+use subtle::ConstantTimeEq;
+use utils::config::Config;
+
+use crate::{
+    config::smtp::SMTP_QUEUE_SENDER_VARS,
+    expr::{if_block::IfBlock, tokenizer::TokenMap},
+};
+
+const HASH_LEN: usize = 4;
+const TIMESTAMP_PRECISION: u64 = 86400;
+const TIMESTAMP_SLOTS: u64 = 1024;
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Clone)]
+pub struct Srs {
+    pub enable: IfBlock,
+    pub domain: IfBlock,
+    // The first key signs new addresses, the remaining ones are only
+    // used to verify addresses signed before a key rotation
+    pub keys: Vec<hmac::Key>,
+    pub max_age: Duration,
+}
+
+impl Srs {
+    pub fn parse(config: &mut Config) -> Self {
+        let sender_vars = TokenMap::default().with_variables(SMTP_QUEUE_SENDER_VARS);
+        let mut srs = Srs::default();
+
+        for (value, key) in [
+            (&mut srs.enable, "queue.srs.enable"),
+            (&mut srs.domain, "queue.srs.domain"),
+        ] {
+            if let Some(if_block) = IfBlock::try_parse(config, key, &sender_vars) {
+                *value = if_block;
+            }
+        }
+        srs.keys = config
+            .values("queue.srs.secret")
+            .map(|(_, secret)| {
+                hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret.as_bytes())
+            })
+            .collect();
+        srs.max_age = config
+            .property_or_default("queue.srs.max-age", "21d")
+            .unwrap_or(Duration::from_secs(21 * 86400));
+
+        srs
+    }
+
+    // Rewrites a return path into an SRS0 or SRS1 address at the given domain
+    pub fn forward(&self, address: &str, domain: &str) -> Option<String> {
+        let key = self.keys.first()?;
+        let (local, sender_domain) = address.rsplit_once('@')?;
+        if sender_domain.eq_ignore_ascii_case(domain) {
+            return None;
+        }
+
+        if let Some((separator, rest)) = srs_local_part(local, "SRS0") {
+            // Already rewritten by a previous forwarder, only keep a reference to it
+            let rest = format!("{separator}{rest}");
+            let hash = hash(key, &[sender_domain, &rest]);
+            Some(format!("SRS1={hash}={sender_domain}={rest}@{domain}"))
+        } else if let Some((_, rest)) = srs_local_part(local, "SRS1") {
+            let (_, rest) = rest.split_once('=')?;
+            let (first_domain, rest) = rest.split_once('=')?;
+            let hash = hash(key, &[first_domain, rest]);
+            Some(format!("SRS1={hash}={first_domain}={rest}@{domain}"))
+        } else {
+            let timestamp = encode_timestamp(now() / TIMESTAMP_PRECISION);
+            let hash = hash(key, &[&timestamp, sender_domain, local]);
+            Some(format!(
+                "SRS0={hash}={timestamp}={sender_domain}={local}@{domain}"
+            ))
+        }
+    }
+
+    // Obtains the address an SRS0 or SRS1 address was created from
+    pub fn reverse(&self, address: &str) -> Result<Option<String>, SrsError> {
+        let Some((local, _)) = address.rsplit_once('@').filter(|_| self.is_enabled()) else {
+            return Ok(None);
+        };
+
+        if let Some((_, rest)) = srs_local_part(local, "SRS0") {
+            let mut parts = rest.splitn(4, '=');
+            let (Some(hash), Some(timestamp), Some(domain), Some(local)) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(SrsError::Invalid);
+            };
+            self.verify(hash, &[timestamp, domain, local])?;
+            let timestamp = decode_timestamp(timestamp).ok_or(SrsError::Invalid)?;
+            let today = (now() / TIMESTAMP_PRECISION) % TIMESTAMP_SLOTS;
+            let age = (today + TIMESTAMP_SLOTS - timestamp) % TIMESTAMP_SLOTS;
+            if age * TIMESTAMP_PRECISION > self.max_age.as_secs() {
+                return Err(SrsError::Expired);
+            }
+
+            Ok(Some(format!("{local}@{domain}")))
+        } else if let Some((_, rest)) = srs_local_part(local, "SRS1") {
+            let mut parts = rest.splitn(3, '=');
+            let (Some(hash), Some(domain), Some(rest)) = (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(SrsError::Invalid);
+            };
+            self.verify(hash, &[domain, rest])?;
+
+            Ok(Some(format!("SRS0{rest}@{domain}")))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn verify(&self, expected: &str, values: &[&str]) -> Result<(), SrsError> {
+        // Check every key to avoid leaking which one matched
+        if self.keys.iter().fold(false, |is_valid, key| {
+            bool::from(hash(key, values).as_bytes().ct_eq(expected.as_bytes())) | is_valid
+        }) {
+            Ok(())
+        } else {
+            Err(SrsError::Invalid)
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrsError {
+    Invalid,
+    Expired,
+}
+
+fn srs_local_part<'x>(local: &'x str, tag: &str) -> Option<(char, &'x str)> {
+    let prefix = local.get(..tag.len())?;
+    let mut rest = local.get(tag.len()..)?.chars();
+    let separator = rest.next().filter(|ch| ['=', '+', '-'].contains(ch))?;
+    if prefix.eq_ignore_ascii_case(tag) {
+        Some((separator, rest.as_str()))
+    } else {
+        None
+    }
+}
+
+fn hash(key: &hmac::Key, values: &[&str]) -> String {
+    let mut ctx = hmac::Context::with_key(key);
+    for value in values {
+        ctx.update(value.to_lowercase().as_bytes());
+    }
+    let mut hash = STANDARD.encode(ctx.sign().as_ref());
+    hash.truncate(HASH_LEN);
+    hash
+}
+
+fn encode_timestamp(days: u64) -> String {
+    let days = days % TIMESTAMP_SLOTS;
+    [
+        BASE32[(days >> 5) as usize & 31] as char,
+        BASE32[days as usize & 31] as char,
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn decode_timestamp(timestamp: &str) -> Option<u64> {
+    if timestamp.len() != 2 {
+        return None;
+    }
+    timestamp.bytes().try_fold(0u64, |acc, ch| {
+        let pos = BASE32.iter().position(|b| *b == ch.to_ascii_uppercase())?;
+        Some((acc << 5) | pos as u64)
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl Default for Srs {
+    fn default() -> Self {
+        Self {
+            enable: IfBlock::new::<()>("queue.srs.enable", [], "false"),
+            domain: IfBlock::new::<()>("queue.srs.domain", [], "key_get('default', 'domain')"),
+            keys: Vec::new(),
+            max_age: Duration::from_secs(21 * 86400),
+        }
+    }
+}
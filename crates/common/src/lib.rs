@@ -7,6 +7,7 @@
 use std::{borrow::Cow, net::IpAddr, sync::Arc, time::Duration};
 
 use arc_swap::ArcSwap;
+use cluster::ClusterEvent;
 use config::{
     imap::ImapConfig,
     jmap::settings::JmapConfig,
@@ -43,14 +44,16 @@ use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
     layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
 };
-use utils::{config::Config, BlobHash};
+use utils::{config::Config, metrics::AUTH_TOTAL, BlobHash};
 use webhooks::{manager::WebhookEvent, WebhookPayload, WebhookType, Webhooks};
 
 pub mod addresses;
+pub mod cluster;
 pub mod config;
 pub mod expr;
 pub mod listener;
 pub mod manager;
+pub mod scram;
 pub mod scripts;
 pub mod webhooks;
 
@@ -58,6 +61,7 @@ pub static USER_AGENT: &str = concat!("Stalwart/", env!("CARGO_PKG_VERSION"),);
 pub static DAEMON_NAME: &str = concat!("Stalwart Mail Server v", env!("CARGO_PKG_VERSION"),);
 
 pub const IPC_CHANNEL_BUFFER: usize = 1024;
+pub const IMPERSONATION_SEPARATOR: char = '*';
 
 pub type SharedCore = Arc<ArcSwap<Core>>;
 
@@ -98,6 +102,22 @@ pub enum AuthResult<T> {
     Failure(AuthFailureReason),
 }
 
+pub(crate) trait RecordAuthMetrics {
+    fn record_metrics(&self, protocol: ServerProtocol);
+}
+
+impl<T> RecordAuthMetrics for directory::Result<AuthResult<T>> {
+    fn record_metrics(&self, protocol: ServerProtocol) {
+        let result = match self {
+            Ok(AuthResult::Success(_)) => "success",
+            Ok(AuthResult::Failure(AuthFailureReason::Banned)) => "banned",
+            Ok(AuthResult::Failure(_)) => "failure",
+            Err(_) => "error",
+        };
+        AUTH_TOTAL.inc(&[protocol.as_str(), result]);
+    }
+}
+
 pub enum AuthFailureReason {
     InvalidCredentials,
     MissingTotp,
@@ -117,6 +137,7 @@ pub enum DeliveryEvent {
 pub struct Ipc {
     pub delivery_tx: mpsc::Sender<DeliveryEvent>,
     pub webhook_tx: mpsc::Sender<WebhookEvent>,
+    pub cluster_tx: mpsc::Sender<ClusterEvent>,
 }
 
 #[derive(Debug)]
@@ -251,6 +272,29 @@ impl Core {
         remote_ip: IpAddr,
         protocol: ServerProtocol,
         return_member_of: bool,
+    ) -> directory::Result<AuthResult<Principal<u32>>> {
+        let result = self
+            .authenticate_credentials(
+                directory,
+                ipc,
+                credentials,
+                remote_ip,
+                protocol,
+                return_member_of,
+            )
+            .await;
+        result.record_metrics(protocol);
+        result
+    }
+
+    async fn authenticate_credentials(
+        &self,
+        directory: &Directory,
+        ipc: &Ipc,
+        credentials: &Credentials<String>,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        return_member_of: bool,
     ) -> directory::Result<AuthResult<Principal<u32>>> {
         // First try to authenticate the user against the default directory
         let result = match directory
@@ -268,6 +312,7 @@ impl Core {
                             remote_ip,
                             typ: principal.typ.into(),
                             as_master: None,
+                            master_login: None,
                         },
                     )
                     .await;
@@ -302,6 +347,7 @@ impl Core {
                                 remote_ip,
                                 typ: Type::Superuser.into(),
                                 as_master: None,
+                                master_login: None,
                             },
                         )
                         .await;
@@ -332,6 +378,7 @@ impl Core {
                                         remote_ip,
                                         typ: principal.typ.into(),
                                         as_master: true.into(),
+                                        master_login: master_user.to_string().into(),
                                     },
                                 )
                                 .await;
@@ -348,6 +395,7 @@ impl Core {
                                         remote_ip,
                                         typ: None,
                                         as_master: true.into(),
+                                        master_login: master_user.to_string().into(),
                                     },
                                 )
                                 .await;
@@ -361,6 +409,155 @@ impl Core {
             _ => {}
         }
 
+        // Lastly check if an authorized principal is logging in as another account
+        if let (Some(group), Credentials::Plain { username, secret }) =
+            (&self.jmap.impersonation_group, credentials)
+        {
+            if let Some((target, login)) = username
+                .rsplit_once(IMPERSONATION_SEPARATOR)
+                .filter(|(target, login)| !target.is_empty() && !login.is_empty())
+            {
+                match directory
+                    .query(
+                        QueryBy::Credentials(&Credentials::Plain {
+                            username: login.to_string(),
+                            secret: secret.to_string(),
+                        }),
+                        true,
+                    )
+                    .await
+                {
+                    Ok(Some(principal)) => {
+                        return self
+                            .impersonate(
+                                directory,
+                                ipc,
+                                group,
+                                principal,
+                                target,
+                                remote_ip,
+                                protocol,
+                                return_member_of,
+                            )
+                            .await;
+                    }
+                    Ok(None) => {}
+                    Err(DirectoryError::MissingTotpCode) => {
+                        return Ok(AuthResult::Failure(AuthFailureReason::MissingTotp))
+                    }
+                    Err(err) => {
+                        return self
+                            .authentication_failed(ipc, username, remote_ip, protocol, Err(err))
+                            .await
+                    }
+                }
+            }
+        }
+
+        self.authentication_failed(ipc, credentials.login(), remote_ip, protocol, result)
+            .await
+    }
+
+    // Obtains the target principal if the authenticated principal is a member
+    // of the impersonation group
+    #[allow(clippy::too_many_arguments)]
+    pub async fn impersonate(
+        &self,
+        directory: &Directory,
+        ipc: &Ipc,
+        group: &str,
+        principal: Principal<u32>,
+        target: &str,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        return_member_of: bool,
+    ) -> directory::Result<AuthResult<Principal<u32>>> {
+        let is_authorized = match directory.query(QueryBy::Name(group), false).await? {
+            Some(group) => principal.member_of.contains(&group.id),
+            None => false,
+        };
+
+        if is_authorized {
+            match directory
+                .query(QueryBy::Name(target), return_member_of)
+                .await?
+            {
+                Some(target_principal)
+                    if target_principal.typ == Type::Individual
+                        && (principal.typ == Type::Superuser
+                            || target_principal
+                                .roles
+                                .iter()
+                                .all(|role| principal.roles.contains(role))) =>
+                {
+                    tracing::info!(
+                        context = "directory",
+                        event = "impersonate",
+                        remote_ip = ?remote_ip,
+                        login = ?principal.name,
+                        account = ?target,
+                        "Principal authenticated as another account",
+                    );
+
+                    // Send webhook event
+                    if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
+                        ipc.send_webhook(
+                            WebhookType::AuthSuccess,
+                            WebhookPayload::Authentication {
+                                login: target.to_string(),
+                                protocol,
+                                remote_ip,
+                                typ: target_principal.typ.into(),
+                                as_master: true.into(),
+                                master_login: principal.name.into(),
+                            },
+                        )
+                        .await;
+                    }
+
+                    return Ok(AuthResult::Success(target_principal));
+                }
+                Some(_) => {
+                    tracing::debug!(
+                        context = "directory",
+                        event = "impersonate",
+                        remote_ip = ?remote_ip,
+                        login = ?principal.name,
+                        account = ?target,
+                        "Principal is not allowed to authenticate as a privileged or non-individual account",
+                    );
+                }
+                None => {}
+            }
+        } else {
+            tracing::debug!(
+                context = "directory",
+                event = "impersonate",
+                remote_ip = ?remote_ip,
+                login = ?principal.name,
+                account = ?target,
+                "Principal is not authorized to authenticate as another account",
+            );
+        }
+
+        self.authentication_failed(
+            ipc,
+            &format!("{target}{IMPERSONATION_SEPARATOR}{}", principal.name),
+            remote_ip,
+            protocol,
+            Ok(()),
+        )
+        .await
+    }
+
+    pub async fn authentication_failed<T>(
+        &self,
+        ipc: &Ipc,
+        login: &str,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        result: directory::Result<()>,
+    ) -> directory::Result<AuthResult<T>> {
         if let Err(err) = result {
             // Send webhook event
             if self.has_webhook_subscribers(WebhookType::AuthError) {
@@ -375,7 +572,6 @@ impl Core {
 
             Err(err)
         } else if self.has_fail2ban() {
-            let login = credentials.login();
             if self.is_fail2banned(remote_ip, login.to_string()).await? {
                 tracing::info!(
                     context = "directory",
@@ -385,16 +581,20 @@ impl Core {
                     "IP address blocked after too many failed login attempts",
                 );
 
+                // Replicate ban to cluster peers
+                ipc.broadcast_cluster_event(ClusterEvent::BlockIp(remote_ip));
+
                 // Send webhook event
                 if self.has_webhook_subscribers(WebhookType::AuthBanned) {
                     ipc.send_webhook(
                         WebhookType::AuthBanned,
                         WebhookPayload::Authentication {
-                            login: credentials.login().to_string(),
+                            login: login.to_string(),
                             protocol,
                             remote_ip,
                             typ: None,
                             as_master: None,
+                            master_login: None,
                         },
                     )
                     .await;
@@ -407,11 +607,12 @@ impl Core {
                     ipc.send_webhook(
                         WebhookType::AuthFailure,
                         WebhookPayload::Authentication {
-                            login: credentials.login().to_string(),
+                            login: login.to_string(),
                             protocol,
                             remote_ip,
                             typ: None,
                             as_master: None,
+                            master_login: None,
                         },
                     )
                     .await;
@@ -425,11 +626,12 @@ impl Core {
                 ipc.send_webhook(
                     WebhookType::AuthFailure,
                     WebhookPayload::Authentication {
-                        login: credentials.login().to_string(),
+                        login: login.to_string(),
                         protocol,
                         remote_ip,
                         typ: None,
                         as_master: None,
+                        master_login: None,
                     },
                 )
                 .await;
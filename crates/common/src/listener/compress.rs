@@ -0,0 +1,244 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    borrow::Cow,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::SessionStream;
+
+const BUF_SIZE: usize = 8192;
+
+// Raw DEFLATE stream (RFC 1951) as used by IMAP COMPRESS (RFC 4978)
+pub struct DeflateStream<T: SessionStream> {
+    inner: T,
+
+    // Decompression state
+    decompress: Decompress,
+    read_buf: Box<[u8]>,
+    read_pos: usize,
+    read_len: usize,
+    read_pending: bool,
+
+    // Compression state
+    compress: Compress,
+    write_buf: Vec<u8>,
+    write_pos: usize,
+    write_pending: bool,
+}
+
+impl<T: SessionStream> DeflateStream<T> {
+    pub fn new(inner: T) -> Self {
+        DeflateStream {
+            inner,
+            decompress: Decompress::new(false),
+            read_buf: vec![0; BUF_SIZE].into_boxed_slice(),
+            read_pos: 0,
+            read_len: 0,
+            read_pending: false,
+            compress: Compress::new(Compression::default(), false),
+            write_buf: Vec::with_capacity(BUF_SIZE),
+            write_pos: 0,
+            write_pending: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.write_pos < self.write_buf.len() {
+            let bytes_written = ready!(
+                Pin::new(&mut self.inner).poll_write(cx, &self.write_buf[self.write_pos..])
+            )?;
+            if bytes_written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_pos += bytes_written;
+        }
+        self.write_buf.clear();
+        self.write_pos = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: SessionStream> AsyncRead for DeflateStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            // Inflate any buffered input or output pending from a previous call
+            if this.read_pos < this.read_len || this.read_pending {
+                let total_in = this.decompress.total_in();
+                let total_out = this.decompress.total_out();
+                let output = buf.initialize_unfilled();
+                let output_len = output.len();
+                let status = this
+                    .decompress
+                    .decompress(
+                        &this.read_buf[this.read_pos..this.read_len],
+                        output,
+                        FlushDecompress::None,
+                    )
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                let bytes_in = (this.decompress.total_in() - total_in) as usize;
+                let bytes_out = (this.decompress.total_out() - total_out) as usize;
+                this.read_pos += bytes_in;
+                this.read_pending = bytes_out == output_len;
+                buf.advance(bytes_out);
+
+                if bytes_out > 0 || status == Status::StreamEnd {
+                    return Poll::Ready(Ok(()));
+                } else if bytes_in == 0 && this.read_pos < this.read_len {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Deflate stream stalled",
+                    )));
+                } else if this.read_pos < this.read_len {
+                    continue;
+                }
+            }
+
+            // Read more compressed data
+            let mut read_buf = ReadBuf::new(&mut this.read_buf);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read_buf))?;
+            let bytes_read = read_buf.filled().len();
+            if bytes_read == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            this.read_pos = 0;
+            this.read_len = bytes_read;
+        }
+    }
+}
+
+impl<T: SessionStream> AsyncWrite for DeflateStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        // Send any previously compressed data first
+        ready!(this.poll_write_buf(cx))?;
+
+        let total_in = this.compress.total_in();
+        this.write_buf.reserve(buf.len() + 64);
+        this.compress
+            .compress_vec(buf, &mut this.write_buf, FlushCompress::None)
+            .map_err(io::Error::other)?;
+        let bytes_in = (this.compress.total_in() - total_in) as usize;
+        if bytes_in > 0 {
+            this.write_pending = true;
+        }
+
+        Poll::Ready(Ok(bytes_in))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            ready!(this.poll_write_buf(cx))?;
+            if !this.write_pending {
+                break;
+            }
+
+            // Emit a sync flush block so the peer can decode everything written so far
+            this.write_buf.reserve(BUF_SIZE);
+            this.compress
+                .compress_vec(&[], &mut this.write_buf, FlushCompress::Sync)
+                .map_err(io::Error::other)?;
+            if this.write_buf.len() < this.write_buf.capacity() {
+                this.write_pending = false;
+            }
+        }
+
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T: SessionStream> SessionStream for DeflateStream<T> {
+    fn is_tls(&self) -> bool {
+        self.inner.is_tls()
+    }
+
+    fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
+        self.inner.tls_version_and_cipher()
+    }
+
+    fn tls_channel_binding(&self) -> Option<Vec<u8>> {
+        self.inner.tls_channel_binding()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use crate::listener::SessionStream;
+
+    use super::DeflateStream;
+
+    impl SessionStream for DuplexStream {
+        fn is_tls(&self) -> bool {
+            false
+        }
+
+        fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
+            (Cow::Borrowed(""), Cow::Borrowed(""))
+        }
+    }
+
+    #[tokio::test]
+    async fn deflate_stream() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut client = DeflateStream::new(client);
+        let mut server = DeflateStream::new(server);
+        let message = "* OK [CAPABILITY IMAP4rev2] Ready\r\n".repeat(1000);
+
+        let writer = tokio::spawn(async move {
+            for chunk in message.as_bytes().chunks(1000) {
+                server.write_all(chunk).await.unwrap();
+                server.flush().await.unwrap();
+            }
+            server.shutdown().await.unwrap();
+            message
+        });
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(writer.await.unwrap().as_bytes(), received);
+    }
+}
@@ -19,7 +19,11 @@ use tokio::{
 };
 use tokio_rustls::server::TlsStream;
 use tracing::Span;
-use utils::{config::Config, UnwrapFailure};
+use utils::{
+    config::Config,
+    metrics::{CONNECTIONS_ACTIVE, CONNECTIONS_TOTAL},
+    UnwrapFailure,
+};
 
 use crate::{
     config::server::{Listener, Server, ServerProtocol, Servers},
@@ -45,12 +49,17 @@ impl Server {
             protocol: self.protocol,
             proxy_networks: self.proxy_networks,
             limiter: ConcurrencyLimiter::new(self.max_connections),
+            compress: self.compress,
             acceptor,
             shutdown_rx,
         });
         let is_tls = matches!(instance.acceptor, TcpAcceptor::Tls { implicit, .. } if implicit);
         let is_https = is_tls && self.protocol == ServerProtocol::Http;
         let has_proxies = !instance.proxy_networks.is_empty();
+        CONNECTIONS_ACTIVE.bind(
+            &[&instance.id, instance.protocol.as_str()],
+            instance.limiter.concurrent.clone(),
+        );
 
         // Spawn listeners
         for listener in self.listeners {
@@ -204,6 +213,7 @@ impl BuildSession for Arc<ServerInstance> {
             None
         } else if let Some(in_flight) = self.limiter.is_allowed() {
             // Enforce concurrency
+            CONNECTIONS_TOTAL.inc(&[&self.id, self.protocol.as_str()]);
             SessionData {
                 stream,
                 in_flight,
@@ -25,6 +25,7 @@ use self::limiter::{ConcurrencyLimiter, InFlight};
 
 pub mod acme;
 pub mod blocked;
+pub mod compress;
 pub mod limiter;
 pub mod listen;
 pub mod stream;
@@ -36,6 +37,7 @@ pub struct ServerInstance {
     pub acceptor: TcpAcceptor,
     pub limiter: ConcurrencyLimiter,
     pub proxy_networks: Vec<IpAddrMask>,
+    pub compress: bool,
     pub shutdown_rx: watch::Receiver<bool>,
 }
 
@@ -75,6 +77,11 @@ pub struct SessionData<T: SessionStream> {
 pub trait SessionStream: AsyncRead + AsyncWrite + Unpin + 'static + Sync + Send {
     fn is_tls(&self) -> bool;
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>);
+
+    // Channel binding data for SCRAM-*-PLUS (RFC 9266 tls-exporter)
+    fn tls_channel_binding(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 pub trait SessionManager: Sync + Send + 'static + Clone {
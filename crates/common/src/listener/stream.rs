@@ -62,6 +62,13 @@ impl<T: SessionStream> SessionStream for TlsStream<T> {
             .into(),
         )
     }
+
+    fn tls_channel_binding(&self) -> Option<Vec<u8>> {
+        self.get_ref()
+            .1
+            .export_keying_material(vec![0u8; 32], b"EXPORTER-Channel-Binding", None)
+            .ok()
+    }
 }
 
 impl SessionStream for ProxiedStream<TcpStream> {
@@ -0,0 +1,628 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    num::NonZeroU32,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use sha2::{Digest, Sha256};
+use utils::{config::Config, failed, UnwrapFailure};
+
+use super::backup::{FILE_VERSION, MAGIC_MARKER};
+
+pub(super) const MANIFEST_FILE: &str = "manifest.json";
+pub(super) const BLOB_MANIFEST_FILE: &str = "blob_manifest";
+
+const FLAG_COMPRESSED: u8 = 1;
+const FLAG_ENCRYPTED: u8 = 1 << 1;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const CHUNK_LAST: u32 = 1 << 31;
+const KDF_ITERATIONS: u32 = 100_000;
+
+pub(super) type BackupKey = [u8; 32];
+
+#[derive(Debug, Clone, Default)]
+pub struct BackupSettings {
+    pub compress: bool,
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(super) struct Manifest {
+    pub version: u32,
+    pub id: String,
+    pub created: u64,
+    #[serde(default)]
+    pub parent: Option<ParentBackup>,
+    #[serde(default)]
+    pub compressed: bool,
+    #[serde(default)]
+    pub encryption: Option<Encryption>,
+    // Change log position of each account and the backup holding its data
+    #[serde(default)]
+    pub accounts: BTreeMap<u32, AccountState>,
+    #[serde(default)]
+    pub files: BTreeMap<String, FileDigest>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(super) struct ParentBackup {
+    pub id: String,
+    // Relative to the directory of the child backup
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(super) struct Encryption {
+    pub algorithm: String,
+    pub iterations: u32,
+    pub salt: String,
+    #[serde(rename = "keyCheck")]
+    pub key_check: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(super) struct AccountState {
+    #[serde(rename = "changeId")]
+    pub change_id: u64,
+    #[serde(default)]
+    pub fingerprint: String,
+    pub backup: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(super) struct FileDigest {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl BackupSettings {
+    pub fn parse(config: &mut Config) -> Self {
+        BackupSettings {
+            compress: match config.value("backup.compression").unwrap_or("none") {
+                "gzip" => true,
+                "none" => false,
+                other => failed(&format!(
+                    "Invalid value {other:?} for backup.compression, expected \"gzip\" or \"none\"."
+                )),
+            },
+            secret: config
+                .value("backup.encryption.secret")
+                .filter(|secret| !secret.is_empty())
+                .map(|secret| secret.to_string()),
+        }
+    }
+}
+
+impl ParentBackup {
+    pub fn new(id: String, parent_dir: &Path, child_dir: &Path) -> Self {
+        let parent_dir = parent_dir.components().collect::<Vec<_>>();
+        let child_dir = child_dir.components().collect::<Vec<_>>();
+        let common = parent_dir
+            .iter()
+            .zip(&child_dir)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut path = PathBuf::new();
+        for _ in common..child_dir.len() {
+            path.push(Component::ParentDir);
+        }
+        for component in &parent_dir[common..] {
+            path.push(component);
+        }
+
+        ParentBackup { id, path }
+    }
+}
+
+impl Manifest {
+    pub fn new(
+        settings: &BackupSettings,
+        parent: Option<ParentBackup>,
+    ) -> (Self, Option<BackupKey>) {
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut random = [0u8; 4];
+        SystemRandom::new()
+            .fill(&mut random)
+            .failed("Failed to generate backup id");
+
+        let (encryption, key) = if let Some(secret) = &settings.secret {
+            let mut salt = [0u8; 16];
+            SystemRandom::new()
+                .fill(&mut salt)
+                .failed("Failed to generate salt");
+            let key = derive_key(secret, &salt, KDF_ITERATIONS);
+            (
+                Some(Encryption {
+                    algorithm: "aes-256-gcm".to_string(),
+                    iterations: KDF_ITERATIONS,
+                    salt: STANDARD.encode(salt),
+                    key_check: STANDARD.encode(Sha256::digest(key)),
+                }),
+                Some(key),
+            )
+        } else {
+            (None, None)
+        };
+
+        (
+            Manifest {
+                version: 1,
+                id: format!("{created}-{}", u32::from_be_bytes(random)),
+                created,
+                parent,
+                compressed: settings.compress,
+                encryption,
+                accounts: BTreeMap::new(),
+                files: BTreeMap::new(),
+            },
+            key,
+        )
+    }
+
+    pub fn read(dir: &Path) -> Option<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        if path.exists() {
+            match serde_json::from_slice(
+                &std::fs::read(&path).failed("Failed to read backup manifest"),
+            ) {
+                Ok(manifest) => Some(manifest),
+                Err(err) => failed(&format!("Invalid backup manifest {path:?}: {err}")),
+            }
+        } else {
+            None
+        }
+    }
+
+    pub fn write(&self, dir: &Path) {
+        std::fs::write(
+            dir.join(MANIFEST_FILE),
+            serde_json::to_vec_pretty(self).failed("Failed to serialize backup manifest"),
+        )
+        .failed("Failed to write backup manifest");
+    }
+
+    // Returns the backups needed to restore this one, starting with the full backup
+    pub fn chain(dir: &Path) -> Vec<(PathBuf, Manifest)> {
+        let mut chain = Vec::new();
+        let mut next = Some((dir.to_path_buf(), None::<String>));
+
+        while let Some((path, expected_id)) = next.take() {
+            let manifest = Manifest::read(&path)
+                .unwrap_or_else(|| failed(&format!("No backup manifest found in {path:?}")));
+            if expected_id.as_ref().is_some_and(|id| id != &manifest.id) {
+                failed(&format!(
+                    "Backup in {path:?} is not the parent of the requested backup."
+                ));
+            }
+            next = manifest
+                .parent
+                .as_ref()
+                .map(|parent| (path.join(&parent.path), Some(parent.id.clone())));
+            chain.push((path, manifest));
+        }
+
+        chain.reverse();
+        chain
+    }
+
+    pub fn key(&self, settings: &BackupSettings) -> Option<BackupKey> {
+        let encryption = self.encryption.as_ref()?;
+        let secret = settings.secret.as_ref().unwrap_or_else(|| {
+            failed(&format!(
+                "Backup {} is encrypted, please set backup.encryption.secret.",
+                self.id
+            ))
+        });
+        if encryption.algorithm != "aes-256-gcm" {
+            failed(&format!(
+                "Unsupported backup encryption algorithm {}.",
+                encryption.algorithm
+            ));
+        }
+        let salt = STANDARD
+            .decode(&encryption.salt)
+            .failed("Invalid salt in backup manifest");
+        let key = derive_key(secret, &salt, encryption.iterations);
+        if STANDARD.encode(Sha256::digest(key)) != encryption.key_check {
+            failed(&format!(
+                "Invalid encryption secret for backup {}.",
+                self.id
+            ));
+        }
+        Some(key)
+    }
+}
+
+fn derive_key(secret: &str, salt: &[u8], iterations: u32) -> BackupKey {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(iterations).failed("Invalid iteration count"),
+        salt,
+        secret.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+// Writes a backup file, optionally compressing and encrypting its contents
+pub(super) struct ArchiveWriter {
+    inner: Compressor,
+    hasher: Sha256,
+    size: u64,
+}
+
+enum Compressor {
+    None(ChunkWriter),
+    Gzip(GzEncoder<ChunkWriter>),
+}
+
+struct ChunkWriter {
+    file: BufWriter<File>,
+    cipher: Option<ChunkCipher>,
+    buf: Vec<u8>,
+}
+
+struct ChunkCipher {
+    key: LessSafeKey,
+    nonce: [u8; 8],
+    counter: u32,
+}
+
+impl ArchiveWriter {
+    pub fn create(path: &Path, compress: bool, key: Option<&BackupKey>) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut flags = 0;
+        if compress {
+            flags |= FLAG_COMPRESSED;
+        }
+        if key.is_some() {
+            flags |= FLAG_ENCRYPTED;
+        }
+        file.write_all(&[MAGIC_MARKER, FILE_VERSION, flags])?;
+
+        let cipher = if let Some(key) = key {
+            let mut nonce = [0u8; 8];
+            SystemRandom::new()
+                .fill(&mut nonce)
+                .map_err(|_| std::io::Error::other("Failed to generate nonce"))?;
+            file.write_all(&nonce)?;
+            Some(ChunkCipher::new(key, nonce))
+        } else {
+            None
+        };
+
+        let writer = ChunkWriter {
+            file,
+            cipher,
+            buf: Vec::new(),
+        };
+
+        Ok(ArchiveWriter {
+            inner: if compress {
+                Compressor::Gzip(GzEncoder::new(writer, Compression::default()))
+            } else {
+                Compressor::None(writer)
+            },
+            hasher: Sha256::new(),
+            size: 0,
+        })
+    }
+
+    pub fn finish(self) -> std::io::Result<FileDigest> {
+        match self.inner {
+            Compressor::None(writer) => writer,
+            Compressor::Gzip(writer) => writer.finish()?,
+        }
+        .finish()?;
+
+        Ok(FileDigest {
+            sha256: format!("{:x}", self.hasher.finalize()),
+            size: self.size,
+        })
+    }
+}
+
+impl Write for ArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = match &mut self.inner {
+            Compressor::None(writer) => writer.write(buf)?,
+            Compressor::Gzip(writer) => writer.write(buf)?,
+        };
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.inner {
+            Compressor::None(writer) => writer.flush(),
+            Compressor::Gzip(writer) => writer.flush(),
+        }
+    }
+}
+
+impl ChunkWriter {
+    fn finish(mut self) -> std::io::Result<()> {
+        if let Some(cipher) = &mut self.cipher {
+            // The last chunk is always written, even when empty, to detect truncation
+            let mut chunk = std::mem::take(&mut self.buf);
+            cipher.seal(&mut self.file, &mut chunk, true)?;
+        }
+        self.file.flush()
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(cipher) = &mut self.cipher {
+            self.buf.extend_from_slice(buf);
+            while self.buf.len() > CHUNK_SIZE {
+                let rest = self.buf.split_off(CHUNK_SIZE);
+                let mut chunk = std::mem::replace(&mut self.buf, rest);
+                cipher.seal(&mut self.file, &mut chunk, false)?;
+            }
+            Ok(buf.len())
+        } else {
+            self.file.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl ChunkCipher {
+    fn new(key: &BackupKey, nonce: [u8; 8]) -> Self {
+        ChunkCipher {
+            key: LessSafeKey::new(
+                UnboundKey::new(&AES_256_GCM, key).expect("Invalid AES-256 key length"),
+            ),
+            nonce,
+            counter: 0,
+        }
+    }
+
+    fn next_nonce(&mut self) -> std::io::Result<Nonce> {
+        let mut nonce = [0u8; 12];
+        nonce[..8].copy_from_slice(&self.nonce);
+        nonce[8..].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| std::io::Error::other("Too many chunks"))?;
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+
+    fn seal(
+        &mut self,
+        file: &mut impl Write,
+        chunk: &mut Vec<u8>,
+        is_last: bool,
+    ) -> std::io::Result<()> {
+        let nonce = self.next_nonce()?;
+        self.key
+            .seal_in_place_append_tag(nonce, Aad::from([is_last as u8]), chunk)
+            .map_err(|_| std::io::Error::other("Failed to encrypt chunk"))?;
+        let len = chunk.len() as u32 | if is_last { CHUNK_LAST } else { 0 };
+        file.write_all(&len.to_be_bytes())?;
+        file.write_all(chunk)
+    }
+
+    fn open(&mut self, file: &mut impl Read, buf: &mut Vec<u8>) -> std::io::Result<bool> {
+        let mut len = [0u8; 4];
+        file.read_exact(&mut len).map_err(|err| {
+            if err.kind() == ErrorKind::UnexpectedEof {
+                std::io::Error::new(ErrorKind::InvalidData, "Backup file is truncated")
+            } else {
+                err
+            }
+        })?;
+        let len = u32::from_be_bytes(len);
+        let is_last = len & CHUNK_LAST != 0;
+        let len = (len & !CHUNK_LAST) as usize;
+        if len > CHUNK_SIZE + AES_256_GCM.tag_len() {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Invalid chunk length",
+            ));
+        }
+        buf.resize(len, 0);
+        file.read_exact(buf)?;
+
+        let nonce = self.next_nonce()?;
+        let plain_len = self
+            .key
+            .open_in_place(nonce, Aad::from([is_last as u8]), buf)
+            .map_err(|_| {
+                std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Failed to decrypt chunk, the backup file is corrupted",
+                )
+            })?
+            .len();
+        buf.truncate(plain_len);
+
+        Ok(is_last)
+    }
+}
+
+// Reads a backup file written by any version of the exporter
+pub(super) struct ArchiveReader {
+    pub version: u8,
+    inner: Decompressor,
+    hasher: Sha256,
+    size: u64,
+}
+
+enum Decompressor {
+    None(Box<ChunkReader>),
+    Gzip(Box<GzDecoder<ChunkReader>>),
+}
+
+struct ChunkReader {
+    file: BufReader<File>,
+    cipher: Option<ChunkCipher>,
+    buf: Vec<u8>,
+    pos: usize,
+    is_done: bool,
+}
+
+impl ArchiveReader {
+    pub fn open(path: &Path, key: Option<&BackupKey>) -> Self {
+        let mut file = BufReader::new(File::open(path).failed("Failed to open file"));
+        let mut header = [0u8; 2];
+        file.read_exact(&mut header)
+            .failed(&format!("Failed to read header from {path:?}"));
+        if header[0] != MAGIC_MARKER {
+            failed(&format!("Invalid magic marker in {path:?}"));
+        }
+        let version = header[1];
+        if version > FILE_VERSION {
+            failed(&format!("Invalid file version in {path:?}"));
+        }
+
+        let mut flags = [0u8];
+        if version > 2 {
+            file.read_exact(&mut flags)
+                .failed(&format!("Failed to read flags from {path:?}"));
+        }
+        let cipher = if flags[0] & FLAG_ENCRYPTED != 0 {
+            let key = key.unwrap_or_else(|| failed(&format!("{path:?} is encrypted.")));
+            let mut nonce = [0u8; 8];
+            file.read_exact(&mut nonce)
+                .failed(&format!("Failed to read nonce from {path:?}"));
+            Some(ChunkCipher::new(key, nonce))
+        } else {
+            None
+        };
+
+        let reader = ChunkReader {
+            file,
+            cipher,
+            buf: Vec::new(),
+            pos: 0,
+            is_done: false,
+        };
+
+        ArchiveReader {
+            version,
+            inner: if flags[0] & FLAG_COMPRESSED != 0 {
+                Decompressor::Gzip(Box::new(GzDecoder::new(reader)))
+            } else {
+                Decompressor::None(Box::new(reader))
+            },
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    pub fn finish(self) -> FileDigest {
+        FileDigest {
+            sha256: format!("{:x}", self.hasher.finalize()),
+            size: self.size,
+        }
+    }
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = match &mut self.inner {
+            Decompressor::None(reader) => reader.read(buf)?,
+            Decompressor::Gzip(reader) => reader.read(buf)?,
+        };
+        self.hasher.update(&buf[..read]);
+        self.size += read as u64;
+        Ok(read)
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(cipher) = &mut self.cipher {
+            while self.pos == self.buf.len() {
+                if self.is_done {
+                    return Ok(0);
+                }
+                self.is_done = cipher.open(&mut self.file, &mut self.buf)?;
+                self.pos = 0;
+            }
+            let len = std::cmp::min(buf.len(), self.buf.len() - self.pos);
+            buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+            self.pos += len;
+            Ok(len)
+        } else {
+            self.file.read(buf)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::{ArchiveReader, ArchiveWriter, CHUNK_SIZE};
+
+    #[test]
+    fn archive_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("archive_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = [7u8; 32];
+        let data = (0..(CHUNK_SIZE * 3 + 123))
+            .map(|n| (n % 251) as u8)
+            .collect::<Vec<_>>();
+
+        for (compress, key) in [
+            (false, None),
+            (true, None),
+            (false, Some(&key)),
+            (true, Some(&key)),
+        ] {
+            let path = dir.join(format!("{compress}_{}", key.is_some()));
+            let mut writer = ArchiveWriter::create(&path, compress, key).unwrap();
+            writer.write_all(&data).unwrap();
+            let digest = writer.finish().unwrap();
+
+            let mut reader = ArchiveReader::open(&path, key);
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents).unwrap();
+            assert_eq!(contents, data, "compress: {compress}, encrypt: {key:?}");
+            assert_eq!(reader.finish(), digest);
+
+            // Tampering with or truncating an encrypted file must be detected
+            if key.is_some() {
+                let bytes = std::fs::read(&path).unwrap();
+                let mut tampered = bytes.clone();
+                tampered[bytes.len() / 2] ^= 0xff;
+                for bytes in [tampered, bytes[..bytes.len() - 10].to_vec()] {
+                    std::fs::write(&path, &bytes).unwrap();
+                    let mut reader = ArchiveReader::open(&path, key);
+                    assert!(reader.read_to_end(&mut Vec::new()).is_err());
+                }
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
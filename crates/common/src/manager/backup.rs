@@ -5,15 +5,19 @@
  */
 
 use std::{
-    collections::BTreeSet,
-    io::{BufWriter, Write},
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
     ops::Range,
     path::{Path, PathBuf},
-    sync::mpsc::{self, SyncSender},
+    sync::{
+        mpsc::{self, SyncSender},
+        Arc,
+    },
 };
 
 use ahash::{AHashMap, AHashSet};
 use jmap_proto::types::{collection::Collection, property::Property};
+use sha2::{Digest, Sha256};
 use store::{
     write::{
         key::DeserializeBigEndian, AnyKey, BitmapClass, BitmapHash, BlobOp, DirectoryClass,
@@ -30,8 +34,16 @@ use utils::{
 
 use crate::Core;
 
+use super::{
+    archive::{
+        AccountState, ArchiveWriter, BackupKey, BackupSettings, FileDigest, Manifest, ParentBackup,
+        BLOB_MANIFEST_FILE,
+    },
+    restore::read_blob_manifest,
+};
+
 pub(super) const MAGIC_MARKER: u8 = 123;
-pub(super) const FILE_VERSION: u8 = 2;
+pub(super) const FILE_VERSION: u8 = 3;
 
 #[derive(Debug)]
 pub(super) enum Op {
@@ -56,47 +68,191 @@ pub(super) enum Family {
     Index = 9,
     Bitmap = 10,
     Log = 11,
+    BlobManifest = 12,
     None = 255,
 }
 
-type TaskHandle = (tokio::task::JoinHandle<()>, std::thread::JoinHandle<()>);
+type TaskHandle = (
+    tokio::task::JoinHandle<()>,
+    std::thread::JoinHandle<(String, FileDigest)>,
+);
+
+pub(super) struct ExportParams {
+    compress: bool,
+    key: Option<BackupKey>,
+    // Accounts to export, all of them when not set
+    accounts: Option<AHashSet<u32>>,
+    // Blobs already present in the parent backup
+    parent_blobs: AHashSet<Vec<u8>>,
+}
+
+impl ExportParams {
+    fn is_exported(&self, account_id: u32) -> bool {
+        account_id == u32::MAX
+            || self
+                .accounts
+                .as_ref()
+                .is_none_or(|accounts| accounts.contains(&account_id))
+    }
+}
 
 impl Core {
-    pub async fn backup(&self, dest: PathBuf) {
+    pub async fn backup(&self, dest: PathBuf, parent: Option<PathBuf>, settings: BackupSettings) {
         if !dest.exists() {
             std::fs::create_dir_all(&dest).failed("Failed to create backup directory");
         } else if !dest.is_dir() {
             eprintln!("Backup destination {dest:?} is not a directory.");
             std::process::exit(1);
         }
+        let dest = dest
+            .canonicalize()
+            .failed("Failed to find backup destination");
+
+        // Obtain the parent backup for incremental exports
+        let parent = parent.map(|path| {
+            let path = path.canonicalize().failed("Failed to find parent backup");
+            let manifest = Manifest::read(&path)
+                .unwrap_or_else(|| failed(&format!("No backup manifest found in {path:?}")));
+            (path, manifest)
+        });
+        let (mut manifest, key) = Manifest::new(
+            &settings,
+            parent
+                .as_ref()
+                .map(|(path, manifest)| ParentBackup::new(manifest.id.clone(), path, &dest)),
+        );
+
+        // Only export accounts that changed since the parent backup
+        let mut changed_accounts = AHashSet::new();
+        for (account_id, (change_id, fingerprint)) in self.backup_account_positions().await {
+            let state = parent
+                .as_ref()
+                .and_then(|(_, parent)| parent.accounts.get(&account_id))
+                .filter(|state| state.change_id == change_id && state.fingerprint == fingerprint)
+                .cloned()
+                .unwrap_or_else(|| {
+                    changed_accounts.insert(account_id);
+                    AccountState {
+                        change_id,
+                        fingerprint,
+                        backup: manifest.id.clone(),
+                    }
+                });
+            manifest.accounts.insert(account_id, state);
+        }
+        let params = Arc::new(ExportParams {
+            compress: settings.compress,
+            key,
+            parent_blobs: parent
+                .as_ref()
+                .map(|(path, manifest)| {
+                    read_blob_manifest(path, manifest, manifest.key(&settings).as_ref())
+                })
+                .unwrap_or_default(),
+            accounts: parent.is_some().then_some(changed_accounts),
+        });
 
         let mut sync_handles = Vec::new();
 
         for (async_handle, sync_handle) in [
-            self.backup_properties(&dest),
-            self.backup_fts_index(&dest),
-            self.backup_acl(&dest),
-            self.backup_blob(&dest),
-            self.backup_config(&dest),
-            self.backup_lookup(&dest),
-            self.backup_directory(&dest),
-            self.backup_queue(&dest),
-            self.backup_index(&dest),
-            self.backup_bitmaps(&dest),
-            self.backup_logs(&dest),
+            self.backup_properties(&dest, &params),
+            self.backup_fts_index(&dest, &params),
+            self.backup_acl(&dest, &params),
+            self.backup_blob(&dest, &params),
+            self.backup_blob_manifest(&dest, &params),
+            self.backup_config(&dest, &params),
+            self.backup_lookup(&dest, &params),
+            self.backup_directory(&dest, &params),
+            self.backup_queue(&dest, &params),
+            self.backup_index(&dest, &params),
+            self.backup_bitmaps(&dest, &params),
+            self.backup_logs(&dest, &params),
         ] {
             async_handle.await.failed("Task failed");
             sync_handles.push(sync_handle);
         }
 
         for handle in sync_handles {
-            handle.join().expect("Failed to join thread");
+            let (file, digest) = handle.join().expect("Failed to join thread");
+            manifest.files.insert(file, digest);
         }
+
+        manifest.write(&dest);
+        println!(
+            "Exported {} of {} accounts to backup {}.",
+            params
+                .accounts
+                .as_ref()
+                .map_or(manifest.accounts.len(), |accounts| accounts.len()),
+            manifest.accounts.len(),
+            manifest.id
+        );
     }
 
-    fn backup_properties(&self, dest: &Path) -> TaskHandle {
+    // Returns the last change id and a fingerprint of the document ids and
+    // used quota of every account with documents. Purging expired messages
+    // does not write to the change log, the fingerprint detects those changes.
+    async fn backup_account_positions(&self) -> BTreeMap<u32, (u64, String)> {
+        let store = &self.storage.data;
+        let mut collections = BTreeSet::new();
+        let mut digests: BTreeMap<u32, Sha256> = BTreeMap::new();
+        store
+            .iterate(
+                IterateParams::new(
+                    AnyKey {
+                        subspace: SUBSPACE_BITMAP_ID,
+                        key: vec![0u8],
+                    },
+                    AnyKey {
+                        subspace: SUBSPACE_BITMAP_ID,
+                        key: vec![u8::MAX; 10],
+                    },
+                ),
+                |key, value| {
+                    let account_id = key.deserialize_be_u32(0)?;
+                    collections.insert((account_id, key.deserialize_u8(U32_LEN)?));
+                    let digest = digests.entry(account_id).or_default();
+                    digest.update(key);
+                    digest.update(value);
+
+                    Ok(true)
+                },
+            )
+            .await
+            .failed("Failed to iterate over data store");
+
+        let mut accounts = BTreeMap::new();
+        for (account_id, collection) in collections {
+            let change_id = store
+                .get_last_change_id(account_id, collection)
+                .await
+                .failed("Failed to obtain last change id")
+                .unwrap_or_default();
+            let last_change_id = accounts.entry(account_id).or_insert(0);
+            *last_change_id = std::cmp::max(*last_change_id, change_id);
+        }
+
+        let mut positions = BTreeMap::new();
+        for (account_id, change_id) in accounts {
+            let used_quota = store
+                .get_counter(DirectoryClass::UsedQuota(account_id))
+                .await
+                .failed("Failed to obtain used quota");
+            let mut digest = digests.remove(&account_id).unwrap_or_default();
+            digest.update(used_quota.to_be_bytes());
+            positions.insert(
+                account_id,
+                (change_id, format!("{:x}", digest.finalize())),
+            );
+        }
+
+        positions
+    }
+
+    fn backup_properties(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("property"));
+        let (handle, writer) = spawn_writer(dest.join("property"), params);
+        let params = params.clone();
         (
             tokio::spawn(async move {
                 writer
@@ -128,7 +284,9 @@ impl Core {
                             let field = key.deserialize_u8(U32_LEN + 1)?;
                             let document_id = key.deserialize_be_u32(U32_LEN + 2)?;
 
-                            keys.insert((account_id, collection, document_id, field));
+                            if params.is_exported(account_id) {
+                                keys.insert((account_id, collection, document_id, field));
+                            }
 
                             Ok(true)
                         },
@@ -206,9 +364,9 @@ impl Core {
         )
     }
 
-    fn backup_fts_index(&self, dest: &Path) -> TaskHandle {
+    fn backup_fts_index(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("fts_index"));
+        let (handle, writer) = spawn_writer(dest.join("fts_index"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -280,9 +438,9 @@ impl Core {
         )
     }
 
-    fn backup_acl(&self, dest: &Path) -> TaskHandle {
+    fn backup_acl(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("acl"));
+        let (handle, writer) = spawn_writer(dest.join("acl"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -353,10 +511,11 @@ impl Core {
         )
     }
 
-    fn backup_blob(&self, dest: &Path) -> TaskHandle {
+    fn backup_blob(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
         let blob_store = self.storage.blob.clone();
-        let (handle, writer) = spawn_writer(dest.join("blob"));
+        let (handle, writer) = spawn_writer(dest.join("blob"), params);
+        let params = params.clone();
         (
             tokio::spawn(async move {
                 writer
@@ -406,7 +565,7 @@ impl Core {
                                 writer
                                     .send(Op::KeyValue((hash, vec![])))
                                     .failed("Failed to send key value");
-                            } else {
+                            } else if !params.parent_blobs.contains(&hash) {
                                 hashes.push(hash);
                             }
 
@@ -444,9 +603,63 @@ impl Core {
         )
     }
 
-    fn backup_config(&self, dest: &Path) -> TaskHandle {
+    fn backup_blob_manifest(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("config"));
+        let (handle, writer) = spawn_writer(dest.join(BLOB_MANIFEST_FILE), params);
+        (
+            tokio::spawn(async move {
+                writer
+                    .send(Op::Family(Family::BlobManifest))
+                    .failed("Failed to send family");
+
+                store
+                    .iterate(
+                        IterateParams::new(
+                            ValueKey {
+                                account_id: 0,
+                                collection: 0,
+                                document_id: 0,
+                                class: ValueClass::Blob(BlobOp::Link {
+                                    hash: Default::default(),
+                                }),
+                            },
+                            ValueKey {
+                                account_id: u32::MAX,
+                                collection: u8::MAX,
+                                document_id: u32::MAX,
+                                class: ValueClass::Blob(BlobOp::Link {
+                                    hash: BlobHash::new_max(),
+                                }),
+                            },
+                        )
+                        .no_values(),
+                        |key, _| {
+                            let account_id = key.deserialize_be_u32(BLOB_HASH_LEN)?;
+                            let document_id =
+                                key.deserialize_be_u32(BLOB_HASH_LEN + U32_LEN + 1)?;
+
+                            if account_id == u32::MAX || document_id == u32::MAX {
+                                writer
+                                    .send(Op::KeyValue((
+                                        key.range(0..BLOB_HASH_LEN)?.to_vec(),
+                                        vec![],
+                                    )))
+                                    .failed("Failed to send key value");
+                            }
+
+                            Ok(true)
+                        },
+                    )
+                    .await
+                    .failed("Failed to iterate over data store");
+            }),
+            handle,
+        )
+    }
+
+    fn backup_config(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
+        let store = self.storage.data.clone();
+        let (handle, writer) = spawn_writer(dest.join("config"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -491,9 +704,9 @@ impl Core {
         )
     }
 
-    fn backup_lookup(&self, dest: &Path) -> TaskHandle {
+    fn backup_lookup(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("lookup"));
+        let (handle, writer) = spawn_writer(dest.join("lookup"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -597,9 +810,9 @@ impl Core {
         )
     }
 
-    fn backup_directory(&self, dest: &Path) -> TaskHandle {
+    fn backup_directory(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("directory"));
+        let (handle, writer) = spawn_writer(dest.join("directory"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -669,9 +882,9 @@ impl Core {
         )
     }
 
-    fn backup_queue(&self, dest: &Path) -> TaskHandle {
+    fn backup_queue(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("queue"));
+        let (handle, writer) = spawn_writer(dest.join("queue"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -750,9 +963,9 @@ impl Core {
         )
     }
 
-    fn backup_index(&self, dest: &Path) -> TaskHandle {
+    fn backup_index(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("index"));
+        let (handle, writer) = spawn_writer(dest.join("index"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -820,10 +1033,11 @@ impl Core {
         )
     }
 
-    fn backup_bitmaps(&self, dest: &Path) -> TaskHandle {
+    fn backup_bitmaps(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
 
-        let (handle, writer) = spawn_writer(dest.join("bitmap"));
+        let (handle, writer) = spawn_writer(dest.join("bitmap"), params);
+        let params = params.clone();
         (
             tokio::spawn(async move {
                 const BM_MARKER: u8 = 1 << 7;
@@ -854,6 +1068,9 @@ impl Core {
                             .no_values(),
                             |key, _| {
                                 let account_id = key.deserialize_be_u32(0)?;
+                                if !params.is_exported(account_id) {
+                                    return Ok(true);
+                                }
 
                                 let key = key.range(0..key.len() - U32_LEN)?;
 
@@ -991,9 +1208,9 @@ impl Core {
         )
     }
 
-    fn backup_logs(&self, dest: &Path) -> TaskHandle {
+    fn backup_logs(&self, dest: &Path, params: &Arc<ExportParams>) -> TaskHandle {
         let store = self.storage.data.clone();
-        let (handle, writer) = spawn_writer(dest.join("log"));
+        let (handle, writer) = spawn_writer(dest.join("log"), params);
         (
             tokio::spawn(async move {
                 writer
@@ -1055,18 +1272,52 @@ impl Core {
     }
 }
 
-fn spawn_writer(path: PathBuf) -> (std::thread::JoinHandle<()>, SyncSender<Op>) {
+fn spawn_writer(
+    path: PathBuf,
+    params: &Arc<ExportParams>,
+) -> (
+    std::thread::JoinHandle<(String, FileDigest)>,
+    SyncSender<Op>,
+) {
     let (tx, rx) = mpsc::sync_channel(10);
+    let params = params.clone();
 
     let handle = std::thread::spawn(move || {
         println!("Exporting database to {}.", path.to_str().unwrap());
 
-        let mut file =
-            BufWriter::new(std::fs::File::create(path).failed("Failed to create backup file"));
-        file.write_all(&[MAGIC_MARKER, FILE_VERSION])
-            .failed("Failed to write version");
+        let mut file = ArchiveWriter::create(&path, params.compress, params.key.as_ref())
+            .failed("Failed to create backup file");
+        let mut is_skipped = false;
+        let mut skipped_context = (None, None);
 
         while let Ok(op) = rx.recv() {
+            // Skip data belonging to accounts that are not being exported,
+            // the collection and document id are only sent when they change
+            // so they are written again once an exported account follows.
+            match &op {
+                Op::AccountId(account_id) => {
+                    is_skipped = !params.is_exported(*account_id);
+                    if is_skipped {
+                        continue;
+                    }
+                }
+                Op::Family(_) => {
+                    is_skipped = false;
+                    skipped_context = (None, None);
+                }
+                Op::Collection(collection) if is_skipped => {
+                    skipped_context.0 = Some(*collection);
+                    continue;
+                }
+                Op::DocumentId(document_id) if is_skipped => {
+                    skipped_context.1 = Some(*document_id);
+                    continue;
+                }
+                _ if is_skipped => continue,
+                _ => (),
+            }
+
+            let is_account_id = matches!(op, Op::AccountId(_));
             match op {
                 Op::Family(f) => {
                     file.write_all(&[0u8, f as u8])
@@ -1099,9 +1350,27 @@ fn spawn_writer(path: PathBuf) -> (std::thread::JoinHandle<()>, SyncSender<Op>)
                         .failed("Failed to write document id");
                 }
             }
+
+            if is_account_id {
+                if let Some(collection) = skipped_context.0.take() {
+                    file.write_all(&[4u8, collection])
+                        .failed("Failed to write collection");
+                }
+                if let Some(document_id) = skipped_context.1.take() {
+                    file.write_all(&[5u8]).failed("Failed to write document id");
+                    file.write_all(&document_id.serialize())
+                        .failed("Failed to write document id");
+                }
+            }
         }
 
-        file.flush().failed("Failed to flush backup file");
+        (
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default()
+                .to_string(),
+            file.finish().failed("Failed to flush backup file"),
+        )
     });
 
     (handle, tx)
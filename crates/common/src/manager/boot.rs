@@ -24,7 +24,9 @@ use crate::{
 };
 
 use super::{
+    archive::BackupSettings,
     config::{ConfigManager, Patterns},
+    restore::verify_backup,
     WEBADMIN_KEY,
 };
 
@@ -43,6 +45,9 @@ Options:
   -c, --config <PATH>              Start server with the specified configuration file
   -e, --export <PATH>              Export all store data to a specific path
   -i, --import <PATH>              Import store data from a specific path
+      --incremental <PATH>         Only export accounts changed since the backup at a specific path
+      --account <ID|NAME>          Only import the data of a specific account
+      --verify <PATH>              Verify the integrity of the backup at a specific path
   -I, --init <PATH>                Initialize a new server at a specific path
   -h, --help                       Print help
   -V, --version                    Print version
@@ -52,6 +57,7 @@ Options:
 enum ImportExport {
     Export(PathBuf),
     Import(PathBuf),
+    Verify(PathBuf),
     None,
 }
 
@@ -59,6 +65,8 @@ impl BootManager {
     pub async fn init() -> Self {
         let mut config_path = std::env::var("CONFIG_PATH").ok();
         let mut import_export = ImportExport::None;
+        let mut backup_parent = None;
+        let mut backup_account = None;
 
         if config_path.is_none() {
             let mut args = std::env::args().skip(1);
@@ -96,6 +104,15 @@ impl BootManager {
                     ("import" | "i", Some(value)) => {
                         import_export = ImportExport::Import(value.into());
                     }
+                    ("incremental", Some(value)) => {
+                        backup_parent = Some(PathBuf::from(value));
+                    }
+                    ("account", Some(value)) => {
+                        backup_account = Some(value);
+                    }
+                    ("verify", Some(value)) => {
+                        import_export = ImportExport::Verify(value.into());
+                    }
                     (_, None) => {
                         failed(&format!("Unrecognized command '{key}', try '--help'."));
                     }
@@ -333,17 +350,21 @@ impl BootManager {
             ImportExport::Export(path) => {
                 Core::parse(&mut config, stores, manager)
                     .await
-                    .backup(path)
+                    .backup(path, backup_parent, BackupSettings::parse(&mut config))
                     .await;
                 std::process::exit(0);
             }
             ImportExport::Import(path) => {
                 Core::parse(&mut config, stores, manager)
                     .await
-                    .restore(path)
+                    .restore(path, backup_account, BackupSettings::parse(&mut config))
                     .await;
                 std::process::exit(0);
             }
+            ImportExport::Verify(path) => {
+                verify_backup(path, BackupSettings::parse(&mut config)).await;
+                std::process::exit(0);
+            }
         }
     }
 }
@@ -10,6 +10,7 @@ use crate::USER_AGENT;
 
 use self::config::ConfigManager;
 
+pub mod archive;
 pub mod backup;
 pub mod boot;
 pub mod config;
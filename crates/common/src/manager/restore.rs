@@ -5,11 +5,14 @@
  */
 
 use std::{
-    io::ErrorKind,
+    io::{BufReader, ErrorKind, Read},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use crate::Core;
+use ahash::AHashSet;
+use directory::backend::internal::manage::ManageDirectory;
 use jmap_proto::types::{collection::Collection, property::Property};
 use store::{
     roaring::RoaringBitmap,
@@ -24,51 +27,326 @@ use store::{
     write::{QueueClass, QueueEvent},
     Deserialize, U64_LEN,
 };
-use tokio::{
-    fs::File,
-    io::{AsyncReadExt, BufReader},
-};
+use tokio::sync::mpsc;
 use utils::{failed, BlobHash, UnwrapFailure};
 
-use super::backup::{DeserializeBytes, Family, Op, FILE_VERSION, MAGIC_MARKER};
+use super::{
+    archive::{ArchiveReader, BackupKey, BackupSettings, FileDigest, Manifest, BLOB_MANIFEST_FILE},
+    backup::{DeserializeBytes, Family, Op},
+};
+
+struct RestoreFilter {
+    // Accounts to restore, or all of them when not set
+    accounts: Option<AHashSet<u32>>,
+    // Blob contents to restore, or all of them when not set
+    blobs: Option<Arc<AHashSet<Vec<u8>>>>,
+    // Whether to restore data that does not belong to an account
+    include_global: bool,
+    // Account whose used quota is restored when global data is excluded
+    quota_account_id: Option<u32>,
+    digest: Option<FileDigest>,
+}
 
 impl Core {
-    pub async fn restore(&self, src: PathBuf) {
-        // Backup the core
-        if src.is_dir() {
-            // Iterate directory and spawn a task for each file
-            let mut tasks = Vec::new();
-            for entry in std::fs::read_dir(&src).failed("Failed to read directory") {
-                let entry = entry.failed("Failed to read entry");
-                let path = entry.path();
-                if path.is_file() {
-                    let storage = self.storage.clone();
-                    let blob_store = self.storage.blob.clone();
-                    tasks.push(tokio::spawn(async move {
-                        restore_file(storage.data, blob_store, &path).await;
-                    }));
+    pub async fn restore(&self, src: PathBuf, account: Option<String>, settings: BackupSettings) {
+        let account_id = match account {
+            Some(account) => {
+                let account_id = match account.parse::<u32>() {
+                    Ok(account_id) => account_id,
+                    Err(_) => self
+                        .storage
+                        .data
+                        .get_account_id(&account)
+                        .await
+                        .failed("Failed to obtain account id")
+                        .unwrap_or_else(|| failed(&format!("Account {account:?} not found."))),
+                };
+                println!("Restoring account {account} (id {account_id}).");
+                Some(account_id)
+            }
+            None => None,
+        };
+
+        if src.is_dir() && Manifest::read(&src).is_some() {
+            self.restore_chain(&src, account_id, &settings).await;
+            return;
+        }
+
+        // Backups created by older versions do not include a manifest
+        let files = if src.is_dir() {
+            std::fs::read_dir(&src)
+                .failed("Failed to read directory")
+                .map(|entry| entry.failed("Failed to read entry").path())
+                .filter(|path| path.is_file())
+                .collect::<Vec<_>>()
+        } else {
+            vec![src]
+        };
+        let blobs = if let Some(account_id) = account_id {
+            let linked = files
+                .iter()
+                .filter(|path| path.file_name().is_some_and(|name| name == "blob"))
+                .flat_map(|path| read_linked_blobs(path, account_id, None))
+                .collect::<AHashSet<_>>();
+            self.storage
+                .data
+                .purge_account(account_id)
+                .await
+                .failed("Failed to purge account");
+            Some(Arc::new(linked))
+        } else {
+            None
+        };
+
+        self.restore_files(
+            files
+                .into_iter()
+                .map(|path| {
+                    (
+                        path,
+                        RestoreFilter {
+                            accounts: account_id
+                                .map(|account_id| AHashSet::from_iter([account_id])),
+                            blobs: blobs.clone(),
+                            include_global: account_id.is_none(),
+                            quota_account_id: account_id,
+                            digest: None,
+                        },
+                    )
+                })
+                .collect(),
+            None,
+        )
+        .await;
+    }
+
+    async fn restore_chain(&self, src: &Path, account_id: Option<u32>, settings: &BackupSettings) {
+        let chain = Manifest::chain(src);
+        let (last_path, last_manifest) = chain.last().unwrap();
+        let mut blobs = read_blob_manifest(
+            last_path,
+            last_manifest,
+            last_manifest.key(settings).as_ref(),
+        );
+
+        if let Some(account_id) = account_id {
+            // Only restore the blobs linked to the account
+            let state = last_manifest.accounts.get(&account_id).unwrap_or_else(|| {
+                failed(&format!(
+                    "Account {account_id} is not included in this backup."
+                ))
+            });
+            let (path, manifest) = chain
+                .iter()
+                .find(|(_, manifest)| manifest.id == state.backup)
+                .unwrap_or_else(|| failed(&format!("Backup {} not found.", state.backup)));
+            let linked = read_linked_blobs(
+                &path.join("blob"),
+                account_id,
+                manifest.key(settings).as_ref(),
+            );
+            blobs.retain(|hash| linked.contains(hash));
+
+            self.storage
+                .data
+                .purge_account(account_id)
+                .await
+                .failed("Failed to purge account");
+        }
+        let blobs = Arc::new(blobs);
+
+        for (pos, (path, manifest)) in chain.iter().enumerate() {
+            println!("Importing backup {} from {}.", manifest.id, path.display());
+
+            // Each account is restored from the last backup that exported it,
+            // while data not belonging to any account is taken from the latest one
+            let is_last = pos == chain.len() - 1;
+            let accounts = last_manifest
+                .accounts
+                .iter()
+                .filter(|(id, state)| {
+                    state.backup == manifest.id && account_id.is_none_or(|a| a == **id)
+                })
+                .map(|(id, _)| *id)
+                .collect::<AHashSet<_>>();
+
+            self.restore_files(
+                manifest
+                    .files
+                    .iter()
+                    .filter(|(file, _)| *file != BLOB_MANIFEST_FILE)
+                    .map(|(file, digest)| {
+                        (
+                            path.join(file),
+                            RestoreFilter {
+                                accounts: Some(accounts.clone()),
+                                blobs: Some(blobs.clone()),
+                                include_global: is_last && account_id.is_none(),
+                                quota_account_id: account_id.filter(|_| is_last),
+                                digest: Some(digest.clone()),
+                            },
+                        )
+                    })
+                    .collect(),
+                manifest.key(settings),
+            )
+            .await;
+        }
+    }
+
+    async fn restore_files(&self, files: Vec<(PathBuf, RestoreFilter)>, key: Option<BackupKey>) {
+        let mut tasks = Vec::with_capacity(files.len());
+        for (path, filter) in files {
+            let store = self.storage.data.clone();
+            let blob_store = self.storage.blob.clone();
+            tasks.push(tokio::spawn(async move {
+                restore_file(store, blob_store, &path, key, filter).await;
+            }));
+        }
+
+        for task in tasks {
+            task.await.failed("Failed to wait for task");
+        }
+    }
+}
+
+pub async fn verify_backup(src: PathBuf, settings: BackupSettings) {
+    let src = src.canonicalize().failed("Failed to find backup");
+    let chain = Manifest::chain(&src);
+    let mut available_blobs = AHashSet::new();
+    let mut errors = 0;
+
+    for (path, manifest) in &chain {
+        println!("Verifying backup {} in {}.", manifest.id, path.display());
+        let key = manifest.key(&settings);
+
+        for (file, expected) in &manifest.files {
+            let mut reader = OpReader::open(&path.join(file), key.as_ref());
+            let mut is_blob_content = false;
+            let mut ops = 0u64;
+
+            loop {
+                match reader.try_next() {
+                    Ok(Some(op)) => {
+                        ops += 1;
+                        match op {
+                            Op::AccountId(account_id) => {
+                                is_blob_content = file == "blob" && account_id == u32::MAX;
+                            }
+                            Op::DocumentId(document_id) => {
+                                is_blob_content |= file == "blob" && document_id == u32::MAX;
+                            }
+                            Op::KeyValue((key, value)) if is_blob_content => {
+                                if BlobHash::from(value.as_slice()).as_slice() == key.as_slice() {
+                                    available_blobs.insert(key);
+                                } else {
+                                    eprintln!("Blob {key:?} in {file} does not match its hash.");
+                                    errors += 1;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        eprintln!("Failed to read {file} after {ops} entries: {err}");
+                        errors += 1;
+                        break;
+                    }
                 }
             }
 
-            for task in tasks {
-                task.await.failed("Failed to wait for task");
+            let digest = reader.finish();
+            if &digest != expected {
+                eprintln!(
+                    "Checksum mismatch in {file}: expected {} ({} bytes), found {} ({} bytes).",
+                    expected.sha256, expected.size, digest.sha256, digest.size
+                );
+                errors += 1;
             }
-        } else {
-            restore_file(self.storage.data.clone(), self.storage.blob.clone(), &src).await;
         }
     }
+
+    let (last_path, last_manifest) = chain.last().unwrap();
+    let missing = read_blob_manifest(
+        last_path,
+        last_manifest,
+        last_manifest.key(&settings).as_ref(),
+    )
+    .into_iter()
+    .filter(|hash| !available_blobs.contains(hash))
+    .count();
+    if missing > 0 {
+        eprintln!("{missing} blobs are missing from the backup.");
+        errors += 1;
+    }
+
+    if errors > 0 {
+        failed(&format!("Backup verification failed with {errors} errors."));
+    }
+
+    println!(
+        "Backup {} is valid ({} backups, {} accounts).",
+        last_manifest.id,
+        chain.len(),
+        last_manifest.accounts.len()
+    );
+}
+
+// Returns the hashes of all blobs committed at the time of the backup
+pub(super) fn read_blob_manifest(
+    path: &Path,
+    manifest: &Manifest,
+    key: Option<&BackupKey>,
+) -> AHashSet<Vec<u8>> {
+    let file = path.join(BLOB_MANIFEST_FILE);
+    let mut reader = OpReader::open(&file, key);
+    let mut hashes = AHashSet::new();
+    while let Some(op) = reader.next() {
+        if let Op::KeyValue((hash, _)) = op {
+            hashes.insert(hash);
+        }
+    }
+    if manifest.files.get(BLOB_MANIFEST_FILE) != Some(&reader.finish()) {
+        failed(&format!("Checksum mismatch in {file:?}"));
+    }
+    hashes
 }
 
-async fn restore_file(store: Store, blob_store: BlobStore, path: &Path) {
+// Returns the hashes of the blobs linked to an account
+fn read_linked_blobs(path: &Path, account_id: u32, key: Option<&BackupKey>) -> AHashSet<Vec<u8>> {
+    let mut reader = OpReader::open(path, key);
+    let mut hashes = AHashSet::new();
+    let mut current_account_id = u32::MAX;
+    while let Some(op) = reader.next() {
+        match op {
+            Op::AccountId(account_id) => current_account_id = account_id,
+            Op::KeyValue((hash, _)) if current_account_id == account_id => {
+                hashes.insert(hash);
+            }
+            _ => {}
+        }
+    }
+    hashes
+}
+
+async fn restore_file(
+    store: Store,
+    blob_store: BlobStore,
+    path: &Path,
+    key: Option<BackupKey>,
+    filter: RestoreFilter,
+) {
     println!("Importing database dump from {}.", path.to_str().unwrap());
 
-    let mut reader = OpReader::new(path).await;
+    let mut reader = OpStream::spawn(path, key);
     let mut account_id = u32::MAX;
     let mut document_id = u32::MAX;
     let mut collection = u8::MAX;
     let mut family = Family::None;
     let email_collection = u8::from(Collection::Email);
     let mut seq = 0;
+    let mut needs_context = false;
 
     let mut batch_size = 0;
     let mut batch = BatchBuilder::new();
@@ -78,18 +356,28 @@ async fn restore_file(store: Store, blob_store: BlobStore, path: &Path) {
             Op::Family(f) => family = f,
             Op::AccountId(a) => {
                 account_id = a;
-                batch.with_account_id(account_id);
+                needs_context = true;
             }
             Op::Collection(c) => {
                 collection = c;
-                batch.with_collection(collection);
+                needs_context = true;
             }
             Op::DocumentId(d) => {
                 document_id = d;
-                batch.update_document(document_id);
+                needs_context = true;
+            }
+            Op::KeyValue((key, _)) if !filter.includes(family, account_id, document_id, &key) => {
+                continue;
             }
             Op::KeyValue((key, value)) => {
                 batch_size += key.len() + value.len() + U32_LEN * 2;
+                if needs_context {
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(collection)
+                        .update_document(document_id);
+                    needs_context = false;
+                }
 
                 match family {
                     Family::Property => {
@@ -177,66 +465,76 @@ async fn restore_file(store: Store, blob_store: BlobStore, path: &Path) {
                     }
                     Family::Directory => {
                         let key = key.as_slice();
-                        let class: DirectoryClass<MaybeDynamicId> =
-                            match key.first().expect("Failed to read directory key type") {
-                                0 => DirectoryClass::NameToId(
-                                    key.get(1..)
-                                        .expect("Failed to read directory string")
-                                        .to_vec(),
+                        let class: DirectoryClass<MaybeDynamicId> = match key
+                            .first()
+                            .expect("Failed to read directory key type")
+                        {
+                            0 => DirectoryClass::NameToId(
+                                key.get(1..)
+                                    .expect("Failed to read directory string")
+                                    .to_vec(),
+                            ),
+                            1 => DirectoryClass::EmailToId(
+                                key.get(1..)
+                                    .expect("Failed to read directory string")
+                                    .to_vec(),
+                            ),
+                            2 => DirectoryClass::Principal(MaybeDynamicId::Static(
+                                key.get(1..)
+                                    .expect("Failed to read range for principal id")
+                                    .deserialize_leb128::<u32>()
+                                    .expect("Failed to deserialize principal id"),
+                            )),
+                            3 => DirectoryClass::Domain(
+                                key.get(1..)
+                                    .expect("Failed to read directory string")
+                                    .to_vec(),
+                            ),
+                            4 => {
+                                let principal_id = key
+                                    .get(1..)
+                                    .expect("Failed to read principal id")
+                                    .deserialize_leb128()
+                                    .expect("Failed to read principal id");
+                                let mut quota =
+                                    i64::deserialize(&value).expect("Failed to deserialize quota");
+                                if !filter.include_global {
+                                    // Replace the quota used by the account being restored
+                                    quota -= store
+                                        .get_counter(DirectoryClass::UsedQuota(principal_id))
+                                        .await
+                                        .failed("Failed to obtain used quota");
+                                }
+                                batch.add(
+                                    ValueClass::Directory(DirectoryClass::UsedQuota(principal_id)),
+                                    quota,
+                                );
+
+                                continue;
+                            }
+                            5 => DirectoryClass::MemberOf {
+                                principal_id: MaybeDynamicId::Static(
+                                    key.deserialize_be_u32(1)
+                                        .expect("Failed to read principal id"),
                                 ),
-                                1 => DirectoryClass::EmailToId(
-                                    key.get(1..)
-                                        .expect("Failed to read directory string")
-                                        .to_vec(),
+                                member_of: MaybeDynamicId::Static(
+                                    key.deserialize_be_u32(1 + U32_LEN)
+                                        .expect("Failed to read principal id"),
                                 ),
-                                2 => DirectoryClass::Principal(MaybeDynamicId::Static(
-                                    key.get(1..)
-                                        .expect("Failed to read range for principal id")
-                                        .deserialize_leb128::<u32>()
-                                        .expect("Failed to deserialize principal id"),
-                                )),
-                                3 => DirectoryClass::Domain(
-                                    key.get(1..)
-                                        .expect("Failed to read directory string")
-                                        .to_vec(),
+                            },
+                            6 => DirectoryClass::Members {
+                                principal_id: MaybeDynamicId::Static(
+                                    key.deserialize_be_u32(1)
+                                        .expect("Failed to read principal id"),
                                 ),
-                                4 => {
-                                    batch.add(
-                                        ValueClass::Directory(DirectoryClass::UsedQuota(
-                                            key.get(1..)
-                                                .expect("Failed to read principal id")
-                                                .deserialize_leb128()
-                                                .expect("Failed to read principal id"),
-                                        )),
-                                        i64::deserialize(&value)
-                                            .expect("Failed to deserialize quota"),
-                                    );
-
-                                    continue;
-                                }
-                                5 => DirectoryClass::MemberOf {
-                                    principal_id: MaybeDynamicId::Static(
-                                        key.deserialize_be_u32(1)
-                                            .expect("Failed to read principal id"),
-                                    ),
-                                    member_of: MaybeDynamicId::Static(
-                                        key.deserialize_be_u32(1 + U32_LEN)
-                                            .expect("Failed to read principal id"),
-                                    ),
-                                },
-                                6 => DirectoryClass::Members {
-                                    principal_id: MaybeDynamicId::Static(
-                                        key.deserialize_be_u32(1)
-                                            .expect("Failed to read principal id"),
-                                    ),
-                                    has_member: MaybeDynamicId::Static(
-                                        key.deserialize_be_u32(1 + U32_LEN)
-                                            .expect("Failed to read principal id"),
-                                    ),
-                                },
+                                has_member: MaybeDynamicId::Static(
+                                    key.deserialize_be_u32(1 + U32_LEN)
+                                        .expect("Failed to read principal id"),
+                                ),
+                            },
 
-                                _ => failed("Invalid directory key"),
-                            };
+                            _ => failed("Invalid directory key"),
+                        };
                         batch.set(ValueClass::Directory(class), value);
                     }
                     Family::Queue => {
@@ -354,6 +652,7 @@ async fn restore_file(store: Store, blob_store: BlobStore, path: &Path) {
                             set: MaybeDynamicValue::Static(value),
                         });
                     }
+                    Family::BlobManifest => {}
                     Family::None => failed("No family specified in file"),
                 }
             }
@@ -379,78 +678,158 @@ async fn restore_file(store: Store, blob_store: BlobStore, path: &Path) {
             .await
             .failed("Failed to write batch");
     }
+
+    let digest = reader.finish().await;
+    if filter.digest.is_some_and(|expected| expected != digest) {
+        failed(&format!("Checksum mismatch in {path:?}"));
+    }
+}
+
+impl RestoreFilter {
+    fn includes(&self, family: Family, account_id: u32, document_id: u32, key: &[u8]) -> bool {
+        match family {
+            Family::Property
+            | Family::FtsIndex
+            | Family::Acl
+            | Family::Index
+            | Family::Bitmap
+            | Family::Log => self.includes_account(account_id),
+            Family::Blob if account_id != u32::MAX && document_id != u32::MAX => {
+                self.includes_account(account_id)
+            }
+            Family::Blob => self
+                .blobs
+                .as_ref()
+                .is_none_or(|blobs| blobs.contains(key)),
+            Family::Config | Family::LookupValue | Family::LookupCounter | Family::Queue => {
+                self.include_global
+            }
+            Family::Directory => {
+                self.include_global
+                    || (key.first() == Some(&4)
+                        && self.quota_account_id.is_some_and(|quota_account_id| {
+                            key.get(1..)
+                                .and_then(|bytes| bytes.deserialize_leb128::<u32>().ok())
+                                == Some(quota_account_id)
+                        }))
+            }
+            Family::BlobManifest => false,
+            Family::None => true,
+        }
+    }
+
+    fn includes_account(&self, account_id: u32) -> bool {
+        self.accounts
+            .as_ref()
+            .is_none_or(|accounts| accounts.contains(&account_id))
+    }
 }
 
 struct OpReader {
     version: u8,
-    file: BufReader<File>,
+    file: BufReader<ArchiveReader>,
 }
 
 impl OpReader {
-    async fn new(path: &Path) -> Self {
-        let mut file = BufReader::new(File::open(&path).await.failed("Failed to open file"));
-
-        if file
-            .read_u8()
-            .await
-            .failed(&format!("Failed to read magic marker from {path:?}"))
-            != MAGIC_MARKER
-        {
-            failed(&format!("Invalid magic marker in {path:?}"));
+    fn open(path: &Path, key: Option<&BackupKey>) -> Self {
+        let file = ArchiveReader::open(path, key);
+        Self {
+            version: file.version,
+            file: BufReader::new(file),
         }
+    }
 
-        let version = file
-            .read_u8()
-            .await
-            .failed(&format!("Failed to read version from {path:?}"));
+    fn next(&mut self) -> Option<Op> {
+        self.try_next()
+            .unwrap_or_else(|err| failed(&format!("Failed to read file: {err}")))
+    }
 
-        if version > FILE_VERSION {
-            failed(&format!("Invalid file version in {path:?}"));
+    fn try_next(&mut self) -> std::io::Result<Option<Op>> {
+        let mut byte = [0u8];
+        match self.file.read_exact(&mut byte) {
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
         }
 
-        Self { file, version }
+        Ok(Some(match byte[0] {
+            0 => Op::Family(
+                Family::try_from(self.read_u8()?)
+                    .map_err(|err| std::io::Error::new(ErrorKind::InvalidData, err))?,
+            ),
+            1 => Op::KeyValue((self.read_sized_bytes()?, self.read_sized_bytes()?)),
+            2 => Op::KeyValue((self.read_sized_bytes()?, vec![])),
+            3 => Op::AccountId(self.read_u32_be()?),
+            4 => Op::Collection(self.read_u8()?),
+            5 => Op::DocumentId(self.read_u32_be()?),
+            unknown => {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Unknown op type {unknown}"),
+                ));
+            }
+        }))
     }
 
-    async fn next(&mut self) -> Option<Op> {
-        match self.file.read_u8().await {
-            Ok(byte) => match byte {
-                0 => Op::Family(
-                    Family::try_from(self.expect_u8().await).failed("Failed to read family"),
-                ),
-                1 => Op::KeyValue((
-                    self.expect_sized_bytes().await,
-                    self.expect_sized_bytes().await,
-                )),
-                2 => Op::KeyValue((self.expect_sized_bytes().await, vec![])),
-                3 => Op::AccountId(self.expect_u32_be().await),
-                4 => Op::Collection(self.expect_u8().await),
-                5 => Op::DocumentId(self.expect_u32_be().await),
-                unknown => {
-                    failed(&format!("Unknown op type {unknown}"));
+    fn read_u8(&mut self) -> std::io::Result<u8> {
+        let mut bytes = [0u8];
+        self.file.read_exact(&mut bytes).map(|_| bytes[0])
+    }
+
+    fn read_u32_be(&mut self) -> std::io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.file
+            .read_exact(&mut bytes)
+            .map(|_| u32::from_be_bytes(bytes))
+    }
+
+    fn read_sized_bytes(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = vec![0; self.read_u32_be()? as usize];
+        self.file.read_exact(&mut bytes).map(|_| bytes)
+    }
+
+    fn finish(self) -> FileDigest {
+        self.file.into_inner().finish()
+    }
+}
+
+// Decrypts and decompresses a backup file in a separate thread
+struct OpStream {
+    version: u8,
+    rx: mpsc::Receiver<Op>,
+    handle: std::thread::JoinHandle<FileDigest>,
+}
+
+impl OpStream {
+    fn spawn(path: &Path, key: Option<BackupKey>) -> Self {
+        let mut reader = OpReader::open(path, key.as_ref());
+        let version = reader.version;
+        let (tx, rx) = mpsc::channel(1024);
+        let handle = std::thread::spawn(move || {
+            while let Some(op) = reader.next() {
+                if tx.blocking_send(op).is_err() {
+                    break;
                 }
             }
-            .into(),
-            Err(err) if err.kind() == ErrorKind::UnexpectedEof => None,
-            Err(err) => failed(&format!("Failed to read file: {err:?}")),
-        }
-    }
+            reader.finish()
+        });
 
-    async fn expect_u8(&mut self) -> u8 {
-        self.file.read_u8().await.failed("Failed to read u8")
+        Self {
+            version,
+            rx,
+            handle,
+        }
     }
 
-    async fn expect_u32_be(&mut self) -> u32 {
-        self.file.read_u32().await.failed("Failed to read u32")
+    async fn next(&mut self) -> Option<Op> {
+        self.rx.recv().await
     }
 
-    async fn expect_sized_bytes(&mut self) -> Vec<u8> {
-        let len = self.expect_u32_be().await as usize;
-        let mut bytes = vec![0; len];
-        self.file
-            .read_exact(&mut bytes)
+    async fn finish(self) -> FileDigest {
+        tokio::task::spawn_blocking(move || self.handle.join())
             .await
-            .failed("Failed to read bytes");
-        bytes
+            .failed("Failed to wait for reader")
+            .unwrap_or_else(|_| failed("Failed to read backup file"))
     }
 }
 
@@ -471,6 +850,7 @@ impl TryFrom<u8> for Family {
             9 => Ok(Self::Index),
             10 => Ok(Self::Bitmap),
             11 => Ok(Self::Log),
+            12 => Ok(Self::BlobManifest),
             other => Err(format!("Unknown family type {other}")),
         }
     }
@@ -0,0 +1,462 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use directory::{
+    core::secret::{ScramAlgorithm, ScramSecret},
+    Directory, Principal, QueryBy,
+};
+use rand::{distributions::Alphanumeric, Rng};
+
+use crate::{
+    config::server::ServerProtocol,
+    webhooks::{WebhookPayload, WebhookType},
+    AuthResult, Core, Ipc, RecordAuthMetrics,
+};
+
+// Only the tls-exporter channel binding type (RFC 9266) is supported
+const CHANNEL_BINDING_TYPE: &str = "tls-exporter";
+const SERVER_NONCE_LEN: usize = 24;
+
+// SCRAM exchange state (RFC 5802 and RFC 7677)
+pub struct ScramServer {
+    pub username: String,
+    pub authzid: Option<String>,
+    pub algorithm: ScramAlgorithm,
+    gs2_header: String,
+    client_first_bare: String,
+    server_first: String,
+    nonce: String,
+    channel_binding: Option<Vec<u8>>,
+    secret: ScramSecret,
+    principal: Option<Principal<u32>>,
+}
+
+pub struct ScramClientFirst {
+    pub username: String,
+    pub authzid: Option<String>,
+    gs2_header: String,
+    client_first_bare: String,
+    client_nonce: String,
+    channel_binding: Option<Vec<u8>>,
+}
+
+impl ScramClientFirst {
+    pub fn parse(
+        client_first: &[u8],
+        is_plus: bool,
+        channel_binding: Option<Vec<u8>>,
+    ) -> Option<Self> {
+        let client_first = std::str::from_utf8(client_first).ok()?;
+        let (cbind_flag, rest) = client_first.split_once(',')?;
+        let (authzid, client_first_bare) = rest.split_once(',')?;
+
+        // Validate the channel binding flag
+        let channel_binding = match cbind_flag {
+            "n" if !is_plus => None,
+            // The client believes the server does not support channel binding,
+            // which is a downgrade attack if it does
+            "y" if !is_plus && channel_binding.is_none() => None,
+            _ if is_plus && cbind_flag.strip_prefix("p=") == Some(CHANNEL_BINDING_TYPE) => {
+                Some(channel_binding?)
+            }
+            _ => return None,
+        };
+
+        let mut username = None;
+        let mut client_nonce = None;
+        for (pos, attribute) in client_first_bare.split(',').enumerate() {
+            match (pos, attribute.split_once('=')?) {
+                (0, ("n", value)) => {
+                    username = decode_sasl_name(value);
+                }
+                (1, ("r", value)) if !value.is_empty() => {
+                    client_nonce = value.to_string().into();
+                }
+                (0 | 1, _) => return None,
+                _ => {}
+            }
+        }
+        let username = username?;
+
+        // Authorization identity, used to log in as another account
+        let authzid_name = if let Some(authzid) = authzid.strip_prefix("a=") {
+            Some(decode_sasl_name(authzid)?).filter(|authzid| authzid != &username)
+        } else if authzid.is_empty() {
+            None
+        } else {
+            return None;
+        };
+
+        Some(ScramClientFirst {
+            username,
+            authzid: authzid_name,
+            gs2_header: format!("{cbind_flag},{authzid},"),
+            client_first_bare: client_first_bare.to_string(),
+            client_nonce: client_nonce?,
+            channel_binding,
+        })
+    }
+
+    pub fn into_server(
+        self,
+        algorithm: ScramAlgorithm,
+        secret: ScramSecret,
+        principal: Option<Principal<u32>>,
+        server_nonce: &str,
+    ) -> ScramServer {
+        let nonce = format!("{}{}", self.client_nonce, server_nonce);
+        let server_first = format!(
+            "r={},s={},i={}",
+            nonce,
+            STANDARD.encode(&secret.salt),
+            secret.iterations
+        );
+
+        ScramServer {
+            username: self.username,
+            authzid: self.authzid,
+            algorithm,
+            gs2_header: self.gs2_header,
+            client_first_bare: self.client_first_bare,
+            server_first,
+            nonce,
+            channel_binding: self.channel_binding,
+            secret,
+            principal,
+        }
+    }
+}
+
+impl ScramServer {
+    pub fn server_first(&self) -> &str {
+        &self.server_first
+    }
+
+    // Base64-encoded server-first message, as sent in SASL challenges
+    pub fn challenge(&self) -> String {
+        STANDARD.encode(&self.server_first)
+    }
+
+    // Verifies the client proof and returns the server-final message
+    pub fn verify(&self, client_final: &[u8]) -> Option<String> {
+        let client_final = std::str::from_utf8(client_final).ok()?;
+        let (client_final_without_proof, proof) = client_final.rsplit_once(",p=")?;
+        let mut attributes = client_final_without_proof.split(',');
+
+        // Validate channel binding
+        let mut cbind_input = self.gs2_header.as_bytes().to_vec();
+        if let Some(channel_binding) = &self.channel_binding {
+            cbind_input.extend_from_slice(channel_binding);
+        }
+        if attributes.next()?.strip_prefix("c=")? != STANDARD.encode(&cbind_input) {
+            return None;
+        }
+
+        // Validate nonce
+        if attributes.next()?.strip_prefix("r=")? != self.nonce {
+            return None;
+        }
+
+        // Validate proof
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, self.server_first, client_final_without_proof
+        );
+        if self
+            .secret
+            .verify_proof(auth_message.as_bytes(), &STANDARD.decode(proof).ok()?)
+        {
+            Some(format!(
+                "v={}",
+                STANDARD.encode(self.secret.server_signature(auth_message.as_bytes()))
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl Core {
+    pub async fn scram_begin(
+        &self,
+        directory: &Directory,
+        algorithm: ScramAlgorithm,
+        is_plus: bool,
+        client_first: &[u8],
+        channel_binding: Option<Vec<u8>>,
+        return_member_of: bool,
+    ) -> directory::Result<Option<ScramServer>> {
+        let client_first = if let Some(client_first) =
+            ScramClientFirst::parse(client_first, is_plus, channel_binding)
+        {
+            client_first
+        } else {
+            return Ok(None);
+        };
+
+        // Obtain the principal's SCRAM keys
+        let principal = match &self.jmap.fallback_admin {
+            Some((fallback_admin, fallback_pass)) if fallback_admin == &client_first.username => {
+                Principal::fallback_admin(fallback_pass).into()
+            }
+            _ => {
+                directory
+                    .query(
+                        QueryBy::Name(&client_first.username),
+                        return_member_of || client_first.authzid.is_some(),
+                    )
+                    .await?
+            }
+        };
+        let (secret, principal) = match principal
+            .as_ref()
+            .and_then(|principal| principal.scram_secret(algorithm))
+        {
+            Some(secret) => (secret, principal),
+            None => {
+                // Continue the exchange with a mock secret to avoid revealing
+                // whether the account exists
+                let mut salt = algorithm.hash(client_first.username.as_bytes());
+                salt.truncate(16);
+                (
+                    ScramSecret::derive(algorithm, &random_string(SERVER_NONCE_LEN), salt, 4096),
+                    None,
+                )
+            }
+        };
+
+        Ok(Some(client_first.into_server(
+            algorithm,
+            secret,
+            principal,
+            &random_string(SERVER_NONCE_LEN),
+        )))
+    }
+
+    // Returns the principal and the base64-encoded server-final message
+    #[allow(clippy::too_many_arguments)]
+    pub async fn scram_finish(
+        &self,
+        directory: &Directory,
+        ipc: &Ipc,
+        server: ScramServer,
+        client_final: &[u8],
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        return_member_of: bool,
+    ) -> directory::Result<AuthResult<(Principal<u32>, String)>> {
+        let result = self
+            .scram_verify(
+                directory,
+                ipc,
+                server,
+                client_final,
+                remote_ip,
+                protocol,
+                return_member_of,
+            )
+            .await;
+        result.record_metrics(protocol);
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn scram_verify(
+        &self,
+        directory: &Directory,
+        ipc: &Ipc,
+        server: ScramServer,
+        client_final: &[u8],
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        return_member_of: bool,
+    ) -> directory::Result<AuthResult<(Principal<u32>, String)>> {
+        if let Some(server_final) = server.verify(client_final) {
+            match (
+                server.principal,
+                server.authzid,
+                &self.jmap.impersonation_group,
+            ) {
+                (Some(principal), Some(authzid), Some(group)) => {
+                    return self
+                        .impersonate(
+                            directory,
+                            ipc,
+                            group,
+                            principal,
+                            &authzid,
+                            remote_ip,
+                            protocol,
+                            return_member_of,
+                        )
+                        .await
+                        .map(|result| match result {
+                            AuthResult::Success(principal) => {
+                                AuthResult::Success((principal, STANDARD.encode(server_final)))
+                            }
+                            AuthResult::Failure(reason) => AuthResult::Failure(reason),
+                        });
+                }
+                (Some(principal), None, _) => {
+                    // Send webhook event
+                    if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
+                        ipc.send_webhook(
+                            WebhookType::AuthSuccess,
+                            WebhookPayload::Authentication {
+                                login: server.username,
+                                protocol,
+                                remote_ip,
+                                typ: principal.typ.into(),
+                                as_master: None,
+                                master_login: None,
+                            },
+                        )
+                        .await;
+                    }
+
+                    return Ok(AuthResult::Success((
+                        principal,
+                        STANDARD.encode(server_final),
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        self.authentication_failed(ipc, &server.username, remote_ip, protocol, Ok(()))
+            .await
+    }
+}
+
+fn decode_sasl_name(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '=' {
+            match (chars.next(), chars.next()) {
+                (Some('2'), Some('C')) => result.push(','),
+                (Some('3'), Some('D')) => result.push('='),
+                _ => return None,
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+
+    if !result.is_empty() {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use directory::core::secret::{ScramAlgorithm, ScramSecret};
+
+    use super::ScramClientFirst;
+
+    #[test]
+    fn scram_exchange() {
+        for (algorithm, salt, client_nonce, server_nonce, proof, signature) in [
+            // RFC 5802
+            (
+                ScramAlgorithm::Sha1,
+                "QSXCR+Q6sek8bf92",
+                "fyko+d2lbbFgONRv9qkxdawL",
+                "3rfcNHYJY1ZVvWVs7j",
+                "v0X8v3Bz2T0CJGbJQyF0X+HI4Ts=",
+                "rmF9pqV8S7suAoZWja4dJRkFsKQ=",
+            ),
+            // RFC 7677
+            (
+                ScramAlgorithm::Sha256,
+                "W22ZaJ0SNY7soEsUEjb6gQ==",
+                "rOprNGfwEbeRWgbNEkqO",
+                "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0",
+                "dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=",
+                "6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=",
+            ),
+        ] {
+            let secret =
+                ScramSecret::derive(algorithm, "pencil", STANDARD.decode(salt).unwrap(), 4096);
+            assert!(secret.verify_password("pencil"));
+            assert!(!secret.verify_password("pencil2"));
+            assert_eq!(
+                ScramSecret::parse(&secret.to_string()),
+                Some(secret.clone())
+            );
+
+            let server = ScramClientFirst::parse(
+                format!("n,,n=user,r={client_nonce}").as_bytes(),
+                false,
+                None,
+            )
+            .unwrap()
+            .into_server(algorithm, secret, None, server_nonce);
+            assert_eq!(
+                server.server_first(),
+                format!("r={client_nonce}{server_nonce},s={salt},i=4096")
+            );
+
+            // Valid proof
+            assert_eq!(
+                server
+                    .verify(format!("c=biws,r={client_nonce}{server_nonce},p={proof}").as_bytes())
+                    .unwrap(),
+                format!("v={signature}")
+            );
+
+            // Invalid nonce and channel binding
+            assert_eq!(
+                server.verify(format!("c=biws,r={client_nonce},p={proof}").as_bytes()),
+                None
+            );
+            assert_eq!(
+                server
+                    .verify(format!("c=eSws,r={client_nonce}{server_nonce},p={proof}").as_bytes()),
+                None
+            );
+        }
+
+        // Channel binding negotiation
+        for (client_first, is_plus, channel_binding, expected) in [
+            ("n,,n=user,r=abc", false, None, true),
+            ("n,,n=user,r=abc", false, Some(vec![1u8]), true),
+            ("y,,n=user,r=abc", false, None, true),
+            ("y,,n=user,r=abc", false, Some(vec![1u8]), false),
+            ("p=tls-exporter,,n=user,r=abc", true, Some(vec![1u8]), true),
+            ("p=tls-exporter,,n=user,r=abc", true, None, false),
+            ("p=tls-unique,,n=user,r=abc", true, Some(vec![1u8]), false),
+            ("n,,n=user,r=abc", true, Some(vec![1u8]), false),
+            ("n,a=user,n=user,r=abc", false, None, true),
+            ("n,a=admin,n=user,r=abc", false, None, true),
+            ("n,b=admin,n=user,r=abc", false, None, false),
+            ("n,,n=us=3Der,r=abc", false, None, true),
+            ("n,,n=us=er,r=abc", false, None, false),
+            ("n,,r=abc,n=user", false, None, false),
+        ] {
+            assert_eq!(
+                ScramClientFirst::parse(client_first.as_bytes(), is_plus, channel_binding)
+                    .is_some(),
+                expected,
+                "{client_first}"
+            );
+        }
+    }
+}
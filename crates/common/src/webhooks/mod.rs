@@ -99,6 +99,9 @@ pub enum WebhookPayload {
         #[serde(rename = "isMasterLogin")]
         #[serde(skip_serializing_if = "Option::is_none")]
         as_master: Option<bool>,
+        #[serde(rename = "masterLogin")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        master_login: Option<String>,
     },
     Error {
         message: String,
@@ -13,7 +13,13 @@ use store::{
     Deserialize, IterateParams, Serialize, Store, ValueKey, U32_LEN,
 };
 
-use crate::{DirectoryError, ManagementError, Principal, QueryBy, Type};
+use crate::{
+    core::{
+        role::Role,
+        secret::{cleartext_secret, scram_secrets, ScramSecret},
+    },
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
 
 use super::{
     lookup::DirectoryStore, PrincipalAction, PrincipalField, PrincipalIdType, PrincipalUpdate,
@@ -169,6 +175,18 @@ impl ManageDirectory for Store {
             }));
         }
 
+        // Store salted SCRAM keys rather than cleartext passwords
+        principal.secrets = std::mem::take(&mut principal.secrets)
+            .into_iter()
+            .flat_map(scram_secrets)
+            .collect();
+
+        // Validate roles
+        principal.roles = std::mem::take(&mut principal.roles)
+            .into_iter()
+            .map(validate_role)
+            .collect::<crate::Result<Vec<_>>>()?;
+
         // Make sure the e-mail is not taken and validate domain
         for email in principal.emails.iter_mut() {
             *email = email.to_lowercase();
@@ -412,7 +430,7 @@ impl ManageDirectory for Store {
                     PrincipalField::Secrets,
                     PrincipalValue::StringList(secrets),
                 ) => {
-                    principal.inner.secrets = secrets;
+                    principal.inner.secrets = secrets.into_iter().flat_map(scram_secrets).collect();
                 }
                 (
                     PrincipalAction::AddItem,
@@ -424,7 +442,7 @@ impl ManageDirectory for Store {
                             // Add OTP Auth URLs to the beginning of the list
                             principal.inner.secrets.insert(0, secret);
                         } else {
-                            principal.inner.secrets.push(secret);
+                            principal.inner.secrets.extend(scram_secrets(secret));
                         }
                     }
                 }
@@ -439,7 +457,15 @@ impl ManageDirectory for Store {
                             .secrets
                             .retain(|v| *v != secret && !v.starts_with(&secret));
                     } else if !secret.is_empty() {
-                        principal.inner.secrets.retain(|v| *v != secret);
+                        // Cleartext passwords are stored as SCRAM keys
+                        let password = cleartext_secret(&secret);
+                        principal.inner.secrets.retain(|v| {
+                            *v != secret
+                                && !password.is_some_and(|password| {
+                                    ScramSecret::parse(v)
+                                        .is_some_and(|scram| scram.verify_password(password))
+                                })
+                        });
                     } else {
                         principal.inner.secrets.retain(|v| !v.is_password());
                     }
@@ -734,6 +760,32 @@ impl ManageDirectory for Store {
                     }
                 }
 
+                // Roles
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::Roles,
+                    PrincipalValue::StringList(roles),
+                ) => {
+                    principal.inner.roles = roles
+                        .into_iter()
+                        .map(validate_role)
+                        .collect::<crate::Result<Vec<_>>>()?;
+                }
+                (PrincipalAction::AddItem, PrincipalField::Roles, PrincipalValue::String(role)) => {
+                    let role = validate_role(role)?;
+                    if !principal.inner.roles.contains(&role) {
+                        principal.inner.roles.push(role);
+                    }
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::Roles,
+                    PrincipalValue::String(role),
+                ) => {
+                    let role = role.to_lowercase();
+                    principal.inner.roles.retain(|v| *v != role);
+                }
+
                 _ => {
                     return Err(DirectoryError::Unsupported);
                 }
@@ -797,6 +849,7 @@ impl ManageDirectory for Store {
             emails: principal.emails,
             member_of: Vec::with_capacity(principal.member_of.len()),
             description: principal.description,
+            roles: principal.roles,
         };
 
         for account_id in principal.member_of {
@@ -824,6 +877,7 @@ impl ManageDirectory for Store {
                 .map_group_names(principal.member_of, create_if_missing)
                 .await?,
             description: principal.description,
+            roles: principal.roles,
         })
     }
 
@@ -1012,6 +1066,12 @@ impl From<DynamicPrincipalIdType> for MaybeDynamicValue {
     }
 }
 
+fn validate_role(role: String) -> crate::Result<String> {
+    Role::parse(&role.to_lowercase())
+        .map(|role| role.to_string())
+        .ok_or(DirectoryError::Management(ManagementError::NotFound(role)))
+}
+
 impl From<Principal<String>> for Principal<u32> {
     fn from(principal: Principal<String>) -> Self {
         Principal {
@@ -1023,6 +1083,7 @@ impl From<Principal<String>> for Principal<u32> {
             emails: principal.emails,
             member_of: Vec::with_capacity(0),
             description: principal.description,
+            roles: principal.roles,
         }
     }
 }
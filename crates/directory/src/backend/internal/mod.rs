@@ -33,6 +33,7 @@ impl Serialize for &Principal<u32> {
                 + self.name.len()
                 + self.emails.iter().map(|s| s.len()).sum::<usize>()
                 + self.secrets.iter().map(|s| s.len()).sum::<usize>()
+                + self.roles.iter().map(|s| s.len()).sum::<usize>()
                 + self.description.as_ref().map(|s| s.len()).unwrap_or(0),
         )
         .write(1u8)
@@ -44,7 +45,7 @@ impl Serialize for &Principal<u32> {
         .write_leb128(self.description.as_ref().map_or(0, |s| s.len()))
         .write(self.description.as_deref().unwrap_or_default().as_bytes());
 
-        for list in [&self.secrets, &self.emails] {
+        for list in [&self.secrets, &self.emails, &self.roles] {
             serializer = serializer.write_leb128(list.len());
             for value in list {
                 serializer = serializer.write_leb128(value.len()).write(value.as_bytes());
@@ -112,6 +113,8 @@ fn deserialize(bytes: &[u8]) -> Option<Principal<u32>> {
         secrets: deserialize_string_list(&mut bytes)?,
         emails: deserialize_string_list(&mut bytes)?,
         member_of: Vec::new(),
+        // Principals written by previous versions have no roles
+        roles: deserialize_string_list(&mut bytes).unwrap_or_default(),
     }
     .into()
 }
@@ -134,6 +137,8 @@ pub enum PrincipalField {
     MemberOf,
     #[serde(rename = "members")]
     Members,
+    #[serde(rename = "roles")]
+    Roles,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -198,6 +203,7 @@ impl Display for PrincipalField {
             PrincipalField::Emails => write!(f, "emails"),
             PrincipalField::MemberOf => write!(f, "memberOf"),
             PrincipalField::Members => write!(f, "members"),
+            PrincipalField::Roles => write!(f, "roles"),
         }
     }
 }
@@ -135,6 +135,10 @@ impl MemoryDirectory {
                 member_of,
                 id,
                 emails,
+                roles: config
+                    .values((prefix.as_str(), "principals", lookup_id, "role"))
+                    .map(|(_, v)| v.to_string())
+                    .collect(),
             });
         }
 
@@ -8,5 +8,6 @@ pub mod imap;
 pub mod internal;
 pub mod ldap;
 pub mod memory;
+pub mod oidc;
 pub mod smtp;
 pub mod sql;
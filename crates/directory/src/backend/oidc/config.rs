@@ -0,0 +1,122 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use store::Store;
+use utils::config::{utils::AsKey, Config};
+
+use super::{KeySet, OpenIdClaims, OpenIdDirectory, TokenValidation};
+
+impl OpenIdDirectory {
+    pub fn from_config(config: &mut Config, prefix: impl AsKey, data_store: Store) -> Option<Self> {
+        let prefix = prefix.as_key();
+        let url = config
+            .value_require((&prefix, "validation.url"))?
+            .to_string();
+        let validation = match config
+            .value((&prefix, "validation.method"))
+            .unwrap_or("jwks")
+        {
+            "jwks" => TokenValidation::Jwks {
+                url,
+                refresh: config
+                    .property_or_default((&prefix, "validation.refresh"), "1h")
+                    .unwrap_or_else(|| Duration::from_secs(3600)),
+            },
+            "introspect" => TokenValidation::Introspect {
+                url,
+                auth: config
+                    .value((&prefix, "validation.auth.username"))
+                    .map(|v| v.to_string())
+                    .zip(
+                        config
+                            .value((&prefix, "validation.auth.secret"))
+                            .map(|v| v.to_string()),
+                    ),
+            },
+            other => {
+                let err = format!("Invalid token validation method: {other:?}");
+                config.new_parse_error((&prefix, "validation.method"), err);
+                return None;
+            }
+        };
+
+        // Signed tokens are only accepted from the configured issuer and audience
+        let (issuer, audience) = if matches!(validation, TokenValidation::Jwks { .. }) {
+            (
+                config
+                    .value_require((&prefix, "validation.issuer"))?
+                    .to_string()
+                    .into(),
+                config
+                    .value_require((&prefix, "validation.audience"))?
+                    .to_string()
+                    .into(),
+            )
+        } else {
+            (
+                config
+                    .value((&prefix, "validation.issuer"))
+                    .map(|v| v.to_string()),
+                config
+                    .value((&prefix, "validation.audience"))
+                    .map(|v| v.to_string()),
+            )
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(
+                config
+                    .property_or_default((&prefix, "timeout"), "15s")
+                    .unwrap_or_else(|| Duration::from_secs(15)),
+            )
+            .danger_accept_invalid_certs(
+                config
+                    .property_or_default((&prefix, "tls.allow-invalid-certs"), "false")
+                    .unwrap_or_default(),
+            )
+            .build()
+            .map_err(|err| {
+                config.new_build_error(
+                    prefix.as_str(),
+                    format!("Failed to build HTTP client: {err}"),
+                )
+            })
+            .ok()?;
+
+        Some(OpenIdDirectory {
+            client,
+            validation,
+            issuer,
+            audience,
+            claims: OpenIdClaims {
+                username: config
+                    .value((&prefix, "claims.username"))
+                    .unwrap_or("preferred_username")
+                    .to_string(),
+                email: config
+                    .value((&prefix, "claims.email"))
+                    .unwrap_or("email")
+                    .to_string(),
+                name: config
+                    .value((&prefix, "claims.name"))
+                    .unwrap_or("name")
+                    .to_string(),
+                groups: config
+                    .value((&prefix, "claims.groups"))
+                    .unwrap_or("groups")
+                    .to_string(),
+            },
+            provision: config
+                .property_or_default((&prefix, "provision"), "false")
+                .unwrap_or_default(),
+            keys: RwLock::new(KeySet::default()),
+            data_store,
+        })
+    }
+}
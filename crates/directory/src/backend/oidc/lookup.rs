@@ -0,0 +1,184 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use mail_send::Credentials;
+use serde_json::Value;
+
+use crate::{
+    backend::internal::{lookup::DirectoryStore, manage::ManageDirectory, PrincipalField},
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
+
+use super::{token::Claims, OpenIdDirectory};
+
+impl OpenIdDirectory {
+    pub async fn query(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
+    ) -> crate::Result<Option<Principal<u32>>> {
+        // Tokens are validated by the identity provider, everything else
+        // is handled by the internal directory
+        match by {
+            QueryBy::Credentials(Credentials::OAuthBearer { token }) => {
+                self.authenticate(token, None, return_member_of).await
+            }
+            QueryBy::Credentials(Credentials::XOauth2 { username, secret }) => {
+                self.authenticate(secret, Some(username), return_member_of)
+                    .await
+            }
+            by => self.data_store.query(by, return_member_of).await,
+        }
+    }
+
+    async fn authenticate(
+        &self,
+        token: &str,
+        username: Option<&str>,
+        return_member_of: bool,
+    ) -> crate::Result<Option<Principal<u32>>> {
+        let Some(claims) = self.validate_token(token).await? else {
+            return Ok(None);
+        };
+
+        // Map claim to account name
+        let Some(account_name) = claims
+            .get(&self.claims.username)
+            .and_then(Value::as_str)
+            .map(|name| name.to_lowercase())
+        else {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                claim = self.claims.username,
+                "Token does not contain the username claim"
+            );
+            return Ok(None);
+        };
+        if username.is_some_and(|username| !username.eq_ignore_ascii_case(&account_name)) {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                account = account_name,
+                "Token was issued to a different account"
+            );
+            return Ok(None);
+        }
+
+        if let Some(principal) = self
+            .data_store
+            .query(QueryBy::Name(&account_name), return_member_of)
+            .await?
+        {
+            Ok(Some(principal))
+        } else if self.provision {
+            self.provision_account(&account_name, &claims).await?;
+            self.data_store
+                .query(QueryBy::Name(&account_name), return_member_of)
+                .await
+        } else {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                account = account_name,
+                "Account does not exist"
+            );
+            Ok(None)
+        }
+    }
+
+    async fn provision_account(&self, account_name: &str, claims: &Claims) -> crate::Result<()> {
+        // Only addresses on local domains can be added
+        let mut emails = Vec::new();
+        if let Some(email) = claims
+            .get(&self.claims.email)
+            .and_then(Value::as_str)
+            .map(|email| email.to_lowercase())
+        {
+            if let Some((_, domain)) = email.rsplit_once('@') {
+                if self.data_store.is_local_domain(domain).await? {
+                    emails.push(email);
+                }
+            }
+        }
+
+        // Only existing groups can be joined
+        let mut member_of = Vec::new();
+        if let Some(groups) = claims.get(&self.claims.groups).and_then(Value::as_array) {
+            for group in groups.iter().filter_map(Value::as_str) {
+                let group = group.trim_start_matches('/').to_lowercase();
+                if self
+                    .data_store
+                    .query(QueryBy::Name(&group), false)
+                    .await?
+                    .is_some_and(|principal| principal.typ == Type::Group)
+                {
+                    member_of.push(group);
+                }
+            }
+        }
+
+        match self
+            .data_store
+            .create_account(
+                Principal {
+                    typ: Type::Individual,
+                    name: account_name.to_string(),
+                    description: claims
+                        .get(&self.claims.name)
+                        .and_then(Value::as_str)
+                        .map(|name| name.to_string()),
+                    emails,
+                    member_of,
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await
+        {
+            Ok(account_id) => {
+                tracing::info!(
+                    context = "directory",
+                    event = "provision",
+                    protocol = "oidc",
+                    account = account_name,
+                    account_id = account_id,
+                    "Provisioned account on first login"
+                );
+                Ok(())
+            }
+            // The account was created by a concurrent login
+            Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                field: PrincipalField::Name,
+                ..
+            })) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn email_to_ids(&self, address: &str) -> crate::Result<Vec<u32>> {
+        self.data_store.email_to_ids(address).await
+    }
+
+    pub async fn rcpt(&self, address: &str) -> crate::Result<bool> {
+        self.data_store.rcpt(address).await
+    }
+
+    pub async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
+        self.data_store.vrfy(address).await
+    }
+
+    pub async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
+        self.data_store.expn(address).await
+    }
+
+    pub async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
+        self.data_store.is_local_domain(domain).await
+    }
+}
@@ -0,0 +1,57 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+pub mod config;
+pub mod lookup;
+pub mod token;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+use parking_lot::RwLock;
+use rsa::RsaPublicKey;
+use store::Store;
+
+pub struct OpenIdDirectory {
+    client: reqwest::Client,
+    validation: TokenValidation,
+    issuer: Option<String>,
+    audience: Option<String>,
+    claims: OpenIdClaims,
+    provision: bool,
+    keys: RwLock<KeySet>,
+    pub(crate) data_store: Store,
+}
+
+#[derive(Debug)]
+enum TokenValidation {
+    Jwks {
+        url: String,
+        refresh: Duration,
+    },
+    Introspect {
+        url: String,
+        auth: Option<(String, String)>,
+    },
+}
+
+#[derive(Debug)]
+struct OpenIdClaims {
+    username: String,
+    email: String,
+    name: String,
+    groups: String,
+}
+
+#[derive(Default, Clone)]
+struct KeySet {
+    keys: Arc<AHashMap<String, RsaPublicKey>>,
+    fetched_at: Option<Instant>,
+    failed_at: Option<Instant>,
+}
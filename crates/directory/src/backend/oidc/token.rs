@@ -0,0 +1,310 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use ahash::AHashMap;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::DirectoryError;
+
+use super::{KeySet, OpenIdDirectory, TokenValidation};
+
+pub type Claims = Map<String, Value>;
+
+// Minimum time between key set refreshes triggered by unknown key ids
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default, rename = "use")]
+    use_: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+}
+
+impl OpenIdDirectory {
+    // Returns the token claims if the token is valid
+    pub async fn validate_token(&self, token: &str) -> crate::Result<Option<Claims>> {
+        let claims = match &self.validation {
+            TokenValidation::Jwks { url, refresh } => {
+                self.validate_jwt(token, url, *refresh).await?
+            }
+            TokenValidation::Introspect { url, auth } => {
+                self.introspect_token(token, url, auth.as_ref()).await?
+            }
+        };
+
+        Ok(claims.filter(|claims| self.verify_claims(claims)))
+    }
+
+    async fn validate_jwt(
+        &self,
+        token: &str,
+        url: &str,
+        refresh: Duration,
+    ) -> crate::Result<Option<Claims>> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Ok(None);
+        };
+        let signed = &token[..header.len() + payload.len() + 1];
+        let Some((header, signature)) = decode_json::<JwtHeader>(header)
+            .zip(URL_SAFE_NO_PAD.decode(signature.trim_end_matches('=')).ok())
+        else {
+            return Ok(None);
+        };
+
+        // Hash the signed contents
+        let (scheme, hash) = match header.alg.as_str() {
+            "RS256" => (
+                Pkcs1v15Sign::new::<Sha256>(),
+                Sha256::digest(signed).to_vec(),
+            ),
+            "RS384" => (
+                Pkcs1v15Sign::new::<Sha384>(),
+                Sha384::digest(signed).to_vec(),
+            ),
+            "RS512" => (
+                Pkcs1v15Sign::new::<Sha512>(),
+                Sha512::digest(signed).to_vec(),
+            ),
+            alg => {
+                tracing::debug!(
+                    context = "directory",
+                    event = "invalid_token",
+                    protocol = "oidc",
+                    alg = alg,
+                    "Unsupported token signature algorithm"
+                );
+                return Ok(None);
+            }
+        };
+
+        // Verify signature
+        let keys = self.key_set(url, refresh, header.kid.as_deref()).await?;
+        let is_valid = match &header.kid {
+            Some(kid) => keys
+                .get(kid)
+                .is_some_and(|key| key.verify(scheme, &hash, &signature).is_ok()),
+            None => keys
+                .values()
+                .any(|key| key.verify(scheme.clone(), &hash, &signature).is_ok()),
+        };
+
+        if is_valid {
+            Ok(decode_json::<Claims>(payload))
+        } else {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                "Invalid token signature"
+            );
+            Ok(None)
+        }
+    }
+
+    async fn introspect_token(
+        &self,
+        token: &str,
+        url: &str,
+        auth: Option<&(String, String)>,
+    ) -> crate::Result<Option<Claims>> {
+        let mut request = self
+            .client
+            .post(url)
+            .form(&[("token", token), ("token_type_hint", "access_token")]);
+        if let Some((username, secret)) = auth {
+            request = request.basic_auth(username, Some(secret));
+        }
+        let response = request.send().await?.error_for_status()?.bytes().await?;
+        let claims = serde_json::from_slice::<Claims>(&response).map_err(|err| {
+            DirectoryError::Oidc(format!("Failed to parse introspection response: {err}"))
+        })?;
+
+        Ok(Some(claims).filter(|claims| claims.get("active") == Some(&Value::Bool(true))))
+    }
+
+    fn verify_claims(&self, claims: &Claims) -> bool {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let is_expired = match claims.get("exp").and_then(Value::as_u64) {
+            Some(exp) => exp <= now,
+            // Signed tokens without an expiration date are not accepted
+            None => matches!(self.validation, TokenValidation::Jwks { .. }),
+        };
+
+        if is_expired {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                "Token expired"
+            );
+            false
+        } else if claims
+            .get("nbf")
+            .and_then(Value::as_u64)
+            .is_some_and(|nbf| nbf > now)
+        {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                "Token not yet valid"
+            );
+            false
+        } else if self.issuer.as_ref().is_some_and(|issuer| {
+            claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str())
+        }) {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                "Invalid token issuer"
+            );
+            false
+        } else if self
+            .audience
+            .as_ref()
+            .is_some_and(|audience| !match claims.get("aud") {
+                Some(Value::String(aud)) => aud == audience,
+                Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+                _ => false,
+            })
+        {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid_token",
+                protocol = "oidc",
+                "Invalid token audience"
+            );
+            false
+        } else {
+            true
+        }
+    }
+
+    async fn key_set(
+        &self,
+        url: &str,
+        refresh: Duration,
+        kid: Option<&str>,
+    ) -> crate::Result<Arc<AHashMap<String, RsaPublicKey>>> {
+        // Refresh the key set when it expires or when an unknown key id is seen,
+        // unless a fetch failed recently
+        let key_set = self.keys.read().clone();
+        let needs_refresh = match key_set.fetched_at {
+            Some(fetched_at) => {
+                fetched_at.elapsed() >= refresh
+                    || (kid.is_some_and(|kid| !key_set.keys.contains_key(kid))
+                        && fetched_at.elapsed() >= MIN_REFRESH_INTERVAL)
+            }
+            None => true,
+        };
+        if !needs_refresh
+            || key_set
+                .failed_at
+                .is_some_and(|failed_at| failed_at.elapsed() < MIN_REFRESH_INTERVAL)
+        {
+            return Ok(key_set.keys);
+        }
+
+        match self.fetch_key_set(url).await {
+            Ok(keys) => {
+                let keys = Arc::new(keys);
+                *self.keys.write() = KeySet {
+                    keys: keys.clone(),
+                    fetched_at: Some(Instant::now()),
+                    failed_at: None,
+                };
+
+                Ok(keys)
+            }
+            Err(err) => {
+                // Back off before retrying a failed fetch
+                self.keys.write().failed_at = Some(Instant::now());
+
+                Err(err)
+            }
+        }
+    }
+
+    async fn fetch_key_set(&self, url: &str) -> crate::Result<AHashMap<String, RsaPublicKey>> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let jwks = serde_json::from_slice::<JwkSet>(&response)
+            .map_err(|err| DirectoryError::Oidc(format!("Failed to parse key set: {err}")))?;
+
+        let mut keys = AHashMap::with_capacity(jwks.keys.len());
+        for (pos, jwk) in jwks.keys.into_iter().enumerate() {
+            if jwk.kty != "RSA" || jwk.use_.as_deref().is_some_and(|u| u != "sig") {
+                continue;
+            }
+            if let Some(key) = jwk
+                .n
+                .as_deref()
+                .and_then(decode_uint)
+                .zip(jwk.e.as_deref().and_then(decode_uint))
+                .and_then(|(n, e)| RsaPublicKey::new(n, e).ok())
+            {
+                keys.insert(jwk.kid.unwrap_or_else(|| pos.to_string()), key);
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+fn decode_json<T: serde::de::DeserializeOwned>(value: &str) -> Option<T> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+}
+
+fn decode_uint(value: &str) -> Option<BigUint> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .ok()
+        .map(|bytes| BigUint::from_bytes_be(&bytes))
+}
@@ -21,6 +21,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.query(by).await,
             DirectoryInner::Smtp(store) => store.query(by).await,
             DirectoryInner::Memory(store) => store.query(by).await,
+            DirectoryInner::Oidc(store) => store.query(by, return_member_of).await,
         }
     }
 
@@ -32,6 +33,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.email_to_ids(email).await,
             DirectoryInner::Smtp(store) => store.email_to_ids(email).await,
             DirectoryInner::Memory(store) => store.email_to_ids(email).await,
+            DirectoryInner::Oidc(store) => store.email_to_ids(email).await,
         }
     }
 
@@ -50,6 +52,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.is_local_domain(domain).await,
             DirectoryInner::Smtp(store) => store.is_local_domain(domain).await,
             DirectoryInner::Memory(store) => store.is_local_domain(domain).await,
+            DirectoryInner::Oidc(store) => store.is_local_domain(domain).await,
         }?;
 
         // Update cache
@@ -75,6 +78,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.rcpt(email).await,
             DirectoryInner::Smtp(store) => store.rcpt(email).await,
             DirectoryInner::Memory(store) => store.rcpt(email).await,
+            DirectoryInner::Oidc(store) => store.rcpt(email).await,
         }?;
 
         // Update cache
@@ -93,6 +97,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.vrfy(address).await,
             DirectoryInner::Smtp(store) => store.vrfy(address).await,
             DirectoryInner::Memory(store) => store.vrfy(address).await,
+            DirectoryInner::Oidc(store) => store.vrfy(address).await,
         }
     }
 
@@ -104,6 +109,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.expn(address).await,
             DirectoryInner::Smtp(store) => store.expn(address).await,
             DirectoryInner::Memory(store) => store.expn(address).await,
+            DirectoryInner::Oidc(store) => store.expn(address).await,
         }
     }
 }
@@ -0,0 +1,147 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::fmt::Display;
+
+use crate::Principal;
+
+// Administrative roles are stored in the principal as "<role>" or
+// "<role>:<domain>", the latter limiting the role to a single domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Role {
+    pub kind: RoleKind,
+    pub domain: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleKind {
+    DomainAdmin,
+    Helpdesk,
+    Auditor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    PrincipalView,
+    PrincipalManage,
+    PasswordReset,
+    DomainView,
+    QueueView,
+    ReportView,
+    SettingsView,
+    LogsView,
+    AuditView,
+}
+
+impl Role {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (kind, domain) = match value.split_once(':') {
+            Some((kind, domain)) if domain.contains('.') => {
+                (kind, Some(domain.trim().to_lowercase()))
+            }
+            Some(_) => return None,
+            None => (value, None),
+        };
+
+        Some(Role {
+            kind: RoleKind::parse(kind.trim())?,
+            domain,
+        })
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        // Domain scoped roles only grant permissions over principals and domains
+        self.kind.permissions().contains(&permission)
+            && (self.domain.is_none() || permission.is_domain_scoped())
+    }
+}
+
+impl RoleKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "domain-admin" => Some(RoleKind::DomainAdmin),
+            "helpdesk" => Some(RoleKind::Helpdesk),
+            "auditor" => Some(RoleKind::Auditor),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoleKind::DomainAdmin => "domain-admin",
+            RoleKind::Helpdesk => "helpdesk",
+            RoleKind::Auditor => "auditor",
+        }
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            RoleKind::DomainAdmin => &[
+                Permission::PrincipalView,
+                Permission::PrincipalManage,
+                Permission::PasswordReset,
+                Permission::DomainView,
+            ],
+            RoleKind::Helpdesk => &[
+                Permission::PrincipalView,
+                Permission::PasswordReset,
+                Permission::QueueView,
+            ],
+            RoleKind::Auditor => &[
+                Permission::PrincipalView,
+                Permission::DomainView,
+                Permission::QueueView,
+                Permission::ReportView,
+                Permission::SettingsView,
+                Permission::LogsView,
+                Permission::AuditView,
+            ],
+        }
+    }
+}
+
+impl Permission {
+    pub fn is_domain_scoped(&self) -> bool {
+        matches!(
+            self,
+            Permission::PrincipalView
+                | Permission::PrincipalManage
+                | Permission::PasswordReset
+                | Permission::DomainView
+        )
+    }
+}
+
+impl<T> Principal<T> {
+    pub fn roles(&self) -> impl Iterator<Item = Role> + '_ {
+        self.roles.iter().filter_map(|role| Role::parse(role))
+    }
+
+    // Returns true if the principal's name and addresses all belong to one of the domains
+    pub fn is_in_domains<'x>(&self, domains: impl Iterator<Item = &'x str> + Clone) -> bool {
+        let in_domains = |address: &str| {
+            address.rsplit_once('@').is_some_and(|(_, domain)| {
+                domains
+                    .clone()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(domain))
+            })
+        };
+
+        (self.name.contains('@') || !self.emails.is_empty())
+            && (!self.name.contains('@') || in_domains(&self.name))
+            && self.emails.iter().all(|email| in_domains(email))
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(domain) = &self.domain {
+            write!(f, "{}:{}", self.kind.as_str(), domain)
+        } else {
+            f.write_str(self.kind.as_str())
+        }
+    }
+}
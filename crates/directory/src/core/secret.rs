@@ -4,17 +4,22 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::fmt::Display;
+
 use argon2::Argon2;
+use hmac::{Hmac, Mac};
 use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use password_hash::PasswordHash;
 use pbkdf2::Pbkdf2;
 use pwhash::{bcrypt, bsdi_crypt, md5_crypt, sha1_crypt, sha256_crypt, sha512_crypt, unix_crypt};
+use rand::RngCore;
 use scrypt::Scrypt;
 use sha1::Digest;
 use sha1::Sha1;
 use sha2::Sha256;
 use sha2::Sha512;
+use subtle::ConstantTimeEq;
 use tokio::sync::oneshot;
 use totp_rs::TOTP;
 
@@ -106,6 +111,221 @@ impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
             Ok(false)
         }
     }
+
+    pub fn scram_secret(&self, algorithm: ScramAlgorithm) -> Option<ScramSecret> {
+        // SCRAM cannot carry a TOTP code
+        if self
+            .secrets
+            .iter()
+            .any(|secret| secret.is_disabled() || secret.is_otp_auth())
+        {
+            return None;
+        }
+
+        // Use the stored keys if available, otherwise derive them from a cleartext password
+        let mut cleartext = None;
+        for secret in &self.secrets {
+            if let Some(scram) = ScramSecret::parse(secret) {
+                if scram.algorithm == algorithm {
+                    return Some(scram);
+                }
+            } else if cleartext.is_none() && secret.is_password() {
+                cleartext = cleartext_secret(secret);
+            }
+        }
+
+        cleartext.map(|password| ScramSecret::new(algorithm, password))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScramAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+// SCRAM keys, stored as "{SCRAM-SHA-256}<iterations>,<salt>,<stored key>,<server key>"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramSecret {
+    pub algorithm: ScramAlgorithm,
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+const SCRAM_ITERATIONS: u32 = 4096;
+const SCRAM_SALT_LEN: usize = 16;
+
+impl ScramAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScramAlgorithm::Sha1 => "SCRAM-SHA-1",
+            ScramAlgorithm::Sha256 => "SCRAM-SHA-256",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.eq_ignore_ascii_case("SCRAM-SHA-1") {
+            Some(ScramAlgorithm::Sha1)
+        } else if value.eq_ignore_ascii_case("SCRAM-SHA-256") {
+            Some(ScramAlgorithm::Sha256)
+        } else {
+            None
+        }
+    }
+
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+            ScramAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+
+    pub fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha1 => {
+                let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            ScramAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    pub fn salted_password(&self, password: &str, salt: &[u8], iterations: u32) -> Vec<u8> {
+        match self {
+            ScramAlgorithm::Sha1 => {
+                pbkdf2::pbkdf2_hmac_array::<Sha1, 20>(password.as_bytes(), salt, iterations)
+                    .to_vec()
+            }
+            ScramAlgorithm::Sha256 => {
+                pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), salt, iterations)
+                    .to_vec()
+            }
+        }
+    }
+}
+
+impl ScramSecret {
+    pub fn new(algorithm: ScramAlgorithm, password: &str) -> Self {
+        let mut salt = vec![0u8; SCRAM_SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self::derive(algorithm, password, salt, SCRAM_ITERATIONS)
+    }
+
+    pub fn derive(
+        algorithm: ScramAlgorithm,
+        password: &str,
+        salt: Vec<u8>,
+        iterations: u32,
+    ) -> Self {
+        let salted_password = algorithm.salted_password(password, &salt, iterations);
+        let client_key = algorithm.hmac(&salted_password, b"Client Key");
+        ScramSecret {
+            algorithm,
+            iterations,
+            stored_key: algorithm.hash(&client_key),
+            server_key: algorithm.hmac(&salted_password, b"Server Key"),
+            salt,
+        }
+    }
+
+    pub fn parse(secret: &str) -> Option<Self> {
+        let (algorithm, keys) = secret.strip_prefix('{')?.split_once('}')?;
+        let algorithm = ScramAlgorithm::parse(algorithm)?;
+        let mut keys = keys.split(',');
+        let iterations = keys.next()?.parse().ok().filter(|&i| i > 0)?;
+        let salt = base64_decode(keys.next()?.as_bytes())?;
+        let stored_key = base64_decode(keys.next()?.as_bytes())?;
+        let server_key = base64_decode(keys.next()?.as_bytes())?;
+        if keys.next().is_none() {
+            Some(ScramSecret {
+                algorithm,
+                iterations,
+                salt,
+                stored_key,
+                server_key,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        Self::derive(self.algorithm, password, self.salt.clone(), self.iterations)
+            .stored_key
+            .ct_eq(&self.stored_key)
+            .into()
+    }
+
+    pub fn verify_proof(&self, auth_message: &[u8], proof: &[u8]) -> bool {
+        let client_signature = self.algorithm.hmac(&self.stored_key, auth_message);
+        if proof.len() == client_signature.len() {
+            let client_key = proof
+                .iter()
+                .zip(client_signature)
+                .map(|(a, b)| a ^ b)
+                .collect::<Vec<_>>();
+            self.algorithm
+                .hash(&client_key)
+                .ct_eq(&self.stored_key)
+                .into()
+        } else {
+            false
+        }
+    }
+
+    pub fn server_signature(&self, auth_message: &[u8]) -> Vec<u8> {
+        self.algorithm.hmac(&self.server_key, auth_message)
+    }
+}
+
+impl Display for ScramSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{{{}}}{},{},{},{}",
+            self.algorithm.as_str(),
+            self.iterations,
+            encode_base64(&self.salt),
+            encode_base64(&self.stored_key),
+            encode_base64(&self.server_key)
+        )
+    }
+}
+
+// Returns the password of a secret stored in cleartext
+pub fn cleartext_secret(secret: &str) -> Option<&str> {
+    if let Some(secret) = secret.strip_prefix('{') {
+        secret.split_once('}').and_then(|(algo, secret)| {
+            matches!(algo, "PLAIN" | "plain" | "CLEAR" | "clear").then_some(secret)
+        })
+    } else if !secret.starts_with(['$', '_']) {
+        Some(secret)
+    } else {
+        None
+    }
+}
+
+// Replaces a cleartext password with salted SCRAM keys, which are used
+// to verify both SCRAM exchanges and plain text logins.
+pub fn scram_secrets(secret: String) -> Vec<String> {
+    match cleartext_secret(&secret).filter(|_| secret.is_password()) {
+        Some(password) if !password.is_empty() => vec![
+            ScramSecret::new(ScramAlgorithm::Sha256, password).to_string(),
+            ScramSecret::new(ScramAlgorithm::Sha1, password).to_string(),
+        ],
+        _ => vec![secret],
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    String::from_utf8(base64_encode(bytes).unwrap_or_default()).unwrap_or_default()
 }
 
 async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
@@ -250,6 +470,20 @@ pub async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
                         unix_crypt::verify(secret, hashed_secret)
                     }
                 }
+                "SCRAM-SHA-1" | "SCRAM-SHA-256" => {
+                    // Salted SCRAM keys
+                    if let Some(scram) = ScramSecret::parse(&format!("{{{algo}}}{hashed_secret}")) {
+                        scram.verify_password(secret)
+                    } else {
+                        tracing::warn!(
+                            context = "directory",
+                            event = "error",
+                            hash = hashed_secret,
+                            "Invalid SCRAM secret"
+                        );
+                        false
+                    }
+                }
                 "PLAIN" | "plain" | "CLEAR" | "clear" => hashed_secret == secret,
                 _ => {
                     tracing::warn!(
@@ -16,6 +16,7 @@ use backend::{
     internal::PrincipalField,
     ldap::LdapDirectory,
     memory::MemoryDirectory,
+    oidc::OpenIdDirectory,
     smtp::SmtpDirectory,
     sql::SqlDirectory,
 };
@@ -51,6 +52,8 @@ pub struct Principal<T> {
     pub member_of: Vec<T>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -78,6 +81,7 @@ pub enum DirectoryError {
     Store(store::Error),
     Imap(ImapError),
     Smtp(mail_send::Error),
+    Oidc(String),
     Pool(String),
     Management(ManagementError),
     TimedOut,
@@ -103,6 +107,7 @@ pub enum DirectoryInner {
     Imap(ImapDirectory),
     Smtp(SmtpDirectory),
     Memory(MemoryDirectory),
+    Oidc(OpenIdDirectory),
 }
 
 pub enum QueryBy<'x> {
@@ -253,6 +258,20 @@ impl From<ImapError> for DirectoryError {
     }
 }
 
+impl From<reqwest::Error> for DirectoryError {
+    fn from(error: reqwest::Error) -> Self {
+        tracing::warn!(
+            context = "directory",
+            event = "error",
+            protocol = "oidc",
+            reason = %error,
+            "OpenID Connect directory error"
+        );
+
+        DirectoryError::Oidc(error.to_string())
+    }
+}
+
 impl From<mail_send::Error> for DirectoryError {
     fn from(error: mail_send::Error) -> Self {
         tracing::warn!(
@@ -308,6 +327,7 @@ impl Display for DirectoryError {
             Self::Store(error) => write!(f, "Store error: {}", error),
             Self::Imap(error) => write!(f, "IMAP error: {}", error),
             Self::Smtp(error) => write!(f, "SMTP error: {}", error),
+            Self::Oidc(error) => write!(f, "OIDC error: {}", error),
             Self::Pool(error) => write!(f, "Pool error: {}", error),
             Self::Management(error) => write!(f, "Management error: {:?}", error),
             Self::TimedOut => write!(f, "Directory timed out"),
@@ -72,6 +72,24 @@ pub enum Command {
 
     // RFC 2971
     Id,
+
+    // RFC 5465
+    Notify,
+
+    // RFC 5464
+    GetMetadata,
+    SetMetadata,
+
+    // RFC 9208
+    GetQuota,
+    GetQuotaRoot,
+    SetQuota,
+
+    // RFC 4978
+    Compress,
+
+    // RFC 7377
+    ESearch,
 }
 
 impl Command {
@@ -150,6 +168,25 @@ pub enum ResponseCode {
 
     // USEATTR
     UseAttr,
+
+    // NOTIFY
+    BadEvent {
+        supported: Vec<protocol::notify::Event>,
+    },
+    NotificationOverflow,
+
+    // METADATA
+    MetadataLongEntries {
+        size: usize,
+    },
+    MetadataMaxSize {
+        size: usize,
+    },
+    MetadataTooMany,
+    MetadataNoPrivate,
+
+    // COMPRESS
+    CompressionActive,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,87 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::{
+    protocol::compress::{self, Algorithm},
+    receiver::Request,
+    Command,
+};
+
+impl Request<Command> {
+    pub fn parse_compress(self) -> crate::Result<compress::Arguments> {
+        let mut tokens = self.tokens.into_iter();
+        let algorithm = Algorithm::parse(
+            &tokens
+                .next()
+                .ok_or((self.tag.as_str(), "Missing compression algorithm."))?
+                .unwrap_bytes(),
+        )
+        .map_err(|v| (self.tag.as_str(), v))?;
+
+        if tokens.next().is_none() {
+            Ok(compress::Arguments {
+                tag: self.tag,
+                algorithm,
+            })
+        } else {
+            Err((self.tag, "Too many arguments.").into())
+        }
+    }
+}
+
+impl Algorithm {
+    pub fn parse(value: &[u8]) -> super::Result<Self> {
+        if value.eq_ignore_ascii_case(b"DEFLATE") {
+            Ok(Algorithm::Deflate)
+        } else {
+            Err(format!(
+                "Unsupported compression algorithm '{}'.",
+                String::from_utf8_lossy(value)
+            )
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::compress::{self, Algorithm},
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_compress() {
+        let mut receiver = Receiver::new();
+
+        assert_eq!(
+            receiver
+                .parse(&mut "A1 COMPRESS deflate\r\n".as_bytes().iter())
+                .unwrap()
+                .parse_compress()
+                .unwrap(),
+            compress::Arguments {
+                tag: "A1".to_string(),
+                algorithm: Algorithm::Deflate,
+            }
+        );
+
+        for command in [
+            "A2 COMPRESS\r\n",
+            "A3 COMPRESS GZIP\r\n",
+            "A4 COMPRESS DEFLATE DEFLATE\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_compress()
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
@@ -14,7 +14,7 @@ use crate::{
     Command,
 };
 
-use super::{parse_number, parse_sequence_set, PushUnique};
+use super::{parse_number, parse_partial_range, parse_sequence_set, PushUnique};
 
 impl Request<Command> {
     #[allow(clippy::while_let_on_iterator)]
@@ -319,6 +319,8 @@ impl Request<Command> {
                         attributes.push_unique(Attribute::EmailId);
                     } else if value.eq_ignore_ascii_case(b"THREADID") {
                         attributes.push_unique(Attribute::ThreadId);
+                    } else if value.eq_ignore_ascii_case(b"SAVEDATE") {
+                        attributes.push_unique(Attribute::SaveDate);
                     } else {
                         return Err((
                             self.tag,
@@ -355,9 +357,10 @@ impl Request<Command> {
             }
         }
 
-        // CONDSTORE parameters
+        // CONDSTORE and PARTIAL parameters
         let mut changed_since = None;
         let mut include_vanished = false;
+        let mut partial = None;
         if let Some(Token::ParenthesisOpen) = tokens.peek() {
             tokens.next();
             while let Some(token) = tokens.next() {
@@ -375,6 +378,16 @@ impl Request<Command> {
                     Token::Argument(param) if param.eq_ignore_ascii_case(b"VANISHED") => {
                         include_vanished = true;
                     }
+                    Token::Argument(param) if param.eq_ignore_ascii_case(b"PARTIAL") => {
+                        partial = parse_partial_range(
+                            &tokens
+                                .next()
+                                .ok_or((self.tag.as_str(), "Missing PARTIAL parameter."))?
+                                .unwrap_bytes(),
+                        )
+                        .map_err(|v| (self.tag.as_str(), v))?
+                        .into();
+                    }
                     Token::ParenthesisClose => {
                         break;
                     }
@@ -396,6 +409,7 @@ impl Request<Command> {
                 attributes,
                 changed_since,
                 include_vanished,
+                partial,
             })
         } else {
             Err((self.tag, "No data items to fetch specified.").into())
@@ -485,7 +499,7 @@ mod tests {
     use crate::{
         protocol::{
             fetch::{self, Attribute, Section},
-            Sequence,
+            PartialRange, Sequence,
         },
         receiver::Receiver,
     };
@@ -513,6 +527,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -527,6 +542,7 @@ mod tests {
                     }],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -541,6 +557,7 @@ mod tests {
                     }],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -561,6 +578,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -582,6 +600,7 @@ mod tests {
                     }],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -604,6 +623,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -620,6 +640,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -635,6 +656,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -708,6 +730,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -723,6 +746,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -739,6 +763,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -753,6 +778,7 @@ mod tests {
                     ],
                     changed_since: None,
                     include_vanished: false,
+                    partial: None,
                 },
             ),
             (
@@ -763,6 +789,7 @@ mod tests {
                     attributes: vec![Attribute::Flags, Attribute::ModSeq],
                     changed_since: 12345.into(),
                     include_vanished: true,
+                    partial: None,
                 },
             ),
             (
@@ -773,6 +800,22 @@ mod tests {
                     attributes: vec![Attribute::Uid],
                     changed_since: 1.into(),
                     include_vanished: true,
+                    partial: None,
+                },
+            ),
+            (
+                "10 UID FETCH 1:* (FLAGS SAVEDATE) (PARTIAL -1:-30)\r\n",
+                fetch::Arguments {
+                    tag: "10".to_string(),
+                    sequence_set: Sequence::range(1.into(), None),
+                    attributes: vec![Attribute::Flags, Attribute::SaveDate],
+                    changed_since: None,
+                    include_vanished: false,
+                    partial: Some(PartialRange {
+                        start: 1,
+                        end: 30,
+                        from_end: true,
+                    }),
                 },
             ),
         ] {
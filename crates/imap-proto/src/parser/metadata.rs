@@ -0,0 +1,345 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::{
+    protocol::{
+        metadata::{self, Depth},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+use super::parse_number;
+
+impl Request<Command> {
+    pub fn parse_get_metadata(
+        self,
+        version: ProtocolVersion,
+    ) -> crate::Result<metadata::GetArguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+        let mut max_size = None;
+        let mut depth = Depth::Zero;
+
+        // Parse options
+        if tokens
+            .peek()
+            .is_some_and(|token| token.is_parenthesis_open())
+        {
+            tokens.next();
+            loop {
+                match tokens.next() {
+                    Some(Token::ParenthesisClose) => break,
+                    Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"MAXSIZE") => {
+                        max_size = parse_number::<usize>(
+                            &tokens
+                                .next()
+                                .ok_or((self.tag.as_str(), "Missing MAXSIZE value."))?
+                                .unwrap_bytes(),
+                        )
+                        .map_err(|v| (self.tag.as_str(), v))?
+                        .into();
+                    }
+                    Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"DEPTH") => {
+                        let value = tokens
+                            .next()
+                            .ok_or((self.tag.as_str(), "Missing DEPTH value."))?
+                            .unwrap_bytes();
+                        depth = if value == b"0" {
+                            Depth::Zero
+                        } else if value == b"1" {
+                            Depth::One
+                        } else if value.eq_ignore_ascii_case(b"infinity") {
+                            Depth::Infinity
+                        } else {
+                            return Err((self.tag, "Invalid DEPTH value.").into());
+                        };
+                    }
+                    Some(token) => {
+                        return Err((self.tag, format!("Invalid option '{token}'.")).into());
+                    }
+                    None => {
+                        return Err((self.tag, "Expected ')' after options.").into());
+                    }
+                }
+            }
+        }
+
+        let mailbox_name = tokens
+            .next()
+            .ok_or((self.tag.as_str(), "Missing mailbox name."))?
+            .unwrap_string()
+            .map(|v| utf7_maybe_decode(v, version))
+            .map_err(|v| (self.tag.as_str(), v))?;
+
+        let mut entries = Vec::new();
+        match tokens.next() {
+            Some(Token::ParenthesisOpen) => {
+                for token in tokens.by_ref() {
+                    match token {
+                        Token::ParenthesisClose => break,
+                        token => {
+                            entries.push(parse_entry(token).map_err(|v| (self.tag.as_str(), v))?);
+                        }
+                    }
+                }
+            }
+            Some(token) => {
+                entries.push(parse_entry(token).map_err(|v| (self.tag.as_str(), v))?);
+            }
+            None => (),
+        }
+
+        if entries.is_empty() {
+            Err((self.tag, "At least one entry name is required.").into())
+        } else if tokens.next().is_some() {
+            Err((self.tag, "Unexpected arguments after entry names.").into())
+        } else {
+            Ok(metadata::GetArguments {
+                tag: self.tag,
+                mailbox_name,
+                entries,
+                max_size,
+                depth,
+            })
+        }
+    }
+
+    pub fn parse_set_metadata(
+        self,
+        version: ProtocolVersion,
+    ) -> crate::Result<metadata::SetArguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+
+        let mailbox_name = tokens
+            .next()
+            .ok_or((self.tag.as_str(), "Missing mailbox name."))?
+            .unwrap_string()
+            .map(|v| utf7_maybe_decode(v, version))
+            .map_err(|v| (self.tag.as_str(), v))?;
+
+        if tokens
+            .next()
+            .is_none_or(|token| !token.is_parenthesis_open())
+        {
+            return Err((self.tag, "Expected '(' before entry list.").into());
+        }
+
+        let entries = parse_entry_values(&mut tokens).map_err(|v| (self.tag.as_str(), v))?;
+        if entries.is_empty() {
+            Err((self.tag, "At least one entry is required.").into())
+        } else if tokens.next().is_some() {
+            Err((self.tag, "Unexpected arguments after entry list.").into())
+        } else {
+            Ok(metadata::SetArguments {
+                tag: self.tag,
+                mailbox_name,
+                entries,
+            })
+        }
+    }
+}
+
+fn parse_entry_values(
+    tokens: &mut Peekable<IntoIter<Token>>,
+) -> super::Result<Vec<(String, Option<Vec<u8>>)>> {
+    let mut entries: Vec<(String, Option<Vec<u8>>)> = Vec::new();
+    loop {
+        let name = match tokens.next() {
+            Some(Token::ParenthesisClose) => break,
+            Some(token) => match parse_entry(token)? {
+                name if name != "/private" && name != "/shared" => name,
+                name => return Err(format!("Invalid entry name '{name}'.").into()),
+            },
+            None => return Err("Expected ')' after entry list.".into()),
+        };
+        let value = match tokens.next() {
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NIL") => None,
+            Some(Token::Argument(value)) => Some(value),
+            Some(Token::Nil) => Some(vec![]),
+            _ => return Err(format!("Missing value for entry '{name}'.").into()),
+        };
+        if let Some(entry) = entries.iter_mut().find(|(n, _)| n == &name) {
+            entry.1 = value;
+        } else {
+            entries.push((name, value));
+        }
+    }
+
+    Ok(entries)
+}
+
+fn parse_entry(token: Token) -> super::Result<String> {
+    let name = token.unwrap_string()?.to_ascii_lowercase();
+
+    if !["/private", "/shared"].iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|child| child.is_empty() || child.starts_with('/'))
+    }) || name.ends_with('/')
+        || name.contains("//")
+        || name
+            .chars()
+            .any(|ch| matches!(ch, '*' | '%') || ch.is_control())
+    {
+        Err(format!("Invalid entry name '{name}'.").into())
+    } else {
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            metadata::{self, Depth},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_get_metadata() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A1 GETMETADATA \"\" /shared/comment\r\n",
+                metadata::GetArguments {
+                    tag: "A1".to_string(),
+                    mailbox_name: "".to_string(),
+                    entries: vec!["/shared/comment".to_string()],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+            (
+                "A2 GETMETADATA INBOX (/shared/Comment /private/comment)\r\n",
+                metadata::GetArguments {
+                    tag: "A2".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        "/shared/comment".to_string(),
+                        "/private/comment".to_string(),
+                    ],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+            (
+                "A3 GETMETADATA (MAXSIZE 1024 DEPTH infinity) \"Other Mailbox\" /private/filters\r\n",
+                metadata::GetArguments {
+                    tag: "A3".to_string(),
+                    mailbox_name: "Other Mailbox".to_string(),
+                    entries: vec!["/private/filters".to_string()],
+                    max_size: Some(1024),
+                    depth: Depth::Infinity,
+                },
+            ),
+            (
+                "A4 GETMETADATA (DEPTH 1) INBOX (/private/vendor)\r\n",
+                metadata::GetArguments {
+                    tag: "A4".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec!["/private/vendor".to_string()],
+                    max_size: None,
+                    depth: Depth::One,
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_get_metadata(ProtocolVersion::Rev2)
+                    .expect(command),
+                arguments,
+                "{command}"
+            );
+        }
+
+        for command in [
+            "A5 GETMETADATA INBOX\r\n",
+            "A6 GETMETADATA INBOX /comment\r\n",
+            "A12 GETMETADATA INBOX /privatecomment\r\n",
+            "A7 GETMETADATA INBOX /shared/*\r\n",
+            "A8 GETMETADATA INBOX /private//comment\r\n",
+            "A9 GETMETADATA INBOX /private/comment/\r\n",
+            "A10 GETMETADATA (DEPTH 2) INBOX /private/comment\r\n",
+            "A11 GETMETADATA (SIZE 2) INBOX /private/comment\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_get_metadata(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_set_metadata() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A1 SETMETADATA INBOX (/private/comment \"My new comment\")\r\n",
+                metadata::SetArguments {
+                    tag: "A1".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![(
+                        "/private/comment".to_string(),
+                        Some(b"My new comment".to_vec()),
+                    )],
+                },
+            ),
+            (
+                "A2 SETMETADATA \"\" (/shared/comment NIL /shared/admin {6+}\r\nhello!)\r\n",
+                metadata::SetArguments {
+                    tag: "A2".to_string(),
+                    mailbox_name: "".to_string(),
+                    entries: vec![
+                        ("/shared/comment".to_string(), None),
+                        ("/shared/admin".to_string(), Some(b"hello!".to_vec())),
+                    ],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_set_metadata(ProtocolVersion::Rev2)
+                    .expect(command),
+                arguments,
+                "{command}"
+            );
+        }
+
+        for command in [
+            "A3 SETMETADATA INBOX\r\n",
+            "A4 SETMETADATA INBOX ()\r\n",
+            "A5 SETMETADATA INBOX (/private/comment)\r\n",
+            "A6 SETMETADATA INBOX (/comment \"value\")\r\n",
+            "A8 SETMETADATA INBOX (/shared \"value\")\r\n",
+            "A7 SETMETADATA INBOX /private/comment \"value\"\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_set_metadata(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
@@ -7,6 +7,7 @@
 pub mod acl;
 pub mod append;
 pub mod authenticate;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -15,6 +16,9 @@ pub mod fetch;
 pub mod list;
 pub mod login;
 pub mod lsub;
+pub mod metadata;
+pub mod notify;
+pub mod quota;
 pub mod rename;
 pub mod search;
 pub mod select;
@@ -29,7 +33,7 @@ use std::{borrow::Cow, str::FromStr};
 use chrono::{DateTime, NaiveDate};
 
 use crate::{
-    protocol::{Flag, Sequence},
+    protocol::{Flag, PartialRange, Sequence},
     receiver::CommandParser,
     Command,
 };
@@ -77,13 +81,21 @@ impl CommandParser for Command {
             b"MYRIGHTS" => Some(Command::MyRights),
             b"UNAUTHENTICATE" => Some(Command::Unauthenticate),
             b"ID" => Some(Command::Id),
+            b"NOTIFY" => Some(Command::Notify),
+            b"GETMETADATA" => Some(Command::GetMetadata),
+            b"SETMETADATA" => Some(Command::SetMetadata),
+            b"GETQUOTA" => Some(Command::GetQuota),
+            b"GETQUOTAROOT" => Some(Command::GetQuotaRoot),
+            b"SETQUOTA" => Some(Command::SetQuota),
+            b"COMPRESS" => Some(Command::Compress),
+            b"ESEARCH" => Some(Command::ESearch),
             _ => None,
         }
     }
 
     #[inline(always)]
     fn tokenize_brackets(&self) -> bool {
-        matches!(self, Command::Fetch(_))
+        matches!(self, Command::Fetch(_) | Command::Notify)
     }
 }
 
@@ -203,6 +215,36 @@ pub fn parse_number<T: FromStr>(value: &[u8]) -> Result<T> {
         .map_err(|_| Cow::from(format!("Expected a number, found {:?}.", string)))
 }
 
+pub fn parse_partial_range(value: &[u8]) -> Result<PartialRange> {
+    let (start, end) = value
+        .iter()
+        .position(|&ch| ch == b':')
+        .map(|pos| (&value[..pos], &value[pos + 1..]))
+        .ok_or_else(|| Cow::from("Expected a partial range."))?;
+    let from_end = start.first() == Some(&b'-');
+    if from_end != (end.first() == Some(&b'-')) {
+        return Err(Cow::from(
+            "Partial range boundaries must be both positive or both negative.",
+        ));
+    }
+    let (start, end) = if from_end {
+        (&start[1..], &end[1..])
+    } else {
+        (start, end)
+    };
+    let start = parse_number::<u32>(start)?;
+    let end = parse_number::<u32>(end)?;
+    if start == 0 || end == 0 {
+        return Err(Cow::from("Partial range boundaries cannot be zero."));
+    }
+
+    Ok(PartialRange {
+        start: std::cmp::min(start, end),
+        end: std::cmp::max(start, end),
+        from_end,
+    })
+}
+
 pub fn parse_sequence_set(value: &[u8]) -> Result<Sequence> {
     let mut sequence_set = Vec::new();
 
@@ -396,7 +438,7 @@ impl<T: PartialEq> PushUnique<T> for Vec<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::protocol::Sequence;
+    use crate::protocol::{PartialRange, Sequence};
 
     #[test]
     fn parse_sequence_set() {
@@ -470,4 +512,63 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn parse_partial_range() {
+        for (range, expected_result) in [
+            (
+                "1:100",
+                PartialRange {
+                    start: 1,
+                    end: 100,
+                    from_end: false,
+                },
+            ),
+            (
+                "-1:-50",
+                PartialRange {
+                    start: 1,
+                    end: 50,
+                    from_end: true,
+                },
+            ),
+            (
+                "200:101",
+                PartialRange {
+                    start: 101,
+                    end: 200,
+                    from_end: false,
+                },
+            ),
+        ] {
+            assert_eq!(
+                super::parse_partial_range(range.as_bytes()).unwrap(),
+                expected_result
+            );
+        }
+
+        for range in ["0:10", "-1:10", "1:", "100", "1:*"] {
+            assert!(
+                super::parse_partial_range(range.as_bytes()).is_err(),
+                "{range}"
+            );
+        }
+
+        let items = (1..=10).collect::<Vec<u32>>();
+        for (range, expected_result) in [
+            ("1:3", vec![1, 2, 3]),
+            ("-1:-3", vec![8, 9, 10]),
+            ("9:20", vec![9, 10]),
+            ("-8:-20", vec![1, 2, 3]),
+            ("11:20", vec![]),
+        ] {
+            assert_eq!(
+                super::parse_partial_range(range.as_bytes())
+                    .unwrap()
+                    .apply(&items),
+                &expected_result,
+                "{range}"
+            );
+        }
+    }
 }
@@ -0,0 +1,376 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::{
+    protocol::{
+        fetch,
+        notify::{self, Event, EventGroup, Filter},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+impl Request<Command> {
+    pub fn parse_notify(self, version: ProtocolVersion) -> crate::Result<notify::Arguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+
+        match tokens.next() {
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NONE") => {
+                return if tokens.next().is_none() {
+                    Ok(notify::Arguments {
+                        tag: self.tag,
+                        status: false,
+                        groups: vec![],
+                    })
+                } else {
+                    Err((self.tag, "Unexpected arguments after NONE.").into())
+                };
+            }
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"SET") => (),
+            _ => return Err((self.tag, "Expected SET or NONE.").into()),
+        }
+
+        let status = if tokens
+            .peek()
+            .is_some_and(|token| token.eq_ignore_ascii_case(b"STATUS"))
+        {
+            tokens.next();
+            true
+        } else {
+            false
+        };
+
+        let mut groups = Vec::new();
+        while let Some(token) = tokens.next() {
+            if !token.is_parenthesis_open() {
+                return Err((self.tag, "Expected event group.").into());
+            }
+            let filter = parse_filter(&mut tokens, version).map_err(|v| (self.tag.as_str(), v))?;
+            let events =
+                parse_events(&mut tokens, &self.tag).map_err(|v| (self.tag.as_str(), v))?;
+            if tokens
+                .next()
+                .is_none_or(|token| !token.is_parenthesis_close())
+            {
+                return Err((self.tag, "Expected ')' after event group.").into());
+            }
+
+            // Validate event combinations
+            let group = EventGroup { filter, events };
+            if group.has_event(&Event::MessageNew(vec![]))
+                != group.has_event(&Event::MessageExpunge)
+            {
+                return Err((
+                    self.tag,
+                    "MessageNew and MessageExpunge must be specified together.",
+                )
+                    .into());
+            } else if (group.has_event(&Event::FlagChange)
+                || group.has_event(&Event::AnnotationChange))
+                && !group.has_message_events()
+            {
+                return Err((
+                    self.tag,
+                    "FlagChange and AnnotationChange require MessageNew and MessageExpunge.",
+                )
+                    .into());
+            } else if !group.filter.is_selected()
+                && group
+                    .new_message_attributes()
+                    .is_some_and(|attributes| !attributes.is_empty())
+            {
+                return Err((
+                    self.tag,
+                    "Fetch attributes are only allowed for the selected mailbox.",
+                )
+                    .into());
+            } else if group.filter.is_selected()
+                && groups.iter().any(|g: &EventGroup| g.filter.is_selected())
+            {
+                return Err((self.tag, "The selected mailbox can only be specified once.").into());
+            }
+            groups.push(group);
+        }
+
+        if !groups.is_empty() {
+            Ok(notify::Arguments {
+                tag: self.tag,
+                status,
+                groups,
+            })
+        } else {
+            Err((self.tag, "At least one event group is required.").into())
+        }
+    }
+}
+
+fn parse_filter(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> super::Result<Filter> {
+    let value = tokens.next().ok_or("Missing filter.")?.unwrap_bytes();
+    if value.eq_ignore_ascii_case(b"SELECTED") {
+        Ok(Filter::Selected)
+    } else if value.eq_ignore_ascii_case(b"SELECTED-DELAYED") {
+        Ok(Filter::SelectedDelayed)
+    } else if value.eq_ignore_ascii_case(b"PERSONAL") {
+        Ok(Filter::Personal)
+    } else if value.eq_ignore_ascii_case(b"INBOXES") {
+        Ok(Filter::Inboxes)
+    } else if value.eq_ignore_ascii_case(b"SUBSCRIBED") {
+        Ok(Filter::Subscribed)
+    } else if value.eq_ignore_ascii_case(b"SUBTREE") {
+        parse_mailboxes(tokens, version).map(Filter::Subtree)
+    } else if value.eq_ignore_ascii_case(b"MAILBOXES") {
+        parse_mailboxes(tokens, version).map(Filter::Mailboxes)
+    } else {
+        Err(format!("Invalid filter '{}'.", String::from_utf8_lossy(&value)).into())
+    }
+}
+
+fn parse_mailboxes(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> super::Result<Vec<String>> {
+    let mut mailboxes = Vec::new();
+    match tokens.next() {
+        Some(Token::ParenthesisOpen) => {
+            for token in tokens.by_ref() {
+                match token {
+                    Token::ParenthesisClose => break,
+                    token => {
+                        mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+                    }
+                }
+            }
+        }
+        Some(token @ (Token::Argument(_) | Token::Nil)) => {
+            mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+        }
+        _ => return Err("Expected mailbox name.".into()),
+    }
+
+    if !mailboxes.is_empty() {
+        Ok(mailboxes)
+    } else {
+        Err("At least one mailbox name is required.".into())
+    }
+}
+
+fn parse_events(tokens: &mut Peekable<IntoIter<Token>>, tag: &str) -> super::Result<Vec<Event>> {
+    match tokens.next() {
+        Some(Token::ParenthesisOpen) => (),
+        Some(token) if token.eq_ignore_ascii_case(b"NONE") => return Ok(vec![]),
+        _ => return Err("Expected event list.".into()),
+    }
+
+    let mut events = Vec::new();
+    while let Some(token) = tokens.next() {
+        let event = match token {
+            Token::ParenthesisClose => break,
+            Token::Argument(value) => {
+                if value.eq_ignore_ascii_case(b"MessageNew") {
+                    Event::MessageNew(
+                        if tokens
+                            .peek()
+                            .is_some_and(|token| token.is_parenthesis_open())
+                        {
+                            parse_fetch_attributes(tokens, tag)?
+                        } else {
+                            vec![]
+                        },
+                    )
+                } else if value.eq_ignore_ascii_case(b"MessageExpunge") {
+                    Event::MessageExpunge
+                } else if value.eq_ignore_ascii_case(b"FlagChange") {
+                    Event::FlagChange
+                } else if value.eq_ignore_ascii_case(b"AnnotationChange") {
+                    Event::AnnotationChange
+                } else if value.eq_ignore_ascii_case(b"MailboxName") {
+                    Event::MailboxName
+                } else if value.eq_ignore_ascii_case(b"SubscriptionChange") {
+                    Event::SubscriptionChange
+                } else if value.eq_ignore_ascii_case(b"MailboxMetadataChange") {
+                    Event::MailboxMetadataChange
+                } else if value.eq_ignore_ascii_case(b"ServerMetadataChange") {
+                    Event::ServerMetadataChange
+                } else {
+                    return Err(
+                        format!("Invalid event '{}'.", String::from_utf8_lossy(&value)).into(),
+                    );
+                }
+            }
+            _ => return Err("Invalid event.".into()),
+        };
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+
+    if !events.is_empty() {
+        Ok(events)
+    } else {
+        Err("At least one event is required.".into())
+    }
+}
+
+fn parse_fetch_attributes(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    tag: &str,
+) -> super::Result<Vec<fetch::Attribute>> {
+    // Collect the attribute list and hand it to the FETCH parser
+    let mut fetch_tokens = vec![Token::Argument(b"1".to_vec())];
+    let mut depth = 0;
+    for token in tokens.by_ref() {
+        match token {
+            Token::ParenthesisOpen => depth += 1,
+            Token::ParenthesisClose => depth -= 1,
+            _ => (),
+        }
+        fetch_tokens.push(token);
+        if depth == 0 {
+            break;
+        }
+    }
+    if depth != 0 {
+        return Err("Unterminated fetch attribute list.".into());
+    }
+
+    Request {
+        tag: tag.to_string(),
+        command: Command::Fetch(false),
+        tokens: fetch_tokens,
+    }
+    .parse_fetch()
+    .map(|arguments| arguments.attributes)
+    .map_err(|response| response.message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            fetch::{Attribute, Section},
+            notify::{self, Event, EventGroup, Filter},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_notify() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A1 NOTIFY NONE\r\n",
+                notify::Arguments {
+                    tag: "A1".to_string(),
+                    status: false,
+                    groups: vec![],
+                },
+            ),
+            (
+                concat!(
+                    "A2 NOTIFY SET STATUS (SELECTED (MessageNew (UID ",
+                    "BODY.PEEK[HEADER.FIELDS (From Subject)]) MessageExpunge FlagChange)) ",
+                    "(SUBTREE (Lists \"Other Lists\") (MessageNew MessageExpunge)) ",
+                    "(MAILBOXES Drafts NONE) ",
+                    "(personal (MailboxName SubscriptionChange))\r\n"
+                ),
+                notify::Arguments {
+                    tag: "A2".to_string(),
+                    status: true,
+                    groups: vec![
+                        EventGroup {
+                            filter: Filter::Selected,
+                            events: vec![
+                                Event::MessageNew(vec![
+                                    Attribute::Uid,
+                                    Attribute::BodySection {
+                                        peek: true,
+                                        sections: vec![Section::HeaderFields {
+                                            not: false,
+                                            fields: vec!["From".into(), "Subject".into()],
+                                        }],
+                                        partial: None,
+                                    },
+                                ]),
+                                Event::MessageExpunge,
+                                Event::FlagChange,
+                            ],
+                        },
+                        EventGroup {
+                            filter: Filter::Subtree(vec![
+                                "Lists".to_string(),
+                                "Other Lists".to_string(),
+                            ]),
+                            events: vec![Event::MessageNew(vec![]), Event::MessageExpunge],
+                        },
+                        EventGroup {
+                            filter: Filter::Mailboxes(vec!["Drafts".to_string()]),
+                            events: vec![],
+                        },
+                        EventGroup {
+                            filter: Filter::Personal,
+                            events: vec![Event::MailboxName, Event::SubscriptionChange],
+                        },
+                    ],
+                },
+            ),
+            (
+                "A3 NOTIFY SET (INBOXES (MessageNew MessageExpunge AnnotationChange))\r\n",
+                notify::Arguments {
+                    tag: "A3".to_string(),
+                    status: false,
+                    groups: vec![EventGroup {
+                        filter: Filter::Inboxes,
+                        events: vec![
+                            Event::MessageNew(vec![]),
+                            Event::MessageExpunge,
+                            Event::AnnotationChange,
+                        ],
+                    }],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_notify(ProtocolVersion::Rev2)
+                    .expect(command),
+                arguments,
+                "{command}"
+            );
+        }
+
+        for command in [
+            "A4 NOTIFY\r\n",
+            "A5 NOTIFY SET\r\n",
+            "A6 NOTIFY SET (PERSONAL (MessageNew))\r\n",
+            "A7 NOTIFY SET (PERSONAL (FlagChange))\r\n",
+            "A8 NOTIFY SET (PERSONAL (MessageNew (UID) MessageExpunge))\r\n",
+            "A9 NOTIFY SET (SELECTED NONE) (SELECTED-DELAYED NONE)\r\n",
+            "A10 NOTIFY SET (UNKNOWN (MailboxName))\r\n",
+            "A11 NOTIFY SET (PERSONAL (MailboxName)\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_notify(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
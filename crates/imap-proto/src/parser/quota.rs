@@ -0,0 +1,225 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::{
+    protocol::{
+        quota::{self, Resource},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+use super::parse_number;
+
+/*
+
+   getquota        = "GETQUOTA" SP quota-root-name
+
+   getquotaroot    = "GETQUOTAROOT" SP mailbox
+
+   setquota        = "SETQUOTA" SP quota-root-name
+                       SP setquota-list
+
+   setquota-list   = "(" [setquota-resource
+                       *(SP setquota-resource)] ")"
+
+   setquota-resource = resource-name SP resource-limit
+
+*/
+
+impl Request<Command> {
+    pub fn parse_get_quota(self, version: ProtocolVersion) -> crate::Result<quota::Arguments> {
+        let is_mailbox = match self.command {
+            Command::GetQuotaRoot => true,
+            Command::GetQuota => false,
+            _ => unreachable!(),
+        };
+        let mut tokens = self.tokens.into_iter();
+        let name = tokens
+            .next()
+            .ok_or((
+                self.tag.as_str(),
+                if is_mailbox {
+                    "Missing mailbox name."
+                } else {
+                    "Missing quota root."
+                },
+            ))?
+            .unwrap_string()
+            .map_err(|v| (self.tag.as_str(), v))?;
+
+        if tokens.next().is_none() {
+            Ok(quota::Arguments {
+                tag: self.tag,
+                name: if is_mailbox {
+                    utf7_maybe_decode(name, version)
+                } else {
+                    name
+                },
+            })
+        } else {
+            Err((self.tag, "Too many arguments.").into())
+        }
+    }
+
+    pub fn parse_set_quota(self) -> crate::Result<quota::SetArguments> {
+        let mut tokens = self.tokens.into_iter();
+        let quota_root = tokens
+            .next()
+            .ok_or((self.tag.as_str(), "Missing quota root."))?
+            .unwrap_string()
+            .map_err(|v| (self.tag.as_str(), v))?;
+
+        if tokens
+            .next()
+            .is_none_or(|token| !token.is_parenthesis_open())
+        {
+            return Err((self.tag, "Expected '(' before resource list.").into());
+        }
+
+        let mut limits: Vec<(Resource, u64)> = Vec::new();
+        loop {
+            let resource = match tokens.next() {
+                Some(Token::ParenthesisClose) => break,
+                Some(Token::Argument(value)) => {
+                    Resource::parse(&value).map_err(|v| (self.tag.as_str(), v))?
+                }
+                _ => return Err((self.tag, "Expected resource name.").into()),
+            };
+            let limit = parse_number::<u64>(
+                &tokens
+                    .next()
+                    .ok_or((self.tag.as_str(), "Missing resource limit."))?
+                    .unwrap_bytes(),
+            )
+            .map_err(|v| (self.tag.as_str(), v))?;
+            if limits.iter().any(|(r, _)| *r == resource) {
+                return Err((self.tag, "Duplicate resource name.").into());
+            }
+            limits.push((resource, limit));
+        }
+
+        if tokens.next().is_none() {
+            Ok(quota::SetArguments {
+                tag: self.tag,
+                quota_root,
+                limits,
+            })
+        } else {
+            Err((self.tag, "Too many arguments.").into())
+        }
+    }
+}
+
+impl Resource {
+    pub fn parse(value: &[u8]) -> super::Result<Self> {
+        if value.eq_ignore_ascii_case(b"STORAGE") {
+            Ok(Resource::Storage)
+        } else if value.eq_ignore_ascii_case(b"MESSAGE") {
+            Ok(Resource::Message)
+        } else {
+            Err(format!("Unsupported resource '{}'.", String::from_utf8_lossy(value)).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            quota::{self, Resource},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_quota() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A1 GETQUOTAROOT INBOX\r\n",
+                quota::Arguments {
+                    tag: "A1".to_string(),
+                    name: "INBOX".to_string(),
+                },
+            ),
+            (
+                "A2 GETQUOTA \"\"\r\n",
+                quota::Arguments {
+                    tag: "A2".to_string(),
+                    name: "".to_string(),
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_get_quota(ProtocolVersion::Rev2)
+                    .expect(command),
+                arguments,
+                "{command}"
+            );
+        }
+
+        for (command, arguments) in [
+            (
+                "A3 SETQUOTA \"\" (STORAGE 512)\r\n",
+                quota::SetArguments {
+                    tag: "A3".to_string(),
+                    quota_root: "".to_string(),
+                    limits: vec![(Resource::Storage, 512)],
+                },
+            ),
+            (
+                "A4 SETQUOTA \"Shared Folders/jane\" (storage 1024 MESSAGE 100)\r\n",
+                quota::SetArguments {
+                    tag: "A4".to_string(),
+                    quota_root: "Shared Folders/jane".to_string(),
+                    limits: vec![(Resource::Storage, 1024), (Resource::Message, 100)],
+                },
+            ),
+            (
+                "A5 SETQUOTA \"\" ()\r\n",
+                quota::SetArguments {
+                    tag: "A5".to_string(),
+                    quota_root: "".to_string(),
+                    limits: vec![],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_set_quota()
+                    .expect(command),
+                arguments,
+                "{command}"
+            );
+        }
+
+        for command in [
+            "A6 SETQUOTA \"\"\r\n",
+            "A7 SETQUOTA \"\" (STORAGE)\r\n",
+            "A8 SETQUOTA \"\" (MAILBOX 10)\r\n",
+            "A9 SETQUOTA \"\" (STORAGE 10 STORAGE 20)\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_set_quota()
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
@@ -12,12 +12,13 @@ use mail_parser::decoders::charsets::map::charset_decoder;
 use mail_parser::decoders::charsets::DecoderFnc;
 
 use crate::protocol::search::{self, Filter};
-use crate::protocol::search::{ModSeqEntry, ResultOption};
+use crate::protocol::search::{ModSeqEntry, ResultOption, Source};
 use crate::protocol::{Flag, ProtocolVersion};
 use crate::receiver::{Request, Token};
+use crate::utf7::utf7_maybe_decode;
 use crate::Command;
 
-use super::{parse_date, parse_number, parse_sequence_set};
+use super::{parse_date, parse_number, parse_partial_range, parse_sequence_set};
 
 impl Request<Command> {
     #[allow(clippy::while_let_on_iterator)]
@@ -65,6 +66,130 @@ impl Request<Command> {
             }),
         }
     }
+
+    pub fn parse_esearch(
+        self,
+        version: ProtocolVersion,
+    ) -> crate::Result<search::MultiSearchArguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+        let mut sources = Vec::new();
+
+        if tokens
+            .peek()
+            .is_some_and(|token| token.eq_ignore_ascii_case(b"IN"))
+        {
+            tokens.next();
+            if tokens
+                .next()
+                .is_none_or(|token| !token.is_parenthesis_open())
+            {
+                return Err((self.tag, "Expected '(' after IN.").into());
+            }
+            loop {
+                let source = match tokens.next() {
+                    Some(Token::ParenthesisClose) => break,
+                    Some(Token::Argument(value)) => value,
+                    _ => return Err((self.tag, "Expected source mailbox filter.").into()),
+                };
+                sources.push(if source.eq_ignore_ascii_case(b"selected") {
+                    Source::Selected
+                } else if source.eq_ignore_ascii_case(b"selected-delayed") {
+                    Source::SelectedDelayed
+                } else if source.eq_ignore_ascii_case(b"inboxes") {
+                    Source::Inboxes
+                } else if source.eq_ignore_ascii_case(b"personal") {
+                    Source::Personal
+                } else if source.eq_ignore_ascii_case(b"subscribed") {
+                    Source::Subscribed
+                } else if source.eq_ignore_ascii_case(b"subtree") {
+                    Source::Subtree(
+                        parse_mailbox_list(&mut tokens, version)
+                            .map_err(|v| (self.tag.as_str(), v))?,
+                    )
+                } else if source.eq_ignore_ascii_case(b"subtree-one") {
+                    Source::SubtreeOne(
+                        parse_mailbox_list(&mut tokens, version)
+                            .map_err(|v| (self.tag.as_str(), v))?,
+                    )
+                } else if source.eq_ignore_ascii_case(b"mailboxes") {
+                    Source::Mailboxes(
+                        parse_mailbox_list(&mut tokens, version)
+                            .map_err(|v| (self.tag.as_str(), v))?,
+                    )
+                } else {
+                    return Err((
+                        self.tag,
+                        format!(
+                            "Unsupported source mailbox filter '{}'.",
+                            String::from_utf8_lossy(&source)
+                        ),
+                    )
+                        .into());
+                });
+            }
+            if sources.is_empty() {
+                return Err((self.tag, "Missing source mailbox filters.").into());
+            }
+        } else {
+            sources.push(Source::Selected);
+        }
+
+        let tag = self.tag;
+        let mut arguments = Request {
+            tag: tag.clone(),
+            command: Command::Search(true),
+            tokens: tokens.collect(),
+        }
+        .parse_search(version)?;
+        arguments.is_esearch = true;
+
+        // Sequence numbers and saved searches only apply to the selected mailbox
+        if sources
+            .iter()
+            .any(|source| !matches!(source, Source::Selected | Source::SelectedDelayed))
+            && (arguments.result_options.contains(&ResultOption::Save)
+                || arguments.filter.iter().any(|filter| {
+                    matches!(filter, Filter::Sequence(sequence, is_uid)
+                        if !is_uid || sequence.is_saved_search())
+                }))
+        {
+            return Err((
+                tag,
+                "Message sequence numbers and saved searches are not allowed in multi-mailbox searches.",
+            )
+                .into());
+        }
+
+        Ok(search::MultiSearchArguments { sources, arguments })
+    }
+}
+
+fn parse_mailbox_list(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> super::Result<Vec<String>> {
+    let mut mailboxes = Vec::new();
+    match tokens.next() {
+        Some(Token::ParenthesisOpen) => loop {
+            match tokens.next() {
+                Some(Token::ParenthesisClose) => break,
+                Some(token) => {
+                    mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+                }
+                None => return Err(Cow::from("Expected ')' after mailbox list.")),
+            }
+        },
+        Some(token) => {
+            mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+        }
+        None => return Err(Cow::from("Missing mailbox name.")),
+    }
+
+    if !mailboxes.is_empty() {
+        Ok(mailboxes)
+    } else {
+        Err(Cow::from("Missing mailbox name."))
+    }
 }
 
 pub fn parse_result_options(
@@ -78,9 +203,17 @@ pub fn parse_result_options(
         return Err(Cow::from("Invalid result option, expected parenthesis."));
     }
 
-    for token in tokens {
+    while let Some(token) = tokens.next() {
         match token {
             Token::ParenthesisClose => break,
+            Token::Argument(value) if value.eq_ignore_ascii_case(b"partial") => {
+                result_options.push(ResultOption::Partial(parse_partial_range(
+                    &tokens
+                        .next()
+                        .ok_or_else(|| Cow::from("Missing partial range."))?
+                        .unwrap_bytes(),
+                )?));
+            }
             Token::Argument(value) => {
                 result_options.push(ResultOption::parse(&value)?);
             }
@@ -88,6 +221,16 @@ pub fn parse_result_options(
         }
     }
 
+    if result_options.contains(&ResultOption::All)
+        && result_options
+            .iter()
+            .any(|option| matches!(option, ResultOption::Partial(_)))
+    {
+        return Err(Cow::from(
+            "The ALL and PARTIAL result options are mutually exclusive.",
+        ));
+    }
+
     Ok(result_options)
 }
 
@@ -311,6 +454,29 @@ pub fn parse_filters(
                             .ok_or_else(|| Cow::from("Expected an THREADID value."))?
                             .unwrap_string()?,
                     ));
+                } else if value.eq_ignore_ascii_case(b"SAVEDBEFORE") {
+                    filters.push(Filter::SavedBefore(parse_date(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected date"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"SAVEDON") {
+                    filters.push(Filter::SavedOn(parse_date(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected date"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"SAVEDSINCE") {
+                    filters.push(Filter::SavedSince(parse_date(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected date"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"SAVEDATESUPPORTED") {
+                    filters.push(Filter::SaveDateSupported);
                 } else if value.eq_ignore_ascii_case(b"OR") {
                     if filters_stack.len() > 10 {
                         return Err(Cow::from("Too many nested filters"));
@@ -428,8 +594,8 @@ impl ResultOption {
 mod tests {
     use crate::{
         protocol::{
-            search::{self, Filter, ModSeqEntry, ResultOption},
-            Flag, ProtocolVersion, Sequence,
+            search::{self, Filter, ModSeqEntry, ResultOption, Source},
+            Flag, PartialRange, ProtocolVersion, Sequence,
         },
         receiver::Receiver,
     };
@@ -740,6 +906,33 @@ mod tests {
                     sort: None,
                 },
             ),
+            (
+                b"6 UID SEARCH RETURN (COUNT PARTIAL -1:-100) SAVEDSINCE 1-Dec-2023\r\n".to_vec(),
+                search::Arguments {
+                    tag: "6".to_string(),
+                    result_options: vec![
+                        ResultOption::Count,
+                        ResultOption::Partial(PartialRange {
+                            start: 1,
+                            end: 100,
+                            from_end: true,
+                        }),
+                    ],
+                    filter: vec![Filter::SavedSince(1701388800)],
+                    is_esearch: true,
+                    sort: None,
+                },
+            ),
+            (
+                b"7 SEARCH SAVEDATESUPPORTED SAVEDBEFORE 1-Dec-2023\r\n".to_vec(),
+                search::Arguments {
+                    tag: "7".to_string(),
+                    result_options: vec![],
+                    filter: vec![Filter::SaveDateSupported, Filter::SavedBefore(1701388800)],
+                    is_esearch: true,
+                    sort: None,
+                },
+            ),
         ] {
             let command_str = String::from_utf8_lossy(&command).into_owned();
             assert_eq!(
@@ -753,5 +946,76 @@ mod tests {
                 command_str
             );
         }
+
+        for command in [
+            "8 SEARCH RETURN (ALL PARTIAL 1:10) ALL\r\n",
+            "9 SEARCH RETURN (PARTIAL 0:10) ALL\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_search(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_esearch() {
+        let mut receiver = Receiver::new();
+
+        for (command, sources, filter) in [
+            (
+                "A1 ESEARCH IN (personal subtree (Archive \"Sent Items\")) FLAGGED\r\n",
+                vec![
+                    Source::Personal,
+                    Source::Subtree(vec!["Archive".to_string(), "Sent Items".to_string()]),
+                ],
+                vec![Filter::Flagged],
+            ),
+            (
+                "A2 ESEARCH IN (mailboxes INBOX subscribed) UID 1:100\r\n",
+                vec![
+                    Source::Mailboxes(vec!["INBOX".to_string()]),
+                    Source::Subscribed,
+                ],
+                vec![Filter::Sequence(
+                    Sequence::range(1.into(), 100.into()),
+                    true,
+                )],
+            ),
+            (
+                "A3 ESEARCH UNSEEN\r\n",
+                vec![Source::Selected],
+                vec![Filter::Unseen],
+            ),
+        ] {
+            let arguments = receiver
+                .parse(&mut command.as_bytes().iter())
+                .unwrap()
+                .parse_esearch(ProtocolVersion::Rev2)
+                .expect(command);
+            assert_eq!(arguments.sources, sources, "{command}");
+            assert_eq!(arguments.arguments.filter, filter, "{command}");
+            assert!(arguments.arguments.is_esearch);
+        }
+
+        for command in [
+            "A4 ESEARCH IN () ALL\r\n",
+            "A5 ESEARCH IN (inboxes) 1:10\r\n",
+            "A6 ESEARCH IN (personal) RETURN (SAVE) ALL\r\n",
+            "A7 ESEARCH IN (unknown) ALL\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_esearch(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
     }
 }
@@ -55,6 +55,8 @@ impl Algorithm {
             Ok(Self::OrderedSubject)
         } else if value.eq_ignore_ascii_case(b"REFERENCES") {
             Ok(Self::References)
+        } else if value.eq_ignore_ascii_case(b"REFS") {
+            Ok(Self::Refs)
         } else {
             Err(format!(
                 "Invalid threading algorithm {:?}",
@@ -97,6 +99,14 @@ mod tests {
                     tag: "A284".to_string(),
                 },
             ),
+            (
+                b"A285 THREAD REFS UTF-8 ALL\r\n".to_vec(),
+                thread::Arguments {
+                    algorithm: Algorithm::Refs,
+                    filter: vec![Filter::All],
+                    tag: "A285".to_string(),
+                },
+            ),
         ] {
             let command_str = String::from_utf8_lossy(&command).into_owned();
 
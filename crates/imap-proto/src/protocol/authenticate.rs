@@ -17,7 +17,9 @@ pub enum Mechanism {
     CramMd5,
     DigestMd5,
     ScramSha1,
+    ScramSha1Plus,
     ScramSha256,
+    ScramSha256Plus,
     Apop,
     Ntlm,
     Gssapi,
@@ -34,7 +36,9 @@ impl Mechanism {
             Mechanism::CramMd5 => b"CRAM-MD5",
             Mechanism::DigestMd5 => b"DIGEST-MD5",
             Mechanism::ScramSha1 => b"SCRAM-SHA-1",
+            Mechanism::ScramSha1Plus => b"SCRAM-SHA-1-PLUS",
             Mechanism::ScramSha256 => b"SCRAM-SHA-256",
+            Mechanism::ScramSha256Plus => b"SCRAM-SHA-256-PLUS",
             Mechanism::Apop => b"APOP",
             Mechanism::Ntlm => b"NTLM",
             Mechanism::Gssapi => b"GSSAPI",
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use super::{authenticate::Mechanism, ImapResponse};
+use super::{authenticate::Mechanism, compress, quota::Resource, thread::Algorithm, ImapResponse};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Response {
@@ -32,8 +32,8 @@ pub enum Capability {
     Enable,
     SearchRes,
     Sort,
-    Thread,       //THREAD=REFERENCES
-    ListExtended, //LIST-EXTENDED
+    Thread(Algorithm), //THREAD=<algorithm>
+    ListExtended,      //LIST-EXTENDED
     ESort,
     SortDisplay,      //SORT=DISPLAY
     SpecialUse,       //SPECIAL-USE
@@ -47,6 +47,16 @@ pub enum Capability {
     ObjectId,
     Preview,
     Utf8Accept,
+    Notify,
+    Metadata,
+    MetadataServer, //METADATA-SERVER
+    Quota,
+    QuotaRes(Resource), //QUOTA=RES-*
+    QuotaSet,
+    Compress(compress::Algorithm), //COMPRESS=<algorithm>
+    Partial,
+    MultiSearch,
+    SaveDate,
     Auth(Mechanism),
 }
 
@@ -58,6 +68,11 @@ impl Capability {
                 mechanism.serialize(buf);
                 return;
             }
+            Capability::Thread(algorithm) => {
+                buf.extend_from_slice(b"THREAD=");
+                algorithm.serialize(buf);
+                return;
+            }
             Capability::IMAP4rev2 => b"IMAP4rev2",
             Capability::IMAP4rev1 => b"IMAP4rev1",
             Capability::StartTLS => b"STARTTLS",
@@ -84,7 +99,6 @@ impl Capability {
             Capability::Enable => b"ENABLE",
             Capability::SearchRes => b"SEARCHRES",
             Capability::Sort => b"SORT",
-            Capability::Thread => b"THREAD=REFERENCES",
             Capability::ListExtended => b"LIST-EXTENDED",
             Capability::ESort => b"ESORT",
             Capability::SortDisplay => b"SORT=DISPLAY",
@@ -92,6 +106,24 @@ impl Capability {
             Capability::CreateSpecialUse => b"CREATE-SPECIAL-USE",
             Capability::Move => b"MOVE",
             Capability::Utf8Accept => b"UTF8=ACCEPT",
+            Capability::Notify => b"NOTIFY",
+            Capability::Metadata => b"METADATA",
+            Capability::MetadataServer => b"METADATA-SERVER",
+            Capability::Quota => b"QUOTA",
+            Capability::QuotaRes(resource) => {
+                buf.extend_from_slice(b"QUOTA=RES-");
+                resource.serialize(buf);
+                return;
+            }
+            Capability::QuotaSet => b"QUOTASET",
+            Capability::Partial => b"PARTIAL",
+            Capability::MultiSearch => b"MULTISEARCH",
+            Capability::SaveDate => b"SAVEDATE",
+            Capability::Compress(algorithm) => {
+                buf.extend_from_slice(b"COMPRESS=");
+                algorithm.serialize(buf);
+                return;
+            }
         });
     }
 
@@ -120,7 +152,9 @@ impl Capability {
                 Capability::Within,
                 Capability::SearchRes,
                 Capability::Sort,
-                Capability::Thread,
+                Capability::Thread(Algorithm::OrderedSubject),
+                Capability::Thread(Algorithm::References),
+                Capability::Thread(Algorithm::Refs),
                 Capability::ListExtended,
                 Capability::ESort,
                 Capability::SortDisplay,
@@ -133,9 +167,28 @@ impl Capability {
                 Capability::StatusSize,
                 Capability::ObjectId,
                 Capability::Preview,
+                Capability::Notify,
+                Capability::Metadata,
+                Capability::MetadataServer,
+                Capability::Quota,
+                Capability::QuotaRes(Resource::Storage),
+                Capability::QuotaRes(Resource::Message),
+                Capability::QuotaSet,
+                Capability::Partial,
+                Capability::MultiSearch,
+                Capability::SaveDate,
             ]);
         } else {
+            // Channel binding requires TLS
+            if is_tls {
+                capabilities.extend([
+                    Capability::Auth(Mechanism::ScramSha256Plus),
+                    Capability::Auth(Mechanism::ScramSha1Plus),
+                ]);
+            }
             capabilities.extend([
+                Capability::Auth(Mechanism::ScramSha256),
+                Capability::Auth(Mechanism::ScramSha1),
                 Capability::Auth(Mechanism::OAuthBearer),
                 Capability::Auth(Mechanism::Plain),
             ]);
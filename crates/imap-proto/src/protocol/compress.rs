@@ -0,0 +1,24 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub algorithm: Algorithm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Deflate,
+}
+
+impl Algorithm {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(match self {
+            Algorithm::Deflate => b"DEFLATE",
+        });
+    }
+}
@@ -0,0 +1,128 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::utf7::utf7_encode;
+
+use super::{literal_string, quoted_or_literal_string, quoted_string};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetArguments {
+    pub tag: String,
+    pub mailbox_name: String,
+    pub entries: Vec<String>,
+    pub max_size: Option<usize>,
+    pub depth: Depth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetArguments {
+    pub tag: String,
+    pub mailbox_name: String,
+    pub entries: Vec<(String, Option<Vec<u8>>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Depth {
+    #[default]
+    Zero,
+    One,
+    Infinity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub mailbox_name: String,
+    pub entries: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl Depth {
+    pub fn matches(&self, entry: &str, name: &str) -> bool {
+        if entry == name {
+            true
+        } else if let Some(child) = name
+            .strip_prefix(entry)
+            .and_then(|child| child.strip_prefix('/'))
+        {
+            match self {
+                Depth::Zero => false,
+                Depth::One => !child.contains('/'),
+                Depth::Infinity => true,
+            }
+        } else {
+            false
+        }
+    }
+}
+
+impl Response {
+    pub fn serialize(&self, buf: &mut Vec<u8>, is_rev2: bool) {
+        buf.extend_from_slice(b"* METADATA ");
+        if is_rev2 {
+            quoted_string(buf, &self.mailbox_name);
+        } else {
+            quoted_string(buf, &utf7_encode(&self.mailbox_name));
+        }
+        buf.extend_from_slice(b" (");
+        for (pos, (name, value)) in self.entries.iter().enumerate() {
+            if pos > 0 {
+                buf.push(b' ');
+            }
+            buf.extend_from_slice(name.as_bytes());
+            buf.push(b' ');
+            match value {
+                Some(value) => match std::str::from_utf8(value) {
+                    Ok(value) if !value.contains('\0') => quoted_or_literal_string(buf, value),
+                    _ => literal_string(buf, value),
+                },
+                None => buf.extend_from_slice(b"NIL"),
+            }
+        }
+        buf.extend_from_slice(b")\r\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Depth, Response};
+
+    #[test]
+    fn serialize_metadata() {
+        let mut buf = Vec::new();
+        Response {
+            mailbox_name: "INBOX".to_string(),
+            entries: vec![
+                (
+                    "/private/comment".to_string(),
+                    Some(b"My own comment".to_vec()),
+                ),
+                ("/shared/comment".to_string(), None),
+                ("/shared/vendor/x".to_string(), Some(b"a\r\nb".to_vec())),
+            ],
+        }
+        .serialize(&mut buf, true);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                "* METADATA \"INBOX\" (/private/comment \"My own comment\" ",
+                "/shared/comment NIL /shared/vendor/x {4}\r\na\r\nb)\r\n"
+            )
+        );
+    }
+
+    #[test]
+    fn depth_matches() {
+        for (depth, name, expected) in [
+            (Depth::Zero, "/private/a", true),
+            (Depth::Zero, "/private/a/b", false),
+            (Depth::One, "/private/a/b", true),
+            (Depth::One, "/private/a/b/c", false),
+            (Depth::Infinity, "/private/a/b/c", true),
+            (Depth::Infinity, "/private/ab", false),
+        ] {
+            assert_eq!(depth.matches("/private/a", name), expected, "{name}");
+        }
+    }
+}
@@ -16,6 +16,7 @@ pub mod acl;
 pub mod append;
 pub mod authenticate;
 pub mod capability;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -24,7 +25,10 @@ pub mod expunge;
 pub mod fetch;
 pub mod list;
 pub mod login;
+pub mod metadata;
 pub mod namespace;
+pub mod notify;
+pub mod quota;
 pub mod rename;
 pub mod search;
 pub mod select;
@@ -147,6 +151,45 @@ impl Sequence {
     }
 }
 
+// RFC 9394 - PARTIAL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialRange {
+    pub start: u32,
+    pub end: u32,
+    pub from_end: bool,
+}
+
+impl PartialRange {
+    pub fn apply<'x, T>(&self, items: &'x [T]) -> &'x [T] {
+        let (from, to) = if !self.from_end {
+            ((self.start - 1) as usize, self.end as usize)
+        } else {
+            (
+                items.len().saturating_sub(self.end as usize),
+                items.len().saturating_sub((self.start - 1) as usize),
+            )
+        };
+        let to = std::cmp::min(to, items.len());
+        if from < to {
+            &items[from..to]
+        } else {
+            &[]
+        }
+    }
+
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        if self.from_end {
+            buf.push(b'-');
+        }
+        buf.extend_from_slice(self.start.to_string().as_bytes());
+        buf.push(b':');
+        if self.from_end {
+            buf.push(b'-');
+        }
+        buf.extend_from_slice(self.end.to_string().as_bytes());
+    }
+}
+
 pub trait ImapResponse {
     fn serialize(self) -> Vec<u8>;
 }
@@ -379,6 +422,31 @@ impl ResponseCode {
                 return;
             }
             ResponseCode::UseAttr => b"USEATTR",
+            ResponseCode::BadEvent { supported } => {
+                buf.extend_from_slice(b"BADEVENT (");
+                for (pos, event) in supported.iter().enumerate() {
+                    if pos > 0 {
+                        buf.push(b' ');
+                    }
+                    event.serialize(buf);
+                }
+                buf.push(b')');
+                return;
+            }
+            ResponseCode::NotificationOverflow => b"NOTIFICATIONOVERFLOW",
+            ResponseCode::MetadataLongEntries { size } => {
+                buf.extend_from_slice(b"METADATA LONGENTRIES ");
+                buf.extend_from_slice(size.to_string().as_bytes());
+                return;
+            }
+            ResponseCode::MetadataMaxSize { size } => {
+                buf.extend_from_slice(b"METADATA MAXSIZE ");
+                buf.extend_from_slice(size.to_string().as_bytes());
+                return;
+            }
+            ResponseCode::MetadataTooMany => b"METADATA TOOMANY",
+            ResponseCode::MetadataNoPrivate => b"METADATA NOPRIVATE",
+            ResponseCode::CompressionActive => b"COMPRESSIONACTIVE",
         });
     }
 }
@@ -507,6 +575,14 @@ impl Display for Command {
             Command::MyRights => write!(f, "MYRIGHTS"),
             Command::Unauthenticate => write!(f, "UNAUTHENTICATE"),
             Command::Id => write!(f, "ID"),
+            Command::Notify => write!(f, "NOTIFY"),
+            Command::GetMetadata => write!(f, "GETMETADATA"),
+            Command::SetMetadata => write!(f, "SETMETADATA"),
+            Command::GetQuota => write!(f, "GETQUOTA"),
+            Command::GetQuotaRoot => write!(f, "GETQUOTAROOT"),
+            Command::SetQuota => write!(f, "SETQUOTA"),
+            Command::Compress => write!(f, "COMPRESS"),
+            Command::ESearch => write!(f, "ESEARCH"),
         }
     }
 }
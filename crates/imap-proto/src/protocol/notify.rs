@@ -0,0 +1,130 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use super::fetch;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub status: bool,
+    // An empty list is sent as NOTIFY NONE
+    pub groups: Vec<EventGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventGroup {
+    pub filter: Filter,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Selected,
+    SelectedDelayed,
+    Personal,
+    Inboxes,
+    Subscribed,
+    Subtree(Vec<String>),
+    Mailboxes(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    MessageNew(Vec<fetch::Attribute>),
+    MessageExpunge,
+    FlagChange,
+    AnnotationChange,
+    MailboxName,
+    SubscriptionChange,
+    MailboxMetadataChange,
+    ServerMetadataChange,
+}
+
+impl Arguments {
+    pub fn selected(&self) -> Option<&EventGroup> {
+        self.groups.iter().find(|group| group.filter.is_selected())
+    }
+}
+
+impl EventGroup {
+    pub fn has_message_events(&self) -> bool {
+        self.events
+            .iter()
+            .any(|event| matches!(event, Event::MessageNew(_) | Event::MessageExpunge))
+    }
+
+    pub fn has_event(&self, event: &Event) -> bool {
+        self.events
+            .iter()
+            .any(|item| std::mem::discriminant(item) == std::mem::discriminant(event))
+    }
+
+    pub fn new_message_attributes(&self) -> Option<&[fetch::Attribute]> {
+        self.events.iter().find_map(|event| match event {
+            Event::MessageNew(attributes) => Some(attributes.as_slice()),
+            _ => None,
+        })
+    }
+}
+
+impl Filter {
+    pub fn is_selected(&self) -> bool {
+        matches!(self, Filter::Selected | Filter::SelectedDelayed)
+    }
+}
+
+impl Event {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(match self {
+            Event::MessageNew(_) => b"MessageNew",
+            Event::MessageExpunge => b"MessageExpunge",
+            Event::FlagChange => b"FlagChange",
+            Event::AnnotationChange => b"AnnotationChange",
+            Event::MailboxName => b"MailboxName",
+            Event::SubscriptionChange => b"SubscriptionChange",
+            Event::MailboxMetadataChange => b"MailboxMetadataChange",
+            Event::ServerMetadataChange => b"ServerMetadataChange",
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::notify::{Event, EventGroup, Filter},
+        ResponseCode,
+    };
+
+    #[test]
+    fn serialize_bad_event() {
+        let mut buf = Vec::new();
+        ResponseCode::BadEvent {
+            supported: vec![Event::MessageNew(vec![]), Event::MessageExpunge],
+        }
+        .serialize(&mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "BADEVENT (MessageNew MessageExpunge)"
+        );
+    }
+
+    #[test]
+    fn event_lookup() {
+        let group = EventGroup {
+            filter: Filter::Personal,
+            events: vec![
+                Event::MessageNew(vec![]),
+                Event::MessageExpunge,
+                Event::MailboxName,
+            ],
+        };
+        assert!(group.has_message_events());
+        assert!(group.has_event(&Event::MailboxName));
+        assert!(!group.has_event(&Event::FlagChange));
+        assert_eq!(group.new_message_attributes(), Some(&[][..]));
+        assert!(!group.filter.is_selected());
+    }
+}
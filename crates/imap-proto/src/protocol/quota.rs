@@ -0,0 +1,142 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::utf7::utf7_encode;
+
+use super::quoted_string;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    // Quota root for GETQUOTA, mailbox name for GETQUOTAROOT
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetArguments {
+    pub tag: String,
+    pub quota_root: String,
+    pub limits: Vec<(Resource, u64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Storage,
+    Message,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaResource {
+    pub resource: Resource,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaResponse {
+    pub quota_root: String,
+    pub resources: Vec<QuotaResource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaRootResponse {
+    pub mailbox_name: String,
+    pub quota_roots: Vec<String>,
+    pub quotas: Vec<QuotaResponse>,
+}
+
+impl Resource {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(match self {
+            Resource::Storage => b"STORAGE",
+            Resource::Message => b"MESSAGE",
+        });
+    }
+}
+
+impl QuotaResponse {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(b"* QUOTA ");
+        quoted_string(buf, &self.quota_root);
+        buf.extend_from_slice(b" (");
+        for (pos, resource) in self.resources.iter().enumerate() {
+            if pos > 0 {
+                buf.push(b' ');
+            }
+            resource.resource.serialize(buf);
+            buf.push(b' ');
+            buf.extend_from_slice(resource.usage.to_string().as_bytes());
+            buf.push(b' ');
+            buf.extend_from_slice(resource.limit.to_string().as_bytes());
+        }
+        buf.extend_from_slice(b")\r\n");
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(32 + self.quota_root.len());
+        self.serialize(&mut buf);
+        buf
+    }
+}
+
+impl QuotaRootResponse {
+    pub fn into_bytes(self, is_rev2: bool) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(64 + self.mailbox_name.len());
+        buf.extend_from_slice(b"* QUOTAROOT ");
+        if is_rev2 {
+            quoted_string(&mut buf, &self.mailbox_name);
+        } else {
+            quoted_string(&mut buf, &utf7_encode(&self.mailbox_name));
+        }
+        for quota_root in &self.quota_roots {
+            buf.push(b' ');
+            quoted_string(&mut buf, quota_root);
+        }
+        buf.extend_from_slice(b"\r\n");
+        for quota in &self.quotas {
+            quota.serialize(&mut buf);
+        }
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuotaResource, QuotaResponse, QuotaRootResponse, Resource};
+
+    #[test]
+    fn serialize_quota() {
+        assert_eq!(
+            String::from_utf8(
+                QuotaRootResponse {
+                    mailbox_name: "INBOX".to_string(),
+                    quota_roots: vec!["".to_string()],
+                    quotas: vec![QuotaResponse {
+                        quota_root: "".to_string(),
+                        resources: vec![
+                            QuotaResource {
+                                resource: Resource::Storage,
+                                usage: 10,
+                                limit: 512,
+                            },
+                            QuotaResource {
+                                resource: Resource::Message,
+                                usage: 3,
+                                limit: 1000,
+                            },
+                        ],
+                    }],
+                }
+                .into_bytes(true)
+            )
+            .unwrap(),
+            concat!(
+                "* QUOTAROOT \"INBOX\" \"\"\r\n",
+                "* QUOTA \"\" (STORAGE 10 512 MESSAGE 3 1000)\r\n"
+            )
+        );
+    }
+}
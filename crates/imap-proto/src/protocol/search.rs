@@ -6,7 +6,7 @@
 
 use store::fts::{FilterItem, FilterType};
 
-use super::{quoted_string, serialize_sequence, Flag, Sequence};
+use super::{quoted_string, serialize_sequence, Flag, PartialRange, Sequence};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Arguments {
@@ -17,6 +17,24 @@ pub struct Arguments {
     pub filter: Vec<Filter>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiSearchArguments {
+    pub sources: Vec<Source>,
+    pub arguments: Arguments,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Selected,
+    SelectedDelayed,
+    Inboxes,
+    Personal,
+    Subscribed,
+    Subtree(Vec<String>),
+    SubtreeOne(Vec<String>),
+    Mailboxes(Vec<String>),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Sort {
     Arrival,
@@ -46,6 +64,9 @@ pub struct Response {
     pub max: Option<u32>,
     pub count: Option<u32>,
     pub highest_modseq: Option<u64>,
+    pub partial: Option<PartialRange>,
+    // Mailbox name and UIDVALIDITY for multi-mailbox searches
+    pub mailbox: Option<(String, u32)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -56,6 +77,7 @@ pub enum ResultOption {
     Count,
     Save,
     Context,
+    Partial(PartialRange),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -112,6 +134,12 @@ pub enum Filter {
     // RFC 8474 - ObjectID
     EmailId(String),
     ThreadId(String),
+
+    // RFC 8514 - SAVEDATE
+    SavedBefore(i64),
+    SavedOn(i64),
+    SavedSince(i64),
+    SaveDateSupported,
 }
 
 impl FilterItem for Filter {
@@ -154,6 +182,15 @@ pub enum ModSeqEntry {
     None,
 }
 
+impl Arguments {
+    pub fn partial(&self) -> Option<PartialRange> {
+        self.result_options.iter().find_map(|option| match option {
+            ResultOption::Partial(range) => Some(*range),
+            _ => None,
+        })
+    }
+}
+
 impl Filter {
     pub fn seq_saved_search() -> Filter {
         Filter::Sequence(Sequence::SavedSearch, false)
@@ -170,6 +207,12 @@ impl Response {
         if self.is_esearch {
             buf.extend_from_slice(b"* ESEARCH (TAG ");
             quoted_string(&mut buf, tag);
+            if let Some((mailbox_name, uid_validity)) = &self.mailbox {
+                buf.extend_from_slice(b" MAILBOX ");
+                quoted_string(&mut buf, mailbox_name);
+                buf.extend_from_slice(b" UIDVALIDITY ");
+                buf.extend_from_slice(uid_validity.to_string().as_bytes());
+            }
             buf.extend_from_slice(b")");
             if self.is_uid {
                 buf.extend_from_slice(b" UID");
@@ -186,7 +229,17 @@ impl Response {
                 buf.extend_from_slice(b" MAX ");
                 buf.extend_from_slice(max.to_string().as_bytes());
             }
-            if !self.ids.is_empty() {
+            if let Some(partial) = &self.partial {
+                buf.extend_from_slice(b" PARTIAL (");
+                partial.serialize(&mut buf);
+                if !self.ids.is_empty() {
+                    buf.push(b' ');
+                    serialize_sequence(&mut buf, &self.ids);
+                } else {
+                    buf.extend_from_slice(b" NIL");
+                }
+                buf.push(b')');
+            } else if !self.ids.is_empty() {
                 buf.extend_from_slice(b" ALL ");
                 serialize_sequence(&mut buf, &self.ids);
             }
@@ -219,6 +272,7 @@ impl Response {
 
 #[cfg(test)]
 mod tests {
+    use crate::protocol::PartialRange;
 
     #[test]
     fn serialize_search() {
@@ -233,6 +287,8 @@ mod tests {
                     max: 11.into(),
                     count: 3.into(),
                     highest_modseq: None,
+                    partial: None,
+                    mailbox: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") COUNT 3 MIN 2 MAX 11 ALL 2,10:11\r\n",),
@@ -250,6 +306,8 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: None,
+                    partial: None,
+                    mailbox: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") ALL 1:3,5,10:13,90,92:99\r\n",),
@@ -265,6 +323,8 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: None,
+                    partial: None,
+                    mailbox: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\")\r\n",),
@@ -280,6 +340,8 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: 12345.into(),
+                    partial: None,
+                    mailbox: None,
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") ALL 10:13,21 MODSEQ 12345\r\n",),
@@ -294,4 +356,56 @@ mod tests {
             assert_eq!(response_v1, expected_v1);
         }
     }
+
+    #[test]
+    fn serialize_esearch_partial() {
+        for (response, expected) in [
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![200, 201, 202, 250],
+                    min: None,
+                    max: None,
+                    count: 1000.into(),
+                    highest_modseq: None,
+                    partial: Some(PartialRange {
+                        start: 1,
+                        end: 4,
+                        from_end: true,
+                    }),
+                    mailbox: None,
+                },
+                "* ESEARCH (TAG \"A1\") UID COUNT 1000 PARTIAL (-1:-4 200:202,250)\r\n",
+            ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![],
+                    min: None,
+                    max: None,
+                    count: None,
+                    highest_modseq: None,
+                    partial: Some(PartialRange {
+                        start: 100,
+                        end: 200,
+                        from_end: false,
+                    }),
+                    mailbox: Some(("Archive".to_string(), 12345)),
+                },
+                concat!(
+                    "* ESEARCH (TAG \"A1\" MAILBOX \"Archive\" UIDVALIDITY 12345) ",
+                    "UID PARTIAL (100:200 NIL)\r\n"
+                ),
+            ),
+        ] {
+            assert_eq!(
+                String::from_utf8(response.serialize("A1")).unwrap(),
+                expected
+            );
+        }
+    }
 }
@@ -13,16 +13,107 @@ pub struct Arguments {
     pub algorithm: Algorithm,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Algorithm {
     OrderedSubject,
     References,
+    Refs,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Response {
     pub is_uid: bool,
-    pub threads: Vec<Vec<u32>>,
+    pub threads: Vec<Thread>,
+}
+
+// A node of a thread tree, dummy nodes (messages referenced but not present
+// in the result set) have no id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Thread {
+    pub id: Option<u32>,
+    pub children: Vec<Thread>,
+}
+
+impl Algorithm {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(match self {
+            Algorithm::OrderedSubject => b"ORDEREDSUBJECT",
+            Algorithm::References => b"REFERENCES",
+            Algorithm::Refs => b"REFS",
+        });
+    }
+}
+
+impl Thread {
+    pub fn new(id: u32) -> Self {
+        Thread {
+            id: Some(id),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_children(mut self, children: Vec<Thread>) -> Self {
+        self.children = children;
+        self
+    }
+
+    // Serializes a thread as described in RFC 5256 section 4, a single child
+    // continues the parent's list while multiple children are nested. Nested
+    // lists are written using an explicit stack as threads can be arbitrarily
+    // deep.
+    fn serialize(&self, buf: &mut Vec<u8>) {
+        let mut stack = vec![Some(self)];
+
+        while let Some(item) = stack.pop() {
+            let mut node = match item {
+                Some(node) => node,
+                None => {
+                    buf.push(b')');
+                    continue;
+                }
+            };
+            let mut is_first = true;
+            buf.push(b'(');
+
+            loop {
+                if let Some(id) = node.id {
+                    if !is_first {
+                        buf.push(b' ');
+                    }
+                    buf.extend_from_slice(id.to_string().as_bytes());
+                    is_first = false;
+                }
+                match node.children.len() {
+                    0 => {
+                        buf.push(b')');
+                        break;
+                    }
+                    1 => {
+                        node = &node.children[0];
+                    }
+                    _ => {
+                        if !is_first {
+                            buf.push(b' ');
+                        }
+                        stack.push(None);
+                        stack.extend(node.children.iter().rev().map(Some));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Thread {
+    fn drop(&mut self) {
+        // Children are dropped iteratively to avoid a stack overflow on deep
+        // threads
+        let mut children = std::mem::take(&mut self.children);
+        while let Some(mut child) = children.pop() {
+            children.append(&mut child.children);
+        }
+    }
 }
 
 impl ImapResponse for Response {
@@ -30,14 +121,7 @@ impl ImapResponse for Response {
         let mut buf = Vec::with_capacity(64);
         buf.extend_from_slice(b"* THREAD ");
         for thread in &self.threads {
-            buf.push(b'(');
-            for (pos, id) in thread.iter().enumerate() {
-                if pos > 0 {
-                    buf.push(b' ');
-                }
-                buf.extend_from_slice(id.to_string().as_bytes());
-            }
-            buf.push(b')');
+            thread.serialize(&mut buf);
         }
         buf.extend_from_slice(b"\r\n");
         buf
@@ -46,7 +130,7 @@ impl ImapResponse for Response {
 
 #[cfg(test)]
 mod tests {
-    use crate::protocol::ImapResponse;
+    use crate::protocol::{thread::Thread, ImapResponse};
 
     #[test]
     fn serialize_thread() {
@@ -54,12 +138,60 @@ mod tests {
             String::from_utf8(
                 super::Response {
                     is_uid: true,
-                    threads: vec![vec![2, 10, 11], vec![49], vec![1, 3]],
+                    threads: vec![
+                        Thread::new(2).with_children(vec![
+                            Thread::new(10).with_children(vec![Thread::new(11)])
+                        ]),
+                        Thread::new(49),
+                        Thread::new(1).with_children(vec![Thread::new(3)]),
+                    ],
                 }
                 .serialize()
             )
             .unwrap(),
             concat!("* THREAD (2 10 11)(49)(1 3)\r\n",)
         );
+
+        // Example from RFC 5256 section 4
+        assert_eq!(
+            String::from_utf8(
+                super::Response {
+                    is_uid: false,
+                    threads: vec![
+                        Thread::new(2),
+                        Thread::new(3).with_children(vec![Thread::new(6).with_children(vec![
+                            Thread::new(4).with_children(vec![Thread::new(23)]),
+                            Thread::new(44).with_children(vec![
+                                Thread::new(7).with_children(vec![Thread::new(96)])
+                            ]),
+                        ])]),
+                        Thread::default().with_children(vec![Thread::new(3), Thread::new(5)]),
+                    ],
+                }
+                .serialize()
+            )
+            .unwrap(),
+            "* THREAD (2)(3 6 (4 23)(44 7 96))((3)(5))\r\n"
+        );
+    }
+
+    #[test]
+    fn serialize_deep_thread() {
+        // Deep threads are serialized and dropped without recursion
+        let mut thread = Thread::new(0);
+        for id in 1..100_000 {
+            thread = Thread::new(id).with_children(vec![Thread::new(id + 100_000), thread]);
+        }
+        let response = String::from_utf8(
+            super::Response {
+                is_uid: false,
+                threads: vec![thread],
+            }
+            .serialize(),
+        )
+        .unwrap();
+        assert!(response.starts_with("* THREAD (99999 (199999)(99998 (199998)("));
+        assert!(response.contains("(1 (100001)(0))"));
+        assert_eq!(response.matches('(').count(), response.matches(')').count());
     }
 }
@@ -13,10 +13,10 @@ use imap_proto::{
 };
 use jmap::auth::rate_limit::ConcurrencyLimiters;
 
-use super::{SelectedMailbox, Session, SessionData, State};
+use super::{SelectedMailbox, Session, SessionData, State, Upgrade};
 
 impl<T: SessionStream> Session<T> {
-    pub async fn ingest(&mut self, bytes: &[u8]) -> crate::Result<bool> {
+    pub async fn ingest(&mut self, bytes: &[u8]) -> crate::Result<Option<Upgrade>> {
         /*for line in String::from_utf8_lossy(bytes).split("\r\n") {
             let c = println!("{}", line);
         }*/
@@ -110,9 +110,35 @@ impl<T: SessionStream> Session<T> {
                 Command::Thread(is_uid) => {
                     self.handle_thread(request, is_uid).await?;
                 }
+                Command::ESearch => {
+                    self.handle_esearch(request).await?;
+                }
                 Command::Idle => {
                     self.handle_idle(request).await?;
                 }
+                Command::Notify => {
+                    self.handle_notify(request).await?;
+                }
+                Command::GetMetadata => {
+                    self.handle_get_metadata(request).await?;
+                }
+                Command::SetMetadata => {
+                    self.handle_set_metadata(request).await?;
+                }
+                Command::GetQuota => {
+                    self.handle_get_quota(request).await?;
+                }
+                Command::GetQuotaRoot => {
+                    self.handle_get_quota_root(request).await?;
+                }
+                Command::SetQuota => {
+                    self.handle_set_quota(request).await?;
+                }
+                Command::Compress => {
+                    if let Some(upgrade) = self.handle_compress(request).await? {
+                        return Ok(Some(upgrade));
+                    }
+                }
                 Command::Subscribe => {
                     self.handle_subscribe(request, true).await?;
                 }
@@ -142,7 +168,7 @@ impl<T: SessionStream> Session<T> {
                                 .into_bytes(),
                         )
                         .await
-                        .map(|_| true);
+                        .map(|_| Some(Upgrade::Tls));
                 }
                 Command::Noop => {
                     self.handle_noop(request).await?;
@@ -182,7 +208,7 @@ impl<T: SessionStream> Session<T> {
                 .await?;
         }
 
-        Ok(false)
+        Ok(None)
     }
 }
 
@@ -237,7 +263,10 @@ impl<T: SessionStream> Session<T> {
         match &request.command {
             Command::Capability | Command::Noop | Command::Logout | Command::Id => Ok(request),
             Command::StartTls => {
-                if !self.is_tls {
+                if self.is_compressed {
+                    Err(StatusResponse::no("TLS is not available after COMPRESS.")
+                        .with_tag(request.tag))
+                } else if !self.is_tls {
                     if self.instance.acceptor.is_tls() {
                         Ok(request)
                     } else {
@@ -282,6 +311,14 @@ impl<T: SessionStream> Session<T> {
             | Command::Status
             | Command::Append
             | Command::Idle
+            | Command::Notify
+            | Command::GetMetadata
+            | Command::SetMetadata
+            | Command::GetQuota
+            | Command::GetQuotaRoot
+            | Command::SetQuota
+            | Command::Compress
+            | Command::ESearch
             | Command::SetAcl
             | Command::DeleteAcl
             | Command::GetAcl
@@ -306,6 +343,23 @@ impl<T: SessionStream> Session<T> {
             | Command::Sort(_)
             | Command::Thread(_) => match state {
                 State::Selected { mailbox, .. } => {
+                    // SPDX-SnippetBegin
+                    // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+                    // SPDX-License-Identifier: LicenseRef-SEL
+                    if mailbox.deleted.is_some()
+                        && matches!(
+                            request.command,
+                            Command::Search(_) | Command::Sort(_) | Command::Thread(_)
+                        )
+                    {
+                        return Err(StatusResponse::no(
+                            "Not supported in the recently deleted messages folder.",
+                        )
+                        .with_tag(request.tag)
+                        .with_code(ResponseCode::Cannot));
+                    }
+                    // SPDX-SnippetEnd
+
                     if mailbox.is_select
                         || !matches!(
                             request.command,
@@ -558,6 +558,13 @@ impl<T: SessionStream> SessionData<T> {
                                     {
                                         changes.changed.push(mailbox_name.to_string());
                                     }
+                                    if mailbox.is_subscribed != old_mailbox.is_subscribed {
+                                        if mailbox.is_subscribed {
+                                            changes.subscribed.push(mailbox_name.to_string());
+                                        } else {
+                                            changes.unsubscribed.push(mailbox_name.to_string());
+                                        }
+                                    }
                                 }
                             } else {
                                 changes.added.push(mailbox_name.to_string());
@@ -128,6 +128,15 @@ impl<T: SessionStream> SessionData<T> {
         &self,
         mailbox: &SelectedMailbox,
     ) -> crate::op::Result<Option<u64>> {
+        // SPDX-SnippetBegin
+        // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+        // SPDX-License-Identifier: LicenseRef-SEL
+        // The recently deleted messages folder is a snapshot without change tracking
+        if mailbox.deleted.is_some() {
+            return Ok(None);
+        }
+        // SPDX-SnippetEnd
+
         // Obtain current modseq
         let modseq = self.get_modseq(mailbox.id.account_id).await?;
         if mailbox.state.lock().modseq != modseq {
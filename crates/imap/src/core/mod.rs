@@ -11,20 +11,25 @@ use std::{
 };
 
 use ahash::AHashMap;
-use common::listener::{limiter::InFlight, ServerInstance, SessionStream};
+use common::{
+    listener::{limiter::InFlight, ServerInstance, SessionStream},
+    scram::ScramServer,
+};
 use dashmap::DashMap;
 use imap_proto::{
-    protocol::{list::Attribute, ProtocolVersion},
+    protocol::{list::Attribute, notify, ProtocolVersion},
     receiver::Receiver,
     Command, ResponseCode, StatusResponse,
 };
 use jmap::{
     auth::{rate_limit::ConcurrencyLimiters, AccessToken},
+    email::undelete::DeletedEmail,
     JmapInstance, JMAP,
 };
+use jmap_proto::types::state::StateChange;
 use tokio::{
     io::{ReadHalf, WriteHalf},
-    sync::watch,
+    sync::{mpsc, watch},
 };
 use utils::lru_cache::LruCache;
 
@@ -62,6 +67,12 @@ pub struct Inner {
 
 pub struct IMAP {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upgrade {
+    Tls,
+    Compress,
+}
+
 pub struct Session<T: SessionStream> {
     pub jmap: JMAP,
     pub imap: Arc<Inner>,
@@ -70,15 +81,27 @@ pub struct Session<T: SessionStream> {
     pub version: ProtocolVersion,
     pub state: State<T>,
     pub is_tls: bool,
+    pub is_compressed: bool,
     pub is_condstore: bool,
     pub is_qresync: bool,
+    pub notify: Option<notify::Arguments>,
+    pub notify_rx: Option<mpsc::Receiver<StateChange>>,
     pub stream_rx: ReadHalf<T>,
     pub stream_tx: Arc<tokio::sync::Mutex<WriteHalf<T>>>,
     pub in_flight: InFlight,
     pub remote_addr: IpAddr,
+    pub channel_binding: Option<Vec<u8>>,
+    pub scram: Option<ScramState>,
     pub span: tracing::Span,
 }
 
+pub enum ScramState {
+    // Server-first message sent, waiting for the client proof
+    Challenge(Box<ScramServer>),
+    // Server signature sent, waiting for the client to acknowledge it
+    Verified(AccessToken),
+}
+
 pub struct SessionData<T: SessionStream> {
     pub account_id: u32,
     pub jmap: JMAP,
@@ -119,6 +142,7 @@ pub struct SelectedMailbox {
     pub saved_search: parking_lot::Mutex<SavedSearch>,
     pub is_select: bool,
     pub is_condstore: bool,
+    pub deleted: Option<Vec<DeletedEmail>>,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -156,6 +180,8 @@ pub struct MailboxSync {
     pub added: Vec<String>,
     pub changed: Vec<String>,
     pub deleted: Vec<String>,
+    pub subscribed: Vec<String>,
+    pub unsubscribed: Vec<String>,
 }
 
 pub enum SavedSearch {
@@ -6,13 +6,17 @@
 
 use std::sync::Arc;
 
-use common::listener::{stream::NullIo, SessionData, SessionManager, SessionStream};
+use common::listener::{
+    compress::DeflateStream, stream::NullIo, SessionData, SessionManager, SessionStream,
+};
 use imap_proto::{protocol::ProtocolVersion, receiver::Receiver};
 use jmap::JMAP;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_rustls::server::TlsStream;
 
-use super::{ImapSessionManager, Session, State};
+use crate::op::notify::recv_state_change;
+
+use super::{ImapSessionManager, Session, State, Upgrade};
 
 impl SessionManager for ImapSessionManager {
     #[allow(clippy::manual_async_fn)]
@@ -22,10 +26,22 @@ impl SessionManager for ImapSessionManager {
     ) -> impl std::future::Future<Output = ()> + Send {
         async move {
             if let Ok(mut session) = Session::new(session, self).await {
-                if session.handle_conn().await && session.instance.acceptor.is_tls() {
-                    if let Ok(mut session) = session.into_tls().await {
-                        session.handle_conn().await;
+                match session.handle_conn().await {
+                    Some(Upgrade::Tls) if session.instance.acceptor.is_tls() => {
+                        if let Ok(mut session) = session.into_tls().await {
+                            if session.handle_conn().await == Some(Upgrade::Compress) {
+                                if let Ok(mut session) = session.into_compressed().await {
+                                    session.handle_conn().await;
+                                }
+                            }
+                        }
                     }
+                    Some(Upgrade::Compress) => {
+                        if let Ok(mut session) = session.into_compressed().await {
+                            session.handle_conn().await;
+                        }
+                    }
+                    _ => (),
                 }
             }
         }
@@ -38,7 +54,7 @@ impl SessionManager for ImapSessionManager {
 }
 
 impl<T: SessionStream> Session<T> {
-    pub async fn handle_conn(&mut self) -> bool {
+    pub async fn handle_conn(&mut self) -> Option<Upgrade> {
         let mut buf = vec![0; 8192];
         let mut shutdown_rx = self.instance.shutdown_rx.clone();
 
@@ -55,9 +71,9 @@ impl<T: SessionStream> Session<T> {
                         Ok(Ok(bytes_read)) => {
                             if bytes_read > 0 {
                                 match self.ingest(&buf[..bytes_read]).await {
-                                    Ok(false) => (),
-                                    Ok(true) => {
-                                        return true;
+                                    Ok(None) => (),
+                                    Ok(upgrade) => {
+                                        return upgrade;
                                     }
                                     Err(_) => {
                                         tracing::debug!(parent: &self.span, event = "disconnect", "Disconnecting client.");
@@ -85,10 +101,18 @@ impl<T: SessionStream> Session<T> {
                     tracing::debug!(parent: &self.span, event = "shutdown", "IMAP server shutting down.");
                     break;
                 }
+                state_change = recv_state_change(&mut self.notify_rx) => {
+                    if let Some(state_change) = state_change {
+                        self.write_notify_changes(state_change).await;
+                    } else {
+                        self.notify = None;
+                        self.notify_rx = None;
+                    }
+                }
             };
         }
 
-        false
+        None
     }
 
     pub async fn new(
@@ -108,6 +132,7 @@ impl<T: SessionStream> Session<T> {
         let _ = session.stream.flush().await;
 
         // Split stream into read and write halves
+        let channel_binding = session.stream.tls_channel_binding();
         let (stream_rx, stream_tx) = tokio::io::split(session.stream);
         let jmap = JMAP::from(manager.imap.jmap_instance);
 
@@ -116,14 +141,19 @@ impl<T: SessionStream> Session<T> {
             version: ProtocolVersion::Rev1,
             state: State::NotAuthenticated { auth_failures: 0 },
             is_tls,
+            is_compressed: false,
             is_condstore: false,
             is_qresync: false,
+            notify: None,
+            notify_rx: None,
             jmap,
             imap: manager.imap.imap_inner,
             instance: session.instance,
             span: session.span,
             in_flight: session.in_flight,
             remote_addr: session.remote_ip,
+            channel_binding,
+            scram: None,
             stream_rx,
             stream_tx: Arc::new(tokio::sync::Mutex::new(stream_tx)),
         })
@@ -153,8 +183,9 @@ impl<T: SessionStream> Session<T> {
         };
 
         // Upgrade to TLS
-        let (stream_rx, stream_tx) =
-            tokio::io::split(self.instance.tls_accept(stream, &self.span).await?);
+        let stream = self.instance.tls_accept(stream, &self.span).await?;
+        let channel_binding = stream.tls_channel_binding();
+        let (stream_rx, stream_tx) = tokio::io::split(stream);
         let stream_tx = Arc::new(tokio::sync::Mutex::new(stream_tx));
 
         Ok(Session {
@@ -165,11 +196,68 @@ impl<T: SessionStream> Session<T> {
             version: self.version,
             state: state.try_replace_stream_tx(stream_tx.clone()).unwrap(),
             is_tls: true,
+            is_compressed: self.is_compressed,
+            is_condstore: self.is_condstore,
+            is_qresync: self.is_qresync,
+            notify: self.notify,
+            notify_rx: self.notify_rx,
+            span: self.span,
+            in_flight: self.in_flight,
+            remote_addr: self.remote_addr,
+            channel_binding,
+            scram: None,
+            stream_rx,
+            stream_tx,
+        })
+    }
+}
+
+impl<T: SessionStream> Session<T> {
+    pub async fn into_compressed(self) -> Result<Session<DeflateStream<T>>, ()> {
+        // Drop references to write half from state
+        let state = if let Some(state) =
+            self.state
+                .try_replace_stream_tx(Arc::new(tokio::sync::Mutex::new(
+                    tokio::io::split(NullIo::default()).1,
+                ))) {
+            state
+        } else {
+            tracing::debug!("Failed to obtain write half state.");
+            return Err(());
+        };
+
+        // Take ownership of WriteHalf and unsplit it from ReadHalf
+        let stream = if let Ok(stream_tx) =
+            Arc::try_unwrap(self.stream_tx).map(|mutex| mutex.into_inner())
+        {
+            self.stream_rx.unsplit(stream_tx)
+        } else {
+            tracing::debug!("Failed to take ownership of write half.");
+            return Err(());
+        };
+
+        // Enable compression
+        let (stream_rx, stream_tx) = tokio::io::split(DeflateStream::new(stream));
+        let stream_tx = Arc::new(tokio::sync::Mutex::new(stream_tx));
+
+        Ok(Session {
+            jmap: self.jmap,
+            imap: self.imap,
+            instance: self.instance,
+            receiver: self.receiver,
+            version: self.version,
+            state: state.try_replace_stream_tx(stream_tx.clone()).unwrap(),
+            is_tls: self.is_tls,
+            is_compressed: true,
             is_condstore: self.is_condstore,
             is_qresync: self.is_qresync,
+            notify: self.notify,
+            notify_rx: self.notify_rx,
             span: self.span,
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
+            channel_binding: self.channel_binding,
+            scram: self.scram,
             stream_rx,
             stream_tx,
         })
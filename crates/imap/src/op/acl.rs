@@ -6,7 +6,7 @@
 
 use std::sync::Arc;
 
-use common::listener::SessionStream;
+use common::{cluster::ClusterEvent, listener::SessionStream};
 use directory::QueryBy;
 use imap_proto::{
     protocol::acl::{
@@ -388,6 +388,11 @@ impl<T: SessionStream> Session<T> {
 
                     // Invalidate ACLs
                     data.jmap.inner.access_tokens.remove(&acl_account_id);
+                    data.jmap
+                        .smtp
+                        .inner
+                        .ipc
+                        .broadcast_cluster_event(ClusterEvent::InvalidatePrincipal(acl_account_id));
 
                     data.write_bytes(
                         StatusResponse::completed(command)
@@ -6,17 +6,20 @@
 
 use common::{
     config::server::ServerProtocol, listener::SessionStream, AuthFailureReason, AuthResult,
+    IMPERSONATION_SEPARATOR,
 };
+use directory::core::secret::ScramAlgorithm;
 use imap_proto::{
-    protocol::{authenticate::Mechanism, capability::Capability},
+    protocol::authenticate::{self, Mechanism},
     receiver::{self, Request},
     Command, ResponseCode, StatusResponse,
 };
+use jmap::auth::AccessToken;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
 
-use crate::core::{Session, SessionData, State};
+use crate::core::{ScramState, Session, SessionData, State};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> crate::OpResult {
@@ -27,7 +30,10 @@ impl<T: SessionStream> Session<T> {
                         match base64_decode(args.params.pop().unwrap().as_bytes()) {
                             Some(challenge) => {
                                 let result = if args.mechanism == Mechanism::Plain {
-                                    decode_challenge_plain(&challenge)
+                                    decode_challenge_plain(
+                                        &challenge,
+                                        self.jmap.core.jmap.impersonation_group.is_some(),
+                                    )
                                 } else {
                                     decode_challenge_oauth(&challenge)
                                 };
@@ -64,6 +70,10 @@ impl<T: SessionStream> Session<T> {
                         self.write_bytes(b"+ \"\"\r\n".to_vec()).await
                     }
                 }
+                Mechanism::ScramSha1
+                | Mechanism::ScramSha1Plus
+                | Mechanism::ScramSha256
+                | Mechanism::ScramSha256Plus => self.handle_scram(args).await,
                 _ => {
                     self.write_bytes(
                         StatusResponse::no("Authentication mechanism not supported.")
@@ -78,12 +88,127 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
-    pub async fn authenticate(
+    async fn handle_scram(&mut self, mut args: authenticate::Arguments) -> crate::OpResult {
+        let response = args.params.pop();
+        match (self.scram.take(), response) {
+            (_, Some(response)) if response == "*" => {
+                self.write_bytes(
+                    StatusResponse::bad("Authentication cancelled.")
+                        .with_tag(args.tag)
+                        .into_bytes(),
+                )
+                .await
+            }
+            (None, None) => {
+                // Request the client-first message
+                self.scram_continue(args.tag, args.mechanism, "").await
+            }
+            (None, Some(client_first)) => {
+                self.throttle_authentication().await?;
+
+                let (algorithm, is_plus) = match args.mechanism {
+                    Mechanism::ScramSha1 => (ScramAlgorithm::Sha1, false),
+                    Mechanism::ScramSha1Plus => (ScramAlgorithm::Sha1, true),
+                    Mechanism::ScramSha256 => (ScramAlgorithm::Sha256, false),
+                    _ => (ScramAlgorithm::Sha256, true),
+                };
+                let result = match base64_decode(client_first.as_bytes()) {
+                    Some(client_first) => {
+                        self.jmap
+                            .scram_begin(
+                                algorithm,
+                                is_plus,
+                                &client_first,
+                                self.channel_binding.clone(),
+                            )
+                            .await
+                    }
+                    None => Ok(None),
+                };
+
+                match result {
+                    Ok(Some(server)) => {
+                        let server_first = server.challenge();
+                        self.scram = Some(ScramState::Challenge(Box::new(server)));
+                        self.scram_continue(args.tag, args.mechanism, &server_first)
+                            .await
+                    }
+                    Ok(None) => {
+                        self.write_bytes(
+                            StatusResponse::no("Invalid SCRAM client-first message.")
+                                .with_tag(args.tag)
+                                .with_code(ResponseCode::Parse)
+                                .into_bytes(),
+                        )
+                        .await
+                    }
+                    Err(_) => {
+                        self.write_bytes(
+                            StatusResponse::no("Temporary authentication failure.")
+                                .with_tag(args.tag)
+                                .with_code(ResponseCode::Unavailable)
+                                .into_bytes(),
+                        )
+                        .await
+                    }
+                }
+            }
+            (Some(ScramState::Challenge(server)), Some(client_final)) => {
+                let client_final = base64_decode(client_final.as_bytes()).unwrap_or_default();
+                match self
+                    .jmap
+                    .authenticate_scram(
+                        *server,
+                        &client_final,
+                        self.remote_addr,
+                        ServerProtocol::Imap,
+                    )
+                    .await
+                {
+                    AuthResult::Success((access_token, server_final)) => {
+                        // Send the server signature and wait for an empty response
+                        self.scram = Some(ScramState::Verified(access_token));
+                        self.scram_continue(args.tag, args.mechanism, &server_final)
+                            .await
+                    }
+                    AuthResult::Failure(AuthFailureReason::Banned) => Err(()),
+                    AuthResult::Failure(_) => {
+                        self.complete_authentication(None, false, args.tag).await
+                    }
+                }
+            }
+            (Some(ScramState::Verified(access_token)), None) => {
+                self.complete_authentication(Some(access_token), false, args.tag)
+                    .await
+            }
+            (Some(ScramState::Challenge(_)), None) | (Some(ScramState::Verified(_)), Some(_)) => {
+                self.write_bytes(
+                    StatusResponse::bad("Unexpected SCRAM response.")
+                        .with_tag(args.tag)
+                        .into_bytes(),
+                )
+                .await
+            }
+        }
+    }
+
+    async fn scram_continue(
         &mut self,
-        credentials: Credentials<String>,
         tag: String,
-    ) -> crate::Result<()> {
-        // Throttle authentication requests
+        mechanism: Mechanism,
+        challenge: &str,
+    ) -> crate::OpResult {
+        self.receiver.request = receiver::Request {
+            tag,
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+        self.write_bytes(format!("+ {challenge}\r\n").into_bytes())
+            .await
+    }
+
+    async fn throttle_authentication(&mut self) -> crate::Result<()> {
         if self
             .jmap
             .is_auth_allowed_soft(&self.remote_addr)
@@ -99,49 +224,66 @@ impl<T: SessionStream> Session<T> {
                 event = "disconnect",
                 "Too many authentication attempts, disconnecting.",
             );
-            return Err(());
+            Err(())
+        } else {
+            Ok(())
         }
+    }
+
+    pub async fn authenticate(
+        &mut self,
+        credentials: Credentials<String>,
+        tag: String,
+    ) -> crate::Result<()> {
+        // Throttle authentication requests
+        self.throttle_authentication().await?;
 
         // Authenticate
         let mut is_totp_error = false;
-        let access_token = match credentials {
-            Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
-                match self
-                    .jmap
+        let result = match credentials {
+            Credentials::Plain { username, secret } => {
+                self.jmap
                     .authenticate_plain(&username, &secret, self.remote_addr, ServerProtocol::Imap)
                     .await
-                {
-                    AuthResult::Success(token) => Some(token),
-                    AuthResult::Failure(
-                        AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
-                    ) => None,
-                    AuthResult::Failure(AuthFailureReason::MissingTotp) => {
-                        is_totp_error = true;
-                        None
-                    }
-                    AuthResult::Failure(AuthFailureReason::Banned) => return Err(()),
-                }
+            }
+            Credentials::XOauth2 { username, secret } => {
+                self.jmap
+                    .authenticate_bearer(
+                        &secret,
+                        Some(&username),
+                        self.remote_addr,
+                        ServerProtocol::Imap,
+                    )
+                    .await
             }
             Credentials::OAuthBearer { token } => {
-                match self
-                    .jmap
-                    .validate_access_token("access_token", &token)
+                self.jmap
+                    .authenticate_bearer(&token, None, self.remote_addr, ServerProtocol::Imap)
                     .await
-                {
-                    Ok((account_id, _, _)) => self.jmap.get_access_token(account_id).await,
-                    Err(err) => {
-                        tracing::debug!(
-                            parent: &self.span,
-                            context = "authenticate",
-                            err = err,
-                            "Failed to validate access token."
-                        );
-                        None
-                    }
-                }
             }
         };
+        let access_token = match result {
+            AuthResult::Success(token) => Some(token),
+            AuthResult::Failure(
+                AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
+            ) => None,
+            AuthResult::Failure(AuthFailureReason::MissingTotp) => {
+                is_totp_error = true;
+                None
+            }
+            AuthResult::Failure(AuthFailureReason::Banned) => return Err(()),
+        };
 
+        self.complete_authentication(access_token, is_totp_error, tag)
+            .await
+    }
+
+    async fn complete_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+        is_totp_error: bool,
+        tag: String,
+    ) -> crate::Result<()> {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = match self
@@ -174,7 +316,7 @@ impl<T: SessionStream> Session<T> {
             self.write_bytes(
                 StatusResponse::ok("Authentication successful")
                     .with_code(ResponseCode::Capability {
-                        capabilities: Capability::all_capabilities(true, self.is_tls),
+                        capabilities: self.capabilities(true),
                     })
                     .with_tag(tag)
                     .into_bytes(),
@@ -217,6 +359,8 @@ impl<T: SessionStream> Session<T> {
 
     pub async fn handle_unauthenticate(&mut self, request: Request<Command>) -> crate::OpResult {
         self.state = State::NotAuthenticated { auth_failures: 0 };
+        self.notify = None;
+        self.notify_rx = None;
 
         self.write_bytes(
             StatusResponse::completed(Command::Unauthenticate)
@@ -227,13 +371,19 @@ impl<T: SessionStream> Session<T> {
     }
 }
 
-pub fn decode_challenge_plain(challenge: &[u8]) -> Result<Credentials<String>, &'static str> {
+pub fn decode_challenge_plain(
+    challenge: &[u8],
+    allow_impersonation: bool,
+) -> Result<Credentials<String>, &'static str> {
+    let mut authzid = Vec::new();
     let mut username = Vec::new();
     let mut secret = Vec::new();
     let mut arg_num = 0;
     for &ch in challenge {
         if ch != 0 {
-            if arg_num == 1 {
+            if arg_num == 0 {
+                authzid.push(ch);
+            } else if arg_num == 1 {
                 username.push(ch);
             } else if arg_num == 2 {
                 secret.push(ch);
@@ -243,9 +393,22 @@ pub fn decode_challenge_plain(challenge: &[u8]) -> Result<Credentials<String>, &
         }
     }
 
-    match (String::from_utf8(username), String::from_utf8(secret)) {
-        (Ok(username), Ok(secret)) if !username.is_empty() && !secret.is_empty() => {
-            Ok((username, secret).into())
+    match (
+        String::from_utf8(authzid),
+        String::from_utf8(username),
+        String::from_utf8(secret),
+    ) {
+        (Ok(authzid), Ok(username), Ok(secret)) if !username.is_empty() && !secret.is_empty() => {
+            if allow_impersonation && !authzid.is_empty() && authzid != username {
+                // Log in as the authorization identity
+                Ok((
+                    format!("{authzid}{IMPERSONATION_SEPARATOR}{username}"),
+                    secret,
+                )
+                    .into())
+            } else {
+                Ok((username, secret).into())
+            }
         }
         _ => Err("Invalid AUTH=PLAIN challenge."),
     }
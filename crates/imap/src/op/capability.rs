@@ -9,6 +9,7 @@ use common::listener::SessionStream;
 use imap_proto::{
     protocol::{
         capability::{Capability, Response},
+        compress::Algorithm,
         ImapResponse,
     },
     receiver::Request,
@@ -22,10 +23,7 @@ impl<T: SessionStream> Session<T> {
                 .with_tag(request.tag)
                 .serialize(
                     Response {
-                        capabilities: Capability::all_capabilities(
-                            self.state.is_authenticated(),
-                            self.is_tls,
-                        ),
+                        capabilities: self.capabilities(self.state.is_authenticated()),
                     }
                     .serialize(),
                 ),
@@ -33,6 +31,14 @@ impl<T: SessionStream> Session<T> {
         .await
     }
 
+    pub fn capabilities(&self, is_authenticated: bool) -> Vec<Capability> {
+        let mut capabilities = Capability::all_capabilities(is_authenticated, self.is_tls);
+        if is_authenticated && self.instance.compress {
+            capabilities.push(Capability::Compress(Algorithm::Deflate));
+        }
+        capabilities
+    }
+
     pub async fn handle_id(&mut self, request: Request<Command>) -> crate::OpResult {
         self.write_bytes(
             StatusResponse::completed(Command::Id)
@@ -0,0 +1,68 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use common::listener::SessionStream;
+use imap_proto::{receiver::Request, Command, ResponseCode, StatusResponse};
+
+use crate::core::{Session, State, Upgrade};
+
+const MAX_PENDING_WAIT: Duration = Duration::from_secs(30);
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_compress(
+        &mut self,
+        request: Request<Command>,
+    ) -> crate::Result<Option<Upgrade>> {
+        let arguments = match request.parse_compress() {
+            Ok(arguments) => arguments,
+            Err(response) => {
+                return self.write_bytes(response.into_bytes()).await.map(|_| None);
+            }
+        };
+
+        let response = if !self.instance.compress {
+            StatusResponse::no("Compression is not available.")
+        } else if self.is_compressed {
+            StatusResponse::no("Compression is already active.")
+                .with_code(ResponseCode::CompressionActive)
+        } else if !self.wait_pending_commands().await {
+            StatusResponse::no("Other commands are still in progress, try again later.")
+        } else {
+            // Compression starts immediately after the tagged response
+            return self
+                .write_bytes(
+                    StatusResponse::ok("DEFLATE active")
+                        .with_tag(arguments.tag)
+                        .into_bytes(),
+                )
+                .await
+                .map(|_| Some(Upgrade::Compress));
+        };
+
+        self.write_bytes(response.with_tag(arguments.tag).into_bytes())
+            .await
+            .map(|_| None)
+    }
+
+    async fn wait_pending_commands(&self) -> bool {
+        // Responses to pipelined commands have to be sent uncompressed
+        let data = match &self.state {
+            State::Authenticated { data } | State::Selected { data, .. } => data,
+            State::NotAuthenticated { .. } => return true,
+        };
+        let mut waited = Duration::ZERO;
+        while Arc::strong_count(data) > 1 {
+            if waited >= MAX_PENDING_WAIT {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            waited += Duration::from_millis(10);
+        }
+        true
+    }
+}
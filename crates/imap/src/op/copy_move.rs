@@ -110,6 +110,16 @@ impl<T: SessionStream> SessionData<T> {
         is_uid: bool,
         is_qresync: bool,
     ) -> Result<(), StatusResponse> {
+        // SPDX-SnippetBegin
+        // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+        // SPDX-License-Identifier: LicenseRef-SEL
+        if let Some(deleted) = &src_mailbox.deleted {
+            return self
+                .restore_deleted(arguments, &src_mailbox, deleted, dest_mailbox, is_uid)
+                .await;
+        }
+        // SPDX-SnippetEnd
+
         // Convert IMAP ids to JMAP ids.
         let ids = match src_mailbox
             .sequence_to_ids(&arguments.sequence_set, is_uid)
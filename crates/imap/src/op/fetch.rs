@@ -102,6 +102,16 @@ impl<T: SessionStream> SessionData<T> {
             }
         }
 
+        // SPDX-SnippetBegin
+        // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+        // SPDX-License-Identifier: LicenseRef-SEL
+        if let Some(deleted) = &mailbox.deleted {
+            return self
+                .fetch_deleted(arguments, &mailbox, deleted, is_uid)
+                .await;
+        }
+        // SPDX-SnippetEnd
+
         // Resync messages if needed
         let account_id = mailbox.id.account_id;
         let mut modseq = match self.synchronize_messages(&mailbox).await {
@@ -261,6 +271,9 @@ impl<T: SessionStream> SessionData<T> {
             .map(|(id, imap_id)| (imap_id.seqnum, imap_id.uid, id))
             .collect::<Vec<_>>();
         ids.sort_unstable_by_key(|(seqnum, _, _)| *seqnum);
+        if let Some(partial) = &arguments.partial {
+            ids = partial.apply(&ids).to_vec();
+        }
         for (seqnum, uid, id) in ids {
             // Obtain attributes and keywords
             let (email, keywords) = if let (Ok(Some(email)), Ok(Some(keywords))) = (
@@ -355,6 +368,11 @@ impl<T: SessionStream> SessionData<T> {
                             date: email.received_at as i64,
                         });
                     }
+                    Attribute::SaveDate => {
+                        items.push(DataItem::SaveDate {
+                            date: Some(email.received_at as i64),
+                        });
+                    }
                     Attribute::Preview { .. } => {
                         items.push(DataItem::Preview {
                             contents: if !email.preview.is_empty() {
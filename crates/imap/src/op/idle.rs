@@ -26,6 +26,8 @@ use utils::map::bitmap::Bitmap;
 
 use crate::core::{SelectedMailbox, Session, SessionData, State};
 
+use super::notify::recv_state_change;
+
 impl<T: SessionStream> Session<T> {
     pub async fn handle_idle(&mut self, request: Request<Command>) -> crate::OpResult {
         let (data, mailbox, types) = match &self.state {
@@ -42,13 +44,15 @@ impl<T: SessionStream> Session<T> {
         let is_rev2 = self.version.is_rev2();
         let is_qresync = self.is_qresync;
 
-        // Register with state manager
-        let mut change_rx = if let Some(change_rx) = self
+        // Register with state manager, unless NOTIFY already did
+        let mut change_rx = if self.notify.is_some() {
+            None
+        } else if let Some(change_rx) = self
             .jmap
             .subscribe_state_manager(data.account_id, types)
             .await
         {
-            change_rx
+            Some(change_rx)
         } else {
             return self
                 .write_bytes(
@@ -93,8 +97,13 @@ impl<T: SessionStream> Session<T> {
                         }
                     }
                 }
-                state_change = change_rx.recv() => {
+                state_change = recv_state_change(if change_rx.is_some() { &mut change_rx } else { &mut self.notify_rx }) => {
                     if let Some(state_change) = state_change {
+                        if change_rx.is_none() {
+                            self.write_notify_changes(state_change).await;
+                            continue;
+                        }
+
                         let mut has_mailbox_changes = false;
                         let mut has_email_changes = false;
 
@@ -248,6 +257,7 @@ impl<T: SessionStream> SessionData<T> {
                             attributes: vec![fetch::Attribute::Flags, fetch::Attribute::Uid],
                             changed_since: None,
                             include_vanished: false,
+                            partial: None,
                         },
                         mailbox.clone(),
                         true,
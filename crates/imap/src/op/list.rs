@@ -236,6 +236,29 @@ impl<T: SessionStream> SessionData<T> {
             }
         }
 
+        // SPDX-SnippetBegin
+        // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+        // SPDX-License-Identifier: LicenseRef-SEL
+        // Add virtual folder with recently deleted messages
+        if let Some(folder) = self.undelete_folder() {
+            if !filter_subscribed
+                && !filter_special_use
+                && matches_pattern(&patterns, folder)
+                && !list_items.iter().any(|item| item.mailbox_name == folder)
+            {
+                list_items.push(ListItem {
+                    mailbox_name: folder.to_string(),
+                    attributes: if include_children {
+                        vec![Attribute::HasNoChildren]
+                    } else {
+                        vec![]
+                    },
+                    tags: vec![],
+                });
+            }
+        }
+        // SPDX-SnippetEnd
+
         // Add status response
         let mut status_items = Vec::new();
         if let Some(include_status) = include_status {
@@ -0,0 +1,315 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::listener::SessionStream;
+use imap_proto::{
+    protocol::metadata::{GetArguments, Response, SetArguments},
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap_proto::{
+    error::method::MethodError,
+    types::{acl::Acl, collection::Collection, property::Property},
+};
+use serde::{Deserialize, Serialize};
+use store::write::{
+    assert::{AssertValue, HashedValue},
+    BatchBuilder, Bincode, F_CLEAR, F_VALUE,
+};
+
+use crate::core::{Session, SessionData};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub entries: Vec<MetadataEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataEntry {
+    pub name: String,
+    pub owner: Option<u32>,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MetadataScope {
+    Mailbox { account_id: u32, mailbox_id: u32 },
+    Server,
+}
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_get_metadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_get_metadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                let is_rev2 = self.version.is_rev2();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    let bytes = match data.get_metadata(arguments, is_rev2).await {
+                        Ok(bytes) => bytes,
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    };
+                    data.write_bytes(bytes).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_set_metadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_set_metadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    data.write_bytes(
+                        match data.set_metadata(arguments).await {
+                            Ok(response) => response,
+                            Err(response) => response,
+                        }
+                        .with_tag(tag)
+                        .into_bytes(),
+                    )
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn get_metadata(
+        &self,
+        arguments: GetArguments,
+        is_rev2: bool,
+    ) -> crate::op::Result<Vec<u8>> {
+        let scope = self.metadata_scope(&arguments.mailbox_name).await?;
+        let mut entries = Vec::new();
+        let mut long_entries = 0;
+
+        for is_private in [true, false] {
+            if !arguments
+                .entries
+                .iter()
+                .any(|name| name.starts_with("/private") == is_private)
+            {
+                continue;
+            }
+            self.check_metadata_acl(scope, is_private, false).await?;
+
+            let owner = is_private.then_some(self.account_id);
+            if let Some(metadata) = self.read_metadata(scope, is_private).await? {
+                for entry in metadata.inner.inner.entries {
+                    if entry.owner == owner
+                        && arguments
+                            .entries
+                            .iter()
+                            .any(|name| arguments.depth.matches(name, &entry.name))
+                    {
+                        if arguments
+                            .max_size
+                            .is_some_and(|max_size| entry.value.len() > max_size)
+                        {
+                            long_entries = std::cmp::max(long_entries, entry.value.len());
+                        } else {
+                            entries.push((entry.name, Some(entry.value)));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut buf = Vec::with_capacity(64);
+        if !entries.is_empty() {
+            entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            Response {
+                mailbox_name: arguments.mailbox_name,
+                entries,
+            }
+            .serialize(&mut buf, is_rev2);
+        }
+
+        let mut response = StatusResponse::completed(Command::GetMetadata).with_tag(arguments.tag);
+        if long_entries > 0 {
+            response = response.with_code(ResponseCode::MetadataLongEntries { size: long_entries });
+        }
+
+        Ok(response.serialize(buf))
+    }
+
+    async fn set_metadata(&self, arguments: SetArguments) -> crate::op::Result<StatusResponse> {
+        let scope = self.metadata_scope(&arguments.mailbox_name).await?;
+
+        // Validate entry sizes
+        let max_size = self.jmap.core.imap.metadata_max_size;
+        if arguments
+            .entries
+            .iter()
+            .any(|(_, value)| value.as_ref().is_some_and(|value| value.len() > max_size))
+        {
+            return Err(StatusResponse::no("Metadata value is too large.")
+                .with_code(ResponseCode::MetadataMaxSize { size: max_size }));
+        }
+
+        let mut batch = BatchBuilder::new();
+        for is_private in [true, false] {
+            let changes = arguments
+                .entries
+                .iter()
+                .filter(|(name, _)| name.starts_with("/private") == is_private)
+                .collect::<Vec<_>>();
+            if changes.is_empty() {
+                continue;
+            }
+            self.check_metadata_acl(scope, is_private, true).await?;
+
+            // Apply changes
+            let owner = is_private.then_some(self.account_id);
+            let current = self.read_metadata(scope, is_private).await?;
+            let mut metadata = current
+                .as_ref()
+                .map(|metadata| metadata.inner.inner.clone())
+                .unwrap_or_default();
+            for (name, value) in changes {
+                metadata
+                    .entries
+                    .retain(|entry| entry.owner != owner || &entry.name != name);
+                if let Some(value) = value {
+                    metadata.entries.push(MetadataEntry {
+                        name: name.clone(),
+                        owner,
+                        value: value.clone(),
+                    });
+                }
+            }
+
+            // Enforce entry limits
+            if metadata
+                .entries
+                .iter()
+                .filter(|entry| entry.owner == owner)
+                .count()
+                > self.jmap.core.imap.metadata_max_entries
+            {
+                return Err(StatusResponse::no("Too many metadata entries.")
+                    .with_code(ResponseCode::MetadataTooMany));
+            }
+
+            // Build batch
+            let (account_id, collection, document_id) = self.metadata_location(scope, is_private);
+            batch
+                .with_account_id(account_id)
+                .with_collection(collection)
+                .update_document(document_id)
+                .assert_value(
+                    Property::Metadata,
+                    current.map_or(AssertValue::None, |current| AssertValue::Hash(current.hash)),
+                );
+            if !metadata.entries.is_empty() {
+                batch.value(Property::Metadata, Bincode::new(metadata), F_VALUE);
+            } else {
+                batch.value(Property::Metadata, (), F_VALUE | F_CLEAR);
+            }
+        }
+
+        match self.jmap.write_batch(batch).await {
+            Ok(_) => Ok(StatusResponse::completed(Command::SetMetadata)),
+            Err(MethodError::ServerUnavailable) => Err(StatusResponse::no(
+                "Another process modified this metadata, please try again.",
+            )),
+            Err(_) => Err(StatusResponse::database_failure()),
+        }
+    }
+
+    async fn metadata_scope(&self, mailbox_name: &str) -> crate::op::Result<MetadataScope> {
+        if mailbox_name.is_empty() {
+            return Ok(MetadataScope::Server);
+        }
+
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        // Validate mailbox
+        self.get_mailbox_by_name(mailbox_name)
+            .map(|mailbox| MetadataScope::Mailbox {
+                account_id: mailbox.account_id,
+                mailbox_id: mailbox.mailbox_id,
+            })
+            .ok_or_else(|| {
+                StatusResponse::no("Mailbox does not exist.").with_code(ResponseCode::NonExistent)
+            })
+    }
+
+    async fn check_metadata_acl(
+        &self,
+        scope: MetadataScope,
+        is_private: bool,
+        is_write: bool,
+    ) -> crate::op::Result<()> {
+        let has_access = match scope {
+            MetadataScope::Mailbox {
+                account_id,
+                mailbox_id,
+            } => {
+                self.check_mailbox_acl(account_id, mailbox_id, Acl::Read)
+                    .await?
+                    && self
+                        .check_mailbox_acl(account_id, mailbox_id, Acl::ReadItems)
+                        .await?
+                    && (is_private
+                        || !is_write
+                        || self
+                            .check_mailbox_acl(account_id, mailbox_id, Acl::ModifyItems)
+                            .await?)
+            }
+            MetadataScope::Server => {
+                is_private || !is_write || self.get_access_token().await?.is_super_user()
+            }
+        };
+
+        if has_access {
+            Ok(())
+        } else {
+            Err(
+                StatusResponse::no("You do not have enough permissions to perform this operation.")
+                    .with_code(ResponseCode::NoPerm),
+            )
+        }
+    }
+
+    async fn read_metadata(
+        &self,
+        scope: MetadataScope,
+        is_private: bool,
+    ) -> crate::op::Result<Option<HashedValue<Bincode<Metadata>>>> {
+        let (account_id, collection, document_id) = self.metadata_location(scope, is_private);
+        self.jmap
+            .get_property::<HashedValue<Bincode<Metadata>>>(
+                account_id,
+                collection,
+                document_id,
+                Property::Metadata,
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    fn metadata_location(&self, scope: MetadataScope, is_private: bool) -> (u32, Collection, u32) {
+        match scope {
+            MetadataScope::Mailbox {
+                account_id,
+                mailbox_id,
+            } => (account_id, Collection::Mailbox, mailbox_id),
+            MetadataScope::Server if is_private => (self.account_id, Collection::Principal, 0),
+            MetadataScope::Server => (u32::MAX, Collection::Principal, u32::MAX),
+        }
+    }
+}
@@ -12,6 +12,7 @@ pub mod append;
 pub mod authenticate;
 pub mod capability;
 pub mod close;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -22,8 +23,11 @@ pub mod idle;
 pub mod list;
 pub mod login;
 pub mod logout;
+pub mod metadata;
 pub mod namespace;
 pub mod noop;
+pub mod notify;
+pub mod quota;
 pub mod rename;
 pub mod search;
 pub mod select;
@@ -31,6 +35,7 @@ pub mod status;
 pub mod store;
 pub mod subscribe;
 pub mod thread;
+pub mod undelete;
 
 trait FromModSeq {
     fn from_modseq(modseq: u64) -> Self;
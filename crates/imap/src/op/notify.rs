@@ -0,0 +1,509 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::sync::Arc;
+
+use ahash::AHashSet;
+use common::listener::SessionStream;
+use imap_proto::{
+    protocol::{
+        fetch,
+        list::{Attribute, ListItem},
+        notify::{self, Event, EventGroup, Filter},
+        status::Status,
+        Sequence,
+    },
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap_proto::types::{collection::Collection, state::StateChange, type_state::DataType};
+use store::query::log::{Change, Query};
+use tokio::sync::mpsc;
+use utils::map::bitmap::Bitmap;
+
+use crate::core::{MailboxId, SelectedMailbox, Session, SessionData, State};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_notify(&mut self, request: Request<Command>) -> crate::OpResult {
+        let mut arguments = match request.parse_notify(self.version) {
+            Ok(arguments) => arguments,
+            Err(response) => return self.write_bytes(response.into_bytes()).await,
+        };
+        let tag = std::mem::take(&mut arguments.tag);
+
+        // Annotation and metadata events are not supported
+        if arguments
+            .groups
+            .iter()
+            .flat_map(|group| group.events.iter())
+            .any(|event| !is_supported(event))
+        {
+            return self
+                .write_bytes(
+                    StatusResponse::no("Unsupported event.")
+                        .with_tag(tag)
+                        .with_code(ResponseCode::BadEvent {
+                            supported: vec![
+                                Event::MessageNew(vec![]),
+                                Event::MessageExpunge,
+                                Event::FlagChange,
+                                Event::MailboxName,
+                                Event::SubscriptionChange,
+                            ],
+                        })
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        // NOTIFY NONE
+        if arguments.groups.is_empty() {
+            self.notify = None;
+            self.notify_rx = None;
+            return self
+                .write_bytes(
+                    StatusResponse::completed(Command::Notify)
+                        .with_tag(tag)
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        let (data, selected_id) = match &self.state {
+            State::Authenticated { data } => (data.clone(), None),
+            State::Selected { data, mailbox } => (data.clone(), mailbox.id.into()),
+            _ => unreachable!(),
+        };
+
+        // Register with state manager
+        if self.notify_rx.is_none() {
+            if let Some(change_rx) = self
+                .jmap
+                .subscribe_state_manager(
+                    data.account_id,
+                    Bitmap::from_iter([
+                        DataType::Email,
+                        DataType::Mailbox,
+                        DataType::EmailDelivery,
+                    ]),
+                )
+                .await
+            {
+                self.notify_rx = change_rx.into();
+            } else {
+                return self
+                    .write_bytes(
+                        StatusResponse::no("It was not possible to start notifications.")
+                            .with_tag(tag)
+                            .with_code(ResponseCode::ContactAdmin)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+        }
+
+        // Refresh mailboxes, changes are reported from this point on
+        if let Err(response) = data.synchronize_mailboxes(false).await {
+            return self.write_bytes(response.with_tag(tag).into_bytes()).await;
+        }
+
+        // Send the status of all monitored mailboxes
+        if arguments.status {
+            let is_rev2 = self.version.is_rev2();
+            let mut buf = Vec::with_capacity(64);
+            for (mailbox_name, mailbox_id, is_subscribed) in data.all_mailboxes() {
+                if Some(mailbox_id) == selected_id {
+                    continue;
+                }
+                if let Some(group) = data
+                    .notify_group(&arguments, &mailbox_name, is_subscribed)
+                    .filter(|group| group.has_message_events())
+                {
+                    if let Ok(status) = data.status(mailbox_name, &status_items(group)).await {
+                        status.serialize(&mut buf, is_rev2);
+                    }
+                }
+            }
+            if !buf.is_empty() {
+                self.write_bytes(buf).await?;
+            }
+        }
+
+        self.notify = arguments.into();
+        self.write_bytes(
+            StatusResponse::completed(Command::Notify)
+                .with_tag(tag)
+                .into_bytes(),
+        )
+        .await
+    }
+
+    pub async fn write_notify_changes(&self, state_change: StateChange) {
+        let (data, mailbox) = match &self.state {
+            State::Authenticated { data } => (data, None),
+            State::Selected { data, mailbox } => (data, Some(mailbox)),
+            State::NotAuthenticated { .. } => return,
+        };
+        let notify = if let Some(notify) = &self.notify {
+            notify
+        } else {
+            return;
+        };
+        let is_rev2 = self.version.is_rev2();
+
+        let mut has_mailbox_changes = false;
+        let mut has_email_changes = false;
+        for (type_state, _) in state_change.types {
+            match type_state {
+                DataType::Email | DataType::EmailDelivery => {
+                    has_email_changes = true;
+                }
+                DataType::Mailbox => {
+                    has_mailbox_changes = true;
+                }
+                _ => {}
+            }
+        }
+
+        // Mailbox events and new messages in other mailboxes are received as mailbox changes
+        if has_mailbox_changes
+            && notify
+                .groups
+                .iter()
+                .any(|group| !group.filter.is_selected() && !group.events.is_empty())
+        {
+            data.write_notify_mailbox_changes(notify, mailbox.map(|mailbox| mailbox.id), is_rev2)
+                .await;
+        }
+
+        if has_email_changes {
+            if let (Some(mailbox), Some(group)) = (mailbox, notify.selected()) {
+                if group.has_message_events() {
+                    data.write_notify_selected_changes(mailbox, group, self.is_qresync, is_rev2)
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn write_notify_mailbox_changes(
+        &self,
+        notify: &notify::Arguments,
+        selected_id: Option<MailboxId>,
+        is_rev2: bool,
+    ) {
+        let changes = match self.synchronize_mailboxes(true).await {
+            Ok(Some(changes)) => changes,
+            Ok(None) => return,
+            Err(_) => {
+                tracing::debug!(parent: &self.span, "Failed to refresh mailboxes.");
+                return;
+            }
+        };
+        let mut buf = Vec::with_capacity(64);
+
+        // Mailbox deletions, creations and renames
+        for (mailbox_names, is_deleted) in [(changes.deleted, true), (changes.added, false)] {
+            for mailbox_name in mailbox_names {
+                let is_subscribed = !is_deleted && self.is_subscribed(&mailbox_name);
+                if self
+                    .notify_group(notify, &mailbox_name, is_subscribed)
+                    .is_some_and(|group| group.has_event(&Event::MailboxName))
+                {
+                    ListItem {
+                        mailbox_name,
+                        attributes: if is_deleted {
+                            vec![Attribute::NonExistent]
+                        } else if is_subscribed {
+                            vec![Attribute::Subscribed]
+                        } else {
+                            vec![]
+                        },
+                        tags: vec![],
+                    }
+                    .serialize(&mut buf, is_rev2, false);
+                }
+            }
+        }
+
+        // Subscription changes
+        for (mailbox_names, is_subscribed) in
+            [(changes.subscribed, true), (changes.unsubscribed, false)]
+        {
+            for mailbox_name in mailbox_names {
+                // Mailboxes that were just unsubscribed still match the SUBSCRIBED filter
+                if self
+                    .notify_group(notify, &mailbox_name, true)
+                    .is_some_and(|group| group.has_event(&Event::SubscriptionChange))
+                {
+                    ListItem {
+                        mailbox_name,
+                        attributes: if is_subscribed {
+                            vec![Attribute::Subscribed]
+                        } else {
+                            vec![]
+                        },
+                        tags: vec![],
+                    }
+                    .serialize(&mut buf, is_rev2, false);
+                }
+            }
+        }
+
+        // Message changes in mailboxes other than the selected one
+        for mailbox_name in changes.changed {
+            if selected_id.is_some() && self.get_mailbox_by_name(&mailbox_name) == selected_id {
+                continue;
+            }
+            if let Some(group) = self
+                .notify_group(notify, &mailbox_name, self.is_subscribed(&mailbox_name))
+                .filter(|group| group.has_message_events())
+            {
+                if let Ok(status) = self.status(mailbox_name, &status_items(group)).await {
+                    status.serialize(&mut buf, is_rev2);
+                }
+            }
+        }
+
+        if !buf.is_empty() {
+            self.write_bytes(buf).await;
+        }
+    }
+
+    async fn write_notify_selected_changes(
+        &self,
+        mailbox: &Arc<SelectedMailbox>,
+        group: &EventGroup,
+        is_qresync: bool,
+        is_rev2: bool,
+    ) {
+        // Expunges are held back until the client issues a command that allows them
+        if group.filter == Filter::SelectedDelayed {
+            match self.synchronize_messages(mailbox).await {
+                Ok(_) => {
+                    if mailbox
+                        .state
+                        .lock()
+                        .next_state
+                        .as_ref()
+                        .is_some_and(|state| !state.deletions.is_empty())
+                    {
+                        return;
+                    }
+                }
+                Err(response) => {
+                    self.write_bytes(response.into_bytes()).await;
+                    return;
+                }
+            }
+        }
+
+        // Write EXISTS and EXPUNGE responses
+        let modseq = mailbox.state.lock().modseq;
+        match self.write_mailbox_changes(mailbox, is_qresync).await {
+            Ok(new_state) => {
+                if new_state == modseq {
+                    return;
+                }
+            }
+            Err(response) => {
+                self.write_bytes(response.into_bytes()).await;
+                return;
+            }
+        }
+
+        // Obtain new and changed messages
+        let (new_uids, changed_uids) = match self
+            .jmap
+            .changes_(
+                mailbox.id.account_id,
+                Collection::Email,
+                modseq.map(Query::Since).unwrap_or(Query::All),
+            )
+            .await
+        {
+            Ok(changelog) => {
+                let state = mailbox.state.lock();
+                let mut new_uids = AHashSet::new();
+                let mut changed_uids = AHashSet::new();
+                for change in changelog.changes {
+                    let is_new = matches!(change, Change::Insert(_));
+                    if let Some(id) = state
+                        .id_to_imap
+                        .get(&((change.unwrap_id() & u32::MAX as u64) as u32))
+                    {
+                        if is_new {
+                            new_uids.insert(id.uid);
+                        } else {
+                            changed_uids.insert(id.uid);
+                        }
+                    }
+                }
+                (new_uids, changed_uids)
+            }
+            Err(_) => {
+                self.write_bytes(StatusResponse::database_failure().into_bytes())
+                    .await;
+                return;
+            }
+        };
+
+        // Fetch the requested attributes of new messages
+        if let Some(attributes) = group
+            .new_message_attributes()
+            .filter(|attributes| !attributes.is_empty() && !new_uids.is_empty())
+        {
+            let mut attributes = attributes.to_vec();
+            if !attributes.contains(&fetch::Attribute::Uid) {
+                attributes.push(fetch::Attribute::Uid);
+            }
+            self.write_notify_fetch(mailbox, new_uids, attributes, is_qresync, is_rev2)
+                .await;
+        }
+
+        // Fetch flags of changed messages
+        if group.has_event(&Event::FlagChange) && !changed_uids.is_empty() {
+            self.write_notify_fetch(
+                mailbox,
+                changed_uids,
+                vec![fetch::Attribute::Flags, fetch::Attribute::Uid],
+                is_qresync,
+                is_rev2,
+            )
+            .await;
+        }
+    }
+
+    async fn write_notify_fetch(
+        &self,
+        mailbox: &Arc<SelectedMailbox>,
+        uids: AHashSet<u32>,
+        attributes: Vec<fetch::Attribute>,
+        is_qresync: bool,
+        is_rev2: bool,
+    ) {
+        self.fetch(
+            fetch::Arguments {
+                tag: String::new(),
+                sequence_set: Sequence::List {
+                    items: uids
+                        .into_iter()
+                        .map(|uid| Sequence::Number { value: uid })
+                        .collect(),
+                },
+                attributes,
+                changed_since: None,
+                include_vanished: false,
+                partial: None,
+            },
+            mailbox.clone(),
+            true,
+            is_qresync,
+            is_rev2,
+            false,
+        )
+        .await;
+    }
+
+    pub fn all_mailboxes(&self) -> Vec<(String, MailboxId, bool)> {
+        let mut mailboxes = Vec::new();
+        for account in self.mailboxes.lock().iter() {
+            for (mailbox_name, mailbox_id) in &account.mailbox_names {
+                mailboxes.push((
+                    mailbox_name.clone(),
+                    MailboxId {
+                        account_id: account.account_id,
+                        mailbox_id: *mailbox_id,
+                    },
+                    account
+                        .mailbox_state
+                        .get(mailbox_id)
+                        .is_some_and(|mailbox| mailbox.is_subscribed),
+                ));
+            }
+        }
+        mailboxes
+    }
+
+    fn is_subscribed(&self, mailbox_name: &str) -> bool {
+        self.mailboxes.lock().iter().any(|account| {
+            account
+                .mailbox_names
+                .get(mailbox_name)
+                .and_then(|mailbox_id| account.mailbox_state.get(mailbox_id))
+                .is_some_and(|mailbox| mailbox.is_subscribed)
+        })
+    }
+
+    fn notify_group<'x>(
+        &self,
+        notify: &'x notify::Arguments,
+        mailbox_name: &str,
+        is_subscribed: bool,
+    ) -> Option<&'x EventGroup> {
+        // The first event group matching the mailbox applies
+        notify.groups.iter().find(|group| match &group.filter {
+            Filter::Selected | Filter::SelectedDelayed => false,
+            Filter::Personal => {
+                let shared_folder = &self.jmap.core.jmap.shared_folder;
+                mailbox_name
+                    .strip_prefix(shared_folder.as_str())
+                    .is_none_or(|path| !path.is_empty() && !path.starts_with('/'))
+            }
+            Filter::Inboxes => mailbox_name == "INBOX",
+            Filter::Subscribed => is_subscribed,
+            Filter::Subtree(roots) => roots.iter().any(|root| {
+                let root = normalize_name(root);
+                mailbox_name == root
+                    || mailbox_name
+                        .strip_prefix(root)
+                        .is_some_and(|path| path.starts_with('/'))
+            }),
+            Filter::Mailboxes(names) => names
+                .iter()
+                .any(|name| normalize_name(name) == mailbox_name),
+        })
+    }
+}
+
+pub async fn recv_state_change(
+    change_rx: &mut Option<mpsc::Receiver<StateChange>>,
+) -> Option<StateChange> {
+    if let Some(change_rx) = change_rx {
+        change_rx.recv().await
+    } else {
+        std::future::pending().await
+    }
+}
+
+fn is_supported(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::MessageNew(_)
+            | Event::MessageExpunge
+            | Event::FlagChange
+            | Event::MailboxName
+            | Event::SubscriptionChange
+    )
+}
+
+fn status_items(group: &EventGroup) -> Vec<Status> {
+    let mut items = vec![Status::Messages, Status::UidNext, Status::UidValidity];
+    if group.has_event(&Event::FlagChange) {
+        items.push(Status::Unseen);
+    }
+    items
+}
+
+pub fn normalize_name(mailbox_name: &str) -> &str {
+    if mailbox_name.eq_ignore_ascii_case("INBOX") {
+        "INBOX"
+    } else {
+        mailbox_name
+    }
+}
@@ -0,0 +1,280 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::listener::SessionStream;
+use directory::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    DirectoryInner, QueryBy,
+};
+use imap_proto::{
+    protocol::quota::{
+        Arguments, QuotaResource, QuotaResponse, QuotaRootResponse, Resource, SetArguments,
+    },
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+
+use crate::core::{Session, SessionData};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_get_quota_root(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_get_quota(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                let is_rev2 = self.version.is_rev2();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    data.write_bytes(match data.get_quota_root(arguments, is_rev2).await {
+                        Ok(bytes) => bytes,
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_get_quota(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_get_quota(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    data.write_bytes(match data.get_quota(arguments).await {
+                        Ok(bytes) => bytes,
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_set_quota(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_set_quota() {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    data.write_bytes(match data.set_quota(arguments).await {
+                        Ok(bytes) => bytes,
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn get_quota_root(
+        &self,
+        arguments: Arguments,
+        is_rev2: bool,
+    ) -> crate::op::Result<Vec<u8>> {
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        // Validate mailbox
+        let account_id = self
+            .get_mailbox_by_name(&arguments.name)
+            .ok_or_else(|| {
+                StatusResponse::no("Mailbox does not exist.").with_code(ResponseCode::NonExistent)
+            })?
+            .account_id;
+        let quota_root = self
+            .mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == account_id)
+            .and_then(|account| account.prefix.clone())
+            .unwrap_or_default();
+
+        // Obtain quota
+        let resources = self.quota_resources(account_id).await?;
+        let (quota_roots, quotas) = if !resources.is_empty() {
+            (
+                vec![quota_root.clone()],
+                vec![QuotaResponse {
+                    quota_root,
+                    resources,
+                }],
+            )
+        } else {
+            (vec![], vec![])
+        };
+
+        Ok(StatusResponse::completed(Command::GetQuotaRoot)
+            .with_tag(arguments.tag)
+            .serialize(
+                QuotaRootResponse {
+                    mailbox_name: arguments.name,
+                    quota_roots,
+                    quotas,
+                }
+                .into_bytes(is_rev2),
+            ))
+    }
+
+    async fn get_quota(&self, arguments: Arguments) -> crate::op::Result<Vec<u8>> {
+        let account_id = self.quota_root_account(&arguments.name).await?;
+        let resources = self.quota_resources(account_id).await?;
+        if !resources.is_empty() {
+            Ok(StatusResponse::completed(Command::GetQuota)
+                .with_tag(arguments.tag)
+                .serialize(
+                    QuotaResponse {
+                        quota_root: arguments.name,
+                        resources,
+                    }
+                    .into_bytes(),
+                ))
+        } else {
+            Err(StatusResponse::no("Quota root has no resource limits.")
+                .with_code(ResponseCode::NonExistent))
+        }
+    }
+
+    async fn set_quota(&self, arguments: SetArguments) -> crate::op::Result<Vec<u8>> {
+        // Only administrators can change quotas
+        if !self.get_access_token().await?.is_super_user() {
+            return Err(StatusResponse::no(
+                "You do not have enough permissions to perform this operation.",
+            )
+            .with_code(ResponseCode::NoPerm));
+        }
+        if !matches!(
+            self.jmap.core.storage.directory.store,
+            DirectoryInner::Internal(_)
+        ) {
+            return Err(StatusResponse::no(
+                "Quotas are managed by an external directory and cannot be changed.",
+            )
+            .with_code(ResponseCode::Cannot));
+        }
+        let account_id = self.quota_root_account(&arguments.quota_root).await?;
+
+        // Build changes, a missing resource removes its limit
+        let mut storage_limit = 0;
+        for (resource, limit) in arguments.limits {
+            match resource {
+                Resource::Storage => {
+                    storage_limit = limit.saturating_mul(1024);
+                }
+                Resource::Message => {
+                    return Err(StatusResponse::no(
+                        "The MESSAGE limit is set server-wide and cannot be changed.",
+                    )
+                    .with_code(ResponseCode::Cannot));
+                }
+            }
+        }
+        self.jmap
+            .core
+            .storage
+            .data
+            .update_account(
+                QueryBy::Id(account_id),
+                vec![PrincipalUpdate::set(
+                    PrincipalField::Quota,
+                    PrincipalValue::Integer(storage_limit),
+                )],
+            )
+            .await
+            .map_err(|err| {
+                tracing::warn!(parent: &self.span,
+                    event = "error",
+                    context = "set_quota",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to update account quota.");
+                StatusResponse::no("Failed to update quota.").with_code(ResponseCode::Cannot)
+            })?;
+
+        let resources = self.quota_resources(account_id).await?;
+        let mut buf = Vec::new();
+        if !resources.is_empty() {
+            QuotaResponse {
+                quota_root: arguments.quota_root,
+                resources,
+            }
+            .serialize(&mut buf);
+        }
+
+        Ok(StatusResponse::completed(Command::SetQuota)
+            .with_tag(arguments.tag)
+            .serialize(buf))
+    }
+
+    async fn quota_root_account(&self, quota_root: &str) -> crate::op::Result<u32> {
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        // Quota roots are named after the account's shared folder prefix
+        let account_id = self
+            .mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.prefix.as_deref().unwrap_or_default() == quota_root)
+            .map(|account| account.account_id);
+        if let Some(account_id) = account_id {
+            return Ok(account_id);
+        }
+
+        // Administrators can also address any account by name
+        if self.get_access_token().await?.is_super_user() {
+            if let Some(principal) = self
+                .jmap
+                .core
+                .storage
+                .directory
+                .query(QueryBy::Name(quota_root), false)
+                .await
+                .unwrap_or_default()
+            {
+                return Ok(principal.id);
+            }
+        }
+
+        Err(StatusResponse::no("Quota root does not exist.").with_code(ResponseCode::NonExistent))
+    }
+
+    async fn quota_resources(&self, account_id: u32) -> crate::op::Result<Vec<QuotaResource>> {
+        let access_token = self.get_access_token().await?;
+        let mut resources = Vec::with_capacity(2);
+
+        // Storage is reported in units of 1024 octets
+        let storage_limit = self.jmap.get_quota(&access_token, account_id).await?;
+        if storage_limit > 0 {
+            resources.push(QuotaResource {
+                resource: Resource::Storage,
+                usage: (self.jmap.get_used_quota(account_id).await?.max(0) as u64).div_ceil(1024),
+                limit: storage_limit as u64 / 1024,
+            });
+        }
+
+        let message_limit = self.jmap.core.jmap.mail_max_messages;
+        if message_limit > 0 {
+            resources.push(QuotaResource {
+                resource: Resource::Message,
+                usage: self.jmap.get_used_messages(account_id).await?,
+                limit: message_limit,
+            });
+        }
+
+        Ok(resources)
+    }
+}
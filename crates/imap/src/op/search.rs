@@ -9,13 +9,16 @@ use std::sync::Arc;
 use common::listener::SessionStream;
 use imap_proto::{
     protocol::{
-        search::{self, Arguments, Filter, Response, ResultOption},
+        search::{self, Arguments, Filter, MultiSearchArguments, Response, ResultOption, Source},
         Sequence,
     },
     receiver::Request,
+    utf7::utf7_encode,
     Command, StatusResponse,
 };
-use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
+use jmap_proto::types::{
+    acl::Acl, collection::Collection, id::Id, keyword::Keyword, property::Property,
+};
 use mail_parser::HeaderName;
 use nlp::language::Language;
 use store::{
@@ -28,7 +31,7 @@ use tokio::sync::watch;
 
 use crate::core::{ImapId, MailboxState, SavedSearch, SelectedMailbox, Session, SessionData};
 
-use super::{FromModSeq, ToModSeq};
+use super::{notify::normalize_name, FromModSeq, ToModSeq};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_search(
@@ -95,9 +98,195 @@ impl<T: SessionStream> Session<T> {
             Err(response) => self.write_bytes(response.into_bytes()).await,
         }
     }
+
+    pub async fn handle_esearch(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_esearch(self.version) {
+            Ok(mut arguments) => {
+                let (data, selected) = self.state.session_mailbox_state();
+                let is_rev2 = self.version.is_rev2();
+
+                // Searching the selected mailbox requires one
+                let selected = if arguments
+                    .sources
+                    .iter()
+                    .any(|source| matches!(source, Source::Selected | Source::SelectedDelayed))
+                {
+                    if let Some(selected) = selected {
+                        Some(selected)
+                    } else {
+                        return self
+                            .write_bytes(
+                                StatusResponse::bad("No mailbox is selected.")
+                                    .with_tag(arguments.arguments.tag)
+                                    .into_bytes(),
+                            )
+                            .await;
+                    }
+                } else {
+                    None
+                };
+
+                // Create channel for results
+                let (results_tx, prev_saved_search) = match &selected {
+                    Some(mailbox)
+                        if arguments
+                            .arguments
+                            .result_options
+                            .contains(&ResultOption::Save) =>
+                    {
+                        let prev_saved_search = Some(mailbox.get_saved_search().await);
+                        let (tx, rx) = watch::channel(Arc::new(Vec::new()));
+                        *mailbox.saved_search.lock() = SavedSearch::InFlight { rx };
+                        (tx.into(), prev_saved_search)
+                    }
+                    _ => (None, None),
+                };
+
+                tokio::spawn(async move {
+                    let tag = std::mem::take(&mut arguments.arguments.tag);
+                    let bytes = match data
+                        .multi_search(
+                            arguments,
+                            selected.clone(),
+                            results_tx,
+                            prev_saved_search.clone(),
+                            &tag,
+                            is_rev2,
+                        )
+                        .await
+                    {
+                        Ok(response) => StatusResponse::completed(Command::ESearch)
+                            .with_tag(tag)
+                            .serialize(response),
+                        Err(response) => {
+                            if let (Some(mailbox), Some(prev_saved_search)) =
+                                (selected, prev_saved_search)
+                            {
+                                *mailbox.saved_search.lock() = prev_saved_search
+                                    .map_or(SavedSearch::None, |s| SavedSearch::Results {
+                                        items: s,
+                                    });
+                            }
+                            response.with_tag(tag).into_bytes()
+                        }
+                    };
+                    data.write_bytes(bytes).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
 }
 
 impl<T: SessionStream> SessionData<T> {
+    async fn multi_search(
+        &self,
+        arguments: MultiSearchArguments,
+        selected: Option<Arc<SelectedMailbox>>,
+        mut results_tx: Option<watch::Sender<Arc<Vec<ImapId>>>>,
+        prev_saved_search: Option<Option<Arc<Vec<ImapId>>>>,
+        tag: &str,
+        is_rev2: bool,
+    ) -> crate::op::Result<Vec<u8>> {
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        // Obtain the mailboxes matching any of the sources
+        let shared_folder = &self.jmap.core.jmap.shared_folder;
+        let mailboxes = self
+            .all_mailboxes()
+            .into_iter()
+            .filter(|(mailbox_name, mailbox_id, is_subscribed)| {
+                arguments.sources.iter().any(|source| match source {
+                    Source::Selected | Source::SelectedDelayed => selected
+                        .as_ref()
+                        .is_some_and(|selected| &selected.id == mailbox_id),
+                    Source::Personal => mailbox_name
+                        .strip_prefix(shared_folder.as_str())
+                        .is_none_or(|path| !path.is_empty() && !path.starts_with('/')),
+                    Source::Inboxes => mailbox_name == "INBOX",
+                    Source::Subscribed => *is_subscribed,
+                    Source::Subtree(roots) => roots.iter().any(|root| {
+                        let root = normalize_name(root);
+                        mailbox_name == root
+                            || mailbox_name
+                                .strip_prefix(root)
+                                .is_some_and(|path| path.starts_with('/'))
+                    }),
+                    Source::SubtreeOne(roots) => roots.iter().any(|root| {
+                        let root = normalize_name(root);
+                        mailbox_name == root
+                            || mailbox_name
+                                .strip_prefix(root)
+                                .and_then(|path| path.strip_prefix('/'))
+                                .is_some_and(|path| !path.is_empty() && !path.contains('/'))
+                    }),
+                    Source::Mailboxes(names) => names
+                        .iter()
+                        .any(|name| normalize_name(name) == mailbox_name),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Search each mailbox, mailboxes without matches are omitted
+        let mut buf = Vec::new();
+        for (mailbox_name, mailbox_id, _) in mailboxes {
+            let mailbox = match &selected {
+                Some(selected) if selected.id == mailbox_id => selected.clone(),
+                _ => {
+                    if !self
+                        .check_mailbox_acl(
+                            mailbox_id.account_id,
+                            mailbox_id.mailbox_id,
+                            Acl::ReadItems,
+                        )
+                        .await?
+                    {
+                        continue;
+                    }
+                    Arc::new(SelectedMailbox {
+                        id: mailbox_id,
+                        state: parking_lot::Mutex::new(self.fetch_messages(&mailbox_id).await?),
+                        saved_search: parking_lot::Mutex::new(SavedSearch::None),
+                        is_select: false,
+                        is_condstore: false,
+                        deleted: None,
+                    })
+                }
+            };
+            let uid_validity = mailbox.state.lock().uid_validity;
+            let mut response = self
+                .search(
+                    arguments.arguments.clone(),
+                    mailbox,
+                    results_tx.take(),
+                    prev_saved_search.clone(),
+                    true,
+                )
+                .await?;
+            if response.ids.is_empty()
+                && response.min.is_none()
+                && response.max.is_none()
+                && response.count.unwrap_or_default() == 0
+                && response.partial.is_none()
+            {
+                continue;
+            }
+            response.mailbox = Some((
+                if is_rev2 {
+                    mailbox_name
+                } else {
+                    utf7_encode(&mailbox_name)
+                },
+                uid_validity,
+            ));
+            buf.extend(response.serialize(tag));
+        }
+
+        Ok(buf)
+    }
+
     pub async fn search(
         &self,
         arguments: Arguments,
@@ -107,6 +296,7 @@ impl<T: SessionStream> SessionData<T> {
         is_uid: bool,
     ) -> Result<search::Response, StatusResponse> {
         // Run query
+        let partial = arguments.partial();
         let (result_set, include_highest_modseq) = self
             .query(arguments.filter, &mailbox, &prev_saved_search)
             .await?;
@@ -121,6 +311,16 @@ impl<T: SessionStream> SessionData<T> {
             None
         };
 
+        // PARTIAL needs the full result list, MIN and MAX are obtained from it
+        let (find_min, find_max) = if partial.is_none() {
+            (
+                arguments.result_options.contains(&ResultOption::Min),
+                arguments.result_options.contains(&ResultOption::Max),
+            )
+        } else {
+            (false, false)
+        };
+
         // Sort and map ids
         let mut min: Option<(u32, ImapId)> = None;
         let mut max: Option<(u32, ImapId)> = None;
@@ -173,8 +373,8 @@ impl<T: SessionStream> SessionData<T> {
                     .into_iter()
                     .map(|id| id as u32),
                 is_uid,
-                arguments.result_options.contains(&ResultOption::Min),
-                arguments.result_options.contains(&ResultOption::Max),
+                find_min,
+                find_max,
                 &mut min,
                 &mut max,
                 &mut total,
@@ -186,8 +386,8 @@ impl<T: SessionStream> SessionData<T> {
             mailbox.map_search_results(
                 result_set.results.into_iter(),
                 is_uid,
-                arguments.result_options.contains(&ResultOption::Min),
-                arguments.result_options.contains(&ResultOption::Max),
+                find_min,
+                find_max,
                 &mut min,
                 &mut max,
                 &mut total,
@@ -208,25 +408,48 @@ impl<T: SessionStream> SessionData<T> {
         }
 
         // Build response
+        let (min, max, ids) = if let Some(partial) = &partial {
+            (
+                if arguments.result_options.contains(&ResultOption::Min) {
+                    imap_ids.iter().min().copied()
+                } else {
+                    None
+                },
+                if arguments.result_options.contains(&ResultOption::Max) {
+                    imap_ids.iter().max().copied()
+                } else {
+                    None
+                },
+                partial.apply(&imap_ids).to_vec(),
+            )
+        } else {
+            (
+                min.map(|(id, _)| id),
+                max.map(|(id, _)| id),
+                if arguments.result_options.is_empty()
+                    || arguments.result_options.contains(&ResultOption::All)
+                {
+                    imap_ids
+                } else {
+                    vec![]
+                },
+            )
+        };
         Ok(Response {
             is_uid,
-            min: min.map(|(id, _)| id),
-            max: max.map(|(id, _)| id),
+            min,
+            max,
             count: if arguments.result_options.contains(&ResultOption::Count) {
                 Some(total)
             } else {
                 None
             },
-            ids: if arguments.result_options.is_empty()
-                || arguments.result_options.contains(&ResultOption::All)
-            {
-                imap_ids
-            } else {
-                vec![]
-            },
+            ids,
             is_sort,
             is_esearch: arguments.is_esearch,
             highest_modseq,
+            partial,
+            mailbox: None,
         })
     }
 
@@ -469,6 +692,25 @@ impl<T: SessionStream> SessionData<T> {
                         ));
                         filters.push(query::Filter::End);
                     }
+                    search::Filter::SavedBefore(date) => {
+                        filters.push(query::Filter::lt(Property::ReceivedAt, date as u64));
+                    }
+                    search::Filter::SavedOn(date) => {
+                        filters.push(query::Filter::And);
+                        filters.push(query::Filter::ge(Property::ReceivedAt, date as u64));
+                        filters.push(query::Filter::lt(
+                            Property::ReceivedAt,
+                            (date + 86400) as u64,
+                        ));
+                        filters.push(query::Filter::End);
+                    }
+                    search::Filter::SavedSince(date) => {
+                        filters.push(query::Filter::ge(Property::ReceivedAt, date as u64));
+                    }
+                    search::Filter::SaveDateSupported => {
+                        // The save date is always available
+                        filters.push(query::Filter::is_in_set(message_ids.clone()));
+                    }
                     search::Filter::Seen => {
                         filters.push(query::Filter::is_in_bitmap(
                             Property::Keywords,
@@ -98,6 +98,7 @@ impl<T: SessionStream> Session<T> {
                         saved_search: parking_lot::Mutex::new(SavedSearch::None),
                         is_select,
                         is_condstore,
+                        deleted: None,
                     });
 
                     // Validate QRESYNC arguments
@@ -126,6 +127,7 @@ impl<T: SessionStream> Session<T> {
                                     attributes: vec![fetch::Attribute::Flags],
                                     changed_since: qresync.modseq.into(),
                                     include_vanished: true,
+                                    partial: None,
                                 },
                                 mailbox.clone(),
                                 true,
@@ -166,6 +168,12 @@ impl<T: SessionStream> Session<T> {
                             .serialize(response.serialize()),
                     )
                     .await
+                } else if data.is_undelete_folder(&arguments.mailbox_name) {
+                    // SPDX-SnippetBegin
+                    // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+                    // SPDX-License-Identifier: LicenseRef-SEL
+                    self.handle_select_deleted(arguments, command).await
+                    // SPDX-SnippetEnd
                 } else {
                     self.write_bytes(
                         StatusResponse::no("Mailbox does not exist.")
@@ -75,6 +75,12 @@ impl<T: SessionStream> SessionData<T> {
         // Get mailbox id
         let mailbox = if let Some(mailbox) = self.get_mailbox_by_name(&mailbox_name) {
             mailbox
+        } else if self.is_undelete_folder(&mailbox_name) {
+            // SPDX-SnippetBegin
+            // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+            // SPDX-License-Identifier: LicenseRef-SEL
+            return self.deleted_status(mailbox_name, items).await;
+            // SPDX-SnippetEnd
         } else {
             // Some IMAP clients will try to get the status of a mailbox with the NoSelect flag
             return if mailbox_name == self.jmap.core.jmap.shared_folder
@@ -11,12 +11,23 @@ use ahash::AHashMap;
 use common::listener::SessionStream;
 use imap_proto::{
     protocol::{
-        thread::{Arguments, Response},
+        thread::{Algorithm, Arguments, Response, Thread},
         ImapResponse,
     },
     receiver::Request,
     Command, StatusResponse,
 };
+use jmap::email::metadata::MessageMetadata;
+use jmap_proto::types::{collection::Collection, property::Property};
+use store::write::Bincode;
+
+// Maximum number of references considered per message, only the closest
+// ancestors are kept.
+const MAX_REFERENCES: usize = 100;
+
+// Maximum number of ancestors walked when checking for loops, deeper links
+// are refused.
+const MAX_ANCESTORS: usize = 1000;
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_thread(
@@ -66,39 +77,559 @@ impl<T: SessionStream> SessionData<T> {
             });
         }
 
-        // Lock the cache
-        let thread_ids = self
-            .jmap
-            .get_cached_thread_ids(mailbox.id.account_id, result_set.results.iter())
-            .await
-            .map_err(|err| {
-                tracing::error!(
-                event = "error",
-                context = "thread_query",
-                error = ?err,
-                "Failed to obtain threadId.");
-                StatusResponse::database_failure()
-            })?;
-
-        // Group messages by thread
-        let mut threads: AHashMap<u32, Vec<u32>> = AHashMap::new();
-        let state = mailbox.state.lock();
-        for (document_id, thread_id) in thread_ids {
-            if let Some((imap_id, _)) = state.map_result_id(document_id, is_uid) {
-                threads.entry(thread_id).or_default().push(imap_id);
+        // Map results to IMAP ids
+        let ids = {
+            let state = mailbox.state.lock();
+            result_set
+                .results
+                .iter()
+                .filter_map(|document_id| {
+                    state
+                        .map_result_id(document_id, is_uid)
+                        .map(|(id, imap_id)| (document_id, id, imap_id.seqnum))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Obtain the headers used for threading
+        let account_id = mailbox.id.account_id;
+        let mut messages = Vec::with_capacity(ids.len());
+        for (document_id, id, seqnum) in ids {
+            let metadata = match self
+                .jmap
+                .get_property::<Bincode<MessageMetadata>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    &Property::BodyStructure,
+                )
+                .await
+            {
+                Ok(Some(metadata)) => metadata.inner,
+                Ok(None) => continue,
+                Err(_) => return Err(StatusResponse::database_failure()),
+            };
+            let received_at = metadata.received_at as i64;
+            let message = metadata.contents.into_message(&metadata.raw_headers);
+            let (subject, is_reply) = base_subject(message.subject().unwrap_or_default());
+            let references = message.references().as_text_list().unwrap_or_default();
+            let mut references = references
+                .iter()
+                .skip(references.len().saturating_sub(MAX_REFERENCES))
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>();
+            if references.is_empty() {
+                if let Some(in_reply_to) = message
+                    .in_reply_to()
+                    .as_text_list()
+                    .and_then(|ids| ids.first().map(|id| id.to_string()))
+                {
+                    references.push(in_reply_to);
+                }
             }
+
+            messages.push(ThreadMessage {
+                id,
+                seqnum,
+                message_id: message.message_id().map(|id| id.to_string()),
+                references,
+                subject,
+                is_reply,
+                date: message
+                    .date()
+                    .map(|date| date.to_timestamp())
+                    .unwrap_or(received_at),
+            });
         }
 
-        let mut threads = threads
+        // Build response
+        Ok(Response {
+            is_uid,
+            threads: match arguments.algorithm {
+                Algorithm::OrderedSubject => thread_ordered_subject(messages),
+                Algorithm::References => ThreadTree::new(messages).build(true),
+                Algorithm::Refs => ThreadTree::new(messages).build(false),
+            },
+        })
+    }
+}
+
+struct ThreadMessage {
+    id: u32,
+    seqnum: u32,
+    message_id: Option<String>,
+    references: Vec<String>,
+    subject: String,
+    is_reply: bool,
+    date: i64,
+}
+
+#[derive(Default)]
+struct Container {
+    message: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+struct ThreadTree {
+    messages: Vec<ThreadMessage>,
+    containers: Vec<Container>,
+}
+
+// ORDEREDSUBJECT, as described in RFC 5256 section 3. Messages are grouped by
+// base subject, the first message of each group is the parent of all others.
+fn thread_ordered_subject(mut messages: Vec<ThreadMessage>) -> Vec<Thread> {
+    messages.sort_unstable_by(|a, b| {
+        a.subject
+            .cmp(&b.subject)
+            .then(a.date.cmp(&b.date))
+            .then(a.seqnum.cmp(&b.seqnum))
+    });
+
+    let mut threads: Vec<(i64, u32, Thread)> = Vec::new();
+    let mut last_subject = None;
+    for message in &messages {
+        if last_subject == Some(&message.subject) {
+            if let Some((_, _, thread)) = threads.last_mut() {
+                thread.children.push(Thread::new(message.id));
+            }
+        } else {
+            threads.push((message.date, message.seqnum, Thread::new(message.id)));
+            last_subject = Some(&message.subject);
+        }
+    }
+
+    threads.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+    threads.into_iter().map(|(_, _, thread)| thread).collect()
+}
+
+impl ThreadTree {
+    fn new(messages: Vec<ThreadMessage>) -> Self {
+        let mut tree = ThreadTree {
+            containers: Vec::with_capacity(messages.len() * 2),
+            messages: Vec::new(),
+        };
+        let mut id_table: AHashMap<String, usize> = AHashMap::with_capacity(messages.len());
+
+        // Link messages using their Message-ID and References headers (step 1)
+        for (message_idx, message) in messages.iter().enumerate() {
+            let container_idx = match message
+                .message_id
+                .as_ref()
+                .and_then(|message_id| id_table.get(message_id))
+            {
+                Some(&container_idx) if tree.containers[container_idx].message.is_none() => {
+                    container_idx
+                }
+                Some(_) | None => {
+                    // Messages without a Message-ID or with a duplicate one are
+                    // treated as unique.
+                    let container_idx = tree.containers.len();
+                    tree.containers.push(Container::default());
+                    if let Some(message_id) = &message.message_id {
+                        id_table.entry(message_id.clone()).or_insert(container_idx);
+                    }
+                    container_idx
+                }
+            };
+            tree.containers[container_idx].message = Some(message_idx);
+
+            let mut prev_idx = None;
+            for reference in &message.references {
+                let reference_idx = if let Some(&reference_idx) = id_table.get(reference) {
+                    reference_idx
+                } else {
+                    let reference_idx = tree.containers.len();
+                    tree.containers.push(Container::default());
+                    id_table.insert(reference.clone(), reference_idx);
+                    reference_idx
+                };
+                if let Some(prev_idx) = prev_idx {
+                    if tree.containers[reference_idx].parent.is_none() {
+                        tree.link(prev_idx, reference_idx);
+                    }
+                }
+                prev_idx = Some(reference_idx);
+            }
+
+            // The last reference is the parent of the message, replacing any
+            // parent presumed from other References headers
+            if let Some(old_parent) = tree.containers[container_idx].parent.take() {
+                tree.containers[old_parent]
+                    .children
+                    .retain(|&idx| idx != container_idx);
+            }
+            if let Some(parent_idx) = prev_idx {
+                tree.link(parent_idx, container_idx);
+            }
+        }
+
+        tree.messages = messages;
+        tree
+    }
+
+    // Links a container to a parent unless it would introduce a loop.
+    fn link(&mut self, parent_idx: usize, child_idx: usize) {
+        // A loop is only possible when the child has descendants
+        if !self.containers[child_idx].children.is_empty() {
+            let mut ancestor = Some(parent_idx);
+            let mut depth = 0;
+            while let Some(ancestor_idx) = ancestor {
+                if ancestor_idx == child_idx || depth == MAX_ANCESTORS {
+                    return;
+                }
+                ancestor = self.containers[ancestor_idx].parent;
+                depth += 1;
+            }
+        } else if parent_idx == child_idx {
+            return;
+        }
+        self.containers[child_idx].parent = Some(parent_idx);
+        self.containers[parent_idx].children.push(child_idx);
+    }
+
+    // REFERENCES and REFS, as described in RFC 5256 section 3 and
+    // draft-ietf-morg-inthread. REFS does not merge threads by subject and
+    // sorts threads by their most recent message.
+    fn build(mut self, group_by_subject: bool) -> Vec<Thread> {
+        // Gather the root set (step 2) and prune empty containers (step 4)
+        let roots = (0..self.containers.len())
+            .filter(|&idx| self.containers[idx].parent.is_none())
+            .collect::<Vec<_>>();
+        let mut roots = self.prune(roots);
+
+        // Group the root set by base subject (step 5)
+        if group_by_subject {
+            roots = self.group_by_subject(roots);
+        }
+
+        // Sort siblings by sent date (step 6)
+        let order = self.post_order(&roots);
+        let sort_keys = if group_by_subject {
+            self.sort(&order)
+        } else {
+            self.sort(&order);
+            self.newest(&order)
+        };
+        let mut threads = self.to_threads(&order);
+        let mut threads = roots
             .into_iter()
-            .map(|(_, mut messages)| {
-                messages.sort_unstable();
-                messages
+            .map(|idx| (sort_keys[idx], threads[idx].take().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        threads.sort_unstable_by_key(|(sort_key, _)| *sort_key);
+        threads.into_iter().map(|(_, thread)| thread).collect()
+    }
+
+    // Returns the containers below the roots with descendants listed before
+    // their ancestors. Trees are walked using an explicit stack as threads can
+    // be arbitrarily deep.
+    fn post_order(&self, roots: &[usize]) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.containers.len());
+        let mut stack = roots.to_vec();
+        while let Some(idx) = stack.pop() {
+            order.push(idx);
+            stack.extend_from_slice(&self.containers[idx].children);
+        }
+        order.reverse();
+        order
+    }
+
+    fn prune(&mut self, roots: Vec<usize>) -> Vec<usize> {
+        // Containers each child is replaced with after pruning
+        let mut pruned: Vec<Vec<usize>> = vec![Vec::new(); self.containers.len()];
+        for idx in self.post_order(&roots) {
+            let children = std::mem::take(&mut self.containers[idx].children)
+                .into_iter()
+                .flat_map(|child_idx| std::mem::take(&mut pruned[child_idx]))
+                .collect::<Vec<_>>();
+            let parent_idx = self.containers[idx].parent;
+            if self.containers[idx].message.is_none() {
+                // Empty containers are removed, their children are promoted unless
+                // that would promote multiple children to the root level
+                if children.is_empty() {
+                    continue;
+                } else if parent_idx.is_some() || children.len() == 1 {
+                    for &child_idx in &children {
+                        self.containers[child_idx].parent = parent_idx;
+                    }
+                    pruned[idx] = children;
+                    continue;
+                }
+            }
+            self.containers[idx].children = children;
+            pruned[idx] = vec![idx];
+        }
+
+        roots
+            .into_iter()
+            .flat_map(|idx| std::mem::take(&mut pruned[idx]))
+            .collect()
+    }
+
+    fn group_by_subject(&mut self, roots: Vec<usize>) -> Vec<usize> {
+        let subjects = roots
+            .iter()
+            .map(|&idx| {
+                let container = &self.containers[idx];
+                container
+                    .message
+                    .or_else(|| {
+                        container
+                            .children
+                            .first()
+                            .and_then(|&child_idx| self.containers[child_idx].message)
+                    })
+                    .filter(|&message_idx| !self.messages[message_idx].subject.is_empty())
             })
             .collect::<Vec<_>>();
-        threads.sort_unstable();
 
-        // Build response
-        Ok(Response { is_uid, threads })
+        // Pick the container that represents each subject, empty containers are
+        // preferred over messages and messages over replies
+        let mut subject_table: AHashMap<&str, usize> = AHashMap::new();
+        for (&idx, message_idx) in roots.iter().zip(subjects.iter()) {
+            if let Some(message_idx) = message_idx {
+                let subject = self.messages[*message_idx].subject.as_str();
+                match subject_table.get(subject) {
+                    Some(&current_idx) => {
+                        let current = &self.containers[current_idx];
+                        let is_dummy = self.containers[idx].message.is_none();
+                        if (is_dummy && current.message.is_some())
+                            || (!is_dummy
+                                && current.message.is_some_and(|current_message_idx| {
+                                    self.messages[current_message_idx].is_reply
+                                })
+                                && !self.messages[*message_idx].is_reply)
+                        {
+                            subject_table.insert(subject, idx);
+                        }
+                    }
+                    None => {
+                        subject_table.insert(subject, idx);
+                    }
+                }
+            }
+        }
+        let mut subject_table = subject_table
+            .into_iter()
+            .map(|(subject, idx)| (subject.to_string(), idx))
+            .collect::<AHashMap<_, _>>();
+
+        // Merge containers with the same subject
+        let mut result = Vec::with_capacity(roots.len());
+        let mut merged = vec![false; self.containers.len()];
+        for (idx, message_idx) in roots.into_iter().zip(subjects) {
+            if merged[idx] {
+                continue;
+            }
+            let (subject, target_idx) = match message_idx {
+                Some(message_idx) => {
+                    let subject = &self.messages[message_idx].subject;
+                    match subject_table.get(subject) {
+                        Some(&target_idx) if target_idx != idx => (subject.clone(), target_idx),
+                        _ => {
+                            result.push(idx);
+                            continue;
+                        }
+                    }
+                }
+                None => {
+                    result.push(idx);
+                    continue;
+                }
+            };
+
+            let is_dummy = self.containers[idx].message.is_none();
+            let target_is_dummy = self.containers[target_idx].message.is_none();
+            if is_dummy && target_is_dummy {
+                let children = std::mem::take(&mut self.containers[idx].children);
+                for &child_idx in &children {
+                    self.containers[child_idx].parent = Some(target_idx);
+                }
+                self.containers[target_idx].children.extend(children);
+            } else if target_is_dummy
+                || (self.containers[target_idx]
+                    .message
+                    .is_some_and(|message_idx| !self.messages[message_idx].is_reply)
+                    && self.containers[idx]
+                        .message
+                        .is_some_and(|message_idx| self.messages[message_idx].is_reply))
+            {
+                self.containers[idx].parent = Some(target_idx);
+                self.containers[target_idx].children.push(idx);
+            } else {
+                // Both are replies or both are not, make them siblings under a
+                // new empty container
+                let dummy_idx = self.containers.len();
+                self.containers.push(Container {
+                    message: None,
+                    parent: None,
+                    children: vec![target_idx, idx],
+                });
+                merged.push(false);
+                self.containers[target_idx].parent = Some(dummy_idx);
+                self.containers[idx].parent = Some(dummy_idx);
+                merged[target_idx] = true;
+                if let Some(pos) = result.iter().position(|&root_idx| root_idx == target_idx) {
+                    result[pos] = dummy_idx;
+                } else {
+                    result.push(dummy_idx);
+                }
+                subject_table.insert(subject, dummy_idx);
+            }
+        }
+        result
+    }
+
+    // Sorts the children of each container by sent date and returns the dates
+    // used to sort the containers themselves.
+    fn sort(&mut self, order: &[usize]) -> Vec<(i64, u32)> {
+        let mut sort_keys = vec![(0, 0); self.containers.len()];
+        for &idx in order {
+            let mut children = std::mem::take(&mut self.containers[idx].children)
+                .into_iter()
+                .map(|child_idx| (sort_keys[child_idx], child_idx))
+                .collect::<Vec<_>>();
+            children.sort_unstable();
+            sort_keys[idx] = match self.containers[idx].message {
+                Some(message_idx) => {
+                    let message = &self.messages[message_idx];
+                    (message.date, message.seqnum)
+                }
+                None => children
+                    .first()
+                    .map(|(sort_key, _)| *sort_key)
+                    .unwrap_or_default(),
+            };
+            self.containers[idx].children = children.into_iter().map(|(_, idx)| idx).collect();
+        }
+        sort_keys
+    }
+
+    // Returns the date of the most recent message below each container.
+    fn newest(&self, order: &[usize]) -> Vec<(i64, u32)> {
+        let mut newest = vec![(0, 0); self.containers.len()];
+        for &idx in order {
+            let container = &self.containers[idx];
+            newest[idx] = container
+                .children
+                .iter()
+                .map(|&child_idx| newest[child_idx])
+                .chain(container.message.map(|message_idx| {
+                    let message = &self.messages[message_idx];
+                    (message.date, message.seqnum)
+                }))
+                .max()
+                .unwrap_or_default();
+        }
+        newest
+    }
+
+    fn to_threads(&self, order: &[usize]) -> Vec<Option<Thread>> {
+        let mut threads: Vec<Option<Thread>> = vec![None; self.containers.len()];
+        for &idx in order {
+            let container = &self.containers[idx];
+            threads[idx] = Some(Thread {
+                id: container
+                    .message
+                    .map(|message_idx| self.messages[message_idx].id),
+                children: container
+                    .children
+                    .iter()
+                    .filter_map(|&child_idx| threads[child_idx].take())
+                    .collect(),
+            });
+        }
+        threads
+    }
+}
+
+// Extracts the base subject of a message as described in RFC 5256 section 2.1,
+// returns whether the subject indicates a reply or forward.
+pub fn base_subject(subject: &str) -> (String, bool) {
+    // Normalize whitespace (step 1)
+    let mut text = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut is_reply = false;
+
+    loop {
+        // Remove trailing "(fwd)" (step 2)
+        let mut base = text.as_str();
+        loop {
+            base = base.trim_end();
+            if let Some(stripped) = strip_suffix_ignore_case(base, "(fwd)") {
+                base = stripped;
+                is_reply = true;
+            } else {
+                break;
+            }
+        }
+
+        // Remove leaders and blobs (steps 3 to 5)
+        loop {
+            let mut changed = false;
+            loop {
+                base = base.trim_start();
+                let mut leader = base;
+                while let Some(stripped) = strip_blob(leader) {
+                    leader = stripped;
+                }
+                if let Some(stripped) = strip_refwd(leader) {
+                    base = stripped;
+                    is_reply = true;
+                    changed = true;
+                } else {
+                    break;
+                }
+            }
+            if let Some(stripped) = strip_blob(base).filter(|stripped| !stripped.is_empty()) {
+                base = stripped;
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        // Remove "[fwd: ... ]" wrappers (step 6)
+        if let Some(inner) =
+            strip_prefix_ignore_case(base, "[fwd:").and_then(|inner| inner.strip_suffix(']'))
+        {
+            text = inner.to_string();
+            is_reply = true;
+        } else {
+            return (base.to_lowercase(), is_reply);
+        }
+    }
+}
+
+fn strip_blob(text: &str) -> Option<&str> {
+    let blob = text.strip_prefix('[')?;
+    let end = blob.find([']', '['])?;
+    if blob.as_bytes()[end] == b']' {
+        Some(blob[end + 1..].trim_start())
+    } else {
+        None
     }
 }
+
+fn strip_refwd(text: &str) -> Option<&str> {
+    let text = ["re", "fwd", "fw"]
+        .iter()
+        .find_map(|prefix| strip_prefix_ignore_case(text, prefix))?
+        .trim_start();
+    let text = strip_blob(text).unwrap_or(text);
+    text.strip_prefix(':')
+}
+
+fn strip_prefix_ignore_case<'x>(text: &'x str, prefix: &str) -> Option<&'x str> {
+    text.get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &text[prefix.len()..])
+}
+
+fn strip_suffix_ignore_case<'x>(text: &'x str, suffix: &str) -> Option<&'x str> {
+    text.len()
+        .checked_sub(suffix.len())
+        .and_then(|start| text.get(start..).map(|end| (start, end)))
+        .filter(|(_, end)| end.eq_ignore_ascii_case(suffix))
+        .map(|(start, _)| &text[..start])
+}
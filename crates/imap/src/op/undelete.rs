@@ -0,0 +1,631 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: LicenseRef-SEL
+ *
+ * This file is subject to the Stalwart Enterprise License Agreement (SEL) and
+ * is not open source software. It must not be modified or distributed without
+ * explicit permission from Stalwart Labs Ltd.
+ * Unauthorized use, modification, or distribution is strictly prohibited.
+ */
+
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use common::listener::SessionStream;
+use imap_proto::{
+    protocol::{
+        copy_move,
+        fetch::{self, Attribute, DataItem, FetchItem},
+        list::ListItem,
+        select::{self, Response},
+        status::{Status, StatusItem, StatusItemType},
+        Flag, ImapResponse,
+    },
+    Command, ResponseCode, ResponseType, StatusResponse,
+};
+use jmap::{
+    email::{
+        ingest::IngestSource,
+        undelete::{DeletedEmail, DELETED_MAILBOX_ID},
+    },
+    IngestError,
+};
+use jmap_proto::types::{id::Id, keyword::Keyword, state::StateChange, type_state::DataType};
+use mail_parser::MessageParser;
+
+use crate::core::{
+    ImapId, MailboxId, MailboxState, NextMailboxState, SavedSearch, SelectedMailbox, Session,
+    SessionData, State,
+};
+
+use super::fetch::AsImapDataItem;
+
+// UIDs below this value number the messages held by older versions, which
+// were not assigned a UID when deleted
+const LEGACY_UID_MAX: u32 = 1 << 31;
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_select_deleted(
+        &mut self,
+        arguments: select::Arguments,
+        command: Command,
+    ) -> crate::OpResult {
+        let data = self.state.session_data();
+        let (deleted, state) = match data.deleted_mailbox_state().await {
+            Ok(result) => result,
+            Err(response) => {
+                return self
+                    .write_bytes(response.with_tag(arguments.tag).into_bytes())
+                    .await;
+            }
+        };
+
+        let closed_previous = self.state.close_mailbox();
+        let response = Response {
+            mailbox: ListItem::new(arguments.mailbox_name),
+            total_messages: state.total_messages,
+            recent_messages: 0,
+            unseen_seq: 0,
+            uid_validity: state.uid_validity,
+            uid_next: state.uid_next,
+            closed_previous,
+            is_rev2: self.version.is_rev2(),
+            highest_modseq: None,
+            mailbox_id: Id::from_parts(data.account_id, DELETED_MAILBOX_ID).to_string(),
+        };
+
+        // The folder is always read-only, messages are restored by copying them out
+        let mailbox = Arc::new(SelectedMailbox {
+            id: MailboxId {
+                account_id: data.account_id,
+                mailbox_id: DELETED_MAILBOX_ID,
+            },
+            state: parking_lot::Mutex::new(state),
+            saved_search: parking_lot::Mutex::new(SavedSearch::None),
+            is_select: false,
+            is_condstore: false,
+            deleted: Some(deleted),
+        });
+        self.state = State::Selected { data, mailbox };
+
+        self.write_bytes(
+            StatusResponse::completed(command)
+                .with_tag(arguments.tag)
+                .with_code(ResponseCode::ReadOnly)
+                .serialize(response.serialize()),
+        )
+        .await
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    pub fn is_undelete_folder(&self, mailbox_name: &str) -> bool {
+        self.jmap
+            .core
+            .imap
+            .undelete_folder
+            .as_ref()
+            .is_some_and(|folder| folder == mailbox_name)
+            && self.jmap.is_undelete_enabled()
+    }
+
+    pub fn undelete_folder(&self) -> Option<&str> {
+        self.jmap
+            .core
+            .imap
+            .undelete_folder
+            .as_deref()
+            .filter(|_| self.jmap.is_undelete_enabled())
+    }
+
+    pub async fn deleted_mailbox_state(
+        &self,
+    ) -> crate::op::Result<(Vec<DeletedEmail>, MailboxState)> {
+        let deleted = self
+            .jmap
+            .email_deleted(self.account_id)
+            .await
+            .map_err(|err| {
+                tracing::error!(parent: &self.span,
+                    event = "error",
+                    context = "store",
+                    account_id = self.account_id,
+                    reason = ?err,
+                    "Failed to list deleted messages");
+                StatusResponse::database_failure()
+            })?;
+
+        let uid_next = self
+            .jmap
+            .deleted_uid_next(self.account_id)
+            .await
+            .map_err(|err| {
+                tracing::error!(parent: &self.span,
+                    event = "error",
+                    context = "store",
+                    account_id = self.account_id,
+                    reason = ?err,
+                    "Failed to obtain deleted messages uid next");
+                StatusResponse::database_failure()
+            })?;
+
+        // Messages keep the UID assigned when they were deleted. Messages held by
+        // older versions are numbered first by position, any change to them
+        // produces a different UID validity.
+        let mut uid_validity: u32 = 0x811c_9dc5;
+        let mut legacy_uid = 0;
+        let mut deleted = deleted
+            .into_iter()
+            .map(|email| {
+                let uid = match email.uid {
+                    Some(uid) => LEGACY_UID_MAX.saturating_add(uid),
+                    None => {
+                        for byte in email.hash.as_slice() {
+                            uid_validity = (uid_validity ^ *byte as u32).wrapping_mul(0x0100_0193);
+                        }
+                        legacy_uid += 1;
+                        legacy_uid
+                    }
+                };
+                (uid, email)
+            })
+            .collect::<Vec<_>>();
+        deleted.sort_unstable_by_key(|(uid, _)| *uid);
+
+        let mut id_to_imap = AHashMap::with_capacity(deleted.len());
+        let mut uid_to_id = AHashMap::with_capacity(deleted.len());
+        let mut uid_max = 0;
+        for (pos, (uid, _)) in deleted.iter().enumerate() {
+            id_to_imap.insert(
+                pos as u32,
+                ImapId {
+                    uid: *uid,
+                    seqnum: pos as u32 + 1,
+                },
+            );
+            uid_to_id.insert(*uid, pos as u32);
+            uid_max = *uid;
+        }
+        let total_messages = deleted.len();
+
+        Ok((
+            deleted.into_iter().map(|(_, email)| email).collect(),
+            MailboxState {
+                uid_next: LEGACY_UID_MAX.saturating_add(uid_next).max(uid_max + 1),
+                uid_validity: uid_validity.max(1),
+                uid_max,
+                id_to_imap,
+                uid_to_id,
+                total_messages,
+                modseq: None,
+                next_state: None,
+            },
+        ))
+    }
+
+    pub async fn deleted_status(
+        &self,
+        mailbox_name: String,
+        items: &[Status],
+    ) -> crate::op::Result<StatusItem> {
+        let (deleted, state) = self.deleted_mailbox_state().await?;
+
+        Ok(StatusItem {
+            mailbox_name,
+            items: items
+                .iter()
+                .map(|item| {
+                    (
+                        *item,
+                        match item {
+                            Status::Messages => StatusItemType::Number(deleted.len() as u64),
+                            Status::UidNext => StatusItemType::Number(state.uid_next as u64),
+                            Status::UidValidity => {
+                                StatusItemType::Number(state.uid_validity as u64)
+                            }
+                            Status::Unseen => StatusItemType::Number(
+                                deleted
+                                    .iter()
+                                    .filter(|email| !email.keywords.contains(&Keyword::Seen))
+                                    .count() as u64,
+                            ),
+                            Status::Deleted => StatusItemType::Number(
+                                deleted
+                                    .iter()
+                                    .filter(|email| email.keywords.contains(&Keyword::Deleted))
+                                    .count() as u64,
+                            ),
+                            Status::Size => StatusItemType::Number(
+                                deleted.iter().map(|email| email.size as u64).sum(),
+                            ),
+                            Status::Recent | Status::HighestModSeq => StatusItemType::Number(0),
+                            Status::MailboxId => StatusItemType::String(
+                                Id::from_parts(self.account_id, DELETED_MAILBOX_ID).to_string(),
+                            ),
+                        },
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    pub async fn fetch_deleted(
+        &self,
+        mut arguments: fetch::Arguments,
+        mailbox: &SelectedMailbox,
+        deleted: &[DeletedEmail],
+        is_uid: bool,
+    ) -> StatusResponse {
+        // Deleted messages never change
+        if arguments.changed_since.is_some() {
+            return StatusResponse::completed(Command::Fetch(is_uid)).with_tag(arguments.tag);
+        }
+
+        let ids = match mailbox
+            .sequence_to_ids(&arguments.sequence_set, is_uid)
+            .await
+        {
+            Ok(ids) => ids,
+            Err(response) => {
+                return response.with_tag(arguments.tag);
+            }
+        };
+
+        let needs_blobs = arguments.attributes.iter().any(|attribute| {
+            !matches!(
+                attribute,
+                Attribute::Flags
+                    | Attribute::InternalDate
+                    | Attribute::SaveDate
+                    | Attribute::Rfc822Size
+                    | Attribute::Uid
+                    | Attribute::ModSeq
+                    | Attribute::EmailId
+                    | Attribute::ThreadId
+            )
+        });
+        if is_uid {
+            if arguments.attributes.is_empty() {
+                arguments.attributes.push(Attribute::Flags);
+            } else if !arguments.attributes.contains(&Attribute::Uid) {
+                arguments.attributes.insert(0, Attribute::Uid);
+            }
+        }
+
+        let mut ids = ids
+            .into_iter()
+            .map(|(id, imap_id)| (imap_id.seqnum, imap_id.uid, id))
+            .collect::<Vec<_>>();
+        ids.sort_unstable_by_key(|(seqnum, _, _)| *seqnum);
+        if let Some(partial) = &arguments.partial {
+            ids = partial.apply(&ids).to_vec();
+        }
+        for (seqnum, uid, id) in ids {
+            let Some(email) = deleted.get(id as usize) else {
+                continue;
+            };
+
+            // Fetch and parse blob
+            let raw_message = if needs_blobs {
+                match self.jmap.get_blob(&email.hash, 0..usize::MAX).await {
+                    Ok(Some(raw_message)) => raw_message,
+                    Ok(None) => {
+                        tracing::debug!(parent: &self.span,
+                            event = "not-found",
+                            account_id = self.account_id,
+                            blob_id = ?email.hash,
+                            "Deleted message blob not found");
+                        continue;
+                    }
+                    Err(_) => {
+                        return StatusResponse::database_failure().with_tag(arguments.tag);
+                    }
+                }
+            } else {
+                Vec::new()
+            };
+            let message = if needs_blobs {
+                match MessageParser::new().parse(&raw_message) {
+                    Some(message) => Some(message),
+                    None => continue,
+                }
+            } else {
+                None
+            };
+
+            let mut items = Vec::with_capacity(arguments.attributes.len());
+            for attribute in &arguments.attributes {
+                match (attribute, &message) {
+                    (Attribute::Flags, _) => {
+                        items.push(DataItem::Flags {
+                            flags: email
+                                .keywords
+                                .iter()
+                                .map(|k| Flag::from(k.clone()))
+                                .collect(),
+                        });
+                    }
+                    (Attribute::InternalDate, _) => {
+                        items.push(DataItem::InternalDate {
+                            date: email.received_at as i64,
+                        });
+                    }
+                    (Attribute::SaveDate, _) => {
+                        items.push(DataItem::SaveDate {
+                            date: Some(email.deleted_at as i64),
+                        });
+                    }
+                    (Attribute::Rfc822Size, _) => {
+                        items.push(DataItem::Rfc822Size { size: email.size });
+                    }
+                    (Attribute::Uid, _) => {
+                        items.push(DataItem::Uid { uid });
+                    }
+                    (Attribute::Envelope, Some(message)) => {
+                        items.push(DataItem::Envelope {
+                            envelope: message.envelope(),
+                        });
+                    }
+                    (Attribute::Preview { .. }, Some(message)) => {
+                        items.push(DataItem::Preview {
+                            contents: message
+                                .body_preview(256)
+                                .map(|preview| preview.into_owned().into_bytes().into()),
+                        });
+                    }
+                    (Attribute::Rfc822, _) => {
+                        items.push(DataItem::Rfc822 {
+                            contents: raw_message.as_slice().into(),
+                        });
+                    }
+                    (Attribute::Rfc822Text, Some(message)) => {
+                        if let Some(text) = raw_message.get(message.root_part().offset_body..) {
+                            items.push(DataItem::Rfc822Text {
+                                contents: text.into(),
+                            });
+                        }
+                    }
+                    (Attribute::Rfc822Header, Some(message)) => {
+                        let message = message.root_part();
+                        if let Some(header) =
+                            raw_message.get(message.offset_header..message.offset_body)
+                        {
+                            items.push(DataItem::Rfc822Header {
+                                contents: header.into(),
+                            });
+                        }
+                    }
+                    (Attribute::Body, Some(message)) => {
+                        items.push(DataItem::Body {
+                            part: message.body_structure(false),
+                        });
+                    }
+                    (Attribute::BodyStructure, Some(message)) => {
+                        items.push(DataItem::BodyStructure {
+                            part: message.body_structure(true),
+                        });
+                    }
+                    (
+                        Attribute::BodySection {
+                            sections, partial, ..
+                        },
+                        Some(message),
+                    ) => {
+                        if let Some(contents) = message.body_section(sections, *partial) {
+                            items.push(DataItem::BodySection {
+                                sections: sections.to_vec(),
+                                origin_octet: partial.map(|(start, _)| start),
+                                contents,
+                            });
+                        }
+                    }
+                    (
+                        Attribute::Binary {
+                            sections, partial, ..
+                        },
+                        Some(message),
+                    ) => {
+                        if let Ok(Some(contents)) = message.binary(sections, *partial) {
+                            items.push(DataItem::Binary {
+                                sections: sections.to_vec(),
+                                offset: partial.map(|(start, _)| start),
+                                contents,
+                            });
+                        }
+                    }
+                    (Attribute::BinarySize { sections }, Some(message)) => {
+                        if let Some(size) = message.binary_size(sections) {
+                            items.push(DataItem::BinarySize {
+                                sections: sections.to_vec(),
+                                size,
+                            });
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            let mut buf = Vec::with_capacity(128);
+            FetchItem { id: seqnum, items }.serialize(&mut buf);
+            if !self.write_bytes(buf).await {
+                break;
+            }
+        }
+
+        StatusResponse::completed(Command::Fetch(is_uid)).with_tag(arguments.tag)
+    }
+
+    pub async fn restore_deleted(
+        &self,
+        arguments: copy_move::Arguments,
+        src_mailbox: &SelectedMailbox,
+        deleted: &[DeletedEmail],
+        dest_mailbox: MailboxId,
+        is_uid: bool,
+    ) -> Result<(), StatusResponse> {
+        let ids = match src_mailbox
+            .sequence_to_ids(&arguments.sequence_set, is_uid)
+            .await
+        {
+            Ok(ids) if !ids.is_empty() => ids,
+            Ok(_) => {
+                return Err(StatusResponse::no("No messages were found.").with_tag(arguments.tag));
+            }
+            Err(response) => {
+                return Err(response.with_tag(arguments.tag));
+            }
+        };
+
+        // Deleted messages can only be restored into the user's own mailboxes
+        let account_id = self.account_id;
+        if dest_mailbox.account_id != account_id {
+            return Err(StatusResponse::no(
+                "Deleted messages can only be restored into your own mailboxes.",
+            )
+            .with_tag(arguments.tag)
+            .with_code(ResponseCode::Cannot));
+        }
+        let account_quota = self
+            .get_access_token()
+            .await
+            .map_err(|r| r.with_tag(&arguments.tag))?
+            .quota as i64;
+
+        let mut ids = ids.into_iter().collect::<Vec<_>>();
+        ids.sort_unstable_by_key(|(_, imap_id)| imap_id.uid);
+        let mut response = StatusResponse::completed(Command::Copy(is_uid));
+        let mut copied_ids = Vec::with_capacity(ids.len());
+        let mut restored_ids = Vec::with_capacity(ids.len());
+        let mut last_change_id = None;
+        for (id, imap_id) in ids {
+            let Some(email) = deleted.get(id as usize) else {
+                continue;
+            };
+
+            match self
+                .jmap
+                .email_restore(
+                    account_id,
+                    account_quota,
+                    email,
+                    vec![dest_mailbox.mailbox_id].into(),
+                    IngestSource::Imap,
+                )
+                .await
+            {
+                Ok(restored) => {
+                    last_change_id = restored.change_id.into();
+                    restored_ids.push(id);
+                    if let Some(assigned_uid) = restored.imap_uids.first() {
+                        copied_ids.push((imap_id.uid, *assigned_uid));
+                    }
+                }
+                Err(IngestError::OverQuota) => {
+                    response = StatusResponse::no("Disk quota exceeded.")
+                        .with_code(ResponseCode::OverQuota);
+                    break;
+                }
+                Err(IngestError::Permanent { reason, .. }) => {
+                    response.rtype = ResponseType::No;
+                    response.message = reason.into();
+                }
+                Err(IngestError::Temporary) => {
+                    response = StatusResponse::database_failure();
+                    break;
+                }
+            }
+        }
+
+        // Restored messages are expunged from the folder
+        if !restored_ids.is_empty() {
+            src_mailbox.remove_deleted(&restored_ids);
+        }
+
+        // Broadcast changes
+        if let Some(change_id) = last_change_id {
+            self.jmap
+                .broadcast_state_change(
+                    StateChange::new(account_id)
+                        .with_change(DataType::Email, change_id)
+                        .with_change(DataType::Thread, change_id)
+                        .with_change(DataType::Mailbox, change_id),
+                )
+                .await;
+        }
+
+        if copied_ids.is_empty() {
+            return Err(if response.rtype != ResponseType::Ok {
+                response
+            } else {
+                StatusResponse::no("No messages were restored.")
+            }
+            .with_tag(arguments.tag));
+        }
+
+        // Prepare response
+        let uid_validity = self
+            .get_uid_validity(&dest_mailbox)
+            .await
+            .map_err(|r| r.with_tag(&arguments.tag))?;
+        let (src_uids, dest_uids) = copied_ids.into_iter().unzip();
+        self.write_bytes(
+            response
+                .with_tag(arguments.tag)
+                .with_code(ResponseCode::CopyUid {
+                    uid_validity,
+                    src_uids,
+                    dest_uids,
+                })
+                .into_bytes(),
+        )
+        .await;
+
+        Ok(())
+    }
+}
+
+impl SelectedMailbox {
+    // Removes messages from the recently deleted folder, the expunges are
+    // reported the next time the mailbox changes are written
+    fn remove_deleted(&self, ids: &[u32]) {
+        let mut current_state = self.state.lock();
+        let (mut next_state, mut deletions) = match current_state.next_state.take() {
+            Some(next_state) => (next_state.next_state, next_state.deletions),
+            None => (current_state.clone(), Vec::new()),
+        };
+
+        for id in ids {
+            if let Some(imap_id) = current_state.id_to_imap.remove(id) {
+                current_state.uid_to_id.remove(&imap_id.uid);
+                deletions.push(imap_id);
+            }
+            if let Some(imap_id) = next_state.id_to_imap.remove(id) {
+                next_state.uid_to_id.remove(&imap_id.uid);
+            }
+        }
+
+        // Renumber the remaining messages
+        let mut uids = next_state
+            .uid_to_id
+            .iter()
+            .map(|(uid, id)| (*uid, *id))
+            .collect::<Vec<_>>();
+        uids.sort_unstable();
+        for (seqnum, (uid, id)) in uids.into_iter().enumerate() {
+            next_state.id_to_imap.insert(
+                id,
+                ImapId {
+                    uid,
+                    seqnum: seqnum as u32 + 1,
+                },
+            );
+        }
+        next_state.total_messages = next_state.id_to_imap.len();
+
+        current_state.next_state = Some(Box::new(NextMailboxState {
+            next_state,
+            deletions,
+        }));
+    }
+}
@@ -52,6 +52,10 @@ pub enum RequestArguments {
     Identity,
     EmailSubmission,
     Quota,
+    AddressBook,
+    ContactCard,
+    Calendar,
+    CalendarEvent,
 }
 
 impl JsonObjectParser for ChangesRequest {
@@ -67,6 +71,10 @@ impl JsonObjectParser for ChangesRequest {
                 MethodObject::Identity => RequestArguments::Identity,
                 MethodObject::EmailSubmission => RequestArguments::EmailSubmission,
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::AddressBook => RequestArguments::AddressBook,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::Calendar => RequestArguments::Calendar,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/changes",
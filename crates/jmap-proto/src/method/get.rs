@@ -37,6 +37,10 @@ pub enum RequestArguments {
     Principal,
     Quota,
     Blob(blob::GetArguments),
+    AddressBook,
+    ContactCard,
+    Calendar,
+    CalendarEvent,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -72,6 +76,10 @@ impl JsonObjectParser for GetRequest<RequestArguments> {
                 MethodObject::Principal => RequestArguments::Principal,
                 MethodObject::Blob => RequestArguments::Blob(Default::default()),
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::AddressBook => RequestArguments::AddressBook,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::Calendar => RequestArguments::Calendar,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/get",
@@ -16,6 +16,7 @@ pub mod query;
 pub mod query_changes;
 pub mod search_snippet;
 pub mod set;
+pub mod undelete;
 pub mod upload;
 pub mod validate;
 
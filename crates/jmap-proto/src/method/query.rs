@@ -10,7 +10,7 @@ use store::fts::{FilterItem, FilterType, FtsFilter};
 
 use crate::{
     error::method::MethodError,
-    object::{email, mailbox},
+    object::{calendar, email, mailbox},
     parser::{json::Parser, Error, Ignore, JsonObjectParser, Token},
     request::{method::MethodObject, RequestProperty, RequestPropertyParser},
     types::{date::UTCDate, id::Id, keyword::Keyword, state::State},
@@ -100,6 +100,11 @@ pub enum Filter {
     IsActive(bool),
     Scope(String),
     ResourceType(String),
+    InAddressBook(Id),
+    Uid(String),
+    InCalendars(Vec<Id>),
+    Title(String),
+    Description(String),
     _T(String),
 
     And,
@@ -148,6 +153,8 @@ pub enum RequestArguments {
     SieveScript,
     Principal,
     Quota,
+    ContactCard,
+    CalendarEvent(calendar::QueryArguments),
 }
 
 impl JsonObjectParser for QueryRequest<RequestArguments> {
@@ -163,6 +170,8 @@ impl JsonObjectParser for QueryRequest<RequestArguments> {
                 MethodObject::SieveScript => RequestArguments::SieveScript,
                 MethodObject::Principal => RequestArguments::Principal,
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent(Default::default()),
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/query",
@@ -426,6 +435,23 @@ pub fn parse_filter(parser: &mut Parser) -> crate::parser::Result<Vec<Filter>> {
                                 .next_token::<String>()?
                                 .unwrap_string("resourceType")?,
                         ),
+                        (0x006b_6f6f_4273_7365_7264_6441_6e69, _) => Filter::InAddressBook(
+                            parser.next_token::<Id>()?.unwrap_string("inAddressBook")?,
+                        ),
+                        (0x0064_6975, _) => {
+                            Filter::Uid(parser.next_token::<String>()?.unwrap_string("uid")?)
+                        }
+                        (0x0073_7261_646e_656c_6143_6e69, _) => {
+                            Filter::InCalendars(<Vec<Id>>::parse(parser)?)
+                        }
+                        (0x0065_6c74_6974, _) => {
+                            Filter::Title(parser.next_token::<String>()?.unwrap_string("title")?)
+                        }
+                        (0x006e_6f69_7470_6972_6373_6564, _) => Filter::Description(
+                            parser
+                                .next_token::<String>()?
+                                .unwrap_string("description")?,
+                        ),
                         _ => {
                             if parser.is_eof || parser.skip_string() {
                                 let filter = Filter::_T(
@@ -630,6 +656,11 @@ impl Display for Filter {
             Filter::IsActive(_) => "isActive",
             Filter::ResourceType(_) => "resourceType",
             Filter::Scope(_) => "scope",
+            Filter::InAddressBook(_) => "inAddressBook",
+            Filter::Uid(_) => "uid",
+            Filter::InCalendars(_) => "inCalendars",
+            Filter::Title(_) => "title",
+            Filter::Description(_) => "description",
             Filter::_T(v) => v.as_str(),
             Filter::And => "and",
             Filter::Or => "or",
@@ -675,6 +706,7 @@ impl RequestPropertyParser for RequestArguments {
         match self {
             RequestArguments::Email(args) => args.parse(parser, property),
             RequestArguments::Mailbox(args) => args.parse(parser, property),
+            RequestArguments::CalendarEvent(args) => args.parse(parser, property),
             _ => Ok(false),
         }
     }
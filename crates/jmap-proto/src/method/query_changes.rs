@@ -70,6 +70,8 @@ impl JsonObjectParser for QueryChangesRequest {
                 MethodObject::Mailbox => RequestArguments::Mailbox(Default::default()),
                 MethodObject::EmailSubmission => RequestArguments::EmailSubmission,
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent(Default::default()),
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/queryChanges",
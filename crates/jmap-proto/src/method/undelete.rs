@@ -0,0 +1,136 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    error::set::SetError,
+    object::Object,
+    parser::{json::Parser, JsonObjectParser, Token},
+    request::RequestProperty,
+    types::{
+        blob::BlobId, date::UTCDate, id::Id, state::StateChange, value::Value, MaybeUnparsable,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct ListDeletedRequest {
+    pub account_id: Id,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ListDeletedResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "list")]
+    pub list: Vec<DeletedEmailInfo>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeletedEmailInfo {
+    #[serde(rename = "blobId")]
+    pub blob_id: BlobId,
+    #[serde(rename = "size")]
+    pub size: usize,
+    #[serde(rename = "receivedAt")]
+    pub received_at: UTCDate,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: UTCDate,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: UTCDate,
+    #[serde(rename = "mailboxIds")]
+    pub mailbox_ids: VecMap<Id, bool>,
+    #[serde(rename = "keywords")]
+    pub keywords: VecMap<String, bool>,
+    #[serde(rename = "subject")]
+    pub subject: Option<String>,
+    #[serde(rename = "from")]
+    pub from: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UndeleteRequest {
+    pub account_id: Id,
+    pub blob_ids: Vec<MaybeUnparsable<BlobId>>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct UndeleteResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "restored")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub restored: VecMap<BlobId, Object<Value>>,
+
+    #[serde(rename = "notRestored")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub not_restored: VecMap<MaybeUnparsable<BlobId>, SetError>,
+
+    #[serde(skip)]
+    pub state_change: Option<StateChange>,
+}
+
+impl JsonObjectParser for ListDeletedRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = ListDeletedRequest {
+            account_id: Id::default(),
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl JsonObjectParser for UndeleteRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = UndeleteRequest {
+            account_id: Id::default(),
+            blob_ids: Vec::new(),
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x0073_6449_626f_6c62 if !key.is_ref => {
+                    request.blob_ids = <Vec<MaybeUnparsable<BlobId>>>::parse(parser)?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::{
+    parser::{json::Parser, Ignore},
+    request::{RequestProperty, RequestPropertyParser},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct SetArguments {
+    pub on_destroy_remove_events: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventSetArguments {
+    pub send_scheduling_messages: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QueryArguments {
+    pub expand_recurrences: Option<bool>,
+    pub time_zone: Option<String>,
+}
+
+impl RequestPropertyParser for SetArguments {
+    fn parse(
+        &mut self,
+        parser: &mut Parser,
+        property: RequestProperty,
+    ) -> crate::parser::Result<bool> {
+        if property.hash[0] == 0x4565_766f_6d65_5279_6f72_7473_6544_6e6f
+            && property.hash[1] == 0x0073_746e_6576
+        {
+            self.on_destroy_remove_events = parser
+                .next_token::<Ignore>()?
+                .unwrap_bool_or_null("onDestroyRemoveEvents")?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl RequestPropertyParser for EventSetArguments {
+    fn parse(
+        &mut self,
+        parser: &mut Parser,
+        property: RequestProperty,
+    ) -> crate::parser::Result<bool> {
+        if property.hash[0] == 0x654d_676e_696c_7564_6568_6353_646e_6573
+            && property.hash[1] == 0x7365_6761_7373
+        {
+            self.send_scheduling_messages = parser
+                .next_token::<Ignore>()?
+                .unwrap_bool_or_null("sendSchedulingMessages")?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl RequestPropertyParser for QueryArguments {
+    fn parse(
+        &mut self,
+        parser: &mut Parser,
+        property: RequestProperty,
+    ) -> crate::parser::Result<bool> {
+        match (&property.hash[0], &property.hash[1]) {
+            (0x6563_6e65_7272_7563_6552_646e_6170_7865, 0x0073) => {
+                self.expand_recurrences = parser
+                    .next_token::<Ignore>()?
+                    .unwrap_bool_or_null("expandRecurrences")?;
+            }
+            (0x656e_6f5a_656d_6974, 0) => {
+                self.time_zone = parser
+                    .next_token::<String>()?
+                    .unwrap_string_or_null("timeZone")?;
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
@@ -5,6 +5,8 @@
  */
 
 pub mod blob;
+pub mod calendar;
+pub mod contact;
 pub mod email;
 pub mod email_submission;
 pub mod index;
@@ -112,6 +114,7 @@ const LIST: u8 = 9;
 const OBJECT: u8 = 10;
 const ACL: u8 = 11;
 const NULL: u8 = 12;
+const INT: u8 = 13;
 
 impl Serialize for Value {
     fn serialize(self) -> Vec<u8> {
@@ -205,6 +208,10 @@ impl SerializeInto for Value {
                 buf.push(UNSIGNED_INT);
                 v.serialize_into(buf);
             }
+            Value::Int(v) => {
+                buf.push(INT);
+                buf.extend_from_slice(&v.to_be_bytes());
+            }
             Value::Bool(v) => {
                 buf.push(if *v { BOOL_TRUE } else { BOOL_FALSE });
             }
@@ -285,6 +292,13 @@ impl DeserializeFrom for Value {
                 Some(Value::Acl(items))
             }
             NULL => Some(Value::Null),
+            INT => {
+                let mut value = [0u8; std::mem::size_of::<i64>()];
+                for byte in value.iter_mut() {
+                    *byte = *bytes.next()?;
+                }
+                Some(Value::Int(i64::from_be_bytes(value)))
+            }
             _ => None,
         }
     }
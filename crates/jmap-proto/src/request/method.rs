@@ -29,6 +29,10 @@ pub enum MethodObject {
     SieveScript,
     Principal,
     Quota,
+    AddressBook,
+    ContactCard,
+    Calendar,
+    CalendarEvent,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,6 +49,8 @@ pub enum MethodFunction {
     Lookup,
     Upload,
     Echo,
+    ListDeleted,
+    Undelete,
 }
 
 impl JsonObjectParser for MethodName {
@@ -96,6 +102,10 @@ impl JsonObjectParser for MethodName {
                 0x0074_7069_7263_5365_7665_6953 => MethodObject::SieveScript,
                 0x006c_6170_6963_6e69_7250 => MethodObject::Principal,
                 0x0061_746f_7551 => MethodObject::Quota,
+                0x006b_6f6f_4273_7365_7264_6441 => MethodObject::AddressBook,
+                0x0064_7261_4374_6361_746e_6f43 => MethodObject::ContactCard,
+                0x7261_646e_656c_6143 => MethodObject::Calendar,
+                0x0074_6e65_7645_7261_646e_656c_6143 => MethodObject::CalendarEvent,
                 0x6572_6f43 => MethodObject::Core,
                 _ => return Err(parser.error_value()),
             },
@@ -112,6 +122,8 @@ impl JsonObjectParser for MethodName {
                 0x7075_6b6f_6f6c => MethodFunction::Lookup,
                 0x6461_6f6c_7075 => MethodFunction::Upload,
                 0x6f68_6365 => MethodFunction::Echo,
+                0x0064_6574_656c_6544_7473_696c => MethodFunction::ListDeleted,
+                0x6574_656c_6564_6e75 => MethodFunction::Undelete,
                 _ => return Err(parser.error_value()),
             },
         })
@@ -158,6 +170,8 @@ impl MethodName {
             (MethodFunction::Copy, MethodObject::Email) => "Email/copy",
             (MethodFunction::Import, MethodObject::Email) => "Email/import",
             (MethodFunction::Parse, MethodObject::Email) => "Email/parse",
+            (MethodFunction::ListDeleted, MethodObject::Email) => "Email/listDeleted",
+            (MethodFunction::Undelete, MethodObject::Email) => "Email/undelete",
 
             (MethodFunction::Get, MethodObject::SearchSnippet) => "SearchSnippet/get",
 
@@ -190,6 +204,29 @@ impl MethodName {
             (MethodFunction::Query, MethodObject::Quota) => "Quota/query",
             (MethodFunction::QueryChanges, MethodObject::Quota) => "Quota/queryChanges",
 
+            (MethodFunction::Get, MethodObject::AddressBook) => "AddressBook/get",
+            (MethodFunction::Changes, MethodObject::AddressBook) => "AddressBook/changes",
+            (MethodFunction::Set, MethodObject::AddressBook) => "AddressBook/set",
+
+            (MethodFunction::Get, MethodObject::ContactCard) => "ContactCard/get",
+            (MethodFunction::Changes, MethodObject::ContactCard) => "ContactCard/changes",
+            (MethodFunction::Query, MethodObject::ContactCard) => "ContactCard/query",
+            (MethodFunction::QueryChanges, MethodObject::ContactCard) => "ContactCard/queryChanges",
+            (MethodFunction::Set, MethodObject::ContactCard) => "ContactCard/set",
+            (MethodFunction::Copy, MethodObject::ContactCard) => "ContactCard/copy",
+
+            (MethodFunction::Get, MethodObject::Calendar) => "Calendar/get",
+            (MethodFunction::Changes, MethodObject::Calendar) => "Calendar/changes",
+            (MethodFunction::Set, MethodObject::Calendar) => "Calendar/set",
+
+            (MethodFunction::Get, MethodObject::CalendarEvent) => "CalendarEvent/get",
+            (MethodFunction::Changes, MethodObject::CalendarEvent) => "CalendarEvent/changes",
+            (MethodFunction::Query, MethodObject::CalendarEvent) => "CalendarEvent/query",
+            (MethodFunction::QueryChanges, MethodObject::CalendarEvent) => {
+                "CalendarEvent/queryChanges"
+            }
+            (MethodFunction::Set, MethodObject::CalendarEvent) => "CalendarEvent/set",
+
             (MethodFunction::Get, MethodObject::Blob) => "Blob/get",
             (MethodFunction::Copy, MethodObject::Blob) => "Blob/copy",
             (MethodFunction::Lookup, MethodObject::Blob) => "Blob/lookup",
@@ -217,6 +254,10 @@ impl Display for MethodObject {
             MethodObject::Thread => "Thread",
             MethodObject::Email => "Email",
             MethodObject::Quota => "Quota",
+            MethodObject::AddressBook => "AddressBook",
+            MethodObject::ContactCard => "ContactCard",
+            MethodObject::Calendar => "Calendar",
+            MethodObject::CalendarEvent => "CalendarEvent",
         })
     }
 }
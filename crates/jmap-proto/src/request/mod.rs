@@ -29,6 +29,7 @@ use crate::{
         query_changes::QueryChangesRequest,
         search_snippet::GetSearchSnippetRequest,
         set::{self, SetRequest},
+        undelete::{ListDeletedRequest, UndeleteRequest},
         upload::BlobUploadRequest,
         validate::ValidateSieveScriptRequest,
     },
@@ -73,6 +74,8 @@ pub enum RequestMethod {
     ValidateScript(ValidateSieveScriptRequest),
     LookupBlob(BlobLookupRequest),
     UploadBlob(BlobUploadRequest),
+    ListDeletedEmail(ListDeletedRequest),
+    UndeleteEmail(UndeleteRequest),
     Echo(Echo),
     Error(MethodError),
 }
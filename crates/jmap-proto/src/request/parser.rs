@@ -22,6 +22,7 @@ use crate::{
         query_changes::QueryChangesRequest,
         search_snippet::GetSearchSnippetRequest,
         set::SetRequest,
+        undelete::{ListDeletedRequest, UndeleteRequest},
         upload::BlobUploadRequest,
         validate::ValidateSieveScriptRequest,
     },
@@ -126,7 +127,11 @@ impl Request {
                                 | MethodObject::SieveScript
                                 | MethodObject::Principal
                                 | MethodObject::Quota
-                                | MethodObject::Blob,
+                                | MethodObject::Blob
+                                | MethodObject::AddressBook
+                                | MethodObject::ContactCard
+                                | MethodObject::Calendar
+                                | MethodObject::CalendarEvent,
                             ) => GetRequest::parse(parser).map(RequestMethod::Get),
                             (MethodFunction::Get, MethodObject::SearchSnippet) => {
                                 GetSearchSnippetRequest::parse(parser)
@@ -144,9 +149,10 @@ impl Request {
                             (MethodFunction::QueryChanges, _) => {
                                 QueryChangesRequest::parse(parser).map(RequestMethod::QueryChanges)
                             }
-                            (MethodFunction::Copy, MethodObject::Email) => {
-                                CopyRequest::parse(parser).map(RequestMethod::Copy)
-                            }
+                            (
+                                MethodFunction::Copy,
+                                MethodObject::Email | MethodObject::ContactCard,
+                            ) => CopyRequest::parse(parser).map(RequestMethod::Copy),
                             (MethodFunction::Copy, MethodObject::Blob) => {
                                 CopyBlobRequest::parse(parser).map(RequestMethod::CopyBlob)
                             }
@@ -162,6 +168,13 @@ impl Request {
                             (MethodFunction::Parse, MethodObject::Email) => {
                                 ParseEmailRequest::parse(parser).map(RequestMethod::ParseEmail)
                             }
+                            (MethodFunction::ListDeleted, MethodObject::Email) => {
+                                ListDeletedRequest::parse(parser)
+                                    .map(RequestMethod::ListDeletedEmail)
+                            }
+                            (MethodFunction::Undelete, MethodObject::Email) => {
+                                UndeleteRequest::parse(parser).map(RequestMethod::UndeleteEmail)
+                            }
                             (MethodFunction::Validate, MethodObject::SieveScript) => {
                                 ValidateSieveScriptRequest::parse(parser)
                                     .map(RequestMethod::ValidateScript)
@@ -22,6 +22,7 @@ use crate::{
         query_changes::QueryChangesResponse,
         search_snippet::GetSearchSnippetResponse,
         set::SetResponse,
+        undelete::{ListDeletedResponse, UndeleteResponse},
         upload::BlobUploadResponse,
         validate::ValidateSieveScriptResponse,
     },
@@ -47,6 +48,8 @@ pub enum ResponseMethod {
     ValidateScript(ValidateSieveScriptResponse),
     LookupBlob(BlobLookupResponse),
     UploadBlob(BlobUploadResponse),
+    ListDeletedEmail(ListDeletedResponse),
+    UndeleteEmail(UndeleteResponse),
     Echo(Echo),
     Error(MethodError),
 }
@@ -190,6 +193,18 @@ impl From<BlobLookupResponse> for ResponseMethod {
     }
 }
 
+impl From<ListDeletedResponse> for ResponseMethod {
+    fn from(list_deleted: ListDeletedResponse) -> Self {
+        ResponseMethod::ListDeletedEmail(list_deleted)
+    }
+}
+
+impl From<UndeleteResponse> for ResponseMethod {
+    fn from(undelete: UndeleteResponse) -> Self {
+        ResponseMethod::UndeleteEmail(undelete)
+    }
+}
+
 impl<T: Into<ResponseMethod>> From<Result<T, MethodError>> for ResponseMethod {
     fn from(result: Result<T, MethodError>) -> Self {
         match result {
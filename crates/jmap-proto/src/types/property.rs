@@ -119,6 +119,24 @@ pub enum Property {
     WarnLimit,
     SoftLimit,
     Scope,
+    AddressBookIds,
+    IsDefault,
+    Uid,
+    MayRead,
+    MayWrite,
+    MayShare,
+    CalendarIds,
+    Color,
+    IsVisible,
+    UtcStart,
+    UtcEnd,
+    MayReadFreeBusy,
+    MayWriteAll,
+    MayWriteOwn,
+    MayUpdatePrivate,
+    MayRsvp,
+    MayAdmin,
+    Metadata,
     Digest(DigestProperty),
     Data(DataProperty),
     _T(String),
@@ -247,7 +265,10 @@ impl JsonObjectParser for SetProperty {
 
         if is_patch {
             match &property {
-                Property::MailboxIds | Property::Members => match Id::parse(parser) {
+                Property::MailboxIds
+                | Property::Members
+                | Property::AddressBookIds
+                | Property::CalendarIds => match Id::parse(parser) {
                     Ok(id) => {
                         patch.push(Value::Id(id));
                     }
@@ -333,6 +354,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
     Some(match first_char {
         b'a' => match hash {
             0x6c63 => Property::Acl,
+            0x0073_6449_6b6f_6f42_7373_6572_6464 => Property::AddressBookIds,
             0x7365_7361_696c => Property::Aliases,
             0x7374_6e65_6d68_6361_7474 => Property::Attachments,
             _ => return None,
@@ -349,6 +371,8 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x63 => Property::Cc,
             0x7465_7372_6168 => Property::Charset,
             0x6469 => Property::Cid,
+            0x7364_4972_6164_6e65_6c61 => Property::CalendarIds,
+            0x726f_6c6f => Property::Color,
             _ => return None,
         },
         b'd' => match hash {
@@ -387,6 +411,8 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x0065_7669_7463_4173 => Property::IsActive,
             0x6465_6c62_616e_4573 => Property::IsEnabled,
             0x0064_6562_6972_6373_6275_5373 => Property::IsSubscribed,
+            0x746c_7561_6665_4473 => Property::IsDefault,
+            0x656c_6269_7369_5673 => Property::IsVisible,
             _ => return None,
         },
         b'k' => match hash {
@@ -459,6 +485,9 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x0073_6c69_616d_4564_6165_726e => Property::UnreadEmails,
             0x7364_6165_7268_5464_6165_726e => Property::UnreadThreads,
             0x6c72 => Property::Url,
+            0x6469 => Property::Uid,
+            0x0074_7261_7453_6374 => Property::UtcStart,
+            0x0064_6e45_6374 => Property::UtcEnd,
             _ => return None,
         },
         b'v' => match hash {
@@ -660,6 +689,16 @@ impl JsonObjectParser for ObjectProperty {
                 0x656d_616e_6552_7961 => Property::MayRename,
                 0x6574_656c_6544_7961 => Property::MayDelete,
                 0x7469_6d62_7553_7961 => Property::MaySubmit,
+                0x6461_6552_7961 => Property::MayRead,
+                0x0065_7469_7257_7961 => Property::MayWrite,
+                0x0065_7261_6853_7961 => Property::MayShare,
+                0x7973_7542_6565_7246_6461_6552_7961 => Property::MayReadFreeBusy,
+                0x6c6c_4165_7469_7257_7961 => Property::MayWriteAll,
+                0x6e77_4f65_7469_7257_7961 => Property::MayWriteOwn,
+                0x0065_7461_7669_7250_6574_6164_7055_7961 => Property::MayUpdatePrivate,
+                0x5056_5352_7961 => Property::MayRsvp,
+                0x006e_696d_6441_7961 => Property::MayAdmin,
+                0x0061_7461_6461_7465 => Property::Metadata,
                 _ => parser.invalid_property()?,
             },
             b'n' => match hash {
@@ -881,6 +920,24 @@ impl Display for Property {
             Property::Used => write!(f, "used"),
             Property::HardLimit => write!(f, "hardLimit"),
             Property::Scope => write!(f, "scope"),
+            Property::AddressBookIds => write!(f, "addressBookIds"),
+            Property::IsDefault => write!(f, "isDefault"),
+            Property::Uid => write!(f, "uid"),
+            Property::MayRead => write!(f, "mayRead"),
+            Property::MayWrite => write!(f, "mayWrite"),
+            Property::MayShare => write!(f, "mayShare"),
+            Property::CalendarIds => write!(f, "calendarIds"),
+            Property::Color => write!(f, "color"),
+            Property::IsVisible => write!(f, "isVisible"),
+            Property::UtcStart => write!(f, "utcStart"),
+            Property::UtcEnd => write!(f, "utcEnd"),
+            Property::MayReadFreeBusy => write!(f, "mayReadFreeBusy"),
+            Property::MayWriteAll => write!(f, "mayWriteAll"),
+            Property::MayWriteOwn => write!(f, "mayWriteOwn"),
+            Property::MayUpdatePrivate => write!(f, "mayUpdatePrivate"),
+            Property::MayRsvp => write!(f, "mayRSVP"),
+            Property::MayAdmin => write!(f, "mayAdmin"),
+            Property::Metadata => write!(f, "metadata"),
             Property::WarnLimit => write!(f, "warnLimit"),
             Property::SoftLimit => write!(f, "softLimit"),
             Property::_T(s) => write!(f, "{s}"),
@@ -1063,6 +1120,24 @@ impl From<&Property> for u8 {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::AddressBookIds => 104,
+            Property::IsDefault => 105,
+            Property::Uid => 106,
+            Property::MayRead => 107,
+            Property::MayWrite => 108,
+            Property::MayShare => 109,
+            Property::CalendarIds => 110,
+            Property::Color => 111,
+            Property::IsVisible => 112,
+            Property::UtcStart => 113,
+            Property::UtcEnd => 114,
+            Property::MayReadFreeBusy => 115,
+            Property::MayWriteAll => 116,
+            Property::MayWriteOwn => 117,
+            Property::MayUpdatePrivate => 118,
+            Property::MayRsvp => 119,
+            Property::MayAdmin => 120,
+            Property::Metadata => 121,
             Property::Digest(_) | Property::Data(_) => unreachable!("invalid property"),
         }
     }
@@ -1205,6 +1280,24 @@ impl SerializeInto for Property {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::AddressBookIds => 104,
+            Property::IsDefault => 105,
+            Property::Uid => 106,
+            Property::MayRead => 107,
+            Property::MayWrite => 108,
+            Property::MayShare => 109,
+            Property::CalendarIds => 110,
+            Property::Color => 111,
+            Property::IsVisible => 112,
+            Property::UtcStart => 113,
+            Property::UtcEnd => 114,
+            Property::MayReadFreeBusy => 115,
+            Property::MayWriteAll => 116,
+            Property::MayWriteOwn => 117,
+            Property::MayUpdatePrivate => 118,
+            Property::MayRsvp => 119,
+            Property::MayAdmin => 120,
+            Property::Metadata => 121,
             Property::Digest(_) | Property::Data(_) => {
                 unreachable!("Property::Digest and Property::Data are not serializable")
             }
@@ -1323,6 +1416,24 @@ impl DeserializeFrom for Property {
             101 => Some(Property::WarnLimit),
             102 => Some(Property::SoftLimit),
             103 => Some(Property::Scope),
+            104 => Some(Property::AddressBookIds),
+            105 => Some(Property::IsDefault),
+            106 => Some(Property::Uid),
+            107 => Some(Property::MayRead),
+            108 => Some(Property::MayWrite),
+            109 => Some(Property::MayShare),
+            110 => Some(Property::CalendarIds),
+            111 => Some(Property::Color),
+            112 => Some(Property::IsVisible),
+            113 => Some(Property::UtcStart),
+            114 => Some(Property::UtcEnd),
+            115 => Some(Property::MayReadFreeBusy),
+            116 => Some(Property::MayWriteAll),
+            117 => Some(Property::MayWriteOwn),
+            118 => Some(Property::MayUpdatePrivate),
+            119 => Some(Property::MayRsvp),
+            120 => Some(Property::MayAdmin),
+            121 => Some(Property::Metadata),
             _ => None,
         }
     }
@@ -31,6 +31,7 @@ use super::{
 pub enum Value {
     Text(String),
     UnsignedInt(u64),
+    Int(i64),
     Bool(bool),
     Id(Id),
     Date(UTCDate),
@@ -111,6 +112,38 @@ impl Value {
         })
     }
 
+    pub fn parse_json(
+        token: Token<String>,
+        parser: &mut Parser<'_>,
+    ) -> crate::parser::Result<Self> {
+        Ok(match token {
+            Token::DictStart => {
+                let mut properties = Object::with_capacity(4);
+                while let Some(key) = parser.next_dict_key::<String>()? {
+                    let value = Value::parse_json(parser.next_token()?, parser)?;
+                    properties.append(Property::_T(key), value);
+                }
+                Value::Object(properties)
+            }
+            Token::ArrayStart => {
+                let mut values = Vec::with_capacity(4);
+                loop {
+                    match parser.next_token::<String>()? {
+                        Token::Comma => (),
+                        Token::ArrayEnd => break,
+                        token => {
+                            values.push(Value::parse_json(token, parser)?);
+                        }
+                    }
+                }
+                Value::List(values)
+            }
+            Token::Integer(v) if v < 0 => Value::Int(v),
+            Token::Float(v) if v < 0.0 => Value::Int(v as i64),
+            token => Value::parse::<String, String>(token, parser)?,
+        })
+    }
+
     pub fn from_property(
         parser: &mut Parser<'_>,
         property: &Property,
@@ -174,7 +207,16 @@ impl Value {
             | Property::MayCreateChild
             | Property::MayRename
             | Property::MayDelete
-            | Property::MaySubmit => Ok(parser
+            | Property::MaySubmit
+            | Property::MayRead
+            | Property::MayWrite
+            | Property::MayShare
+            | Property::MayReadFreeBusy
+            | Property::MayWriteAll
+            | Property::MayWriteOwn
+            | Property::MayUpdatePrivate
+            | Property::MayRsvp
+            | Property::MayAdmin => Ok(parser
                 .next_token::<String>()?
                 .unwrap_bool_or_null("")?
                 .map(Value::Bool)
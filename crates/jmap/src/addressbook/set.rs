@@ -0,0 +1,516 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{SetRequest, SetResponse},
+    object::{
+        contact::SetArguments,
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    query::Filter,
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+};
+
+use crate::{
+    auth::{acl::EffectiveAcl, AccessToken},
+    mailbox::set::MailboxSubscribe,
+    JMAP,
+};
+
+use super::DEFAULT_ADDRESS_BOOK_ID;
+
+struct SetContext<'x> {
+    access_token: &'x AccessToken,
+    is_shared: bool,
+    response: SetResponse,
+}
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::Name)
+        .index_as(IndexAs::Text {
+            tokenize: true,
+            index: true,
+        })
+        .required(),
+    IndexProperty::new(Property::SortOrder).index_as(IndexAs::Integer),
+    IndexProperty::new(Property::IsSubscribed).index_as(IndexAs::IntegerList),
+    IndexProperty::new(Property::Acl).index_as(IndexAs::Acl),
+];
+
+impl JMAP {
+    pub async fn address_book_set(
+        &self,
+        mut request: SetRequest<SetArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        // Prepare response
+        let account_id = request.account_id.document_id();
+        let on_destroy_remove_contents = request
+            .arguments
+            .on_destroy_remove_contents
+            .unwrap_or(false);
+        let will_destroy = request.unwrap_destroy();
+        let mut ctx = SetContext {
+            is_shared: access_token.is_shared(account_id),
+            access_token,
+            response: self
+                .prepare_set_response(&request, Collection::AddressBook)
+                .await?,
+        };
+        self.address_book_get_or_create(account_id).await?;
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        'create: for (id, object) in request.unwrap_create() {
+            if ctx.is_shared {
+                ctx.response.not_created.append(
+                    id,
+                    SetError::forbidden()
+                        .with_description("You are not allowed to create address books."),
+                );
+                continue 'create;
+            }
+
+            match self.address_book_set_item(object, None, &ctx).await? {
+                Ok(builder) => {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::AddressBook)
+                        .create_document()
+                        .custom(builder);
+
+                    match self
+                        .core
+                        .storage
+                        .data
+                        .write(batch.build())
+                        .await
+                        .and_then(|ids| ids.last_document_id())
+                    {
+                        Ok(document_id) => {
+                            changes.log_insert(Collection::AddressBook, document_id);
+                            ctx.response.created(id, document_id);
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                event = "error",
+                                context = "address_book_set",
+                                account_id = account_id,
+                                error = ?err,
+                                "Failed to create address book.");
+                            return Err(MethodError::ServerPartialFail);
+                        }
+                    }
+                }
+                Err(err) => {
+                    ctx.response.not_created.append(id, err);
+                    continue 'create;
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                ctx.response
+                    .not_updated
+                    .append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain address book
+            let document_id = id.document_id();
+            if let Some(address_book) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::AddressBook,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                // Validate ACL
+                if ctx.is_shared {
+                    let acl = address_book.inner.effective_acl(access_token);
+                    if !acl.contains(Acl::Modify) {
+                        ctx.response.not_updated.append(
+                            id,
+                            SetError::forbidden().with_description(
+                                "You are not allowed to modify this address book.",
+                            ),
+                        );
+                        continue 'update;
+                    } else if object.properties.contains_key(&Property::Acl)
+                        && !acl.contains(Acl::Administer)
+                    {
+                        ctx.response.not_updated.append(
+                            id,
+                            SetError::forbidden().with_description(
+                                "You are not allowed to change the permissions of this address book.",
+                            ),
+                        );
+                        continue 'update;
+                    }
+                }
+
+                match self
+                    .address_book_set_item(object, address_book.into(), &ctx)
+                    .await?
+                {
+                    Ok(builder) => {
+                        let mut batch = BatchBuilder::new();
+                        batch
+                            .with_account_id(account_id)
+                            .with_collection(Collection::AddressBook)
+                            .update_document(document_id)
+                            .custom(builder);
+
+                        if !batch.is_empty() {
+                            match self.core.storage.data.write(batch.build()).await {
+                                Ok(_) => {
+                                    changes.log_update(Collection::AddressBook, document_id);
+                                }
+                                Err(store::Error::AssertValueFailed) => {
+                                    ctx.response.not_updated.append(id, SetError::forbidden().with_description(
+                                        "Another process modified this address book, please try again.",
+                                    ));
+                                    continue 'update;
+                                }
+                                Err(err) => {
+                                    tracing::error!(
+                                        event = "error",
+                                        context = "address_book_set",
+                                        account_id = account_id,
+                                        error = ?err,
+                                        "Failed to update address book(s).");
+                                    return Err(MethodError::ServerPartialFail);
+                                }
+                            }
+                        }
+                        ctx.response.updated.append(id, None);
+                    }
+                    Err(err) => {
+                        ctx.response.not_updated.append(id, err);
+                        continue 'update;
+                    }
+                }
+            } else {
+                ctx.response.not_updated.append(id, SetError::not_found());
+            }
+        }
+
+        // Process deletions
+        let mut did_remove_contents = false;
+        for id in will_destroy {
+            match self
+                .address_book_destroy(
+                    account_id,
+                    id.document_id(),
+                    &mut changes,
+                    ctx.access_token,
+                    on_destroy_remove_contents,
+                )
+                .await?
+            {
+                Ok(removed_contents) => {
+                    did_remove_contents |= removed_contents;
+                    ctx.response.destroyed.push(id);
+                }
+                Err(err) => {
+                    ctx.response.not_destroyed.append(id, err);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            let state_change =
+                StateChange::new(account_id).with_change(DataType::AddressBook, changes.change_id);
+            ctx.response.state_change = if did_remove_contents {
+                state_change.with_change(DataType::ContactCard, changes.change_id)
+            } else {
+                state_change
+            }
+            .into();
+            ctx.response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        Ok(ctx.response)
+    }
+
+    pub async fn address_book_destroy(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        changes: &mut ChangeLogBuilder,
+        access_token: &AccessToken,
+        remove_contents: bool,
+    ) -> Result<Result<bool, SetError>, MethodError> {
+        // The default address book cannot be deleted
+        if document_id == DEFAULT_ADDRESS_BOOK_ID && !access_token.is_super_user() {
+            return Ok(Err(SetError::forbidden().with_description(
+                "You are not allowed to delete the default address book.",
+            )));
+        }
+
+        // Obtain address book
+        let address_book = if let Some(address_book) = self
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::AddressBook,
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            address_book
+        } else {
+            return Ok(Err(SetError::not_found()));
+        };
+
+        // Validate ACLs
+        if access_token.is_shared(account_id) {
+            let acl = address_book.inner.effective_acl(access_token);
+            if !acl.contains(Acl::Administer) {
+                if !acl.contains(Acl::Delete) {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "You are not allowed to delete this address book.",
+                    )));
+                } else if remove_contents && !acl.contains(Acl::RemoveItems) {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "You are not allowed to delete contacts from this address book.",
+                    )));
+                }
+            }
+        }
+
+        // Verify that the address book is empty
+        let mut did_remove_contents = false;
+        let card_ids = self
+            .filter(
+                account_id,
+                Collection::ContactCard,
+                vec![Filter::eq(Property::AddressBookIds, document_id)],
+            )
+            .await?
+            .results;
+        if !card_ids.is_empty() {
+            if remove_contents {
+                did_remove_contents = true;
+
+                // If the card is in multiple address books, remove it from the current one,
+                // otherwise delete it.
+                for card_id in card_ids {
+                    if let Err(err) = self
+                        .contact_card_remove_from_address_book(
+                            account_id,
+                            card_id,
+                            document_id,
+                            changes,
+                        )
+                        .await?
+                    {
+                        return Ok(Err(err));
+                    }
+                }
+            } else {
+                return Ok(Err(SetError::new(SetErrorType::AddressBookHasContents)
+                    .with_description("Address book is not empty.")));
+            }
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::AddressBook)
+            .delete_document(document_id)
+            .custom(ObjectIndexBuilder::new(SCHEMA).with_current(address_book));
+
+        match self.core.storage.data.write(batch.build()).await {
+            Ok(_) => {
+                changes.log_delete(Collection::AddressBook, document_id);
+                Ok(Ok(did_remove_contents))
+            }
+            Err(store::Error::AssertValueFailed) => Ok(Err(SetError::forbidden()
+                .with_description(concat!(
+                    "Another process modified this address book ",
+                    "while deleting it, please try again."
+                )))),
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "address_book_set",
+                    account_id = account_id,
+                    document_id = document_id,
+                    error = ?err,
+                    "Failed to delete address book.");
+                Err(MethodError::ServerPartialFail)
+            }
+        }
+    }
+
+    async fn address_book_set_item(
+        &self,
+        changes_: Object<SetValue>,
+        update: Option<HashedValue<Object<Value>>>,
+        ctx: &SetContext<'_>,
+    ) -> Result<Result<ObjectIndexBuilder, SetError>, MethodError> {
+        // Parse properties
+        let mut changes = Object::with_capacity(changes_.properties.len());
+        for (property, value) in changes_.properties {
+            let value = match ctx.response.eval_object_references(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    return Ok(Err(err));
+                }
+            };
+            let value = match (&property, value) {
+                (Property::Name, MaybePatchValue::Value(Value::Text(value))) => {
+                    let value = value.trim();
+                    if !value.is_empty() && value.len() < self.core.jmap.address_book_name_max_len {
+                        Value::Text(value.to_string())
+                    } else {
+                        return Ok(Err(SetError::invalid_properties()
+                            .with_property(Property::Name)
+                            .with_description(
+                                if !value.is_empty() {
+                                    "Address book name is too long."
+                                } else {
+                                    "Address book name cannot be empty."
+                                }
+                                .to_string(),
+                            )));
+                    }
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Text(value))) => {
+                    if value.len() < self.core.jmap.address_book_name_max_len {
+                        Value::Text(value)
+                    } else {
+                        return Ok(Err(SetError::invalid_properties()
+                            .with_property(Property::Description)
+                            .with_description("Address book description is too long.")));
+                    }
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Null)) => Value::Null,
+                (Property::SortOrder, MaybePatchValue::Value(Value::UnsignedInt(value))) => {
+                    Value::UnsignedInt(value)
+                }
+                (Property::IsSubscribed, MaybePatchValue::Value(Value::Bool(subscribe))) => {
+                    if let Some(current) = update.as_ref() {
+                        if let Some(value) = current
+                            .inner
+                            .mailbox_subscribe(ctx.access_token.primary_id(), subscribe)
+                        {
+                            value
+                        } else {
+                            continue;
+                        }
+                    } else if subscribe {
+                        Value::List(vec![Value::Id(ctx.access_token.primary_id().into())])
+                    } else {
+                        continue;
+                    }
+                }
+                (Property::Acl, value) => {
+                    match self.acl_set(&mut changes, update.as_ref(), value).await {
+                        Ok(_) => continue,
+                        Err(err) => {
+                            return Ok(Err(err));
+                        }
+                    }
+                }
+
+                _ => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())))
+                }
+            };
+
+            changes.append(property, value);
+        }
+
+        // Refresh ACLs
+        if changes.properties.contains_key(&Property::Acl) {
+            self.refresh_acls(&changes, &update);
+        }
+
+        // Validate
+        Ok(ObjectIndexBuilder::new(SCHEMA)
+            .with_changes(changes)
+            .with_current_opt(update)
+            .validate())
+    }
+
+    pub async fn address_book_get_or_create(
+        &self,
+        account_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut address_book_ids = self
+            .get_document_ids(account_id, Collection::AddressBook)
+            .await?
+            .unwrap_or_default();
+        if !address_book_ids.is_empty() {
+            return Ok(address_book_ids);
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::AddressBook)
+            .create_document_with_id(DEFAULT_ADDRESS_BOOK_ID)
+            .custom(
+                ObjectIndexBuilder::new(SCHEMA).with_changes(
+                    Object::with_capacity(3)
+                        .with_property(
+                            Property::Name,
+                            self.core.jmap.address_book_default_name.clone(),
+                        )
+                        .with_property(Property::IsDefault, Value::Bool(true))
+                        .with_property(
+                            Property::IsSubscribed,
+                            Value::List(vec![Value::Id(account_id.into())]),
+                        ),
+                ),
+            );
+        address_book_ids.insert(DEFAULT_ADDRESS_BOOK_ID);
+
+        self.core
+            .storage
+            .data
+            .write(batch.build())
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "address_book_get_or_create",
+                    error = ?err,
+                    "Failed to create default address book.");
+                MethodError::ServerPartialFail
+            })?;
+
+        Ok(address_book_ids)
+    }
+}
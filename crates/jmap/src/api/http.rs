@@ -29,8 +29,9 @@ use jmap_proto::{
 };
 
 use crate::{
-    auth::oauth::OAuthMetadata,
+    auth::oauth::{oidc::OpenIdMetadata, OAuthMetadata},
     blob::{DownloadResponse, UploadResponse},
+    dav::response::DavResponse,
     services::state,
     JmapInstance, JMAP,
 };
@@ -224,6 +225,16 @@ impl JMAP {
                         Err(err) => err.into_http_response(),
                     };
                 }
+                ("openid-configuration", &Method::GET) if self.core.jmap.oidc.is_enabled() => {
+                    // Limit anonymous requests
+                    return match self.is_anonymous_allowed(&session.remote_ip).await {
+                        Ok(_) => JsonResponse::new(OpenIdMetadata::new(
+                            session.resolve_url(&self.core).await,
+                        ))
+                        .into_http_response(),
+                        Err(err) => err.into_http_response(),
+                    };
+                }
                 ("acme-challenge", &Method::GET) if self.core.has_acme_http_providers() => {
                     if let Some(token) = path.next() {
                         return match self
@@ -264,6 +275,11 @@ impl JMAP {
                         return self.handle_autoconfig_request(&req).await;
                     }
                 }
+                ("carddav" | "caldav", _) => {
+                    return DavResponse::new(StatusCode::MOVED_PERMANENTLY)
+                        .with_header("Location", "/dav/")
+                        .into_http_response();
+                }
                 (_, &Method::OPTIONS) => {
                     return StatusCode::NO_CONTENT.into_http_response();
                 }
@@ -281,15 +297,46 @@ impl JMAP {
                 }
                 ("token", &Method::POST) => {
                     return match self.is_anonymous_allowed(&session.remote_ip).await {
-                        Ok(_) => self.handle_token_request(&mut req).await,
+                        Ok(_) => {
+                            self.handle_token_request(
+                                &mut req,
+                                session.resolve_url(&self.core).await,
+                            )
+                            .await
+                        }
                         Err(err) => err.into_http_response(),
                     }
                 }
+                ("userinfo", &Method::GET | &Method::POST) => {
+                    // Authenticate request
+                    return match self.authenticate_headers(&req, session.remote_ip).await {
+                        Ok(Some((_, access_token))) => {
+                            let bearer_token = req
+                                .headers()
+                                .get(header::AUTHORIZATION)
+                                .and_then(|h| h.to_str().ok())
+                                .and_then(|h| h.split_once(' '))
+                                .filter(|(mechanism, _)| mechanism.eq_ignore_ascii_case("bearer"))
+                                .map(|(_, token)| token.trim());
+                            self.handle_userinfo_request(&access_token, bearer_token)
+                                .await
+                        }
+                        Ok(None) => RequestError::unauthorized().into_http_response(),
+                        Err(err) => err.into_http_response(),
+                    };
+                }
                 (_, &Method::OPTIONS) => {
                     return StatusCode::NO_CONTENT.into_http_response();
                 }
                 _ => (),
             },
+            "jwks.json" if req.method() == Method::GET => {
+                // Limit anonymous requests
+                return match self.is_anonymous_allowed(&session.remote_ip).await {
+                    Ok(_) => self.handle_jwks_request(),
+                    Err(err) => err.into_http_response(),
+                };
+            }
             "api" => {
                 // Allow CORS preflight requests
                 if req.method() == Method::OPTIONS {
@@ -300,13 +347,16 @@ impl JMAP {
                 return match self.authenticate_headers(&req, session.remote_ip).await {
                     Ok(Some((_, access_token))) => {
                         let body = fetch_body(&mut req, 1024 * 1024).await;
-                        self.handle_api_manage_request(&req, body, access_token)
+                        self.handle_api_manage_request(&req, body, access_token, session.remote_ip)
                             .await
                     }
                     Ok(None) => RequestError::unauthorized().into_http_response(),
                     Err(err) => err.into_http_response(),
                 };
             }
+            "dav" => {
+                return self.handle_dav_request(req, &session).await;
+            }
             "mail" => {
                 if req.method() == Method::GET
                     && path.next().unwrap_or_default() == "config-v1.1.xml"
@@ -323,6 +373,9 @@ impl JMAP {
                         .await;
                 }
             }
+            "metrics" if req.method() == Method::GET => {
+                return self.handle_metrics_request(&req, session.remote_ip).await;
+            }
             "robots.txt" => {
                 return Resource {
                     content_type: "text/plain",
@@ -0,0 +1,402 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
+
+use directory::{
+    backend::internal::{lookup::DirectoryStore, manage::ManageDirectory},
+    QueryBy,
+};
+use http_body_util::{BodyExt, Full};
+use hyper::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use store::{
+    write::{now, AuditClass, BatchBuilder, ValueClass},
+    IterateParams, ValueKey,
+};
+use utils::url_params::UrlParams;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    auth::AccessToken,
+    JMAP,
+};
+
+use super::{decode_path_element, principal::PrincipalResponse, settings::UpdateSettings};
+
+const REDACTED: &str = "********";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub actor: String,
+    #[serde(rename = "actorId")]
+    pub actor_id: u32,
+    #[serde(rename = "remoteIp")]
+    pub remote_ip: IpAddr,
+    pub action: String,
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl JMAP {
+    pub async fn handle_manage_audit(&self, req: &HttpRequest) -> HttpResponse {
+        let params = UrlParams::new(req.uri().query());
+        let actor = params.get("actor");
+        let filter = params.get("text");
+        let page: usize = params.parse("page").unwrap_or_default();
+        let limit: usize = params.parse("limit").unwrap_or_default();
+
+        let mut items = Vec::new();
+        let mut offset = page.saturating_sub(1) * limit;
+        let mut total = 0;
+        let result = self
+            .core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Audit(AuditClass { id: 0, expires: 0 })),
+                    ValueKey::from(ValueClass::Audit(AuditClass {
+                        id: u64::MAX,
+                        expires: u64::MAX,
+                    })),
+                )
+                .descending(),
+                |_, value| {
+                    let entry = serde_json::from_slice::<AuditEntry>(value).map_err(|err| {
+                        store::Error::InternalError(format!("Failed to parse audit entry: {err}"))
+                    })?;
+                    let matches = actor.is_none_or(|actor| entry.actor.eq_ignore_ascii_case(actor))
+                        && filter.is_none_or(|filter| {
+                            std::str::from_utf8(value).is_ok_and(|value| value.contains(filter))
+                        });
+                    if matches {
+                        if offset == 0 {
+                            if limit == 0 || items.len() < limit {
+                                items.push(entry);
+                            }
+                        } else {
+                            offset -= 1;
+                        }
+                        total += 1;
+                    }
+
+                    Ok(true)
+                },
+            )
+            .await;
+
+        match result {
+            Ok(_) => JsonResponse::new(json!({
+                    "data": {
+                        "items": items,
+                        "total": total,
+                    },
+            }))
+            .into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
+
+    pub(super) async fn handle_audited_request(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+        access_token: Arc<AccessToken>,
+        remote_ip: IpAddr,
+    ) -> HttpResponse {
+        let mut entry = AuditEntry {
+            timestamp: now(),
+            actor: access_token.name.clone(),
+            actor_id: access_token.primary_id,
+            remote_ip,
+            action: req.method().to_string(),
+            target: req
+                .uri()
+                .path_and_query()
+                .map(|p| p.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            request: body
+                .as_deref()
+                .filter(|body| !body.is_empty())
+                .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+                .map(|mut request| {
+                    redact(&mut request);
+                    request
+                }),
+            before: None,
+            after: None,
+            outcome: AuditOutcome::Success,
+            error: None,
+        };
+
+        // Execute request, keeping snapshots of the target before and after
+        let before = self.audit_snapshot(&path, body.as_deref()).await;
+        let response = self
+            .handle_manage_request(req, path.clone(), body.clone(), access_token)
+            .await;
+        let (parts, response_body) = response.into_parts();
+        let response_body = response_body
+            .collect()
+            .await
+            .map(|body| body.to_bytes())
+            .unwrap_or_default();
+
+        // Requests that fail return either an error status or an error object
+        let response_json = serde_json::from_slice::<Value>(&response_body).ok();
+        if !parts.status.is_success() {
+            entry.outcome = AuditOutcome::Failure;
+            entry.error = response_json
+                .as_ref()
+                .and_then(|v| v.get("detail").or_else(|| v.get("title")))
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .or_else(|| Some(parts.status.to_string()));
+        } else if let Some(error) = response_json
+            .as_ref()
+            .and_then(|v| v.get("error"))
+            .and_then(|v| v.as_str())
+        {
+            entry.outcome = AuditOutcome::Failure;
+            entry.error = Some(error.to_string());
+        }
+        if entry.outcome == AuditOutcome::Success {
+            let after = self.audit_snapshot(&path, body.as_deref()).await;
+            (entry.before, entry.after) = diff(before, after);
+        } else {
+            entry.before = before;
+        }
+
+        // Write entry
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::Audit(AuditClass {
+                id: self.inner.snowflake_id.generate().unwrap_or_default(),
+                expires: entry.timestamp + self.core.jmap.audit_retention.as_secs(),
+            }),
+            serde_json::to_vec(&entry).unwrap_or_default(),
+        );
+        if let Err(err) = self.core.storage.data.write(batch.build()).await {
+            tracing::error!(
+                context = "audit",
+                event = "error",
+                reason = %err,
+                "Failed to write audit log entry."
+            );
+        }
+
+        hyper::Response::from_parts(
+            parts,
+            Full::new(response_body)
+                .map_err(|never| match never {})
+                .boxed(),
+        )
+    }
+
+    // Obtains the current state of the object targeted by a request
+    async fn audit_snapshot(&self, path: &[&str], body: Option<&[u8]>) -> Option<Value> {
+        let body_field = |field: &str| {
+            body.and_then(|body| serde_json::from_slice::<Value>(body).ok())
+                .and_then(|body| body.get(field)?.as_str().map(|v| v.to_string()))
+        };
+
+        match path {
+            ["principal", name, ..] => self.principal_snapshot(&decode_path_element(name)).await,
+            ["principal"] => self.principal_snapshot(&body_field("name")?).await,
+            ["domain", name] => {
+                let name = decode_path_element(name);
+                self.core
+                    .storage
+                    .data
+                    .is_local_domain(name.as_ref())
+                    .await
+                    .ok()?
+                    .then(|| json!({ "name": name }))
+            }
+            ["settings", key] => {
+                self.settings_snapshot(vec![(decode_path_element(key).into_owned(), false)])
+                    .await
+            }
+            ["settings"] => {
+                let mut keys = Vec::new();
+                for change in serde_json::from_slice::<Vec<UpdateSettings>>(body?).ok()? {
+                    match change {
+                        UpdateSettings::Delete { keys: delete_keys } => {
+                            keys.extend(delete_keys.into_iter().map(|key| (key, false)));
+                        }
+                        UpdateSettings::Clear { prefix } => {
+                            keys.push((prefix, true));
+                        }
+                        UpdateSettings::Insert {
+                            prefix: Some(prefix),
+                            ..
+                        } => {
+                            keys.push((format!("{prefix}."), true));
+                        }
+                        UpdateSettings::Insert {
+                            prefix: None,
+                            values,
+                            ..
+                        } => {
+                            keys.extend(values.into_iter().map(|(key, _)| (key, false)));
+                        }
+                    }
+                }
+                self.settings_snapshot(keys).await
+            }
+            ["oauth", "client", client_id] => self
+                .get_oauth_client(&decode_path_element(client_id))
+                .await
+                .ok()?
+                .and_then(|client| serde_json::to_value(client).ok()),
+            ["oauth", "client"] => self
+                .get_oauth_client(&body_field("clientId")?)
+                .await
+                .ok()?
+                .and_then(|client| serde_json::to_value(client).ok()),
+            _ => None,
+        }
+    }
+
+    async fn principal_snapshot(&self, name: &str) -> Option<Value> {
+        let principal = self
+            .core
+            .storage
+            .data
+            .query(QueryBy::Name(name), true)
+            .await
+            .ok()??;
+        let principal = self.core.storage.data.map_group_ids(principal).await.ok()?;
+        let mut principal = serde_json::to_value(PrincipalResponse::from(principal)).ok()?;
+        if let Some(principal) = principal.as_object_mut() {
+            principal.remove("usedQuota");
+            principal.remove("members");
+        }
+        redact(&mut principal);
+        Some(principal)
+    }
+
+    async fn settings_snapshot(&self, keys: Vec<(String, bool)>) -> Option<Value> {
+        let mut values = BTreeMap::new();
+        for (key, is_prefix) in keys {
+            if is_prefix {
+                values.extend(self.core.storage.config.list(&key, false).await.ok()?);
+            } else if let Some(value) = self.core.storage.config.get(&key).await.ok()? {
+                values.insert(key, value);
+            }
+        }
+
+        let mut snapshot = Map::with_capacity(values.len());
+        for (key, value) in values {
+            let value = if is_sensitive(&key) {
+                REDACTED.to_string()
+            } else {
+                value
+            };
+            snapshot.insert(key, Value::String(value));
+        }
+        Some(Value::Object(snapshot))
+    }
+}
+
+pub(super) fn is_auditable(method: &Method, path: &[&str]) -> bool {
+    match path.first().copied().unwrap_or_default() {
+        // Requests that modify the server state using the GET method
+        "reload" | "update" | "restart" => true,
+        "store" => path.get(1) == Some(&"purge"),
+        // Authorization codes are issued using POST
+        "oauth" => method != Method::GET && path.get(1) == Some(&"client"),
+        "audit" => false,
+        _ => method != Method::GET,
+    }
+}
+
+// Removes the properties that are identical before and after the change
+pub fn diff(before: Option<Value>, after: Option<Value>) -> (Option<Value>, Option<Value>) {
+    match (before, after) {
+        (Some(Value::Object(before)), Some(Value::Object(after))) => {
+            let changed = |a: &Map<String, Value>, b: &Map<String, Value>| {
+                a.iter()
+                    .filter(|(key, value)| b.get(*key) != Some(value))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect::<Map<_, _>>()
+            };
+            (
+                Some(Value::Object(changed(&before, &after))),
+                Some(Value::Object(changed(&after, &before))),
+            )
+        }
+        (before, after) => (before, after),
+    }
+}
+
+// Removes passwords, secrets and private keys
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            // Principal updates include secrets as values
+            let is_secret_update = object
+                .get("field")
+                .and_then(|v| v.as_str())
+                .is_some_and(is_sensitive);
+            for (key, value) in object.iter_mut() {
+                if is_sensitive(key) || (is_secret_update && key == "value") {
+                    redact_all(value);
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => {
+            // Settings are represented as key-value pairs
+            if let [Value::String(key), value] = items.as_mut_slice() {
+                if is_sensitive(key) {
+                    redact_all(value);
+                    return;
+                }
+            }
+            items.iter_mut().for_each(redact);
+        }
+        Value::String(text) if text.starts_with("otpauth://") => {
+            *text = REDACTED.to_string();
+        }
+        _ => (),
+    }
+}
+
+fn redact_all(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(redact_all),
+        Value::Null => (),
+        _ => *value = Value::String(REDACTED.to_string()),
+    }
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["secret", "password", "private-key", "privatekey"]
+        .iter()
+        .any(|word| key.contains(word))
+}
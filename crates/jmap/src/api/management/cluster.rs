@@ -0,0 +1,22 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use serde_json::json;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+impl JMAP {
+    pub fn handle_manage_cluster(&self) -> HttpResponse {
+        // Cluster status is null when clustering is disabled
+        JsonResponse::new(json!({
+            "data": self.inner.cluster_status.read().clone(),
+        }))
+        .into_http_response()
+    }
+}
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use directory::backend::internal::manage::ManageDirectory;
+use directory::{backend::internal::manage::ManageDirectory, core::role::Permission};
 
 use hyper::Method;
 use jmap_proto::error::request::RequestError;
@@ -21,6 +21,7 @@ use crate::{
         management::dkim::{obtain_dkim_public_key, Algorithm},
         HttpRequest, HttpResponse, JsonResponse,
     },
+    auth::AccessToken,
     JMAP,
 };
 
@@ -35,7 +36,12 @@ struct DnsRecord {
 }
 
 impl JMAP {
-    pub async fn handle_manage_domain(&self, req: &HttpRequest, path: Vec<&str>) -> HttpResponse {
+    pub async fn handle_manage_domain(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        access_token: &AccessToken,
+    ) -> HttpResponse {
         match (path.get(1), req.method()) {
             (None, &Method::GET) => {
                 // List domains
@@ -45,7 +51,14 @@ impl JMAP {
                 let limit: usize = params.parse("limit").unwrap_or(0);
 
                 match self.core.storage.data.list_domains(filter).await {
-                    Ok(domains) => {
+                    Ok(mut domains) => {
+                        // Only list the domains the principal is allowed to view
+                        if !access_token.has_permission(Permission::DomainView) {
+                            domains.retain(|domain| {
+                                access_token.has_domain_permission(Permission::DomainView, domain)
+                            });
+                        }
+
                         let (total, domains) = if limit > 0 {
                             let offset = page.saturating_sub(1) * limit;
                             (
@@ -70,6 +83,9 @@ impl JMAP {
             (Some(domain), &Method::GET) => {
                 // Obtain DNS records
                 let domain = decode_path_element(domain);
+                if !access_token.has_domain_permission(Permission::DomainView, domain.as_ref()) {
+                    return RequestError::forbidden().into_http_response();
+                }
                 match self.build_dns_records(domain.as_ref()).await {
                     Ok(records) => JsonResponse::new(json!({
                         "data": records,
@@ -100,6 +100,7 @@ impl JMAP {
                                         )
                                         .to_rfc3339(),
                                         collection: Collection::from(blob.collection).to_string(),
+                                        metadata: Vec::new(),
                                     });
                                     if results.len() == limit {
                                         break;
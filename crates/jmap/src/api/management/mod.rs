@@ -4,6 +4,8 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+pub mod audit;
+pub mod cluster;
 pub mod dkim;
 pub mod domain;
 pub mod enterprise;
@@ -16,12 +18,15 @@ pub mod settings;
 pub mod sieve;
 pub mod stores;
 
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, net::IpAddr, sync::Arc};
 
+use directory::core::role::Permission;
 use hyper::Method;
 use jmap_proto::error::request::RequestError;
 use serde::Serialize;
 
+use audit::is_auditable;
+
 use super::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse};
 use crate::{auth::AccessToken, JMAP};
 use se_common::EnterpriseCore;
@@ -57,23 +62,70 @@ impl JMAP {
         req: &HttpRequest,
         body: Option<Vec<u8>>,
         access_token: Arc<AccessToken>,
+        remote_ip: IpAddr,
     ) -> HttpResponse {
         let path = req.uri().path().split('/').skip(2).collect::<Vec<_>>();
+
+        if self.core.jmap.audit_enable && is_auditable(req.method(), &path) {
+            self.handle_audited_request(req, path, body, access_token, remote_ip)
+                .await
+        } else {
+            self.handle_manage_request(req, path, body, access_token)
+                .await
+        }
+    }
+
+    async fn handle_manage_request(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+        access_token: Arc<AccessToken>,
+    ) -> HttpResponse {
         let is_superuser = access_token.is_super_user();
+        let is_get = req.method() == Method::GET;
 
         match path.first().copied().unwrap_or_default() {
-            "queue" if is_superuser => self.handle_manage_queue(req, path).await,
-            "settings" if is_superuser => self.handle_manage_settings(req, path, body).await,
-            "reports" if is_superuser => self.handle_manage_reports(req, path).await,
-            "principal" if is_superuser => self.handle_manage_principal(req, path, body).await,
-            "domain" if is_superuser => self.handle_manage_domain(req, path).await,
+            "queue"
+                if is_superuser
+                    || (is_get && access_token.has_permission(Permission::QueueView)) =>
+            {
+                self.handle_manage_queue(req, path).await
+            }
+            "settings"
+                if is_superuser
+                    || (is_get && access_token.has_permission(Permission::SettingsView)) =>
+            {
+                self.handle_manage_settings(req, path, body, &access_token)
+                    .await
+            }
+            "reports"
+                if is_superuser
+                    || (is_get && access_token.has_permission(Permission::ReportView)) =>
+            {
+                self.handle_manage_reports(req, path).await
+            }
+            "principal" if access_token.has_any_permission(Permission::PrincipalView) => {
+                self.handle_manage_principal(req, path, body, &access_token)
+                    .await
+            }
+            "domain"
+                if is_superuser
+                    || (is_get && access_token.has_any_permission(Permission::DomainView)) =>
+            {
+                self.handle_manage_domain(req, path, &access_token).await
+            }
             "store" if is_superuser => self.handle_manage_store(req, path).await,
             "reload" if is_superuser => self.handle_manage_reload(req, path).await,
+            "cluster" if is_superuser && is_get => self.handle_manage_cluster(),
             "dkim" if is_superuser => self.handle_manage_dkim(req, path, body).await,
             "update" if is_superuser => self.handle_manage_update(req, path).await,
-            "logs" if is_superuser && req.method() == Method::GET => {
+            "logs" if is_get && access_token.has_permission(Permission::LogsView) => {
                 self.handle_view_logs(req).await
             }
+            "audit" if is_get && access_token.has_permission(Permission::AuditView) => {
+                self.handle_manage_audit(req).await
+            }
             "sieve" if is_superuser => self.handle_run_sieve(req, path, body).await,
             "restart" if is_superuser && req.method() == Method::GET => {
                 ManagementApiError::Unsupported {
@@ -81,6 +133,9 @@ impl JMAP {
                 }
                 .into_http_response()
             }
+            "oauth" if is_superuser && path.get(1) == Some(&"client") => {
+                self.handle_manage_oauth_client(req, path, body).await
+            }
             "oauth" => self.handle_oauth_api_request(access_token, body).await,
             "account" => match (path.get(1).copied().unwrap_or_default(), req.method()) {
                 ("crypto", &Method::POST) => self.handle_crypto_post(access_token, body).await,
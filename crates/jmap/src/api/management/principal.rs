@@ -6,11 +6,13 @@
 
 use std::sync::Arc;
 
+use common::cluster::ClusterEvent;
 use directory::{
     backend::internal::{
         lookup::DirectoryStore, manage::ManageDirectory, PrincipalAction, PrincipalField,
         PrincipalUpdate, PrincipalValue, SpecialSecrets,
     },
+    core::role::Permission,
     DirectoryError, DirectoryInner, ManagementError, Principal, QueryBy, Type,
 };
 
@@ -51,6 +53,8 @@ pub struct PrincipalResponse {
     pub members: Vec<String>,
     #[serde(default)]
     pub description: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -80,6 +84,7 @@ impl JMAP {
         req: &HttpRequest,
         path: Vec<&str>,
         body: Option<Vec<u8>>,
+        access_token: &AccessToken,
     ) -> HttpResponse {
         match (path.get(1), req.method()) {
             (None, &Method::POST) => {
@@ -93,29 +98,50 @@ impl JMAP {
                     body.as_deref().unwrap_or_default(),
                 ) {
                     Ok(principal) => {
+                        let members = principal.members;
+                        let principal = Principal {
+                            id: principal.id,
+                            typ: principal.typ,
+                            quota: principal.quota,
+                            name: principal.name,
+                            secrets: principal.secrets,
+                            emails: principal.emails,
+                            member_of: principal.member_of,
+                            description: principal.description,
+                            roles: principal.roles,
+                        };
+
+                        // Validate permissions
+                        if !access_token
+                            .has_principal_permission(Permission::PrincipalManage, &principal)
+                            || !self
+                                .can_manage_principals(
+                                    access_token,
+                                    principal.member_of.iter().chain(members.iter()),
+                                )
+                                .await
+                        {
+                            return RequestError::forbidden().into_http_response();
+                        }
+
                         match self
                             .core
                             .storage
                             .data
-                            .create_account(
-                                Principal {
-                                    id: principal.id,
-                                    typ: principal.typ,
-                                    quota: principal.quota,
-                                    name: principal.name,
-                                    secrets: principal.secrets,
-                                    emails: principal.emails,
-                                    member_of: principal.member_of,
-                                    description: principal.description,
-                                },
-                                principal.members,
-                            )
+                            .create_account(principal, members)
                             .await
                         {
-                            Ok(account_id) => JsonResponse::new(json!({
-                                "data": account_id,
-                            }))
-                            .into_http_response(),
+                            Ok(account_id) => {
+                                // Invalidate cached lookups on cluster peers
+                                self.smtp.inner.ipc.broadcast_cluster_event(
+                                    ClusterEvent::InvalidatePrincipal(account_id),
+                                );
+
+                                JsonResponse::new(json!({
+                                    "data": account_id,
+                                }))
+                                .into_http_response()
+                            }
                             Err(err) => err.into_http_response(),
                         }
                     }
@@ -131,7 +157,33 @@ impl JMAP {
                 let limit: usize = params.parse("limit").unwrap_or(0);
 
                 match self.core.storage.data.list_accounts(filter, typ).await {
-                    Ok(accounts) => {
+                    Ok(mut accounts) => {
+                        // Only list the principals under the allowed domains
+                        if !access_token.has_permission(Permission::PrincipalView) {
+                            let mut permitted = Vec::with_capacity(accounts.len());
+                            for account in accounts {
+                                match self
+                                    .core
+                                    .storage
+                                    .data
+                                    .query(QueryBy::Name(&account), false)
+                                    .await
+                                {
+                                    Ok(Some(principal))
+                                        if access_token.has_principal_permission(
+                                            Permission::PrincipalView,
+                                            &principal,
+                                        ) =>
+                                    {
+                                        permitted.push(account);
+                                    }
+                                    Ok(_) => {}
+                                    Err(err) => return err.into_http_response(),
+                                }
+                            }
+                            accounts = permitted;
+                        }
+
                         let (total, accounts) = if limit > 0 {
                             let offset = page.saturating_sub(1) * limit;
                             (
@@ -171,6 +223,47 @@ impl JMAP {
                     }
                 };
 
+                // Validate permissions
+                if !access_token.is_super_user() {
+                    let permission = match *method {
+                        Method::GET => Permission::PrincipalView,
+                        Method::PATCH => {
+                            match serde_json::from_slice::<Vec<PrincipalUpdate>>(
+                                body.as_deref().unwrap_or_default(),
+                            ) {
+                                Ok(changes) => {
+                                    if !self.can_update_principal(access_token, &changes).await {
+                                        return RequestError::forbidden().into_http_response();
+                                    }
+                                    if changes
+                                        .iter()
+                                        .all(|change| change.field == PrincipalField::Secrets)
+                                    {
+                                        Permission::PasswordReset
+                                    } else {
+                                        Permission::PrincipalManage
+                                    }
+                                }
+                                Err(err) => return err.into_http_response(),
+                            }
+                        }
+                        _ => Permission::PrincipalManage,
+                    };
+
+                    match self
+                        .core
+                        .storage
+                        .data
+                        .query(QueryBy::Id(account_id), false)
+                        .await
+                    {
+                        Ok(Some(principal))
+                            if access_token.has_principal_permission(permission, &principal) => {}
+                        Ok(_) => return RequestError::forbidden().into_http_response(),
+                        Err(err) => return err.into_http_response(),
+                    }
+                }
+
                 match *method {
                     Method::GET => {
                         let result = match self
@@ -196,8 +289,14 @@ impl JMAP {
 
                         match result {
                             Ok(principal) => {
-                                // Obtain quota usage
                                 let mut principal = PrincipalResponse::from(principal);
+
+                                // Only superusers may read the stored secrets
+                                if !access_token.is_super_user() {
+                                    principal.secrets.clear();
+                                }
+
+                                // Obtain quota usage
                                 principal.used_quota =
                                     self.get_used_quota(account_id).await.unwrap_or_default()
                                         as u64;
@@ -247,6 +346,9 @@ impl JMAP {
                             Ok(_) => {
                                 // Remove entries from cache
                                 self.inner.sessions.retain(|_, id| id.item != account_id);
+                                self.smtp.inner.ipc.broadcast_cluster_event(
+                                    ClusterEvent::InvalidatePrincipal(account_id),
+                                );
 
                                 JsonResponse::new(json!({
                                     "data": (),
@@ -290,6 +392,9 @@ impl JMAP {
                                                 .sessions
                                                 .retain(|_, id| id.item != account_id);
                                         }
+                                        self.smtp.inner.ipc.broadcast_cluster_event(
+                                            ClusterEvent::InvalidatePrincipal(account_id),
+                                        );
 
                                         JsonResponse::new(json!({
                                             "data": (),
@@ -471,6 +576,12 @@ impl JMAP {
                 self.inner
                     .sessions
                     .retain(|_, id| id.item != access_token.primary_id());
+                self.smtp
+                    .inner
+                    .ipc
+                    .broadcast_cluster_event(ClusterEvent::InvalidatePrincipal(
+                        access_token.primary_id(),
+                    ));
 
                 JsonResponse::new(json!({
                     "data": (),
@@ -481,10 +592,82 @@ impl JMAP {
         }
     }
 
+    // Returns true if the changes only reference principals and
+    // addresses the access token is allowed to manage
+    async fn can_update_principal(
+        &self,
+        access_token: &AccessToken,
+        changes: &[PrincipalUpdate],
+    ) -> bool {
+        let mut names = Vec::new();
+        for change in changes {
+            let values = match &change.value {
+                PrincipalValue::String(value) => std::slice::from_ref(value),
+                PrincipalValue::StringList(values) => values.as_slice(),
+                PrincipalValue::Integer(_) => &[],
+            };
+
+            match change.field {
+                // Only superusers can grant administrative privileges
+                PrincipalField::Type | PrincipalField::Roles => return false,
+                // New names and addresses have to be under the allowed domains
+                PrincipalField::Name | PrincipalField::Emails
+                    if change.action != PrincipalAction::RemoveItem
+                        && !values.iter().all(|value| {
+                            value.rsplit_once('@').map_or(
+                                change.field == PrincipalField::Name,
+                                |(_, domain)| {
+                                    access_token
+                                        .has_domain_permission(Permission::PrincipalManage, domain)
+                                },
+                            )
+                        }) =>
+                {
+                    return false;
+                }
+                PrincipalField::MemberOf | PrincipalField::Members => {
+                    names.extend(values.iter());
+                }
+                _ => {}
+            }
+        }
+
+        self.can_manage_principals(access_token, names.into_iter())
+            .await
+    }
+
+    async fn can_manage_principals<'x>(
+        &self,
+        access_token: &AccessToken,
+        names: impl Iterator<Item = &'x String>,
+    ) -> bool {
+        if access_token.has_permission(Permission::PrincipalManage) {
+            return true;
+        }
+
+        for name in names {
+            match self
+                .core
+                .storage
+                .data
+                .query(QueryBy::Name(name), false)
+                .await
+            {
+                Ok(Some(principal))
+                    if access_token
+                        .has_principal_permission(Permission::PrincipalManage, &principal) => {}
+                Ok(None) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
     pub fn assert_supported_directory(&self) -> Option<HttpResponse> {
         ManagementApiError::UnsupportedDirectoryOperation {
             class: match &self.core.storage.directory.store {
-                DirectoryInner::Internal(_) => return None,
+                DirectoryInner::Internal(_) | DirectoryInner::Oidc(_) => return None,
                 DirectoryInner::Ldap(_) => "LDAP",
                 DirectoryInner::Sql(_) => "SQL",
                 DirectoryInner::Imap(_) => "IMAP",
@@ -511,6 +694,7 @@ impl From<Principal<String>> for PrincipalResponse {
             secrets: principal.secrets,
             used_quota: 0,
             members: Vec::new(),
+            roles: principal.roles,
         }
     }
 }
@@ -17,10 +17,13 @@ use mail_auth::{
 use mail_parser::DateTime;
 use serde::{Deserializer, Serializer};
 use serde_json::json;
-use smtp::queue::{self, ErrorDetails, HostResponse, QueueId, Status};
+use smtp::{
+    core::transcript::TranscriptRecord,
+    queue::{self, history::DeliveryRecord, ErrorDetails, HostResponse, QueueId, Status},
+};
 use store::{
     write::{key::DeserializeBigEndian, now, Bincode, QueueClass, ReportEvent, ValueClass},
-    Deserialize, IterateParams, ValueKey,
+    Deserialize, IterateParams, ValueKey, U64_LEN,
 };
 use utils::url_params::UrlParams;
 
@@ -489,6 +492,213 @@ impl JMAP {
                     RequestError::not_found().into_http_response()
                 }
             }
+            ("history", None, &Method::GET) => {
+                let text = params.get("text");
+                let from = params.get("from");
+                let to = params.get("to");
+                let queue_id = params.parse::<QueueId>("queue-id");
+                let message_id = params.get("message-id");
+                let status = params.get("status");
+                let page = params.parse::<usize>("page").unwrap_or_default();
+                let limit = params.parse::<usize>("limit").unwrap_or_default();
+
+                let mut items = Vec::new();
+                let mut offset = page.saturating_sub(1) * limit;
+                let mut total = 0;
+                let result = self
+                    .core
+                    .storage
+                    .data
+                    .iterate(
+                        IterateParams::new(
+                            ValueKey::from(ValueClass::Queue(QueueClass::DeliveryHistory {
+                                id: 0,
+                                expires: 0,
+                            })),
+                            ValueKey::from(ValueClass::Queue(QueueClass::DeliveryHistory {
+                                id: u64::MAX,
+                                expires: u64::MAX,
+                            })),
+                        )
+                        .descending(),
+                        |_, value| {
+                            let record =
+                                serde_json::from_slice::<DeliveryRecord>(value).map_err(|err| {
+                                    store::Error::InternalError(format!(
+                                        "Failed to parse delivery record: {err}"
+                                    ))
+                                })?;
+                            let matches = queue_id.map_or(true, |id| record.queue_id == id)
+                                && message_id.map_or(true, |id| {
+                                    record.message_id.as_deref().is_some_and(|message_id| {
+                                        message_id.trim_matches(['<', '>'])
+                                            == id.trim_matches(['<', '>'])
+                                    })
+                                })
+                                && from.map_or(true, |from| record.sender.contains(from))
+                                && to.map_or(true, |to| {
+                                    record.recipients.iter().any(|r| r.address.contains(to))
+                                })
+                                && status.map_or(true, |status| {
+                                    serde_json::to_value(record.status)
+                                        .is_ok_and(|value| value == status)
+                                })
+                                && text.map_or(true, |text| {
+                                    std::str::from_utf8(value)
+                                        .is_ok_and(|value| value.contains(text))
+                                });
+
+                            if matches {
+                                if offset == 0 {
+                                    if limit == 0 || items.len() < limit {
+                                        items.push(record);
+                                    }
+                                } else {
+                                    offset -= 1;
+                                }
+                                total += 1;
+                            }
+
+                            Ok(true)
+                        },
+                    )
+                    .await;
+
+                match result {
+                    Ok(_) => JsonResponse::new(json!({
+                            "data": {
+                                "items": items,
+                                "total": total,
+                            },
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            ("transcripts", None, &Method::GET) => {
+                let text = params.get("text");
+                let direction = params.get("direction");
+                let remote_ip = params.get("remote-ip");
+                let remote_host = params.get("remote-host");
+                let queue_id = params.parse::<QueueId>("queue-id");
+                let page = params.parse::<usize>("page").unwrap_or_default();
+                let limit = params.parse::<usize>("limit").unwrap_or_default();
+                let has_filters = direction.is_some()
+                    || remote_ip.is_some()
+                    || remote_host.is_some()
+                    || queue_id.is_some();
+
+                let matches = |record: TranscriptRecord| {
+                    direction.map_or(true, |direction| {
+                        serde_json::to_value(record.direction).is_ok_and(|value| value == direction)
+                    }) && remote_ip.map_or(true, |ip| record.remote_ip.to_string() == ip)
+                        && remote_host.map_or(true, |host| {
+                            record
+                                .remote_host
+                                .as_deref()
+                                .is_some_and(|remote_host| remote_host.contains(host))
+                        })
+                        && queue_id.map_or(true, |id| record.queue_id == Some(id))
+                };
+
+                // Filter using the summaries stored in the index, newest first
+                let mut ids = Vec::new();
+                let from_key = ValueKey::from(ValueClass::Queue(QueueClass::TranscriptIndex {
+                    id: 0,
+                    expires: 0,
+                }));
+                let to_key = ValueKey::from(ValueClass::Queue(QueueClass::TranscriptIndex {
+                    id: u64::MAX,
+                    expires: u64::MAX,
+                }));
+                let result = self
+                    .core
+                    .storage
+                    .data
+                    .iterate(
+                        IterateParams::new(from_key, to_key)
+                            .descending()
+                            .set_values(has_filters),
+                        |key, value| {
+                            if !has_filters || matches(TranscriptRecord::deserialize(value)?) {
+                                ids.push(key.deserialize_be_u64(key.len() - U64_LEN)?);
+                            }
+
+                            Ok(true)
+                        },
+                    )
+                    .await;
+                if let Err(err) = result {
+                    return err.into_http_response();
+                }
+
+                // Transcripts are only read when searching their lines or filling the page
+                let mut items = Vec::new();
+                let mut total = 0;
+                let offset = page.saturating_sub(1) * limit;
+                for id in ids {
+                    let in_page = total >= offset && (limit == 0 || items.len() < limit);
+                    if text.is_none() && !in_page {
+                        total += 1;
+                        continue;
+                    }
+
+                    let mut record = match self
+                        .core
+                        .storage
+                        .data
+                        .get_value::<TranscriptRecord>(ValueKey::from(ValueClass::Queue(
+                            QueueClass::Transcript(id),
+                        )))
+                        .await
+                    {
+                        Ok(Some(record)) => record,
+                        Ok(None) => continue,
+                        Err(err) => return err.into_http_response(),
+                    };
+
+                    if text.map_or(true, |text| {
+                        record.lines.iter().any(|line| line.text.contains(text))
+                    }) {
+                        if in_page {
+                            // Lines are only returned when fetching a single transcript
+                            record.lines.clear();
+                            items.push(record);
+                        }
+                        total += 1;
+                    }
+                }
+
+                JsonResponse::new(json!({
+                        "data": {
+                            "items": items,
+                            "total": total,
+                        },
+                }))
+                .into_http_response()
+            }
+            ("transcripts", Some(id), &Method::GET) => {
+                let Ok(id) = id.parse::<u64>() else {
+                    return RequestError::not_found().into_http_response();
+                };
+
+                match self
+                    .core
+                    .storage
+                    .data
+                    .get_value::<TranscriptRecord>(ValueKey::from(ValueClass::Queue(
+                        QueueClass::Transcript(id),
+                    )))
+                    .await
+                {
+                    Ok(Some(transcript)) => JsonResponse::new(json!({
+                            "data": transcript,
+                    }))
+                    .into_http_response(),
+                    Ok(None) => RequestError::not_found().into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
             _ => RequestError::not_found().into_http_response(),
         }
     }
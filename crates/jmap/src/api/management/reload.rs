@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use common::cluster::ClusterEvent;
 use hyper::Method;
 use jmap_proto::error::request::RequestError;
 use serde_json::json;
@@ -46,6 +47,10 @@ impl JMAP {
                     Ok(result) => {
                         // Increment version counter
                         self.core.network.blocked_ips.increment_version();
+                        self.smtp
+                            .inner
+                            .ipc
+                            .broadcast_cluster_event(ClusterEvent::ReloadBlockedIps);
 
                         JsonResponse::new(json!({
                             "data": result.config,
@@ -65,6 +70,10 @@ impl JMAP {
 
                                 // Increment version counter
                                 self.inner.increment_config_version();
+                                self.smtp
+                                    .inner
+                                    .ipc
+                                    .broadcast_cluster_event(ClusterEvent::ReloadSettings);
                             }
 
                             // Reload ACME
@@ -12,6 +12,7 @@ use utils::{config::ConfigKey, url_params::UrlParams};
 
 use crate::{
     api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    auth::AccessToken,
     JMAP,
 };
 
@@ -39,6 +40,7 @@ impl JMAP {
         req: &HttpRequest,
         path: Vec<&str>,
         body: Option<Vec<u8>>,
+        access_token: &AccessToken,
     ) -> HttpResponse {
         match (path.get(1).copied(), req.method()) {
             (Some("group"), &Method::GET) => {
@@ -71,7 +73,16 @@ impl JMAP {
                     params.parse::<usize>("page").unwrap_or(0).saturating_sub(1) * limit;
                 let has_filter = !filter.is_empty();
 
-                match self.core.storage.config.list(&prefix, true).await {
+                match self
+                    .core
+                    .storage
+                    .config
+                    .list(&prefix, true)
+                    .await
+                    .map(|mut settings| {
+                        redact_secrets(access_token, settings.iter_mut().map(|(k, v)| (&*k, v)));
+                        settings
+                    }) {
                     Ok(settings) => if !suffix.is_empty() && !settings.is_empty() {
                         // Obtain record ids
                         let mut total = 0;
@@ -186,7 +197,16 @@ impl JMAP {
                 let limit: usize = params.parse("limit").unwrap_or(0);
                 let offset = params.parse::<usize>("page").unwrap_or(0).saturating_sub(1) * limit;
 
-                match self.core.storage.config.list(&prefix, true).await {
+                match self
+                    .core
+                    .storage
+                    .config
+                    .list(&prefix, true)
+                    .await
+                    .map(|mut settings| {
+                        redact_secrets(access_token, settings.iter_mut().map(|(k, v)| (&*k, v)));
+                        settings
+                    }) {
                     Ok(settings) => {
                         let total = settings.len();
                         let items = settings
@@ -249,6 +269,8 @@ impl JMAP {
                     }
                 }
 
+                redact_secrets(access_token, results.iter_mut());
+
                 match err {
                     None => JsonResponse::new(json!({
                         "data": results,
@@ -369,3 +391,21 @@ impl JMAP {
         }
     }
 }
+
+// Secrets stored in the configuration are only disclosed to superusers
+fn redact_secrets<'x>(
+    access_token: &AccessToken,
+    settings: impl Iterator<Item = (&'x String, &'x mut String)>,
+) {
+    if !access_token.is_super_user() {
+        for (key, value) in settings {
+            let name = key.rsplit('.').next().unwrap_or_default();
+            if ["secret", "password", "key"]
+                .iter()
+                .any(|secret| name.contains(secret))
+            {
+                *value = "********".to_string();
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    net::IpAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use common::manager::webadmin::Resource;
+use hyper::header::AUTHORIZATION;
+use jmap_proto::error::request::RequestError;
+use mail_parser::decoders::base64::base64_decode;
+use store::{
+    write::{now, QueueClass, ValueClass},
+    IterateParams, ValueKey,
+};
+use subtle::ConstantTimeEq;
+use utils::metrics::{export_prometheus, FTS_INDEX_BACKLOG, QUEUE_MESSAGES};
+
+use crate::JMAP;
+
+use super::{http::ToHttpResponse, HttpRequest, HttpResponse};
+
+// Minimum number of seconds between store scans for the queue and index gauges
+const GAUGES_UPDATE_INTERVAL: u64 = 30;
+static GAUGES_UPDATED_AT: AtomicU64 = AtomicU64::new(0);
+
+impl JMAP {
+    pub async fn handle_metrics_request(
+        &self,
+        req: &HttpRequest,
+        remote_ip: IpAddr,
+    ) -> HttpResponse {
+        if !self.core.jmap.metrics_prometheus {
+            return RequestError::not_found().into_http_response();
+        }
+
+        // Limit anonymous requests
+        if let Err(err) = self.is_anonymous_allowed(&remote_ip).await {
+            return err.into_http_response();
+        }
+
+        // Validate credentials, if configured
+        if let Some((username, secret)) = &self.core.jmap.metrics_prometheus_auth {
+            let is_authorized = req
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| {
+                    h.strip_prefix("Basic ")
+                        .or_else(|| h.strip_prefix("basic "))
+                })
+                .and_then(|h| base64_decode(h.trim().as_bytes()))
+                .and_then(|h| String::from_utf8(h).ok())
+                .is_some_and(|h| {
+                    h.split_once(':').is_some_and(|(user, pass)| {
+                        bool::from(
+                            user.as_bytes().ct_eq(username.as_bytes())
+                                & pass.as_bytes().ct_eq(secret.as_bytes()),
+                        )
+                    })
+                });
+            if !is_authorized {
+                return RequestError::unauthorized().into_http_response();
+            }
+        }
+
+        // Update gauges that are obtained from the store, at most once per interval
+        let now = now();
+        let last_update = GAUGES_UPDATED_AT.load(Ordering::Relaxed);
+        if last_update + GAUGES_UPDATE_INTERVAL <= now
+            && GAUGES_UPDATED_AT
+                .compare_exchange(last_update, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            match self.queue_size().await {
+                Ok(size) => QUEUE_MESSAGES.set(&[], size),
+                Err(err) => return err.into_http_response(),
+            }
+            match self.fts_index_backlog().await {
+                Ok(backlog) => FTS_INDEX_BACKLOG.set(&[], backlog),
+                Err(err) => return err.into_http_response(),
+            }
+        }
+
+        Resource {
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            contents: export_prometheus().into_bytes(),
+        }
+        .into_http_response()
+    }
+
+    async fn queue_size(&self) -> store::Result<u64> {
+        let mut size = 0;
+        self.core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Queue(QueueClass::Message(0))),
+                    ValueKey::from(ValueClass::Queue(QueueClass::Message(u64::MAX))),
+                )
+                .ascending()
+                .no_values(),
+                |_, _| {
+                    size += 1;
+                    Ok(true)
+                },
+            )
+            .await
+            .map(|_| size)
+    }
+}
@@ -10,7 +10,7 @@ use common::listener::ServerInstance;
 use jmap_proto::{
     error::{method::MethodError, request::RequestError},
     method::{
-        get, query,
+        copy, get, query,
         set::{self},
     },
     request::{method::MethodName, Call, Request, RequestMethod},
@@ -69,6 +69,12 @@ impl JMAP {
                                     self.broadcast_state_change(state_change).await;
                                 }
                             }
+                            ResponseMethod::UndeleteEmail(undelete_response) => {
+                                // Publish state changes
+                                if let Some(state_change) = undelete_response.state_change.take() {
+                                    self.broadcast_state_change(state_change).await;
+                                }
+                            }
                             ResponseMethod::Copy(copy_response) => {
                                 // Publish state changes
                                 if let Some(state_change) = copy_response.state_change.take() {
@@ -177,6 +183,26 @@ impl JMAP {
                         .await?
                         .into()
                 }
+                get::RequestArguments::AddressBook => {
+                    access_token.assert_has_access(req.account_id, Collection::AddressBook)?;
+
+                    self.address_book_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::Calendar => {
+                    access_token.assert_has_access(req.account_id, Collection::Calendar)?;
+
+                    self.calendar_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::CalendarEvent => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_get(req, access_token).await?.into()
+                }
             },
             RequestMethod::Query(mut req) => match req.take_arguments() {
                 query::RequestArguments::Email(arguments) => {
@@ -217,6 +243,18 @@ impl JMAP {
 
                     self.quota_query(req, access_token).await?.into()
                 }
+                query::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_query(req, access_token).await?.into()
+                }
+                query::RequestArguments::CalendarEvent(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_query(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
             },
             RequestMethod::Set(mut req) => match req.take_arguments() {
                 set::RequestArguments::Email => {
@@ -258,15 +296,52 @@ impl JMAP {
 
                     self.vacation_response_set(req).await?.into()
                 }
+                set::RequestArguments::AddressBook(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::AddressBook)?;
+
+                    self.address_book_set(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
+                set::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_set(req, access_token).await?.into()
+                }
+                set::RequestArguments::Calendar(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::Calendar)?;
+
+                    self.calendar_set(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
+                set::RequestArguments::CalendarEvent(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_set(req.with_arguments(arguments), access_token, instance)
+                        .await?
+                        .into()
+                }
             },
             RequestMethod::Changes(req) => self.changes(req, access_token).await?.into(),
-            RequestMethod::Copy(req) => {
-                access_token
-                    .assert_has_access(req.account_id, Collection::Email)?
-                    .assert_has_access(req.from_account_id, Collection::Email)?;
+            RequestMethod::Copy(req) => match req.arguments {
+                copy::RequestArguments::Email => {
+                    access_token
+                        .assert_has_access(req.account_id, Collection::Email)?
+                        .assert_has_access(req.from_account_id, Collection::Email)?;
 
-                self.email_copy(req, access_token, next_call).await?.into()
-            }
+                    self.email_copy(req, access_token, next_call).await?.into()
+                }
+                copy::RequestArguments::ContactCard => {
+                    access_token
+                        .assert_has_access(req.account_id, Collection::ContactCard)?
+                        .assert_has_access(req.from_account_id, Collection::ContactCard)?;
+
+                    self.contact_card_copy(req, access_token, next_call)
+                        .await?
+                        .into()
+                }
+            },
             RequestMethod::ImportEmail(req) => {
                 access_token.assert_has_access(req.account_id, Collection::Email)?;
 
@@ -303,6 +378,16 @@ impl JMAP {
 
                 self.blob_upload_many(req, access_token).await?.into()
             }
+            RequestMethod::ListDeletedEmail(req) => {
+                access_token.assert_is_member(req.account_id)?;
+
+                self.email_list_deleted(req).await?.into()
+            }
+            RequestMethod::UndeleteEmail(req) => {
+                access_token.assert_is_member(req.account_id)?;
+
+                self.email_undelete(req, access_token).await?.into()
+            }
             RequestMethod::Echo(req) => req.into(),
             RequestMethod::Error(error) => return Err(error),
         })
@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use common::cluster::ClusterEvent;
 use directory::QueryBy;
 use jmap_proto::{
     error::{method::MethodError, set::SetError},
@@ -16,7 +17,7 @@ use jmap_proto::{
     },
 };
 use store::{
-    query::acl::AclQuery,
+    query::{acl::AclQuery, Filter},
     roaring::RoaringBitmap,
     write::{assert::HashedValue, ValueClass},
     ValueKey,
@@ -70,6 +71,16 @@ impl JMAP {
                     {
                         collections.insert(Collection::Email);
                     }
+                    if collection == Collection::AddressBook
+                        && (acl.contains(Acl::ReadItems) || acl.contains(Acl::Administer))
+                    {
+                        collections.insert(Collection::ContactCard);
+                    }
+                    if collection == Collection::Calendar
+                        && (acl.contains(Acl::ReadItems) || acl.contains(Acl::Administer))
+                    {
+                        collections.insert(Collection::CalendarEvent);
+                    }
 
                     if !collections.is_empty() {
                         if let Some((_, sharing)) = access_token
@@ -166,6 +177,72 @@ impl JMAP {
         Ok(shared_messages)
     }
 
+    pub async fn shared_contacts(
+        &self,
+        access_token: &AccessToken,
+        to_account_id: u32,
+        check_acls: impl Into<Bitmap<Acl>>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let check_acls = check_acls.into();
+        let shared_address_books = self
+            .shared_documents(
+                access_token,
+                to_account_id,
+                Collection::AddressBook,
+                check_acls,
+            )
+            .await?;
+        if shared_address_books.is_empty() {
+            return Ok(shared_address_books);
+        }
+        let mut shared_contacts = RoaringBitmap::new();
+        for address_book_id in shared_address_books {
+            shared_contacts |= self
+                .filter(
+                    to_account_id,
+                    Collection::ContactCard,
+                    vec![Filter::eq(Property::AddressBookIds, address_book_id)],
+                )
+                .await?
+                .results;
+        }
+
+        Ok(shared_contacts)
+    }
+
+    pub async fn shared_events(
+        &self,
+        access_token: &AccessToken,
+        to_account_id: u32,
+        check_acls: impl Into<Bitmap<Acl>>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let check_acls = check_acls.into();
+        let shared_calendars = self
+            .shared_documents(
+                access_token,
+                to_account_id,
+                Collection::Calendar,
+                check_acls,
+            )
+            .await?;
+        if shared_calendars.is_empty() {
+            return Ok(shared_calendars);
+        }
+        let mut shared_events = RoaringBitmap::new();
+        for calendar_id in shared_calendars {
+            shared_events |= self
+                .filter(
+                    to_account_id,
+                    Collection::CalendarEvent,
+                    vec![Filter::eq(Property::CalendarIds, calendar_id)],
+                )
+                .await?
+                .results;
+        }
+
+        Ok(shared_events)
+    }
+
     pub async fn owned_or_shared_documents(
         &self,
         access_token: &AccessToken,
@@ -367,7 +444,13 @@ impl JMAP {
         current: &Option<HashedValue<Object<Value>>>,
     ) {
         if let Value::Acl(acl_changes) = changes.get(&Property::Acl) {
-            let access_tokens = &self.inner.access_tokens;
+            let invalidate_token = |account_id: u32| {
+                self.inner.access_tokens.remove(&account_id);
+                self.smtp
+                    .inner
+                    .ipc
+                    .broadcast_cluster_event(ClusterEvent::InvalidatePrincipal(account_id));
+            };
             if let Some(Value::Acl(acl_current)) = current
                 .as_ref()
                 .and_then(|current| current.inner.properties.get(&Property::Acl))
@@ -381,7 +464,7 @@ impl JMAP {
                         }
                     }
                     if invalidate {
-                        access_tokens.remove(&current_item.account_id);
+                        invalidate_token(current_item.account_id);
                     }
                 }
 
@@ -394,12 +477,12 @@ impl JMAP {
                         }
                     }
                     if invalidate {
-                        access_tokens.remove(&change_item.account_id);
+                        invalidate_token(change_item.account_id);
                     }
                 }
             } else {
                 for value in acl_changes {
-                    access_tokens.remove(&value.account_id);
+                    invalidate_token(value.account_id);
                 }
             }
         }
@@ -7,9 +7,10 @@
 use std::{net::IpAddr, sync::Arc, time::Instant};
 
 use common::{
-    config::server::ServerProtocol, listener::limiter::InFlight, AuthFailureReason, AuthResult,
+    config::server::ServerProtocol, listener::limiter::InFlight, scram::ScramServer,
+    AuthFailureReason, AuthResult,
 };
-use directory::{Principal, QueryBy};
+use directory::{core::secret::ScramAlgorithm, DirectoryInner, Principal, QueryBy};
 use hyper::header;
 use jmap_proto::error::request::RequestError;
 use mail_parser::decoders::base64::base64_decode;
@@ -77,16 +78,12 @@ impl JMAP {
                     // Enforce anonymous rate limit for bearer auth requests
                     self.is_anonymous_allowed(&remote_ip).await?;
 
-                    match self.validate_access_token("access_token", &token).await {
-                        Ok((account_id, _, _)) => self.get_access_token(account_id).await,
-                        Err(err) => {
-                            tracing::debug!(
-                                context = "authenticate_headers",
-                                err = err,
-                                "Failed to validate access token."
-                            );
-                            None
-                        }
+                    match self
+                        .authenticate_bearer(&token, None, remote_ip, ServerProtocol::Http)
+                        .await
+                    {
+                        AuthResult::Success(access_token) => Some(access_token),
+                        _ => None,
                     }
                 } else {
                     // Enforce anonymous rate limit
@@ -150,16 +147,76 @@ impl JMAP {
         secret: &str,
         remote_ip: IpAddr,
         protocol: ServerProtocol,
+    ) -> AuthResult<AccessToken> {
+        self.authenticate_credentials(
+            &Credentials::Plain {
+                username: username.to_string(),
+                secret: secret.to_string(),
+            },
+            remote_ip,
+            protocol,
+        )
+        .await
+    }
+
+    pub async fn authenticate_bearer(
+        &self,
+        token: &str,
+        username: Option<&str>,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+    ) -> AuthResult<AccessToken> {
+        // Try tokens issued by this server first
+        match self.validate_access_token("access_token", token).await {
+            Ok((account_id, _, _)) => {
+                if let Some(access_token) = self.get_access_token(account_id).await.filter(|t| {
+                    username.is_none_or(|username| t.name.eq_ignore_ascii_case(username))
+                }) {
+                    return AuthResult::Success(access_token);
+                }
+            }
+            Err(err) => {
+                tracing::debug!(
+                    context = "authenticate_bearer",
+                    err = err,
+                    "Failed to validate access token."
+                );
+            }
+        }
+
+        // Then try the directory, which validates tokens issued by external
+        // identity providers when configured to do so
+        let is_oidc = matches!(self.core.storage.directory.store, DirectoryInner::Oidc(_));
+        let credentials = match username {
+            Some(username) if is_oidc => Credentials::XOauth2 {
+                username: username.to_string(),
+                secret: token.to_string(),
+            },
+            Some(username) => Credentials::Plain {
+                username: username.to_string(),
+                secret: token.to_string(),
+            },
+            None if is_oidc => Credentials::OAuthBearer {
+                token: token.to_string(),
+            },
+            None => return AuthResult::Failure(AuthFailureReason::InvalidCredentials),
+        };
+        self.authenticate_credentials(&credentials, remote_ip, protocol)
+            .await
+    }
+
+    async fn authenticate_credentials(
+        &self,
+        credentials: &Credentials<String>,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
     ) -> AuthResult<AccessToken> {
         match self
             .core
             .authenticate(
                 &self.core.storage.directory,
                 &self.smtp.inner.ipc,
-                &Credentials::Plain {
-                    username: username.to_string(),
-                    secret: secret.to_string(),
-                },
+                credentials,
                 remote_ip,
                 protocol,
                 true,
@@ -177,6 +234,56 @@ impl JMAP {
         }
     }
 
+    pub async fn scram_begin(
+        &self,
+        algorithm: ScramAlgorithm,
+        is_plus: bool,
+        client_first: &[u8],
+        channel_binding: Option<Vec<u8>>,
+    ) -> directory::Result<Option<ScramServer>> {
+        self.core
+            .scram_begin(
+                &self.core.storage.directory,
+                algorithm,
+                is_plus,
+                client_first,
+                channel_binding,
+                true,
+            )
+            .await
+    }
+
+    pub async fn authenticate_scram(
+        &self,
+        server: ScramServer,
+        client_final: &[u8],
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+    ) -> AuthResult<(AccessToken, String)> {
+        match self
+            .core
+            .scram_finish(
+                &self.core.storage.directory,
+                &self.smtp.inner.ipc,
+                server,
+                client_final,
+                remote_ip,
+                protocol,
+                true,
+            )
+            .await
+        {
+            Ok(AuthResult::Success((principal, server_final))) => {
+                AuthResult::Success((AccessToken::new(principal), server_final))
+            }
+            Ok(AuthResult::Failure(reason)) => {
+                let _ = self.is_auth_allowed_hard(&remote_ip).await;
+                AuthResult::Failure(reason)
+            }
+            Err(err) => AuthResult::Failure(AuthFailureReason::InternalError(err)),
+        }
+    }
+
     pub async fn get_access_token(&self, account_id: u32) -> Option<AccessToken> {
         match self
             .core
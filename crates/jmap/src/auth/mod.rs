@@ -14,7 +14,10 @@ use aes_gcm_siv::{
     AeadInPlace, Aes256GcmSiv, KeyInit, Nonce,
 };
 
-use directory::{Principal, Type};
+use directory::{
+    core::role::{Permission, Role},
+    Principal, Type,
+};
 use jmap_proto::{
     error::method::MethodError,
     types::{collection::Collection, id::Id},
@@ -36,10 +39,12 @@ pub struct AccessToken {
     pub description: Option<String>,
     pub quota: u64,
     pub is_superuser: bool,
+    pub roles: Vec<Role>,
 }
 
 impl AccessToken {
     pub fn new(principal: Principal<u32>) -> Self {
+        let roles = principal.roles().collect();
         Self {
             primary_id: principal.id,
             member_of: principal.member_of,
@@ -48,6 +53,7 @@ impl AccessToken {
             description: principal.description,
             quota: principal.quota,
             is_superuser: principal.typ == Type::Superuser,
+            roles,
         }
     }
 
@@ -85,6 +91,55 @@ impl AccessToken {
         self.is_superuser
     }
 
+    // Returns true if the permission is granted without domain restrictions
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.is_superuser
+            || self
+                .roles
+                .iter()
+                .any(|role| role.domain.is_none() && role.has_permission(permission))
+    }
+
+    // Returns true if the permission is granted, either globally or for some domains
+    pub fn has_any_permission(&self, permission: Permission) -> bool {
+        self.is_superuser
+            || self
+                .roles
+                .iter()
+                .any(|role| role.has_permission(permission))
+    }
+
+    pub fn has_domain_permission(&self, permission: Permission, domain: &str) -> bool {
+        self.has_permission(permission)
+            || self
+                .permitted_domains(permission)
+                .any(|permitted| permitted.eq_ignore_ascii_case(domain))
+    }
+
+    pub fn has_principal_permission<T>(
+        &self,
+        permission: Permission,
+        principal: &Principal<T>,
+    ) -> bool {
+        // Only superusers can modify principals holding administrative privileges
+        self.is_superuser
+            || ((permission == Permission::PrincipalView
+                || (principal.typ != Type::Superuser && principal.roles.is_empty()))
+                && (self.has_permission(permission)
+                    || principal.is_in_domains(self.permitted_domains(permission))))
+    }
+
+    pub fn permitted_domains(
+        &self,
+        permission: Permission,
+    ) -> impl Iterator<Item = &str> + Clone + '_ {
+        self.roles.iter().filter_map(move |role| {
+            role.domain
+                .as_deref()
+                .filter(|_| role.has_permission(permission))
+        })
+    }
+
     pub fn is_shared(&self, account_id: u32) -> bool {
         !self.is_member(account_id) && self.access_to.iter().any(|(id, _)| *id == account_id)
     }
@@ -26,8 +26,8 @@ use crate::{
 use se_common::EnterpriseCore;
 
 use super::{
-    DeviceAuthResponse, FormData, OAuthCode, OAuthCodeRequest, CLIENT_ID_MAX_LEN, DEVICE_CODE_LEN,
-    MAX_POST_LEN, USER_CODE_ALPHABET, USER_CODE_LEN,
+    oidc::has_scope, DeviceAuthResponse, FormData, OAuthCode, OAuthCodeRequest, CLIENT_ID_MAX_LEN,
+    DEVICE_CODE_LEN, MAX_POST_LEN, USER_CODE_ALPHABET, USER_CODE_LEN,
 };
 
 impl JMAP {
@@ -42,6 +42,8 @@ impl JMAP {
                     OAuthCodeRequest::Code {
                         client_id,
                         redirect_uri,
+                        scope,
+                        nonce,
                     } => {
                         // Validate clientId
                         if client_id.len() > CLIENT_ID_MAX_LEN {
@@ -49,12 +51,51 @@ impl JMAP {
                                 details: "Client ID is invalid.".into(),
                             }
                             .into_http_response();
-                        } else if redirect_uri
-                            .as_ref()
-                            .map_or(false, |uri| !uri.starts_with("https://"))
+                        }
+
+                        // Registered clients may only use their own redirect URIs
+                        match self.get_oauth_client(&client_id).await {
+                            Ok(Some(client)) => {
+                                if !redirect_uri
+                                    .as_ref()
+                                    .is_some_and(|uri| client.redirect_uris.contains(uri))
+                                {
+                                    return ManagementApiError::Other {
+                                        details: "Redirect URI is not registered for this client."
+                                            .into(),
+                                    }
+                                    .into_http_response();
+                                }
+                            }
+                            Ok(None) => {
+                                if redirect_uri
+                                    .as_ref()
+                                    .is_some_and(|uri| !uri.starts_with("https://"))
+                                {
+                                    return ManagementApiError::Other {
+                                        details: "Redirect URI must be HTTPS.".into(),
+                                    }
+                                    .into_http_response();
+                                } else if scope
+                                    .as_deref()
+                                    .is_some_and(|scope| has_scope(scope, "openid"))
+                                {
+                                    return ManagementApiError::Other {
+                                        details: "OpenID Connect requires a registered client."
+                                            .into(),
+                                    }
+                                    .into_http_response();
+                                }
+                            }
+                            Err(err) => return err.into_http_response(),
+                        }
+                        if !self.core.jmap.oidc.is_enabled()
+                            && scope
+                                .as_deref()
+                                .is_some_and(|scope| has_scope(scope, "openid"))
                         {
                             return ManagementApiError::Other {
-                                details: "Redirect URI must be HTTPS.".into(),
+                                details: "OpenID Connect is not enabled.".into(),
                             }
                             .into_http_response();
                         }
@@ -72,6 +113,8 @@ impl JMAP {
                             account_id: access_token.primary_id(),
                             client_id,
                             params: redirect_uri.unwrap_or_default(),
+                            scope,
+                            nonce,
                         })
                         .serialize();
 
@@ -207,6 +250,8 @@ impl JMAP {
             account_id: u32::MAX,
             client_id,
             params: device_code.clone(),
+            scope: None,
+            nonce: None,
         })
         .serialize();
 
@@ -0,0 +1,275 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::collections::BTreeSet;
+
+use hyper::{header::AUTHORIZATION, Method};
+use jmap_proto::error::request::RequestError;
+use mail_parser::decoders::base64::base64_decode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utils::url_params::UrlParams;
+
+use crate::{
+    api::{
+        http::ToHttpResponse,
+        management::{decode_path_element, ManagementApiError},
+        HttpRequest, HttpResponse, JsonResponse,
+    },
+    JMAP,
+};
+
+use super::{FormData, CLIENT_ID_MAX_LEN};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OAuthClient {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "redirectUris", default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+}
+
+impl JMAP {
+    pub async fn handle_manage_oauth_client(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        match (path.get(2), req.method()) {
+            (None, &Method::GET) => {
+                // List clients
+                let params = UrlParams::new(req.uri().query());
+                let page: usize = params.parse("page").unwrap_or(0);
+                let limit: usize = params.parse("limit").unwrap_or(0);
+
+                let client_ids = match self.core.storage.config.list("oauth.client.", true).await {
+                    Ok(keys) => keys
+                        .into_iter()
+                        .filter_map(|(key, _)| key.split_once('.').map(|(id, _)| id.to_string()))
+                        .collect::<BTreeSet<_>>(),
+                    Err(err) => return err.into_http_response(),
+                };
+                let total = client_ids.len();
+                let offset = page.saturating_sub(1) * limit;
+                let mut clients = Vec::new();
+                for client_id in client_ids.into_iter().skip(offset).take(if limit > 0 {
+                    limit
+                } else {
+                    usize::MAX
+                }) {
+                    match self.get_oauth_client(&client_id).await {
+                        Ok(Some(client)) => clients.push(client),
+                        Ok(None) => (),
+                        Err(err) => return err.into_http_response(),
+                    }
+                }
+
+                JsonResponse::new(json!({
+                        "data": {
+                            "items": clients,
+                            "total": total,
+                        },
+                }))
+                .into_http_response()
+            }
+            (Some(client_id), &Method::GET) => {
+                // Fetch client
+                match self
+                    .get_oauth_client(decode_path_element(client_id).as_ref())
+                    .await
+                {
+                    Ok(Some(client)) => JsonResponse::new(json!({
+                        "data": client,
+                    }))
+                    .into_http_response(),
+                    Ok(None) => RequestError::not_found().into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            (None, &Method::POST) => {
+                // Register client
+                let client = match serde_json::from_slice::<OAuthClient>(
+                    body.as_deref().unwrap_or_default(),
+                ) {
+                    Ok(client) => client,
+                    Err(err) => return err.into_http_response(),
+                };
+
+                // Validate client
+                if !is_valid_client_id(&client.client_id) {
+                    return ManagementApiError::Other {
+                        details: "Client ID is invalid.".into(),
+                    }
+                    .into_http_response();
+                } else if client.redirect_uris.is_empty() {
+                    return ManagementApiError::FieldMissing {
+                        field: "redirectUris".into(),
+                    }
+                    .into_http_response();
+                } else if let Some(uri) = client
+                    .redirect_uris
+                    .iter()
+                    .find(|uri| !is_valid_redirect_uri(uri))
+                {
+                    return ManagementApiError::Other {
+                        details: format!("Redirect URI {uri:?} is invalid.").into(),
+                    }
+                    .into_http_response();
+                }
+                match self.get_oauth_client(&client.client_id).await {
+                    Ok(None) => (),
+                    Ok(Some(_)) => {
+                        return ManagementApiError::FieldAlreadyExists {
+                            field: "clientId".into(),
+                            value: client.client_id.into(),
+                        }
+                        .into_http_response();
+                    }
+                    Err(err) => return err.into_http_response(),
+                }
+
+                // Write client
+                let prefix = format!("oauth.client.{}", client.client_id);
+                let mut keys = client
+                    .redirect_uris
+                    .into_iter()
+                    .enumerate()
+                    .map(|(pos, uri)| (format!("{prefix}.redirect-uri.{pos:04}"), uri))
+                    .collect::<Vec<_>>();
+                if let Some(description) = client.description {
+                    keys.push((format!("{prefix}.description"), description));
+                }
+                if let Some(secret) = client.secret {
+                    keys.push((format!("{prefix}.secret"), secret));
+                }
+
+                match self.core.storage.config.set(keys).await {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            (Some(client_id), &Method::DELETE) => {
+                // Delete client
+                let client_id = decode_path_element(client_id);
+                if !is_valid_client_id(client_id.as_ref()) {
+                    return RequestError::not_found().into_http_response();
+                }
+
+                match self
+                    .core
+                    .storage
+                    .config
+                    .clear_prefix(format!("oauth.client.{client_id}."))
+                    .await
+                {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+
+    pub async fn get_oauth_client(&self, client_id: &str) -> store::Result<Option<OAuthClient>> {
+        if !is_valid_client_id(client_id) {
+            return Ok(None);
+        }
+
+        let mut entries = self
+            .core
+            .storage
+            .config
+            .list(&format!("oauth.client.{client_id}."), true)
+            .await?;
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let mut client = OAuthClient {
+            client_id: client_id.to_string(),
+            ..Default::default()
+        };
+        for (key, value) in entries {
+            match key.as_str() {
+                "description" => client.description = Some(value),
+                "secret" => client.secret = Some(value),
+                _ if key.starts_with("redirect-uri") => client.redirect_uris.push(value),
+                _ => (),
+            }
+        }
+
+        Ok(Some(client))
+    }
+}
+
+impl OAuthClient {
+    // Verifies the client secret using either the "client_secret_basic"
+    // or the "client_secret_post" authentication methods
+    pub fn verify_secret(&self, req: &HttpRequest, params: &FormData) -> bool {
+        let Some(secret) = &self.secret else {
+            return true;
+        };
+
+        if let Some(client_secret) = params.get("client_secret") {
+            return client_secret == secret;
+        }
+
+        req.headers()
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| {
+                h.strip_prefix("Basic ")
+                    .or_else(|| h.strip_prefix("basic "))
+            })
+            .and_then(|h| base64_decode(h.trim().as_bytes()))
+            .and_then(|h| String::from_utf8(h).ok())
+            .is_some_and(|h| {
+                h.split_once(':').is_some_and(|(client_id, client_secret)| {
+                    client_id == self.client_id && client_secret == secret
+                })
+            })
+    }
+}
+
+fn is_valid_client_id(client_id: &str) -> bool {
+    !client_id.is_empty()
+        && client_id.len() <= CLIENT_ID_MAX_LEN
+        && client_id
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+}
+
+// Redirect URIs have to use HTTPS, except for native applications listening on
+// the loopback interface (RFC 8252)
+pub fn is_valid_redirect_uri(uri: &str) -> bool {
+    let Some(uri) = uri
+        .parse::<hyper::Uri>()
+        .ok()
+        .filter(|_| !uri.contains('#'))
+    else {
+        return false;
+    };
+
+    match (uri.scheme_str(), uri.host()) {
+        (Some("https"), Some(host)) => !host.is_empty(),
+        (Some("http"), Some(host)) => {
+            matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+        }
+        _ => false,
+    }
+}
@@ -15,6 +15,8 @@ use crate::api::{
 };
 
 pub mod auth;
+pub mod client;
+pub mod oidc;
 pub mod token;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -50,6 +52,10 @@ pub struct OAuthCode {
     pub account_id: u32,
     pub client_id: String,
     pub params: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub nonce: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -121,6 +127,8 @@ pub struct OAuthResponse {
     pub refresh_token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -183,6 +191,10 @@ pub enum OAuthCodeRequest {
     Code {
         client_id: String,
         redirect_uri: Option<String>,
+        #[serde(default)]
+        scope: Option<String>,
+        #[serde(default)]
+        nonce: Option<String>,
     },
     Device {
         code: String,
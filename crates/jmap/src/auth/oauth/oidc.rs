@@ -0,0 +1,265 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::SystemTime;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use directory::QueryBy;
+use jmap_proto::error::request::RequestError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpResponse, JsonResponse},
+    auth::AccessToken,
+    JMAP,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenIdMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UserInfo {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    pub exp: u64,
+    pub iat: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub userinfo: UserInfo,
+}
+
+impl UserInfo {
+    pub fn restrict_to_scope(&mut self, scope: &str) {
+        if !has_scope(scope, "profile") {
+            self.name = None;
+            self.preferred_username = None;
+        }
+        if !has_scope(scope, "email") {
+            self.email = None;
+            self.email_verified = None;
+        }
+        if !has_scope(scope, "groups") {
+            self.groups.clear();
+        }
+    }
+}
+
+impl OpenIdMetadata {
+    pub fn new(base_url: impl AsRef<str>) -> Self {
+        let base_url = base_url.as_ref();
+        OpenIdMetadata {
+            issuer: base_url.into(),
+            authorization_endpoint: format!("{}/authorize/code", base_url),
+            token_endpoint: format!("{}/auth/token", base_url),
+            userinfo_endpoint: format!("{}/auth/userinfo", base_url),
+            jwks_uri: format!("{}/jwks.json", base_url),
+            scopes_supported: vec![
+                "openid".to_string(),
+                "profile".to_string(),
+                "email".to_string(),
+                "groups".to_string(),
+                "offline_access".to_string(),
+            ],
+            response_types_supported: vec!["code".to_string()],
+            grant_types_supported: vec![
+                "authorization_code".to_string(),
+                "refresh_token".to_string(),
+            ],
+            subject_types_supported: vec!["public".to_string()],
+            id_token_signing_alg_values_supported: vec!["RS256".to_string()],
+            token_endpoint_auth_methods_supported: vec![
+                "client_secret_basic".to_string(),
+                "client_secret_post".to_string(),
+                "none".to_string(),
+            ],
+            claims_supported: vec![
+                "sub".to_string(),
+                "iss".to_string(),
+                "aud".to_string(),
+                "exp".to_string(),
+                "iat".to_string(),
+                "nonce".to_string(),
+                "name".to_string(),
+                "preferred_username".to_string(),
+                "email".to_string(),
+                "email_verified".to_string(),
+                "groups".to_string(),
+            ],
+        }
+    }
+}
+
+impl JMAP {
+    pub fn handle_jwks_request(&self) -> HttpResponse {
+        if self.core.jmap.oidc.is_enabled() {
+            JsonResponse::new(json!({
+                "keys": self
+                    .core
+                    .jmap
+                    .oidc
+                    .keys
+                    .iter()
+                    .map(|key| key.to_jwk())
+                    .collect::<Vec<_>>(),
+            }))
+            .into_http_response()
+        } else {
+            RequestError::not_found().into_http_response()
+        }
+    }
+
+    pub async fn handle_userinfo_request(
+        &self,
+        access_token: &AccessToken,
+        bearer_token: Option<&str>,
+    ) -> HttpResponse {
+        // Obtain the scope granted to the bearer token
+        let scope = if let Some(bearer_token) = bearer_token {
+            match self.token_scope(bearer_token).await {
+                Ok(scope) => scope,
+                Err(err) => return err.into_http_response(),
+            }
+        } else {
+            None
+        };
+
+        match self.build_userinfo(access_token.primary_id()).await {
+            Ok(Some(mut userinfo)) => {
+                // Only return the claims covered by the granted scope
+                userinfo.restrict_to_scope(scope.as_deref().unwrap_or_default());
+                JsonResponse::new(userinfo).into_http_response()
+            }
+            Ok(None) => RequestError::not_found().into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
+
+    pub async fn issue_id_token(
+        &self,
+        account_id: u32,
+        client_id: &str,
+        scope: &str,
+        nonce: Option<String>,
+        issuer: impl Into<String>,
+    ) -> Result<String, &'static str> {
+        let key = self
+            .core
+            .jmap
+            .oidc
+            .signature_key
+            .as_ref()
+            .ok_or("OpenID Connect is not configured")?;
+
+        // Only include the claims requested by the client
+        let mut userinfo = self
+            .build_userinfo(account_id)
+            .await
+            .map_err(|_| "Temporary lookup error")?
+            .ok_or("Account no longer exists")?;
+        userinfo.restrict_to_scope(scope);
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let claims = IdTokenClaims {
+            iss: issuer.into(),
+            aud: client_id.to_string(),
+            exp: now + self.core.jmap.oidc.expiry_id_token,
+            iat: now,
+            nonce,
+            userinfo,
+        };
+
+        // Build and sign JWT
+        let header = json!({
+            "alg": "RS256",
+            "typ": "JWT",
+            "kid": key.id,
+        });
+        let mut token = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD
+                .encode(serde_json::to_vec(&claims).map_err(|_| "Failed to serialize claims")?)
+        );
+        let signature = key.sign(token.as_bytes()).ok_or("Failed to sign token")?;
+        token.push('.');
+        token.push_str(&URL_SAFE_NO_PAD.encode(signature));
+
+        Ok(token)
+    }
+
+    async fn build_userinfo(&self, account_id: u32) -> directory::Result<Option<UserInfo>> {
+        let Some(principal) = self
+            .core
+            .storage
+            .directory
+            .query(QueryBy::Id(account_id), true)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut groups = Vec::with_capacity(principal.member_of.len());
+        for group_id in principal.member_of {
+            if let Some(group) = self
+                .core
+                .storage
+                .directory
+                .query(QueryBy::Id(group_id), false)
+                .await?
+            {
+                groups.push(group.name);
+            }
+        }
+
+        Ok(Some(UserInfo {
+            sub: account_id.to_string(),
+            name: principal
+                .description
+                .or_else(|| Some(principal.name.clone())),
+            email_verified: principal.emails.first().map(|_| true),
+            email: principal.emails.into_iter().next(),
+            preferred_username: Some(principal.name),
+            groups,
+        }))
+    }
+}
+
+pub fn has_scope(scope: &str, name: &str) -> bool {
+    scope.split_ascii_whitespace().any(|s| s == name)
+}
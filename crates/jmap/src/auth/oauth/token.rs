@@ -24,13 +24,17 @@ use crate::{
 };
 
 use super::{
-    ErrorType, FormData, OAuthCode, OAuthResponse, OAuthStatus, TokenResponse, CLIENT_ID_MAX_LEN,
-    MAX_POST_LEN, RANDOM_CODE_LEN,
+    oidc::has_scope, ErrorType, FormData, OAuthCode, OAuthResponse, OAuthStatus, TokenResponse,
+    CLIENT_ID_MAX_LEN, MAX_POST_LEN, RANDOM_CODE_LEN,
 };
 
 impl JMAP {
     // Token endpoint
-    pub async fn handle_token_request(&self, req: &mut HttpRequest) -> HttpResponse {
+    pub async fn handle_token_request(
+        &self,
+        req: &mut HttpRequest,
+        base_url: impl AsRef<str>,
+    ) -> HttpResponse {
         // Parse form
         let params = match FormData::from_request(req, MAX_POST_LEN).await {
             Ok(params) => params,
@@ -56,7 +60,16 @@ impl JMAP {
                 {
                     Ok(Some(auth_code)) => {
                         let oauth = auth_code.inner;
-                        if client_id != oauth.client_id || redirect_uri != oauth.params {
+                        let is_authenticated = match self.get_oauth_client(client_id).await {
+                            Ok(Some(client)) => client.verify_secret(req, &params),
+                            Ok(None) => true,
+                            Err(err) => return err.into_http_response(),
+                        };
+
+                        if client_id != oauth.client_id
+                            || redirect_uri != oauth.params
+                            || !is_authenticated
+                        {
                             TokenResponse::error(ErrorType::InvalidClient)
                         } else if oauth.status == OAuthStatus::Authorized {
                             // Mark this token as issued
@@ -71,13 +84,53 @@ impl JMAP {
                             }
 
                             // Issue token
-                            self.issue_token(oauth.account_id, &oauth.client_id, true)
-                                .await
-                                .map(TokenResponse::Granted)
-                                .unwrap_or_else(|err| {
-                                    tracing::error!("Failed to generate OAuth token: {}", err);
-                                    TokenResponse::error(ErrorType::InvalidRequest)
-                                })
+                            let mut response = self
+                                .issue_token(oauth.account_id, &oauth.client_id, true)
+                                .await;
+
+                            // Issue ID token
+                            if let (Ok(response), Some(scope)) = (&mut response, oauth.scope) {
+                                if has_scope(&scope, "openid") {
+                                    match self
+                                        .issue_id_token(
+                                            oauth.account_id,
+                                            &oauth.client_id,
+                                            &scope,
+                                            oauth.nonce,
+                                            base_url.as_ref(),
+                                        )
+                                        .await
+                                    {
+                                        Ok(id_token) => {
+                                            response.id_token = id_token.into();
+                                        }
+                                        Err(err) => {
+                                            tracing::error!(
+                                                context = "oidc",
+                                                event = "error",
+                                                reason = %err,
+                                                "Failed to generate ID token."
+                                            );
+                                            return JsonResponse::with_status(
+                                                StatusCode::BAD_REQUEST,
+                                                TokenResponse::error(ErrorType::InvalidRequest),
+                                            )
+                                            .into_http_response();
+                                        }
+                                    }
+                                }
+
+                                // Remember the granted scope for the userinfo endpoint
+                                if let Err(err) = self.store_token_scope(response, &scope).await {
+                                    return err.into_http_response();
+                                }
+                                response.scope = scope.into();
+                            }
+
+                            response.map(TokenResponse::Granted).unwrap_or_else(|err| {
+                                tracing::error!("Failed to generate OAuth token: {}", err);
+                                TokenResponse::error(ErrorType::InvalidRequest)
+                            })
                         } else {
                             TokenResponse::error(ErrorType::InvalidGrant)
                         }
@@ -152,18 +205,34 @@ impl JMAP {
                     .await
                 {
                     // TODO: implement revoking client ids
-                    response = self
+                    response = match self
                         .issue_token(
                             account_id,
                             &client_id,
                             time_left <= self.core.jmap.oauth_expiry_refresh_token_renew,
                         )
                         .await
-                        .map(TokenResponse::Granted)
-                        .unwrap_or_else(|err| {
+                    {
+                        Ok(mut granted) => {
+                            // Carry over the scope granted to the refresh token
+                            match self.token_scope(refresh_token).await {
+                                Ok(Some(scope)) => {
+                                    if let Err(err) = self.store_token_scope(&granted, &scope).await
+                                    {
+                                        return err.into_http_response();
+                                    }
+                                    granted.scope = scope.into();
+                                }
+                                Ok(None) => {}
+                                Err(err) => return err.into_http_response(),
+                            }
+                            TokenResponse::Granted(granted)
+                        }
+                        Err(err) => {
                             tracing::debug!("Failed to refresh OAuth token: {}", err);
                             TokenResponse::error(ErrorType::InvalidGrant)
-                        });
+                        }
+                    };
                 }
             } else {
                 response = TokenResponse::error(ErrorType::InvalidRequest);
@@ -232,9 +301,45 @@ impl JMAP {
                 None
             },
             scope: None,
+            id_token: None,
         })
     }
 
+    async fn store_token_scope(&self, response: &OAuthResponse, scope: &str) -> store::Result<()> {
+        for (token, expiry) in [
+            (
+                Some(&response.access_token),
+                self.core.jmap.oauth_expiry_token,
+            ),
+            (
+                response.refresh_token.as_ref(),
+                self.core.jmap.oauth_expiry_refresh_token,
+            ),
+        ] {
+            if let Some(token) = token {
+                self.core
+                    .storage
+                    .lookup
+                    .key_set(
+                        token_scope_key(token),
+                        scope.as_bytes().to_vec(),
+                        expiry.into(),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn token_scope(&self, token: &str) -> store::Result<Option<String>> {
+        self.core
+            .storage
+            .lookup
+            .key_get::<String>(token_scope_key(token))
+            .await
+    }
+
     fn encode_access_token(
         &self,
         grant_type: &str,
@@ -350,3 +455,7 @@ impl JMAP {
         Ok((account_id, client_id, expiry - now))
     }
 }
+
+fn token_scope_key(token: &str) -> Vec<u8> {
+    format!("oauth_scope:{}", blake3::hash(token.as_bytes()).to_hex()).into_bytes()
+}
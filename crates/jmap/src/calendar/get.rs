@@ -0,0 +1,162 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{acl::Acl, collection::Collection, property::Property, value::Value},
+};
+
+use utils::map::bitmap::Bitmap;
+
+use crate::{
+    auth::{acl::EffectiveAcl, AccessToken},
+    JMAP,
+};
+
+impl JMAP {
+    pub async fn calendar_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.core.jmap.get_max_objects)?;
+        let properties = request.unwrap_properties(&[
+            Property::Id,
+            Property::Name,
+            Property::Description,
+            Property::SortOrder,
+            Property::Color,
+            Property::IsDefault,
+            Property::IsSubscribed,
+            Property::IsVisible,
+            Property::MyRights,
+        ]);
+        let account_id = request.account_id.document_id();
+        let mut calendar_ids = self.calendar_get_or_create(account_id).await?;
+        if access_token.is_shared(account_id) {
+            calendar_ids &= self
+                .shared_documents(access_token, account_id, Collection::Calendar, Acl::Read)
+                .await?;
+        }
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            calendar_ids
+                .iter()
+                .take(self.core.jmap.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::Calendar)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the calendar object
+            let document_id = id.document_id();
+            if !calendar_ids.contains(document_id) {
+                response.not_found.push(id.into());
+                continue;
+            }
+            let mut values = if let Some(values) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::Calendar,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                values
+            } else {
+                response.not_found.push(id.into());
+                continue;
+            };
+
+            let mut calendar = Object::with_capacity(properties.len());
+            for property in &properties {
+                let value = match property {
+                    Property::Id => Value::Id(id),
+                    Property::Name | Property::Description | Property::Color => {
+                        values.remove(property)
+                    }
+                    Property::SortOrder => values
+                        .properties
+                        .remove(property)
+                        .unwrap_or(Value::UnsignedInt(0)),
+                    Property::IsDefault => values
+                        .properties
+                        .remove(property)
+                        .unwrap_or(Value::Bool(false)),
+                    Property::IsVisible => values
+                        .properties
+                        .remove(property)
+                        .unwrap_or(Value::Bool(true)),
+                    Property::IsSubscribed => values
+                        .properties
+                        .remove(property)
+                        .map(|subscriptions| match subscriptions {
+                            Value::List(values)
+                                if values
+                                    .contains(&Value::Id(access_token.primary_id().into())) =>
+                            {
+                                Value::Bool(true)
+                            }
+                            _ => Value::Bool(false),
+                        })
+                        .unwrap_or(Value::Bool(false)),
+                    Property::MyRights => {
+                        let acl = if access_token.is_shared(account_id) {
+                            values.effective_acl(access_token)
+                        } else {
+                            Bitmap::all()
+                        };
+                        let can_write = acl.contains(Acl::ModifyItems);
+                        Object::with_capacity(8)
+                            .with_property(Property::MayReadFreeBusy, acl.contains(Acl::Read))
+                            .with_property(Property::MayReadItems, acl.contains(Acl::ReadItems))
+                            .with_property(Property::MayWriteAll, can_write)
+                            .with_property(Property::MayWriteOwn, can_write)
+                            .with_property(Property::MayUpdatePrivate, can_write)
+                            .with_property(Property::MayRsvp, can_write)
+                            .with_property(Property::MayAdmin, acl.contains(Acl::Administer))
+                            .with_property(Property::MayDelete, acl.contains(Acl::Delete))
+                            .into()
+                    }
+                    Property::Acl => {
+                        self.acl_get(
+                            values
+                                .properties
+                                .get(&Property::Acl)
+                                .and_then(|v| v.as_acl())
+                                .map(|v| &v[..])
+                                .unwrap_or_else(|| &[]),
+                            access_token,
+                            account_id,
+                        )
+                        .await
+                    }
+
+                    _ => Value::Null,
+                };
+
+                calendar.append(property.clone(), value);
+            }
+
+            // Add result to response
+            response.list.push(calendar);
+        }
+        Ok(response)
+    }
+}
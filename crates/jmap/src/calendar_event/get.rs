@@ -0,0 +1,184 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{
+        acl::Acl, collection::Collection, date::UTCDate, id::Id, property::Property, value::Value,
+    },
+};
+
+use crate::{auth::AccessToken, contact::set::ContactCardObject, JMAP};
+
+use super::{
+    recurrence::{
+        format_local_date_time, recurrence_id_from_prefix, recurrence_override, ExpandRecurrence,
+    },
+    EventFields,
+};
+
+impl JMAP {
+    pub async fn calendar_event_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.core.jmap.get_max_objects)?;
+        let properties = request.unwrap_properties(&[]);
+        let account_id = request.account_id.document_id();
+        let event_ids = if access_token.is_shared(account_id) {
+            self.shared_events(access_token, account_id, Acl::ReadItems)
+                .await?
+        } else {
+            self.get_document_ids(account_id, Collection::CalendarEvent)
+                .await?
+                .unwrap_or_default()
+        };
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            event_ids
+                .iter()
+                .take(self.core.jmap.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::CalendarEvent)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the event object
+            let document_id = id.document_id();
+            if !event_ids.contains(document_id) {
+                response.not_found.push(id.into());
+                continue;
+            }
+            let mut values = if let Some(values) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                values
+            } else {
+                response.not_found.push(id.into());
+                continue;
+            };
+
+            // Instances of recurring events are returned as standalone events
+            if let Some(recurrence_id) = recurrence_id_from_prefix(id.prefix_id()) {
+                if let Some(occurrence) =
+                    values.instance(recurrence_id, 0, self.core.jmap.calendar_max_instances)
+                {
+                    let patch = values
+                        .event_obj("recurrenceOverrides")
+                        .and_then(|overrides| recurrence_override(overrides, &recurrence_id))
+                        .cloned();
+                    for property in [
+                        "recurrenceRules",
+                        "excludedRecurrenceRules",
+                        "recurrenceOverrides",
+                    ] {
+                        values.remove(&Property::parse(property));
+                    }
+                    for (property, value) in patch.into_iter().flat_map(|p| p.properties) {
+                        let path = property.to_string();
+                        if path == "excluded" {
+                            continue;
+                        } else if path.contains('/') {
+                            values.patch_json(&path, value);
+                        } else if value != Value::Null {
+                            values.set(Property::parse(&path), value);
+                        } else {
+                            values.remove(&Property::parse(&path));
+                        }
+                    }
+                    values.set(
+                        Property::parse("recurrenceId"),
+                        Value::Text(format_local_date_time(&recurrence_id)),
+                    );
+                    values.set(
+                        Property::parse("start"),
+                        Value::Text(format_local_date_time(&occurrence.start)),
+                    );
+                    values.set(
+                        Property::UtcStart,
+                        Value::UnsignedInt(occurrence.utc_start as u64),
+                    );
+                    values.set(
+                        Property::UtcEnd,
+                        Value::UnsignedInt(occurrence.utc_end as u64),
+                    );
+                } else {
+                    response.not_found.push(id.into());
+                    continue;
+                }
+            }
+
+            // Calendar ids are stored as a list and returned as an id map
+            let calendar_ids =
+                values
+                    .calendar_ids()
+                    .fold(Object::with_capacity(1), |obj, calendar_id| {
+                        obj.with_property(
+                            Property::_T(Id::from(calendar_id).to_string()),
+                            Value::Bool(true),
+                        )
+                    });
+            values.set(Property::CalendarIds, Value::Object(calendar_ids));
+
+            // The UTC start and end times are only returned when requested
+            let utc_start = values.remove(&Property::UtcStart);
+            let utc_end = values.remove(&Property::UtcEnd);
+
+            let event = if properties.is_empty() {
+                let mut event = Object::with_capacity(values.properties.len() + 1);
+                event.append(Property::Id, Value::Id(id));
+                for (property, value) in values.properties {
+                    event.append(property, value);
+                }
+                event
+            } else {
+                let mut event = Object::with_capacity(properties.len());
+                for property in &properties {
+                    let value = match property {
+                        Property::Id => Value::Id(id),
+                        Property::UtcStart => utc_date(&utc_start),
+                        Property::UtcEnd => utc_date(&utc_end),
+                        property => values.remove(property),
+                    };
+                    event.append(property.clone(), value);
+                }
+                event
+            };
+
+            // Add result to response
+            response.list.push(event);
+        }
+        Ok(response)
+    }
+}
+
+fn utc_date(value: &Value) -> Value {
+    match value {
+        Value::UnsignedInt(timestamp) if *timestamp != u64::MAX => {
+            Value::Date(UTCDate::from_timestamp(*timestamp as i64))
+        }
+        _ => Value::Null,
+    }
+}
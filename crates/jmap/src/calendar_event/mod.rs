@@ -0,0 +1,237 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::collections::HashSet;
+
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+use store::write::{
+    BatchBuilder, BitmapClass, BitmapHash, IntoOperations, Operation, TokenizeText,
+};
+
+pub mod get;
+pub mod query;
+pub mod recurrence;
+pub mod scheduling;
+pub mod set;
+
+// Full-text index of the title, description, locations and participants of a
+// JSCalendar event, which are nested within the event and not covered by the
+// object schema.
+#[derive(Debug, Default)]
+pub struct EventTextIndex {
+    tokens: Vec<(Property, HashSet<String>, HashSet<String>)>,
+}
+
+impl EventTextIndex {
+    pub fn new(current: Option<&Object<Value>>, event: Option<&Object<Value>>) -> Self {
+        let mut index = EventTextIndex::default();
+
+        for (property, fn_values) in [
+            (
+                Property::Name,
+                (|event| event.event_str("title").into_iter().collect())
+                    as fn(&Object<Value>) -> Vec<&str>,
+            ),
+            (Property::Description, |event| {
+                event.event_str("description").into_iter().collect()
+            }),
+            (Property::Location, |event| event.event_locations()),
+            (Property::Email, |event| {
+                event
+                    .event_participants()
+                    .into_iter()
+                    .flat_map(|p| [p.name, p.email])
+                    .flatten()
+                    .collect()
+            }),
+        ] {
+            let mut add_tokens = HashSet::new();
+            let mut remove_tokens = HashSet::new();
+            if let Some(current) = current {
+                for value in fn_values(current) {
+                    value.tokenize_into(&mut remove_tokens);
+                }
+            }
+            if let Some(event) = event {
+                for value in fn_values(event) {
+                    for token in value.to_tokens() {
+                        if !remove_tokens.remove(&token) {
+                            add_tokens.insert(token);
+                        }
+                    }
+                }
+            }
+            index.tokens.push((property, add_tokens, remove_tokens));
+        }
+
+        index
+    }
+}
+
+impl IntoOperations for EventTextIndex {
+    fn build(self, batch: &mut BatchBuilder) {
+        for (property, add_tokens, remove_tokens) in self.tokens {
+            let field = u8::from(property);
+            for (tokens, set) in [(add_tokens, true), (remove_tokens, false)] {
+                for token in tokens {
+                    batch.ops.push(Operation::Bitmap {
+                        class: BitmapClass::Text {
+                            field,
+                            token: BitmapHash::new(token),
+                        },
+                        set,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Participant<'x> {
+    pub name: Option<&'x str>,
+    pub email: Option<&'x str>,
+    pub is_owner: bool,
+    pub is_attendee: bool,
+    pub participation_status: Option<&'x str>,
+    pub expect_reply: bool,
+}
+
+pub trait EventFields {
+    fn event_value(&self, name: &str) -> &Value;
+    fn event_str(&self, name: &str) -> Option<&str>;
+    fn event_uint(&self, name: &str) -> Option<u64>;
+    fn event_int(&self, name: &str) -> Option<i64>;
+    fn event_list(&self, name: &str) -> Option<&Vec<Value>>;
+    fn event_obj(&self, name: &str) -> Option<&Object<Value>>;
+    fn event_int_list(&self, name: &str) -> Vec<i32>;
+    fn event_uint_list(&self, name: &str) -> Vec<u32>;
+    fn event_locations(&self) -> Vec<&str>;
+    fn event_participants(&self) -> Vec<Participant<'_>>;
+    fn event_organizer(&self) -> Option<&str>;
+    fn calendar_ids(&self) -> impl Iterator<Item = u32> + '_;
+}
+
+impl EventFields for Object<Value> {
+    // Top-level properties of an event may have been parsed as well-known
+    // properties, while nested objects are always keyed by name.
+    fn event_value(&self, name: &str) -> &Value {
+        match self.get(&Property::parse(name)) {
+            Value::Null => self.get(&Property::_T(name.to_string())),
+            value => value,
+        }
+    }
+
+    fn event_str(&self, name: &str) -> Option<&str> {
+        self.event_value(name).as_string()
+    }
+
+    fn event_uint(&self, name: &str) -> Option<u64> {
+        self.event_value(name).as_uint()
+    }
+
+    fn event_int(&self, name: &str) -> Option<i64> {
+        match self.event_value(name) {
+            Value::UnsignedInt(value) => Some(*value as i64),
+            Value::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn event_list(&self, name: &str) -> Option<&Vec<Value>> {
+        self.event_value(name).as_list()
+    }
+
+    fn event_obj(&self, name: &str) -> Option<&Object<Value>> {
+        self.event_value(name).as_obj()
+    }
+
+    fn event_int_list(&self, name: &str) -> Vec<i32> {
+        self.event_list(name)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| match value {
+                Value::UnsignedInt(value) => Some(*value as i32),
+                Value::Int(value) => Some(*value as i32),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn event_uint_list(&self, name: &str) -> Vec<u32> {
+        self.event_list(name)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_uint().map(|value| value as u32))
+            .collect()
+    }
+
+    fn event_locations(&self) -> Vec<&str> {
+        self.event_obj("locations")
+            .into_iter()
+            .flat_map(|locations| locations.properties.values())
+            .filter_map(|location| location.as_obj().and_then(|l| l.event_str("name")))
+            .collect()
+    }
+
+    fn event_participants(&self) -> Vec<Participant<'_>> {
+        self.event_obj("participants")
+            .into_iter()
+            .flat_map(|participants| participants.properties.values())
+            .filter_map(|participant| {
+                let participant = participant.as_obj()?;
+                let roles = participant.event_obj("roles");
+                let has_role = |role: &str| {
+                    roles.is_some_and(|roles| matches!(roles.event_value(role), Value::Bool(true)))
+                };
+                Some(Participant {
+                    name: participant.event_str("name"),
+                    email: participant
+                        .event_obj("sendTo")
+                        .and_then(|send_to| send_to.event_str("imip"))
+                        .map(strip_mailto)
+                        .or_else(|| participant.event_str("email")),
+                    is_owner: has_role("owner"),
+                    is_attendee: has_role("attendee") || roles.is_none(),
+                    participation_status: participant.event_str("participationStatus"),
+                    expect_reply: matches!(
+                        participant.event_value("expectReply"),
+                        Value::Bool(true)
+                    ),
+                })
+            })
+            .collect()
+    }
+
+    fn event_organizer(&self) -> Option<&str> {
+        self.event_obj("replyTo")
+            .and_then(|reply_to| reply_to.event_str("imip"))
+            .map(strip_mailto)
+            .or_else(|| {
+                self.event_participants()
+                    .into_iter()
+                    .find(|p| p.is_owner)
+                    .and_then(|p| p.email)
+            })
+    }
+
+    fn calendar_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.get(&Property::CalendarIds)
+            .as_list()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_id().map(|id| id.document_id()))
+    }
+}
+
+fn strip_mailto(uri: &str) -> &str {
+    uri.strip_prefix("mailto:")
+        .or_else(|| uri.strip_prefix("MAILTO:"))
+        .unwrap_or(uri)
+}
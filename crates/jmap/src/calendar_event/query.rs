@@ -0,0 +1,230 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::query::{Comparator, Filter, QueryRequest, QueryResponse, SortProperty},
+    object::{calendar::QueryArguments, Object},
+    types::{acl::Acl, collection::Collection, id::Id, property::Property, value::Value},
+};
+use store::query::{self};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::recurrence::{time_zone_offset, ExpandRecurrence};
+
+impl JMAP {
+    pub async fn calendar_event_query(
+        &self,
+        mut request: QueryRequest<QueryArguments>,
+        access_token: &AccessToken,
+    ) -> Result<QueryResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut filters = Vec::with_capacity(request.filter.len());
+        let mut after = None;
+        let mut before = None;
+        let mut depth = 0;
+
+        for cond in std::mem::take(&mut request.filter) {
+            match cond {
+                Filter::InCalendars(calendar_ids) => {
+                    filters.push(query::Filter::Or);
+                    for calendar_id in calendar_ids {
+                        filters.push(query::Filter::eq(
+                            Property::CalendarIds,
+                            calendar_id.document_id(),
+                        ));
+                    }
+                    filters.push(query::Filter::End);
+                }
+                Filter::Uid(uid) => filters.push(query::Filter::eq(Property::Uid, uid)),
+                Filter::Title(title) => {
+                    filters.push(query::Filter::has_text(Property::Name, &title))
+                }
+                Filter::Description(description) => {
+                    filters.push(query::Filter::has_text(Property::Description, &description))
+                }
+                Filter::Text(text) => {
+                    filters.push(query::Filter::Or);
+                    for property in [
+                        Property::Name,
+                        Property::Description,
+                        Property::Location,
+                        Property::Email,
+                    ] {
+                        filters.push(query::Filter::has_text(property, &text));
+                    }
+                    filters.push(query::Filter::End);
+                }
+                // The indexed range is widened by one day to account for
+                // floating events, the exact match is performed below.
+                Filter::After(date) => {
+                    let date = date.timestamp();
+                    if depth == 0 {
+                        after = Some(date);
+                    }
+                    filters.push(query::Filter::gt(
+                        Property::UtcEnd,
+                        date.saturating_sub(86400).max(0) as u64,
+                    ));
+                }
+                Filter::Before(date) => {
+                    let date = date.timestamp();
+                    if depth == 0 {
+                        before = Some(date);
+                    }
+                    filters.push(query::Filter::lt(
+                        Property::UtcStart,
+                        date.saturating_add(86400).max(0) as u64,
+                    ));
+                }
+                Filter::And | Filter::Or | Filter::Not => {
+                    depth += 1;
+                    filters.push(cond.into());
+                }
+                Filter::Close => {
+                    depth -= 1;
+                    filters.push(cond.into());
+                }
+                other => return Err(MethodError::UnsupportedFilter(other.to_string())),
+            }
+        }
+
+        let expand_recurrences = request.arguments.expand_recurrences.unwrap_or(false);
+        if expand_recurrences {
+            match (after, before) {
+                (Some(after), Some(before))
+                    if before > after
+                        && (before - after) as u64
+                            <= self.core.jmap.calendar_max_expanded_duration.as_secs() => {}
+                _ => {
+                    return Err(MethodError::InvalidArguments(
+                        concat!(
+                            "Expanding recurrences requires a bounded 'after' and 'before' ",
+                            "filter within the maximum expanded duration."
+                        )
+                        .to_string(),
+                    ))
+                }
+            }
+        }
+
+        let mut result_set = self
+            .filter(account_id, Collection::CalendarEvent, filters)
+            .await?;
+        if access_token.is_shared(account_id) {
+            result_set.apply_mask(
+                self.shared_events(access_token, account_id, Acl::ReadItems)
+                    .await?,
+            );
+        }
+
+        // Match the time range against the actual occurrences of each event
+        let mut instances = Vec::new();
+        if after.is_some() || before.is_some() {
+            let from = after.unwrap_or(i64::MIN);
+            let to = before.unwrap_or(i64::MAX);
+            let floating_offset = request
+                .arguments
+                .time_zone
+                .as_deref()
+                .map_or(0, time_zone_offset);
+            for document_id in result_set.results.clone() {
+                let occurrences = self
+                    .get_property::<Object<Value>>(
+                        account_id,
+                        Collection::CalendarEvent,
+                        document_id,
+                        &Property::Value,
+                    )
+                    .await?
+                    .and_then(|event| {
+                        event.expand(
+                            from,
+                            to,
+                            floating_offset,
+                            self.core.jmap.calendar_max_instances,
+                        )
+                    })
+                    .map(|expansion| expansion.occurrences)
+                    .unwrap_or_default();
+                if occurrences.is_empty() {
+                    result_set.results.remove(document_id);
+                } else if expand_recurrences {
+                    instances.extend(occurrences.into_iter().map(|occurrence| {
+                        (
+                            occurrence.utc_start,
+                            Id::from_parts(occurrence.instance_prefix(), document_id),
+                        )
+                    }));
+                }
+            }
+        }
+
+        let (mut response, paginate) = self.build_query_response(&result_set, &request).await?;
+
+        if expand_recurrences {
+            // Expanded instances are not stored and are always sorted by start
+            let is_ascending = request
+                .sort
+                .as_ref()
+                .and_then(|sort| sort.first())
+                .is_none_or(|comparator| comparator.is_ascending);
+            instances.sort_by(|a, b| {
+                let order = a.0.cmp(&b.0).then(a.1.id().cmp(&b.1.id()));
+                if is_ascending {
+                    order
+                } else {
+                    order.reverse()
+                }
+            });
+
+            let total = instances.len();
+            let limit = std::cmp::min(
+                request.limit.unwrap_or(self.core.jmap.query_max_results),
+                self.core.jmap.query_max_results,
+            );
+            let position = match request.position.unwrap_or(0) {
+                position if position >= 0 => std::cmp::min(position as usize, total),
+                position => total.saturating_sub(position.unsigned_abs() as usize),
+            };
+            response.can_calculate_changes = false;
+            response.position = position as i32;
+            response.ids = instances
+                .into_iter()
+                .skip(position)
+                .take(limit)
+                .map(|(_, id)| id)
+                .collect();
+            response.total = request.calculate_total.unwrap_or(false).then_some(total);
+            response.limit = (total > limit).then_some(limit);
+            Ok(response)
+        } else if let Some(paginate) = paginate {
+            // Parse sort criteria
+            let mut comparators = Vec::with_capacity(request.sort.as_ref().map_or(1, |s| s.len()));
+            for comparator in request
+                .sort
+                .and_then(|s| if !s.is_empty() { s.into() } else { None })
+                .unwrap_or_else(|| vec![Comparator::ascending(SortProperty::_T("start".into()))])
+            {
+                comparators.push(match &comparator.property {
+                    SortProperty::_T(property) if property == "start" => {
+                        query::Comparator::field(Property::UtcStart, comparator.is_ascending)
+                    }
+                    SortProperty::_T(property) if property == "uid" => {
+                        query::Comparator::field(Property::Uid, comparator.is_ascending)
+                    }
+                    other => return Err(MethodError::UnsupportedSort(other.to_string())),
+                });
+            }
+
+            // Sort results
+            self.sort(result_set, comparators, paginate, response).await
+        } else {
+            Ok(response)
+        }
+    }
+}
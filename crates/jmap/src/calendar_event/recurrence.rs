@@ -0,0 +1,914 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::fmt::Write;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use jmap_proto::{object::Object, types::value::Value};
+
+use super::EventFields;
+
+// Recurrence ids are encoded in the prefix of instance ids as the number of
+// minutes elapsed since 0001-01-01T00:00:00, which leaves the document id of
+// the master event in the lower 32 bits.
+const INSTANCE_EPOCH: i64 = -62_135_596_800;
+
+// Upper bound on the number of periods to walk when a rule yields no
+// occurrences (e.g. the 30th of February), to guarantee termination.
+const MAX_EMPTY_PERIODS: usize = 1000;
+
+// Upper bound on the number of periods to walk before the expansion window
+// for rules limited by a count, which cannot skip periods.
+const MAX_COUNTED_PERIODS: i64 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Frequency {
+    Yearly,
+    Monthly,
+    Weekly,
+    Daily,
+    Hourly,
+    Minutely,
+    Secondly,
+}
+
+#[derive(Debug, Clone)]
+struct RecurrenceRule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<NaiveDateTime>,
+    by_day: Vec<(Weekday, Option<i32>)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+    by_year_day: Vec<i32>,
+    by_hour: Vec<u32>,
+    by_minute: Vec<u32>,
+    by_second: Vec<u32>,
+    by_set_position: Vec<i32>,
+    first_day_of_week: Weekday,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub recurrence_id: Option<NaiveDateTime>,
+    pub start: NaiveDateTime,
+    pub utc_start: i64,
+    pub utc_end: i64,
+}
+
+pub struct Expansion {
+    pub occurrences: Vec<Occurrence>,
+    pub is_truncated: bool,
+}
+
+impl Occurrence {
+    pub fn instance_prefix(&self) -> u32 {
+        self.recurrence_id.map_or(0, |recurrence_id| {
+            ((recurrence_id.and_utc().timestamp() - INSTANCE_EPOCH) / 60 + 1) as u32
+        })
+    }
+}
+
+pub fn recurrence_id_from_prefix(prefix: u32) -> Option<NaiveDateTime> {
+    if prefix > 0 {
+        chrono::DateTime::from_timestamp((prefix as i64 - 1) * 60 + INSTANCE_EPOCH, 0)
+            .map(|dt| dt.naive_utc())
+    } else {
+        None
+    }
+}
+
+pub trait ExpandRecurrence {
+    fn is_recurring(&self) -> bool;
+    fn expand(
+        &self,
+        from: i64,
+        to: i64,
+        floating_offset: i64,
+        max_instances: usize,
+    ) -> Option<Expansion>;
+    fn instance(
+        &self,
+        recurrence_id: NaiveDateTime,
+        floating_offset: i64,
+        max_instances: usize,
+    ) -> Option<Occurrence>;
+    fn utc_range(&self, max_instances: usize) -> Option<(u64, u64)>;
+}
+
+struct EventTiming<'x> {
+    start: NaiveDateTime,
+    offset: i64,
+    duration: i64,
+    overrides: Option<&'x Object<Value>>,
+}
+
+impl ExpandRecurrence for Object<Value> {
+    fn is_recurring(&self) -> bool {
+        self.event_list("recurrenceRules")
+            .is_some_and(|rules| !rules.is_empty())
+            || self
+                .event_obj("recurrenceOverrides")
+                .is_some_and(|overrides| !overrides.properties.is_empty())
+    }
+
+    // Returns all occurrences of the event overlapping the [from, to) UTC
+    // interval, sorted by start time.
+    fn expand(
+        &self,
+        from: i64,
+        to: i64,
+        floating_offset: i64,
+        max_instances: usize,
+    ) -> Option<Expansion> {
+        let timing = self.timing(floating_offset)?;
+        if !self.is_recurring() {
+            let utc_start = utc_from_local(timing.start, timing.offset);
+            let utc_end = utc_start + timing.duration;
+            return Some(Expansion {
+                occurrences: if overlaps(utc_start, utc_end, from, to) {
+                    vec![Occurrence {
+                        recurrence_id: None,
+                        start: timing.start,
+                        utc_start,
+                        utc_end,
+                    }]
+                } else {
+                    vec![]
+                },
+                is_truncated: false,
+            });
+        }
+
+        // Occurrences starting before the window can still overlap it
+        let local_start = local_from_utc(from, timing.offset);
+        let local_start = Duration::try_seconds(timing.duration)
+            .and_then(|duration| local_start.checked_sub_signed(duration))
+            .unwrap_or(local_start);
+        let (recurrence_ids, is_truncated) = self.recurrence_ids(
+            &timing,
+            local_start,
+            local_from_utc(to, timing.offset),
+            max_instances,
+        );
+        let mut occurrences = recurrence_ids
+            .into_iter()
+            .map(|recurrence_id| timing.occurrence(recurrence_id))
+            .filter(|o| overlaps(o.utc_start, o.utc_end, from, to))
+            .collect::<Vec<_>>();
+        occurrences.sort_unstable_by_key(|o| o.utc_start);
+
+        Some(Expansion {
+            occurrences,
+            is_truncated,
+        })
+    }
+
+    // Returns the occurrence with the given recurrence id, if it is part of
+    // the recurrence set of the event.
+    fn instance(
+        &self,
+        recurrence_id: NaiveDateTime,
+        floating_offset: i64,
+        max_instances: usize,
+    ) -> Option<Occurrence> {
+        let timing = self.timing(floating_offset)?;
+        if self.is_recurring()
+            && self
+                .recurrence_ids(&timing, recurrence_id, recurrence_id, max_instances)
+                .0
+                .contains(&recurrence_id)
+        {
+            Some(timing.occurrence(recurrence_id))
+        } else {
+            None
+        }
+    }
+
+    // Returns the first start and last end time of the event in UTC, used for
+    // indexing. Events recurring forever end at u64::MAX.
+    fn utc_range(&self, max_instances: usize) -> Option<(u64, u64)> {
+        let is_infinite = self.event_list("recurrenceRules").is_some_and(|rules| {
+            rules
+                .iter()
+                .filter_map(RecurrenceRule::parse)
+                .any(|rule| rule.count.is_none() && rule.until.is_none())
+        });
+        let expansion = self.expand(i64::MIN / 2, i64::MAX / 2, 0, max_instances)?;
+        let utc_start = expansion.occurrences.iter().map(|o| o.utc_start).min()?;
+        let utc_end = if is_infinite || expansion.is_truncated {
+            u64::MAX
+        } else {
+            expansion
+                .occurrences
+                .iter()
+                .map(|o| o.utc_end.max(0) as u64)
+                .max()
+                .unwrap_or_default()
+        };
+
+        Some((utc_start.max(0) as u64, utc_end))
+    }
+}
+
+trait RecurrenceSet {
+    fn timing(&self, floating_offset: i64) -> Option<EventTiming<'_>>;
+    fn recurrence_ids(
+        &self,
+        timing: &EventTiming<'_>,
+        local_start: NaiveDateTime,
+        local_end: NaiveDateTime,
+        max_instances: usize,
+    ) -> (Vec<NaiveDateTime>, bool);
+}
+
+impl RecurrenceSet for Object<Value> {
+    fn timing(&self, floating_offset: i64) -> Option<EventTiming<'_>> {
+        Some(EventTiming {
+            start: parse_local_date_time(self.event_str("start")?)?,
+            offset: self
+                .event_str("timeZone")
+                .map_or(floating_offset, time_zone_offset),
+            duration: self
+                .event_str("duration")
+                .and_then(parse_duration)
+                .unwrap_or(0),
+            overrides: self.event_obj("recurrenceOverrides"),
+        })
+    }
+
+    // Expands the recurrence rules within [local_start, local_end], then
+    // applies the exclusion rules and overrides.
+    fn recurrence_ids(
+        &self,
+        timing: &EventTiming<'_>,
+        local_start: NaiveDateTime,
+        local_end: NaiveDateTime,
+        max_instances: usize,
+    ) -> (Vec<NaiveDateTime>, bool) {
+        let mut recurrence_ids = Vec::new();
+        let mut is_truncated = false;
+
+        if let Some(rules) = self
+            .event_list("recurrenceRules")
+            .filter(|rules| !rules.is_empty())
+        {
+            for rule in rules.iter().filter_map(RecurrenceRule::parse) {
+                let (ids, truncated) =
+                    rule.expand(timing.start, local_start, local_end, max_instances);
+                is_truncated |= truncated;
+                recurrence_ids.extend(ids);
+            }
+            if let Some(rules) = self.event_list("excludedRecurrenceRules") {
+                for rule in rules.iter().filter_map(RecurrenceRule::parse) {
+                    let (excluded, _) =
+                        rule.expand(timing.start, local_start, local_end, max_instances);
+                    recurrence_ids.retain(|id| !excluded.contains(id));
+                }
+            }
+        } else {
+            recurrence_ids.push(timing.start);
+        }
+
+        if let Some(overrides) = timing.overrides {
+            for (key, patch) in overrides.properties.iter() {
+                if let Some(recurrence_id) = parse_local_date_time(&key.to_string()) {
+                    if patch.as_obj().is_some_and(is_excluded) {
+                        recurrence_ids.retain(|id| id != &recurrence_id);
+                    } else if !recurrence_ids.contains(&recurrence_id) {
+                        recurrence_ids.push(recurrence_id);
+                    }
+                }
+            }
+        }
+        recurrence_ids.sort_unstable();
+        recurrence_ids.dedup();
+
+        (recurrence_ids, is_truncated)
+    }
+}
+
+impl EventTiming<'_> {
+    fn occurrence(&self, recurrence_id: NaiveDateTime) -> Occurrence {
+        let patch = self
+            .overrides
+            .and_then(|overrides| recurrence_override(overrides, &recurrence_id));
+        let start = patch
+            .and_then(|patch| patch.event_str("start"))
+            .and_then(parse_local_date_time)
+            .unwrap_or(recurrence_id);
+        let duration = patch
+            .and_then(|patch| patch.event_str("duration"))
+            .and_then(parse_duration)
+            .unwrap_or(self.duration);
+        let utc_start = utc_from_local(start, self.offset);
+
+        Occurrence {
+            recurrence_id: Some(recurrence_id),
+            start,
+            utc_start,
+            utc_end: utc_start + duration,
+        }
+    }
+}
+
+// Returns whether all the rules in the list can be expanded.
+pub fn is_valid_recurrence_rules(rules: &[Value]) -> bool {
+    rules
+        .iter()
+        .all(|rule| RecurrenceRule::parse(rule).is_some())
+}
+
+pub fn recurrence_override<'x>(
+    overrides: &'x Object<Value>,
+    recurrence_id: &NaiveDateTime,
+) -> Option<&'x Object<Value>> {
+    overrides
+        .properties
+        .iter()
+        .find(|(key, _)| parse_local_date_time(&key.to_string()).as_ref() == Some(recurrence_id))
+        .and_then(|(_, patch)| patch.as_obj())
+}
+
+impl RecurrenceRule {
+    fn parse(value: &Value) -> Option<Self> {
+        let rule = value.as_obj()?;
+        let frequency = match rule.event_str("frequency")? {
+            "yearly" => Frequency::Yearly,
+            "monthly" => Frequency::Monthly,
+            "weekly" => Frequency::Weekly,
+            "daily" => Frequency::Daily,
+            "hourly" => Frequency::Hourly,
+            "minutely" => Frequency::Minutely,
+            "secondly" => Frequency::Secondly,
+            _ => return None,
+        };
+
+        Some(RecurrenceRule {
+            frequency,
+            interval: match rule.event_uint("interval") {
+                Some(interval) => u32::try_from(interval).ok().filter(|i| *i > 0)?,
+                None => 1,
+            },
+            count: rule.event_uint("count").map(|c| c as usize),
+            until: rule.event_str("until").and_then(parse_local_date_time),
+            by_day: rule
+                .event_list("byDay")
+                .into_iter()
+                .flatten()
+                .filter_map(|day| {
+                    let day = day.as_obj()?;
+                    Some((
+                        parse_weekday(day.event_str("day")?)?,
+                        day.event_int("nthOfPeriod").map(|n| n as i32),
+                    ))
+                })
+                .collect(),
+            by_month_day: rule.event_int_list("byMonthDay"),
+            by_month: rule
+                .event_list("byMonth")
+                .into_iter()
+                .flatten()
+                .filter_map(|month| {
+                    month
+                        .as_string()?
+                        .trim_end_matches('L')
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|m| (1..=12).contains(m))
+                })
+                .collect(),
+            by_year_day: rule.event_int_list("byYearDay"),
+            by_hour: rule.event_uint_list("byHour"),
+            by_minute: rule.event_uint_list("byMinute"),
+            by_second: rule.event_uint_list("bySecond"),
+            by_set_position: rule.event_int_list("bySetPosition"),
+            first_day_of_week: rule
+                .event_str("firstDayOfWeek")
+                .and_then(parse_weekday)
+                .unwrap_or(Weekday::Mon),
+        })
+    }
+
+    // Expands the occurrences of the rule within [from, end] (local time),
+    // the start date-time is always the first occurrence. Rules without a
+    // count skip the periods preceding `from`, and `max_instances` only
+    // limits the occurrences within the window.
+    fn expand(
+        &self,
+        start: NaiveDateTime,
+        from: NaiveDateTime,
+        end: NaiveDateTime,
+        max_instances: usize,
+    ) -> (Vec<NaiveDateTime>, bool) {
+        let mut occurrences = if start >= from { vec![start] } else { vec![] };
+        let mut total = 1;
+        let mut empty_periods = 0;
+        let mut period = if self.count.is_none() {
+            self.periods_until(start, from)
+        } else {
+            0
+        };
+
+        loop {
+            let (period_start, mut candidates) = match self.period(start, period) {
+                Some(period) => period,
+                None => return (occurrences, false),
+            };
+            if period_start > end {
+                return (occurrences, false);
+            } else if self.count.is_some() && period_start < from && period > MAX_COUNTED_PERIODS {
+                return (occurrences, true);
+            }
+            candidates.sort_unstable();
+            candidates.dedup();
+            if !self.by_set_position.is_empty() {
+                candidates = self
+                    .by_set_position
+                    .iter()
+                    .filter_map(|pos| nth(&candidates, *pos).copied())
+                    .collect();
+                candidates.sort_unstable();
+                candidates.dedup();
+            }
+
+            if candidates.is_empty() {
+                empty_periods += 1;
+                if empty_periods > MAX_EMPTY_PERIODS {
+                    return (occurrences, false);
+                }
+            } else {
+                empty_periods = 0;
+            }
+
+            for candidate in candidates {
+                if candidate <= start {
+                    continue;
+                } else if candidate > end
+                    || self.until.is_some_and(|until| candidate > until)
+                    || self.count.is_some_and(|count| total >= count)
+                {
+                    return (occurrences, false);
+                }
+                total += 1;
+                if candidate < from {
+                    continue;
+                } else if occurrences.len() >= max_instances {
+                    return (occurrences, true);
+                }
+                occurrences.push(candidate);
+            }
+
+            period = match period.checked_add(self.interval as i64) {
+                Some(period) => period,
+                None => return (occurrences, false),
+            };
+        }
+    }
+
+    // Returns the index of the last period, as a multiple of the interval,
+    // starting at or before `from`.
+    fn periods_until(&self, start: NaiveDateTime, from: NaiveDateTime) -> i64 {
+        if from <= start {
+            return 0;
+        }
+        let elapsed = from - start;
+        let periods = match self.frequency {
+            Frequency::Yearly => (from.year() - start.year()) as i64,
+            Frequency::Monthly => {
+                (from.year() - start.year()) as i64 * 12 + from.month0() as i64
+                    - start.month0() as i64
+            }
+            Frequency::Weekly => elapsed.num_weeks(),
+            Frequency::Daily => elapsed.num_days(),
+            Frequency::Hourly => elapsed.num_hours(),
+            Frequency::Minutely => elapsed.num_minutes(),
+            Frequency::Secondly => elapsed.num_seconds(),
+        };
+
+        periods - periods.rem_euclid(self.interval as i64)
+    }
+
+    // Returns the start of the nth period along with the candidate
+    // occurrences within that period.
+    fn period(
+        &self,
+        start: NaiveDateTime,
+        period: i64,
+    ) -> Option<(NaiveDateTime, Vec<NaiveDateTime>)> {
+        let mut dates = Vec::new();
+        let period_start;
+
+        match self.frequency {
+            Frequency::Yearly => {
+                let year = start.year() + i32::try_from(period).ok()?;
+                if year > 9999 {
+                    return None;
+                }
+                period_start = NaiveDate::from_ymd_opt(year, 1, 1)?.and_time(NaiveTime::MIN);
+                if !self.by_year_day.is_empty() {
+                    let days = days_in_year(year);
+                    for day in &self.by_year_day {
+                        if let Some(date) = resolve_index(*day, days)
+                            .and_then(|day| NaiveDate::from_yo_opt(year, day as u32))
+                        {
+                            dates.push(date);
+                        }
+                    }
+                } else if self.by_month.is_empty()
+                    && self.by_month_day.is_empty()
+                    && !self.by_day.is_empty()
+                {
+                    dates = self.weekdays_in_range(
+                        NaiveDate::from_ymd_opt(year, 1, 1)?,
+                        NaiveDate::from_ymd_opt(year, 12, 31)?,
+                    );
+                } else {
+                    let months = if !self.by_month.is_empty() {
+                        self.by_month.clone()
+                    } else if !self.by_month_day.is_empty() {
+                        (1..=12).collect()
+                    } else {
+                        vec![start.month()]
+                    };
+                    for month in months {
+                        dates.extend(self.days_in_month(year, month, start.day()));
+                    }
+                }
+            }
+            Frequency::Monthly => {
+                let month = start.year() as i64 * 12 + start.month0() as i64 + period;
+                let (year, month) = ((month / 12) as i32, (month % 12) as u32 + 1);
+                if year > 9999 {
+                    return None;
+                }
+                period_start = NaiveDate::from_ymd_opt(year, month, 1)?.and_time(NaiveTime::MIN);
+                if self.by_month.is_empty() || self.by_month.contains(&month) {
+                    dates = self.days_in_month(year, month, start.day());
+                }
+            }
+            Frequency::Weekly => {
+                let week_start = start
+                    .date()
+                    .checked_sub_signed(Duration::days(
+                        (start.weekday().num_days_from_monday() as i64
+                            - self.first_day_of_week.num_days_from_monday() as i64)
+                            .rem_euclid(7),
+                    ))?
+                    .checked_add_signed(Duration::try_weeks(period)?)?;
+                if week_start.year() > 9999 {
+                    return None;
+                }
+                period_start = week_start.and_time(NaiveTime::MIN);
+                for date in week_start.iter_days().take(7) {
+                    if (if self.by_day.is_empty() {
+                        date.weekday() == start.weekday()
+                    } else {
+                        self.by_day.iter().any(|(day, _)| *day == date.weekday())
+                    }) && (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+                    {
+                        dates.push(date);
+                    }
+                }
+            }
+            Frequency::Daily => {
+                let date = start
+                    .date()
+                    .checked_add_signed(Duration::try_days(period)?)?;
+                if date.year() > 9999 {
+                    return None;
+                }
+                period_start = date.and_time(NaiveTime::MIN);
+                if self.matches_date(date) {
+                    dates.push(date);
+                }
+            }
+            Frequency::Hourly | Frequency::Minutely | Frequency::Secondly => {
+                let step = match self.frequency {
+                    Frequency::Hourly => Duration::try_hours(period),
+                    Frequency::Minutely => Duration::try_minutes(period),
+                    _ => Duration::try_seconds(period),
+                };
+                let date_time = start.checked_add_signed(step?)?;
+                if date_time.year() > 9999 {
+                    return None;
+                }
+                period_start = date_time;
+                let mut candidates = Vec::new();
+                if self.matches_date(date_time.date())
+                    && (self.by_hour.is_empty() || self.by_hour.contains(&date_time.hour()))
+                {
+                    let minutes = if self.frequency == Frequency::Hourly {
+                        expand_or(&self.by_minute, date_time.minute())
+                    } else if self.by_minute.is_empty()
+                        || self.by_minute.contains(&date_time.minute())
+                    {
+                        vec![date_time.minute()]
+                    } else {
+                        vec![]
+                    };
+                    let seconds = if self.frequency != Frequency::Secondly {
+                        expand_or(&self.by_second, date_time.second())
+                    } else if self.by_second.is_empty()
+                        || self.by_second.contains(&date_time.second())
+                    {
+                        vec![date_time.second()]
+                    } else {
+                        vec![]
+                    };
+                    for minute in &minutes {
+                        for second in &seconds {
+                            if let Some(time) =
+                                NaiveTime::from_hms_opt(date_time.hour(), *minute, *second)
+                            {
+                                candidates.push(date_time.date().and_time(time));
+                            }
+                        }
+                    }
+                }
+                return Some((period_start, candidates));
+            }
+        }
+
+        // Expand times
+        let hours = expand_or(&self.by_hour, start.hour());
+        let minutes = expand_or(&self.by_minute, start.minute());
+        let seconds = expand_or(&self.by_second, start.second());
+        let mut candidates = Vec::with_capacity(dates.len());
+        for date in dates {
+            for hour in &hours {
+                for minute in &minutes {
+                    for second in &seconds {
+                        if let Some(time) = NaiveTime::from_hms_opt(*hour, *minute, *second) {
+                            candidates.push(date.and_time(time));
+                        }
+                    }
+                }
+            }
+        }
+
+        Some((period_start, candidates))
+    }
+
+    fn days_in_month(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        let num_days = days_in_month(year, month);
+        if !self.by_month_day.is_empty() {
+            for day in &self.by_month_day {
+                if let Some(date) = resolve_index(*day, num_days)
+                    .and_then(|day| NaiveDate::from_ymd_opt(year, month, day as u32))
+                {
+                    if self.by_day.is_empty()
+                        || self.by_day.iter().any(|(day, _)| *day == date.weekday())
+                    {
+                        dates.push(date);
+                    }
+                }
+            }
+        } else if !self.by_day.is_empty() {
+            if let (Some(first), Some(last)) = (
+                NaiveDate::from_ymd_opt(year, month, 1),
+                NaiveDate::from_ymd_opt(year, month, num_days as u32),
+            ) {
+                dates = self.weekdays_in_range(first, last);
+            }
+        } else if let Some(date) = NaiveDate::from_ymd_opt(year, month, default_day) {
+            dates.push(date);
+        }
+        dates
+    }
+
+    fn weekdays_in_range(&self, first: NaiveDate, last: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        for (weekday, nth_of_period) in &self.by_day {
+            let matching = first
+                .iter_days()
+                .take_while(|date| date <= &last)
+                .filter(|date| date.weekday() == *weekday)
+                .collect::<Vec<_>>();
+            if let Some(n) = nth_of_period {
+                if let Some(date) = nth(&matching, *n) {
+                    dates.push(*date);
+                }
+            } else {
+                dates.extend(matching);
+            }
+        }
+        dates
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+            && (self.by_month_day.is_empty()
+                || self.by_month_day.iter().any(|day| {
+                    resolve_index(*day, days_in_month(date.year(), date.month()))
+                        == Some(date.day() as i64)
+                }))
+            && (self.by_day.is_empty() || self.by_day.iter().any(|(day, _)| *day == date.weekday()))
+    }
+}
+
+fn is_excluded(patch: &Object<Value>) -> bool {
+    matches!(patch.event_value("excluded"), Value::Bool(true))
+}
+
+fn overlaps(start: i64, end: i64, from: i64, to: i64) -> bool {
+    start < to && (end > from || (start == end && start >= from))
+}
+
+fn expand_or(values: &[u32], default: u32) -> Vec<u32> {
+    if !values.is_empty() {
+        values.to_vec()
+    } else {
+        vec![default]
+    }
+}
+
+fn nth<T>(items: &[T], pos: i32) -> Option<&T> {
+    match pos {
+        1.. => items.get(pos as usize - 1),
+        ..=-1 => items
+            .len()
+            .checked_sub(pos.unsigned_abs() as usize)
+            .and_then(|idx| items.get(idx)),
+        0 => None,
+    }
+}
+
+fn resolve_index(index: i32, len: i64) -> Option<i64> {
+    let index = if index < 0 {
+        len + index as i64 + 1
+    } else {
+        index as i64
+    };
+    if (1..=len).contains(&index) {
+        Some(index)
+    } else {
+        None
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> i64 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .zip(NaiveDate::from_ymd_opt(year, month, 1))
+        .map_or(30, |(next, first)| (next - first).num_days())
+}
+
+fn days_in_year(year: i32) -> i64 {
+    if NaiveDate::from_ymd_opt(year, 2, 29).is_some() {
+        366
+    } else {
+        365
+    }
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    match day {
+        "mo" => Some(Weekday::Mon),
+        "tu" => Some(Weekday::Tue),
+        "we" => Some(Weekday::Wed),
+        "th" => Some(Weekday::Thu),
+        "fr" => Some(Weekday::Fri),
+        "sa" => Some(Weekday::Sat),
+        "su" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+pub fn parse_local_date_time(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+}
+
+pub fn format_local_date_time(value: &NaiveDateTime) -> String {
+    value.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+// Parses a JSCalendar duration (RFC 8984, section 1.4.6) into seconds.
+pub fn parse_duration(value: &str) -> Option<i64> {
+    let (sign, value) = if let Some(value) = value.strip_prefix('-') {
+        (-1, value)
+    } else {
+        (1, value.strip_prefix('+').unwrap_or(value))
+    };
+    let value = value.strip_prefix('P')?;
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    let mut in_time = false;
+
+    for ch in value.chars() {
+        match ch {
+            '0'..='9' | '.' => number.push(ch),
+            'T' => in_time = true,
+            _ => {
+                let n = number.parse::<f64>().ok()? as i64;
+                number.clear();
+                seconds += n * match (ch, in_time) {
+                    ('W', false) => 7 * 86400,
+                    ('D', false) => 86400,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+
+    if number.is_empty() {
+        Some(sign * seconds)
+    } else {
+        None
+    }
+}
+
+// Formats a number of seconds as a JSCalendar duration.
+pub fn format_duration(seconds: i64) -> String {
+    let mut duration = String::with_capacity(16);
+    if seconds < 0 {
+        duration.push('-');
+    }
+    duration.push('P');
+    let seconds = seconds.unsigned_abs();
+    let (days, hours, minutes, seconds) = (
+        seconds / 86400,
+        (seconds % 86400) / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60,
+    );
+    if days > 0 {
+        let _ = write!(duration, "{days}D");
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+        duration.push('T');
+        if hours > 0 {
+            let _ = write!(duration, "{hours}H");
+        }
+        if minutes > 0 {
+            let _ = write!(duration, "{minutes}M");
+        }
+        if seconds > 0 || (days == 0 && hours == 0 && minutes == 0) {
+            let _ = write!(duration, "{seconds}S");
+        }
+    }
+    duration
+}
+
+// Returns the offset from UTC in seconds of a time zone. Only UTC, the
+// fixed "Etc/GMT" zones and numeric offsets are supported; any other time
+// zone is treated as UTC.
+pub fn time_zone_offset(tz: &str) -> i64 {
+    if let Some(offset) = tz
+        .strip_prefix("Etc/GMT")
+        .or_else(|| tz.strip_prefix("GMT"))
+        .or_else(|| tz.strip_prefix("UTC"))
+        .filter(|offset| !offset.is_empty())
+    {
+        // POSIX style zones have their sign inverted
+        let hours = offset.parse::<i64>().unwrap_or(0);
+        if tz.starts_with("Etc/") {
+            -hours * 3600
+        } else {
+            hours * 3600
+        }
+    } else if let Some((sign, offset)) = tz
+        .strip_prefix('+')
+        .map(|offset| (1, offset))
+        .or_else(|| tz.strip_prefix('-').map(|offset| (-1, offset)))
+    {
+        let offset = offset.replace(':', "");
+        let (hours, minutes) = offset.split_at(offset.len().min(2));
+        sign * (hours.parse::<i64>().unwrap_or(0) * 3600 + minutes.parse::<i64>().unwrap_or(0) * 60)
+    } else {
+        0
+    }
+}
+
+pub fn utc_from_local(local: NaiveDateTime, offset: i64) -> i64 {
+    local.and_utc().timestamp() - offset
+}
+
+fn local_from_utc(utc: i64, offset: i64) -> NaiveDateTime {
+    chrono::DateTime::from_timestamp(utc.saturating_add(offset), 0)
+        .map(|dt| dt.naive_utc())
+        .unwrap_or(if utc > 0 {
+            NaiveDateTime::MAX
+        } else {
+            NaiveDateTime::MIN
+        })
+}
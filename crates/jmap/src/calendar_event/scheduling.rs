@@ -0,0 +1,290 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::sync::Arc;
+
+use common::listener::{stream::NullIo, ServerInstance};
+use directory::QueryBy;
+use jmap_proto::{error::method::MethodError, object::Object, types::value::Value};
+use mail_builder::{
+    headers::{address::Address, content_type::ContentType},
+    mime::{make_boundary, BodyPart, MimePart},
+    MessageBuilder,
+};
+use smtp::core::{Session, SessionData, State};
+use smtp_proto::{MailFrom, RcptTo};
+
+use crate::{dav::ical::event_to_icalendar, JMAP};
+
+use super::EventFields;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItipMethod {
+    Request,
+    Cancel,
+    Reply,
+}
+
+impl JMAP {
+    // Sends iMIP (RFC 6047) messages to the participants of an event after it
+    // was created, updated or destroyed by either its organizer or one of its
+    // attendees. Delivery failures are logged and do not fail the request.
+    pub async fn calendar_event_schedule(
+        &self,
+        account_id: u32,
+        instance: &Arc<ServerInstance>,
+        method: ItipMethod,
+        event: &Object<Value>,
+        previous: Option<&Object<Value>>,
+    ) -> Result<(), MethodError> {
+        let organizer = if let Some(organizer) = event.event_organizer() {
+            organizer
+        } else {
+            return Ok(());
+        };
+        let emails = self.account_emails(account_id).await?;
+        let is_own = |email: &str| emails.iter().any(|own| own.eq_ignore_ascii_case(email));
+        let participants = event.event_participants();
+        let attendees = |participants: &[super::Participant<'_>]| {
+            participants
+                .iter()
+                .filter(|p| p.is_attendee)
+                .filter_map(|p| p.email)
+                .filter(|email| !is_own(email))
+                .map(|email| email.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut messages = Vec::new();
+        if is_own(organizer) {
+            messages.push((method, organizer, None, attendees(&participants)));
+
+            // Notify attendees removed from the event
+            if let (ItipMethod::Request, Some(previous)) = (method, previous) {
+                let current = attendees(&participants);
+                let removed = attendees(&previous.event_participants())
+                    .into_iter()
+                    .filter(|email| !current.contains(email))
+                    .collect::<Vec<_>>();
+                messages.push((ItipMethod::Cancel, organizer, None, removed));
+            }
+        } else if let (ItipMethod::Request, Some(previous)) = (method, previous) {
+            // Attendees reply to the organizer when their participation status changes
+            let previous_participants = previous.event_participants();
+            for participant in &participants {
+                if let Some(email) = participant.email.filter(|email| is_own(email)) {
+                    let previous_status = previous_participants
+                        .iter()
+                        .find(|p| p.email == Some(email))
+                        .and_then(|p| p.participation_status);
+                    if participant.participation_status.is_some()
+                        && participant.participation_status != previous_status
+                    {
+                        messages.push((
+                            ItipMethod::Reply,
+                            email,
+                            Some(email),
+                            vec![organizer.to_string()],
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (method, sender, reply_from, recipients) in messages {
+            if recipients.is_empty() {
+                continue;
+            }
+            let message =
+                build_itip_message(event, method, organizer, sender, reply_from, &recipients);
+            self.send_itip_message(account_id, instance, sender, recipients, message)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    pub async fn is_event_organizer(
+        &self,
+        account_id: u32,
+        event: &Object<Value>,
+    ) -> Result<bool, MethodError> {
+        if let Some(organizer) = event.event_organizer() {
+            Ok(self
+                .account_emails(account_id)
+                .await?
+                .iter()
+                .any(|email| email.eq_ignore_ascii_case(organizer)))
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub(crate) async fn account_emails(&self, account_id: u32) -> Result<Vec<String>, MethodError> {
+        Ok(self
+            .core
+            .storage
+            .directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "calendar_event_schedule",
+                    error = ?err,
+                    "Failed to query directory.");
+                MethodError::ServerPartialFail
+            })?
+            .map(|principal| principal.emails)
+            .unwrap_or_default())
+    }
+
+    async fn send_itip_message(
+        &self,
+        account_id: u32,
+        instance: &Arc<ServerInstance>,
+        sender: &str,
+        recipients: Vec<String>,
+        message: Vec<u8>,
+    ) {
+        // Begin local SMTP session
+        let mut session =
+            Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
+
+        // MAIL FROM
+        let _ = session
+            .handle_mail_from(MailFrom {
+                address: sender.to_string(),
+                ..Default::default()
+            })
+            .await;
+        if let Some(error) = session.has_failed() {
+            tracing::warn!(
+                context = "calendar_event_schedule",
+                event = "error",
+                account_id = account_id,
+                from = sender,
+                reason = error.trim(),
+                "Server rejected MAIL-FROM for scheduling message."
+            );
+            return;
+        }
+
+        // RCPT TO
+        let mut has_success = false;
+        for address in recipients {
+            let _ = session
+                .handle_rcpt_to(RcptTo {
+                    address,
+                    ..Default::default()
+                })
+                .await;
+            if let Some(error) = session.has_failed() {
+                tracing::debug!(
+                    context = "calendar_event_schedule",
+                    event = "error",
+                    account_id = account_id,
+                    reason = error.trim(),
+                    "Server rejected RCPT-TO for scheduling message."
+                );
+            } else {
+                has_success = true;
+            }
+        }
+
+        // DATA
+        if has_success {
+            session.data.message = message;
+            let response = session.queue_message().await;
+            if !matches!(session.state, State::Accepted(_)) {
+                tracing::warn!(
+                    context = "calendar_event_schedule",
+                    event = "error",
+                    account_id = account_id,
+                    reason = std::str::from_utf8(&response).unwrap_or_default().trim(),
+                    "Server rejected DATA for scheduling message."
+                );
+            }
+        }
+    }
+}
+
+fn build_itip_message(
+    event: &Object<Value>,
+    method: ItipMethod,
+    organizer: &str,
+    sender: &str,
+    reply_from: Option<&str>,
+    recipients: &[String],
+) -> Vec<u8> {
+    let title = event.event_str("title").unwrap_or("(no title)");
+    let (subject, text) = match method {
+        ItipMethod::Request => (
+            format!("Invitation: {title}"),
+            format!("You have been invited to \"{title}\" by {organizer}."),
+        ),
+        ItipMethod::Cancel => (
+            format!("Cancelled: {title}"),
+            format!("The event \"{title}\" has been cancelled by {organizer}."),
+        ),
+        ItipMethod::Reply => {
+            let status = event
+                .event_participants()
+                .into_iter()
+                .find(|p| p.email == reply_from)
+                .and_then(|p| p.participation_status)
+                .unwrap_or("needs-action");
+            (
+                format!("{title}: {status}"),
+                format!("{sender} has replied \"{status}\" to \"{title}\"."),
+            )
+        }
+    };
+
+    MessageBuilder::new()
+        .from(sender)
+        .to(Address::new_list(
+            recipients
+                .iter()
+                .map(|email| Address::from(email.as_str()))
+                .collect(),
+        ))
+        .message_id(format!("<{}@{}>", make_boundary("."), domain_part(sender)))
+        .subject(subject)
+        .body(MimePart::new(
+            ContentType::new("multipart/alternative"),
+            BodyPart::Multipart(vec![
+                MimePart::new(
+                    ContentType::new("text/plain").attribute("charset", "utf-8"),
+                    BodyPart::Text(text.into()),
+                ),
+                MimePart::new(
+                    ContentType::new("text/calendar")
+                        .attribute("method", method.as_str())
+                        .attribute("charset", "utf-8"),
+                    BodyPart::Text(event_to_icalendar(event, Some(method), reply_from).into()),
+                ),
+            ]),
+        ))
+        .write_to_vec()
+        .unwrap_or_default()
+}
+
+fn domain_part(email: &str) -> &str {
+    email
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain)
+}
+
+impl ItipMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItipMethod::Request => "REQUEST",
+            ItipMethod::Cancel => "CANCEL",
+            ItipMethod::Reply => "REPLY",
+        }
+    }
+}
@@ -0,0 +1,846 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::sync::Arc;
+
+use common::listener::ServerInstance;
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{SetRequest, SetResponse},
+    object::{
+        calendar::EventSetArguments,
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        id::Id,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder, DirectoryClass},
+    Serialize,
+};
+
+use crate::{
+    auth::AccessToken,
+    calendar::DEFAULT_CALENDAR_ID,
+    contact::set::{generate_uid, ContactCardObject},
+    JMAP,
+};
+
+use super::{
+    recurrence::{
+        format_local_date_time, is_valid_recurrence_rules, parse_duration, parse_local_date_time,
+        recurrence_id_from_prefix, ExpandRecurrence,
+    },
+    scheduling::ItipMethod,
+    EventFields, EventTextIndex,
+};
+
+pub struct SetContext<'x> {
+    pub response: &'x dyn EvalObjectReferences,
+    pub calendar_ids: &'x RoaringBitmap,
+    pub can_add_calendar_ids: Option<&'x RoaringBitmap>,
+    pub can_remove_calendar_ids: Option<&'x RoaringBitmap>,
+}
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::CalendarIds).index_as(IndexAs::IntegerList),
+    IndexProperty::new(Property::Uid)
+        .index_as(IndexAs::Text {
+            tokenize: false,
+            index: true,
+        })
+        .max_size(255)
+        .required(),
+    IndexProperty::new(Property::UtcStart).index_as(IndexAs::LongInteger),
+    IndexProperty::new(Property::UtcEnd).index_as(IndexAs::LongInteger),
+];
+
+impl JMAP {
+    pub async fn calendar_event_set(
+        &self,
+        mut request: SetRequest<EventSetArguments>,
+        access_token: &AccessToken,
+        instance: &Arc<ServerInstance>,
+    ) -> Result<SetResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let send_scheduling_messages = request.arguments.send_scheduling_messages.unwrap_or(false);
+        let mut response = self
+            .prepare_set_response(&request, Collection::CalendarEvent)
+            .await?;
+        let will_destroy = request.unwrap_destroy();
+        let calendar_ids = self.calendar_get_or_create(account_id).await?;
+        let account_quota = self.get_quota(access_token, account_id).await?;
+        let (can_add_calendar_ids, can_modify_calendar_ids, can_remove_calendar_ids) =
+            if access_token.is_shared(account_id) {
+                (
+                    self.shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::Calendar,
+                        Acl::AddItems,
+                    )
+                    .await?
+                    .into(),
+                    self.shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::Calendar,
+                        Acl::ModifyItems,
+                    )
+                    .await?
+                    .into(),
+                    self.shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::Calendar,
+                        Acl::RemoveItems,
+                    )
+                    .await?
+                    .into(),
+                )
+            } else {
+                (None, None, None)
+            };
+        let mut changes = ChangeLogBuilder::new();
+        let mut scheduling = Vec::new();
+
+        // Process creates
+        'create: for (id, object) in request.unwrap_create() {
+            let event = match self.calendar_event_set_item(
+                object,
+                None,
+                &SetContext {
+                    response: &response,
+                    calendar_ids: &calendar_ids,
+                    can_add_calendar_ids: can_add_calendar_ids.as_ref(),
+                    can_remove_calendar_ids: can_remove_calendar_ids.as_ref(),
+                },
+            ) {
+                Ok(event) => event,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue 'create;
+                }
+            };
+
+            // Check quota
+            let size = (&event).serialize().len() as i64;
+            if !self
+                .has_available_quota(account_id, account_quota, size)
+                .await?
+            {
+                response.not_created.append(id, SetError::over_quota());
+                continue 'create;
+            }
+
+            let uid = event.get(&Property::Uid).clone();
+            let builder = match ObjectIndexBuilder::new(SCHEMA)
+                .with_changes(event)
+                .validate()
+            {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue 'create;
+                }
+            };
+            if send_scheduling_messages {
+                if let Some(event) = builder.changes() {
+                    scheduling.push((ItipMethod::Request, event.clone(), None));
+                }
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent)
+                .create_document()
+                .add(DirectoryClass::UsedQuota(account_id), size)
+                .custom(EventTextIndex::new(None, builder.changes()))
+                .custom(builder);
+
+            match self
+                .core
+                .storage
+                .data
+                .write(batch.build())
+                .await
+                .and_then(|ids| ids.last_document_id())
+            {
+                Ok(document_id) => {
+                    changes.log_insert(Collection::CalendarEvent, document_id);
+                    response.created.insert(
+                        id,
+                        Object::with_capacity(2)
+                            .with_property(Property::Id, Value::Id(document_id.into()))
+                            .with_property(Property::Uid, uid),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "calendar_event_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to create calendar event.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain event
+            let document_id = id.document_id();
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            // Validate ACL
+            if let Some(can_modify_calendar_ids) = &can_modify_calendar_ids {
+                if !current
+                    .inner
+                    .calendar_ids()
+                    .any(|id| can_modify_calendar_ids.contains(id))
+                {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("You are not allowed to modify this event."),
+                    );
+                    continue 'update;
+                }
+            }
+
+            // Updates to instances of a recurring event are stored as overrides
+            let object = if let Some(recurrence_id) = recurrence_id_from_prefix(id.prefix_id()) {
+                match self.calendar_event_override(&current.inner, recurrence_id, object) {
+                    Ok(object) => object,
+                    Err(err) => {
+                        response.not_updated.append(id, err);
+                        continue 'update;
+                    }
+                }
+            } else {
+                object
+            };
+
+            let mut event = match self.calendar_event_set_item(
+                object,
+                Some(&current.inner),
+                &SetContext {
+                    response: &response,
+                    calendar_ids: &calendar_ids,
+                    can_add_calendar_ids: can_add_calendar_ids.as_ref(),
+                    can_remove_calendar_ids: can_remove_calendar_ids.as_ref(),
+                },
+            ) {
+                Ok(event) => event,
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                    continue 'update;
+                }
+            };
+            if event == current.inner {
+                response.updated.append(id, None);
+                continue 'update;
+            }
+
+            // Check quota
+            let size =
+                (&event).serialize().len() as i64 - (&current.inner).serialize().len() as i64;
+            if size > 0
+                && !self
+                    .has_available_quota(account_id, account_quota, size)
+                    .await?
+            {
+                response.not_updated.append(id, SetError::over_quota());
+                continue 'update;
+            }
+            if send_scheduling_messages {
+                // Organizers increase the sequence number on every change
+                if self.is_event_organizer(account_id, &event).await? {
+                    let sequence = event.event_uint("sequence").unwrap_or(0) + 1;
+                    event.set(Property::parse("sequence"), Value::UnsignedInt(sequence));
+                }
+                scheduling.push((
+                    ItipMethod::Request,
+                    event.clone(),
+                    Some(current.inner.clone()),
+                ));
+            }
+
+            let text_index = EventTextIndex::new(Some(&current.inner), Some(&event));
+            let builder = match ObjectIndexBuilder::new(SCHEMA)
+                .with_changes(event.changes_from(&current.inner))
+                .with_current(current)
+                .validate()
+            {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                    continue 'update;
+                }
+            };
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent)
+                .update_document(document_id)
+                .custom(text_index)
+                .custom(builder);
+            if size != 0 {
+                batch.add(DirectoryClass::UsedQuota(account_id), size);
+            }
+
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_update(Collection::CalendarEvent, document_id);
+                    response.updated.append(id, None);
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "Another process modified this event, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "calendar_event_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to update calendar event.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+                continue;
+            };
+
+            // Validate ACL
+            if let Some(can_remove_calendar_ids) = &can_remove_calendar_ids {
+                if !current
+                    .inner
+                    .calendar_ids()
+                    .all(|id| can_remove_calendar_ids.contains(id))
+                {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("You are not allowed to delete this event."),
+                    );
+                    continue;
+                }
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent);
+
+            // Destroying an instance of a recurring event excludes it from the
+            // recurrence set
+            if let Some(recurrence_id) = recurrence_id_from_prefix(id.prefix_id()) {
+                let mut event = current.inner.clone();
+                if event
+                    .instance(recurrence_id, 0, self.core.jmap.calendar_max_instances)
+                    .is_none()
+                {
+                    response.not_destroyed.append(id, SetError::not_found());
+                    continue;
+                }
+                set_recurrence_override(
+                    &mut event,
+                    recurrence_id,
+                    Property::_T("excluded".to_string()),
+                    Value::Bool(true),
+                );
+                if let Some((utc_start, utc_end)) =
+                    event.utc_range(self.core.jmap.calendar_max_instances)
+                {
+                    event.set(Property::UtcStart, Value::UnsignedInt(utc_start));
+                    event.set(Property::UtcEnd, Value::UnsignedInt(utc_end));
+                }
+                let size =
+                    (&event).serialize().len() as i64 - (&current.inner).serialize().len() as i64;
+                batch
+                    .update_document(document_id)
+                    .add(DirectoryClass::UsedQuota(account_id), size)
+                    .custom(
+                        ObjectIndexBuilder::new(SCHEMA)
+                            .with_changes(event.changes_from(&current.inner))
+                            .with_current(current),
+                    );
+            } else {
+                if send_scheduling_messages {
+                    scheduling.push((ItipMethod::Cancel, current.inner.clone(), None));
+                }
+                batch
+                    .delete_document(document_id)
+                    .add(
+                        DirectoryClass::UsedQuota(account_id),
+                        -((&current.inner).serialize().len() as i64),
+                    )
+                    .custom(EventTextIndex::new(Some(&current.inner), None))
+                    .custom(ObjectIndexBuilder::new(SCHEMA).with_current(current));
+            }
+
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    if id.prefix_id() != 0 {
+                        changes.log_update(Collection::CalendarEvent, document_id);
+                    } else {
+                        changes.log_delete(Collection::CalendarEvent, document_id);
+                    }
+                    response.destroyed.push(id);
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "Another process modified this event, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "calendar_event_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to delete calendar event.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            response.state_change = StateChange::new(account_id)
+                .with_change(DataType::CalendarEvent, changes.change_id)
+                .into();
+            response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        // Send scheduling messages
+        for (method, event, previous) in scheduling {
+            self.calendar_event_schedule(account_id, instance, method, &event, previous.as_ref())
+                .await?;
+        }
+
+        Ok(response)
+    }
+
+    pub fn calendar_event_set_item(
+        &self,
+        changes: Object<SetValue>,
+        current: Option<&Object<Value>>,
+        ctx: &SetContext<'_>,
+    ) -> Result<Object<Value>, SetError> {
+        let mut event = current
+            .cloned()
+            .unwrap_or_else(|| Object::with_capacity(changes.properties.len() + 4));
+
+        for (property, value) in changes.properties {
+            let value = ctx.response.eval_object_references(value)?;
+
+            match (property, value) {
+                (Property::CalendarIds, MaybePatchValue::Value(Value::List(ids))) => {
+                    let mut calendar_ids = Vec::with_capacity(ids.len());
+                    for id in ids {
+                        if let Some(id) = id.try_unwrap_id() {
+                            let id = Value::Id(Id::from(id.document_id()));
+                            if !calendar_ids.contains(&id) {
+                                calendar_ids.push(id);
+                            }
+                        }
+                    }
+                    event.set(Property::CalendarIds, Value::List(calendar_ids));
+                }
+                (Property::CalendarIds, MaybePatchValue::Patch(patch)) => {
+                    let mut patch = patch.into_iter();
+                    if let Some(id) = patch.next().unwrap().try_unwrap_id() {
+                        let id = Value::Id(Id::from(id.document_id()));
+                        let mut calendar_ids = event
+                            .remove(&Property::CalendarIds)
+                            .try_unwrap_list()
+                            .unwrap_or_default();
+                        if patch.next().unwrap().try_unwrap_bool().unwrap_or_default() {
+                            if !calendar_ids.contains(&id) {
+                                calendar_ids.push(id);
+                            }
+                        } else {
+                            calendar_ids.retain(|item| item != &id);
+                        }
+                        event.set(Property::CalendarIds, Value::List(calendar_ids));
+                    }
+                }
+                (Property::Uid, MaybePatchValue::Value(Value::Text(uid))) => {
+                    if current.is_some_and(|current| {
+                        current.get(&Property::Uid).as_string() != Some(uid.as_str())
+                    }) {
+                        return Err(SetError::invalid_properties()
+                            .with_property(Property::Uid)
+                            .with_description("The uid of an event cannot be changed."));
+                    }
+                    event.set(Property::Uid, Value::Text(uid));
+                }
+                (Property::_T(path), MaybePatchValue::Value(value)) if path.contains('/') => {
+                    if !event.patch_json(&path, value) {
+                        return Err(SetError::new(SetErrorType::InvalidPatch)
+                            .with_property(Property::_T(path))
+                            .with_description("Invalid patch path."));
+                    }
+                }
+                (
+                    property @ (Property::Id
+                    | Property::Uid
+                    | Property::CalendarIds
+                    | Property::UtcStart
+                    | Property::UtcEnd),
+                    _,
+                ) => {
+                    return Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value."));
+                }
+                (property, MaybePatchValue::Value(value)) => {
+                    if value != Value::Null {
+                        event.set(property, value);
+                    } else {
+                        event.remove(&property);
+                    }
+                }
+                (property, MaybePatchValue::Patch(_)) => {
+                    return Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value."));
+                }
+            }
+        }
+
+        // Validate calendars
+        let calendar_ids = event.calendar_ids().collect::<Vec<_>>();
+        if calendar_ids.is_empty() {
+            if current.is_none() && ctx.can_add_calendar_ids.is_none() {
+                event.set(
+                    Property::CalendarIds,
+                    Value::List(vec![Value::Id(Id::from(DEFAULT_CALENDAR_ID))]),
+                );
+            } else {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::CalendarIds)
+                    .with_description("Event has to belong to at least one calendar."));
+            }
+        }
+        let current_calendar_ids = current
+            .map(|current| current.calendar_ids().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for calendar_id in &calendar_ids {
+            if !ctx.calendar_ids.contains(*calendar_id) {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::CalendarIds)
+                    .with_description(format!(
+                        "calendarId {} does not exist.",
+                        Id::from(*calendar_id)
+                    )));
+            } else if !current_calendar_ids.contains(calendar_id)
+                && matches!(&ctx.can_add_calendar_ids, Some(ids) if !ids.contains(*calendar_id))
+            {
+                return Err(SetError::forbidden().with_description(format!(
+                    "You are not allowed to add events to calendar {}.",
+                    Id::from(*calendar_id)
+                )));
+            }
+        }
+        if let Some(can_remove_calendar_ids) = &ctx.can_remove_calendar_ids {
+            for calendar_id in &current_calendar_ids {
+                if !calendar_ids.contains(calendar_id)
+                    && !can_remove_calendar_ids.contains(*calendar_id)
+                {
+                    return Err(SetError::forbidden().with_description(format!(
+                        "You are not allowed to remove events from calendar {}.",
+                        Id::from(*calendar_id)
+                    )));
+                }
+            }
+        }
+
+        // Validate start and duration
+        for (property, is_valid) in [
+            (
+                "start",
+                event
+                    .event_str("start")
+                    .is_some_and(|start| parse_local_date_time(start).is_some()),
+            ),
+            (
+                "duration",
+                event
+                    .event_str("duration")
+                    .is_none_or(|duration| parse_duration(duration).is_some_and(|d| d >= 0)),
+            ),
+        ] {
+            if !is_valid {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::parse(property))
+                    .with_description(format!("Missing or invalid {property}.")));
+            }
+        }
+
+        // Validate recurrence rules
+        for property in ["recurrenceRules", "excludedRecurrenceRules"] {
+            if !matches!(event.event_value(property), Value::Null)
+                && !event
+                    .event_list(property)
+                    .is_some_and(|rules| is_valid_recurrence_rules(rules))
+            {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::parse(property))
+                    .with_description(format!("Invalid {property}.")));
+            }
+        }
+
+        // Add server-set properties
+        if current.is_none() {
+            if let Value::Null = event.get(&Property::Uid) {
+                event.set(Property::Uid, Value::Text(generate_uid()));
+            }
+            if let Value::Null = event.get(&Property::_T("@type".to_string())) {
+                event.set(
+                    Property::_T("@type".to_string()),
+                    Value::Text("Event".to_string()),
+                );
+            }
+        }
+        let (utc_start, utc_end) = event
+            .utc_range(self.core.jmap.calendar_max_instances)
+            .unwrap_or_default();
+        event.set(Property::UtcStart, Value::UnsignedInt(utc_start));
+        event.set(Property::UtcEnd, Value::UnsignedInt(utc_end));
+
+        // Validate size
+        if (&event).serialize().len() > self.core.jmap.calendar_event_max_size {
+            return Err(
+                SetError::new(SetErrorType::TooLarge).with_description(format!(
+                    "Event exceeds the maximum size of {} bytes.",
+                    self.core.jmap.calendar_event_max_size
+                )),
+            );
+        }
+
+        Ok(event)
+    }
+
+    fn calendar_event_override(
+        &self,
+        current: &Object<Value>,
+        recurrence_id: chrono::NaiveDateTime,
+        changes: Object<SetValue>,
+    ) -> Result<Object<SetValue>, SetError> {
+        if current
+            .instance(recurrence_id, 0, self.core.jmap.calendar_max_instances)
+            .is_none()
+        {
+            return Err(SetError::not_found());
+        }
+
+        let mut event = current.clone();
+        for (property, value) in changes.properties {
+            match (property, value) {
+                (
+                    property @ (Property::Id
+                    | Property::Uid
+                    | Property::CalendarIds
+                    | Property::UtcStart
+                    | Property::UtcEnd),
+                    _,
+                ) => {
+                    return Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Property cannot be changed on an instance."));
+                }
+                (property, SetValue::Value(value)) => {
+                    set_recurrence_override(&mut event, recurrence_id, property, value);
+                }
+                (property, _) => {
+                    return Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value."));
+                }
+            }
+        }
+
+        let overrides = Property::parse("recurrenceOverrides");
+        let mut object = Object {
+            properties: Default::default(),
+        };
+        object
+            .properties
+            .append(overrides.clone(), SetValue::Value(event.remove(&overrides)));
+        Ok(object)
+    }
+
+    pub async fn calendar_event_remove_from_calendar(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        calendar_id: u32,
+        changes: &mut ChangeLogBuilder,
+    ) -> Result<Result<(), SetError>, MethodError> {
+        let current = if let Some(current) = self
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::CalendarEvent,
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            current
+        } else {
+            return Ok(Ok(()));
+        };
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::CalendarEvent);
+        let calendar_ids = current
+            .inner
+            .calendar_ids()
+            .filter(|id| *id != calendar_id)
+            .map(|id| Value::Id(Id::from(id)))
+            .collect::<Vec<_>>();
+        if !calendar_ids.is_empty() {
+            let mut event = current.inner.clone();
+            event.set(Property::CalendarIds, Value::List(calendar_ids));
+            let size =
+                (&event).serialize().len() as i64 - (&current.inner).serialize().len() as i64;
+            batch
+                .update_document(document_id)
+                .add(DirectoryClass::UsedQuota(account_id), size)
+                .custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_changes(event.changes_from(&current.inner))
+                        .with_current(current),
+                );
+            changes.log_update(Collection::CalendarEvent, document_id);
+        } else {
+            batch
+                .delete_document(document_id)
+                .add(
+                    DirectoryClass::UsedQuota(account_id),
+                    -((&current.inner).serialize().len() as i64),
+                )
+                .custom(EventTextIndex::new(Some(&current.inner), None))
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_current(current));
+            changes.log_delete(Collection::CalendarEvent, document_id);
+        }
+
+        match self.core.storage.data.write(batch.build()).await {
+            Ok(_) => Ok(Ok(())),
+            Err(store::Error::AssertValueFailed) => Ok(Err(SetError::forbidden()
+                .with_description(concat!(
+                    "Another process modified an event in this calendar ",
+                    "while deleting it, please try again."
+                )))),
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "calendar_event_set",
+                    account_id = account_id,
+                    document_id = document_id,
+                    error = ?err,
+                    "Failed to update event while deleting calendar.");
+                Err(MethodError::ServerPartialFail)
+            }
+        }
+    }
+}
+
+fn set_recurrence_override(
+    event: &mut Object<Value>,
+    recurrence_id: chrono::NaiveDateTime,
+    property: Property,
+    value: Value,
+) {
+    let overrides = event
+        .properties
+        .get_mut_or_insert_with(Property::parse("recurrenceOverrides"), || {
+            Value::Object(Object::with_capacity(1))
+        });
+    if !matches!(overrides, Value::Object(_)) {
+        *overrides = Value::Object(Object::with_capacity(1));
+    }
+    let patch = overrides
+        .as_obj_mut()
+        .unwrap()
+        .properties
+        .get_mut_or_insert_with(Property::_T(format_local_date_time(&recurrence_id)), || {
+            Value::Object(Object::with_capacity(1))
+        });
+    if !matches!(patch, Value::Object(_)) {
+        *patch = Value::Object(Object::with_capacity(1));
+    }
+    let patch = patch.as_obj_mut().unwrap();
+    let property = Property::_T(property.to_string());
+    if value != Value::Null {
+        patch.set(property, value);
+    } else {
+        patch.remove(&property);
+    }
+}
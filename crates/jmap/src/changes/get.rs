@@ -50,6 +50,26 @@ impl JMAP {
 
                 return Err(MethodError::CannotCalculateChanges);
             }
+            RequestArguments::AddressBook => {
+                access_token.assert_has_access(request.account_id, Collection::AddressBook)?;
+
+                Collection::AddressBook
+            }
+            RequestArguments::ContactCard => {
+                access_token.assert_has_access(request.account_id, Collection::ContactCard)?;
+
+                Collection::ContactCard
+            }
+            RequestArguments::Calendar => {
+                access_token.assert_has_access(request.account_id, Collection::Calendar)?;
+
+                Collection::Calendar
+            }
+            RequestArguments::CalendarEvent => {
+                access_token.assert_has_access(request.account_id, Collection::CalendarEvent)?;
+
+                Collection::CalendarEvent
+            }
         };
 
         let max_changes = if self.core.jmap.changes_max_results > 0
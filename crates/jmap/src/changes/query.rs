@@ -35,6 +35,12 @@ impl JMAP {
                             changes::RequestArguments::EmailSubmission
                         }
                         query::RequestArguments::Quota => changes::RequestArguments::Quota,
+                        query::RequestArguments::ContactCard => {
+                            changes::RequestArguments::ContactCard
+                        }
+                        query::RequestArguments::CalendarEvent(_) => {
+                            changes::RequestArguments::CalendarEvent
+                        }
                         _ => return Err(MethodError::UnknownMethod("Unknown method".to_string())),
                     },
                 },
@@ -82,6 +88,13 @@ impl JMAP {
                     self.email_submission_query(query).await?
                 }
                 query::RequestArguments::Quota => self.quota_query(query, access_token).await?,
+                query::RequestArguments::ContactCard => {
+                    self.contact_card_query(query, access_token).await?
+                }
+                query::RequestArguments::CalendarEvent(arguments) => {
+                    self.calendar_event_query(query.with_arguments(arguments), access_token)
+                        .await?
+                }
                 _ => unreachable!(),
             };
 
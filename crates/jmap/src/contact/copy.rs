@@ -0,0 +1,242 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::{method::MethodError, set::SetError},
+    method::{
+        copy::{CopyRequest, CopyResponse, RequestArguments},
+        set::{self, SetRequest},
+    },
+    object::{index::ObjectIndexBuilder, Object},
+    request::{
+        method::{MethodFunction, MethodName, MethodObject},
+        reference::MaybeReference,
+        Call, RequestMethod,
+    },
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{SetValue, Value},
+    },
+};
+use store::{
+    write::{log::ChangeLogBuilder, BatchBuilder, DirectoryClass},
+    Serialize,
+};
+use utils::map::vec_map::VecMap;
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::{
+    set::{SetContext, SCHEMA},
+    ContactTextIndex,
+};
+
+impl JMAP {
+    pub async fn contact_card_copy(
+        &self,
+        request: CopyRequest<RequestArguments>,
+        access_token: &AccessToken,
+        next_call: &mut Option<Call<RequestMethod>>,
+    ) -> Result<CopyResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let from_account_id = request.from_account_id.document_id();
+
+        if account_id == from_account_id {
+            return Err(MethodError::InvalidArguments(
+                "From accountId is equal to fromAccountId".to_string(),
+            ));
+        }
+        let old_state = self
+            .assert_state(account_id, Collection::ContactCard, &request.if_in_state)
+            .await?;
+        let mut response = CopyResponse {
+            from_account_id: request.from_account_id,
+            account_id: request.account_id,
+            new_state: old_state.clone(),
+            old_state,
+            created: VecMap::with_capacity(request.create.len()),
+            not_created: VecMap::new(),
+            state_change: None,
+        };
+
+        let from_card_ids = if access_token.is_shared(from_account_id) {
+            self.shared_contacts(access_token, from_account_id, Acl::ReadItems)
+                .await?
+        } else {
+            self.get_document_ids(from_account_id, Collection::ContactCard)
+                .await?
+                .unwrap_or_default()
+        };
+        let address_book_ids = self.address_book_get_or_create(account_id).await?;
+        let account_quota = self.get_quota(access_token, account_id).await?;
+        let can_add_address_book_ids = if access_token.is_shared(account_id) {
+            self.shared_documents(
+                access_token,
+                account_id,
+                Collection::AddressBook,
+                Acl::AddItems,
+            )
+            .await?
+            .into()
+        } else {
+            None
+        };
+        let on_success_delete = request.on_success_destroy_original.unwrap_or(false);
+        let mut destroy_ids = Vec::new();
+        let mut changes = ChangeLogBuilder::new();
+
+        for (id, create) in request.create {
+            let id = id.unwrap();
+            let from_card_id = id.document_id();
+            let from_card = if from_card_ids.contains(from_card_id) {
+                self.get_property::<Object<Value>>(
+                    from_account_id,
+                    Collection::ContactCard,
+                    from_card_id,
+                    Property::Value,
+                )
+                .await?
+            } else {
+                None
+            };
+            let from_card = if let Some(from_card) = from_card {
+                from_card
+            } else {
+                response.not_created.append(
+                    id,
+                    SetError::not_found().with_description(format!(
+                        "Item {} not found not found in account {}.",
+                        id, response.from_account_id
+                    )),
+                );
+                continue;
+            };
+
+            // Build the new card from the original, applying any overrides
+            let mut object = Object {
+                properties: VecMap::with_capacity(from_card.properties.len()),
+            };
+            for (property, value) in from_card.properties {
+                if property != Property::AddressBookIds {
+                    object.properties.append(property, SetValue::Value(value));
+                }
+            }
+            for (property, value) in create.properties {
+                object.properties.set(property, value);
+            }
+            let card = match self.contact_card_set_item(
+                object,
+                None,
+                &SetContext {
+                    response: &response,
+                    address_book_ids: &address_book_ids,
+                    can_add_address_book_ids: can_add_address_book_ids.as_ref(),
+                    can_remove_address_book_ids: None,
+                },
+            ) {
+                Ok(card) => card,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue;
+                }
+            };
+
+            // Check quota
+            let size = (&card).serialize().len() as i64;
+            if !self
+                .has_available_quota(account_id, account_quota, size)
+                .await?
+            {
+                response.not_created.append(id, SetError::over_quota());
+                continue;
+            }
+
+            let builder = match ObjectIndexBuilder::new(SCHEMA)
+                .with_changes(card)
+                .validate()
+            {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue;
+                }
+            };
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .create_document()
+                .add(DirectoryClass::UsedQuota(account_id), size)
+                .custom(ContactTextIndex::new(None, builder.changes()))
+                .custom(builder);
+
+            match self
+                .core
+                .storage
+                .data
+                .write(batch.build())
+                .await
+                .and_then(|ids| ids.last_document_id())
+            {
+                Ok(document_id) => {
+                    changes.log_insert(Collection::ContactCard, document_id);
+                    response.created.append(
+                        id,
+                        Object::with_capacity(1)
+                            .with_property(Property::Id, Value::Id(document_id.into())),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "contact_card_copy",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to copy contact card.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+
+            // Add to destroy list
+            if on_success_delete {
+                destroy_ids.push(id);
+            }
+        }
+
+        // Update state
+        if !changes.is_empty() {
+            let change_id = self.commit_changes(account_id, changes).await?;
+            response.new_state = change_id.into();
+            response.state_change = StateChange::new(account_id)
+                .with_change(DataType::ContactCard, change_id)
+                .into();
+        }
+
+        // Destroy ids
+        if on_success_delete && !destroy_ids.is_empty() {
+            *next_call = Call {
+                id: String::new(),
+                name: MethodName::new(MethodObject::ContactCard, MethodFunction::Set),
+                method: RequestMethod::Set(SetRequest {
+                    account_id: request.from_account_id,
+                    if_in_state: request.destroy_from_if_in_state,
+                    create: None,
+                    update: None,
+                    destroy: MaybeReference::Value(destroy_ids).into(),
+                    arguments: set::RequestArguments::ContactCard,
+                }),
+            }
+            .into();
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,112 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{acl::Acl, collection::Collection, id::Id, property::Property, value::Value},
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::set::ContactCardObject;
+
+impl JMAP {
+    pub async fn contact_card_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.core.jmap.get_max_objects)?;
+        let properties = request.unwrap_properties(&[]);
+        let account_id = request.account_id.document_id();
+        let card_ids = if access_token.is_shared(account_id) {
+            self.shared_contacts(access_token, account_id, Acl::ReadItems)
+                .await?
+        } else {
+            self.get_document_ids(account_id, Collection::ContactCard)
+                .await?
+                .unwrap_or_default()
+        };
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            card_ids
+                .iter()
+                .take(self.core.jmap.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::ContactCard)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the contact card object
+            let document_id = id.document_id();
+            if !card_ids.contains(document_id) {
+                response.not_found.push(id.into());
+                continue;
+            }
+            let mut values = if let Some(values) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                values
+            } else {
+                response.not_found.push(id.into());
+                continue;
+            };
+
+            // Address book ids are stored as a list and returned as an id map
+            let address_book_ids =
+                values
+                    .address_book_ids()
+                    .fold(Object::with_capacity(1), |obj, address_book_id| {
+                        obj.with_property(
+                            Property::_T(Id::from(address_book_id).to_string()),
+                            Value::Bool(true),
+                        )
+                    });
+            values.set(Property::AddressBookIds, Value::Object(address_book_ids));
+
+            let card = if properties.is_empty() {
+                let mut card = Object::with_capacity(values.properties.len() + 1);
+                card.append(Property::Id, Value::Id(id));
+                for (property, value) in values.properties {
+                    card.append(property, value);
+                }
+                card
+            } else {
+                let mut card = Object::with_capacity(properties.len());
+                for property in &properties {
+                    let value = match property {
+                        Property::Id => Value::Id(id),
+                        property => values.remove(property),
+                    };
+                    card.append(property.clone(), value);
+                }
+                card
+            };
+
+            // Add result to response
+            response.list.push(card);
+        }
+        Ok(response)
+    }
+}
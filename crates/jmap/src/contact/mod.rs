@@ -0,0 +1,195 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::collections::HashSet;
+
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+use store::{
+    write::{BatchBuilder, BitmapClass, BitmapHash, IntoOperations, Operation, TokenizeText},
+    Serialize,
+};
+
+pub mod copy;
+pub mod get;
+pub mod query;
+pub mod set;
+
+pub const MAX_SORT_FIELD_LENGTH: usize = 255;
+
+// Full-text index of the name and email address fields of a JSContact card,
+// which are nested within the card and not covered by the object schema.
+#[derive(Debug, Default)]
+pub struct ContactTextIndex {
+    name_tokens: (HashSet<String>, HashSet<String>),
+    email_tokens: (HashSet<String>, HashSet<String>),
+    sort_name: (Option<String>, Option<String>),
+}
+
+impl ContactTextIndex {
+    pub fn new(current: Option<&Object<Value>>, card: Option<&Object<Value>>) -> Self {
+        let mut index = ContactTextIndex::default();
+        let mut add_names = HashSet::new();
+        let mut remove_names = HashSet::new();
+        let mut add_emails = HashSet::new();
+        let mut remove_emails = HashSet::new();
+
+        if let Some(current) = current {
+            for name in current.contact_names() {
+                name.tokenize_into(&mut remove_names);
+            }
+            for email in current.contact_emails() {
+                email.tokenize_into(&mut remove_emails);
+            }
+            index.sort_name.0 = current.contact_sort_name();
+        }
+        if let Some(card) = card {
+            for name in card.contact_names() {
+                for token in name.to_tokens() {
+                    if !remove_names.remove(&token) {
+                        add_names.insert(token);
+                    }
+                }
+            }
+            for email in card.contact_emails() {
+                for token in email.to_tokens() {
+                    if !remove_emails.remove(&token) {
+                        add_emails.insert(token);
+                    }
+                }
+            }
+            index.sort_name.1 = card.contact_sort_name();
+        }
+        if index.sort_name.0 == index.sort_name.1 {
+            index.sort_name = (None, None);
+        }
+        index.name_tokens = (add_names, remove_names);
+        index.email_tokens = (add_emails, remove_emails);
+
+        index
+    }
+}
+
+impl IntoOperations for ContactTextIndex {
+    fn build(self, batch: &mut BatchBuilder) {
+        for (field, (add_tokens, remove_tokens)) in [
+            (u8::from(Property::Name), self.name_tokens),
+            (u8::from(Property::Email), self.email_tokens),
+        ] {
+            for (tokens, set) in [(add_tokens, true), (remove_tokens, false)] {
+                for token in tokens {
+                    batch.ops.push(Operation::Bitmap {
+                        class: BitmapClass::Text {
+                            field,
+                            token: BitmapHash::new(token),
+                        },
+                        set,
+                    });
+                }
+            }
+        }
+
+        let (remove_name, add_name) = self.sort_name;
+        for (name, set) in [(remove_name, false), (add_name, true)] {
+            if let Some(name) = name {
+                batch.ops.push(Operation::Index {
+                    field: Property::Name.into(),
+                    key: name.serialize(),
+                    set,
+                });
+            }
+        }
+    }
+}
+
+pub trait ContactFields {
+    fn contact_names(&self) -> Vec<&str>;
+    fn contact_emails(&self) -> Vec<&str>;
+    fn contact_sort_name(&self) -> Option<String>;
+}
+
+impl ContactFields for Object<Value> {
+    fn contact_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        if let Value::Object(name) = self.get(&Property::Name) {
+            if let Some(full) = name.get(&Property::_T("full".to_string())).as_string() {
+                names.push(full);
+            }
+            if let Some(components) = name.get(&Property::_T("components".to_string())).as_list() {
+                for component in components {
+                    if let Some(value) = component
+                        .as_obj()
+                        .and_then(|c| c.get(&Property::_T("value".to_string())).as_string())
+                    {
+                        names.push(value);
+                    }
+                }
+            }
+        }
+        for property in ["nicknames", "organizations"] {
+            if let Value::Object(items) = self.get(&Property::parse(property)) {
+                for item in items.properties.values() {
+                    if let Some(name) = item
+                        .as_obj()
+                        .and_then(|item| item.get(&Property::_T("name".to_string())).as_string())
+                    {
+                        names.push(name);
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    fn contact_emails(&self) -> Vec<&str> {
+        let mut emails = Vec::new();
+        if let Value::Object(items) = self.get(&Property::parse("emails")) {
+            for item in items.properties.values() {
+                if let Some(address) = item
+                    .as_obj()
+                    .and_then(|item| item.get(&Property::_T("address".to_string())).as_string())
+                {
+                    emails.push(address);
+                }
+            }
+        }
+        emails
+    }
+
+    fn contact_sort_name(&self) -> Option<String> {
+        let name = if let Value::Object(name) = self.get(&Property::Name) {
+            if let Some(full) = name.get(&Property::_T("full".to_string())).as_string() {
+                full.to_string()
+            } else {
+                name.get(&Property::_T("components".to_string()))
+                    .as_list()
+                    .map(|components| {
+                        components
+                            .iter()
+                            .filter_map(|component| {
+                                component.as_obj().and_then(|c| {
+                                    c.get(&Property::_T("value".to_string())).as_string()
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .unwrap_or_default()
+            }
+        } else {
+            String::new()
+        };
+        let name = name.trim().to_lowercase();
+
+        if !name.is_empty() {
+            Some(name.chars().take(MAX_SORT_FIELD_LENGTH).collect())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,85 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::query::{
+        Comparator, Filter, QueryRequest, QueryResponse, RequestArguments, SortProperty,
+    },
+    types::{acl::Acl, collection::Collection, property::Property},
+};
+use store::query::{self};
+
+use crate::{auth::AccessToken, JMAP};
+
+impl JMAP {
+    pub async fn contact_card_query(
+        &self,
+        mut request: QueryRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<QueryResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut filters = Vec::with_capacity(request.filter.len());
+
+        for cond in std::mem::take(&mut request.filter) {
+            match cond {
+                Filter::InAddressBook(address_book_id) => filters.push(query::Filter::eq(
+                    Property::AddressBookIds,
+                    address_book_id.document_id(),
+                )),
+                Filter::Uid(uid) => filters.push(query::Filter::eq(Property::Uid, uid)),
+                Filter::Name(name) => filters.push(query::Filter::has_text(Property::Name, &name)),
+                Filter::Email(email) => {
+                    filters.push(query::Filter::has_text(Property::Email, &email))
+                }
+                Filter::Text(text) => {
+                    filters.push(query::Filter::Or);
+                    filters.push(query::Filter::has_text(Property::Name, &text));
+                    filters.push(query::Filter::has_text(Property::Email, &text));
+                    filters.push(query::Filter::End);
+                }
+                Filter::And | Filter::Or | Filter::Not | Filter::Close => {
+                    filters.push(cond.into());
+                }
+                other => return Err(MethodError::UnsupportedFilter(other.to_string())),
+            }
+        }
+
+        let mut result_set = self
+            .filter(account_id, Collection::ContactCard, filters)
+            .await?;
+        if access_token.is_shared(account_id) {
+            result_set.apply_mask(
+                self.shared_contacts(access_token, account_id, Acl::ReadItems)
+                    .await?,
+            );
+        }
+
+        let (response, paginate) = self.build_query_response(&result_set, &request).await?;
+
+        if let Some(paginate) = paginate {
+            // Parse sort criteria
+            let mut comparators = Vec::with_capacity(request.sort.as_ref().map_or(1, |s| s.len()));
+            for comparator in request
+                .sort
+                .and_then(|s| if !s.is_empty() { s.into() } else { None })
+                .unwrap_or_else(|| vec![Comparator::ascending(SortProperty::Name)])
+            {
+                comparators.push(match comparator.property {
+                    SortProperty::Name => {
+                        query::Comparator::field(Property::Name, comparator.is_ascending)
+                    }
+                    other => return Err(MethodError::UnsupportedSort(other.to_string())),
+                });
+            }
+
+            // Sort results
+            self.sort(result_set, comparators, paginate, response).await
+        } else {
+            Ok(response)
+        }
+    }
+}
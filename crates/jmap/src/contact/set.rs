@@ -0,0 +1,686 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{RequestArguments, SetRequest, SetResponse},
+    object::{
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        id::Id,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder, DirectoryClass},
+    Serialize,
+};
+
+use crate::{addressbook::DEFAULT_ADDRESS_BOOK_ID, auth::AccessToken, JMAP};
+
+use super::ContactTextIndex;
+
+pub struct SetContext<'x> {
+    pub response: &'x dyn EvalObjectReferences,
+    pub address_book_ids: &'x RoaringBitmap,
+    pub can_add_address_book_ids: Option<&'x RoaringBitmap>,
+    pub can_remove_address_book_ids: Option<&'x RoaringBitmap>,
+}
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::AddressBookIds).index_as(IndexAs::IntegerList),
+    IndexProperty::new(Property::Uid)
+        .index_as(IndexAs::Text {
+            tokenize: false,
+            index: true,
+        })
+        .max_size(255)
+        .required(),
+];
+
+impl JMAP {
+    pub async fn contact_card_set(
+        &self,
+        mut request: SetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut response = self
+            .prepare_set_response(&request, Collection::ContactCard)
+            .await?;
+        let will_destroy = request.unwrap_destroy();
+        let address_book_ids = self.address_book_get_or_create(account_id).await?;
+        let account_quota = self.get_quota(access_token, account_id).await?;
+        let (can_add_address_book_ids, can_modify_address_book_ids, can_remove_address_book_ids) =
+            if access_token.is_shared(account_id) {
+                (
+                    self.shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::AddressBook,
+                        Acl::AddItems,
+                    )
+                    .await?
+                    .into(),
+                    self.shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::AddressBook,
+                        Acl::ModifyItems,
+                    )
+                    .await?
+                    .into(),
+                    self.shared_documents(
+                        access_token,
+                        account_id,
+                        Collection::AddressBook,
+                        Acl::RemoveItems,
+                    )
+                    .await?
+                    .into(),
+                )
+            } else {
+                (None, None, None)
+            };
+        let mut changes = ChangeLogBuilder::new();
+
+        // Process creates
+        'create: for (id, object) in request.unwrap_create() {
+            let card = match self.contact_card_set_item(
+                object,
+                None,
+                &SetContext {
+                    response: &response,
+                    address_book_ids: &address_book_ids,
+                    can_add_address_book_ids: can_add_address_book_ids.as_ref(),
+                    can_remove_address_book_ids: can_remove_address_book_ids.as_ref(),
+                },
+            ) {
+                Ok(card) => card,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue 'create;
+                }
+            };
+
+            // Check quota
+            let size = (&card).serialize().len() as i64;
+            if !self
+                .has_available_quota(account_id, account_quota, size)
+                .await?
+            {
+                response.not_created.append(id, SetError::over_quota());
+                continue 'create;
+            }
+
+            let uid = card.get(&Property::Uid).clone();
+            let builder = match ObjectIndexBuilder::new(SCHEMA)
+                .with_changes(card)
+                .validate()
+            {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue 'create;
+                }
+            };
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .create_document()
+                .add(DirectoryClass::UsedQuota(account_id), size)
+                .custom(ContactTextIndex::new(None, builder.changes()))
+                .custom(builder);
+
+            match self
+                .core
+                .storage
+                .data
+                .write(batch.build())
+                .await
+                .and_then(|ids| ids.last_document_id())
+            {
+                Ok(document_id) => {
+                    changes.log_insert(Collection::ContactCard, document_id);
+                    response.created.insert(
+                        id,
+                        Object::with_capacity(2)
+                            .with_property(Property::Id, Value::Id(document_id.into()))
+                            .with_property(Property::Uid, uid),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "contact_card_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to create contact card.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain card
+            let document_id = id.document_id();
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            // Validate ACL
+            if let Some(can_modify_address_book_ids) = &can_modify_address_book_ids {
+                if !current
+                    .inner
+                    .address_book_ids()
+                    .any(|id| can_modify_address_book_ids.contains(id))
+                {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("You are not allowed to modify this contact card."),
+                    );
+                    continue 'update;
+                }
+            }
+
+            let card = match self.contact_card_set_item(
+                object,
+                Some(&current.inner),
+                &SetContext {
+                    response: &response,
+                    address_book_ids: &address_book_ids,
+                    can_add_address_book_ids: can_add_address_book_ids.as_ref(),
+                    can_remove_address_book_ids: can_remove_address_book_ids.as_ref(),
+                },
+            ) {
+                Ok(card) => card,
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                    continue 'update;
+                }
+            };
+            if card == current.inner {
+                response.updated.append(id, None);
+                continue 'update;
+            }
+
+            // Check quota
+            let size = (&card).serialize().len() as i64 - (&current.inner).serialize().len() as i64;
+            if size > 0
+                && !self
+                    .has_available_quota(account_id, account_quota, size)
+                    .await?
+            {
+                response.not_updated.append(id, SetError::over_quota());
+                continue 'update;
+            }
+
+            let text_index = ContactTextIndex::new(Some(&current.inner), Some(&card));
+            let builder = match ObjectIndexBuilder::new(SCHEMA)
+                .with_changes(card.changes_from(&current.inner))
+                .with_current(current)
+                .validate()
+            {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                    continue 'update;
+                }
+            };
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .update_document(document_id)
+                .custom(text_index)
+                .custom(builder);
+            if size != 0 {
+                batch.add(DirectoryClass::UsedQuota(account_id), size);
+            }
+
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_update(Collection::ContactCard, document_id);
+                    response.updated.append(id, None);
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "Another process modified this contact card, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "contact_card_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to update contact card.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+                continue;
+            };
+
+            // Validate ACL
+            if let Some(can_remove_address_book_ids) = &can_remove_address_book_ids {
+                if !current
+                    .inner
+                    .address_book_ids()
+                    .all(|id| can_remove_address_book_ids.contains(id))
+                {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("You are not allowed to delete this contact card."),
+                    );
+                    continue;
+                }
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .delete_document(document_id)
+                .add(
+                    DirectoryClass::UsedQuota(account_id),
+                    -((&current.inner).serialize().len() as i64),
+                )
+                .custom(ContactTextIndex::new(Some(&current.inner), None))
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_current(current));
+
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_delete(Collection::ContactCard, document_id);
+                    response.destroyed.push(id);
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "Another process modified this contact card, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "contact_card_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to delete contact card.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            response.state_change = StateChange::new(account_id)
+                .with_change(DataType::ContactCard, changes.change_id)
+                .into();
+            response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        Ok(response)
+    }
+
+    pub fn contact_card_set_item(
+        &self,
+        changes: Object<SetValue>,
+        current: Option<&Object<Value>>,
+        ctx: &SetContext<'_>,
+    ) -> Result<Object<Value>, SetError> {
+        let mut card = current
+            .cloned()
+            .unwrap_or_else(|| Object::with_capacity(changes.properties.len() + 2));
+
+        for (property, value) in changes.properties {
+            let value = ctx.response.eval_object_references(value)?;
+
+            match (property, value) {
+                (Property::AddressBookIds, MaybePatchValue::Value(Value::List(ids))) => {
+                    let mut address_book_ids = Vec::with_capacity(ids.len());
+                    for id in ids {
+                        if let Some(id) = id.try_unwrap_id() {
+                            let id = Value::Id(Id::from(id.document_id()));
+                            if !address_book_ids.contains(&id) {
+                                address_book_ids.push(id);
+                            }
+                        }
+                    }
+                    card.set(Property::AddressBookIds, Value::List(address_book_ids));
+                }
+                (Property::AddressBookIds, MaybePatchValue::Patch(patch)) => {
+                    let mut patch = patch.into_iter();
+                    if let Some(id) = patch.next().unwrap().try_unwrap_id() {
+                        let id = Value::Id(Id::from(id.document_id()));
+                        let mut address_book_ids = card
+                            .remove(&Property::AddressBookIds)
+                            .try_unwrap_list()
+                            .unwrap_or_default();
+                        if patch.next().unwrap().try_unwrap_bool().unwrap_or_default() {
+                            if !address_book_ids.contains(&id) {
+                                address_book_ids.push(id);
+                            }
+                        } else {
+                            address_book_ids.retain(|item| item != &id);
+                        }
+                        card.set(Property::AddressBookIds, Value::List(address_book_ids));
+                    }
+                }
+                (Property::Uid, MaybePatchValue::Value(Value::Text(uid))) => {
+                    if current.is_some_and(|current| {
+                        current.get(&Property::Uid).as_string() != Some(uid.as_str())
+                    }) {
+                        return Err(SetError::invalid_properties()
+                            .with_property(Property::Uid)
+                            .with_description("The uid of a contact card cannot be changed."));
+                    }
+                    card.set(Property::Uid, Value::Text(uid));
+                }
+                (Property::_T(path), MaybePatchValue::Value(value)) if path.contains('/') => {
+                    if !card.patch_json(&path, value) {
+                        return Err(SetError::new(SetErrorType::InvalidPatch)
+                            .with_property(Property::_T(path))
+                            .with_description("Invalid patch path."));
+                    }
+                }
+                (property @ (Property::Id | Property::Uid | Property::AddressBookIds), _) => {
+                    return Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value."));
+                }
+                (property, MaybePatchValue::Value(value)) => {
+                    if value != Value::Null {
+                        card.set(property, value);
+                    } else {
+                        card.remove(&property);
+                    }
+                }
+                (property, MaybePatchValue::Patch(_)) => {
+                    return Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value."));
+                }
+            }
+        }
+
+        // Validate address books
+        let address_book_ids = card.address_book_ids().collect::<Vec<_>>();
+        if address_book_ids.is_empty() {
+            if current.is_none() && ctx.can_add_address_book_ids.is_none() {
+                card.set(
+                    Property::AddressBookIds,
+                    Value::List(vec![Value::Id(Id::from(DEFAULT_ADDRESS_BOOK_ID))]),
+                );
+            } else {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::AddressBookIds)
+                    .with_description("Contact card has to belong to at least one address book."));
+            }
+        }
+        let current_address_book_ids = current
+            .map(|current| current.address_book_ids().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for address_book_id in &address_book_ids {
+            if !ctx.address_book_ids.contains(*address_book_id) {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::AddressBookIds)
+                    .with_description(format!(
+                        "addressBookId {} does not exist.",
+                        Id::from(*address_book_id)
+                    )));
+            } else if !current_address_book_ids.contains(address_book_id)
+                && matches!(&ctx.can_add_address_book_ids, Some(ids) if !ids.contains(*address_book_id))
+            {
+                return Err(SetError::forbidden().with_description(format!(
+                    "You are not allowed to add contacts to address book {}.",
+                    Id::from(*address_book_id)
+                )));
+            }
+        }
+        if let Some(can_remove_address_book_ids) = &ctx.can_remove_address_book_ids {
+            for address_book_id in &current_address_book_ids {
+                if !address_book_ids.contains(address_book_id)
+                    && !can_remove_address_book_ids.contains(*address_book_id)
+                {
+                    return Err(SetError::forbidden().with_description(format!(
+                        "You are not allowed to remove contacts from address book {}.",
+                        Id::from(*address_book_id)
+                    )));
+                }
+            }
+        }
+
+        // Add server-set properties
+        if current.is_none() {
+            if let Value::Null = card.get(&Property::Uid) {
+                card.set(Property::Uid, Value::Text(generate_uid()));
+            }
+            for (property, value) in [("@type", "Card"), ("version", "1.0")] {
+                let property = Property::parse(property);
+                if let Value::Null = card.get(&property) {
+                    card.set(property, Value::Text(value.to_string()));
+                }
+            }
+        }
+
+        // Validate size
+        if (&card).serialize().len() > self.core.jmap.contact_max_size {
+            return Err(
+                SetError::new(SetErrorType::TooLarge).with_description(format!(
+                    "Contact card exceeds the maximum size of {} bytes.",
+                    self.core.jmap.contact_max_size
+                )),
+            );
+        }
+
+        Ok(card)
+    }
+
+    pub async fn contact_card_remove_from_address_book(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        address_book_id: u32,
+        changes: &mut ChangeLogBuilder,
+    ) -> Result<Result<(), SetError>, MethodError> {
+        let current = if let Some(current) = self
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::ContactCard,
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            current
+        } else {
+            return Ok(Ok(()));
+        };
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::ContactCard);
+        let address_book_ids = current
+            .inner
+            .address_book_ids()
+            .filter(|id| *id != address_book_id)
+            .map(|id| Value::Id(Id::from(id)))
+            .collect::<Vec<_>>();
+        if !address_book_ids.is_empty() {
+            let mut card = current.inner.clone();
+            card.set(Property::AddressBookIds, Value::List(address_book_ids));
+            let size = (&card).serialize().len() as i64 - (&current.inner).serialize().len() as i64;
+            batch
+                .update_document(document_id)
+                .add(DirectoryClass::UsedQuota(account_id), size)
+                .custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_changes(card.changes_from(&current.inner))
+                        .with_current(current),
+                );
+            changes.log_update(Collection::ContactCard, document_id);
+        } else {
+            batch
+                .delete_document(document_id)
+                .add(
+                    DirectoryClass::UsedQuota(account_id),
+                    -((&current.inner).serialize().len() as i64),
+                )
+                .custom(ContactTextIndex::new(Some(&current.inner), None))
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_current(current));
+            changes.log_delete(Collection::ContactCard, document_id);
+        }
+
+        match self.core.storage.data.write(batch.build()).await {
+            Ok(_) => Ok(Ok(())),
+            Err(store::Error::AssertValueFailed) => Ok(Err(SetError::forbidden()
+                .with_description(concat!(
+                    "Another process modified a contact card in this address book ",
+                    "while deleting it, please try again."
+                )))),
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "contact_card_set",
+                    account_id = account_id,
+                    document_id = document_id,
+                    error = ?err,
+                    "Failed to update contact card while deleting address book.");
+                Err(MethodError::ServerPartialFail)
+            }
+        }
+    }
+}
+
+pub trait ContactCardObject {
+    fn address_book_ids(&self) -> impl Iterator<Item = u32> + '_;
+    fn changes_from(&self, current: &Object<Value>) -> Object<Value>;
+    fn patch_json(&mut self, path: &str, value: Value) -> bool;
+}
+
+impl ContactCardObject for Object<Value> {
+    fn address_book_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.get(&Property::AddressBookIds)
+            .as_list()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_id().map(|id| id.document_id()))
+    }
+
+    fn changes_from(&self, current: &Object<Value>) -> Object<Value> {
+        let mut changes = Object::with_capacity(self.properties.len());
+        for (property, value) in self.properties.iter() {
+            if current.get(property) != value {
+                changes.append(property.clone(), value.clone());
+            }
+        }
+        for property in current.properties.keys() {
+            if !self.properties.contains_key(property) {
+                changes.append(property.clone(), Value::Null);
+            }
+        }
+        changes
+    }
+
+    fn patch_json(&mut self, path: &str, value: Value) -> bool {
+        let mut path = path
+            .split('/')
+            .map(|item| item.replace("~1", "/").replace("~0", "~"));
+        let property = Property::parse(&path.next().unwrap_or_default());
+        let mut path = path.collect::<Vec<_>>();
+        let key = if let Some(key) = path.pop() {
+            Property::_T(key)
+        } else {
+            return false;
+        };
+
+        let mut obj = match self.properties.get_mut(&property) {
+            Some(Value::Object(obj)) => obj,
+            _ => return false,
+        };
+        for item in path {
+            obj = match obj.properties.get_mut(&Property::_T(item)) {
+                Some(Value::Object(obj)) => obj,
+                _ => return false,
+            };
+        }
+        if value != Value::Null {
+            obj.set(key, value);
+        } else {
+            obj.remove(&key);
+        }
+        true
+    }
+}
+
+pub(crate) fn generate_uid() -> String {
+    format!(
+        "urn:uuid:{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        rand::random::<u32>(),
+        rand::random::<u16>(),
+        rand::random::<u16>() & 0x0fff,
+        (rand::random::<u16>() & 0x3fff) | 0x8000,
+        rand::random::<u64>() & 0xffff_ffff_ffff
+    )
+}
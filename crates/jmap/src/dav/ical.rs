@@ -0,0 +1,821 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::fmt::Write;
+
+use chrono::NaiveDateTime;
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+use crate::{
+    calendar_event::{
+        recurrence::{
+            format_duration, format_local_date_time, parse_duration, parse_local_date_time,
+        },
+        scheduling::ItipMethod,
+        EventFields,
+    },
+    contact::set::ContactCardObject,
+};
+
+use super::text::{escape_text, parse_content_lines, split_unescaped, ContentLine};
+
+// Properties of an event that describe its recurrence and are never part of
+// an override.
+const RECURRENCE_PROPERTIES: [&str; 4] = [
+    "recurrenceRules",
+    "excludedRecurrenceRules",
+    "recurrenceOverrides",
+    "recurrenceId",
+];
+
+// Converts a JSCalendar event (RFC 8984) to an iCalendar object (RFC 5545).
+// Overrides of recurring events are written as separate components with
+// a RECURRENCE-ID, excluded instances as EXDATE values. When an iTIP method
+// is provided, the object is built for scheduling as described in RFC 5546.
+pub fn event_to_icalendar(
+    event: &Object<Value>,
+    method: Option<ItipMethod>,
+    reply_from: Option<&str>,
+) -> String {
+    let mut ical = String::with_capacity(1024);
+    let mut write = |line: ContentLine| line.write(&mut ical);
+
+    write(ContentLine::new("BEGIN", "VCALENDAR"));
+    write(ContentLine::new(
+        "PRODID",
+        "-//Stalwart Labs Ltd.//Stalwart Mail Server//EN",
+    ));
+    write(ContentLine::new("VERSION", "2.0"));
+    if let Some(method) = method {
+        write(ContentLine::new("METHOD", method.as_str()));
+    }
+    for line in event_to_vevent(event, None, method, reply_from) {
+        write(line);
+    }
+
+    // Replies only describe the master event
+    if method != Some(ItipMethod::Reply) {
+        for (recurrence_id, patch) in event
+            .event_obj("recurrenceOverrides")
+            .into_iter()
+            .flat_map(|overrides| overrides.properties.iter())
+        {
+            let (recurrence_id, patch) = match (
+                parse_local_date_time(&recurrence_id.to_string()),
+                patch.as_obj(),
+            ) {
+                (Some(recurrence_id), Some(patch))
+                    if !matches!(patch.event_value("excluded"), Value::Bool(true)) =>
+                {
+                    (recurrence_id, patch)
+                }
+                _ => continue,
+            };
+
+            let mut instance = event.clone();
+            for property in RECURRENCE_PROPERTIES {
+                instance.remove(&Property::parse(property));
+            }
+            instance.set(
+                Property::parse("start"),
+                Value::Text(format_local_date_time(&recurrence_id)),
+            );
+            for (property, value) in patch.properties.iter() {
+                let path = property.to_string();
+                if path.contains('/') {
+                    instance.patch_json(&path, value.clone());
+                } else if value != &Value::Null {
+                    instance.set(Property::parse(&path), value.clone());
+                } else {
+                    instance.remove(&Property::parse(&path));
+                }
+            }
+            for line in event_to_vevent(&instance, Some(recurrence_id), method, reply_from) {
+                write(line);
+            }
+        }
+    }
+    write(ContentLine::new("END", "VCALENDAR"));
+
+    ical
+}
+
+fn event_to_vevent(
+    event: &Object<Value>,
+    recurrence_id: Option<NaiveDateTime>,
+    method: Option<ItipMethod>,
+    reply_from: Option<&str>,
+) -> Vec<ContentLine> {
+    let mut lines = Vec::with_capacity(16);
+    lines.push(ContentLine::new("BEGIN", "VEVENT"));
+    if let Some(uid) = event.get(&Property::Uid).as_string() {
+        lines.push(ContentLine::new("UID", escape_text(uid)));
+    }
+    lines.push(ContentLine::new(
+        "DTSTAMP",
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+    ));
+    lines.push(ContentLine::new(
+        "SEQUENCE",
+        event.event_uint("sequence").unwrap_or(0).to_string(),
+    ));
+
+    let time_zone = event.event_str("timeZone");
+    let show_without_time = matches!(event.event_value("showWithoutTime"), Value::Bool(true));
+    if let Some(recurrence_id) = recurrence_id {
+        lines.push(date_time_line(
+            "RECURRENCE-ID",
+            recurrence_id,
+            time_zone,
+            show_without_time,
+        ));
+    }
+    if let Some(start) = event.event_str("start").and_then(parse_local_date_time) {
+        lines.push(date_time_line(
+            "DTSTART",
+            start,
+            time_zone,
+            show_without_time,
+        ));
+    }
+    if let Some(duration) = event.event_str("duration") {
+        lines.push(ContentLine::new("DURATION", duration));
+    }
+
+    // Recurrence rules and exclusions
+    for (name, property) in [
+        ("RRULE", "recurrenceRules"),
+        ("EXRULE", "excludedRecurrenceRules"),
+    ] {
+        for rule in event
+            .event_list(property)
+            .into_iter()
+            .flatten()
+            .filter_map(|rule| rule.as_obj())
+        {
+            lines.push(ContentLine::new(name, rule_to_rrule(rule)));
+        }
+    }
+    for (recurrence_id, patch) in event
+        .event_obj("recurrenceOverrides")
+        .into_iter()
+        .flat_map(|overrides| overrides.properties.iter())
+    {
+        if let (Some(recurrence_id), Some(true)) = (
+            parse_local_date_time(&recurrence_id.to_string()),
+            patch
+                .as_obj()
+                .map(|patch| matches!(patch.event_value("excluded"), Value::Bool(true))),
+        ) {
+            lines.push(date_time_line(
+                "EXDATE",
+                recurrence_id,
+                time_zone,
+                show_without_time,
+            ));
+        }
+    }
+
+    // Descriptive properties
+    for (name, value) in [
+        ("SUMMARY", event.event_str("title")),
+        ("DESCRIPTION", event.event_str("description")),
+        ("LOCATION", event.event_locations().first().copied()),
+    ] {
+        if let Some(value) = value {
+            lines.push(ContentLine::new(name, escape_text(value)));
+        }
+    }
+    if let Some(keywords) = event.event_obj("keywords") {
+        let keywords = keywords
+            .properties
+            .keys()
+            .map(|keyword| escape_text(&keyword.to_string()))
+            .collect::<Vec<_>>();
+        if !keywords.is_empty() {
+            lines.push(ContentLine::new("CATEGORIES", keywords.join(",")));
+        }
+    }
+    if let Some(priority) = event.event_uint("priority").filter(|p| *p > 0) {
+        lines.push(ContentLine::new("PRIORITY", priority.to_string()));
+    }
+    if let Some(privacy) = event.event_str("privacy") {
+        lines.push(ContentLine::new(
+            "CLASS",
+            match privacy {
+                "private" => "PRIVATE",
+                "secret" => "CONFIDENTIAL",
+                _ => "PUBLIC",
+            },
+        ));
+    }
+    if let Some(free_busy) = event.event_str("freeBusyStatus") {
+        lines.push(ContentLine::new(
+            "TRANSP",
+            if free_busy == "free" {
+                "TRANSPARENT"
+            } else {
+                "OPAQUE"
+            },
+        ));
+    }
+    if method == Some(ItipMethod::Cancel) {
+        lines.push(ContentLine::new("STATUS", "CANCELLED"));
+    } else if let Some(status) = event.event_str("status") {
+        lines.push(ContentLine::new("STATUS", status.to_ascii_uppercase()));
+    }
+
+    // Participants
+    if let Some(organizer) = event.event_organizer() {
+        let mut line = ContentLine::new("ORGANIZER", format!("mailto:{organizer}"));
+        if let Some(name) = event
+            .event_participants()
+            .into_iter()
+            .find(|p| p.email == Some(organizer))
+            .and_then(|p| p.name)
+        {
+            line = line.with_param("CN", name);
+        }
+        lines.push(line);
+    }
+    for participant in event.event_participants() {
+        let email = match participant.email {
+            Some(email) if participant.is_attendee => email,
+            _ => continue,
+        };
+        if reply_from.is_some_and(|reply_from| reply_from != email) {
+            continue;
+        }
+        let mut line = ContentLine::new("ATTENDEE", format!("mailto:{email}"));
+        if let Some(name) = participant.name {
+            line = line.with_param("CN", name);
+        }
+        line = line.with_param(
+            "PARTSTAT",
+            participant
+                .participation_status
+                .unwrap_or("needs-action")
+                .to_ascii_uppercase(),
+        );
+        if participant.expect_reply {
+            line = line.with_param("RSVP", "TRUE");
+        }
+        lines.push(line);
+    }
+    lines.push(ContentLine::new("END", "VEVENT"));
+
+    lines
+}
+
+// Converts an iCalendar object containing a single event, and optionally
+// overrides of its instances, to a JSCalendar event.
+pub fn icalendar_to_event(text: &str) -> Result<Object<Value>, &'static str> {
+    let lines = parse_content_lines(text);
+    if !lines
+        .first()
+        .is_some_and(|line| line.is("BEGIN") && line.value.eq_ignore_ascii_case("VCALENDAR"))
+    {
+        return Err("Expected an iCalendar object.");
+    }
+
+    // Collect the properties of each VEVENT, ignoring nested components
+    let mut components: Vec<Vec<ContentLine>> = Vec::new();
+    let mut component: Option<Vec<ContentLine>> = None;
+    let mut depth = 0;
+    for line in lines {
+        if line.is("BEGIN") {
+            depth += 1;
+            if depth == 2 && line.value.eq_ignore_ascii_case("VEVENT") {
+                component = Some(Vec::new());
+            }
+        } else if line.is("END") {
+            if depth == 2 {
+                if let Some(component) = component.take() {
+                    components.push(component);
+                }
+            }
+            depth -= 1;
+        } else if depth == 2 {
+            if let Some(component) = component.as_mut() {
+                component.push(line);
+            }
+        }
+    }
+
+    let mut master = None;
+    let mut overrides = Vec::new();
+    for component in components {
+        let recurrence_id = component
+            .iter()
+            .find(|line| line.is("RECURRENCE-ID"))
+            .and_then(|line| parse_date_time(&line.value))
+            .map(|(date_time, _)| date_time);
+        let event = vevent_to_event(component)?;
+        if let Some(recurrence_id) = recurrence_id {
+            overrides.push((recurrence_id, event));
+        } else if master.is_none() {
+            master = Some(event);
+        } else {
+            return Err("Only one event per resource is allowed.");
+        }
+    }
+    let mut master = master.ok_or("No event found.")?;
+    let uid = master
+        .get(&Property::Uid)
+        .as_string()
+        .map(|uid| uid.to_string());
+    if uid.is_none() {
+        return Err("Event is missing a UID.");
+    }
+
+    // Store overrides as patches of the master event
+    let recurrence_overrides = master
+        .properties
+        .get_mut_or_insert_with(Property::parse("recurrenceOverrides"), || {
+            Value::Object(Object::with_capacity(overrides.len()))
+        });
+    if let Value::Object(recurrence_overrides) = recurrence_overrides {
+        for (recurrence_id, event) in overrides {
+            if event.get(&Property::Uid).as_string() != uid.as_deref() {
+                return Err("Events in a resource must have the same UID.");
+            }
+            let mut patch = Object::with_capacity(event.properties.len());
+            for (property, value) in event.properties {
+                if !matches!(property, Property::Uid)
+                    && !RECURRENCE_PROPERTIES.contains(&property.to_string().as_str())
+                {
+                    patch.set(Property::_T(property.to_string()), value);
+                }
+            }
+            recurrence_overrides.set(
+                Property::_T(format_local_date_time(&recurrence_id)),
+                Value::Object(patch),
+            );
+        }
+    }
+    if master
+        .event_obj("recurrenceOverrides")
+        .is_some_and(|overrides| overrides.properties.is_empty())
+    {
+        master.remove(&Property::parse("recurrenceOverrides"));
+    }
+
+    Ok(master)
+}
+
+fn vevent_to_event(lines: Vec<ContentLine>) -> Result<Object<Value>, &'static str> {
+    let mut event = Object::with_capacity(lines.len() + 1);
+    event.set(Property::parse("@type"), Value::Text("Event".to_string()));
+    let mut start = None;
+    let mut end = None;
+    let mut participants: Vec<(String, Object<Value>)> = Vec::new();
+    let mut overrides = Object::with_capacity(0);
+    let mut rules = Vec::new();
+    let mut excluded_rules = Vec::new();
+
+    for line in lines {
+        match line.name.to_ascii_uppercase().as_str() {
+            "UID" => {
+                event.set(Property::Uid, Value::Text(line.text()));
+            }
+            "SUMMARY" => {
+                event.set(Property::parse("title"), Value::Text(line.text()));
+            }
+            "DESCRIPTION" => {
+                event.set(Property::Description, Value::Text(line.text()));
+            }
+            "LOCATION" => {
+                event.set(
+                    Property::parse("locations"),
+                    Value::Object(
+                        Object::with_capacity(1).with_property(
+                            Property::_T("l1".to_string()),
+                            Value::Object(
+                                Object::with_capacity(2)
+                                    .with_property(
+                                        Property::_T("@type".to_string()),
+                                        Value::Text("Location".to_string()),
+                                    )
+                                    .with_property(
+                                        Property::_T("name".to_string()),
+                                        Value::Text(line.text()),
+                                    ),
+                            ),
+                        ),
+                    ),
+                );
+            }
+            "DTSTART" => {
+                let (date_time, is_utc) =
+                    parse_date_time(&line.value).ok_or("Invalid DTSTART value.")?;
+                let is_date = line.value.len() == 8 || line.has_param_value("VALUE", "DATE");
+                event.set(
+                    Property::parse("start"),
+                    Value::Text(format_local_date_time(&date_time)),
+                );
+                if let Some(tz) = line.param("TZID") {
+                    event.set(Property::parse("timeZone"), Value::Text(tz.to_string()));
+                } else if is_utc {
+                    event.set(
+                        Property::parse("timeZone"),
+                        Value::Text("Etc/UTC".to_string()),
+                    );
+                }
+                if is_date {
+                    event.set(Property::parse("showWithoutTime"), Value::Bool(true));
+                }
+                start = Some(date_time);
+            }
+            "DTEND" => {
+                end = parse_date_time(&line.value).map(|(date_time, _)| date_time);
+            }
+            "DURATION" if parse_duration(&line.value).is_some() => {
+                event.set(Property::parse("duration"), Value::Text(line.value));
+            }
+            "RRULE" => {
+                rules.push(rrule_to_rule(&line.value).ok_or("Invalid RRULE value.")?);
+            }
+            "EXRULE" => {
+                excluded_rules.push(rrule_to_rule(&line.value).ok_or("Invalid EXRULE value.")?);
+            }
+            "EXDATE" | "RDATE" => {
+                for value in line.value.split(',') {
+                    if let Some((date_time, _)) = parse_date_time(value) {
+                        let patch = if line.is("EXDATE") {
+                            Object::with_capacity(1).with_property(
+                                Property::_T("excluded".to_string()),
+                                Value::Bool(true),
+                            )
+                        } else {
+                            Object::with_capacity(0)
+                        };
+                        overrides.set(
+                            Property::_T(format_local_date_time(&date_time)),
+                            Value::Object(patch),
+                        );
+                    }
+                }
+            }
+            "SEQUENCE" => {
+                if let Ok(sequence) = line.value.trim().parse::<u64>() {
+                    event.set(Property::parse("sequence"), Value::UnsignedInt(sequence));
+                }
+            }
+            "PRIORITY" => {
+                if let Ok(priority) = line.value.trim().parse::<u64>() {
+                    event.set(Property::parse("priority"), Value::UnsignedInt(priority));
+                }
+            }
+            "STATUS" => {
+                event.set(
+                    Property::parse("status"),
+                    Value::Text(line.value.to_ascii_lowercase()),
+                );
+            }
+            "TRANSP" => {
+                event.set(
+                    Property::parse("freeBusyStatus"),
+                    Value::Text(
+                        if line.value.eq_ignore_ascii_case("TRANSPARENT") {
+                            "free"
+                        } else {
+                            "busy"
+                        }
+                        .to_string(),
+                    ),
+                );
+            }
+            "CLASS" => {
+                event.set(
+                    Property::parse("privacy"),
+                    Value::Text(
+                        match line.value.to_ascii_uppercase().as_str() {
+                            "PRIVATE" => "private",
+                            "CONFIDENTIAL" => "secret",
+                            _ => "public",
+                        }
+                        .to_string(),
+                    ),
+                );
+            }
+            "CATEGORIES" => {
+                let keywords = event
+                    .properties
+                    .get_mut_or_insert_with(Property::parse("keywords"), || {
+                        Value::Object(Object::with_capacity(1))
+                    });
+                if let Value::Object(keywords) = keywords {
+                    for keyword in split_unescaped(&line.value, ',') {
+                        keywords.set(Property::_T(keyword), Value::Bool(true));
+                    }
+                }
+            }
+            "ORGANIZER" | "ATTENDEE" => {
+                let address = line.text();
+                let email = address
+                    .strip_prefix("mailto:")
+                    .or_else(|| address.strip_prefix("MAILTO:"))
+                    .unwrap_or(&address)
+                    .to_string();
+                let participant = if let Some((_, participant)) =
+                    participants.iter_mut().find(|(id, _)| id == &email)
+                {
+                    participant
+                } else {
+                    let mut participant = Object::with_capacity(6);
+                    participant.set(
+                        Property::_T("@type".to_string()),
+                        Value::Text("Participant".to_string()),
+                    );
+                    participant.set(
+                        Property::_T("email".to_string()),
+                        Value::Text(email.clone()),
+                    );
+                    participant.set(
+                        Property::_T("sendTo".to_string()),
+                        Value::Object(Object::with_capacity(1).with_property(
+                            Property::_T("imip".to_string()),
+                            Value::Text(format!("mailto:{email}")),
+                        )),
+                    );
+                    participants.push((email.clone(), participant));
+                    &mut participants.last_mut().unwrap().1
+                };
+                if let Some(name) = line.param("CN") {
+                    participant.set(
+                        Property::_T("name".to_string()),
+                        Value::Text(name.to_string()),
+                    );
+                }
+                let role = if line.is("ORGANIZER") {
+                    event.set(
+                        Property::parse("replyTo"),
+                        Value::Object(Object::with_capacity(1).with_property(
+                            Property::_T("imip".to_string()),
+                            Value::Text(format!("mailto:{email}")),
+                        )),
+                    );
+                    "owner"
+                } else {
+                    if let Some(status) = line.param("PARTSTAT") {
+                        participant.set(
+                            Property::_T("participationStatus".to_string()),
+                            Value::Text(status.to_ascii_lowercase()),
+                        );
+                    }
+                    if line.has_param_value("RSVP", "TRUE") {
+                        participant.set(Property::_T("expectReply".to_string()), Value::Bool(true));
+                    }
+                    "attendee"
+                };
+                let roles = participant
+                    .properties
+                    .get_mut_or_insert_with(Property::_T("roles".to_string()), || {
+                        Value::Object(Object::with_capacity(1))
+                    });
+                if let Value::Object(roles) = roles {
+                    roles.set(Property::_T(role.to_string()), Value::Bool(true));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Calculate the duration from the end time
+    if let (Some(start), Some(end), Value::Null) =
+        (start, end, event.get(&Property::parse("duration")))
+    {
+        if end > start {
+            event.set(
+                Property::parse("duration"),
+                Value::Text(format_duration((end - start).num_seconds())),
+            );
+        }
+    }
+    if !rules.is_empty() {
+        event.set(Property::parse("recurrenceRules"), Value::List(rules));
+    }
+    if !excluded_rules.is_empty() {
+        event.set(
+            Property::parse("excludedRecurrenceRules"),
+            Value::List(excluded_rules),
+        );
+    }
+    if !overrides.properties.is_empty() {
+        event.set(
+            Property::parse("recurrenceOverrides"),
+            Value::Object(overrides),
+        );
+    }
+    if !participants.is_empty() {
+        let mut map = Object::with_capacity(participants.len());
+        for (num, (_, participant)) in participants.into_iter().enumerate() {
+            map.set(
+                Property::_T(format!("p{}", num + 1)),
+                Value::Object(participant),
+            );
+        }
+        event.set(Property::parse("participants"), Value::Object(map));
+    }
+
+    Ok(event)
+}
+
+fn rrule_to_rule(value: &str) -> Option<Value> {
+    let mut rule = Object::with_capacity(4);
+    rule.set(
+        Property::_T("@type".to_string()),
+        Value::Text("RecurrenceRule".to_string()),
+    );
+    for part in value.split(';') {
+        let (name, value) = part.split_once('=')?;
+        let name = name.to_ascii_uppercase();
+        let int_list = || {
+            Value::List(
+                value
+                    .split(',')
+                    .filter_map(|value| value.trim().parse::<i64>().ok())
+                    .map(|value| {
+                        if value >= 0 {
+                            Value::UnsignedInt(value as u64)
+                        } else {
+                            Value::Int(value)
+                        }
+                    })
+                    .collect(),
+            )
+        };
+        let (property, value) = match name.as_str() {
+            "FREQ" => ("frequency", Value::Text(value.to_ascii_lowercase())),
+            "INTERVAL" => ("interval", Value::UnsignedInt(value.parse().ok()?)),
+            "COUNT" => ("count", Value::UnsignedInt(value.parse().ok()?)),
+            "UNTIL" => (
+                "until",
+                Value::Text(format_local_date_time(&parse_date_time(value)?.0)),
+            ),
+            "WKST" => ("firstDayOfWeek", Value::Text(value.to_ascii_lowercase())),
+            "BYDAY" => (
+                "byDay",
+                Value::List(
+                    value
+                        .split(',')
+                        .filter_map(|day| {
+                            let day = day.trim();
+                            let (nth, weekday) = day.split_at(day.len().checked_sub(2)?);
+                            let mut by_day = Object::with_capacity(3).with_property(
+                                Property::_T("@type".to_string()),
+                                Value::Text("NDay".to_string()),
+                            );
+                            by_day.set(
+                                Property::_T("day".to_string()),
+                                Value::Text(weekday.to_ascii_lowercase()),
+                            );
+                            if !nth.is_empty() {
+                                let nth = nth.trim_start_matches('+').parse::<i64>().ok()?;
+                                by_day.set(
+                                    Property::_T("nthOfPeriod".to_string()),
+                                    if nth >= 0 {
+                                        Value::UnsignedInt(nth as u64)
+                                    } else {
+                                        Value::Int(nth)
+                                    },
+                                );
+                            }
+                            Some(Value::Object(by_day))
+                        })
+                        .collect(),
+                ),
+            ),
+            "BYMONTH" => (
+                "byMonth",
+                Value::List(
+                    value
+                        .split(',')
+                        .map(|month| Value::Text(month.trim().to_ascii_uppercase()))
+                        .collect(),
+                ),
+            ),
+            "BYMONTHDAY" => ("byMonthDay", int_list()),
+            "BYYEARDAY" => ("byYearDay", int_list()),
+            "BYWEEKNO" => ("byWeekNo", int_list()),
+            "BYHOUR" => ("byHour", int_list()),
+            "BYMINUTE" => ("byMinute", int_list()),
+            "BYSECOND" => ("bySecond", int_list()),
+            "BYSETPOS" => ("bySetPosition", int_list()),
+            _ => continue,
+        };
+        rule.set(Property::_T(property.to_string()), value);
+    }
+
+    if rule.event_str("frequency").is_some() {
+        Some(Value::Object(rule))
+    } else {
+        None
+    }
+}
+
+fn rule_to_rrule(rule: &Object<Value>) -> String {
+    let mut rrule = format!(
+        "FREQ={}",
+        rule.event_str("frequency")
+            .unwrap_or("daily")
+            .to_ascii_uppercase()
+    );
+    if let Some(interval) = rule.event_uint("interval") {
+        let _ = write!(rrule, ";INTERVAL={interval}");
+    }
+    if let Some(count) = rule.event_uint("count") {
+        let _ = write!(rrule, ";COUNT={count}");
+    }
+    if let Some(until) = rule.event_str("until").and_then(parse_local_date_time) {
+        let _ = write!(rrule, ";UNTIL={}", until.format("%Y%m%dT%H%M%S"));
+    }
+    let by_day = rule
+        .event_list("byDay")
+        .into_iter()
+        .flatten()
+        .filter_map(|day| {
+            let day = day.as_obj()?;
+            let weekday = day.event_str("day")?.to_ascii_uppercase();
+            Some(match day.event_int("nthOfPeriod") {
+                Some(nth) => format!("{nth}{weekday}"),
+                None => weekday,
+            })
+        })
+        .collect::<Vec<_>>();
+    if !by_day.is_empty() {
+        let _ = write!(rrule, ";BYDAY={}", by_day.join(","));
+    }
+    for (name, values) in [
+        ("BYMONTHDAY", rule.event_int_list("byMonthDay")),
+        ("BYYEARDAY", rule.event_int_list("byYearDay")),
+        ("BYWEEKNO", rule.event_int_list("byWeekNo")),
+        ("BYHOUR", rule.event_int_list("byHour")),
+        ("BYMINUTE", rule.event_int_list("byMinute")),
+        ("BYSECOND", rule.event_int_list("bySecond")),
+        ("BYSETPOS", rule.event_int_list("bySetPosition")),
+    ] {
+        if !values.is_empty() {
+            let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            let _ = write!(rrule, ";{name}={}", values.join(","));
+        }
+    }
+    let by_month = rule
+        .event_list("byMonth")
+        .into_iter()
+        .flatten()
+        .filter_map(|month| month.as_string())
+        .collect::<Vec<_>>();
+    if !by_month.is_empty() {
+        let _ = write!(rrule, ";BYMONTH={}", by_month.join(","));
+    }
+    if let Some(first_day) = rule.event_str("firstDayOfWeek") {
+        let _ = write!(rrule, ";WKST={}", first_day.to_ascii_uppercase());
+    }
+
+    rrule
+}
+
+fn date_time_line(
+    name: &str,
+    date_time: NaiveDateTime,
+    time_zone: Option<&str>,
+    show_without_time: bool,
+) -> ContentLine {
+    if show_without_time {
+        ContentLine::new(name, date_time.format("%Y%m%d").to_string()).with_param("VALUE", "DATE")
+    } else {
+        match time_zone {
+            Some("UTC" | "Etc/UTC") => {
+                ContentLine::new(name, date_time.format("%Y%m%dT%H%M%SZ").to_string())
+            }
+            Some(tz) => ContentLine::new(name, date_time.format("%Y%m%dT%H%M%S").to_string())
+                .with_param("TZID", tz),
+            None => ContentLine::new(name, date_time.format("%Y%m%dT%H%M%S").to_string()),
+        }
+    }
+}
+
+// Parses an iCalendar DATE or DATE-TIME value, returning whether it is in UTC.
+fn parse_date_time(value: &str) -> Option<(NaiveDateTime, bool)> {
+    let value = value.trim();
+    let (value, is_utc) = value
+        .strip_suffix(['Z', 'z'])
+        .map_or((value, false), |value| (value, true));
+    if value.len() == 8 {
+        chrono::NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date_time| (date_time, is_utc))
+    } else {
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|date_time| (date_time, is_utc))
+    }
+}
@@ -0,0 +1,476 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::fmt::Write;
+
+use hyper::{header, Method, StatusCode};
+use jmap_proto::{
+    error::method::MethodError,
+    object::Object,
+    types::{acl::Acl, collection::Collection, id::Id, property::Property, value::Value},
+};
+use store::{query::Filter, roaring::RoaringBitmap, write::assert::HashedValue};
+
+use crate::{
+    api::{
+        http::{fetch_body, HttpSessionData, ToHttpResponse},
+        HttpRequest, HttpResponse,
+    },
+    auth::AccessToken,
+    JMAP,
+};
+
+use self::response::DavResponse;
+
+pub mod ical;
+pub mod propfind;
+pub mod report;
+pub mod request;
+pub mod resource;
+pub mod response;
+pub mod text;
+pub mod vcard;
+
+pub const DAV_CAPABILITIES: &str = "1, 3, addressbook, calendar-access";
+pub const DAV_METHODS: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, REPORT, MKCOL, MKCALENDAR";
+pub const SYNC_TOKEN_PREFIX: &str = "http://stalw.art/ns/sync/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DavCollection {
+    AddressBook,
+    Calendar,
+}
+
+// Resources are addressed as /dav/{card|cal}/<accountId>/<collectionId>/<name>,
+// where collections are identified by their JMAP id and items by their
+// percent-encoded UID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DavResource {
+    Root,
+    Principal {
+        account_id: u32,
+    },
+    Home {
+        collection: DavCollection,
+        account_id: u32,
+    },
+    Collection {
+        collection: DavCollection,
+        account_id: u32,
+        document_id: Option<u32>,
+        name: String,
+    },
+    Item {
+        collection: DavCollection,
+        account_id: u32,
+        document_id: Option<u32>,
+        uid: String,
+    },
+}
+
+pub struct DavItem {
+    pub document_id: u32,
+    pub value: HashedValue<Object<Value>>,
+}
+
+impl JMAP {
+    pub async fn handle_dav_request(
+        &self,
+        mut req: HttpRequest,
+        session: &HttpSessionData,
+    ) -> HttpResponse {
+        if req.method() == Method::OPTIONS {
+            return DavResponse::new(StatusCode::OK)
+                .with_header("DAV", DAV_CAPABILITIES)
+                .with_header("Allow", DAV_METHODS)
+                .into_http_response();
+        }
+
+        // Authenticate request
+        let access_token = match self.authenticate_headers(&req, session.remote_ip).await {
+            Ok(Some((_, access_token))) => access_token,
+            Ok(None) => {
+                return DavResponse::new(StatusCode::UNAUTHORIZED)
+                    .with_header(
+                        "WWW-Authenticate",
+                        "Basic realm=\"Stalwart Server\", charset=\"UTF-8\"",
+                    )
+                    .into_http_response()
+            }
+            Err(err) => return err.into_http_response(),
+        };
+
+        // Parse resource and validate access
+        let resource = match DavResource::parse(req.uri().path()) {
+            Some(resource) => resource,
+            None => return StatusCode::NOT_FOUND.into_http_response(),
+        };
+        if let (Some(account_id), Some(collection)) = (resource.account_id(), resource.collection())
+        {
+            if !access_token.has_access(account_id, collection.collection()) {
+                return StatusCode::FORBIDDEN.into_http_response();
+            }
+        }
+        let depth = match req
+            .headers()
+            .get("Depth")
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.trim())
+        {
+            Some("0") => 0,
+            _ => 1,
+        };
+        let if_match = req
+            .headers()
+            .get(header::IF_MATCH)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.trim().to_string());
+        let if_none_match = req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.trim().to_string());
+        let method = req.method().clone();
+        let body = match fetch_body(
+            &mut req,
+            std::cmp::max(
+                self.core.jmap.contact_max_size,
+                self.core.jmap.calendar_event_max_size,
+            ),
+        )
+        .await
+        {
+            Some(body) => body,
+            None => return StatusCode::PAYLOAD_TOO_LARGE.into_http_response(),
+        };
+
+        match method.as_str() {
+            "PROPFIND" => {
+                self.dav_propfind(&access_token, &resource, depth, &body)
+                    .await
+            }
+            "PROPPATCH" => self.dav_proppatch(&access_token, &resource, &body).await,
+            "MKCOL" | "MKCALENDAR" => self.dav_mkcol(&access_token, &resource, &body).await,
+            "REPORT" => self.dav_report(&access_token, &resource, &body).await,
+            "GET" | "HEAD" => {
+                self.dav_get(&access_token, &resource, method == Method::HEAD)
+                    .await
+            }
+            "PUT" => {
+                self.dav_put(
+                    &access_token,
+                    &resource,
+                    &session.instance,
+                    if_match,
+                    if_none_match,
+                    &body,
+                )
+                .await
+            }
+            "DELETE" => {
+                self.dav_delete(&access_token, &resource, &session.instance, if_match)
+                    .await
+            }
+            _ => {
+                Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)
+                    .with_header("Allow", DAV_METHODS))
+            }
+        }
+        .unwrap_or_else(DavResponse::from)
+        .with_header("DAV", DAV_CAPABILITIES)
+        .into_http_response()
+    }
+
+    // Returns the collections of an account the user is allowed to read.
+    pub async fn dav_collection_ids(
+        &self,
+        access_token: &AccessToken,
+        collection: DavCollection,
+        account_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut document_ids = match collection {
+            DavCollection::AddressBook => self.address_book_get_or_create(account_id).await?,
+            DavCollection::Calendar => self.calendar_get_or_create(account_id).await?,
+        };
+        if access_token.is_shared(account_id) {
+            document_ids &= self
+                .shared_documents(access_token, account_id, collection.collection(), Acl::Read)
+                .await?;
+        }
+        Ok(document_ids)
+    }
+
+    // Returns the items of a collection the user is allowed to read.
+    pub async fn dav_item_ids(
+        &self,
+        access_token: &AccessToken,
+        collection: DavCollection,
+        account_id: u32,
+        document_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut result_set = self
+            .filter(
+                account_id,
+                collection.item_collection(),
+                vec![Filter::eq(collection.item_ids_property(), document_id)],
+            )
+            .await?;
+        if access_token.is_shared(account_id) {
+            result_set.apply_mask(match collection {
+                DavCollection::AddressBook => {
+                    self.shared_contacts(access_token, account_id, Acl::ReadItems)
+                        .await?
+                }
+                DavCollection::Calendar => {
+                    self.shared_events(access_token, account_id, Acl::ReadItems)
+                        .await?
+                }
+            });
+        }
+        Ok(result_set.results)
+    }
+
+    // Resolves an item of a collection by its UID.
+    pub async fn dav_item(
+        &self,
+        access_token: &AccessToken,
+        collection: DavCollection,
+        account_id: u32,
+        document_id: u32,
+        uid: &str,
+    ) -> Result<Option<DavItem>, MethodError> {
+        let item_ids = self
+            .dav_item_ids(access_token, collection, account_id, document_id)
+            .await?;
+        let result_set = self
+            .filter(
+                account_id,
+                collection.item_collection(),
+                vec![Filter::eq(Property::Uid, uid.to_string())],
+            )
+            .await?;
+        for item_id in result_set.results {
+            if item_ids.contains(item_id) {
+                if let Some(value) = self
+                    .get_property::<HashedValue<Object<Value>>>(
+                        account_id,
+                        collection.item_collection(),
+                        item_id,
+                        Property::Value,
+                    )
+                    .await?
+                {
+                    return Ok(Some(DavItem {
+                        document_id: item_id,
+                        value,
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn dav_sync_token(
+        &self,
+        collection: DavCollection,
+        account_id: u32,
+    ) -> Result<String, MethodError> {
+        let state = self
+            .get_state(account_id, collection.item_collection())
+            .await?;
+        Ok(sync_token(
+            Some(state.get_change_id()).filter(|id| *id != u64::MAX),
+        ))
+    }
+}
+
+impl DavCollection {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "card" => Some(DavCollection::AddressBook),
+            "cal" => Some(DavCollection::Calendar),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DavCollection::AddressBook => "card",
+            DavCollection::Calendar => "cal",
+        }
+    }
+
+    pub fn collection(&self) -> Collection {
+        match self {
+            DavCollection::AddressBook => Collection::AddressBook,
+            DavCollection::Calendar => Collection::Calendar,
+        }
+    }
+
+    pub fn item_collection(&self) -> Collection {
+        match self {
+            DavCollection::AddressBook => Collection::ContactCard,
+            DavCollection::Calendar => Collection::CalendarEvent,
+        }
+    }
+
+    pub fn item_ids_property(&self) -> Property {
+        match self {
+            DavCollection::AddressBook => Property::AddressBookIds,
+            DavCollection::Calendar => Property::CalendarIds,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DavCollection::AddressBook => ".vcf",
+            DavCollection::Calendar => ".ics",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DavCollection::AddressBook => "text/vcard; charset=utf-8",
+            DavCollection::Calendar => "text/calendar; charset=utf-8",
+        }
+    }
+
+    pub fn home_href(&self, account_id: u32) -> String {
+        format!("/dav/{}/{}/", self.as_str(), Id::from(account_id))
+    }
+
+    pub fn collection_href(&self, account_id: u32, document_id: u32) -> String {
+        format!(
+            "/dav/{}/{}/{}/",
+            self.as_str(),
+            Id::from(account_id),
+            Id::from(document_id)
+        )
+    }
+
+    pub fn item_href(&self, account_id: u32, document_id: u32, item: &Object<Value>) -> String {
+        format!(
+            "{}{}{}",
+            self.collection_href(account_id, document_id),
+            encode_name(item.get(&Property::Uid).as_string().unwrap_or_default()),
+            self.extension()
+        )
+    }
+}
+
+impl DavResource {
+    pub fn parse(path: &str) -> Option<Self> {
+        let mut path = path
+            .strip_prefix("/dav")?
+            .split('/')
+            .filter(|segment| !segment.is_empty());
+        let kind = match path.next() {
+            Some(kind) => kind,
+            None => return Some(DavResource::Root),
+        };
+        let account_id = Id::from_bytes(path.next()?.as_bytes())?.document_id();
+        let resource = if kind == "principal" {
+            DavResource::Principal { account_id }
+        } else {
+            let collection = DavCollection::parse(kind)?;
+            match (path.next(), path.next()) {
+                (None, _) => DavResource::Home {
+                    collection,
+                    account_id,
+                },
+                (Some(name), None) => DavResource::Collection {
+                    collection,
+                    account_id,
+                    document_id: Id::from_bytes(name.as_bytes()).map(|id| id.document_id()),
+                    name: decode_name(name)?,
+                },
+                (Some(name), Some(item)) => DavResource::Item {
+                    collection,
+                    account_id,
+                    document_id: Id::from_bytes(name.as_bytes()).map(|id| id.document_id()),
+                    uid: decode_name(item.strip_suffix(collection.extension()).unwrap_or(item))?,
+                },
+            }
+        };
+        if path.next().is_none() {
+            Some(resource)
+        } else {
+            None
+        }
+    }
+
+    pub fn account_id(&self) -> Option<u32> {
+        match self {
+            DavResource::Root => None,
+            DavResource::Principal { account_id }
+            | DavResource::Home { account_id, .. }
+            | DavResource::Collection { account_id, .. }
+            | DavResource::Item { account_id, .. } => Some(*account_id),
+        }
+    }
+
+    pub fn collection(&self) -> Option<DavCollection> {
+        match self {
+            DavResource::Root | DavResource::Principal { .. } => None,
+            DavResource::Home { collection, .. }
+            | DavResource::Collection { collection, .. }
+            | DavResource::Item { collection, .. } => Some(*collection),
+        }
+    }
+}
+
+pub fn principal_href(account_id: u32) -> String {
+    format!("/dav/principal/{}/", Id::from(account_id))
+}
+
+pub fn sync_token(change_id: Option<u64>) -> String {
+    format!(
+        "{SYNC_TOKEN_PREFIX}{}",
+        change_id.map_or(0, |change_id| change_id + 1)
+    )
+}
+
+// Returns the change id encoded in a sync token, or None for the initial state.
+pub fn parse_sync_token(token: &str) -> Option<Option<u64>> {
+    token
+        .strip_prefix(SYNC_TOKEN_PREFIX)?
+        .parse::<u64>()
+        .ok()
+        .map(|id| id.checked_sub(1))
+}
+
+pub fn encode_name(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~' | b'@') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+pub fn decode_name(name: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(name.len());
+    let mut bytes = name.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+pub type DavResult = Result<DavResponse, MethodError>;
+
+pub(crate) fn etag(value: &HashedValue<Object<Value>>) -> String {
+    format!("\"{:x}\"", value.hash)
+}
@@ -0,0 +1,693 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use directory::QueryBy;
+use hyper::StatusCode;
+use jmap_proto::{
+    error::method::MethodError,
+    method::set::SetRequest,
+    object::{calendar, contact, Object},
+    types::{
+        acl::Acl,
+        id::Id,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use utils::map::{bitmap::Bitmap, vec_map::VecMap};
+
+use crate::{auth::acl::EffectiveAcl, auth::AccessToken, JMAP};
+
+use super::{
+    ical::event_to_icalendar,
+    principal_href,
+    request::{DavProperty, PropFind, PropertyUpdate, NS_CALDAV, NS_CARDDAV, NS_DAV},
+    resource::set_error_response,
+    response::{escape_xml, href_xml, DavResponse, MultiStatus, ResponseItem},
+    vcard::card_to_vcard,
+    DavCollection, DavItem, DavResource, DavResult,
+};
+
+const PRINCIPAL_PROPERTIES: &[DavProperty] = &[
+    DavProperty::ResourceType,
+    DavProperty::DisplayName,
+    DavProperty::CurrentUserPrincipal,
+    DavProperty::PrincipalUrl,
+    DavProperty::AddressBookHomeSet,
+    DavProperty::CalendarHomeSet,
+    DavProperty::CalendarUserAddressSet,
+];
+
+const HOME_PROPERTIES: &[DavProperty] = &[
+    DavProperty::ResourceType,
+    DavProperty::CurrentUserPrincipal,
+    DavProperty::Owner,
+    DavProperty::CurrentUserPrivilegeSet,
+];
+
+const ADDRESS_BOOK_PROPERTIES: &[DavProperty] = &[
+    DavProperty::ResourceType,
+    DavProperty::DisplayName,
+    DavProperty::AddressBookDescription,
+    DavProperty::CurrentUserPrincipal,
+    DavProperty::Owner,
+    DavProperty::CurrentUserPrivilegeSet,
+    DavProperty::SyncToken,
+    DavProperty::GetCTag,
+    DavProperty::SupportedReportSet,
+    DavProperty::SupportedAddressData,
+    DavProperty::AddressBookMaxResourceSize,
+];
+
+const CALENDAR_PROPERTIES: &[DavProperty] = &[
+    DavProperty::ResourceType,
+    DavProperty::DisplayName,
+    DavProperty::CalendarDescription,
+    DavProperty::CalendarColor,
+    DavProperty::CurrentUserPrincipal,
+    DavProperty::Owner,
+    DavProperty::CurrentUserPrivilegeSet,
+    DavProperty::SyncToken,
+    DavProperty::GetCTag,
+    DavProperty::SupportedReportSet,
+    DavProperty::SupportedCalendarComponentSet,
+    DavProperty::CalendarMaxResourceSize,
+];
+
+const ITEM_PROPERTIES: &[DavProperty] = &[
+    DavProperty::ResourceType,
+    DavProperty::GetETag,
+    DavProperty::GetContentType,
+];
+
+impl JMAP {
+    pub(super) async fn dav_propfind(
+        &self,
+        access_token: &AccessToken,
+        resource: &DavResource,
+        depth: u32,
+        body: &[u8],
+    ) -> DavResult {
+        let request = if let Some(request) = PropFind::parse(body) {
+            request
+        } else {
+            return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+        };
+        let mut multistatus = MultiStatus::default();
+
+        match resource {
+            DavResource::Root => {
+                multistatus.responses.push(
+                    self.dav_principal_response(access_token, None, &request)
+                        .await?,
+                );
+            }
+            DavResource::Principal { account_id } => {
+                if !access_token.is_member(*account_id) {
+                    return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+                }
+                multistatus.responses.push(
+                    self.dav_principal_response(access_token, Some(*account_id), &request)
+                        .await?,
+                );
+            }
+            DavResource::Home {
+                collection,
+                account_id,
+            } => {
+                let owner = href_xml(&principal_href(*account_id));
+                let current_user = href_xml(&principal_href(access_token.primary_id()));
+                let privileges = privilege_set(Bitmap::new().with_item(Acl::Read));
+                multistatus.responses.push(resolve_properties(
+                    collection.home_href(*account_id),
+                    &request,
+                    HOME_PROPERTIES,
+                    |property| match property {
+                        DavProperty::ResourceType => Some("<D:collection/>".to_string()),
+                        DavProperty::CurrentUserPrincipal => Some(current_user.clone()),
+                        DavProperty::Owner => Some(owner.clone()),
+                        DavProperty::CurrentUserPrivilegeSet => Some(privileges.clone()),
+                        _ => None,
+                    },
+                ));
+
+                if depth > 0 {
+                    for document_id in self
+                        .dav_collection_ids(access_token, *collection, *account_id)
+                        .await?
+                    {
+                        if let Some(response) = self
+                            .dav_collection_response(
+                                access_token,
+                                *collection,
+                                *account_id,
+                                document_id,
+                                &request,
+                            )
+                            .await?
+                        {
+                            multistatus.responses.push(response);
+                        }
+                    }
+                }
+            }
+            DavResource::Collection {
+                collection,
+                account_id,
+                document_id,
+                ..
+            } => {
+                let document_id = match document_id {
+                    Some(document_id)
+                        if self
+                            .dav_collection_ids(access_token, *collection, *account_id)
+                            .await?
+                            .contains(*document_id) =>
+                    {
+                        *document_id
+                    }
+                    _ => return Ok(DavResponse::new(StatusCode::NOT_FOUND)),
+                };
+                if let Some(response) = self
+                    .dav_collection_response(
+                        access_token,
+                        *collection,
+                        *account_id,
+                        document_id,
+                        &request,
+                    )
+                    .await?
+                {
+                    multistatus.responses.push(response);
+                }
+
+                if depth > 0 {
+                    for item_id in self
+                        .dav_item_ids(access_token, *collection, *account_id, document_id)
+                        .await?
+                    {
+                        if let Some(value) = self
+                            .get_property(
+                                *account_id,
+                                collection.item_collection(),
+                                item_id,
+                                Property::Value,
+                            )
+                            .await?
+                        {
+                            multistatus.responses.push(dav_item_response(
+                                *collection,
+                                *account_id,
+                                document_id,
+                                &DavItem {
+                                    document_id: item_id,
+                                    value,
+                                },
+                                &request,
+                            ));
+                        }
+                    }
+                }
+            }
+            DavResource::Item {
+                collection,
+                account_id,
+                document_id: Some(document_id),
+                uid,
+            } => {
+                if let Some(item) = self
+                    .dav_item(access_token, *collection, *account_id, *document_id, uid)
+                    .await?
+                {
+                    multistatus.responses.push(dav_item_response(
+                        *collection,
+                        *account_id,
+                        *document_id,
+                        &item,
+                        &request,
+                    ));
+                } else {
+                    return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+                }
+            }
+            DavResource::Item { .. } => return Ok(DavResponse::new(StatusCode::NOT_FOUND)),
+        }
+
+        Ok(multistatus.into_response())
+    }
+
+    pub(super) async fn dav_proppatch(
+        &self,
+        access_token: &AccessToken,
+        resource: &DavResource,
+        body: &[u8],
+    ) -> DavResult {
+        let (collection, account_id, document_id) = match resource {
+            DavResource::Collection {
+                collection,
+                account_id,
+                document_id: Some(document_id),
+                ..
+            } => (*collection, *account_id, *document_id),
+            _ => return Ok(DavResponse::new(StatusCode::FORBIDDEN)),
+        };
+        let update = if let Some(update) = PropertyUpdate::parse(body) {
+            update
+        } else {
+            return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+        };
+
+        // Unsupported properties are reported as protected while the rest are applied
+        let mut response = ResponseItem::new(collection.collection_href(account_id, document_id));
+        let mut changes = Object {
+            properties: VecMap::with_capacity(update.set.len() + update.remove.len()),
+        };
+        for (property, value) in update
+            .set
+            .into_iter()
+            .map(|(property, value)| (property, Value::Text(value)))
+            .chain(
+                update
+                    .remove
+                    .into_iter()
+                    .map(|property| (property, Value::Null)),
+            )
+        {
+            if let Some(jmap_property) = collection_property(collection, &property) {
+                let value = match value {
+                    Value::Text(value) if jmap_property == Property::Name && value.is_empty() => {
+                        Value::Null
+                    }
+                    value => value,
+                };
+                changes
+                    .properties
+                    .set(jmap_property, SetValue::Value(value));
+                response.add_property(StatusCode::OK, property, String::new());
+            } else {
+                response.add_property(StatusCode::FORBIDDEN, property, String::new());
+            }
+        }
+
+        if !changes.properties.is_empty() {
+            let mut update = VecMap::with_capacity(1);
+            update.append(Id::from(document_id), changes);
+            let set_response = self
+                .dav_collection_set(access_token, collection, account_id, None, Some(update))
+                .await?;
+            if let Some(err) = set_response.not_updated.values().next() {
+                return Ok(set_error_response(err, collection));
+            };
+        }
+
+        Ok(MultiStatus {
+            responses: vec![response],
+            sync_token: None,
+        }
+        .into_response())
+    }
+
+    pub(super) async fn dav_mkcol(
+        &self,
+        access_token: &AccessToken,
+        resource: &DavResource,
+        body: &[u8],
+    ) -> DavResult {
+        let (collection, account_id, name) = match resource {
+            DavResource::Collection {
+                collection,
+                account_id,
+                document_id,
+                name,
+            } => {
+                if let Some(document_id) = document_id {
+                    if self
+                        .dav_collection_ids(access_token, *collection, *account_id)
+                        .await?
+                        .contains(*document_id)
+                    {
+                        return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED));
+                    }
+                }
+                (*collection, *account_id, name)
+            }
+            _ => return Ok(DavResponse::new(StatusCode::FORBIDDEN)),
+        };
+        let update = if let Some(update) = PropertyUpdate::parse(body) {
+            update
+        } else {
+            return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+        };
+
+        // The collection name is used as a display name unless one is provided
+        let mut object = Object {
+            properties: VecMap::with_capacity(update.set.len() + 1),
+        };
+        object.properties.set(
+            Property::Name,
+            SetValue::Value(Value::Text(name.to_string())),
+        );
+        for (property, value) in update.set {
+            if let Some(property) = collection_property(collection, &property) {
+                object
+                    .properties
+                    .set(property, SetValue::Value(Value::Text(value)));
+            }
+        }
+
+        let mut create = VecMap::with_capacity(1);
+        create.append("dav".to_string(), object);
+        let mut set_response = self
+            .dav_collection_set(access_token, collection, account_id, Some(create), None)
+            .await?;
+        if let Some(err) = set_response.not_created.values().next() {
+            return Ok(set_error_response(err, collection));
+        }
+        match set_response
+            .created
+            .remove("dav")
+            .and_then(|created| created.get(&Property::Id).as_id().copied())
+        {
+            Some(id) => Ok(DavResponse::new(StatusCode::CREATED).with_header(
+                "Location",
+                collection.collection_href(account_id, id.document_id()),
+            )),
+            None => Ok(DavResponse::new(StatusCode::INTERNAL_SERVER_ERROR)),
+        }
+    }
+
+    pub(super) async fn dav_collection_set(
+        &self,
+        access_token: &AccessToken,
+        collection: DavCollection,
+        account_id: u32,
+        create: Option<VecMap<String, Object<SetValue>>>,
+        update: Option<VecMap<Id, Object<SetValue>>>,
+    ) -> Result<jmap_proto::method::set::SetResponse, MethodError> {
+        let mut response = match collection {
+            DavCollection::AddressBook => {
+                self.address_book_set(
+                    SetRequest {
+                        account_id: Id::from(account_id),
+                        if_in_state: None,
+                        create,
+                        update,
+                        destroy: None,
+                        arguments: contact::SetArguments::default(),
+                    },
+                    access_token,
+                )
+                .await?
+            }
+            DavCollection::Calendar => {
+                self.calendar_set(
+                    SetRequest {
+                        account_id: Id::from(account_id),
+                        if_in_state: None,
+                        create,
+                        update,
+                        destroy: None,
+                        arguments: calendar::SetArguments::default(),
+                    },
+                    access_token,
+                )
+                .await?
+            }
+        };
+        if let Some(state_change) = response.state_change.take() {
+            self.broadcast_state_change(state_change).await;
+        }
+        Ok(response)
+    }
+
+    async fn dav_principal_response(
+        &self,
+        access_token: &AccessToken,
+        account_id: Option<u32>,
+        request: &PropFind,
+    ) -> Result<ResponseItem, MethodError> {
+        let (href, name, emails) = if let Some(account_id) = account_id {
+            let principal = self
+                .core
+                .storage
+                .directory
+                .query(QueryBy::Id(account_id), false)
+                .await
+                .map_err(|err| {
+                    tracing::error!(
+                        event = "error",
+                        context = "dav_propfind",
+                        error = ?err,
+                        "Failed to query directory.");
+                    MethodError::ServerPartialFail
+                })?;
+            (
+                principal_href(account_id),
+                principal
+                    .as_ref()
+                    .map(|principal| principal.name.clone())
+                    .unwrap_or_default(),
+                principal
+                    .map(|principal| principal.emails)
+                    .unwrap_or_default(),
+            )
+        } else {
+            ("/dav/".to_string(), String::new(), Vec::new())
+        };
+        let is_own = account_id == Some(access_token.primary_id());
+        let current_user = href_xml(&principal_href(access_token.primary_id()));
+
+        // Home sets include the accounts shared with the user
+        let home_set = |collection: DavCollection| {
+            let mut home_set = String::new();
+            if let Some(account_id) = account_id {
+                home_set.push_str(&href_xml(&collection.home_href(account_id)));
+                if is_own {
+                    for shared_id in access_token.shared_accounts(collection.collection()) {
+                        home_set.push_str(&href_xml(&collection.home_href(*shared_id)));
+                    }
+                }
+            }
+            home_set
+        };
+        let address_book_home_set = home_set(DavCollection::AddressBook);
+        let calendar_home_set = home_set(DavCollection::Calendar);
+
+        Ok(resolve_properties(
+            href.clone(),
+            request,
+            PRINCIPAL_PROPERTIES,
+            |property| match property {
+                DavProperty::ResourceType => Some(if account_id.is_some() {
+                    "<D:principal/>".to_string()
+                } else {
+                    "<D:collection/>".to_string()
+                }),
+                DavProperty::DisplayName if !name.is_empty() => Some(escape_xml(&name)),
+                DavProperty::CurrentUserPrincipal => Some(current_user.clone()),
+                DavProperty::PrincipalUrl if account_id.is_some() => Some(href_xml(&href)),
+                DavProperty::AddressBookHomeSet if account_id.is_some() => {
+                    Some(address_book_home_set.clone())
+                }
+                DavProperty::CalendarHomeSet if account_id.is_some() => {
+                    Some(calendar_home_set.clone())
+                }
+                DavProperty::CalendarUserAddressSet if !emails.is_empty() => Some(
+                    emails
+                        .iter()
+                        .map(|email| href_xml(&format!("mailto:{email}")))
+                        .collect(),
+                ),
+                _ => None,
+            },
+        ))
+    }
+
+    pub(super) async fn dav_collection_response(
+        &self,
+        access_token: &AccessToken,
+        collection: DavCollection,
+        account_id: u32,
+        document_id: u32,
+        request: &PropFind,
+    ) -> Result<Option<ResponseItem>, MethodError> {
+        let values = if let Some(values) = self
+            .get_property::<Object<Value>>(
+                account_id,
+                collection.collection(),
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            values
+        } else {
+            return Ok(None);
+        };
+        let acl = if access_token.is_shared(account_id) {
+            values.effective_acl(access_token)
+        } else {
+            Bitmap::all()
+        };
+        let sync_token = self.dav_sync_token(collection, account_id).await?;
+        let owner = href_xml(&principal_href(account_id));
+        let current_user = href_xml(&principal_href(access_token.primary_id()));
+        let text = |property: Property| values.get(&property).as_string().map(escape_xml);
+
+        Ok(Some(resolve_properties(
+            collection.collection_href(account_id, document_id),
+            request,
+            match collection {
+                DavCollection::AddressBook => ADDRESS_BOOK_PROPERTIES,
+                DavCollection::Calendar => CALENDAR_PROPERTIES,
+            },
+            |property| match (collection, property) {
+                (DavCollection::AddressBook, DavProperty::ResourceType) => {
+                    Some("<D:collection/><CR:addressbook/>".to_string())
+                }
+                (DavCollection::Calendar, DavProperty::ResourceType) => {
+                    Some("<D:collection/><C:calendar/>".to_string())
+                }
+                (_, DavProperty::DisplayName) => text(Property::Name),
+                (DavCollection::AddressBook, DavProperty::AddressBookDescription)
+                | (DavCollection::Calendar, DavProperty::CalendarDescription) => {
+                    text(Property::Description)
+                }
+                (DavCollection::Calendar, DavProperty::CalendarColor) => text(Property::Color),
+                (_, DavProperty::CurrentUserPrincipal) => Some(current_user.clone()),
+                (_, DavProperty::Owner) => Some(owner.clone()),
+                (_, DavProperty::CurrentUserPrivilegeSet) => Some(privilege_set(acl)),
+                (_, DavProperty::SyncToken | DavProperty::GetCTag) => Some(escape_xml(&sync_token)),
+                (DavCollection::AddressBook, DavProperty::SupportedReportSet) => {
+                    Some(supported_reports(&[
+                        (NS_DAV, "D:sync-collection"),
+                        (NS_CARDDAV, "CR:addressbook-multiget"),
+                        (NS_CARDDAV, "CR:addressbook-query"),
+                    ]))
+                }
+                (DavCollection::Calendar, DavProperty::SupportedReportSet) => {
+                    Some(supported_reports(&[
+                        (NS_DAV, "D:sync-collection"),
+                        (NS_CALDAV, "C:calendar-multiget"),
+                        (NS_CALDAV, "C:calendar-query"),
+                    ]))
+                }
+                (DavCollection::AddressBook, DavProperty::SupportedAddressData) => Some(
+                    "<CR:address-data-type content-type=\"text/vcard\" version=\"3.0\"/>"
+                        .to_string(),
+                ),
+                (DavCollection::Calendar, DavProperty::SupportedCalendarComponentSet) => {
+                    Some("<C:comp name=\"VEVENT\"/>".to_string())
+                }
+                (DavCollection::AddressBook, DavProperty::AddressBookMaxResourceSize) => {
+                    Some(self.core.jmap.contact_max_size.to_string())
+                }
+                (DavCollection::Calendar, DavProperty::CalendarMaxResourceSize) => {
+                    Some(self.core.jmap.calendar_event_max_size.to_string())
+                }
+                _ => None,
+            },
+        )))
+    }
+}
+
+pub(super) fn dav_item_response(
+    collection: DavCollection,
+    account_id: u32,
+    document_id: u32,
+    item: &DavItem,
+    request: &PropFind,
+) -> ResponseItem {
+    resolve_properties(
+        collection.item_href(account_id, document_id, &item.value.inner),
+        request,
+        ITEM_PROPERTIES,
+        |property| match (collection, property) {
+            (_, DavProperty::ResourceType) => Some(String::new()),
+            (_, DavProperty::GetETag) => Some(escape_xml(&super::etag(&item.value))),
+            (_, DavProperty::GetContentType) => Some(collection.content_type().to_string()),
+            (DavCollection::AddressBook, DavProperty::AddressData) => {
+                Some(escape_xml(&card_to_vcard(&item.value.inner)))
+            }
+            (DavCollection::Calendar, DavProperty::CalendarData) => Some(escape_xml(
+                &event_to_icalendar(&item.value.inner, None, None),
+            )),
+            _ => None,
+        },
+    )
+}
+
+// Builds a response with the requested properties, properties without a value
+// are reported as not found.
+fn resolve_properties(
+    href: String,
+    request: &PropFind,
+    all_properties: &[DavProperty],
+    value: impl Fn(&DavProperty) -> Option<String>,
+) -> ResponseItem {
+    let mut response = ResponseItem::new(href);
+    match request {
+        PropFind::AllProp => {
+            for property in all_properties {
+                if let Some(value) = value(property) {
+                    response.add_property(StatusCode::OK, property.clone(), value);
+                }
+            }
+        }
+        PropFind::PropName => {
+            for property in all_properties {
+                response.add_property(StatusCode::OK, property.clone(), String::new());
+            }
+        }
+        PropFind::Prop(properties) => {
+            for property in properties {
+                if let Some(value) = value(property) {
+                    response.add_property(StatusCode::OK, property.clone(), value);
+                } else {
+                    response.add_property(StatusCode::NOT_FOUND, property.clone(), String::new());
+                }
+            }
+        }
+    }
+    response
+}
+
+fn collection_property(collection: DavCollection, property: &DavProperty) -> Option<Property> {
+    match (collection, property) {
+        (_, DavProperty::DisplayName) => Some(Property::Name),
+        (DavCollection::AddressBook, DavProperty::AddressBookDescription)
+        | (DavCollection::Calendar, DavProperty::CalendarDescription) => {
+            Some(Property::Description)
+        }
+        (DavCollection::Calendar, DavProperty::CalendarColor) => Some(Property::Color),
+        _ => None,
+    }
+}
+
+fn privilege_set(acl: Bitmap<Acl>) -> String {
+    let mut privileges = String::new();
+    for (item, privilege) in [
+        (Acl::ReadItems, "D:read"),
+        (Acl::ModifyItems, "D:write-content"),
+        (Acl::Modify, "D:write-properties"),
+        (Acl::AddItems, "D:bind"),
+        (Acl::RemoveItems, "D:unbind"),
+    ] {
+        if acl.contains(item) {
+            privileges.push_str(&format!("<D:privilege><{privilege}/></D:privilege>"));
+        }
+    }
+    privileges
+}
+
+fn supported_reports(reports: &[(&str, &str)]) -> String {
+    reports
+        .iter()
+        .map(|(_, report)| {
+            format!("<D:supported-report><D:report><{report}/></D:report></D:supported-report>")
+        })
+        .collect()
+}
@@ -0,0 +1,245 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use hyper::StatusCode;
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+use store::{
+    query::log::{Change, Query},
+    roaring::RoaringBitmap,
+    write::assert::HashedValue,
+};
+
+use crate::{auth::AccessToken, calendar_event::recurrence::ExpandRecurrence, JMAP};
+
+use super::{
+    parse_sync_token,
+    propfind::dav_item_response,
+    request::{PropFind, ReportRequest, NS_DAV},
+    response::{DavResponse, MultiStatus, ResponseItem},
+    DavCollection, DavItem, DavResource, DavResult,
+};
+
+impl JMAP {
+    pub(super) async fn dav_report(
+        &self,
+        access_token: &AccessToken,
+        resource: &DavResource,
+        body: &[u8],
+    ) -> DavResult {
+        let (collection, account_id, document_id) = match resource {
+            DavResource::Collection {
+                collection,
+                account_id,
+                document_id: Some(document_id),
+                ..
+            } if self
+                .dav_collection_ids(access_token, *collection, *account_id)
+                .await?
+                .contains(*document_id) =>
+            {
+                (*collection, *account_id, *document_id)
+            }
+            _ => return Ok(DavResponse::new(StatusCode::NOT_FOUND)),
+        };
+        let request = if let Some(request) = ReportRequest::parse(body) {
+            request
+        } else {
+            return Ok(DavResponse::new(StatusCode::BAD_REQUEST));
+        };
+        let item_ids = self
+            .dav_item_ids(access_token, collection, account_id, document_id)
+            .await?;
+        let mut multistatus = MultiStatus::default();
+
+        match request {
+            ReportRequest::SyncCollection { sync_token, props } => {
+                let since = match sync_token.as_deref().filter(|token| !token.is_empty()) {
+                    Some(token) => match parse_sync_token(token) {
+                        Some(since) => since,
+                        None => return Ok(invalid_sync_token()),
+                    },
+                    None => None,
+                };
+
+                if let Some(since) = since {
+                    let changes = self
+                        .changes_(
+                            account_id,
+                            collection.item_collection(),
+                            Query::Since(since),
+                        )
+                        .await?;
+
+                    // Destroyed items can no longer be mapped to their UID-based hrefs,
+                    // clients are asked to perform a full sync instead.
+                    if changes
+                        .changes
+                        .iter()
+                        .any(|change| matches!(change, Change::Delete(_)))
+                    {
+                        return Ok(invalid_sync_token());
+                    }
+
+                    let mut changed_ids = RoaringBitmap::new();
+                    for change in changes.changes {
+                        if let Change::Insert(id) | Change::Update(id) | Change::ChildUpdate(id) =
+                            change
+                        {
+                            changed_ids.insert(id as u32);
+                        }
+                    }
+                    for item_id in changed_ids {
+                        multistatus.responses.push(
+                            self.dav_report_item(
+                                collection,
+                                account_id,
+                                document_id,
+                                item_id,
+                                item_ids.contains(item_id),
+                                &props,
+                            )
+                            .await?,
+                        );
+                    }
+                } else {
+                    for item_id in &item_ids {
+                        multistatus.responses.push(
+                            self.dav_report_item(
+                                collection,
+                                account_id,
+                                document_id,
+                                item_id,
+                                true,
+                                &props,
+                            )
+                            .await?,
+                        );
+                    }
+                }
+                multistatus.sync_token = self.dav_sync_token(collection, account_id).await?.into();
+            }
+            ReportRequest::Multiget { props, hrefs } => {
+                for href in hrefs {
+                    let item = match DavResource::parse(&href) {
+                        Some(DavResource::Item {
+                            collection: item_collection,
+                            account_id: item_account_id,
+                            document_id: Some(item_document_id),
+                            uid,
+                        }) if item_collection == collection
+                            && item_account_id == account_id
+                            && item_document_id == document_id =>
+                        {
+                            self.dav_item(access_token, collection, account_id, document_id, &uid)
+                                .await?
+                        }
+                        _ => None,
+                    };
+                    multistatus.responses.push(if let Some(item) = item {
+                        dav_item_response(collection, account_id, document_id, &item, &props)
+                    } else {
+                        ResponseItem::new(href).with_status(StatusCode::NOT_FOUND)
+                    });
+                }
+            }
+            ReportRequest::Query { props, time_range } => {
+                for item_id in &item_ids {
+                    let value = if let Some(value) = self
+                        .get_property::<HashedValue<Object<Value>>>(
+                            account_id,
+                            collection.item_collection(),
+                            item_id,
+                            Property::Value,
+                        )
+                        .await?
+                    {
+                        value
+                    } else {
+                        continue;
+                    };
+
+                    // Time ranges are matched against the actual occurrences of each event
+                    if let (DavCollection::Calendar, Some((from, to))) = (collection, time_range) {
+                        if value
+                            .inner
+                            .expand(from, to, 0, self.core.jmap.calendar_max_instances)
+                            .is_none_or(|expansion| expansion.occurrences.is_empty())
+                        {
+                            continue;
+                        }
+                    }
+
+                    multistatus.responses.push(dav_item_response(
+                        collection,
+                        account_id,
+                        document_id,
+                        &DavItem {
+                            document_id: item_id,
+                            value,
+                        },
+                        &props,
+                    ));
+                }
+            }
+        }
+
+        Ok(multistatus.into_response())
+    }
+
+    async fn dav_report_item(
+        &self,
+        collection: DavCollection,
+        account_id: u32,
+        document_id: u32,
+        item_id: u32,
+        is_member: bool,
+        props: &PropFind,
+    ) -> Result<ResponseItem, jmap_proto::error::method::MethodError> {
+        if is_member {
+            if let Some(value) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    collection.item_collection(),
+                    item_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                return Ok(dav_item_response(
+                    collection,
+                    account_id,
+                    document_id,
+                    &DavItem {
+                        document_id: item_id,
+                        value,
+                    },
+                    props,
+                ));
+            }
+        }
+
+        // Items moved out of the collection are reported as removed, their
+        // href is still known as the UID is immutable.
+        let href = self
+            .get_property::<Object<Value>>(
+                account_id,
+                collection.item_collection(),
+                item_id,
+                Property::Value,
+            )
+            .await?
+            .map(|value| collection.item_href(account_id, document_id, &value))
+            .unwrap_or_else(|| collection.collection_href(account_id, document_id));
+        Ok(ResponseItem::new(href).with_status(StatusCode::NOT_FOUND))
+    }
+}
+
+fn invalid_sync_token() -> DavResponse {
+    DavResponse::precondition(StatusCode::FORBIDDEN, NS_DAV, "valid-sync-token")
+}
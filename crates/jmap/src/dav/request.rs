@@ -0,0 +1,407 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use chrono::NaiveDateTime;
+use quick_xml::{
+    events::{BytesStart, Event},
+    name::ResolveResult,
+    NsReader,
+};
+
+pub const NS_DAV: &str = "DAV:";
+pub const NS_CARDDAV: &str = "urn:ietf:params:xml:ns:carddav";
+pub const NS_CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+pub const NS_CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+pub const NS_APPLE: &str = "http://apple.com/ns/ical/";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DavProperty {
+    ResourceType,
+    DisplayName,
+    GetETag,
+    GetContentType,
+    CurrentUserPrincipal,
+    PrincipalUrl,
+    Owner,
+    SyncToken,
+    SupportedReportSet,
+    CurrentUserPrivilegeSet,
+    GetCTag,
+    AddressBookHomeSet,
+    AddressBookDescription,
+    SupportedAddressData,
+    AddressData,
+    AddressBookMaxResourceSize,
+    CalendarHomeSet,
+    CalendarDescription,
+    SupportedCalendarComponentSet,
+    CalendarData,
+    CalendarUserAddressSet,
+    CalendarMaxResourceSize,
+    CalendarColor,
+    Other { namespace: String, name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropFind {
+    AllProp,
+    PropName,
+    Prop(Vec<DavProperty>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportRequest {
+    SyncCollection {
+        sync_token: Option<String>,
+        props: PropFind,
+    },
+    Multiget {
+        props: PropFind,
+        hrefs: Vec<String>,
+    },
+    Query {
+        props: PropFind,
+        time_range: Option<(i64, i64)>,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PropertyUpdate {
+    pub set: Vec<(DavProperty, String)>,
+    pub remove: Vec<DavProperty>,
+}
+
+// Minimal namespace-aware XML tree, WebDAV request bodies are small enough to
+// be parsed in full before being interpreted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmlElement {
+    pub namespace: String,
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    pub fn parse(bytes: &[u8]) -> Option<XmlElement> {
+        let mut reader = NsReader::from_reader(bytes);
+        reader.config_mut().trim_text(true);
+        let mut buf = Vec::with_capacity(128);
+        let mut stack: Vec<XmlElement> = Vec::new();
+
+        loop {
+            match reader.read_resolved_event_into(&mut buf).ok()? {
+                (ns, Event::Start(e)) => {
+                    stack.push(XmlElement::new(ns, &e));
+                }
+                (ns, Event::Empty(e)) => {
+                    let element = XmlElement::new(ns, &e);
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(element);
+                    } else {
+                        return Some(element);
+                    }
+                }
+                (_, Event::End(_)) => {
+                    let element = stack.pop()?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(element);
+                    } else {
+                        return Some(element);
+                    }
+                }
+                (_, Event::Text(text)) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&text.unescape().ok()?);
+                    }
+                }
+                (_, Event::CData(text)) => {
+                    if let Some(element) = stack.last_mut() {
+                        element
+                            .text
+                            .push_str(std::str::from_utf8(text.as_ref()).ok()?);
+                    }
+                }
+                (_, Event::Eof) => return None,
+                _ => (),
+            }
+            buf.clear();
+        }
+    }
+
+    fn new(namespace: ResolveResult<'_>, e: &BytesStart<'_>) -> Self {
+        XmlElement {
+            namespace: match namespace {
+                ResolveResult::Bound(ns) => String::from_utf8_lossy(ns.as_ref()).into_owned(),
+                _ => String::new(),
+            },
+            name: String::from_utf8_lossy(e.local_name().as_ref()).into_owned(),
+            attributes: e
+                .attributes()
+                .filter_map(|attr| {
+                    let attr = attr.ok()?;
+                    Some((
+                        String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned(),
+                        attr.unescape_value().ok()?.into_owned(),
+                    ))
+                })
+                .collect(),
+            text: String::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn is(&self, namespace: &str, name: &str) -> bool {
+        self.name == name && self.namespace == namespace
+    }
+
+    pub fn child(&self, namespace: &str, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.is(namespace, name))
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attr, _)| attr == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Finds the first descendant with the given name.
+    pub fn find(&self, namespace: &str, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find_map(|child| {
+            if child.is(namespace, name) {
+                Some(child)
+            } else {
+                child.find(namespace, name)
+            }
+        })
+    }
+}
+
+impl DavProperty {
+    pub fn parse(namespace: &str, name: &str) -> Self {
+        match (namespace, name) {
+            (NS_DAV, "resourcetype") => DavProperty::ResourceType,
+            (NS_DAV, "displayname") => DavProperty::DisplayName,
+            (NS_DAV, "getetag") => DavProperty::GetETag,
+            (NS_DAV, "getcontenttype") => DavProperty::GetContentType,
+            (NS_DAV, "current-user-principal") => DavProperty::CurrentUserPrincipal,
+            (NS_DAV, "principal-URL") => DavProperty::PrincipalUrl,
+            (NS_DAV, "owner") => DavProperty::Owner,
+            (NS_DAV, "sync-token") => DavProperty::SyncToken,
+            (NS_DAV, "supported-report-set") => DavProperty::SupportedReportSet,
+            (NS_DAV, "current-user-privilege-set") => DavProperty::CurrentUserPrivilegeSet,
+            (NS_CALENDARSERVER, "getctag") => DavProperty::GetCTag,
+            (NS_CARDDAV, "addressbook-home-set") => DavProperty::AddressBookHomeSet,
+            (NS_CARDDAV, "addressbook-description") => DavProperty::AddressBookDescription,
+            (NS_CARDDAV, "supported-address-data") => DavProperty::SupportedAddressData,
+            (NS_CARDDAV, "address-data") => DavProperty::AddressData,
+            (NS_CARDDAV, "max-resource-size") => DavProperty::AddressBookMaxResourceSize,
+            (NS_CALDAV, "calendar-home-set") => DavProperty::CalendarHomeSet,
+            (NS_CALDAV, "calendar-description") => DavProperty::CalendarDescription,
+            (NS_CALDAV, "supported-calendar-component-set") => {
+                DavProperty::SupportedCalendarComponentSet
+            }
+            (NS_CALDAV, "calendar-data") => DavProperty::CalendarData,
+            (NS_CALDAV, "calendar-user-address-set") => DavProperty::CalendarUserAddressSet,
+            (NS_CALDAV, "max-resource-size") => DavProperty::CalendarMaxResourceSize,
+            (NS_APPLE, "calendar-color") => DavProperty::CalendarColor,
+            _ => DavProperty::Other {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+            },
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        match self {
+            DavProperty::ResourceType
+            | DavProperty::DisplayName
+            | DavProperty::GetETag
+            | DavProperty::GetContentType
+            | DavProperty::CurrentUserPrincipal
+            | DavProperty::PrincipalUrl
+            | DavProperty::Owner
+            | DavProperty::SyncToken
+            | DavProperty::SupportedReportSet
+            | DavProperty::CurrentUserPrivilegeSet => NS_DAV,
+            DavProperty::GetCTag => NS_CALENDARSERVER,
+            DavProperty::AddressBookHomeSet
+            | DavProperty::AddressBookDescription
+            | DavProperty::SupportedAddressData
+            | DavProperty::AddressData
+            | DavProperty::AddressBookMaxResourceSize => NS_CARDDAV,
+            DavProperty::CalendarHomeSet
+            | DavProperty::CalendarDescription
+            | DavProperty::SupportedCalendarComponentSet
+            | DavProperty::CalendarData
+            | DavProperty::CalendarUserAddressSet
+            | DavProperty::CalendarMaxResourceSize => NS_CALDAV,
+            DavProperty::CalendarColor => NS_APPLE,
+            DavProperty::Other { namespace, .. } => namespace,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            DavProperty::ResourceType => "resourcetype",
+            DavProperty::DisplayName => "displayname",
+            DavProperty::GetETag => "getetag",
+            DavProperty::GetContentType => "getcontenttype",
+            DavProperty::CurrentUserPrincipal => "current-user-principal",
+            DavProperty::PrincipalUrl => "principal-URL",
+            DavProperty::Owner => "owner",
+            DavProperty::SyncToken => "sync-token",
+            DavProperty::SupportedReportSet => "supported-report-set",
+            DavProperty::CurrentUserPrivilegeSet => "current-user-privilege-set",
+            DavProperty::GetCTag => "getctag",
+            DavProperty::AddressBookHomeSet => "addressbook-home-set",
+            DavProperty::AddressBookDescription => "addressbook-description",
+            DavProperty::SupportedAddressData => "supported-address-data",
+            DavProperty::AddressData => "address-data",
+            DavProperty::AddressBookMaxResourceSize | DavProperty::CalendarMaxResourceSize => {
+                "max-resource-size"
+            }
+            DavProperty::CalendarHomeSet => "calendar-home-set",
+            DavProperty::CalendarDescription => "calendar-description",
+            DavProperty::SupportedCalendarComponentSet => "supported-calendar-component-set",
+            DavProperty::CalendarData => "calendar-data",
+            DavProperty::CalendarUserAddressSet => "calendar-user-address-set",
+            DavProperty::CalendarColor => "calendar-color",
+            DavProperty::Other { name, .. } => name,
+        }
+    }
+}
+
+impl PropFind {
+    // An empty PROPFIND body is equivalent to requesting all properties.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.iter().all(|ch| ch.is_ascii_whitespace()) {
+            return Some(PropFind::AllProp);
+        }
+        let root = XmlElement::parse(bytes)?;
+        if root.is(NS_DAV, "propfind") {
+            PropFind::from_element(&root)
+        } else {
+            None
+        }
+    }
+
+    fn from_element(element: &XmlElement) -> Option<Self> {
+        if let Some(prop) = element.child(NS_DAV, "prop") {
+            Some(PropFind::Prop(parse_props(prop)))
+        } else if element.child(NS_DAV, "propname").is_some() {
+            Some(PropFind::PropName)
+        } else if element.child(NS_DAV, "allprop").is_some() {
+            Some(PropFind::AllProp)
+        } else {
+            None
+        }
+    }
+}
+
+impl ReportRequest {
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let root = XmlElement::parse(bytes)?;
+        let props = PropFind::from_element(&root).unwrap_or(PropFind::AllProp);
+
+        match (root.namespace.as_str(), root.name.as_str()) {
+            (NS_DAV, "sync-collection") => Some(ReportRequest::SyncCollection {
+                sync_token: root
+                    .child(NS_DAV, "sync-token")
+                    .map(|token| token.text.trim().to_string())
+                    .filter(|token| !token.is_empty()),
+                props,
+            }),
+            (NS_CARDDAV, "addressbook-multiget") | (NS_CALDAV, "calendar-multiget") => {
+                Some(ReportRequest::Multiget {
+                    props,
+                    hrefs: root
+                        .children
+                        .iter()
+                        .filter(|child| child.is(NS_DAV, "href"))
+                        .map(|href| href.text.trim().to_string())
+                        .collect(),
+                })
+            }
+            (NS_CARDDAV, "addressbook-query") => Some(ReportRequest::Query {
+                props,
+                time_range: None,
+            }),
+            (NS_CALDAV, "calendar-query") => Some(ReportRequest::Query {
+                props,
+                time_range: root
+                    .child(NS_CALDAV, "filter")
+                    .and_then(|filter| filter.find(NS_CALDAV, "time-range"))
+                    .map(|range| {
+                        (
+                            range
+                                .attribute("start")
+                                .and_then(parse_utc_date_time)
+                                .unwrap_or(i64::MIN / 2),
+                            range
+                                .attribute("end")
+                                .and_then(parse_utc_date_time)
+                                .unwrap_or(i64::MAX / 2),
+                        )
+                    }),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl PropertyUpdate {
+    // Parses PROPPATCH, extended MKCOL (RFC 5689) and MKCALENDAR bodies.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut update = PropertyUpdate::default();
+        if bytes.iter().all(|ch| ch.is_ascii_whitespace()) {
+            return Some(update);
+        }
+        let root = XmlElement::parse(bytes)?;
+        if !(root.is(NS_DAV, "propertyupdate")
+            || root.is(NS_DAV, "mkcol")
+            || root.is(NS_CALDAV, "mkcalendar"))
+        {
+            return None;
+        }
+
+        for action in &root.children {
+            let is_set = action.is(NS_DAV, "set");
+            if !is_set && !action.is(NS_DAV, "remove") {
+                continue;
+            }
+            for prop in action
+                .children
+                .iter()
+                .filter(|child| child.is(NS_DAV, "prop"))
+                .flat_map(|prop| prop.children.iter())
+            {
+                let property = DavProperty::parse(&prop.namespace, &prop.name);
+                if is_set {
+                    update.set.push((property, prop.text.trim().to_string()));
+                } else {
+                    update.remove.push(property);
+                }
+            }
+        }
+
+        Some(update)
+    }
+}
+
+fn parse_props(prop: &XmlElement) -> Vec<DavProperty> {
+    prop.children
+        .iter()
+        .map(|child| DavProperty::parse(&child.namespace, &child.name))
+        .collect()
+}
+
+fn parse_utc_date_time(value: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(value.trim().trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|date_time| date_time.and_utc().timestamp())
+}
@@ -0,0 +1,485 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::sync::Arc;
+
+use common::listener::ServerInstance;
+use hyper::StatusCode;
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{RequestArguments, SetRequest, SetResponse},
+    object::{calendar, contact, Object},
+    request::reference::MaybeReference,
+    types::{
+        id::Id,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use store::write::assert::HashedValue;
+use utils::map::vec_map::VecMap;
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::{
+    etag,
+    ical::{event_to_icalendar, icalendar_to_event},
+    request::{NS_CALDAV, NS_CARDDAV},
+    response::DavResponse,
+    vcard::{card_to_vcard, vcard_to_card},
+    DavCollection, DavItem, DavResource, DavResult,
+};
+
+impl JMAP {
+    pub(super) async fn dav_get(
+        &self,
+        access_token: &AccessToken,
+        resource: &DavResource,
+        is_head: bool,
+    ) -> DavResult {
+        let (collection, item) = match resource {
+            DavResource::Item {
+                collection,
+                account_id,
+                document_id: Some(document_id),
+                uid,
+            } => (
+                *collection,
+                self.dav_item(access_token, *collection, *account_id, *document_id, uid)
+                    .await?,
+            ),
+            DavResource::Item { .. } => (DavCollection::AddressBook, None),
+            _ => return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        };
+        let item = if let Some(item) = item {
+            item
+        } else {
+            return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+        };
+
+        let response = DavResponse::new(StatusCode::OK).with_header("ETag", etag(&item.value));
+        if !is_head {
+            Ok(response.with_body(
+                collection.content_type(),
+                match collection {
+                    DavCollection::AddressBook => card_to_vcard(&item.value.inner),
+                    DavCollection::Calendar => event_to_icalendar(&item.value.inner, None, None),
+                },
+            ))
+        } else {
+            Ok(response.with_header("Content-Type", collection.content_type()))
+        }
+    }
+
+    pub(super) async fn dav_put(
+        &self,
+        access_token: &AccessToken,
+        resource: &DavResource,
+        instance: &Arc<ServerInstance>,
+        if_match: Option<String>,
+        if_none_match: Option<String>,
+        body: &[u8],
+    ) -> DavResult {
+        let (collection, account_id, document_id, uid) = match resource {
+            DavResource::Item {
+                collection,
+                account_id,
+                document_id: Some(document_id),
+                uid,
+            } if self
+                .dav_collection_ids(access_token, *collection, *account_id)
+                .await?
+                .contains(*document_id) =>
+            {
+                (*collection, *account_id, *document_id, uid)
+            }
+            DavResource::Item { .. } => return Ok(DavResponse::new(StatusCode::CONFLICT)),
+            _ => return Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        };
+
+        // Parse the resource
+        let object = match std::str::from_utf8(body)
+            .map_err(|_| "Invalid UTF-8 content.")
+            .and_then(|text| match collection {
+                DavCollection::AddressBook => vcard_to_card(text),
+                DavCollection::Calendar => icalendar_to_event(text),
+            }) {
+            Ok(object) => object,
+            Err(err) => {
+                return Ok(DavResponse::precondition(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    collection.namespace(),
+                    collection.valid_data_precondition(),
+                )
+                .with_header("X-Error", err))
+            }
+        };
+        if object.get(&Property::Uid).as_string() != Some(uid.as_str()) {
+            return Ok(DavResponse::precondition(
+                StatusCode::BAD_REQUEST,
+                collection.namespace(),
+                "no-uid-conflict",
+            ));
+        }
+
+        // Validate preconditions
+        let current = self
+            .dav_item(access_token, collection, account_id, document_id, uid)
+            .await?;
+        if !matches_preconditions(current.as_ref(), if_match, if_none_match) {
+            return Ok(DavResponse::new(StatusCode::PRECONDITION_FAILED));
+        }
+
+        let mut changes = Object {
+            properties: VecMap::with_capacity(object.properties.len()),
+        };
+        for (property, value) in object.properties {
+            if !is_protected_property(&property) || (property == Property::Uid && current.is_none())
+            {
+                changes.properties.append(property, SetValue::Value(value));
+            }
+        }
+        let (create, update) = if let Some(current) = &current {
+            // Properties not present in the new version are removed
+            for property in current.value.inner.properties.keys() {
+                if !is_protected_property(property) && !changes.properties.contains_key(property) {
+                    changes
+                        .properties
+                        .append(property.clone(), SetValue::Value(Value::Null));
+                }
+            }
+            let mut update = VecMap::with_capacity(1);
+            update.append(Id::from(current.document_id), changes);
+            (None, Some(update))
+        } else {
+            changes.properties.append(
+                collection.item_ids_property(),
+                SetValue::Value(Value::List(vec![Value::Id(Id::from(document_id))])),
+            );
+            let mut create = VecMap::with_capacity(1);
+            create.append("dav".to_string(), changes);
+            (Some(create), None)
+        };
+
+        let mut response = self
+            .dav_item_set(
+                access_token,
+                collection,
+                account_id,
+                instance,
+                create,
+                update,
+                None,
+            )
+            .await?;
+        let (status, item_id) = if let Some(current) = &current {
+            if let Some(err) = response.not_updated.values().next() {
+                return Ok(set_error_response(err, collection));
+            }
+            (StatusCode::NO_CONTENT, Some(current.document_id))
+        } else {
+            if let Some(err) = response.not_created.values().next() {
+                return Ok(set_error_response(err, collection));
+            }
+            (
+                StatusCode::CREATED,
+                response
+                    .created
+                    .remove("dav")
+                    .and_then(|created| created.get(&Property::Id).as_id().copied())
+                    .map(|id| id.document_id()),
+            )
+        };
+
+        let mut response = DavResponse::new(status);
+        if let Some(item_id) = item_id {
+            if let Some(value) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    collection.item_collection(),
+                    item_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                response = response.with_header("ETag", etag(&value));
+            }
+        }
+        Ok(response)
+    }
+
+    pub(super) async fn dav_delete(
+        &self,
+        access_token: &AccessToken,
+        resource: &DavResource,
+        instance: &Arc<ServerInstance>,
+        if_match: Option<String>,
+    ) -> DavResult {
+        match resource {
+            DavResource::Item {
+                collection,
+                account_id,
+                document_id: Some(document_id),
+                uid,
+            } => {
+                let collection = *collection;
+                let item = if let Some(item) = self
+                    .dav_item(access_token, collection, *account_id, *document_id, uid)
+                    .await?
+                {
+                    item
+                } else {
+                    return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+                };
+                if !matches_preconditions(Some(&item), if_match, None) {
+                    return Ok(DavResponse::new(StatusCode::PRECONDITION_FAILED));
+                }
+
+                // Items that belong to other collections are only removed from this one
+                let collection_id = Value::Id(Id::from(*document_id));
+                let other_ids = item
+                    .value
+                    .inner
+                    .get(&collection.item_ids_property())
+                    .as_list()
+                    .map(|ids| {
+                        ids.iter()
+                            .filter(|id| **id != collection_id)
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let response = if !other_ids.is_empty() {
+                    let mut update = VecMap::with_capacity(1);
+                    update.append(
+                        Id::from(item.document_id),
+                        Object {
+                            properties: VecMap::from_iter([(
+                                collection.item_ids_property(),
+                                SetValue::Value(Value::List(other_ids)),
+                            )]),
+                        },
+                    );
+                    self.dav_item_set(
+                        access_token,
+                        collection,
+                        *account_id,
+                        instance,
+                        None,
+                        Some(update),
+                        None,
+                    )
+                    .await?
+                    .not_updated
+                } else {
+                    self.dav_item_set(
+                        access_token,
+                        collection,
+                        *account_id,
+                        instance,
+                        None,
+                        None,
+                        Some(vec![Id::from(item.document_id)]),
+                    )
+                    .await?
+                    .not_destroyed
+                };
+
+                let response = if let Some(err) = response.values().next() {
+                    set_error_response(err, collection)
+                } else {
+                    DavResponse::new(StatusCode::NO_CONTENT)
+                };
+                Ok(response)
+            }
+            DavResource::Collection {
+                collection,
+                account_id,
+                document_id: Some(document_id),
+                ..
+            } => {
+                let destroy = Some(MaybeReference::Value(vec![Id::from(*document_id)]));
+                let mut response = match collection {
+                    DavCollection::AddressBook => {
+                        self.address_book_set(
+                            SetRequest {
+                                account_id: Id::from(*account_id),
+                                if_in_state: None,
+                                create: None,
+                                update: None,
+                                destroy,
+                                arguments: contact::SetArguments {
+                                    on_destroy_remove_contents: Some(true),
+                                },
+                            },
+                            access_token,
+                        )
+                        .await?
+                    }
+                    DavCollection::Calendar => {
+                        self.calendar_set(
+                            SetRequest {
+                                account_id: Id::from(*account_id),
+                                if_in_state: None,
+                                create: None,
+                                update: None,
+                                destroy,
+                                arguments: calendar::SetArguments {
+                                    on_destroy_remove_events: Some(true),
+                                },
+                            },
+                            access_token,
+                        )
+                        .await?
+                    }
+                };
+                if let Some(state_change) = response.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+
+                let response = if let Some(err) = response.not_destroyed.values().next() {
+                    set_error_response(err, *collection)
+                } else {
+                    DavResponse::new(StatusCode::NO_CONTENT)
+                };
+                Ok(response)
+            }
+            DavResource::Item { .. } | DavResource::Collection { .. } => {
+                Ok(DavResponse::new(StatusCode::NOT_FOUND))
+            }
+            _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn dav_item_set(
+        &self,
+        access_token: &AccessToken,
+        collection: DavCollection,
+        account_id: u32,
+        instance: &Arc<ServerInstance>,
+        create: Option<VecMap<String, Object<SetValue>>>,
+        update: Option<VecMap<Id, Object<SetValue>>>,
+        destroy: Option<Vec<Id>>,
+    ) -> Result<SetResponse, MethodError> {
+        let destroy = destroy.map(MaybeReference::Value);
+        let mut response = match collection {
+            DavCollection::AddressBook => {
+                self.contact_card_set(
+                    SetRequest {
+                        account_id: Id::from(account_id),
+                        if_in_state: None,
+                        create,
+                        update,
+                        destroy,
+                        arguments: RequestArguments::ContactCard,
+                    },
+                    access_token,
+                )
+                .await?
+            }
+            DavCollection::Calendar => {
+                // Scheduling messages are left to the CalDAV client
+                self.calendar_event_set(
+                    SetRequest {
+                        account_id: Id::from(account_id),
+                        if_in_state: None,
+                        create,
+                        update,
+                        destroy,
+                        arguments: calendar::EventSetArguments {
+                            send_scheduling_messages: Some(false),
+                        },
+                    },
+                    access_token,
+                    instance,
+                )
+                .await?
+            }
+        };
+        if let Some(state_change) = response.state_change.take() {
+            self.broadcast_state_change(state_change).await;
+        }
+        Ok(response)
+    }
+}
+
+impl DavCollection {
+    pub fn namespace(&self) -> &'static str {
+        match self {
+            DavCollection::AddressBook => NS_CARDDAV,
+            DavCollection::Calendar => NS_CALDAV,
+        }
+    }
+
+    fn valid_data_precondition(&self) -> &'static str {
+        match self {
+            DavCollection::AddressBook => "valid-address-data",
+            DavCollection::Calendar => "valid-calendar-data",
+        }
+    }
+}
+
+pub(super) fn set_error_response(err: &SetError, collection: DavCollection) -> DavResponse {
+    match err.type_ {
+        SetErrorType::Forbidden => DavResponse::new(StatusCode::FORBIDDEN),
+        SetErrorType::NotFound => DavResponse::new(StatusCode::NOT_FOUND),
+        SetErrorType::OverQuota => DavResponse::new(StatusCode::INSUFFICIENT_STORAGE),
+        SetErrorType::TooLarge => DavResponse::precondition(
+            StatusCode::FORBIDDEN,
+            collection.namespace(),
+            "max-resource-size",
+        ),
+        _ => {
+            let response = DavResponse::new(StatusCode::BAD_REQUEST);
+            if let Some(description) = &err.description {
+                response.with_header("X-Error", description.to_string())
+            } else {
+                response
+            }
+        }
+    }
+}
+
+fn matches_preconditions(
+    current: Option<&DavItem>,
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+) -> bool {
+    let matches = |condition: &str| {
+        condition.split(',').map(|tag| tag.trim()).any(|tag| {
+            tag == "*"
+                || current
+                    .is_some_and(|current| tag.trim_start_matches("W/") == etag(&current.value))
+        })
+    };
+    if let Some(if_match) = if_match {
+        if current.is_none() || !matches(&if_match) {
+            return false;
+        }
+    }
+    if let Some(if_none_match) = if_none_match {
+        if current.is_some() && matches(&if_none_match) {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_protected_property(property: &Property) -> bool {
+    matches!(
+        property,
+        Property::Id
+            | Property::Uid
+            | Property::AddressBookIds
+            | Property::CalendarIds
+            | Property::UtcStart
+            | Property::UtcEnd
+    )
+}
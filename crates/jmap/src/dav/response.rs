@@ -0,0 +1,223 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::fmt::Write;
+
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Bytes, StatusCode};
+use jmap_proto::error::method::MethodError;
+
+use crate::api::{http::ToHttpResponse, HttpResponse};
+
+use super::request::{DavProperty, NS_APPLE, NS_CALDAV, NS_CALENDARSERVER, NS_CARDDAV, NS_DAV};
+
+pub struct DavResponse {
+    pub status: StatusCode,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Option<(&'static str, Vec<u8>)>,
+}
+
+#[derive(Debug, Default)]
+pub struct MultiStatus {
+    pub responses: Vec<ResponseItem>,
+    pub sync_token: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ResponseItem {
+    pub href: String,
+    pub propstat: Vec<(StatusCode, Vec<(DavProperty, String)>)>,
+    pub status: Option<StatusCode>,
+}
+
+const NAMESPACES: [(&str, &str); 5] = [
+    ("D", NS_DAV),
+    ("C", NS_CALDAV),
+    ("CR", NS_CARDDAV),
+    ("CS", NS_CALENDARSERVER),
+    ("A", NS_APPLE),
+];
+
+impl DavResponse {
+    pub fn new(status: StatusCode) -> Self {
+        DavResponse {
+            status,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn with_body(mut self, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some((content_type, body.into()));
+        self
+    }
+
+    // Error responses with a precondition element, as described in RFC 4918
+    // section 16.
+    pub fn precondition(status: StatusCode, namespace: &str, name: &str) -> Self {
+        let (prefix, _) = NAMESPACES
+            .iter()
+            .find(|(_, ns)| *ns == namespace)
+            .copied()
+            .unwrap_or(NAMESPACES[0]);
+        let mut xml = xml_header("error");
+        let _ = write!(xml, "<{prefix}:{name}/></D:error>");
+        DavResponse::new(status).with_body("application/xml; charset=utf-8", xml)
+    }
+}
+
+impl MultiStatus {
+    pub fn into_response(self) -> DavResponse {
+        let mut xml = xml_header("multistatus");
+        for response in self.responses {
+            let _ = write!(
+                xml,
+                "<D:response><D:href>{}</D:href>",
+                escape_xml(&response.href)
+            );
+            if let Some(status) = response.status {
+                write_status(&mut xml, status);
+            } else {
+                for (status, properties) in &response.propstat {
+                    xml.push_str("<D:propstat><D:prop>");
+                    for (property, value) in properties {
+                        write_property(&mut xml, property, value);
+                    }
+                    xml.push_str("</D:prop>");
+                    write_status(&mut xml, *status);
+                    xml.push_str("</D:propstat>");
+                }
+            }
+            xml.push_str("</D:response>");
+        }
+        if let Some(sync_token) = self.sync_token {
+            let _ = write!(
+                xml,
+                "<D:sync-token>{}</D:sync-token>",
+                escape_xml(&sync_token)
+            );
+        }
+        xml.push_str("</D:multistatus>");
+
+        DavResponse::new(StatusCode::MULTI_STATUS).with_body("application/xml; charset=utf-8", xml)
+    }
+}
+
+impl ResponseItem {
+    pub fn new(href: impl Into<String>) -> Self {
+        ResponseItem {
+            href: href.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn add_property(&mut self, status: StatusCode, property: DavProperty, value: String) {
+        if let Some((_, properties)) = self.propstat.iter_mut().find(|(s, _)| *s == status) {
+            properties.push((property, value));
+        } else {
+            self.propstat.push((status, vec![(property, value)]));
+        }
+    }
+}
+
+impl From<MethodError> for DavResponse {
+    fn from(err: MethodError) -> Self {
+        match err {
+            MethodError::Forbidden(_) | MethodError::AccountReadOnly => {
+                DavResponse::new(StatusCode::FORBIDDEN)
+            }
+            MethodError::NotFound | MethodError::AccountNotFound => {
+                DavResponse::new(StatusCode::NOT_FOUND)
+            }
+            MethodError::InvalidArguments(_) | MethodError::RequestTooLarge => {
+                DavResponse::new(StatusCode::BAD_REQUEST)
+            }
+            _ => DavResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+}
+
+impl ToHttpResponse for DavResponse {
+    fn into_http_response(self) -> HttpResponse {
+        let mut builder = hyper::Response::builder().status(self.status);
+        for (name, value) in self.headers {
+            builder = builder.header(name, value);
+        }
+        let body = if let Some((content_type, body)) = self.body {
+            builder = builder.header(hyper::header::CONTENT_TYPE, content_type);
+            Bytes::from(body)
+        } else {
+            Bytes::new()
+        };
+        builder
+            .body(Full::new(body).map_err(|never| match never {}).boxed())
+            .unwrap()
+    }
+}
+
+fn xml_header(root: &str) -> String {
+    let mut xml = String::with_capacity(1024);
+    let _ = write!(xml, "<?xml version=\"1.0\" encoding=\"utf-8\"?><D:{root}");
+    for (prefix, namespace) in NAMESPACES {
+        let _ = write!(xml, " xmlns:{prefix}=\"{namespace}\"");
+    }
+    xml.push('>');
+    xml
+}
+
+fn write_property(xml: &mut String, property: &DavProperty, value: &str) {
+    let name = property.name();
+    let namespace = property.namespace();
+    let tag = if let Some((prefix, _)) = NAMESPACES.iter().find(|(_, ns)| *ns == namespace) {
+        let _ = write!(xml, "<{prefix}:{name}");
+        format!("{prefix}:{name}")
+    } else {
+        let _ = write!(xml, "<X:{name} xmlns:X=\"{}\"", escape_xml(namespace));
+        format!("X:{name}")
+    };
+    if value.is_empty() {
+        xml.push_str("/>");
+    } else {
+        let _ = write!(xml, ">{value}</{tag}>");
+    }
+}
+
+fn write_status(xml: &mut String, status: StatusCode) {
+    let _ = write!(
+        xml,
+        "<D:status>HTTP/1.1 {} {}</D:status>",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+pub fn href_xml(href: &str) -> String {
+    format!("<D:href>{}</D:href>", escape_xml(href))
+}
@@ -0,0 +1,215 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+// Content lines as defined by RFC 5545 (iCalendar) and RFC 6350 (vCard),
+// which share the same line folding, parameter and escaping rules.
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ContentLine {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl ContentLine {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        ContentLine {
+            name: name.into(),
+            params: Vec::new(),
+            value: value.into(),
+        }
+    }
+
+    pub fn with_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn has_param_value(&self, name: &str, value: &str) -> bool {
+        self.params.iter().any(|(param, values)| {
+            param.eq_ignore_ascii_case(name)
+                && values
+                    .split(',')
+                    .any(|item| item.trim().eq_ignore_ascii_case(value))
+        })
+    }
+
+    pub fn text(&self) -> String {
+        unescape_text(&self.value)
+    }
+
+    pub fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    pub fn write(&self, out: &mut String) {
+        let mut line = String::with_capacity(self.name.len() + self.value.len() + 16);
+        line.push_str(&self.name);
+        for (name, value) in &self.params {
+            line.push(';');
+            line.push_str(name);
+            line.push('=');
+            if value.contains([':', ';', ',']) {
+                line.push('"');
+                line.push_str(&value.replace('"', "'"));
+                line.push('"');
+            } else {
+                line.push_str(value);
+            }
+        }
+        line.push(':');
+        line.push_str(&self.value);
+        fold_line(out, &line);
+    }
+}
+
+pub fn parse_content_lines(text: &str) -> Vec<ContentLine> {
+    let mut lines = Vec::new();
+    let mut unfolded = String::new();
+
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(continuation) = line.strip_prefix([' ', '\t']) {
+            unfolded.push_str(continuation);
+        } else {
+            if !unfolded.is_empty() {
+                if let Some(line) = parse_content_line(&unfolded) {
+                    lines.push(line);
+                }
+            }
+            unfolded.clear();
+            unfolded.push_str(line);
+        }
+    }
+    if !unfolded.is_empty() {
+        if let Some(line) = parse_content_line(&unfolded) {
+            lines.push(line);
+        }
+    }
+
+    lines
+}
+
+fn parse_content_line(line: &str) -> Option<ContentLine> {
+    let mut result = ContentLine::default();
+    let chars = line.char_indices();
+    let mut token = String::new();
+    let mut param_name = None;
+    let mut in_quotes = false;
+
+    // Name and parameters are separated by ';', the value starts after the
+    // first unquoted ':'.
+    for (pos, ch) in chars {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            ':' | ';' if !in_quotes => {
+                if let Some(name) = param_name.take() {
+                    result.params.push((name, std::mem::take(&mut token)));
+                } else if result.name.is_empty() {
+                    result.name = std::mem::take(&mut token);
+                } else if !token.is_empty() {
+                    result
+                        .params
+                        .push((std::mem::take(&mut token), String::new()));
+                }
+                if ch == ':' {
+                    result.value = line[pos + 1..].to_string();
+                    break;
+                }
+            }
+            '=' if !in_quotes && param_name.is_none() && !result.name.is_empty() => {
+                param_name = Some(std::mem::take(&mut token));
+            }
+            _ => token.push(ch),
+        }
+    }
+
+    if !result.name.is_empty() {
+        // Strip vCard property groups such as "item1.EMAIL"
+        if let Some((_, name)) = result.name.rsplit_once('.') {
+            result.name = name.to_string();
+        }
+        Some(result)
+    } else {
+        None
+    }
+}
+
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+pub fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(ch) => unescaped.push(ch),
+                None => (),
+            }
+        } else {
+            unescaped.push(ch);
+        }
+    }
+    unescaped
+}
+
+// Splits a structured value on unescaped separators.
+pub fn split_unescaped(text: &str, separator: char) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            item.push(ch);
+            if let Some(ch) = chars.next() {
+                item.push(ch);
+            }
+        } else if ch == separator {
+            items.push(unescape_text(&item));
+            item.clear();
+        } else {
+            item.push(ch);
+        }
+    }
+    items.push(unescape_text(&item));
+    items
+}
+
+// Content lines longer than 75 octets are folded as described in RFC 5545.
+pub fn fold_line(out: &mut String, line: &str) {
+    let mut line_len = 0;
+    for ch in line.chars() {
+        if line_len + ch.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            line_len = 1;
+        }
+        out.push(ch);
+        line_len += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+}
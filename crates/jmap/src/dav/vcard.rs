@@ -0,0 +1,544 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+use crate::calendar_event::EventFields;
+
+use super::text::{escape_text, parse_content_lines, split_unescaped, ContentLine};
+
+// Mapping of the structured N property to JSContact name components
+const NAME_COMPONENTS: [&str; 5] = ["surname", "given", "given2", "title", "credential"];
+
+// Mapping of the structured ADR property to JSContact address components
+const ADDRESS_COMPONENTS: [&str; 7] = [
+    "postOfficeBox",
+    "apartment",
+    "name",
+    "locality",
+    "region",
+    "postcode",
+    "country",
+];
+
+// Converts a vCard (RFC 6350 or RFC 2426) to a JSContact card (RFC 9553)
+// following the conversion rules of RFC 9555 for the most common properties.
+pub fn vcard_to_card(text: &str) -> Result<Object<Value>, &'static str> {
+    let lines = parse_content_lines(text);
+    if !lines
+        .first()
+        .is_some_and(|line| line.is("BEGIN") && line.value.eq_ignore_ascii_case("VCARD"))
+    {
+        return Err("Expected a vCard object.");
+    }
+
+    let mut card = Object::with_capacity(lines.len());
+    card.set(Property::parse("@type"), Value::Text("Card".to_string()));
+    card.set(Property::parse("version"), Value::Text("1.0".to_string()));
+    let mut name = Object::with_capacity(2);
+    let mut depth = 0;
+
+    for line in lines {
+        match line.name.to_ascii_uppercase().as_str() {
+            "BEGIN" => depth += 1,
+            "END" => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            "UID" => {
+                card.set(Property::Uid, Value::Text(line.text()));
+            }
+            "KIND" => {
+                card.set(
+                    Property::parse("kind"),
+                    Value::Text(line.text().to_ascii_lowercase()),
+                );
+            }
+            "FN" => {
+                name.set(Property::_T("full".to_string()), Value::Text(line.text()));
+            }
+            "N" => {
+                let components = split_unescaped(&line.value, ';')
+                    .into_iter()
+                    .zip(NAME_COMPONENTS)
+                    .flat_map(|(values, kind)| {
+                        values
+                            .split(',')
+                            .filter(|value| !value.is_empty())
+                            .map(|value| component(kind, value))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                if !components.is_empty() {
+                    name.set(
+                        Property::_T("components".to_string()),
+                        Value::List(components),
+                    );
+                }
+            }
+            "NICKNAME" => {
+                for nickname in split_unescaped(&line.value, ',') {
+                    add_entry(
+                        &mut card,
+                        "nicknames",
+                        Object::with_capacity(1)
+                            .with_property(Property::_T("name".to_string()), Value::Text(nickname)),
+                    );
+                }
+            }
+            "EMAIL" => {
+                let mut email = with_contexts(&line, Object::with_capacity(3));
+                email.set(
+                    Property::_T("address".to_string()),
+                    Value::Text(line.text()),
+                );
+                add_entry(&mut card, "emails", email);
+            }
+            "TEL" => {
+                let mut phone = with_contexts(&line, Object::with_capacity(3));
+                let features = ["voice", "fax", "cell", "video", "pager", "text"]
+                    .into_iter()
+                    .filter(|feature| line.has_param_value("TYPE", feature))
+                    .fold(Object::with_capacity(1), |features, feature| {
+                        features.with_property(
+                            Property::_T(
+                                if feature == "cell" { "mobile" } else { feature }.to_string(),
+                            ),
+                            Value::Bool(true),
+                        )
+                    });
+                if !features.properties.is_empty() {
+                    phone.set(
+                        Property::_T("features".to_string()),
+                        Value::Object(features),
+                    );
+                }
+                phone.set(Property::_T("number".to_string()), Value::Text(line.text()));
+                add_entry(&mut card, "phones", phone);
+            }
+            "ADR" => {
+                let components = split_unescaped(&line.value, ';')
+                    .into_iter()
+                    .zip(ADDRESS_COMPONENTS)
+                    .filter(|(value, _)| !value.is_empty())
+                    .map(|(value, kind)| component(kind, &value))
+                    .collect::<Vec<_>>();
+                if !components.is_empty() {
+                    add_entry(
+                        &mut card,
+                        "addresses",
+                        with_contexts(&line, Object::with_capacity(2)).with_property(
+                            Property::_T("components".to_string()),
+                            Value::List(components),
+                        ),
+                    );
+                }
+            }
+            "ORG" => {
+                let mut units = split_unescaped(&line.value, ';').into_iter();
+                let mut organization = Object::with_capacity(2);
+                if let Some(name) = units.next().filter(|name| !name.is_empty()) {
+                    organization.set(Property::_T("name".to_string()), Value::Text(name));
+                }
+                let units = units
+                    .filter(|unit| !unit.is_empty())
+                    .map(|unit| {
+                        Value::Object(
+                            Object::with_capacity(1)
+                                .with_property(Property::_T("name".to_string()), Value::Text(unit)),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if !units.is_empty() {
+                    organization.set(Property::_T("units".to_string()), Value::List(units));
+                }
+                add_entry(&mut card, "organizations", organization);
+            }
+            "TITLE" | "ROLE" => {
+                add_entry(
+                    &mut card,
+                    "titles",
+                    Object::with_capacity(2)
+                        .with_property(Property::_T("name".to_string()), Value::Text(line.text()))
+                        .with_property(
+                            Property::_T("kind".to_string()),
+                            Value::Text(
+                                if line.is("TITLE") { "title" } else { "role" }.to_string(),
+                            ),
+                        ),
+                );
+            }
+            "NOTE" => {
+                add_entry(
+                    &mut card,
+                    "notes",
+                    Object::with_capacity(1)
+                        .with_property(Property::_T("note".to_string()), Value::Text(line.text())),
+                );
+            }
+            "URL" => {
+                add_entry(
+                    &mut card,
+                    "links",
+                    with_contexts(&line, Object::with_capacity(2))
+                        .with_property(Property::_T("uri".to_string()), Value::Text(line.text())),
+                );
+            }
+            "PHOTO" if !line.value.is_empty() && line.param("ENCODING").is_none() => {
+                add_entry(
+                    &mut card,
+                    "media",
+                    Object::with_capacity(2)
+                        .with_property(
+                            Property::_T("kind".to_string()),
+                            Value::Text("photo".to_string()),
+                        )
+                        .with_property(Property::_T("uri".to_string()), Value::Text(line.text())),
+                );
+            }
+            "BDAY" | "ANNIVERSARY" => {
+                if let Some(date) = parse_partial_date(&line.value) {
+                    add_entry(
+                        &mut card,
+                        "anniversaries",
+                        Object::with_capacity(2)
+                            .with_property(
+                                Property::_T("kind".to_string()),
+                                Value::Text(
+                                    if line.is("BDAY") { "birth" } else { "wedding" }.to_string(),
+                                ),
+                            )
+                            .with_property(Property::_T("date".to_string()), Value::Object(date)),
+                    );
+                }
+            }
+            "CATEGORIES" => {
+                let keywords = card
+                    .properties
+                    .get_mut_or_insert_with(Property::parse("keywords"), || {
+                        Value::Object(Object::with_capacity(1))
+                    });
+                if let Value::Object(keywords) = keywords {
+                    for keyword in split_unescaped(&line.value, ',') {
+                        keywords.set(Property::_T(keyword), Value::Bool(true));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if !name.properties.is_empty() {
+        card.set(Property::Name, Value::Object(name));
+    }
+    if card.get(&Property::Uid).as_string().is_none() {
+        return Err("vCard is missing a UID.");
+    }
+
+    Ok(card)
+}
+
+// Converts a JSContact card to a vCard 3.0 object, which is supported by
+// all CardDAV clients.
+pub fn card_to_vcard(card: &Object<Value>) -> String {
+    let mut vcard = String::with_capacity(512);
+    let mut write = |line: ContentLine| line.write(&mut vcard);
+
+    write(ContentLine::new("BEGIN", "VCARD"));
+    write(ContentLine::new("VERSION", "3.0"));
+    write(ContentLine::new(
+        "PRODID",
+        "-//Stalwart Labs Ltd.//Stalwart Mail Server//EN",
+    ));
+    if let Some(uid) = card.get(&Property::Uid).as_string() {
+        write(ContentLine::new("UID", escape_text(uid)));
+    }
+    if let Some(kind) = card.event_str("kind").filter(|kind| *kind != "individual") {
+        write(ContentLine::new("X-ADDRESSBOOKSERVER-KIND", kind));
+    }
+
+    let name = card.get(&Property::Name).as_obj();
+    let components = name
+        .and_then(|name| name.event_list("components"))
+        .map(|components| {
+            components
+                .iter()
+                .filter_map(|component| component.as_obj())
+                .filter_map(|component| {
+                    Some((component.event_str("kind")?, component.event_str("value")?))
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let full_name = name
+        .and_then(|name| name.event_str("full"))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| {
+            components
+                .iter()
+                .filter(|(kind, _)| ["title", "given", "given2", "surname"].contains(kind))
+                .map(|(_, value)| *value)
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+    write(ContentLine::new("FN", escape_text(&full_name)));
+    write(ContentLine::new(
+        "N",
+        NAME_COMPONENTS
+            .iter()
+            .map(|kind| {
+                components
+                    .iter()
+                    .filter(|(component, _)| component == kind)
+                    .map(|(_, value)| escape_text(value))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>()
+            .join(";"),
+    ));
+
+    for (_, entry) in entries(card, "nicknames") {
+        if let Some(name) = entry.event_str("name") {
+            write(ContentLine::new("NICKNAME", escape_text(name)));
+        }
+    }
+    for (_, entry) in entries(card, "emails") {
+        if let Some(address) = entry.event_str("address") {
+            write(contexts_to_params(
+                entry,
+                ContentLine::new("EMAIL", escape_text(address)).with_param("TYPE", "INTERNET"),
+            ));
+        }
+    }
+    for (_, entry) in entries(card, "phones") {
+        if let Some(number) = entry.event_str("number") {
+            let mut line = contexts_to_params(entry, ContentLine::new("TEL", escape_text(number)));
+            for (feature, _) in entry
+                .event_obj("features")
+                .into_iter()
+                .flat_map(|features| features.properties.iter())
+            {
+                let feature = feature.to_string();
+                line = line.with_param(
+                    "TYPE",
+                    if feature == "mobile" {
+                        "CELL".to_string()
+                    } else {
+                        feature.to_ascii_uppercase()
+                    },
+                );
+            }
+            write(line);
+        }
+    }
+    for (_, entry) in entries(card, "addresses") {
+        let components = entry.event_list("components");
+        let value = ADDRESS_COMPONENTS
+            .iter()
+            .map(|kind| {
+                components
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|component| component.as_obj())
+                    .filter(|component| component.event_str("kind") == Some(*kind))
+                    .filter_map(|component| component.event_str("value"))
+                    .map(escape_text)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>();
+        if value.iter().any(|value| !value.is_empty()) {
+            write(contexts_to_params(
+                entry,
+                ContentLine::new("ADR", value.join(";")),
+            ));
+        } else if let Some(full) = entry.event_str("full") {
+            write(ContentLine::new(
+                "ADR",
+                format!(";;{};;;;", escape_text(full)),
+            ));
+        }
+    }
+    for (_, entry) in entries(card, "organizations") {
+        let mut value = vec![escape_text(entry.event_str("name").unwrap_or_default())];
+        for unit in entry.event_list("units").into_iter().flatten() {
+            if let Some(name) = unit.as_obj().and_then(|unit| unit.event_str("name")) {
+                value.push(escape_text(name));
+            }
+        }
+        write(ContentLine::new("ORG", value.join(";")));
+    }
+    for (_, entry) in entries(card, "titles") {
+        if let Some(name) = entry.event_str("name") {
+            write(ContentLine::new(
+                if entry.event_str("kind") == Some("role") {
+                    "ROLE"
+                } else {
+                    "TITLE"
+                },
+                escape_text(name),
+            ));
+        }
+    }
+    for (_, entry) in entries(card, "notes") {
+        if let Some(note) = entry.event_str("note") {
+            write(ContentLine::new("NOTE", escape_text(note)));
+        }
+    }
+    for (_, entry) in entries(card, "links") {
+        if let Some(uri) = entry.event_str("uri") {
+            write(contexts_to_params(entry, ContentLine::new("URL", uri)));
+        }
+    }
+    for (_, entry) in entries(card, "media") {
+        if let (Some("photo"), Some(uri)) = (entry.event_str("kind"), entry.event_str("uri")) {
+            write(ContentLine::new("PHOTO", uri).with_param("VALUE", "uri"));
+        }
+    }
+    for (_, entry) in entries(card, "anniversaries") {
+        if let Some(date) = entry.event_obj("date").and_then(format_partial_date) {
+            write(ContentLine::new(
+                if entry.event_str("kind") == Some("birth") {
+                    "BDAY"
+                } else {
+                    "ANNIVERSARY"
+                },
+                date,
+            ));
+        }
+    }
+    if let Some(keywords) = card.event_obj("keywords") {
+        let keywords = keywords
+            .properties
+            .keys()
+            .map(|keyword| escape_text(&keyword.to_string()))
+            .collect::<Vec<_>>();
+        if !keywords.is_empty() {
+            write(ContentLine::new("CATEGORIES", keywords.join(",")));
+        }
+    }
+    write(ContentLine::new("END", "VCARD"));
+
+    vcard
+}
+
+fn component(kind: &str, value: &str) -> Value {
+    Value::Object(
+        Object::with_capacity(2)
+            .with_property(
+                Property::_T("kind".to_string()),
+                Value::Text(kind.to_string()),
+            )
+            .with_property(
+                Property::_T("value".to_string()),
+                Value::Text(value.to_string()),
+            ),
+    )
+}
+
+fn add_entry(card: &mut Object<Value>, property: &str, entry: Object<Value>) {
+    let prefix = property.chars().next().unwrap_or('k');
+    let entries = card
+        .properties
+        .get_mut_or_insert_with(Property::parse(property), || {
+            Value::Object(Object::with_capacity(1))
+        });
+    if let Value::Object(entries) = entries {
+        let id = format!("{prefix}{}", entries.properties.len() + 1);
+        entries.set(Property::_T(id), Value::Object(entry));
+    }
+}
+
+fn entries<'x>(
+    card: &'x Object<Value>,
+    property: &str,
+) -> impl Iterator<Item = (&'x Property, &'x Object<Value>)> {
+    card.event_obj(property)
+        .into_iter()
+        .flat_map(|entries| entries.properties.iter())
+        .filter_map(|(id, entry)| entry.as_obj().map(|entry| (id, entry)))
+}
+
+fn with_contexts(line: &ContentLine, mut entry: Object<Value>) -> Object<Value> {
+    let mut contexts = Object::with_capacity(1);
+    for (param, context) in [("work", "work"), ("home", "private")] {
+        if line.has_param_value("TYPE", param) {
+            contexts.set(Property::_T(context.to_string()), Value::Bool(true));
+        }
+    }
+    if !contexts.properties.is_empty() {
+        entry.set(
+            Property::_T("contexts".to_string()),
+            Value::Object(contexts),
+        );
+    }
+    if line.has_param_value("TYPE", "pref") || line.param("PREF").is_some() {
+        entry.set(Property::_T("pref".to_string()), Value::UnsignedInt(1));
+    }
+    entry
+}
+
+fn contexts_to_params(entry: &Object<Value>, mut line: ContentLine) -> ContentLine {
+    for (context, param) in [("work", "WORK"), ("private", "HOME")] {
+        if entry
+            .event_obj("contexts")
+            .is_some_and(|contexts| matches!(contexts.event_value(context), Value::Bool(true)))
+        {
+            line = line.with_param("TYPE", param);
+        }
+    }
+    if entry.event_uint("pref").is_some() {
+        line = line.with_param("TYPE", "PREF");
+    }
+    line
+}
+
+fn parse_partial_date(value: &str) -> Option<Object<Value>> {
+    let value = value.split('T').next().unwrap_or_default().replace('-', "");
+    let mut date = Object::with_capacity(4);
+    date.set(
+        Property::_T("@type".to_string()),
+        Value::Text("PartialDate".to_string()),
+    );
+    let month_day = if let Some(month_day) = value.strip_prefix("--") {
+        month_day
+    } else if value.len() >= 8 {
+        let (year, month_day) = value.split_at(4);
+        date.set(
+            Property::_T("year".to_string()),
+            Value::UnsignedInt(year.parse().ok()?),
+        );
+        month_day
+    } else {
+        return None;
+    };
+    if month_day.len() >= 4 {
+        date.set(
+            Property::_T("month".to_string()),
+            Value::UnsignedInt(month_day[..2].parse().ok()?),
+        );
+        date.set(
+            Property::_T("day".to_string()),
+            Value::UnsignedInt(month_day[2..4].parse().ok()?),
+        );
+    }
+    Some(date)
+}
+
+fn format_partial_date(date: &Object<Value>) -> Option<String> {
+    let month = date.event_uint("month")?;
+    let day = date.event_uint("day")?;
+    Some(match date.event_uint("year") {
+        Some(year) => format!("{year:04}-{month:02}-{day:02}"),
+        None => format!("--{month:02}{day:02}"),
+    })
+}
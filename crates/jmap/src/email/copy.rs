@@ -300,6 +300,7 @@ impl JMAP {
         if !self
             .has_available_quota(account_id, account_quota, metadata.size as i64)
             .await?
+            || !self.has_available_message_quota(account_id).await?
         {
             return Ok(Err(SetError::over_quota()));
         }
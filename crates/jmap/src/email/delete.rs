@@ -13,7 +13,6 @@ use jmap_proto::{
         type_state::DataType,
     },
 };
-use se_common::undelete::Undelete;
 use store::{
     ahash::AHashMap,
     roaring::RoaringBitmap,
@@ -130,6 +129,13 @@ impl JMAP {
             })?;
 
         // Tombstone message and untag it from the mailboxes
+        let is_undelete_enabled = self.is_undelete_enabled();
+        let mut deleted_uid = if is_undelete_enabled && !delete_properties.is_empty() {
+            self.assign_deleted_uids(account_id, delete_properties.len() as u32)
+                .await?
+        } else {
+            0
+        };
         let mut batch = BatchBuilder::new();
         batch
             .with_account_id(account_id)
@@ -138,6 +144,25 @@ impl JMAP {
         for (document_id, delete_properties) in delete_properties {
             batch.update_document(document_id);
 
+            // SPDX-SnippetBegin
+            // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+            // SPDX-License-Identifier: LicenseRef-SEL
+
+            // Hold blob for undeletion
+            if is_undelete_enabled {
+                self.email_hold_undelete(
+                    &mut batch,
+                    account_id,
+                    document_id,
+                    &delete_properties.mailboxes,
+                    deleted_uid,
+                )
+                .await?;
+                deleted_uid += 1;
+            }
+
+            // SPDX-SnippetEnd
+
             if !delete_properties.mailboxes.is_empty() {
                 for mailbox_id in &delete_properties.mailboxes {
                     debug_assert!(mailbox_id.uid != 0);
@@ -524,20 +549,6 @@ impl JMAP {
                 })
                 .await?
             {
-                // SPDX-SnippetBegin
-                // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
-                // SPDX-License-Identifier: LicenseRef-SEL
-
-                // Hold blob for undeletion
-                self.core.hold_undelete(
-                    &mut batch,
-                    Collection::Email.into(),
-                    &metadata.inner.blob_hash,
-                    metadata.inner.size,
-                );
-
-                // SPDX-SnippetEnd
-
                 // Delete message
                 batch.custom(EmailIndexBuilder::clear(metadata.inner));
 
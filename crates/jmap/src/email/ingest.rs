@@ -29,7 +29,7 @@ use store::{
     },
     BitmapKey, BlobClass, Serialize,
 };
-use utils::map::vec_map::VecMap;
+use utils::{map::vec_map::VecMap, metrics::SPAM_VERDICTS_TOTAL};
 
 use crate::{
     email::index::{IndexMessage, VisitValues, MAX_ID_LENGTH},
@@ -85,6 +85,10 @@ impl JMAP {
             .has_available_quota(params.account_id, params.account_quota, raw_message_len)
             .await
             .map_err(|_| IngestError::Temporary)?
+            || !self
+                .has_available_message_quota(params.account_id)
+                .await
+                .map_err(|_| IngestError::Temporary)?
         {
             return Err(IngestError::OverQuota);
         }
@@ -98,16 +102,19 @@ impl JMAP {
 
         // Check for Spam headers
         if let Some((header_name, header_value)) = &self.core.jmap.spam_header {
-            if params.mailbox_ids == [INBOX_ID]
-                && message.root_part().headers().iter().any(|header| {
+            if params.mailbox_ids == [INBOX_ID] {
+                if message.root_part().headers().iter().any(|header| {
                     &header.name == header_name
                         && header
                             .value()
                             .as_text()
                             .map_or(false, |value| value.contains(header_value))
-                })
-            {
-                params.mailbox_ids[0] = JUNK_ID;
+                }) {
+                    params.mailbox_ids[0] = JUNK_ID;
+                    SPAM_VERDICTS_TOTAL.inc(&["spam"]);
+                } else {
+                    SPAM_VERDICTS_TOTAL.inc(&["ham"]);
+                }
             }
         }
 
@@ -19,3 +19,4 @@ pub mod parse;
 pub mod query;
 pub mod set;
 pub mod snippet;
+pub mod undelete;
@@ -0,0 +1,480 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: LicenseRef-SEL
+ *
+ * This file is subject to the Stalwart Enterprise License Agreement (SEL) and
+ * is not open source software. It must not be modified or distributed without
+ * explicit permission from Stalwart Labs Ltd.
+ * Unauthorized use, modification, or distribution is strictly prohibited.
+ */
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::undelete::{
+        DeletedEmailInfo, ListDeletedRequest, ListDeletedResponse, UndeleteRequest,
+        UndeleteResponse,
+    },
+    types::{
+        blob::BlobId, collection::Collection, date::UTCDate, id::Id, keyword::Keyword,
+        property::Property, state::StateChange, type_state::DataType, MaybeUnparsable,
+    },
+};
+use mail_parser::MessageParser;
+use se_common::undelete::Undelete;
+use store::{
+    write::{BatchBuilder, Bincode, BlobOp, DeserializeFrom, SerializeInto, ValueClass},
+    BlobClass, ValueKey,
+};
+use utils::{
+    codec::leb128::{Leb128Iterator, Leb128Vec},
+    map::vec_map::VecMap,
+    BlobHash,
+};
+
+use crate::{
+    auth::AccessToken,
+    mailbox::{UidMailbox, INBOX_ID},
+    IngestError, JMAP,
+};
+
+use super::{
+    ingest::{IngestEmail, IngestSource, IngestedEmail},
+    metadata::MessageMetadata,
+};
+
+// Mailbox id reserved for the virtual folder listing recently deleted messages,
+// its UID counter numbers the messages as they are deleted
+pub const DELETED_MAILBOX_ID: u32 = u32::MAX - 2;
+
+#[derive(Debug, Clone)]
+pub struct DeletedEmail {
+    pub uid: Option<u32>,
+    pub hash: BlobHash,
+    pub size: usize,
+    pub deleted_at: u64,
+    pub expires_at: u64,
+    pub received_at: u64,
+    pub mailbox_ids: Vec<u32>,
+    pub keywords: Vec<Keyword>,
+    pub subject: Option<String>,
+    pub from: Option<String>,
+}
+
+impl JMAP {
+    pub fn is_undelete_enabled(&self) -> bool {
+        self.core
+            .enterprise
+            .as_ref()
+            .is_some_and(|e| e.undelete_period.is_some())
+    }
+
+    // Holds the blob of a message that is about to be deleted, along with the
+    // details needed to restore it into its original mailboxes
+    pub async fn email_hold_undelete(
+        &self,
+        batch: &mut BatchBuilder,
+        account_id: u32,
+        document_id: u32,
+        mailboxes: &[UidMailbox],
+        uid: u32,
+    ) -> Result<(), MethodError> {
+        let Some(metadata) = self
+            .get_property::<Bincode<MessageMetadata>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::BodyStructure,
+            )
+            .await?
+        else {
+            return Ok(());
+        };
+        let keywords = self
+            .get_property::<Vec<Keyword>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::Keywords,
+            )
+            .await?
+            .unwrap_or_default();
+        let metadata = metadata.inner;
+        let headers = MessageParser::new().parse_headers(&metadata.raw_headers);
+
+        let mut bytes = Vec::with_capacity(64);
+        bytes.push_leb128(metadata.received_at);
+        bytes.push_leb128(mailboxes.len());
+        for mailbox in mailboxes {
+            bytes.push_leb128(mailbox.mailbox_id);
+        }
+        bytes.push_leb128(keywords.len());
+        for keyword in &keywords {
+            keyword.serialize_into(&mut bytes);
+        }
+        for value in [
+            headers.as_ref().and_then(|h| h.subject()),
+            headers
+                .as_ref()
+                .and_then(|h| h.from())
+                .and_then(|from| from.first())
+                .and_then(|addr| addr.address()),
+        ] {
+            value
+                .unwrap_or_default()
+                .to_string()
+                .serialize_into(&mut bytes);
+        }
+        bytes.push_leb128(uid);
+
+        self.core.hold_undelete(
+            batch,
+            Collection::Email.into(),
+            &metadata.blob_hash,
+            metadata.size,
+            bytes,
+        );
+
+        Ok(())
+    }
+
+    // Reserves a range of UIDs for messages about to be deleted and returns the first one
+    pub async fn assign_deleted_uids(
+        &self,
+        account_id: u32,
+        count: u32,
+    ) -> Result<u32, MethodError> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Mailbox)
+            .update_document(DELETED_MAILBOX_ID)
+            .add_and_get(Property::EmailIds, count as i64);
+        self.core
+            .storage
+            .data
+            .write(batch.build())
+            .await
+            .and_then(|v| v.last_counter_id())
+            .map(|last_uid| (last_uid as u32).saturating_sub(count) + 1)
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "email_delete",
+                    error = ?err,
+                    "Failed to assign deleted message UIDs.");
+                MethodError::ServerPartialFail
+            })
+    }
+
+    pub async fn deleted_uid_next(&self, account_id: u32) -> store::Result<u32> {
+        self.core
+            .storage
+            .data
+            .get_counter(ValueKey {
+                account_id,
+                collection: Collection::Mailbox.into(),
+                document_id: DELETED_MAILBOX_ID,
+                class: ValueClass::Property(Property::EmailIds.into()),
+            })
+            .await
+            .map(|uid| uid as u32 + 1)
+    }
+
+    // Returns the deleted messages that can still be restored, oldest first
+    pub async fn email_deleted(&self, account_id: u32) -> store::Result<Vec<DeletedEmail>> {
+        let mut deleted = self
+            .core
+            .list_deleted(account_id)
+            .await?
+            .into_iter()
+            .filter(|blob| blob.collection == u8::from(Collection::Email))
+            .map(|blob| {
+                let mut bytes = blob.metadata.iter();
+                let mut email = DeletedEmail {
+                    uid: None,
+                    hash: blob.hash,
+                    size: blob.size,
+                    deleted_at: blob.deleted_at,
+                    expires_at: blob.expires_at,
+                    received_at: blob.deleted_at,
+                    mailbox_ids: Vec::new(),
+                    keywords: Vec::new(),
+                    subject: None,
+                    from: None,
+                };
+
+                // Messages held by older versions do not include any metadata
+                if let Some(received_at) = bytes.next_leb128::<u64>() {
+                    email.received_at = received_at;
+                    for _ in 0..bytes.next_leb128::<usize>().unwrap_or_default() {
+                        if let Some(mailbox_id) = bytes.next_leb128() {
+                            email.mailbox_ids.push(mailbox_id);
+                        }
+                    }
+                    for _ in 0..bytes.next_leb128::<usize>().unwrap_or_default() {
+                        if let Some(keyword) = Keyword::deserialize_from(&mut bytes) {
+                            email.keywords.push(keyword);
+                        }
+                    }
+                    email.subject = String::deserialize_from(&mut bytes).filter(|s| !s.is_empty());
+                    email.from = String::deserialize_from(&mut bytes).filter(|s| !s.is_empty());
+                    email.uid = bytes.next_leb128();
+                }
+
+                email
+            })
+            .collect::<Vec<_>>();
+        deleted.sort_by(|a, b| {
+            a.deleted_at
+                .cmp(&b.deleted_at)
+                .then_with(|| a.expires_at.cmp(&b.expires_at))
+                .then_with(|| a.hash.as_slice().cmp(b.hash.as_slice()))
+        });
+
+        Ok(deleted)
+    }
+
+    // Restores a deleted message into its original mailboxes, or into the
+    // requested ones, and releases the hold on its blob
+    pub async fn email_restore(
+        &self,
+        account_id: u32,
+        account_quota: i64,
+        deleted: &DeletedEmail,
+        mailbox_ids: Option<Vec<u32>>,
+        source: IngestSource,
+    ) -> Result<IngestedEmail, IngestError> {
+        let raw_message = self
+            .get_blob(&deleted.hash, 0..usize::MAX)
+            .await
+            .map_err(|_| IngestError::Temporary)?
+            .ok_or_else(|| IngestError::Permanent {
+                code: [5, 5, 0],
+                reason: "Deleted message is no longer available.".to_string(),
+            })?;
+
+        // Restore into the original mailboxes that still exist, or the Inbox otherwise
+        let mailbox_ids = match mailbox_ids {
+            Some(mailbox_ids) => mailbox_ids,
+            None => {
+                let valid_ids = self
+                    .mailbox_get_or_create(account_id)
+                    .await
+                    .map_err(|_| IngestError::Temporary)?;
+                let mut mailbox_ids = deleted
+                    .mailbox_ids
+                    .iter()
+                    .copied()
+                    .filter(|id| valid_ids.contains(*id))
+                    .collect::<Vec<_>>();
+                if mailbox_ids.is_empty() {
+                    mailbox_ids.push(INBOX_ID);
+                }
+                mailbox_ids
+            }
+        };
+
+        let email = self
+            .email_ingest(IngestEmail {
+                raw_message: &raw_message,
+                message: MessageParser::new().parse(&raw_message),
+                account_id,
+                account_quota,
+                mailbox_ids,
+                keywords: deleted
+                    .keywords
+                    .iter()
+                    .filter(|k| !matches!(k, Keyword::Deleted))
+                    .cloned()
+                    .collect(),
+                received_at: deleted.received_at.into(),
+                source,
+                encrypt: false,
+            })
+            .await?;
+
+        // Release hold
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .clear(ValueClass::Blob(BlobOp::Reserve {
+                hash: deleted.hash.clone(),
+                until: deleted.expires_at,
+            }));
+        self.core
+            .storage
+            .data
+            .write(batch.build())
+            .await
+            .map_err(|_| IngestError::Temporary)?;
+
+        Ok(email)
+    }
+
+    pub async fn email_list_deleted(
+        &self,
+        request: ListDeletedRequest,
+    ) -> Result<ListDeletedResponse, MethodError> {
+        if !self.is_undelete_enabled() {
+            return Err(MethodError::Forbidden(
+                "Undeleting messages is not enabled on this server.".to_string(),
+            ));
+        }
+
+        let account_id = request.account_id.document_id();
+        let deleted = self.email_deleted(account_id).await.map_err(|err| {
+            tracing::error!(event = "error",
+                            context = "email_list_deleted",
+                            account_id = account_id,
+                            error = ?err,
+                            "Failed to list deleted messages.");
+            MethodError::ServerPartialFail
+        })?;
+
+        Ok(ListDeletedResponse {
+            account_id: request.account_id,
+            list: deleted
+                .into_iter()
+                .map(|email| DeletedEmailInfo {
+                    blob_id: BlobId {
+                        hash: email.hash,
+                        class: BlobClass::Reserved {
+                            account_id,
+                            expires: email.expires_at,
+                        },
+                        section: None,
+                    },
+                    size: email.size,
+                    received_at: UTCDate::from_timestamp(email.received_at as i64),
+                    deleted_at: UTCDate::from_timestamp(email.deleted_at as i64),
+                    expires_at: UTCDate::from_timestamp(email.expires_at as i64),
+                    mailbox_ids: email
+                        .mailbox_ids
+                        .into_iter()
+                        .map(|id| (Id::from(id), true))
+                        .collect(),
+                    keywords: email
+                        .keywords
+                        .into_iter()
+                        .map(|k| (k.to_string(), true))
+                        .collect(),
+                    subject: email.subject,
+                    from: email.from,
+                })
+                .collect(),
+        })
+    }
+
+    pub async fn email_undelete(
+        &self,
+        request: UndeleteRequest,
+        access_token: &AccessToken,
+    ) -> Result<UndeleteResponse, MethodError> {
+        if !self.is_undelete_enabled() {
+            return Err(MethodError::Forbidden(
+                "Undeleting messages is not enabled on this server.".to_string(),
+            ));
+        }
+
+        let account_id = request.account_id.document_id();
+        let account_quota = self.get_quota(access_token, account_id).await?;
+        let mut deleted = self.email_deleted(account_id).await.map_err(|err| {
+            tracing::error!(event = "error",
+                            context = "email_undelete",
+                            account_id = account_id,
+                            error = ?err,
+                            "Failed to list deleted messages.");
+            MethodError::ServerPartialFail
+        })?;
+        let mut response = UndeleteResponse {
+            account_id: request.account_id,
+            restored: VecMap::with_capacity(request.blob_ids.len()),
+            not_restored: VecMap::new(),
+            state_change: None,
+        };
+        let mut change_id = None;
+
+        for blob_id in request.blob_ids {
+            // Each message is only restored once
+            let is_duplicate = match &blob_id {
+                MaybeUnparsable::Value(blob_id) => response.restored.contains_key(blob_id),
+                MaybeUnparsable::ParseError(_) => false,
+            } || response.not_restored.contains_key(&blob_id);
+            if is_duplicate {
+                continue;
+            }
+
+            let email_pos = match &blob_id {
+                MaybeUnparsable::Value(BlobId {
+                    hash,
+                    class:
+                        BlobClass::Reserved {
+                            account_id: blob_account_id,
+                            expires,
+                        },
+                    section: None,
+                }) if *blob_account_id == account_id => deleted
+                    .iter()
+                    .position(|email| &email.hash == hash && email.expires_at == *expires),
+                _ => None,
+            };
+            let (email_pos, blob_id) = match (email_pos, blob_id) {
+                (Some(email_pos), MaybeUnparsable::Value(blob_id)) => (email_pos, blob_id),
+                (_, blob_id) => {
+                    response.not_restored.append(
+                        blob_id,
+                        SetError::not_found().with_description("Deleted message not found."),
+                    );
+                    continue;
+                }
+            };
+
+            match self
+                .email_restore(
+                    account_id,
+                    account_quota,
+                    &deleted[email_pos],
+                    None,
+                    IngestSource::Jmap,
+                )
+                .await
+            {
+                Ok(restored) => {
+                    deleted.swap_remove(email_pos);
+                    change_id = restored.change_id.into();
+                    response.restored.append(blob_id, restored.into());
+                }
+                Err(IngestError::Permanent { reason, .. }) => {
+                    response.not_restored.append(
+                        MaybeUnparsable::Value(blob_id),
+                        SetError::new(SetErrorType::InvalidEmail).with_description(reason),
+                    );
+                }
+                Err(IngestError::OverQuota) => {
+                    response.not_restored.append(
+                        MaybeUnparsable::Value(blob_id),
+                        SetError::new(SetErrorType::OverQuota)
+                            .with_description("You have exceeded your disk quota."),
+                    );
+                }
+                Err(IngestError::Temporary) => {
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        if let Some(change_id) = change_id {
+            response.state_change = StateChange::new(account_id)
+                .with_change(DataType::Email, change_id)
+                .with_change(DataType::Mailbox, change_id)
+                .with_change(DataType::Thread, change_id)
+                .into();
+        }
+
+        Ok(response)
+    }
+}
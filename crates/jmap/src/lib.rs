@@ -28,8 +28,10 @@ use jmap_proto::{
     },
     types::{collection::Collection, property::Property},
 };
+use parking_lot::RwLock;
 use services::{
     delivery::spawn_delivery_manager,
+    gossip::ClusterStatus,
     housekeeper::{self, init_housekeeper, spawn_housekeeper},
     state::{self, init_state_manager, spawn_state_manager},
 };
@@ -54,10 +56,15 @@ use utils::{
     snowflake::SnowflakeIdGenerator,
 };
 
+pub mod addressbook;
 pub mod api;
 pub mod auth;
 pub mod blob;
+pub mod calendar;
+pub mod calendar_event;
 pub mod changes;
+pub mod contact;
+pub mod dav;
 pub mod email;
 pub mod identity;
 pub mod mailbox;
@@ -101,6 +108,7 @@ pub struct Inner {
     pub housekeeper_tx: mpsc::Sender<housekeeper::Event>,
 
     pub cache_threads: LruCache<u32, Arc<Threads>>,
+    pub cluster_status: RwLock<Option<ClusterStatus>>,
 }
 
 #[derive(Debug)]
@@ -145,6 +153,7 @@ impl JMAP {
                 config.property("cache.thread.size").unwrap_or(2048),
             ),
             config_version: 0.into(),
+            cluster_status: RwLock::new(None),
         };
 
         // Unpack webadmin
@@ -428,6 +437,17 @@ impl JMAP {
         }
     }
 
+    pub async fn get_used_messages(&self, account_id: u32) -> Result<u64, MethodError> {
+        self.get_document_ids(account_id, Collection::Email)
+            .await
+            .map(|ids| ids.map_or(0, |ids| ids.len()))
+    }
+
+    pub async fn has_available_message_quota(&self, account_id: u32) -> Result<bool, MethodError> {
+        let max_messages = self.core.jmap.mail_max_messages;
+        Ok(max_messages == 0 || self.get_used_messages(account_id).await? < max_messages)
+    }
+
     pub async fn filter(
         &self,
         account_id: u32,
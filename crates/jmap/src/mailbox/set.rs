@@ -484,6 +484,7 @@ impl JMAP {
                 .with_collection(Collection::Mailbox)
                 .delete_document(document_id)
                 .value(Property::EmailIds, (), F_VALUE | F_CLEAR)
+                .value(Property::Metadata, (), F_VALUE | F_CLEAR)
                 .custom(ObjectIndexBuilder::new(SCHEMA).with_current(mailbox));
 
             match self.core.storage.data.write(batch.build()).await {
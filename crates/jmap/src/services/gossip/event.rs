@@ -0,0 +1,68 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::cluster::ClusterEvent;
+
+use super::{request::Request, Gossiper};
+
+impl Gossiper {
+    pub async fn broadcast_events(&self, events: Vec<ClusterEvent>) {
+        for peer in &self.peers {
+            if !peer.is_offline() {
+                self.send_gossip(peer.addr, Request::Events(events.clone()))
+                    .await;
+            }
+        }
+    }
+
+    pub fn handle_events(&self, events: Vec<ClusterEvent>) {
+        let mut update_config = false;
+        let mut update_lists = false;
+
+        for event in events {
+            match event {
+                ClusterEvent::BlockIp(ip) => {
+                    tracing::debug!("Peer blocked IP address {}.", ip);
+                    self.core
+                        .core
+                        .load()
+                        .network
+                        .blocked_ips
+                        .ip_addresses
+                        .write()
+                        .insert(ip);
+                }
+                ClusterEvent::InvalidatePrincipal(account_id) => {
+                    tracing::debug!("Peer invalidated principal {}.", account_id);
+                    let inner = &self.core.jmap_inner;
+                    inner.access_tokens.remove(&account_id);
+                    inner.sessions.retain(|_, id| id.item != account_id);
+
+                    let core = self.core.core.load();
+                    for directory in std::iter::once(&core.storage.directory)
+                        .chain(core.storage.directories.values())
+                    {
+                        if let Some(cache) = &directory.cache {
+                            cache.clear();
+                        }
+                    }
+                }
+                ClusterEvent::ReloadSettings => {
+                    tracing::debug!("Peer requested a configuration reload.");
+                    update_config = true;
+                }
+                ClusterEvent::ReloadBlockedIps => {
+                    tracing::debug!("Peer requested a blocked IP list reload.");
+                    update_lists = true;
+                }
+            }
+        }
+
+        if update_config || update_lists {
+            self.reload_settings(update_config);
+        }
+    }
+}
@@ -32,6 +32,7 @@ impl Gossiper {
 
                     // Reload
                     self.request_reload();
+                    self.publish_status();
 
                     break;
                 }
@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+pub mod event;
 pub mod heartbeat;
 pub mod leave;
 pub mod peer;
@@ -15,7 +16,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     net::{IpAddr, SocketAddr},
     sync::atomic::Ordering,
-    time::Instant,
+    time::{Instant, SystemTime},
 };
 use tokio::sync::mpsc;
 
@@ -26,6 +27,7 @@ use self::request::Request;
 const UDP_MAX_PAYLOAD: usize = 65500;
 const HEARTBEAT_WINDOW: usize = 1 << 10;
 const HEARTBEAT_WINDOW_MASK: usize = HEARTBEAT_WINDOW - 1;
+const MAX_EVENTS_PER_PACKET: usize = 1024;
 
 pub type EpochId = u64;
 pub type GenerationId = u8;
@@ -47,7 +49,8 @@ pub struct Gossiper {
     pub gossip_tx: mpsc::Sender<(SocketAddr, Request)>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum State {
     Seed,
     Alive,
@@ -84,6 +87,27 @@ pub struct PeerStatus {
     pub gen_lists: GenerationId,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClusterStatus {
+    pub addr: IpAddr,
+    pub port: u16,
+    pub epoch: EpochId,
+    pub peers: Vec<PeerHealth>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PeerHealth {
+    pub addr: IpAddr,
+    pub state: State,
+    pub epoch: EpochId,
+    #[serde(rename = "configVersion")]
+    pub gen_config: GenerationId,
+    #[serde(rename = "listsVersion")]
+    pub gen_lists: GenerationId,
+    #[serde(rename = "lastHeartbeat")]
+    pub last_heartbeat: u64,
+}
+
 impl From<&Peer> for PeerStatus {
     fn from(peer: &Peer) -> Self {
         PeerStatus {
@@ -127,4 +151,28 @@ impl Gossiper {
             tracing::error!("Failed to send gossip message: {}", err);
         };
     }
+
+    pub fn publish_status(&self) {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        *self.core.jmap_inner.cluster_status.write() = Some(ClusterStatus {
+            addr: self.addr,
+            port: self.port,
+            epoch: self.epoch,
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| PeerHealth {
+                    addr: peer.addr,
+                    state: peer.state,
+                    epoch: peer.epoch,
+                    gen_config: peer.gen_config,
+                    gen_lists: peer.gen_lists,
+                    last_heartbeat: now.saturating_sub(peer.last_heartbeat.elapsed().as_secs()),
+                })
+                .collect(),
+        });
+    }
 }
@@ -62,6 +62,8 @@ impl Gossiper {
         if node_became_offline {
             self.request_reload();
         }
+
+        self.publish_status();
     }
 
     pub fn request_reload(&self) {
@@ -156,39 +158,43 @@ impl Gossiper {
 
         // Reload settings
         if update_config || update_lists {
-            let core = self.core.core.clone();
-            let inner = self.core.jmap_inner.clone();
-
-            tokio::spawn(async move {
-                let result = if update_config {
-                    core.load().reload().await
-                } else {
-                    core.load().reload_blocked_ips().await
-                };
-                match result {
-                    Ok(result) => {
-                        if let Some(new_core) = result.new_core {
-                            // Update core
-                            core.store(new_core.into());
-
-                            // Reload ACME
-                            if let Err(err) = inner
-                                .housekeeper_tx
-                                .send(housekeeper::Event::AcmeReload)
-                                .await
-                            {
-                                tracing::warn!(
-                                    "Failed to send ACME reload event to housekeeper: {}",
-                                    err
-                                );
-                            }
+            self.reload_settings(update_config);
+        }
+    }
+
+    pub fn reload_settings(&self, update_config: bool) {
+        let core = self.core.core.clone();
+        let inner = self.core.jmap_inner.clone();
+
+        tokio::spawn(async move {
+            let result = if update_config {
+                core.load().reload().await
+            } else {
+                core.load().reload_blocked_ips().await
+            };
+            match result {
+                Ok(result) => {
+                    if let Some(new_core) = result.new_core {
+                        // Update core
+                        core.store(new_core.into());
+
+                        // Reload ACME
+                        if let Err(err) = inner
+                            .housekeeper_tx
+                            .send(housekeeper::Event::AcmeReload)
+                            .await
+                        {
+                            tracing::warn!(
+                                "Failed to send ACME reload event to housekeeper: {}",
+                                err
+                            );
                         }
                     }
-                    Err(err) => {
-                        tracing::error!("Failed to reload configuration: {}", err);
-                    }
                 }
-            });
-        }
+                Err(err) => {
+                    tracing::error!("Failed to reload configuration: {}", err);
+                }
+            }
+        });
     }
 }
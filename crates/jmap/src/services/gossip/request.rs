@@ -8,6 +8,7 @@ use crate::auth::SymmetricEncrypt;
 
 use super::{EpochId, PeerStatus};
 
+use common::cluster::ClusterEvent;
 use std::net::IpAddr;
 use utils::codec::leb128::Leb128_;
 
@@ -16,16 +17,27 @@ pub enum Request {
     Ping(Vec<PeerStatus>),
     Pong(Vec<PeerStatus>),
     Leave(Vec<PeerStatus>),
+    Events(Vec<ClusterEvent>),
 }
 
 impl Request {
     const PING: u8 = 0;
     const PONG: u8 = 1;
     const LEAVE: u8 = 2;
+    const EVENTS: u8 = 3;
+
+    const EVENT_BLOCK_IPV4: u8 = 0;
+    const EVENT_BLOCK_IPV6: u8 = 1;
+    const EVENT_INVALIDATE_PRINCIPAL: u8 = 2;
+    const EVENT_RELOAD_SETTINGS: u8 = 3;
+    const EVENT_RELOAD_BLOCKED_IPS: u8 = 4;
 
     pub fn from_bytes(bytes: &[u8]) -> Option<Request> {
         let mut it = bytes.iter();
         let flags = it.next().copied()?;
+        if flags == Self::EVENTS {
+            return Self::events_from_bytes(it);
+        }
         let is_ipv6 = flags & (1 << 7) != 0;
 
         let mut peers = Vec::with_capacity(bytes.len() / std::mem::size_of::<PeerStatus>());
@@ -73,6 +85,7 @@ impl Request {
             Request::Ping(peers) => (Self::PING, peers),
             Request::Pong(peers) => (Self::PONG, peers),
             Request::Leave(peers) => (Self::LEAVE, peers),
+            Request::Events(events) => return Self::events_to_bytes(events),
         };
 
         debug_assert!(!peers.is_empty());
@@ -112,4 +125,63 @@ impl Request {
 
         bytes
     }
+
+    fn events_from_bytes(mut it: std::slice::Iter<'_, u8>) -> Option<Request> {
+        let mut events = Vec::new();
+        while let Some(event_type) = it.next().copied() {
+            events.push(match event_type {
+                Self::EVENT_BLOCK_IPV4 => {
+                    let mut octets = [0u8; 4];
+                    for octet in octets.iter_mut() {
+                        *octet = it.next().copied()?;
+                    }
+                    ClusterEvent::BlockIp(IpAddr::V4(octets.into()))
+                }
+                Self::EVENT_BLOCK_IPV6 => {
+                    let mut octets = [0u8; 16];
+                    for octet in octets.iter_mut() {
+                        *octet = it.next().copied()?;
+                    }
+                    ClusterEvent::BlockIp(IpAddr::V6(octets.into()))
+                }
+                Self::EVENT_INVALIDATE_PRINCIPAL => {
+                    ClusterEvent::InvalidatePrincipal(u32::from_leb128_it(&mut it)?)
+                }
+                Self::EVENT_RELOAD_SETTINGS => ClusterEvent::ReloadSettings,
+                Self::EVENT_RELOAD_BLOCKED_IPS => ClusterEvent::ReloadBlockedIps,
+                _ => return None,
+            });
+        }
+
+        Request::Events(events).into()
+    }
+
+    fn events_to_bytes(events: &[ClusterEvent]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            1 + (events.len() * std::mem::size_of::<ClusterEvent>())
+                + SymmetricEncrypt::ENCRYPT_TAG_LEN,
+        );
+        bytes.push(Self::EVENTS);
+
+        for event in events {
+            match event {
+                ClusterEvent::BlockIp(IpAddr::V4(addr)) => {
+                    bytes.push(Self::EVENT_BLOCK_IPV4);
+                    bytes.extend_from_slice(addr.octets().as_slice());
+                }
+                ClusterEvent::BlockIp(IpAddr::V6(addr)) => {
+                    bytes.push(Self::EVENT_BLOCK_IPV6);
+                    bytes.extend_from_slice(addr.octets().as_slice());
+                }
+                ClusterEvent::InvalidatePrincipal(account_id) => {
+                    bytes.push(Self::EVENT_INVALIDATE_PRINCIPAL);
+                    (*account_id).to_leb128_bytes(&mut bytes);
+                }
+                ClusterEvent::ReloadSettings => bytes.push(Self::EVENT_RELOAD_SETTINGS),
+                ClusterEvent::ReloadBlockedIps => bytes.push(Self::EVENT_RELOAD_BLOCKED_IPS),
+            }
+        }
+
+        bytes
+    }
 }
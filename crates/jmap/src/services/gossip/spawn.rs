@@ -8,8 +8,8 @@ use crate::auth::SymmetricEncrypt;
 use crate::JmapInstance;
 
 use super::request::Request;
-use super::{Gossiper, Peer, UDP_MAX_PAYLOAD};
-use common::IPC_CHANNEL_BUFFER;
+use super::{Gossiper, Peer, MAX_EVENTS_PER_PACKET, UDP_MAX_PAYLOAD};
+use common::{cluster::ClusterEvent, IPC_CHANNEL_BUFFER};
 use std::net::IpAddr;
 use std::time::{Duration, Instant};
 use std::{net::SocketAddr, sync::Arc};
@@ -64,7 +64,12 @@ impl GossiperBuilder {
         builder.into()
     }
 
-    pub async fn spawn(self, core: JmapInstance, mut shutdown_rx: watch::Receiver<bool>) {
+    pub async fn spawn(
+        self,
+        core: JmapInstance,
+        mut cluster_rx: mpsc::Receiver<ClusterEvent>,
+        mut shutdown_rx: watch::Receiver<bool>,
+    ) {
         // Bind port
         let quidnunc = Arc::new(Quidnunc {
             socket: match UdpSocket::bind(SocketAddr::new(self.bind_addr, self.port)).await {
@@ -151,6 +156,9 @@ impl GossiperBuilder {
                                                 Request::Leave(peers) => {
                                                     gossiper.handle_leave(peers).await;
                                                 },
+                                                Request::Events(events) => {
+                                                    gossiper.handle_events(events);
+                                                },
                                             }
                                         } else {
                                             tracing::debug!("Received invalid gossip message from {}", addr);
@@ -166,6 +174,17 @@ impl GossiperBuilder {
                             }
                         }
                     },
+                    Some(event) = cluster_rx.recv() => {
+                        // Replicate local events to peers
+                        let mut events = vec![event];
+                        while events.len() < MAX_EVENTS_PER_PACKET {
+                            match cluster_rx.try_recv() {
+                                Ok(event) => events.push(event),
+                                Err(_) => break,
+                            }
+                        }
+                        gossiper.broadcast_events(events).await;
+                    },
                     _ = tokio::time::sleep(wait) => {
                         // Send ping
                         gossiper.ping_peers().await;
@@ -35,25 +35,26 @@ struct IndexEmail {
 const INDEX_LOCK_EXPIRY: u64 = 60 * 5;
 
 impl JMAP {
+    // Returns the number of documents pending to be indexed
+    pub async fn fts_index_backlog(&self) -> store::Result<u64> {
+        let (from_key, to_key) = fts_queue_range();
+        let mut backlog = 0;
+        self.core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(from_key, to_key).ascending().no_values(),
+                |_, _| {
+                    backlog += 1;
+                    Ok(true)
+                },
+            )
+            .await
+            .map(|_| backlog)
+    }
+
     pub async fn fts_index_queued(&self) {
-        let from_key = ValueKey::<ValueClass<u32>> {
-            account_id: 0,
-            collection: 0,
-            document_id: 0,
-            class: ValueClass::FtsQueue(FtsQueueClass {
-                seq: 0,
-                hash: BlobHash::default(),
-            }),
-        };
-        let to_key = ValueKey::<ValueClass<u32>> {
-            account_id: u32::MAX,
-            collection: u8::MAX,
-            document_id: u32::MAX,
-            class: ValueClass::FtsQueue(FtsQueueClass {
-                seq: u64::MAX,
-                hash: BlobHash::default(),
-            }),
-        };
+        let (from_key, to_key) = fts_queue_range();
 
         // Retrieve entries pending to be indexed
         let mut entries = Vec::new();
@@ -269,3 +270,26 @@ impl IndexEmail {
         })
     }
 }
+
+fn fts_queue_range() -> (ValueKey<ValueClass<u32>>, ValueKey<ValueClass<u32>>) {
+    (
+        ValueKey {
+            account_id: 0,
+            collection: 0,
+            document_id: 0,
+            class: ValueClass::FtsQueue(FtsQueueClass {
+                seq: 0,
+                hash: BlobHash::default(),
+            }),
+        },
+        ValueKey {
+            account_id: u32::MAX,
+            collection: u8::MAX,
+            document_id: u32::MAX,
+            class: ValueClass::FtsQueue(FtsQueueClass {
+                seq: u64::MAX,
+                hash: BlobHash::default(),
+            }),
+        },
+    )
+}
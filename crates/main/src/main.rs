@@ -40,9 +40,11 @@ async fn main() -> std::io::Result<()> {
 
     // Setup IPC channels
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
+    let (cluster_tx, cluster_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
     let ipc = Ipc {
         delivery_tx,
         webhook_tx,
+        cluster_tx,
     };
 
     // Init servers
@@ -96,7 +98,10 @@ async fn main() -> std::io::Result<()> {
 
     // Spawn gossip
     if let Some(gossiper) = gossiper {
-        gossiper.spawn(jmap, shutdown_rx.clone()).await;
+        gossiper.spawn(jmap, cluster_rx, shutdown_rx.clone()).await;
+    } else {
+        // Close the channel so cluster events are discarded on single-node deployments
+        drop(cluster_rx);
     }
 
     // Wait for shutdown signal
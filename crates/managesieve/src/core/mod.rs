@@ -9,7 +9,10 @@ pub mod session;
 
 use std::{borrow::Cow, net::IpAddr, sync::Arc};
 
-use common::listener::{limiter::InFlight, ServerInstance};
+use common::{
+    listener::{limiter::InFlight, ServerInstance},
+    scram::ScramServer,
+};
 use imap::core::{ImapInstance, Inner};
 use imap_proto::receiver::{CommandParser, Receiver};
 use jmap::{auth::AccessToken, JMAP};
@@ -22,6 +25,7 @@ pub struct Session<T: AsyncRead + AsyncWrite> {
     pub receiver: Receiver<Command>,
     pub state: State,
     pub remote_addr: IpAddr,
+    pub scram: Option<ScramServer>,
     pub stream: T,
     pub span: tracing::Span,
     pub in_flight: InFlight,
@@ -124,7 +128,7 @@ pub enum ResponseCode {
     QuotaMaxScripts,
     QuotaMaxSize,
     Referral,
-    Sasl,
+    Sasl(String),
     TransitionNeeded,
     TryLater,
     Active,
@@ -143,7 +147,12 @@ impl ResponseCode {
             ResponseCode::QuotaMaxScripts => b"QUOTA/MAXSCRIPTS",
             ResponseCode::QuotaMaxSize => b"QUOTA/MAXSIZE",
             ResponseCode::Referral => b"REFERRAL",
-            ResponseCode::Sasl => b"SASL",
+            ResponseCode::Sasl(challenge) => {
+                buf.extend_from_slice(b"SASL \"");
+                buf.extend_from_slice(challenge.as_bytes());
+                buf.push(b'"');
+                return;
+            }
             ResponseCode::TransitionNeeded => b"TRANSITION-NEEDED",
             ResponseCode::TryLater => b"TRYLATER",
             ResponseCode::Active => b"ACTIVE",
@@ -33,6 +33,7 @@ impl SessionManager for ManageSieveSessionManager {
                 stream: session.stream,
                 in_flight: session.in_flight,
                 remote_addr: session.remote_ip,
+                scram: None,
             };
 
             if session
@@ -137,6 +138,7 @@ impl<T: SessionStream> Session<T> {
             imap: self.imap,
             receiver: self.receiver,
             remote_addr: self.remote_addr,
+            scram: None,
         })
     }
 }
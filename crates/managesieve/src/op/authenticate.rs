@@ -9,17 +9,18 @@ use common::{
     listener::{limiter::ConcurrencyLimiter, SessionStream},
     AuthFailureReason, AuthResult,
 };
+use directory::core::secret::ScramAlgorithm;
 use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
 use imap_proto::{
     protocol::authenticate::Mechanism,
     receiver::{self, Request},
 };
-use jmap::auth::rate_limit::ConcurrencyLimiters;
+use jmap::auth::{rate_limit::ConcurrencyLimiters, AccessToken};
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
 
-use crate::core::{Command, Session, State, StatusResponse};
+use crate::core::{Command, ResponseCode, Session, State, StatusResponse};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> crate::op::OpResult {
@@ -40,21 +41,24 @@ impl<T: SessionStream> Session<T> {
                     let challenge = base64_decode(params.pop().unwrap().as_bytes())
                         .ok_or_else(|| StatusResponse::no("Failed to decode challenge."))?;
                     (if mechanism == Mechanism::Plain {
-                        decode_challenge_plain(&challenge)
+                        decode_challenge_plain(
+                            &challenge,
+                            self.jmap.core.jmap.impersonation_group.is_some(),
+                        )
                     } else {
                         decode_challenge_oauth(&challenge)
                     }
                     .map_err(StatusResponse::no))?
                 } else {
-                    self.receiver.request = receiver::Request {
-                        tag: String::new(),
-                        command: Command::Authenticate,
-                        tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
-                    };
-                    self.receiver.state = receiver::State::Argument { last_ch: b' ' };
-                    return Ok(b"{0}\r\n".to_vec());
+                    return Ok(self.sasl_continue(mechanism, ""));
                 }
             }
+            Mechanism::ScramSha1
+            | Mechanism::ScramSha1Plus
+            | Mechanism::ScramSha256
+            | Mechanism::ScramSha256Plus => {
+                return self.handle_scram(mechanism, params.pop()).await;
+            }
             _ => {
                 return Err(StatusResponse::no(
                     "Authentication mechanism not supported.",
@@ -63,27 +67,13 @@ impl<T: SessionStream> Session<T> {
         };
 
         // Throttle authentication requests
-        if self
-            .jmap
-            .is_auth_allowed_soft(&self.remote_addr)
-            .await
-            .is_err()
-        {
-            tracing::debug!(parent: &self.span,
-                event = "disconnect",
-                "Too many authentication attempts, disconnecting.",
-            );
-            return Err(StatusResponse::bye(
-                "Too many authentication requests from this IP address.",
-            ));
-        }
+        self.throttle_auth().await?;
 
         // Authenticate
         let mut is_totp_error = false;
-        let access_token = match credentials {
-            Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
-                match self
-                    .jmap
+        let result = match credentials {
+            Credentials::Plain { username, secret } => {
+                self.jmap
                     .authenticate_plain(
                         &username,
                         &secret,
@@ -91,42 +81,148 @@ impl<T: SessionStream> Session<T> {
                         ServerProtocol::ManageSieve,
                     )
                     .await
-                {
-                    AuthResult::Success(token) => Some(token),
-                    AuthResult::Failure(
-                        AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
-                    ) => None,
-                    AuthResult::Failure(AuthFailureReason::MissingTotp) => {
-                        is_totp_error = true;
-                        None
-                    }
-                    AuthResult::Failure(AuthFailureReason::Banned) => {
-                        return Err(StatusResponse::bye(
-                            "Too many authentication requests from this IP address.",
-                        ))
-                    }
-                }
+            }
+            Credentials::XOauth2 { username, secret } => {
+                self.jmap
+                    .authenticate_bearer(
+                        &secret,
+                        Some(&username),
+                        self.remote_addr,
+                        ServerProtocol::ManageSieve,
+                    )
+                    .await
             }
             Credentials::OAuthBearer { token } => {
+                self.jmap
+                    .authenticate_bearer(
+                        &token,
+                        None,
+                        self.remote_addr,
+                        ServerProtocol::ManageSieve,
+                    )
+                    .await
+            }
+        };
+        let access_token = match result {
+            AuthResult::Success(token) => Some(token),
+            AuthResult::Failure(
+                AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
+            ) => None,
+            AuthResult::Failure(AuthFailureReason::MissingTotp) => {
+                is_totp_error = true;
+                None
+            }
+            AuthResult::Failure(AuthFailureReason::Banned) => {
+                return Err(StatusResponse::bye(
+                    "Too many authentication requests from this IP address.",
+                ))
+            }
+        };
+
+        self.complete_auth(access_token, is_totp_error, None)
+    }
+
+    async fn handle_scram(
+        &mut self,
+        mechanism: Mechanism,
+        response: Option<String>,
+    ) -> crate::op::OpResult {
+        match (self.scram.take(), response) {
+            (_, Some(response)) if response == "*" => {
+                Err(StatusResponse::no("Authentication cancelled."))
+            }
+            (None, None) => Ok(self.sasl_continue(mechanism, "")),
+            (None, Some(client_first)) => {
+                self.throttle_auth().await?;
+
+                let (algorithm, is_plus) = match mechanism {
+                    Mechanism::ScramSha1 => (ScramAlgorithm::Sha1, false),
+                    Mechanism::ScramSha1Plus => (ScramAlgorithm::Sha1, true),
+                    Mechanism::ScramSha256 => (ScramAlgorithm::Sha256, false),
+                    _ => (ScramAlgorithm::Sha256, true),
+                };
+                let client_first = base64_decode(client_first.as_bytes())
+                    .ok_or_else(|| StatusResponse::no("Failed to decode challenge."))?;
+                let server = self
+                    .jmap
+                    .scram_begin(
+                        algorithm,
+                        is_plus,
+                        &client_first,
+                        self.stream.tls_channel_binding(),
+                    )
+                    .await
+                    .map_err(|_| StatusResponse::no("Temporary server failure."))?
+                    .ok_or_else(|| StatusResponse::no("Invalid SCRAM client-first message."))?;
+                let response = self.sasl_continue(mechanism, &server.challenge());
+                self.scram = Some(server);
+                Ok(response)
+            }
+            (Some(server), Some(client_final)) => {
+                let client_final = base64_decode(client_final.as_bytes()).unwrap_or_default();
                 match self
                     .jmap
-                    .validate_access_token("access_token", &token)
+                    .authenticate_scram(
+                        server,
+                        &client_final,
+                        self.remote_addr,
+                        ServerProtocol::ManageSieve,
+                    )
                     .await
                 {
-                    Ok((account_id, _, _)) => self.jmap.get_access_token(account_id).await,
-                    Err(err) => {
-                        tracing::debug!(
-                            parent: &self.span,
-                            context = "authenticate",
-                            err = err,
-                            "Failed to validate access token."
-                        );
-                        None
+                    AuthResult::Success((access_token, server_final)) => {
+                        // The server signature is sent with the OK response
+                        self.complete_auth(Some(access_token), false, Some(server_final))
                     }
+                    AuthResult::Failure(AuthFailureReason::Banned) => Err(StatusResponse::bye(
+                        "Too many authentication requests from this IP address.",
+                    )),
+                    AuthResult::Failure(_) => self.complete_auth(None, false, None),
                 }
             }
+            (Some(_), None) => Err(StatusResponse::no("Unexpected SCRAM response.")),
+        }
+    }
+
+    fn sasl_continue(&mut self, mechanism: Mechanism, challenge: &str) -> Vec<u8> {
+        self.receiver.request = receiver::Request {
+            tag: String::new(),
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
         };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+        if !challenge.is_empty() {
+            format!("\"{challenge}\"\r\n").into_bytes()
+        } else {
+            b"{0}\r\n".to_vec()
+        }
+    }
 
+    async fn throttle_auth(&self) -> Result<(), StatusResponse> {
+        if self
+            .jmap
+            .is_auth_allowed_soft(&self.remote_addr)
+            .await
+            .is_err()
+        {
+            tracing::debug!(parent: &self.span,
+                event = "disconnect",
+                "Too many authentication attempts, disconnecting.",
+            );
+            Err(StatusResponse::bye(
+                "Too many authentication requests from this IP address.",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn complete_auth(
+        &mut self,
+        access_token: Option<AccessToken>,
+        is_totp_error: bool,
+        server_final: Option<String>,
+    ) -> crate::op::OpResult {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = match self
@@ -154,7 +250,13 @@ impl<T: SessionStream> Session<T> {
                 in_flight,
             };
 
-            Ok(StatusResponse::ok("Authentication successful").into_bytes())
+            let response = StatusResponse::ok("Authentication successful");
+            Ok(if let Some(server_final) = server_final {
+                response.with_code(ResponseCode::Sasl(server_final))
+            } else {
+                response
+            }
+            .into_bytes())
         } else {
             match &self.state {
                 State::NotAuthenticated { auth_failures }
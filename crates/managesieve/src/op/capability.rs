@@ -17,10 +17,15 @@ impl<T: SessionStream> Session<T> {
         if !self.stream.is_tls() {
             response.extend_from_slice(b"\"STARTTLS\"\r\n");
         }
-        if self.stream.is_tls() || self.jmap.core.imap.allow_plain_auth {
-            response.extend_from_slice(b"\"SASL\" \"PLAIN OAUTHBEARER\"\r\n");
+        if self.stream.is_tls() {
+            response.extend_from_slice(
+                b"\"SASL\" \"PLAIN OAUTHBEARER SCRAM-SHA-256 SCRAM-SHA-1 SCRAM-SHA-256-PLUS SCRAM-SHA-1-PLUS\"\r\n",
+            );
+        } else if self.jmap.core.imap.allow_plain_auth {
+            response
+                .extend_from_slice(b"\"SASL\" \"PLAIN OAUTHBEARER SCRAM-SHA-256 SCRAM-SHA-1\"\r\n");
         } else {
-            response.extend_from_slice(b"\"SASL\" \"OAUTHBEARER\"\r\n");
+            response.extend_from_slice(b"\"SASL\" \"OAUTHBEARER SCRAM-SHA-256 SCRAM-SHA-1\"\r\n");
         };
         if let Some(sieve) =
             self.jmap
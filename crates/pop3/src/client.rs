@@ -100,12 +100,21 @@ impl<T: SessionStream> Session<T> {
                             self.handle_rset().await?;
                         }
                         Command::Capa => {
-                            let mechanisms =
-                                if self.stream.is_tls() || self.jmap.core.imap.allow_plain_auth {
-                                    vec![Mechanism::Plain, Mechanism::OAuthBearer]
-                                } else {
-                                    vec![Mechanism::OAuthBearer]
-                                };
+                            let mut mechanisms = Vec::with_capacity(7);
+                            if self.stream.is_tls() || self.jmap.core.imap.allow_plain_auth {
+                                mechanisms.push(Mechanism::Plain);
+                            }
+                            mechanisms.extend([
+                                Mechanism::OAuthBearer,
+                                Mechanism::XOauth2,
+                                Mechanism::ScramSha256,
+                                Mechanism::ScramSha1,
+                            ]);
+                            if self.stream.is_tls() {
+                                // Channel binding requires TLS
+                                mechanisms
+                                    .extend([Mechanism::ScramSha256Plus, Mechanism::ScramSha1Plus]);
+                            }
 
                             self.write_bytes(
                                 Response::Capability::<u32> {
@@ -7,7 +7,7 @@
 use std::{net::IpAddr, sync::Arc};
 
 use common::listener::{limiter::InFlight, ServerInstance, SessionStream};
-use imap::core::{ImapInstance, Inner};
+use imap::core::{ImapInstance, Inner, ScramState};
 use jmap::JMAP;
 use mailbox::Mailbox;
 use protocol::request::Parser;
@@ -40,6 +40,7 @@ pub struct Session<T: SessionStream> {
     pub stream: T,
     pub in_flight: InFlight,
     pub remote_addr: IpAddr,
+    pub scram: Option<ScramState>,
     pub span: tracing::Span,
 }
 
@@ -6,15 +6,27 @@
 
 use std::collections::BTreeMap;
 
-use common::listener::SessionStream;
-use jmap::mailbox::{UidMailbox, INBOX_ID};
+use common::{config::imap::Pop3View, listener::SessionStream};
+use jmap::{
+    email::set::TagManager,
+    mailbox::{UidMailbox, INBOX_ID, JUNK_ID, TRASH_ID},
+};
 use jmap_proto::{
     error::method::MethodError,
     object::Object,
-    types::{collection::Collection, property::Property, value::Value},
+    types::{
+        collection::Collection, id::Id, keyword::Keyword, property::Property, state::StateChange,
+        type_state::DataType, value::Value,
+    },
 };
 use store::{
-    ahash::AHashMap, write::key::DeserializeBigEndian, IndexKey, IterateParams, Serialize, U32_LEN,
+    ahash::AHashMap,
+    roaring::RoaringBitmap,
+    write::{
+        assert::HashedValue, key::DeserializeBigEndian, log::ChangeLogBuilder, BatchBuilder,
+        F_VALUE,
+    },
+    IndexKey, IterateParams, Serialize, U32_LEN,
 };
 
 use crate::Session;
@@ -23,7 +35,6 @@ use crate::Session;
 pub struct Mailbox {
     pub messages: Vec<Message>,
     pub account_id: u32,
-    pub uid_validity: u32,
     pub total: u32,
     pub size: u32,
 }
@@ -31,54 +42,90 @@ pub struct Mailbox {
 pub struct Message {
     pub id: u32,
     pub uid: u32,
+    pub uidl: String,
     pub size: u32,
     pub deleted: bool,
+    pub retrieved: bool,
 }
 
 impl<T: SessionStream> Session<T> {
     pub async fn fetch_mailbox(&self, account_id: u32) -> Result<Mailbox, MethodError> {
-        // Obtain message ids
-        let message_ids = self
-            .jmap
-            .get_tag(
-                account_id,
-                Collection::Email,
-                Property::MailboxIds,
-                INBOX_ID,
-            )
-            .await?
-            .unwrap_or_default();
+        // Obtain the mailboxes included in the POP3 view
+        self.jmap.mailbox_get_or_create(account_id).await?;
+        let mailbox_ids = match &self.jmap.core.imap.pop3_view {
+            Pop3View::Inbox => vec![INBOX_ID],
+            Pop3View::All => self
+                .jmap
+                .get_document_ids(account_id, Collection::Mailbox)
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|mailbox_id| ![TRASH_ID, JUNK_ID].contains(mailbox_id))
+                .collect(),
+            Pop3View::Folder(folder) => {
+                if let Some(mailbox_id) = self.jmap.mailbox_get_by_name(account_id, folder).await? {
+                    vec![mailbox_id]
+                } else {
+                    tracing::debug!(parent: &self.span,
+                        context = "fetch_mailbox",
+                        account_id = account_id,
+                        folder = folder,
+                        "POP3 folder does not exist");
+                    return Ok(Mailbox {
+                        account_id,
+                        ..Default::default()
+                    });
+                }
+            }
+        };
+
+        // Obtain message ids and UID validity of each mailbox
+        let mut message_ids = RoaringBitmap::new();
+        let mut uid_validities = AHashMap::with_capacity(mailbox_ids.len());
+        for &mailbox_id in &mailbox_ids {
+            message_ids |= self
+                .jmap
+                .get_tag(
+                    account_id,
+                    Collection::Email,
+                    Property::MailboxIds,
+                    mailbox_id,
+                )
+                .await?
+                .unwrap_or_default();
+            let uid_validity = self
+                .jmap
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::Mailbox,
+                    mailbox_id,
+                    &Property::Value,
+                )
+                .await?
+                .and_then(|obj| obj.get(&Property::Cid).as_uint())
+                .ok_or_else(|| {
+                    tracing::debug!(event = "error",
+                    context = "store",
+                    account_id = account_id,
+                    collection = ?Collection::Mailbox,
+                    mailbox_id = mailbox_id,
+                    "Failed to obtain uid validity");
+                    MethodError::ServerPartialFail
+                })
+                .map(|v| v as u32)?;
+            uid_validities.insert(mailbox_id, uid_validity);
+        }
 
         if message_ids.is_empty() {
-            return Ok(Mailbox::default());
+            return Ok(Mailbox {
+                account_id,
+                ..Default::default()
+            });
         }
 
         let mut message_map = BTreeMap::new();
         let mut message_sizes = AHashMap::new();
 
-        // Obtain UID validity
-        self.jmap.mailbox_get_or_create(account_id).await?;
-        let uid_validity = self
-            .jmap
-            .get_property::<Object<Value>>(
-                account_id,
-                Collection::Mailbox,
-                INBOX_ID,
-                &Property::Value,
-            )
-            .await?
-            .and_then(|obj| obj.get(&Property::Cid).as_uint())
-            .ok_or_else(|| {
-                tracing::debug!(event = "error",
-                context = "store",
-                account_id = account_id,
-                collection = ?Collection::Mailbox,
-                mailbox_id = INBOX_ID,
-                "Failed to obtain uid validity");
-                MethodError::ServerPartialFail
-            })
-            .map(|v| v as u32)?;
-
         // Obtain message sizes
         self.jmap
             .core
@@ -123,7 +170,7 @@ impl<T: SessionStream> Session<T> {
                 MethodError::ServerPartialFail
             })?;
 
-        // Sort by UID
+        // Sort by mailbox and UID, messages in multiple mailboxes are listed once
         for (message_id, uid_mailbox) in self
             .jmap
             .get_properties::<Vec<UidMailbox>, _, _>(
@@ -135,27 +182,40 @@ impl<T: SessionStream> Session<T> {
             .await?
             .into_iter()
         {
-            // Make sure the message is still in Inbox
-            if let Some(item) = uid_mailbox.iter().find(|item| item.mailbox_id == INBOX_ID) {
+            // Make sure the message is still in the view
+            if let Some(item) = uid_mailbox
+                .iter()
+                .filter(|item| mailbox_ids.contains(&item.mailbox_id))
+                .min_by_key(|item| item.mailbox_id)
+            {
                 debug_assert!(item.uid != 0, "UID is zero for message {item:?}");
-                message_map.insert(item.uid, message_id);
+                message_map.insert((item.mailbox_id, item.uid), message_id);
             }
         }
 
         // Create mailbox
         let mut mailbox = Mailbox {
             messages: Vec::with_capacity(message_map.len()),
-            uid_validity,
             account_id,
             ..Default::default()
         };
-        for (uid, id) in message_map {
+        let is_multi_folder = matches!(&self.jmap.core.imap.pop3_view, Pop3View::All);
+        for ((mailbox_id, uid), id) in message_map {
             if let Some(size) = message_sizes.get(&id) {
+                let uid_validity = uid_validities.get(&mailbox_id).copied().unwrap_or_default();
                 mailbox.messages.push(Message {
                     id,
                     uid,
+                    // UIDLs of views spanning multiple folders use a fixed width
+                    // encoding to avoid collisions between folders
+                    uidl: if is_multi_folder {
+                        format!("{uid_validity:08x}{uid:08x}")
+                    } else {
+                        format!("{uid_validity}{uid}")
+                    },
                     size: *size,
                     deleted: false,
+                    retrieved: false,
                 });
                 mailbox.total += 1;
                 mailbox.size += *size;
@@ -164,4 +224,80 @@ impl<T: SessionStream> Session<T> {
 
         Ok(mailbox)
     }
+
+    pub async fn set_download_keyword(
+        &self,
+        account_id: u32,
+        keyword: Keyword,
+        message_ids: Vec<u32>,
+    ) -> Result<(), MethodError> {
+        let mut changelog = ChangeLogBuilder::new();
+        for message_id in message_ids {
+            // Obtain current keywords
+            let (mut keywords, thread_id) = if let (Some(keywords), Some(thread_id)) = (
+                self.jmap
+                    .get_property::<HashedValue<Vec<Keyword>>>(
+                        account_id,
+                        Collection::Email,
+                        message_id,
+                        Property::Keywords,
+                    )
+                    .await?,
+                self.jmap
+                    .get_property::<u32>(
+                        account_id,
+                        Collection::Email,
+                        message_id,
+                        Property::ThreadId,
+                    )
+                    .await?,
+            ) {
+                (TagManager::new(keywords), thread_id)
+            } else {
+                continue;
+            };
+
+            keywords.update(keyword.clone(), true);
+            if !keywords.has_changes() {
+                continue;
+            }
+
+            // Write changes
+            if changelog.change_id == u64::MAX {
+                changelog.change_id = self.jmap.assign_change_id(account_id).await?;
+            }
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Email)
+                .update_document(message_id);
+            keywords.update_batch(&mut batch, Property::Keywords);
+            batch.value(Property::Cid, changelog.change_id, F_VALUE);
+            match self.jmap.write_batch(batch).await {
+                Ok(_) => {
+                    changelog.log_update(Collection::Email, Id::from_parts(thread_id, message_id));
+                }
+                Err(MethodError::ServerUnavailable) => {
+                    // The message was modified by another session, skip it
+                    tracing::debug!(parent: &self.span,
+                        context = "set_download_keyword",
+                        account_id = account_id,
+                        document_id = message_id,
+                        "Message was modified concurrently");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        if !changelog.is_empty() {
+            let change_id = self.jmap.commit_changes(account_id, changelog).await?;
+            self.jmap
+                .broadcast_state_change(
+                    StateChange::new(account_id).with_change(DataType::Email, change_id),
+                )
+                .await;
+        }
+
+        Ok(())
+    }
 }
@@ -9,8 +9,12 @@ use common::{
     listener::{limiter::ConcurrencyLimiter, SessionStream},
     AuthFailureReason, AuthResult,
 };
-use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
-use jmap::auth::rate_limit::ConcurrencyLimiters;
+use directory::core::secret::ScramAlgorithm;
+use imap::{
+    core::ScramState,
+    op::authenticate::{decode_challenge_oauth, decode_challenge_plain},
+};
+use jmap::auth::{rate_limit::ConcurrencyLimiters, AccessToken};
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
@@ -27,13 +31,16 @@ impl<T: SessionStream> Session<T> {
         mut params: Vec<String>,
     ) -> Result<(), ()> {
         match mechanism {
-            Mechanism::Plain | Mechanism::OAuthBearer => {
+            Mechanism::Plain | Mechanism::OAuthBearer | Mechanism::XOauth2 => {
                 if !params.is_empty() {
                     let result = base64_decode(params.pop().unwrap().as_bytes())
                         .ok_or("Failed to decode challenge.")
                         .and_then(|challenge| {
                             if mechanism == Mechanism::Plain {
-                                decode_challenge_plain(&challenge)
+                                decode_challenge_plain(
+                                    &challenge,
+                                    self.jmap.core.jmap.impersonation_group.is_some(),
+                                )
                             } else {
                                 decode_challenge_oauth(&challenge)
                             }
@@ -44,19 +51,13 @@ impl<T: SessionStream> Session<T> {
                         Err(err) => self.write_err(err).await,
                     }
                 } else {
-                    // TODO: This hack is temporary until the SASL library is developed
-                    self.receiver.state = request::State::Argument {
-                        request: Command::Auth {
-                            mechanism: mechanism.as_str().as_bytes().to_vec(),
-                            params: vec![],
-                        },
-                        num: 1,
-                        last_is_space: true,
-                    };
-
-                    self.write_bytes("+\r\n").await
+                    self.sasl_continue(mechanism, "").await
                 }
             }
+            Mechanism::ScramSha1
+            | Mechanism::ScramSha1Plus
+            | Mechanism::ScramSha256
+            | Mechanism::ScramSha256Plus => self.handle_scram(mechanism, params.pop()).await,
             _ => {
                 self.write_err("Authentication mechanism not supported.")
                     .await
@@ -64,8 +65,102 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
-    pub async fn handle_auth(&mut self, credentials: Credentials<String>) -> Result<(), ()> {
-        // Throttle authentication requests
+    async fn handle_scram(
+        &mut self,
+        mechanism: Mechanism,
+        response: Option<String>,
+    ) -> Result<(), ()> {
+        match (self.scram.take(), response) {
+            (_, Some(response)) if response == "*" => {
+                self.write_err("Authentication cancelled.").await
+            }
+            (None, None) => self.sasl_continue(mechanism, "").await,
+            (None, Some(client_first)) => {
+                self.throttle_auth().await?;
+
+                let (algorithm, is_plus) = match mechanism {
+                    Mechanism::ScramSha1 => (ScramAlgorithm::Sha1, false),
+                    Mechanism::ScramSha1Plus => (ScramAlgorithm::Sha1, true),
+                    Mechanism::ScramSha256 => (ScramAlgorithm::Sha256, false),
+                    _ => (ScramAlgorithm::Sha256, true),
+                };
+                let result = match base64_decode(client_first.as_bytes()) {
+                    Some(client_first) => {
+                        self.jmap
+                            .scram_begin(
+                                algorithm,
+                                is_plus,
+                                &client_first,
+                                self.stream.tls_channel_binding(),
+                            )
+                            .await
+                    }
+                    None => Ok(None),
+                };
+
+                match result {
+                    Ok(Some(server)) => {
+                        let server_first = server.challenge();
+                        self.scram = Some(ScramState::Challenge(Box::new(server)));
+                        self.sasl_continue(mechanism, &server_first).await
+                    }
+                    Ok(None) => self.write_err("Invalid SCRAM client-first message.").await,
+                    Err(_) => self.write_err("Temporary server failure").await,
+                }
+            }
+            (Some(ScramState::Challenge(server)), Some(client_final)) => {
+                let client_final = base64_decode(client_final.as_bytes()).unwrap_or_default();
+                match self
+                    .jmap
+                    .authenticate_scram(
+                        *server,
+                        &client_final,
+                        self.remote_addr,
+                        ServerProtocol::Pop3,
+                    )
+                    .await
+                {
+                    AuthResult::Success((access_token, server_final)) => {
+                        // Send the server signature and wait for an empty response
+                        self.scram = Some(ScramState::Verified(access_token));
+                        self.sasl_continue(mechanism, &server_final).await
+                    }
+                    AuthResult::Failure(AuthFailureReason::Banned) => {
+                        self.write_err("Too many authentication requests from this IP address.")
+                            .await?;
+                        Err(())
+                    }
+                    AuthResult::Failure(_) => self.complete_auth(None, false).await,
+                }
+            }
+            (Some(ScramState::Verified(access_token)), None) => {
+                self.complete_auth(Some(access_token), false).await
+            }
+            (Some(ScramState::Challenge(_)), None) | (Some(ScramState::Verified(_)), Some(_)) => {
+                self.write_err("Unexpected SCRAM response.").await
+            }
+        }
+    }
+
+    async fn sasl_continue(&mut self, mechanism: Mechanism, challenge: &str) -> Result<(), ()> {
+        // TODO: This hack is temporary until the SASL library is developed
+        self.receiver.state = request::State::Argument {
+            request: Command::Auth {
+                mechanism: mechanism.as_str().as_bytes().to_vec(),
+                params: vec![],
+            },
+            num: 1,
+            last_is_space: true,
+        };
+
+        if !challenge.is_empty() {
+            self.write_bytes(format!("+ {challenge}\r\n")).await
+        } else {
+            self.write_bytes("+\r\n").await
+        }
+    }
+
+    async fn throttle_auth(&mut self) -> Result<(), ()> {
         if self
             .jmap
             .is_auth_allowed_soft(&self.remote_addr)
@@ -79,53 +174,64 @@ impl<T: SessionStream> Session<T> {
 
             self.write_err("Too many authentication requests from this IP address.")
                 .await?;
-            return Err(());
+            Err(())
+        } else {
+            Ok(())
         }
+    }
+
+    pub async fn handle_auth(&mut self, credentials: Credentials<String>) -> Result<(), ()> {
+        // Throttle authentication requests
+        self.throttle_auth().await?;
 
         // Authenticate
         let mut is_totp_error = false;
-        let access_token = match credentials {
-            Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
-                match self
-                    .jmap
+        let result = match credentials {
+            Credentials::Plain { username, secret } => {
+                self.jmap
                     .authenticate_plain(&username, &secret, self.remote_addr, ServerProtocol::Pop3)
                     .await
-                {
-                    AuthResult::Success(token) => Some(token),
-                    AuthResult::Failure(
-                        AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
-                    ) => None,
-                    AuthResult::Failure(AuthFailureReason::MissingTotp) => {
-                        is_totp_error = true;
-                        None
-                    }
-                    AuthResult::Failure(AuthFailureReason::Banned) => {
-                        self.write_err("Too many authentication requests from this IP address.")
-                            .await?;
-                        return Err(());
-                    }
-                }
+            }
+            Credentials::XOauth2 { username, secret } => {
+                self.jmap
+                    .authenticate_bearer(
+                        &secret,
+                        Some(&username),
+                        self.remote_addr,
+                        ServerProtocol::Pop3,
+                    )
+                    .await
             }
             Credentials::OAuthBearer { token } => {
-                match self
-                    .jmap
-                    .validate_access_token("access_token", &token)
+                self.jmap
+                    .authenticate_bearer(&token, None, self.remote_addr, ServerProtocol::Pop3)
                     .await
-                {
-                    Ok((account_id, _, _)) => self.jmap.get_access_token(account_id).await,
-                    Err(err) => {
-                        tracing::debug!(
-                            parent: &self.span,
-                            context = "authenticate",
-                            err = err,
-                            "Failed to validate access token."
-                        );
-                        None
-                    }
-                }
+            }
+        };
+        let access_token = match result {
+            AuthResult::Success(token) => Some(token),
+            AuthResult::Failure(
+                AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
+            ) => None,
+            AuthResult::Failure(AuthFailureReason::MissingTotp) => {
+                is_totp_error = true;
+                None
+            }
+            AuthResult::Failure(AuthFailureReason::Banned) => {
+                self.write_err("Too many authentication requests from this IP address.")
+                    .await?;
+                return Err(());
             }
         };
 
+        self.complete_auth(access_token, is_totp_error).await
+    }
+
+    async fn complete_auth(
+        &mut self,
+        access_token: Option<AccessToken>,
+        is_totp_error: bool,
+    ) -> Result<(), ()> {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = match self
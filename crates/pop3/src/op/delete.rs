@@ -5,7 +5,7 @@
  */
 
 use common::listener::SessionStream;
-use jmap_proto::types::{state::StateChange, type_state::DataType};
+use jmap_proto::types::{keyword::Keyword, state::StateChange, type_state::DataType};
 use store::roaring::RoaringBitmap;
 
 use crate::{Session, State};
@@ -47,6 +47,32 @@ impl<T: SessionStream> Session<T> {
 
     pub async fn handle_quit(&mut self) -> Result<(), ()> {
         if let State::Authenticated { mailbox, .. } = &self.state {
+            // Flag retrieved messages that are left on the server
+            if let Some(keyword) = &self.jmap.core.imap.pop3_download_keyword {
+                let retrieved = mailbox
+                    .messages
+                    .iter()
+                    .filter(|message| message.retrieved && !message.deleted)
+                    .map(|message| message.id)
+                    .collect::<Vec<_>>();
+                if !retrieved.is_empty() {
+                    if let Err(err) = self
+                        .set_download_keyword(
+                            mailbox.account_id,
+                            Keyword::from(keyword.clone()),
+                            retrieved,
+                        )
+                        .await
+                    {
+                        tracing::warn!(parent: &self.span,
+                            context = "quit",
+                            account_id = mailbox.account_id,
+                            error = ?err,
+                            "Failed to set download keyword.");
+                    }
+                }
+            }
+
             let mut deleted = RoaringBitmap::new();
             for message in &mailbox.messages {
                 if message.deleted {
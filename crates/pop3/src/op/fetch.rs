@@ -32,6 +32,18 @@ impl<T: SessionStream> Session<T> {
                         .await
                     {
                         Ok(Some(bytes)) => {
+                            // Messages retrieved in full are flagged as downloaded on QUIT
+                            if lines.is_none() {
+                                if let Some(message) = self
+                                    .state
+                                    .mailbox_mut()
+                                    .messages
+                                    .get_mut(msg.saturating_sub(1) as usize)
+                                {
+                                    message.retrieved = true;
+                                }
+                            }
+
                             self.write_bytes(
                                 Response::Message::<u32> {
                                     bytes,
@@ -30,8 +30,7 @@ impl<T: SessionStream> Session<T> {
         let mailbox = self.state.mailbox();
         if let Some(msg) = msg {
             if let Some(message) = mailbox.messages.get(msg.saturating_sub(1) as usize) {
-                self.write_ok(format!("{} {}{}", msg, mailbox.uid_validity, message.uid))
-                    .await
+                self.write_ok(format!("{} {}", msg, message.uidl)).await
             } else {
                 self.write_err("No such message").await
             }
@@ -41,7 +40,7 @@ impl<T: SessionStream> Session<T> {
                     mailbox
                         .messages
                         .iter()
-                        .map(|m| format!("{}{}", mailbox.uid_validity, m.uid))
+                        .map(|m| m.uidl.clone())
                         .collect::<Vec<_>>(),
                 )
                 .serialize(),
@@ -63,7 +63,9 @@ pub enum Mechanism {
     CramMd5,
     DigestMd5,
     ScramSha1,
+    ScramSha1Plus,
     ScramSha256,
+    ScramSha256Plus,
     Apop,
     Ntlm,
     Gssapi,
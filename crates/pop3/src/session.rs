@@ -36,6 +36,7 @@ impl SessionManager for Pop3SessionManager {
                 stream: session.stream,
                 in_flight: session.in_flight,
                 remote_addr: session.remote_ip,
+                scram: None,
                 span: session.span,
             };
 
@@ -124,6 +125,7 @@ impl<T: SessionStream> Session<T> {
             span: self.span,
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
+            scram: None,
         })
     }
 }
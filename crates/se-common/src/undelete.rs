@@ -31,6 +31,8 @@ pub struct DeletedBlob<H, T, C> {
     #[serde(rename = "expiresAt")]
     pub expires_at: T,
     pub collection: C,
+    #[serde(skip)]
+    pub metadata: Vec<u8>,
 }
 
 pub trait Undelete: Sync + Send {
@@ -40,6 +42,7 @@ pub trait Undelete: Sync + Send {
         collection: u8,
         blob_hash: &BlobHash,
         blob_size: usize,
+        metadata: Vec<u8>,
     );
     fn list_deleted(
         &self,
@@ -54,6 +57,7 @@ impl Undelete for Core {
         collection: u8,
         blob_hash: &BlobHash,
         blob_size: usize,
+        metadata: Vec<u8>,
     ) {
         if let Some(hold_period) = self.enterprise.as_ref().and_then(|e| e.undelete_period) {
             let now = now();
@@ -63,10 +67,11 @@ impl Undelete for Core {
                     hash: blob_hash.clone(),
                     until: now + hold_period.as_secs(),
                 },
-                KeySerializer::new(U64_LEN + U64_LEN)
+                KeySerializer::new(U64_LEN + U64_LEN + metadata.len())
                     .write(blob_size as u32)
                     .write(now)
                     .write(collection)
+                    .write(metadata.as_slice())
                     .finalize(),
             );
         }
@@ -104,7 +109,7 @@ impl Undelete for Core {
                 IterateParams::new(from_key, to_key).ascending(),
                 |key, value| {
                     let expires_at = key.deserialize_be_u64(key.len() - U64_LEN)?;
-                    if value.len() == U32_LEN + U64_LEN + 1 && expires_at > now {
+                    if value.len() > U32_LEN + U64_LEN && expires_at > now {
                         results.push(DeletedBlob {
                             hash: BlobHash::try_from_hash_slice(
                                 key.get(U32_LEN..U32_LEN + BLOB_HASH_LEN).ok_or_else(|| {
@@ -117,7 +122,8 @@ impl Undelete for Core {
                             size: value.deserialize_be_u32(0)? as usize,
                             deleted_at: value.deserialize_be_u64(U32_LEN)?,
                             expires_at,
-                            collection: *value.last().unwrap(),
+                            collection: value[U32_LEN + U64_LEN],
+                            metadata: value[U32_LEN + U64_LEN + 1..].to_vec(),
                         });
                     }
                     Ok(true)
@@ -35,14 +35,19 @@ use utils::snowflake::SnowflakeIdGenerator;
 
 use crate::{
     inbound::auth::SaslToken,
+    outbound::pool::ConnectionPool,
     queue::{self, DomainPart, QueueId},
     reporting,
 };
 
-use self::throttle::{ThrottleKey, ThrottleKeyHasherBuilder};
+use self::{
+    throttle::{ThrottleKey, ThrottleKeyHasherBuilder},
+    transcript::SessionTranscript,
+};
 
 pub mod params;
 pub mod throttle;
+pub mod transcript;
 
 #[derive(Clone)]
 pub struct SmtpInstance {
@@ -85,6 +90,7 @@ pub struct Inner {
     pub connectors: TlsConnectors,
     pub ipc: Ipc,
     pub script_cache: ScriptCache,
+    pub connection_pool: ConnectionPool,
 }
 
 pub struct TlsConnectors {
@@ -145,6 +151,8 @@ pub struct SessionData {
     pub spf_ehlo: Option<SpfOutput>,
     pub spf_mail_from: Option<SpfOutput>,
     pub dnsbl_error: Option<Vec<u8>>,
+
+    pub transcript: Option<SessionTranscript>,
 }
 
 #[derive(Clone)]
@@ -214,6 +222,7 @@ impl SessionData {
             spf_ehlo: None,
             spf_mail_from: None,
             dnsbl_error: None,
+            transcript: None,
         }
     }
 }
@@ -270,6 +279,7 @@ static ref SIEVE: Arc<ServerInstance> = Arc::new(ServerInstance {
     limiter: ConcurrencyLimiter::new(0),
     shutdown_rx: tokio::sync::watch::channel(false).1,
     proxy_networks: vec![],
+    compress: false,
 });
 }
 
@@ -380,6 +390,7 @@ impl SessionData {
             spf_ehlo: None,
             spf_mail_from: None,
             dnsbl_error: None,
+            transcript: None,
         }
     }
 }
@@ -419,8 +430,10 @@ impl Default for Inner {
             ipc: Ipc {
                 delivery_tx: mpsc::channel(1).0,
                 webhook_tx: mpsc::channel(1).0,
+                cluster_tx: mpsc::channel(1).0,
             },
             script_cache: Default::default(),
+            connection_pool: Default::default(),
         }
     }
 }
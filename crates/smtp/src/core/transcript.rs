@@ -0,0 +1,349 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{borrow::Cow, net::IpAddr, time::Duration};
+
+use common::{config::smtp::session::Transcript, listener::SessionStream};
+use serde::{Deserialize, Serialize};
+use smtp_proto::{request::receiver::MAX_LINE_LENGTH, EhloResponse, Response};
+use store::write::{now, BatchBuilder, QueueClass, ValueClass};
+
+use crate::queue::QueueId;
+
+use super::{Session, SMTP};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptRecord {
+    pub id: u64,
+    pub direction: Direction,
+    #[serde(rename = "remoteIp")]
+    pub remote_ip: IpAddr,
+    #[serde(rename = "remoteHost")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listener: Option<String>,
+    #[serde(rename = "queueId")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_id: Option<QueueId>,
+    pub created: u64,
+    pub truncated: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<TranscriptLine>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptLine {
+    #[serde(rename = "type")]
+    pub typ: LineType,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineType {
+    Client,
+    Server,
+    Info,
+}
+
+pub enum ClientInput {
+    Command,
+    Sasl,
+    Data,
+}
+
+// Records the command/response exchange of a session, the transcript
+// is written to the store once dropped if the session was selected.
+pub struct SessionTranscript {
+    core: SMTP,
+    record: TranscriptRecord,
+    size: usize,
+    max_size: usize,
+    retention: Duration,
+    line: Vec<u8>,
+    data_len: usize,
+    pub selected: bool,
+}
+
+impl SessionTranscript {
+    pub fn new(core: SMTP, config: &Transcript, direction: Direction, remote_ip: IpAddr) -> Self {
+        let created = now();
+        SessionTranscript {
+            record: TranscriptRecord {
+                id: core.inner.snowflake_id.generate().unwrap_or(created),
+                direction,
+                remote_ip,
+                remote_host: None,
+                listener: None,
+                queue_id: None,
+                created,
+                truncated: false,
+                lines: Vec::new(),
+            },
+            core,
+            size: 0,
+            max_size: config.max_size,
+            retention: config.retention,
+            line: Vec::new(),
+            data_len: 0,
+            selected: false,
+        }
+    }
+
+    pub fn with_remote_host(mut self, remote_host: impl Into<String>) -> Self {
+        self.record.remote_host = Some(remote_host.into());
+        self
+    }
+
+    pub fn with_listener(mut self, listener: impl Into<String>) -> Self {
+        self.record.listener = Some(listener.into());
+        self
+    }
+
+    pub fn with_queue_id(mut self, queue_id: QueueId) -> Self {
+        self.record.queue_id = Some(queue_id);
+        self
+    }
+
+    pub fn set_remote_host(&mut self, remote_host: &str) {
+        if self.record.remote_host.as_deref() != Some(remote_host) {
+            self.record.remote_host = Some(remote_host.to_string());
+        }
+    }
+
+    pub fn client(&mut self, bytes: &[u8]) {
+        self.flush_data();
+        for &ch in bytes {
+            if ch == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end();
+                self.push(LineType::Client, &redact_auth(line));
+            } else if self.line.len() < MAX_LINE_LENGTH {
+                self.line.push(ch);
+            }
+        }
+    }
+
+    pub fn client_sasl(&mut self, bytes: &[u8]) {
+        self.flush_data();
+        for _ in bytes.iter().filter(|&&ch| ch == b'\n') {
+            self.push(LineType::Client, "[redacted]");
+        }
+    }
+
+    pub fn client_data(&mut self, len: usize) {
+        self.data_len += len;
+    }
+
+    pub fn server(&mut self, bytes: &[u8]) {
+        self.flush_data();
+        for line in String::from_utf8_lossy(bytes).lines() {
+            self.push(LineType::Server, line);
+        }
+    }
+
+    pub fn reply(&mut self, response: &Response<String>) {
+        let text = if response.esc[0] != 0 {
+            format!(
+                "{} {}.{}.{} {}",
+                response.code, response.esc[0], response.esc[1], response.esc[2], response.message
+            )
+        } else {
+            format!("{} {}", response.code, response.message)
+        };
+        self.server(text.as_bytes());
+    }
+
+    pub fn ehlo(&mut self, response: &EhloResponse<String>) {
+        let mut buf = Vec::with_capacity(128);
+        let _ = response.write(&mut buf);
+        self.server(&buf);
+    }
+
+    pub fn error(&mut self, err: &mail_send::Error) {
+        match err {
+            mail_send::Error::UnexpectedReply(response) => self.reply(response),
+            err => self.info(err.to_string()),
+        }
+    }
+
+    pub fn result(&mut self, result: &Result<Response<String>, mail_send::Error>) {
+        match result {
+            Ok(response) => self.reply(response),
+            Err(err) => self.error(err),
+        }
+    }
+
+    pub fn info(&mut self, text: impl AsRef<str>) {
+        self.flush_data();
+        self.push(LineType::Info, text.as_ref());
+    }
+
+    fn flush_data(&mut self) {
+        if self.data_len > 0 {
+            let text = format!("[{} bytes of message data redacted]", self.data_len);
+            self.data_len = 0;
+            self.push(LineType::Info, &text);
+        }
+    }
+
+    fn push(&mut self, typ: LineType, text: &str) {
+        if !self.record.truncated {
+            if self.size + text.len() <= self.max_size {
+                self.size += text.len();
+                self.record.lines.push(TranscriptLine {
+                    typ,
+                    text: text.to_string(),
+                });
+            } else {
+                self.record.truncated = true;
+            }
+        }
+    }
+}
+
+impl Drop for SessionTranscript {
+    fn drop(&mut self) {
+        if self.selected {
+            self.flush_data();
+            let core = self.core.clone();
+            let id = self.record.id;
+            let expires = self.record.created + self.retention.as_secs();
+            let value = serde_json::to_vec(&self.record).unwrap_or_default();
+            let lines = std::mem::take(&mut self.record.lines);
+            let summary = serde_json::to_vec(&self.record).unwrap_or_default();
+            self.record.lines = lines;
+
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    core.write_transcript(id, expires, value, summary).await;
+                });
+            }
+        }
+    }
+}
+
+impl SMTP {
+    // The full transcript is stored by id, while the index holds a summary
+    // without lines ordered by expiration.
+    async fn write_transcript(&self, id: u64, expires: u64, value: Vec<u8>, summary: Vec<u8>) {
+        let mut batch = BatchBuilder::new();
+        batch
+            .set(ValueClass::Queue(QueueClass::Transcript(id)), value)
+            .set(
+                ValueClass::Queue(QueueClass::TranscriptIndex { id, expires }),
+                summary,
+            );
+        if let Err(err) = self.core.storage.data.write(batch.build()).await {
+            tracing::error!(
+                context = "transcript",
+                event = "error",
+                reason = %err,
+                "Failed to write session transcript."
+            );
+        }
+    }
+}
+
+impl store::Deserialize for TranscriptRecord {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        serde_json::from_slice(bytes).map_err(|err| {
+            store::Error::InternalError(format!("Failed to parse transcript: {err}"))
+        })
+    }
+}
+
+impl<T: SessionStream> Session<T> {
+    pub async fn init_transcript(&mut self) {
+        let config = &self.core.core.smtp.session.transcript;
+        if !config.enable.is_empty() {
+            self.data.transcript = SessionTranscript::new(
+                self.core.clone(),
+                config,
+                Direction::Inbound,
+                self.data.remote_ip,
+            )
+            .with_listener(self.instance.id.as_str())
+            .into();
+            self.select_transcript().await;
+        }
+    }
+
+    // Evaluates whether the session should be recorded, called each time
+    // the connection, HELO or envelope variables change.
+    pub async fn select_transcript(&mut self) {
+        if self.data.transcript.as_ref().is_some_and(|t| !t.selected)
+            && self
+                .core
+                .core
+                .eval_if(&self.core.core.smtp.session.transcript.enable, self)
+                .await
+                .unwrap_or(false)
+        {
+            tracing::debug!(
+                parent: &self.span,
+                context = "transcript",
+                event = "selected",
+                "Recording session transcript."
+            );
+            if let Some(transcript) = &mut self.data.transcript {
+                transcript.selected = true;
+            }
+        }
+
+        if let Some(transcript) = &mut self.data.transcript {
+            if !self.data.helo_domain.is_empty() {
+                transcript.set_remote_host(&self.data.helo_domain);
+            }
+        }
+    }
+
+    // Records the client input consumed by the receiver since the last call
+    #[inline(always)]
+    pub(crate) fn transcript_input(
+        &mut self,
+        input: ClientInput,
+        bytes: &[u8],
+        offset: &mut usize,
+        remaining: usize,
+    ) {
+        let end = bytes.len() - remaining;
+        if let Some(transcript) = &mut self.data.transcript {
+            if let Some(bytes) = bytes.get(*offset..end).filter(|bytes| !bytes.is_empty()) {
+                match input {
+                    ClientInput::Command => transcript.client(bytes),
+                    ClientInput::Sasl => transcript.client_sasl(bytes),
+                    ClientInput::Data => transcript.client_data(bytes.len()),
+                }
+            }
+        }
+        *offset = end;
+    }
+}
+
+fn redact_auth(line: &str) -> Cow<'_, str> {
+    let mut parts = line.splitn(3, ' ');
+    if parts
+        .next()
+        .is_some_and(|cmd| cmd.eq_ignore_ascii_case("AUTH"))
+    {
+        match (parts.next(), parts.next()) {
+            (Some(mechanism), Some(_)) => format!("AUTH {mechanism} [redacted]").into(),
+            _ => line.into(),
+        }
+    } else {
+        line.into()
+    }
+}
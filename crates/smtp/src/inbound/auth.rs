@@ -4,27 +4,45 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::{listener::SessionStream, AuthFailureReason, AuthResult};
+use common::{listener::SessionStream, scram::ScramServer, AuthFailureReason, AuthResult};
+use directory::{core::secret::ScramAlgorithm, Principal};
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
-use smtp_proto::{IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
+use smtp_proto::{
+    IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_SCRAM_SHA_1, AUTH_SCRAM_SHA_1_PLUS,
+    AUTH_SCRAM_SHA_256, AUTH_SCRAM_SHA_256_PLUS, AUTH_XOAUTH2,
+};
 
 use crate::core::Session;
 
 pub struct SaslToken {
     mechanism: u64,
     credentials: Credentials<String>,
+    scram: Option<ScramState>,
+}
+
+enum ScramState {
+    // Server-first message sent, waiting for the client proof
+    Challenge(Box<ScramServer>),
+    // Server signature sent, waiting for the client to acknowledge it
+    Verified(String, Principal<u32>),
 }
 
 impl SaslToken {
     pub fn from_mechanism(mechanism: u64) -> Option<SaslToken> {
         match mechanism {
-            AUTH_PLAIN | AUTH_LOGIN => SaslToken {
+            AUTH_PLAIN
+            | AUTH_LOGIN
+            | AUTH_SCRAM_SHA_1
+            | AUTH_SCRAM_SHA_1_PLUS
+            | AUTH_SCRAM_SHA_256
+            | AUTH_SCRAM_SHA_256_PLUS => SaslToken {
                 mechanism,
                 credentials: Credentials::Plain {
                     username: String::new(),
                     secret: String::new(),
                 },
+                scram: None,
             }
             .into(),
             AUTH_OAUTHBEARER => SaslToken {
@@ -32,6 +50,7 @@ impl SaslToken {
                 credentials: Credentials::OAuthBearer {
                     token: String::new(),
                 },
+                scram: None,
             }
             .into(),
             AUTH_XOAUTH2 => SaslToken {
@@ -40,6 +59,7 @@ impl SaslToken {
                     username: String::new(),
                     secret: String::new(),
                 },
+                scram: None,
             }
             .into(),
             _ => None,
@@ -53,6 +73,17 @@ impl<T: SessionStream> Session<T> {
         token: &mut SaslToken,
         response: &[u8],
     ) -> Result<bool, ()> {
+        if response == b"*" {
+            self.write(b"501 5.7.0 Authentication cancelled.\r\n")
+                .await?;
+            return Ok(false);
+        } else if matches!(
+            token.mechanism,
+            AUTH_SCRAM_SHA_1 | AUTH_SCRAM_SHA_1_PLUS | AUTH_SCRAM_SHA_256 | AUTH_SCRAM_SHA_256_PLUS
+        ) {
+            return self.handle_scram_response(token, response).await;
+        }
+
         if response.is_empty() {
             match (token.mechanism, &token.credentials) {
                 (AUTH_PLAIN | AUTH_XOAUTH2 | AUTH_OAUTHBEARER, _) => {
@@ -156,6 +187,98 @@ impl<T: SessionStream> Session<T> {
         self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
     }
 
+    async fn handle_scram_response(
+        &mut self,
+        token: &mut SaslToken,
+        response: &[u8],
+    ) -> Result<bool, ()> {
+        match token.scram.take() {
+            None if response.is_empty() => {
+                self.write(b"334 \r\n").await?;
+                Ok(true)
+            }
+            None => {
+                let (algorithm, is_plus) = match token.mechanism {
+                    AUTH_SCRAM_SHA_1 => (ScramAlgorithm::Sha1, false),
+                    AUTH_SCRAM_SHA_1_PLUS => (ScramAlgorithm::Sha1, true),
+                    AUTH_SCRAM_SHA_256 => (ScramAlgorithm::Sha256, false),
+                    _ => (ScramAlgorithm::Sha256, true),
+                };
+                let (Some(directory), Some(client_first)) =
+                    (&self.params.auth_directory, base64_decode(response))
+                else {
+                    return self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await;
+                };
+
+                match self
+                    .core
+                    .core
+                    .scram_begin(
+                        directory,
+                        algorithm,
+                        is_plus,
+                        &client_first,
+                        self.stream.tls_channel_binding(),
+                        false,
+                    )
+                    .await
+                {
+                    Ok(Some(server)) => {
+                        self.write(format!("334 {}\r\n", server.challenge()).as_bytes())
+                            .await?;
+                        token.scram = Some(ScramState::Challenge(Box::new(server)));
+                        Ok(true)
+                    }
+                    Ok(None) => self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await,
+                    Err(err) => self.handle_auth_result(String::new(), Err(err)).await,
+                }
+            }
+            Some(ScramState::Challenge(server)) => {
+                let Some(directory) = self.params.auth_directory.clone() else {
+                    return self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await;
+                };
+                let authenticated_as = server
+                    .authzid
+                    .clone()
+                    .unwrap_or_else(|| server.username.clone());
+                match self
+                    .core
+                    .core
+                    .scram_finish(
+                        &directory,
+                        &self.core.inner.ipc,
+                        *server,
+                        &base64_decode(response).unwrap_or_default(),
+                        self.data.remote_ip,
+                        self.instance.protocol,
+                        false,
+                    )
+                    .await
+                {
+                    Ok(AuthResult::Success((principal, server_final))) => {
+                        // Send the server signature and wait for an empty response
+                        self.write(format!("334 {server_final}\r\n").as_bytes())
+                            .await?;
+                        token.scram = Some(ScramState::Verified(authenticated_as, principal));
+                        Ok(true)
+                    }
+                    Ok(AuthResult::Failure(reason)) => {
+                        self.handle_auth_result(authenticated_as, Ok(AuthResult::Failure(reason)))
+                            .await
+                    }
+                    Err(err) => self.handle_auth_result(authenticated_as, Err(err)).await,
+                }
+            }
+            Some(ScramState::Verified(authenticated_as, principal)) if response.is_empty() => {
+                self.handle_auth_result(authenticated_as, Ok(AuthResult::Success(principal)))
+                    .await
+            }
+            Some(ScramState::Verified(..)) => {
+                self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
+            }
+        }
+    }
+
     pub async fn authenticate(&mut self, credentials: Credentials<String>) -> Result<bool, ()> {
         if let Some(directory) = &self.params.auth_directory {
             let authenticated_as = match &credentials {
@@ -163,7 +286,7 @@ impl<T: SessionStream> Session<T> {
                 | Credentials::XOauth2 { username, .. }
                 | Credentials::OAuthBearer { token: username } => username.to_string(),
             };
-            match self
+            let result = self
                 .core
                 .core
                 .authenticate(
@@ -174,65 +297,8 @@ impl<T: SessionStream> Session<T> {
                     self.instance.protocol,
                     false,
                 )
-                .await
-            {
-                Ok(AuthResult::Success(principal)) => {
-                    tracing::debug!(
-                        parent: &self.span,
-                        context = "auth",
-                        event = "authenticate",
-                        result = "success"
-                    );
-
-                    self.data.authenticated_as = authenticated_as.to_lowercase();
-                    self.data.authenticated_emails = principal
-                        .emails
-                        .into_iter()
-                        .map(|e| e.trim().to_lowercase())
-                        .collect();
-                    self.eval_post_auth_params().await;
-                    self.write(b"235 2.7.0 Authentication succeeded.\r\n")
-                        .await?;
-                    return Ok(false);
-                }
-                Ok(AuthResult::Failure(AuthFailureReason::InvalidCredentials)) => {
-                    tracing::debug!(
-                        parent: &self.span,
-                        context = "auth",
-                        event = "authenticate",
-                        result = "failed"
-                    );
-
-                    return self
-                        .auth_error(b"535 5.7.8 Authentication credentials invalid.\r\n")
-                        .await;
-                }
-                Ok(AuthResult::Failure(AuthFailureReason::Banned)) => {
-                    tracing::debug!(
-                        parent: &self.span,
-                        context = "auth",
-                        event = "authenticate",
-                        result = "banned"
-                    );
-
-                    return Err(());
-                }
-                Ok(AuthResult::Failure(AuthFailureReason::MissingTotp)) => {
-                    tracing::debug!(
-                        parent: &self.span,
-                        context = "auth",
-                        event = "authenticate",
-                        result = "missing-totp"
-                    );
-
-                    return self
-                        .auth_error(
-                            b"334 5.7.8 Missing TOTP token, try with 'secret$totp_code'.\r\n",
-                        )
-                        .await;
-                }
-                _ => (),
-            }
+                .await;
+            self.handle_auth_result(authenticated_as, result).await
         } else {
             tracing::warn!(
                 parent: &self.span,
@@ -240,6 +306,73 @@ impl<T: SessionStream> Session<T> {
                 event = "error",
                 "No lookup list configured for authentication."
             );
+            self.write(b"454 4.7.0 Temporary authentication failure\r\n")
+                .await?;
+
+            Ok(false)
+        }
+    }
+
+    async fn handle_auth_result(
+        &mut self,
+        authenticated_as: String,
+        result: directory::Result<AuthResult<Principal<u32>>>,
+    ) -> Result<bool, ()> {
+        match result {
+            Ok(AuthResult::Success(principal)) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "auth",
+                    event = "authenticate",
+                    result = "success"
+                );
+
+                self.data.authenticated_as = authenticated_as.to_lowercase();
+                self.data.authenticated_emails = principal
+                    .emails
+                    .into_iter()
+                    .map(|e| e.trim().to_lowercase())
+                    .collect();
+                self.eval_post_auth_params().await;
+                self.write(b"235 2.7.0 Authentication succeeded.\r\n")
+                    .await?;
+                return Ok(false);
+            }
+            Ok(AuthResult::Failure(AuthFailureReason::InvalidCredentials)) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "auth",
+                    event = "authenticate",
+                    result = "failed"
+                );
+
+                return self
+                    .auth_error(b"535 5.7.8 Authentication credentials invalid.\r\n")
+                    .await;
+            }
+            Ok(AuthResult::Failure(AuthFailureReason::Banned)) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "auth",
+                    event = "authenticate",
+                    result = "banned"
+                );
+
+                return Err(());
+            }
+            Ok(AuthResult::Failure(AuthFailureReason::MissingTotp)) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "auth",
+                    event = "authenticate",
+                    result = "missing-totp"
+                );
+
+                return self
+                    .auth_error(b"334 5.7.8 Missing TOTP token, try with 'secret$totp_code'.\r\n")
+                    .await;
+            }
+            _ => (),
         }
         self.write(b"454 4.7.0 Temporary authentication failure\r\n")
             .await?;
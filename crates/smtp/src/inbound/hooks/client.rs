@@ -5,13 +5,12 @@
  */
 
 use common::config::smtp::session::MTAHook;
+use serde::{de::DeserializeOwned, Serialize};
 
-use super::{Request, Response};
-
-pub(super) async fn send_mta_hook_request(
+pub(crate) async fn send_mta_hook_request<Req: Serialize, Res: DeserializeOwned>(
     mta_hook: &MTAHook,
-    request: Request,
-) -> Result<Response, String> {
+    request: Req,
+) -> Result<Res, String> {
     let response = reqwest::Client::builder()
         .timeout(mta_hook.timeout)
         .danger_accept_invalid_certs(mta_hook.tls_allow_invalid_certs)
@@ -39,13 +38,17 @@ pub(super) async fn send_mta_hook_request(
         }
 
         // TODO: Stream response body to limit response size
-        serde_json::from_slice(
-            response
-                .bytes()
-                .await
-                .map_err(|err| format!("Failed to parse Hook response: {}", err))?
-                .as_ref(),
-        )
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|err| format!("Failed to parse Hook response: {}", err))?;
+
+        // Empty replies are parsed as an empty object
+        serde_json::from_slice(if bytes.iter().all(u8::is_ascii_whitespace) {
+            b"{}"
+        } else {
+            bytes.as_ref()
+        })
         .map_err(|err| format!("Failed to parse Hook response: {}", err))
     } else {
         Err(format!(
@@ -96,6 +96,10 @@ pub enum Stage {
     Rcpt,
     #[serde(rename = "data")]
     Data,
+    #[serde(rename = "delivery")]
+    Delivery,
+    #[serde(rename = "result")]
+    Result,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -201,6 +205,8 @@ impl From<common::config::smtp::session::Stage> for Stage {
             common::config::smtp::session::Stage::Mail => Stage::Mail,
             common::config::smtp::session::Stage::Rcpt => Stage::Rcpt,
             common::config::smtp::session::Stage::Data => Stage::Data,
+            common::config::smtp::session::Stage::Delivery => Stage::Delivery,
+            common::config::smtp::session::Stage::Result => Stage::Result,
         }
     }
 }
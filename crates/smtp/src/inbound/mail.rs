@@ -388,6 +388,7 @@ impl<T: SessionStream> Session<T> {
                 address = &self.data.mail_from.as_ref().unwrap().address);
 
             self.eval_rcpt_params().await;
+            self.select_transcript().await;
             self.write(b"250 2.1.0 OK\r\n").await
         } else {
             self.data.mail_from = None;
@@ -172,32 +172,62 @@ impl<T: SessionStream> Session<T> {
         {
             if let Ok(is_local_domain) = directory.is_local_domain(&rcpt.domain).await {
                 if is_local_domain {
-                    if let Ok(is_local_address) =
-                        self.core.core.rcpt(directory, &rcpt.address_lcase).await
-                    {
-                        if !is_local_address {
+                    // Bounces sent to addresses rewritten with SRS
+                    match self.core.core.smtp.queue.srs.reverse(&rcpt.address) {
+                        Ok(Some(address)) => {
                             tracing::debug!(parent: &self.span,
-                                            context = "rcpt", 
-                                            event = "error",
-                                            address = &rcpt.address_lcase,
-                                            "Mailbox does not exist.");
+                                context = "rcpt",
+                                event = "srs-reverse",
+                                address = &rcpt.address_lcase,
+                                original = &address);
+
+                            let rcpt = self.data.rcpt_to.last_mut().unwrap();
+                            rcpt.address_lcase = address.to_lowercase();
+                            rcpt.domain = rcpt.address_lcase.domain_part().to_string();
+                            rcpt.address = address;
+                        }
+                        Ok(None) => {
+                            if let Ok(is_local_address) =
+                                self.core.core.rcpt(directory, &rcpt.address_lcase).await
+                            {
+                                if !is_local_address {
+                                    tracing::debug!(parent: &self.span,
+                                                    context = "rcpt", 
+                                                    event = "error",
+                                                    address = &rcpt.address_lcase,
+                                                    "Mailbox does not exist.");
+
+                                    self.data.rcpt_to.pop();
+                                    return self
+                                        .rcpt_error(b"550 5.1.2 Mailbox does not exist.\r\n")
+                                        .await;
+                                }
+                            } else {
+                                tracing::debug!(parent: &self.span,
+                                    context = "rcpt", 
+                                    event = "error",
+                                    address = &rcpt.address_lcase,
+                                    "Temporary address verification failure.");
+
+                                self.data.rcpt_to.pop();
+                                return self
+                                    .write(b"451 4.4.3 Unable to verify address at this time.\r\n")
+                                    .await;
+                            }
+                        }
+                        Err(err) => {
+                            tracing::debug!(parent: &self.span,
+                                context = "rcpt",
+                                event = "error",
+                                address = &rcpt.address_lcase,
+                                reason = ?err,
+                                "Invalid SRS address.");
 
                             self.data.rcpt_to.pop();
                             return self
-                                .rcpt_error(b"550 5.1.2 Mailbox does not exist.\r\n")
+                                .rcpt_error(b"550 5.1.1 Invalid or expired SRS address.\r\n")
                                 .await;
                         }
-                    } else {
-                        tracing::debug!(parent: &self.span,
-                            context = "rcpt", 
-                            event = "error",
-                            address = &rcpt.address_lcase,
-                            "Temporary address verification failure.");
-
-                        self.data.rcpt_to.pop();
-                        return self
-                            .write(b"451 4.4.3 Unable to verify address at this time.\r\n")
-                            .await;
                     }
                 } else if !self
                     .core
@@ -256,6 +286,7 @@ impl<T: SessionStream> Session<T> {
                 .await;
         }
 
+        self.select_transcript().await;
         self.write(b"250 2.1.5 OK\r\n").await
     }
 
@@ -18,19 +18,22 @@ use smtp_proto::{
 };
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::core::{Session, State};
+use crate::core::{transcript::ClientInput, Session, State};
 
 use super::auth::SaslToken;
 
 impl<T: SessionStream> Session<T> {
     pub async fn ingest(&mut self, bytes: &[u8]) -> Result<bool, ()> {
         let mut iter = bytes.iter();
+        let mut offset = 0;
         let mut state = std::mem::replace(&mut self.state, State::None);
 
         'outer: loop {
             match &mut state {
                 State::Request(receiver) => loop {
-                    match receiver.ingest(&mut iter, bytes) {
+                    let result = receiver.ingest(&mut iter, bytes);
+                    self.transcript_input(ClientInput::Command, bytes, &mut offset, iter.len());
+                    match result {
                         Ok(request) => match request {
                             Request::Rcpt { to } => {
                                 self.handle_rcpt_to(to).await?;
@@ -215,7 +218,9 @@ impl<T: SessionStream> Session<T> {
                 },
                 State::Data(receiver) => {
                     if self.data.message.len() + bytes.len() < self.params.max_message_size {
-                        if receiver.ingest(&mut iter, &mut self.data.message) {
+                        let is_done = receiver.ingest(&mut iter, &mut self.data.message);
+                        self.transcript_input(ClientInput::Data, bytes, &mut offset, iter.len());
+                        if is_done {
                             let num_rcpts = self.data.rcpt_to.len();
                             let message = self.queue_message().await;
                             if !message.is_empty() {
@@ -240,7 +245,9 @@ impl<T: SessionStream> Session<T> {
                     }
                 }
                 State::Bdat(receiver) => {
-                    if receiver.ingest(&mut iter, &mut self.data.message) {
+                    let is_done = receiver.ingest(&mut iter, &mut self.data.message);
+                    self.transcript_input(ClientInput::Data, bytes, &mut offset, iter.len());
+                    if is_done {
                         if self.can_send_data().await? {
                             if receiver.is_last {
                                 let num_rcpts = self.data.rcpt_to.len();
@@ -270,7 +277,9 @@ impl<T: SessionStream> Session<T> {
                     }
                 }
                 State::Sasl(receiver) => {
-                    if receiver.ingest(&mut iter) {
+                    let is_done = receiver.ingest(&mut iter);
+                    self.transcript_input(ClientInput::Sasl, bytes, &mut offset, iter.len());
+                    if is_done {
                         if receiver.buf.len() < MAX_LINE_LENGTH {
                             if self
                                 .handle_sasl_response(&mut receiver.state, &receiver.buf)
@@ -291,7 +300,9 @@ impl<T: SessionStream> Session<T> {
                     }
                 }
                 State::DataTooLarge(receiver) => {
-                    if receiver.ingest(&mut iter) {
+                    let is_done = receiver.ingest(&mut iter);
+                    self.transcript_input(ClientInput::Data, bytes, &mut offset, iter.len());
+                    if is_done {
                         tracing::debug!(
                             parent: &self.span,
                             context = "data",
@@ -308,7 +319,9 @@ impl<T: SessionStream> Session<T> {
                     }
                 }
                 State::RequestTooLarge(receiver) => {
-                    if receiver.ingest(&mut iter) {
+                    let is_done = receiver.ingest(&mut iter);
+                    self.transcript_input(ClientInput::Data, bytes, &mut offset, iter.len());
+                    if is_done {
                         self.write(b"554 5.3.4 Line is too long.\r\n").await?;
                         state = State::default();
                     } else {
@@ -337,6 +350,10 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
 
     #[inline(always)]
     pub async fn write(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        if let Some(transcript) = &mut self.data.transcript {
+            transcript.server(bytes);
+        }
+
         let err = match self.stream.write_all(bytes).await {
             Ok(_) => match self.stream.flush().await {
                 Ok(_) => {
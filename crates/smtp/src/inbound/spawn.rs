@@ -79,6 +79,7 @@ impl SessionManager for SmtpSessionManager {
 impl<T: SessionStream> Session<T> {
     pub async fn init_conn(&mut self) -> bool {
         self.eval_session_params().await;
+        self.init_transcript().await;
 
         let config = &self.core.core.smtp.session.connect;
 
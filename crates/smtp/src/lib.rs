@@ -55,6 +55,7 @@ impl SMTP {
             },
             ipc,
             script_cache: ScriptCache::parse(config),
+            connection_pool: Default::default(),
         };
         let inner = SmtpInstance::new(core, inner);
 
@@ -15,22 +15,32 @@ use mail_auth::{
     report::tlsrpt::{FailureDetails, ResultType},
 };
 use mail_send::SmtpClient;
+use parking_lot::Mutex;
 use smtp_proto::MAIL_REQUIRETLS;
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
     time::Duration,
 };
 use store::write::{now, BatchBuilder, QueueClass, QueueEvent, ValueClass};
+use utils::metrics::DELIVERY_TOTAL;
 
 use crate::{
-    core::SMTP,
-    queue::{ErrorDetails, Message},
+    core::{
+        transcript::{Direction, SessionTranscript},
+        SMTP,
+    },
+    queue::{
+        history::{DaneOutcome, DeliveryTracker},
+        ErrorDetails, Message,
+    },
     reporting::{tls::TlsRptOptions, PolicyType, TlsEvent},
 };
 
 use super::{
+    hooks::Route,
     lookup::ToNextHop,
     mta_sts,
+    pool::{PoolKey, PoolSlot},
     session::{read_greeting, say_helo, try_start_tls, SessionParams, StartTlsResult},
     NextHop, TlsStrategy,
 };
@@ -75,10 +85,13 @@ impl DeliveryAttempt {
             );
 
             // Check that the message still has recipients to be delivered
+            let mut history = DeliveryTracker::new(&message);
             let has_pending_delivery = message.has_pending_delivery(&span);
 
             // Send any due Delivery Status Notifications
             core.send_dsn(&mut message, &span).await;
+            core.write_delivery_history(&message, &mut history, &span)
+                .await;
 
             if has_pending_delivery {
                 // Re-queue the message if its not yet due for delivery
@@ -146,6 +159,7 @@ impl DeliveryAttempt {
             let mut on_hold = Vec::new();
             let no_ip = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
             let mut recipients = std::mem::take(&mut message.recipients);
+            let mut hooked_domains = Vec::new();
             'next_domain: for domain_idx in 0..message.domains.len() {
                 // Only process domains due for delivery
                 let domain = &message.domains[domain_idx];
@@ -165,6 +179,7 @@ impl DeliveryAttempt {
 
                 // Build envelope
                 let mut envelope = QueueEnvelope::new(&message, domain_idx);
+                history.attempt(domain_idx);
 
                 // Throttle recipient domain
                 let mut in_flight = Vec::new();
@@ -179,14 +194,69 @@ impl DeliveryAttempt {
                 }
 
                 // Obtain next hop
-                let (mut remote_hosts, is_smtp) = match core
+                let mut next_hop = core
                     .core
                     .eval_if::<String, _>(&queue_config.next_hop, &envelope)
-                    .await
-                    .and_then(|name| core.core.get_relay_host(&name))
+                    .await;
+
+                // Run outbound MTA hooks
+                let mut mx_domain = None;
+                if !queue_config.hooks.is_empty() {
+                    let directive = core
+                        .run_delivery_hooks(&envelope, &mut recipients, next_hop.as_deref(), &span)
+                        .await;
+                    hooked_domains.push((domain_idx, domain.retry.inner + 1));
+                    if let Some(reason) = directive.defer {
+                        tracing::info!(
+                            parent: &span,
+                            context = "mta_hook",
+                            event = "deferred",
+                            reason = reason,
+                        );
+                        let schedule = core
+                            .core
+                            .eval_if::<Vec<Duration>, _>(&queue_config.retry, &envelope)
+                            .await
+                            .unwrap_or_else(|| vec![Duration::from_secs(60)]);
+                        message.domains[domain_idx]
+                            .set_status(Status::TemporaryFailure(Error::Io(reason)), &schedule);
+                        continue 'next_domain;
+                    }
+                    if recipients.iter().all(|rcpt| {
+                        rcpt.domain_idx != domain_idx
+                            || matches!(
+                                &rcpt.status,
+                                Status::Completed(_) | Status::PermanentFailure(_)
+                            )
+                    }) {
+                        message.domains[domain_idx].set_status(
+                            Status::PermanentFailure(Error::Io(
+                                "All recipients were removed by an outbound MTA hook.".to_string(),
+                            )),
+                            &[],
+                        );
+                        continue 'next_domain;
+                    }
+                    match directive.route {
+                        Some(Route::Relay(relay)) => {
+                            next_hop = Some(relay);
+                        }
+                        Some(Route::Domain(domain)) => {
+                            next_hop = None;
+                            mx_domain = Some(domain);
+                        }
+                        None => (),
+                    }
+                }
+                let mx_domain = mx_domain.as_deref().unwrap_or(domain.domain.as_str());
+
+                let (mut remote_hosts, is_smtp) = match next_hop
+                    .as_deref()
+                    .and_then(|name| core.core.get_relay_host(name))
                 {
                     Some(next_hop) if next_hop.protocol == ServerProtocol::Http => {
                         // Deliver message locally
+                        history.transport(domain_idx).set_host("local", None);
                         let delivery_result = message
                             .deliver_local(
                                 recipients.iter_mut().filter(|r| r.domain_idx == domain_idx),
@@ -272,7 +342,7 @@ impl DeliveryAttempt {
                 let mta_sts_policy = if tls_strategy.try_mta_sts() && is_smtp {
                     match core
                         .lookup_mta_sts_policy(
-                            &domain.domain,
+                            mx_domain,
                             core.core
                                 .eval_if(&queue_config.timeout.mta_sts, &envelope)
                                 .await
@@ -287,6 +357,7 @@ impl DeliveryAttempt {
                                 event = "policy-fetched",
                                 policy = ?mta_sts_policy,
                             );
+                            history.transport(domain_idx).set_mta_sts(&mta_sts_policy);
 
                             mta_sts_policy.into()
                         }
@@ -360,7 +431,7 @@ impl DeliveryAttempt {
                 let mx_list;
                 if is_smtp && remote_hosts.is_empty() {
                     // Lookup MX
-                    mx_list = match core.core.smtp.resolvers.dns.mx_lookup(&domain.domain).await {
+                    mx_list = match core.core.smtp.resolvers.dns.mx_lookup(mx_domain).await {
                         Ok(mx) => mx,
                         Err(err) => {
                             tracing::info!(
@@ -380,7 +451,7 @@ impl DeliveryAttempt {
                     };
 
                     if let Some(remote_hosts_) = mx_list.to_remote_hosts(
-                        &domain.domain,
+                        mx_domain,
                         core.core
                             .eval_if(&queue_config.max_mx, &envelope)
                             .await
@@ -631,19 +702,174 @@ impl DeliveryAttempt {
                         };
                         envelope.local_ip = source_ip.unwrap_or(no_ip);
 
+                        // Obtain EHLO hostname
+                        let local_hostname = core
+                            .core
+                            .eval_if::<String, _>(&queue_config.hostname, &envelope)
+                            .await
+                            .filter(|s| !s.is_empty())
+                            .unwrap_or_else(|| {
+                                tracing::warn!(parent: &span,
+                                    context = "queue",
+                                    event = "ehlo",
+                                    "No outbound hostname configured, using 'local.host'."
+                                );
+                                "local.host".to_string()
+                            });
+
+                        // Look for an idle connection to this host
+                        let is_strict_tls = tls_strategy.is_tls_required()
+                            || (message.flags & MAIL_REQUIRETLS) != 0
+                            || mta_sts_policy.is_some()
+                            || dane_policy.is_some();
+                        envelope.remote_ip = remote_ip;
+                        let pool_slot = if core
+                            .core
+                            .eval_if(&queue_config.pool.enable, &envelope)
+                            .await
+                            .unwrap_or(false)
+                        {
+                            Some(PoolSlot {
+                                key: PoolKey {
+                                    mx: envelope.mx.to_string(),
+                                    port: remote_host.port(),
+                                    remote_ip,
+                                    source_ip,
+                                    local_hostname: local_hostname.clone(),
+                                    credentials: remote_host.credentials().cloned(),
+                                    implicit_tls: remote_host.implicit_tls(),
+                                    invalid_certs: allow_invalid_certs
+                                        || remote_host.allow_invalid_certs(),
+                                    tls_strategy,
+                                    dane: dane_policy.is_some(),
+                                },
+                                idle_timeout: core
+                                    .core
+                                    .eval_if(&queue_config.pool.idle_timeout, &envelope)
+                                    .await
+                                    .unwrap_or_else(|| Duration::from_secs(30)),
+                                max_messages: core
+                                    .core
+                                    .eval_if(&queue_config.pool.max_messages, &envelope)
+                                    .await
+                                    .unwrap_or(100),
+                            })
+                        } else {
+                            None
+                        };
+                        let mut pooled_conn = pool_slot.as_ref().and_then(|slot| {
+                            core.inner
+                                .connection_pool
+                                .checkout(&slot.key, is_strict_tls)
+                        });
+                        if let Some(conn) = &mut pooled_conn {
+                            // Release the limiters held while idle, they are acquired again below
+                            conn.in_flight.clear();
+                        }
+
                         // Throttle remote host
                         let mut in_flight_host = Vec::new();
-                        envelope.remote_ip = remote_ip;
                         for throttle in &queue_config.throttle.host {
                             if let Err(err) = core
                                 .is_allowed(throttle, &envelope, &mut in_flight_host, &span)
                                 .await
                             {
+                                if let Some(conn) = pooled_conn {
+                                    conn.quit().await;
+                                }
                                 message.domains[domain_idx].set_throttle_error(err, &mut on_hold);
                                 continue 'next_domain;
                             }
                         }
 
+                        // Record the session transcript, if enabled for this host
+                        let transcript = if core
+                            .core
+                            .eval_if(&queue_config.transcript.enable, &envelope)
+                            .await
+                            .unwrap_or(false)
+                        {
+                            let mut transcript = SessionTranscript::new(
+                                core.clone(),
+                                &queue_config.transcript,
+                                Direction::Outbound,
+                                remote_ip,
+                            )
+                            .with_remote_host(envelope.mx)
+                            .with_queue_id(message.id);
+                            transcript.selected = true;
+                            Some(Mutex::new(transcript))
+                        } else {
+                            None
+                        };
+                        let params = SessionParams {
+                            span: &span,
+                            core: &core,
+                            credentials: remote_host.credentials(),
+                            is_smtp: remote_host.is_smtp(),
+                            hostname: envelope.mx,
+                            local_hostname: &local_hostname,
+                            timeout_ehlo: core
+                                .core
+                                .eval_if(&queue_config.timeout.ehlo, &envelope)
+                                .await
+                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+                            timeout_mail: core
+                                .core
+                                .eval_if(&queue_config.timeout.mail, &envelope)
+                                .await
+                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+                            timeout_rcpt: core
+                                .core
+                                .eval_if(&queue_config.timeout.rcpt, &envelope)
+                                .await
+                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+                            timeout_data: core
+                                .core
+                                .eval_if(&queue_config.timeout.data, &envelope)
+                                .await
+                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+                            transcript: transcript.as_ref(),
+                        };
+
+                        // Deliver over the idle connection, if any
+                        if let (Some(conn), Some(slot)) = (pooled_conn, &pool_slot) {
+                            let transport = history.transport(domain_idx);
+                            transport.set_host(envelope.mx, remote_ip.into());
+                            if let Some(tls_connection) = conn.client.tls_connection() {
+                                transport.set_tls(tls_connection);
+                            }
+                            if dane_policy.is_some() {
+                                transport.dane = DaneOutcome::Verified.into();
+                            }
+
+                            match message
+                                .deliver_reused(
+                                    conn,
+                                    slot.clone(),
+                                    in_flight_host,
+                                    recipients.iter_mut().filter(|r| r.domain_idx == domain_idx),
+                                    &params,
+                                )
+                                .await
+                            {
+                                Ok(delivery_result) => {
+                                    let schedule = core
+                                        .core
+                                        .eval_if::<Vec<Duration>, _>(&queue_config.retry, &envelope)
+                                        .await
+                                        .unwrap_or_else(|| vec![Duration::from_secs(60)]);
+                                    message.domains[domain_idx]
+                                        .set_status(delivery_result, &schedule);
+                                    continue 'next_domain;
+                                }
+                                Err(in_flight) => {
+                                    // The connection was closed by the remote host
+                                    in_flight_host = in_flight;
+                                }
+                            }
+                        }
+
                         // Connect
                         let conn_timeout = core
                             .core
@@ -674,6 +900,15 @@ impl DeliveryAttempt {
                                     remote_ip = %remote_ip,
                                     remote_port = remote_host.port(),
                                 );
+                                history
+                                    .transport(domain_idx)
+                                    .set_host(envelope.mx, remote_ip.into());
+                                params.transcript(|t| {
+                                    t.info(format!(
+                                        "Connected to {remote_ip}:{}",
+                                        remote_host.port()
+                                    ))
+                                });
 
                                 smtp_client
                             }
@@ -685,59 +920,13 @@ impl DeliveryAttempt {
                                     mx = envelope.mx,
                                     reason = %err,
                                 );
+                                params.transcript(|t| t.error(&err));
                                 last_status = Status::from_smtp_error(envelope.mx, "", err);
                                 continue 'next_ip;
                             }
                         };
 
-                        // Obtain session parameters
-                        let local_hostname = core
-                            .core
-                            .eval_if::<String, _>(&queue_config.hostname, &envelope)
-                            .await
-                            .filter(|s| !s.is_empty())
-                            .unwrap_or_else(|| {
-                                tracing::warn!(parent: &span,
-                                    context = "queue",
-                                    event = "ehlo",
-                                    "No outbound hostname configured, using 'local.host'."
-                                );
-                                "local.host".to_string()
-                            });
-                        let params = SessionParams {
-                            span: &span,
-                            core: &core,
-                            credentials: remote_host.credentials(),
-                            is_smtp: remote_host.is_smtp(),
-                            hostname: envelope.mx,
-                            local_hostname: &local_hostname,
-                            timeout_ehlo: core
-                                .core
-                                .eval_if(&queue_config.timeout.ehlo, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                            timeout_mail: core
-                                .core
-                                .eval_if(&queue_config.timeout.mail, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                            timeout_rcpt: core
-                                .core
-                                .eval_if(&queue_config.timeout.rcpt, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                            timeout_data: core
-                                .core
-                                .eval_if(&queue_config.timeout.data, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                        };
-
                         // Prepare TLS connector
-                        let is_strict_tls = tls_strategy.is_tls_required()
-                            || (message.flags & MAIL_REQUIRETLS) != 0
-                            || mta_sts_policy.is_some()
-                            || dane_policy.is_some();
                         let tls_connector =
                             if allow_invalid_certs || remote_host.allow_invalid_certs() {
                                 &core.inner.connectors.dummy_verify
@@ -752,8 +941,7 @@ impl DeliveryAttempt {
                                 .eval_if(&queue_config.timeout.greeting, &envelope)
                                 .await
                                 .unwrap_or_else(|| Duration::from_secs(5 * 60));
-                            if let Err(status) = read_greeting(&mut smtp_client, envelope.mx).await
-                            {
+                            if let Err(status) = read_greeting(&mut smtp_client, &params).await {
                                 tracing::info!(
                                     parent: &span,
                                     context = "greeting",
@@ -793,7 +981,7 @@ impl DeliveryAttempt {
                                 match try_start_tls(
                                     smtp_client,
                                     tls_connector,
-                                    envelope.mx,
+                                    &params,
                                     &capabilities,
                                 )
                                 .await
@@ -807,6 +995,9 @@ impl DeliveryAttempt {
                                             protocol = ?smtp_client.tls_connection().protocol_version(),
                                             cipher = ?smtp_client.tls_connection().negotiated_cipher_suite(),
                                         );
+                                        history
+                                            .transport(domain_idx)
+                                            .set_tls(smtp_client.tls_connection());
 
                                         // Verify DANE
                                         if let Some(dane_policy) = &dane_policy {
@@ -815,6 +1006,9 @@ impl DeliveryAttempt {
                                                 envelope.mx,
                                                 smtp_client.tls_connection().peer_certificates(),
                                             ) {
+                                                history.transport(domain_idx).dane =
+                                                    DaneOutcome::Failed.into();
+
                                                 // Report DANE verification failure
                                                 if let Some(tls_report) = &tls_report {
                                                     core.schedule_report(TlsEvent {
@@ -838,6 +1032,8 @@ impl DeliveryAttempt {
                                                 last_status = status;
                                                 continue 'next_host;
                                             }
+                                            history.transport(domain_idx).dane =
+                                                DaneOutcome::Verified.into();
                                         }
 
                                         // Report TLS success
@@ -854,12 +1050,14 @@ impl DeliveryAttempt {
 
                                         // Deliver message over TLS
                                         message
-                                            .deliver(
+                                            .deliver_and_release(
                                                 smtp_client,
                                                 recipients
                                                     .iter_mut()
                                                     .filter(|r| r.domain_idx == domain_idx),
                                                 params,
+                                                pool_slot,
+                                                in_flight_host,
                                             )
                                             .await
                                     }
@@ -907,12 +1105,14 @@ impl DeliveryAttempt {
                                         } else {
                                             // TLS is not required, proceed in plain-text
                                             message
-                                                .deliver(
+                                                .deliver_and_release(
                                                     smtp_client,
                                                     recipients
                                                         .iter_mut()
                                                         .filter(|r| r.domain_idx == domain_idx),
                                                     params,
+                                                    pool_slot,
+                                                    in_flight_host,
                                                 )
                                                 .await
                                         }
@@ -966,12 +1166,14 @@ impl DeliveryAttempt {
                                 );
 
                                 message
-                                    .deliver(
+                                    .deliver_and_release(
                                         smtp_client,
                                         recipients
                                             .iter_mut()
                                             .filter(|r| r.domain_idx == domain_idx),
                                         params,
+                                        pool_slot,
+                                        in_flight_host,
                                     )
                                     .await
                             }
@@ -984,8 +1186,15 @@ impl DeliveryAttempt {
                                 .unwrap_or_else(|| Duration::from_secs(3 * 60));
                             let mut smtp_client =
                                 match smtp_client.into_tls(tls_connector, envelope.mx).await {
-                                    Ok(smtp_client) => smtp_client,
+                                    Ok(smtp_client) => {
+                                        history
+                                            .transport(domain_idx)
+                                            .set_tls(smtp_client.tls_connection());
+                                        params.transcript(|t| t.info("TLS handshake completed"));
+                                        smtp_client
+                                    }
                                     Err(error) => {
+                                        params.transcript(|t| t.error(&error));
                                         tracing::info!(
                                             parent: &span,
                                             context = "tls",
@@ -1005,8 +1214,7 @@ impl DeliveryAttempt {
                                 .eval_if(&queue_config.timeout.greeting, &envelope)
                                 .await
                                 .unwrap_or_else(|| Duration::from_secs(5 * 60));
-                            if let Err(status) = read_greeting(&mut smtp_client, envelope.mx).await
-                            {
+                            if let Err(status) = read_greeting(&mut smtp_client, &params).await {
                                 tracing::info!(
                                     parent: &span,
                                     context = "greeting",
@@ -1021,10 +1229,12 @@ impl DeliveryAttempt {
 
                             // Deliver message
                             message
-                                .deliver(
+                                .deliver_and_release(
                                     smtp_client,
                                     recipients.iter_mut().filter(|r| r.domain_idx == domain_idx),
                                     params,
+                                    pool_slot,
+                                    in_flight_host,
                                 )
                                 .await
                         };
@@ -1050,8 +1260,16 @@ impl DeliveryAttempt {
             }
             message.recipients = recipients;
 
+            // Notify outbound MTA hooks of the delivery results
+            for (domain_idx, attempt) in hooked_domains {
+                core.run_result_hooks(&message, domain_idx, attempt, &span)
+                    .await;
+            }
+
             // Send Delivery Status Notifications
             core.send_dsn(&mut message, &span).await;
+            core.write_delivery_history(&message, &mut history, &span)
+                .await;
 
             // Notify queue manager
             let span = span;
@@ -1170,6 +1388,13 @@ impl Message {
 impl Domain {
     pub fn set_status(&mut self, status: impl Into<Status<(), Error>>, schedule: &[Duration]) {
         self.status = status.into();
+        let result = match &self.status {
+            Status::Completed(_) => "completed",
+            Status::TemporaryFailure(_) => "temp_fail",
+            Status::PermanentFailure(_) => "perm_fail",
+            Status::Scheduled => "scheduled",
+        };
+        DELIVERY_TOTAL.inc(&[&self.domain, result]);
         if matches!(
             &self.status,
             Status::TemporaryFailure(_) | Status::Scheduled
@@ -0,0 +1,361 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::config::smtp::session::Stage;
+use serde::{Deserialize, Serialize};
+use smtp_proto::Response as SmtpReply;
+
+use crate::{
+    core::SMTP,
+    inbound::hooks::{self, client::send_mta_hook_request, Address, Envelope, Protocol, Queue},
+    queue::{
+        ErrorDetails, HostResponse, Message, QueueEnvelope, Recipient, Status, RCPT_STATUS_CHANGED,
+    },
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct Request {
+    pub context: Context,
+    pub envelope: Envelope,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub result: Option<DeliveryResult>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Context {
+    pub stage: hooks::Stage,
+    pub queue: Queue,
+    pub domain: String,
+    #[serde(rename = "nextHop")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_hop: Option<String>,
+    pub attempt: u32,
+    pub protocol: Protocol,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeliveryResult {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub response: Option<String>,
+    pub recipients: Vec<RecipientResult>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecipientResult {
+    pub address: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub response: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Response {
+    pub action: Action,
+    #[serde(default)]
+    pub response: Option<hooks::SmtpResponse>,
+    #[serde(default)]
+    pub modifications: Vec<Modification>,
+}
+
+// Result notifications carry no instructions, replies may be empty
+#[derive(Serialize, Deserialize, Default)]
+pub struct ResultResponse {
+    #[serde(default)]
+    pub action: Option<Action>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Action {
+    #[serde(rename = "accept")]
+    Accept,
+    #[serde(rename = "defer")]
+    Defer,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Modification {
+    #[serde(rename = "changeRelay")]
+    ChangeRelay { value: String },
+    #[serde(rename = "reroute")]
+    Reroute { value: String },
+    #[serde(rename = "deleteRecipient")]
+    DeleteRecipient {
+        value: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+// Routing decisions taken by the hooks run before a delivery attempt
+#[derive(Debug, Default)]
+pub struct DeliveryDirective {
+    pub route: Option<Route>,
+    pub defer: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Route {
+    Relay(String),
+    Domain(String),
+}
+
+impl SMTP {
+    pub async fn run_delivery_hooks(
+        &self,
+        envelope: &QueueEnvelope<'_>,
+        recipients: &mut [Recipient],
+        next_hop: Option<&str>,
+        span: &tracing::Span,
+    ) -> DeliveryDirective {
+        let mut directive = DeliveryDirective::default();
+        let domain_idx = envelope.current_domain;
+
+        for mta_hook in &self.core.smtp.queue.hooks {
+            if !mta_hook.run_on_stage.contains(&Stage::Delivery)
+                || !self
+                    .core
+                    .eval_if(&mta_hook.enable, envelope)
+                    .await
+                    .unwrap_or(false)
+            {
+                continue;
+            }
+
+            let current_hop = match &directive.route {
+                Some(Route::Relay(relay)) => Some(relay.as_str()),
+                Some(Route::Domain(_)) => None,
+                None => next_hop,
+            };
+            let request = build_request(
+                Stage::Delivery,
+                envelope.message,
+                domain_idx,
+                recipients,
+                current_hop,
+                envelope.message.domains[domain_idx].retry.inner + 1,
+                None,
+            );
+
+            match send_mta_hook_request::<_, Response>(mta_hook, request).await {
+                Ok(response) => {
+                    for modification in response.modifications {
+                        match modification {
+                            Modification::ChangeRelay { value } => {
+                                if self.core.get_relay_host(&value).is_some() {
+                                    directive.route = Some(Route::Relay(value));
+                                } else {
+                                    tracing::warn!(
+                                        parent: span,
+                                        mta_hook.url = &mta_hook.url,
+                                        context = "mta_hook",
+                                        event = "invalid-relay",
+                                        relay = value,
+                                        "MTAHook requested an unknown relay host."
+                                    );
+                                }
+                            }
+                            Modification::Reroute { value } => {
+                                directive.route = Some(Route::Domain(value.to_lowercase()));
+                            }
+                            Modification::DeleteRecipient { value, reason } => {
+                                for rcpt in recipients.iter_mut().filter(|rcpt| {
+                                    rcpt.domain_idx == domain_idx
+                                        && rcpt.address_lcase.eq_ignore_ascii_case(&value)
+                                        && !matches!(
+                                            &rcpt.status,
+                                            Status::Completed(_) | Status::PermanentFailure(_)
+                                        )
+                                }) {
+                                    tracing::info!(
+                                        parent: span,
+                                        context = "mta_hook",
+                                        event = "rcpt-removed",
+                                        rcpt = rcpt.address,
+                                    );
+
+                                    rcpt.flags |= RCPT_STATUS_CHANGED;
+                                    rcpt.status = Status::PermanentFailure(HostResponse {
+                                        hostname: ErrorDetails {
+                                            entity: "localhost".to_string(),
+                                            details: format!("RCPT TO:<{}>", rcpt.address),
+                                        },
+                                        response: SmtpReply {
+                                            code: 550,
+                                            esc: [5, 7, 1],
+                                            message: reason.clone().unwrap_or_else(|| {
+                                                "Recipient rejected by delivery policy.".to_string()
+                                            }),
+                                        },
+                                    });
+                                }
+                            }
+                        }
+                    }
+
+                    if let Action::Defer = response.action {
+                        directive.defer = Some(
+                            response
+                                .response
+                                .and_then(|response| response.message)
+                                .unwrap_or_else(|| "Delivery deferred by MTAHook.".to_string()),
+                        );
+                        break;
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        parent: span,
+                        mta_hook.url = &mta_hook.url,
+                        context = "mta_hook",
+                        event = "error",
+                        reason = ?err,
+                        "MTAHook filter failed");
+                    if mta_hook.tempfail_on_error {
+                        directive.defer = Some("Outbound MTAHook request failed.".to_string());
+                        break;
+                    }
+                }
+            }
+        }
+
+        directive
+    }
+
+    pub async fn run_result_hooks(
+        &self,
+        message: &Message,
+        domain_idx: usize,
+        attempt: u32,
+        span: &tracing::Span,
+    ) {
+        let envelope = QueueEnvelope::new(message, domain_idx);
+        let domain = &message.domains[domain_idx];
+        let (status, response) = match &domain.status {
+            Status::Scheduled => ("scheduled", None),
+            Status::Completed(_) => ("completed", None),
+            Status::TemporaryFailure(err) => ("temp_fail", Some(err.to_string())),
+            Status::PermanentFailure(err) => ("perm_fail", Some(err.to_string())),
+        };
+
+        for mta_hook in &self.core.smtp.queue.hooks {
+            if !mta_hook.run_on_stage.contains(&Stage::Result)
+                || !self
+                    .core
+                    .eval_if(&mta_hook.enable, &envelope)
+                    .await
+                    .unwrap_or(false)
+            {
+                continue;
+            }
+
+            let request = build_request(
+                Stage::Result,
+                message,
+                domain_idx,
+                &message.recipients,
+                None,
+                attempt,
+                Some(DeliveryResult {
+                    status: status.to_string(),
+                    response: response.clone(),
+                    recipients: message
+                        .recipients
+                        .iter()
+                        .filter(|rcpt| rcpt.domain_idx == domain_idx)
+                        .map(|rcpt| RecipientResult {
+                            address: rcpt.address_lcase.clone(),
+                            status: match &rcpt.status {
+                                Status::Scheduled => "scheduled",
+                                Status::Completed(_) => "completed",
+                                Status::TemporaryFailure(_) => "temp_fail",
+                                Status::PermanentFailure(_) => "perm_fail",
+                            }
+                            .to_string(),
+                            response: match &rcpt.status {
+                                Status::Scheduled => None,
+                                Status::Completed(response) => Some(response.response.to_string()),
+                                Status::TemporaryFailure(response)
+                                | Status::PermanentFailure(response) => {
+                                    Some(response.response.to_string())
+                                }
+                            },
+                        })
+                        .collect(),
+                }),
+            );
+
+            // Result notifications are sent in the background to avoid delaying the queue
+            let mta_hook = mta_hook.clone();
+            let span = span.clone();
+            tokio::spawn(async move {
+                if let Err(err) =
+                    send_mta_hook_request::<_, ResultResponse>(&mta_hook, request).await
+                {
+                    tracing::warn!(
+                        parent: &span,
+                        mta_hook.url = &mta_hook.url,
+                        context = "mta_hook",
+                        event = "error",
+                        reason = ?err,
+                        "MTAHook filter failed");
+                }
+            });
+        }
+    }
+}
+
+fn build_request(
+    stage: Stage,
+    message: &Message,
+    domain_idx: usize,
+    recipients: &[Recipient],
+    next_hop: Option<&str>,
+    attempt: u32,
+    result: Option<DeliveryResult>,
+) -> Request {
+    let domain = &message.domains[domain_idx];
+
+    Request {
+        context: Context {
+            stage: stage.into(),
+            queue: Queue {
+                id: format!("{:X}", message.id),
+            },
+            domain: domain.domain.clone(),
+            next_hop: next_hop.map(|next_hop| next_hop.to_string()),
+            attempt,
+            protocol: Protocol { version: 1 },
+        },
+        envelope: Envelope {
+            from: Address {
+                address: message.return_path_lcase.clone(),
+                parameters: None,
+            },
+            to: recipients
+                .iter()
+                .filter(|rcpt| {
+                    rcpt.domain_idx == domain_idx
+                        && (result.is_some()
+                            || !matches!(
+                                &rcpt.status,
+                                Status::Completed(_) | Status::PermanentFailure(_)
+                            ))
+                })
+                .map(|rcpt| Address {
+                    address: rcpt.address_lcase.clone(),
+                    parameters: None,
+                })
+                .collect(),
+        },
+        result,
+    }
+}
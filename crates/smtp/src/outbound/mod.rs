@@ -19,13 +19,15 @@ use crate::queue::{
 
 pub mod dane;
 pub mod delivery;
+pub mod hooks;
 
 pub mod local;
 pub mod lookup;
 pub mod mta_sts;
+pub mod pool;
 pub mod session;
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct TlsStrategy {
     pub dane: RequireOptional,
     pub mta_sts: RequireOptional,
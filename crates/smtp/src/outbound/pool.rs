@@ -0,0 +1,287 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    net::IpAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use common::listener::limiter::InFlight;
+use dashmap::DashMap;
+use mail_send::{smtp::AssertReply, Credentials, SmtpClient};
+use rustls::ClientConnection;
+use smtp_proto::EhloResponse;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::client::TlsStream;
+
+use crate::{
+    core::SMTP,
+    queue::{Error, Message, Recipient, Status},
+};
+
+use super::{
+    session::{quit, start_session, SessionParams},
+    TlsStrategy,
+};
+
+// Idle outbound connections, grouped by destination
+#[derive(Default)]
+pub struct ConnectionPool {
+    connections: DashMap<PoolKey, Vec<PooledConnection>>,
+    next_id: AtomicU64,
+}
+
+// Connections are only reused by sessions that would have been
+// established with the same parameters
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub mx: String,
+    pub port: u16,
+    pub remote_ip: IpAddr,
+    pub source_ip: Option<IpAddr>,
+    pub local_hostname: String,
+    pub credentials: Option<Credentials<String>>,
+    pub implicit_tls: bool,
+    pub invalid_certs: bool,
+    pub tls_strategy: TlsStrategy,
+    pub dane: bool,
+}
+
+#[derive(Clone)]
+pub struct PoolSlot {
+    pub key: PoolKey,
+    pub idle_timeout: Duration,
+    pub max_messages: usize,
+}
+
+pub struct PooledConnection {
+    id: u64,
+    pub client: PooledClient,
+    pub capabilities: EhloResponse<String>,
+    // Idle connections keep counting towards the queue concurrency limits
+    pub in_flight: Vec<InFlight>,
+    pub messages: usize,
+}
+
+pub enum PooledClient {
+    Plain(SmtpClient<TcpStream>),
+    Tls(SmtpClient<TlsStream<TcpStream>>),
+}
+
+impl ConnectionPool {
+    // Takes the most recently used idle connection to a destination
+    pub fn checkout(&self, key: &PoolKey, require_tls: bool) -> Option<PooledConnection> {
+        let mut connections = self.connections.get_mut(key)?;
+        let pos = connections
+            .iter()
+            .rposition(|conn| !require_tls || conn.client.tls_connection().is_some())?;
+        Some(connections.remove(pos))
+    }
+
+    pub fn idle_connections(&self) -> usize {
+        self.connections
+            .iter()
+            .map(|connections| connections.len())
+            .sum()
+    }
+
+    fn checkin(&self, key: PoolKey, mut conn: PooledConnection) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        conn.id = id;
+        self.connections.entry(key).or_default().push(conn);
+        id
+    }
+
+    fn remove(&self, key: &PoolKey, id: u64) -> Option<PooledConnection> {
+        let conn = {
+            let mut connections = self.connections.get_mut(key)?;
+            let pos = connections.iter().position(|conn| conn.id == id)?;
+            connections.remove(pos)
+        };
+        self.connections
+            .remove_if(key, |_, connections| connections.is_empty());
+        Some(conn)
+    }
+}
+
+impl PooledClient {
+    pub fn tls_connection(&self) -> Option<&ClientConnection> {
+        match self {
+            PooledClient::Plain(_) => None,
+            PooledClient::Tls(client) => Some(client.tls_connection()),
+        }
+    }
+}
+
+impl PooledConnection {
+    pub async fn quit(self) {
+        match self.client {
+            PooledClient::Plain(client) => quit(client).await,
+            PooledClient::Tls(client) => quit(client).await,
+        }
+    }
+}
+
+impl From<SmtpClient<TcpStream>> for PooledClient {
+    fn from(client: SmtpClient<TcpStream>) -> Self {
+        PooledClient::Plain(client)
+    }
+}
+
+impl From<SmtpClient<TlsStream<TcpStream>>> for PooledClient {
+    fn from(client: SmtpClient<TlsStream<TcpStream>>) -> Self {
+        PooledClient::Tls(client)
+    }
+}
+
+impl SMTP {
+    // Returns a connection to the pool, closing it once it has been idle for too long
+    pub fn release_connection(&self, slot: PoolSlot, conn: PooledConnection) {
+        if conn.messages >= slot.max_messages {
+            tokio::spawn(conn.quit());
+            return;
+        }
+
+        let id = self.inner.connection_pool.checkin(slot.key.clone(), conn);
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(slot.idle_timeout).await;
+            if let Some(conn) = inner.connection_pool.remove(&slot.key, id) {
+                conn.quit().await;
+            }
+        });
+    }
+}
+
+impl Message {
+    // Delivers the message over a new connection and, when pooling is enabled,
+    // keeps it open for subsequent messages to the same destination
+    pub async fn deliver_and_release<T: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut smtp_client: SmtpClient<T>,
+        recipients: impl Iterator<Item = &mut Recipient>,
+        params: SessionParams<'_>,
+        pool: Option<PoolSlot>,
+        in_flight: Vec<InFlight>,
+    ) -> Status<(), Error>
+    where
+        SmtpClient<T>: Into<PooledClient>,
+    {
+        let Some(slot) = pool else {
+            let status = self.deliver(smtp_client, recipients, params).await;
+            drop(in_flight);
+            return status;
+        };
+
+        let capabilities = match start_session(&mut smtp_client, &params).await {
+            Ok(capabilities) => capabilities,
+            Err(status) => {
+                quit(smtp_client).await;
+                return status;
+            }
+        };
+        match self
+            .send_transaction(&mut smtp_client, &capabilities, recipients, &params)
+            .await
+        {
+            Ok(status) => {
+                params.core.release_connection(
+                    slot,
+                    PooledConnection {
+                        id: 0,
+                        client: smtp_client.into(),
+                        capabilities,
+                        in_flight,
+                        messages: 1,
+                    },
+                );
+                status
+            }
+            Err(status) => {
+                quit(smtp_client).await;
+                status
+            }
+        }
+    }
+
+    // Delivers the message over an idle pooled connection, the connection
+    // limiters are handed back if the session could not be reset.
+    pub async fn deliver_reused(
+        &self,
+        mut conn: PooledConnection,
+        slot: PoolSlot,
+        in_flight: Vec<InFlight>,
+        recipients: impl Iterator<Item = &mut Recipient>,
+        params: &SessionParams<'_>,
+    ) -> Result<Status<(), Error>, Vec<InFlight>> {
+        let result = match &mut conn.client {
+            PooledClient::Plain(client) => {
+                self.reuse_session(client, &conn.capabilities, recipients, params)
+                    .await
+            }
+            PooledClient::Tls(client) => {
+                self.reuse_session(client, &conn.capabilities, recipients, params)
+                    .await
+            }
+        };
+
+        match result {
+            Some(Ok(status)) => {
+                conn.in_flight = in_flight;
+                conn.messages += 1;
+                params.core.release_connection(slot, conn);
+                Ok(status)
+            }
+            Some(Err(status)) => {
+                conn.quit().await;
+                Ok(status)
+            }
+            None => Err(in_flight),
+        }
+    }
+
+    async fn reuse_session<T: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        smtp_client: &mut SmtpClient<T>,
+        capabilities: &EhloResponse<String>,
+        recipients: impl Iterator<Item = &mut Recipient>,
+        params: &SessionParams<'_>,
+    ) -> Option<Result<Status<(), Error>, Status<(), Error>>> {
+        smtp_client.timeout = params.timeout_mail;
+        params.transcript(|t| {
+            t.info("Reusing pooled connection");
+            t.client(b"RSET\r\n");
+        });
+        let result = smtp_client.cmd(b"RSET\r\n").await;
+        params.transcript(|t| t.result(&result));
+        if let Err(err) = result.and_then(|r| r.assert_positive_completion()) {
+            tracing::debug!(
+                parent: params.span,
+                context = "pool",
+                event = "reset-failed",
+                mx = &params.hostname,
+                reason = %err,
+            );
+            return None;
+        }
+
+        tracing::debug!(
+            parent: params.span,
+            context = "pool",
+            event = "reuse",
+            mx = &params.hostname,
+        );
+
+        Some(
+            self.send_transaction(smtp_client, capabilities, recipients, params)
+                .await,
+        )
+    }
+}
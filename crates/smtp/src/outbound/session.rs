@@ -6,6 +6,7 @@
 
 use common::config::smtp::queue::RequireOptional;
 use mail_send::{smtp::AssertReply, Credentials, SmtpClient};
+use parking_lot::Mutex;
 use smtp_proto::{
     EhloResponse, Response, Severity, EXT_CHUNKING, EXT_DSN, EXT_REQUIRE_TLS, EXT_SIZE,
     EXT_SMTP_UTF8, EXT_START_TLS, MAIL_REQUIRETLS, MAIL_RET_FULL, MAIL_RET_HDRS, MAIL_SMTPUTF8,
@@ -20,7 +21,7 @@ use tokio::{
 use tokio_rustls::{client::TlsStream, TlsConnector};
 
 use crate::{
-    core::SMTP,
+    core::{transcript::SessionTranscript, SMTP},
     queue::{ErrorDetails, HostResponse, RCPT_STATUS_CHANGED},
 };
 
@@ -39,6 +40,16 @@ pub struct SessionParams<'x> {
     pub timeout_mail: Duration,
     pub timeout_rcpt: Duration,
     pub timeout_data: Duration,
+    pub transcript: Option<&'x Mutex<SessionTranscript>>,
+}
+
+impl SessionParams<'_> {
+    #[inline(always)]
+    pub fn transcript(&self, f: impl FnOnce(&mut SessionTranscript)) {
+        if let Some(transcript) = self.transcript {
+            f(&mut transcript.lock());
+        }
+    }
 }
 
 impl Message {
@@ -48,62 +59,39 @@ impl Message {
         recipients: impl Iterator<Item = &mut Recipient>,
         params: SessionParams<'_>,
     ) -> Status<(), Error> {
-        // Obtain capabilities
-        let capabilities = match say_helo(&mut smtp_client, &params).await {
+        let capabilities = match start_session(&mut smtp_client, &params).await {
             Ok(capabilities) => capabilities,
             Err(status) => {
-                tracing::info!(
-                    parent: params.span,
-                    context = "ehlo",
-                    event = "rejected",
-                    mx = &params.hostname,
-                    reason = %status,
-                );
                 quit(smtp_client).await;
                 return status;
             }
         };
+        let status = match self
+            .send_transaction(&mut smtp_client, &capabilities, recipients, &params)
+            .await
+        {
+            Ok(status) | Err(status) => status,
+        };
+        quit(smtp_client).await;
+        status
+    }
 
-        // Authenticate
-        if let Some(credentials) = params.credentials {
-            if let Err(err) = smtp_client.authenticate(credentials, &capabilities).await {
-                tracing::info!(
-                    parent: params.span,
-                    context = "auth",
-                    event = "failed",
-                    mx = &params.hostname,
-                    reason = %err,
-                );
-                quit(smtp_client).await;
-                return Status::from_smtp_error(params.hostname, "AUTH ...", err);
-            }
-
-            // Refresh capabilities
-            // Disabled as some SMTP servers deauthenticate after EHLO
-            /*capabilities = match say_helo(&mut smtp_client, &params).await {
-                Ok(capabilities) => capabilities,
-                Err(status) => {
-                    tracing::info!(
-                        parent: params.span,
-                        context = "ehlo",
-                        event = "rejected",
-                        mx = &params.hostname,
-                        reason = %status,
-                    );
-                    quit(smtp_client).await;
-                    return status;
-                }
-            };*/
-        }
-
+    // Sends a mail transaction, an error is returned when the connection
+    // is no longer usable for subsequent transactions.
+    pub async fn send_transaction<T: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        smtp_client: &mut SmtpClient<T>,
+        capabilities: &EhloResponse<String>,
+        recipients: impl Iterator<Item = &mut Recipient>,
+        params: &SessionParams<'_>,
+    ) -> Result<Status<(), Error>, Status<(), Error>> {
         // MAIL FROM
         smtp_client.timeout = params.timeout_mail;
-        let cmd = self.build_mail_from(&capabilities);
-        if let Err(err) = smtp_client
-            .cmd(cmd.as_bytes())
-            .await
-            .and_then(|r| r.assert_positive_completion())
-        {
+        let cmd = self.build_mail_from(capabilities);
+        params.transcript(|t| t.client(cmd.as_bytes()));
+        let result = smtp_client.cmd(cmd.as_bytes()).await;
+        params.transcript(|t| t.result(&result));
+        if let Err(err) = result.and_then(|r| r.assert_positive_completion()) {
             tracing::info!(
                 parent: params.span,
                 context = "sender",
@@ -111,8 +99,7 @@ impl Message {
                 mx = &params.hostname,
                 reason = %err,
             );
-            quit(smtp_client).await;
-            return Status::from_smtp_error(params.hostname, &cmd, err);
+            return Err(Status::from_smtp_error(params.hostname, &cmd, err));
         }
 
         // RCPT TO
@@ -130,8 +117,11 @@ impl Message {
                 continue;
             }
 
-            let cmd = self.build_rcpt_to(rcpt, &capabilities);
-            match smtp_client.cmd(cmd.as_bytes()).await {
+            let cmd = self.build_rcpt_to(rcpt, capabilities);
+            params.transcript(|t| t.client(cmd.as_bytes()));
+            let result = smtp_client.cmd(cmd.as_bytes()).await;
+            params.transcript(|t| t.result(&result));
+            match result {
                 Ok(response) => match response.severity() {
                     Severity::PositiveCompletion => {
                         accepted_rcpts.push((
@@ -179,8 +169,7 @@ impl Message {
                     );
 
                     // Something went wrong, abort.
-                    quit(smtp_client).await;
-                    return Status::from_smtp_error(params.hostname, "", err);
+                    return Err(Status::from_smtp_error(params.hostname, "", err));
                 }
             }
         }
@@ -193,7 +182,7 @@ impl Message {
                 None
             };
 
-            if let Err(status) = send_message(&mut smtp_client, self, &bdat_cmd, &params).await {
+            if let Err(status) = send_message(smtp_client, self, &bdat_cmd, params).await {
                 tracing::info!(
                     parent: params.span,
                     context = "message",
@@ -202,13 +191,17 @@ impl Message {
                     reason = %status,
                 );
 
-                quit(smtp_client).await;
-                return status;
+                return Err(status);
             }
 
             if params.is_smtp {
                 // Handle SMTP response
-                match read_smtp_data_response(&mut smtp_client, params.hostname, &bdat_cmd).await {
+                let result = read_smtp_data_response(smtp_client, params.hostname, &bdat_cmd).await;
+                params.transcript(|t| match &result {
+                    Ok(response) => t.reply(response),
+                    Err(status) => t.info(status.to_string()),
+                });
+                match result {
                     Ok(response) => {
                         // Mark recipients as delivered
                         if response.code() == 250 {
@@ -235,12 +228,12 @@ impl Message {
                                 reason = %response,
                             );
 
-                            quit(smtp_client).await;
-                            return Status::from_smtp_error(
+                            // The session is still usable after a rejected message
+                            return Ok(Status::from_smtp_error(
                                 params.hostname,
                                 bdat_cmd.as_deref().unwrap_or("DATA"),
                                 mail_send::Error::UnexpectedReply(response),
-                            );
+                            ));
                         }
                     }
                     Err(status) => {
@@ -252,19 +245,19 @@ impl Message {
                             reason = %status,
                         );
 
-                        quit(smtp_client).await;
-                        return status;
+                        return Err(status);
                     }
                 }
             } else {
                 // Handle LMTP responses
-                match read_lmtp_data_response(
-                    &mut smtp_client,
-                    params.hostname,
-                    accepted_rcpts.len(),
-                )
-                .await
-                {
+                let result =
+                    read_lmtp_data_response(smtp_client, params.hostname, accepted_rcpts.len())
+                        .await;
+                params.transcript(|t| match &result {
+                    Ok(responses) => responses.iter().for_each(|response| t.reply(response)),
+                    Err(status) => t.info(status.to_string()),
+                });
+                match result {
                     Ok(responses) => {
                         for ((rcpt, _), response) in accepted_rcpts.into_iter().zip(responses) {
                             rcpt.flags |= RCPT_STATUS_CHANGED;
@@ -324,18 +317,16 @@ impl Message {
                             reason = %status,
                         );
 
-                        quit(smtp_client).await;
-                        return status;
+                        return Err(status);
                     }
                 }
             }
         }
 
-        quit(smtp_client).await;
         if total_completed == total_rcpt {
-            Status::Completed(())
+            Ok(Status::Completed(()))
         } else {
-            Status::Scheduled
+            Ok(Status::Scheduled)
         }
     }
 
@@ -429,16 +420,25 @@ pub enum StartTlsResult {
 pub async fn try_start_tls(
     mut smtp_client: SmtpClient<TcpStream>,
     tls_connector: &TlsConnector,
-    hostname: &str,
+    params: &SessionParams<'_>,
     capabilities: &EhloResponse<String>,
 ) -> StartTlsResult {
     if capabilities.has_capability(EXT_START_TLS) {
-        match smtp_client.cmd("STARTTLS\r\n").await {
+        params.transcript(|t| t.client(b"STARTTLS\r\n"));
+        let result = smtp_client.cmd("STARTTLS\r\n").await;
+        params.transcript(|t| t.result(&result));
+        match result {
             Ok(response) => {
                 if response.code() == 220 {
-                    match smtp_client.into_tls(tls_connector, hostname).await {
-                        Ok(smtp_client) => StartTlsResult::Success { smtp_client },
-                        Err(error) => StartTlsResult::Error { error },
+                    match smtp_client.into_tls(tls_connector, params.hostname).await {
+                        Ok(smtp_client) => {
+                            params.transcript(|t| t.info("TLS handshake completed"));
+                            StartTlsResult::Success { smtp_client }
+                        }
+                        Err(error) => {
+                            params.transcript(|t| t.error(&error));
+                            StartTlsResult::Error { error }
+                        }
                     }
                 } else {
                     StartTlsResult::Unavailable {
@@ -457,15 +457,71 @@ pub async fn try_start_tls(
     }
 }
 
+pub async fn start_session<T: AsyncRead + AsyncWrite + Unpin>(
+    smtp_client: &mut SmtpClient<T>,
+    params: &SessionParams<'_>,
+) -> Result<EhloResponse<String>, Status<(), Error>> {
+    // Obtain capabilities
+    let capabilities = match say_helo(smtp_client, params).await {
+        Ok(capabilities) => capabilities,
+        Err(status) => {
+            tracing::info!(
+                parent: params.span,
+                context = "ehlo",
+                event = "rejected",
+                mx = &params.hostname,
+                reason = %status,
+            );
+            return Err(status);
+        }
+    };
+
+    // Authenticate
+    if let Some(credentials) = params.credentials {
+        params.transcript(|t| t.info("AUTH exchange redacted"));
+        if let Err(err) = smtp_client.authenticate(credentials, &capabilities).await {
+            params.transcript(|t| t.error(&err));
+            tracing::info!(
+                parent: params.span,
+                context = "auth",
+                event = "failed",
+                mx = &params.hostname,
+                reason = %err,
+            );
+            return Err(Status::from_smtp_error(params.hostname, "AUTH ...", err));
+        }
+
+        // Refresh capabilities
+        // Disabled as some SMTP servers deauthenticate after EHLO
+        /*capabilities = match say_helo(smtp_client, params).await {
+            Ok(capabilities) => capabilities,
+            Err(status) => {
+                tracing::info!(
+                    parent: params.span,
+                    context = "ehlo",
+                    event = "rejected",
+                    mx = &params.hostname,
+                    reason = %status,
+                );
+                return Err(status);
+            }
+        };*/
+    }
+
+    Ok(capabilities)
+}
+
 pub async fn read_greeting<T: AsyncRead + AsyncWrite + Unpin>(
     smtp_client: &mut SmtpClient<T>,
-    hostname: &str,
+    params: &SessionParams<'_>,
 ) -> Result<(), Status<(), Error>> {
-    tokio::time::timeout(smtp_client.timeout, smtp_client.read())
+    let result = tokio::time::timeout(smtp_client.timeout, smtp_client.read())
         .await
-        .map_err(|_| Status::timeout(hostname, "reading greeting"))?
+        .map_err(|_| Status::timeout(params.hostname, "reading greeting"))?;
+    params.transcript(|t| t.result(&result));
+    result
         .and_then(|r| r.assert_code(220))
-        .map_err(|err| Status::from_smtp_error(hostname, "", err))
+        .map_err(|err| Status::from_smtp_error(params.hostname, "", err))
 }
 
 pub async fn read_smtp_data_response<T: AsyncRead + AsyncWrite + Unpin>(
@@ -528,10 +584,18 @@ pub async fn send_message<T: AsyncRead + AsyncWrite + Unpin>(
     {
         Ok(Some(raw_message)) => tokio::time::timeout(params.timeout_data, async {
             if let Some(bdat_cmd) = bdat_cmd {
+                params.transcript(|t| {
+                    t.client(bdat_cmd.as_bytes());
+                    t.client_data(raw_message.len());
+                });
                 write_chunks(smtp_client, &[bdat_cmd.as_bytes(), &raw_message]).await
             } else {
+                params.transcript(|t| t.client(b"DATA\r\n"));
                 write_chunks(smtp_client, &[b"DATA\r\n"]).await?;
-                smtp_client.read().await?.assert_code(354)?;
+                let result = smtp_client.read().await;
+                params.transcript(|t| t.result(&result));
+                result?.assert_code(354)?;
+                params.transcript(|t| t.client_data(raw_message.len()));
                 smtp_client
                     .write_message(&raw_message)
                     .await
@@ -577,14 +641,19 @@ pub async fn say_helo<T: AsyncRead + AsyncWrite + Unpin>(
     } else {
         format!("LHLO {}\r\n", params.local_hostname)
     };
-    tokio::time::timeout(params.timeout_ehlo, async {
+    params.transcript(|t| t.client(cmd.as_bytes()));
+    let result = tokio::time::timeout(params.timeout_ehlo, async {
         smtp_client.stream.write_all(cmd.as_bytes()).await?;
         smtp_client.stream.flush().await?;
         smtp_client.read_ehlo().await
     })
     .await
-    .map_err(|_| Status::timeout(params.hostname, "reading EHLO response"))?
-    .map_err(|err| Status::from_smtp_error(params.hostname, &cmd, err))
+    .map_err(|_| Status::timeout(params.hostname, "reading EHLO response"))?;
+    params.transcript(|t| match &result {
+        Ok(capabilities) => t.ehlo(capabilities),
+        Err(err) => t.error(err),
+    });
+    result.map_err(|err| Status::from_smtp_error(params.hostname, &cmd, err))
 }
 
 pub async fn quit<T: AsyncRead + AsyncWrite + Unpin>(mut smtp_client: SmtpClient<T>) {
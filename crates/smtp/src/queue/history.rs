@@ -0,0 +1,288 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use common::config::smtp::resolver::{Mode, Policy};
+use mail_parser::MessageParser;
+use rustls::ClientConnection;
+use serde::{Deserialize, Serialize};
+use store::write::{now, BatchBuilder, QueueClass, ValueClass};
+
+use crate::core::SMTP;
+
+use super::{Message, QueueId, Status};
+
+// Maximum number of bytes read from the message to obtain its Message-ID
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    #[serde(rename = "queueId")]
+    pub queue_id: QueueId,
+    #[serde(rename = "messageId")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub sender: String,
+    pub domain: String,
+    pub recipients: Vec<RecipientRecord>,
+    #[serde(flatten)]
+    pub transport: DeliveryTransport,
+    pub status: DeliveryOutcome,
+    pub response: String,
+    pub created: u64,
+    pub completed: u64,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipientRecord {
+    pub address: String,
+    pub status: DeliveryOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub response: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeliveryTransport {
+    #[serde(rename = "remoteHost")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_host: Option<String>,
+    #[serde(rename = "remoteIp")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_ip: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dane: Option<DaneOutcome>,
+    #[serde(rename = "mtaSts")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mta_sts: Option<MtaStsOutcome>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsDetails {
+    pub protocol: String,
+    pub cipher: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryOutcome {
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DaneOutcome {
+    Verified,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MtaStsOutcome {
+    Enforce,
+    Testing,
+}
+
+// Keeps track of the transport used for each domain during a delivery attempt,
+// a history record is written once a domain reaches a final status.
+pub struct DeliveryTracker {
+    domains: Vec<TrackedDomain>,
+}
+
+#[derive(Default)]
+struct TrackedDomain {
+    is_final: bool,
+    attempted: bool,
+    transport: DeliveryTransport,
+}
+
+impl DeliveryTracker {
+    pub fn new(message: &Message) -> Self {
+        DeliveryTracker {
+            domains: message
+                .domains
+                .iter()
+                .map(|domain| TrackedDomain {
+                    is_final: matches!(
+                        &domain.status,
+                        Status::Completed(_) | Status::PermanentFailure(_)
+                    ),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    pub fn attempt(&mut self, domain_idx: usize) -> &mut DeliveryTransport {
+        let domain = &mut self.domains[domain_idx];
+        domain.attempted = true;
+        domain.transport = DeliveryTransport::default();
+        &mut domain.transport
+    }
+
+    pub fn transport(&mut self, domain_idx: usize) -> &mut DeliveryTransport {
+        &mut self.domains[domain_idx].transport
+    }
+}
+
+impl DeliveryTransport {
+    pub fn set_host(&mut self, hostname: &str, remote_ip: Option<IpAddr>) {
+        self.remote_host = Some(hostname.to_string());
+        self.remote_ip = remote_ip;
+        self.tls = None;
+        self.dane = None;
+    }
+
+    pub fn set_tls(&mut self, conn: &ClientConnection) {
+        self.tls = Some(TlsDetails {
+            protocol: conn
+                .protocol_version()
+                .map(|v| format!("{v:?}"))
+                .unwrap_or_default(),
+            cipher: conn
+                .negotiated_cipher_suite()
+                .map(|c| format!("{:?}", c.suite()))
+                .unwrap_or_default(),
+        });
+    }
+
+    pub fn set_mta_sts(&mut self, policy: &Policy) {
+        self.mta_sts = match policy.mode {
+            Mode::Enforce => Some(MtaStsOutcome::Enforce),
+            Mode::Testing => Some(MtaStsOutcome::Testing),
+            Mode::None => None,
+        };
+    }
+}
+
+impl SMTP {
+    pub async fn write_delivery_history(
+        &self,
+        message: &Message,
+        tracker: &mut DeliveryTracker,
+        span: &tracing::Span,
+    ) {
+        let Some(retention) = self.core.smtp.queue.history_retention else {
+            return;
+        };
+
+        // Obtain the domains that reached a final status since the tracker was created
+        let finalized = message
+            .domains
+            .iter()
+            .zip(tracker.domains.iter_mut())
+            .enumerate()
+            .filter_map(|(domain_idx, (domain, tracked))| {
+                if !tracked.is_final
+                    && matches!(
+                        &domain.status,
+                        Status::Completed(_) | Status::PermanentFailure(_)
+                    )
+                {
+                    tracked.is_final = true;
+                    Some((domain_idx, domain, &*tracked))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if finalized.is_empty() {
+            return;
+        }
+
+        // Obtain Message-ID
+        let message_id = match self
+            .core
+            .storage
+            .blob
+            .get_blob(message.blob_hash.as_slice(), 0..MAX_HEADER_SIZE)
+            .await
+        {
+            Ok(Some(headers)) => MessageParser::new()
+                .parse_headers(headers.as_slice())
+                .and_then(|headers| headers.message_id().map(|id| id.to_string())),
+            _ => None,
+        };
+
+        let completed = now();
+        let mut batch = BatchBuilder::new();
+        for (domain_idx, domain, tracked) in finalized {
+            let recipients = message
+                .recipients
+                .iter()
+                .filter(|rcpt| rcpt.domain_idx == domain_idx)
+                .map(|rcpt| match &rcpt.status {
+                    Status::Completed(response) => RecipientRecord {
+                        address: rcpt.address.clone(),
+                        status: DeliveryOutcome::Delivered,
+                        hostname: Some(response.hostname.clone()),
+                        response: response.response.to_string(),
+                    },
+                    Status::PermanentFailure(response) => RecipientRecord {
+                        address: rcpt.address.clone(),
+                        status: DeliveryOutcome::Failed,
+                        hostname: Some(response.hostname.entity.clone()),
+                        response: response.response.to_string(),
+                    },
+                    status => RecipientRecord {
+                        address: rcpt.address.clone(),
+                        status: DeliveryOutcome::Failed,
+                        hostname: None,
+                        response: status.to_string(),
+                    },
+                })
+                .collect::<Vec<_>>();
+            let (status, response) = match &domain.status {
+                Status::PermanentFailure(err) => (DeliveryOutcome::Failed, err.to_string()),
+                _ => (
+                    DeliveryOutcome::Delivered,
+                    recipients
+                        .iter()
+                        .find(|rcpt| rcpt.status == DeliveryOutcome::Delivered)
+                        .map(|rcpt| rcpt.response.clone())
+                        .unwrap_or_default(),
+                ),
+            };
+
+            let record = DeliveryRecord {
+                queue_id: message.id,
+                message_id: message_id.clone(),
+                sender: message.return_path.clone(),
+                domain: domain.domain.clone(),
+                recipients,
+                transport: tracked.transport.clone(),
+                status,
+                response,
+                created: message.created,
+                completed,
+                attempts: domain.retry.inner + u32::from(tracked.attempted),
+            };
+
+            batch.set(
+                ValueClass::Queue(QueueClass::DeliveryHistory {
+                    id: self.inner.snowflake_id.generate().unwrap_or(completed),
+                    expires: completed + retention.as_secs(),
+                }),
+                serde_json::to_vec(&record).unwrap_or_default(),
+            );
+        }
+
+        if let Err(err) = self.core.storage.data.write(batch.build()).await {
+            tracing::error!(
+                parent: span,
+                context = "queue",
+                event = "error",
+                reason = %err,
+                "Failed to write delivery history."
+            );
+        }
+    }
+}
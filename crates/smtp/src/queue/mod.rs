@@ -22,6 +22,7 @@ use utils::BlobHash;
 use self::spool::QueueEventLock;
 
 pub mod dsn;
+pub mod history;
 pub mod manager;
 pub mod quota;
 pub mod spool;
@@ -177,6 +177,11 @@ impl Message {
         core: &SMTP,
         span: &tracing::Span,
     ) -> bool {
+        // Rewrite the return path of relayed messages
+        if core.core.smtp.queue.srs.is_enabled() {
+            self.srs_rewrite(core, span).await;
+        }
+
         // Write blob
         let message = if let Some(raw_headers) = raw_headers {
             let mut message = Vec::with_capacity(raw_headers.len() + raw_message.len());
@@ -316,6 +321,65 @@ impl Message {
         true
     }
 
+    async fn srs_rewrite(&mut self, core: &SMTP, span: &tracing::Span) {
+        let srs = &core.core.smtp.queue.srs;
+        if self.return_path.is_empty()
+            || !core.core.eval_if(&srs.enable, self).await.unwrap_or(false)
+        {
+            return;
+        }
+
+        // Only rewrite messages from remote senders relayed to remote recipients
+        let directory = &core.core.storage.directory;
+        let mut is_relayed = false;
+        for (pos, domain) in std::iter::once(&self.return_path_domain)
+            .chain(self.domains.iter().map(|d| &d.domain))
+            .enumerate()
+        {
+            match directory.is_local_domain(domain).await {
+                Ok(true) if pos == 0 => return,
+                Ok(false) if pos > 0 => {
+                    is_relayed = true;
+                    break;
+                }
+                Ok(_) => (),
+                Err(err) => {
+                    tracing::warn!(
+                        parent: span,
+                        context = "srs",
+                        event = "error",
+                        domain = domain,
+                        "Failed to verify domain: {}",
+                        err
+                    );
+                    return;
+                }
+            }
+        }
+        if !is_relayed {
+            return;
+        }
+
+        if let Some(return_path) = core
+            .core
+            .eval_if::<String, _>(&srs.domain, self)
+            .await
+            .and_then(|domain| srs.forward(&self.return_path, &domain))
+        {
+            tracing::debug!(
+                parent: span,
+                context = "srs",
+                event = "rewrite",
+                from = self.return_path,
+                to = return_path,
+            );
+
+            self.return_path_lcase = return_path.to_lowercase();
+            self.return_path_domain = self.return_path_lcase.domain_part().to_string();
+            self.return_path = return_path;
+        }
+    }
+
     pub async fn add_recipient_parts(
         &mut self,
         rcpt: impl Into<String>,
@@ -25,6 +25,7 @@ use store::{
     write::{now, BatchBuilder, Bincode, ReportClass, ValueClass},
     Serialize,
 };
+use utils::metrics::REPORTS_TOTAL;
 
 use crate::core::SMTP;
 
@@ -231,6 +232,9 @@ impl SMTP {
                                     .await;
                             }
 
+                            // Update metrics
+                            REPORTS_TOTAL.inc(&["dmarc", "incoming"]);
+
                             // Log
                             report.log();
                             Format::Dmarc(report)
@@ -261,6 +265,9 @@ impl SMTP {
                                     .await;
                             }
 
+                            // Update metrics
+                            REPORTS_TOTAL.inc(&["tls", "incoming"]);
+
                             // Log
 
                             report.log();
@@ -292,6 +299,9 @@ impl SMTP {
                                     .await;
                             }
 
+                            // Update metrics
+                            REPORTS_TOTAL.inc(&["arf", "incoming"]);
+
                             // Log
                             report.log();
                             Format::Arf(report.into_owned())
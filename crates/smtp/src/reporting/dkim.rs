@@ -8,7 +8,7 @@ use common::listener::SessionStream;
 use mail_auth::{
     common::verify::VerifySignature, AuthenticatedMessage, AuthenticationResults, DkimOutput,
 };
-use utils::config::Rate;
+use utils::{config::Rate, metrics::REPORTS_TOTAL};
 
 use crate::core::Session;
 
@@ -89,6 +89,7 @@ impl<T: SessionStream> Session<T> {
         );
 
         // Send report
+        REPORTS_TOTAL.inc(&["arf", "outgoing"]);
         self.core
             .send_report(
                 &from_addr,
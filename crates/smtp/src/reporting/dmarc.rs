@@ -19,7 +19,7 @@ use store::{
     write::{now, BatchBuilder, Bincode, QueueClass, ReportEvent, ValueClass},
     Deserialize, IterateParams, Serialize, ValueKey,
 };
-use utils::config::Rate;
+use utils::{config::Rate, metrics::REPORTS_TOTAL};
 
 use crate::{
     core::{Session, SMTP},
@@ -219,6 +219,7 @@ impl<T: SessionStream> Session<T> {
                 );
 
                 // Send report
+                REPORTS_TOTAL.inc(&["arf", "outgoing"]);
                 self.core
                     .send_report(
                         &from_addr,
@@ -404,6 +405,7 @@ impl SMTP {
         );
 
         // Send report
+        REPORTS_TOTAL.inc(&["dmarc", "outgoing"]);
         self.send_report(&from_addr, rua.iter(), message, &config.sign, &span, false)
             .await;
 
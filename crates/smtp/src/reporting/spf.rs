@@ -6,7 +6,7 @@
 
 use common::listener::SessionStream;
 use mail_auth::{report::AuthFailureType, AuthenticationResults, SpfOutput};
-use utils::config::Rate;
+use utils::{config::Rate, metrics::REPORTS_TOTAL};
 
 use crate::core::Session;
 
@@ -89,6 +89,7 @@ impl<T: SessionStream> Session<T> {
         );
 
         // Send report
+        REPORTS_TOTAL.inc(&["arf", "outgoing"]);
         self.core
             .send_report(
                 &from_addr,
@@ -30,6 +30,8 @@ use store::{
     Deserialize, IterateParams, Serialize, ValueKey,
 };
 
+use utils::metrics::REPORTS_TOTAL;
+
 use crate::{core::SMTP, queue::RecipientDomain};
 
 use super::{scheduler::ToHash, AggregateTimestamp, ReportLock, SerializedSize, TlsEvent};
@@ -151,6 +153,7 @@ impl SMTP {
                                         event = "success",
                                         url = uri,
                                     );
+                                    REPORTS_TOTAL.inc(&["tls", "outgoing"]);
                                     self.delete_tls_report(events).await;
                                     return;
                                 } else {
@@ -214,6 +217,7 @@ impl SMTP {
             );
 
             // Send report
+            REPORTS_TOTAL.inc(&["tls", "outgoing"]);
             self.send_report(
                 &from_addr,
                 rcpts.iter(),
@@ -4,18 +4,22 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::ops::{BitAndAssign, Range};
+use std::{
+    ops::{BitAndAssign, Range},
+    time::Instant,
+};
 
 use roaring::RoaringBitmap;
+use utils::metrics::{STORE_READ_DURATION, STORE_WRITE_DURATION};
 
 use crate::{
     write::{
         key::{DeserializeBigEndian, KeySerializer},
-        now, AnyClass, AnyKey, AssignedIds, Batch, BatchBuilder, BitmapClass, BitmapHash,
-        Operation, ReportClass, ValueClass, ValueOp,
+        now, AnyClass, AnyKey, AssignedIds, AuditClass, Batch, BatchBuilder, BitmapClass,
+        BitmapHash, Operation, QueueClass, ReportClass, ValueClass, ValueOp,
     },
     BitmapKey, Deserialize, IterateParams, Key, Store, ValueKey, SUBSPACE_BITMAP_ID,
-    SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN,
+    SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN, U64_LEN,
 };
 
 use super::DocumentSet;
@@ -31,7 +35,8 @@ impl Store {
     where
         U: Deserialize + 'static,
     {
-        match self {
+        let time = Instant::now();
+        let result = match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.get_value(key).await,
             #[cfg(feature = "foundation")]
@@ -43,14 +48,17 @@ impl Store {
             #[cfg(feature = "rocks")]
             Self::RocksDb(store) => store.get_value(key).await,
             Self::None => Err(crate::Error::InternalError("No store configured".into())),
-        }
+        };
+        STORE_READ_DURATION.observe(time.elapsed());
+        result
     }
 
     pub async fn get_bitmap(
         &self,
         key: BitmapKey<BitmapClass<u32>>,
     ) -> crate::Result<Option<RoaringBitmap>> {
-        match self {
+        let time = Instant::now();
+        let result = match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.get_bitmap(key).await,
             #[cfg(feature = "foundation")]
@@ -62,7 +70,9 @@ impl Store {
             #[cfg(feature = "rocks")]
             Self::RocksDb(store) => store.get_bitmap(key).await,
             Self::None => Err(crate::Error::InternalError("No store configured".into())),
-        }
+        };
+        STORE_READ_DURATION.observe(time.elapsed());
+        result
     }
 
     pub async fn get_bitmaps_intersection(
@@ -92,7 +102,8 @@ impl Store {
         params: IterateParams<T>,
         cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> crate::Result<bool> + Sync + Send,
     ) -> crate::Result<()> {
-        match self {
+        let time = Instant::now();
+        let result = match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.iterate(params, cb).await,
             #[cfg(feature = "foundation")]
@@ -104,14 +115,17 @@ impl Store {
             #[cfg(feature = "rocks")]
             Self::RocksDb(store) => store.iterate(params, cb).await,
             Self::None => Err(crate::Error::InternalError("No store configured".into())),
-        }
+        };
+        STORE_READ_DURATION.observe(time.elapsed());
+        result
     }
 
     pub async fn get_counter(
         &self,
         key: impl Into<ValueKey<ValueClass<u32>>> + Sync + Send,
     ) -> crate::Result<i64> {
-        match self {
+        let time = Instant::now();
+        let result = match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.get_counter(key).await,
             #[cfg(feature = "foundation")]
@@ -123,7 +137,9 @@ impl Store {
             #[cfg(feature = "rocks")]
             Self::RocksDb(store) => store.get_counter(key).await,
             Self::None => Err(crate::Error::InternalError("No store configured".into())),
-        }
+        };
+        STORE_READ_DURATION.observe(time.elapsed());
+        result
     }
 
     pub async fn write(&self, batch: Batch) -> crate::Result<AssignedIds> {
@@ -214,7 +230,8 @@ impl Store {
             return Ok(AssignedIds::default());
         }
 
-        match self {
+        let time = Instant::now();
+        let result = match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.write(batch).await,
             #[cfg(feature = "foundation")]
@@ -226,7 +243,9 @@ impl Store {
             #[cfg(feature = "rocks")]
             Self::RocksDb(store) => store.write(batch).await,
             Self::None => Err(crate::Error::InternalError("No store configured".into())),
-        }
+        };
+        STORE_WRITE_DURATION.observe(time.elapsed());
+        result
     }
 
     pub async fn purge_store(&self) -> crate::Result<()> {
@@ -256,6 +275,55 @@ impl Store {
             })),
         )
         .await?;
+        self.delete_range(
+            ValueKey::from(ValueClass::Audit(AuditClass { id: 0, expires: 0 })),
+            ValueKey::from(ValueClass::Audit(AuditClass {
+                id: u64::MAX,
+                expires: now,
+            })),
+        )
+        .await?;
+        self.delete_range(
+            ValueKey::from(ValueClass::Queue(QueueClass::DeliveryHistory {
+                id: 0,
+                expires: 0,
+            })),
+            ValueKey::from(ValueClass::Queue(QueueClass::DeliveryHistory {
+                id: u64::MAX,
+                expires: now,
+            })),
+        )
+        .await?;
+
+        // Transcripts are stored by id, obtain the expired ones from the index
+        let from_key = ValueKey::from(ValueClass::Queue(QueueClass::TranscriptIndex {
+            id: 0,
+            expires: 0,
+        }));
+        let to_key = ValueKey::from(ValueClass::Queue(QueueClass::TranscriptIndex {
+            id: u64::MAX,
+            expires: now,
+        }));
+        let mut expired_ids = Vec::new();
+        self.iterate(
+            IterateParams::new(from_key.clone(), to_key.clone()).no_values(),
+            |key, _| {
+                expired_ids.push(key.deserialize_be_u64(key.len() - U64_LEN)?);
+                Ok(true)
+            },
+        )
+        .await?;
+        let mut batch = BatchBuilder::new();
+        for id in expired_ids {
+            if batch.ops.len() >= 1000 {
+                self.write(std::mem::take(&mut batch).build()).await?;
+            }
+            batch.clear(ValueClass::Queue(QueueClass::Transcript(id)));
+        }
+        if !batch.is_empty() {
+            self.write(batch.build()).await?;
+        }
+        self.delete_range(from_key, to_key).await?;
 
         match self {
             #[cfg(feature = "sqlite")]
@@ -353,6 +353,13 @@ impl<T: ResolveId> ValueClass<T> {
                     .write(event.seq_id),
                 QueueClass::QuotaCount(key) => serializer.write(0u8).write(key.as_slice()),
                 QueueClass::QuotaSize(key) => serializer.write(1u8).write(key.as_slice()),
+                QueueClass::DeliveryHistory { id, expires } => {
+                    serializer.write(3u8).write(*expires).write(*id)
+                }
+                QueueClass::Transcript(id) => serializer.write(4u8).write(*id),
+                QueueClass::TranscriptIndex { id, expires } => {
+                    serializer.write(5u8).write(*expires).write(*id)
+                }
             },
             ValueClass::Report(report) => match report {
                 ReportClass::Tls { id, expires } => {
@@ -365,6 +372,7 @@ impl<T: ResolveId> ValueClass<T> {
                     serializer.write(2u8).write(*expires).write(*id)
                 }
             },
+            ValueClass::Audit(audit) => serializer.write(3u8).write(audit.expires).write(audit.id),
             ValueClass::Any(any) => serializer.write(any.key.as_slice()),
         }
         .finalize()
@@ -541,8 +549,12 @@ impl<T> ValueClass<T> {
                     event.domain.len() + (U64_LEN * 3) + 1
                 }
                 QueueClass::QuotaCount(v) | QueueClass::QuotaSize(v) => v.len(),
+                QueueClass::DeliveryHistory { .. } | QueueClass::TranscriptIndex { .. } => {
+                    U64_LEN * 2 + 1
+                }
+                QueueClass::Transcript(_) => U64_LEN + 1,
             },
-            ValueClass::Report(_) => U64_LEN * 2 + 1,
+            ValueClass::Report(_) | ValueClass::Audit(_) => U64_LEN * 2 + 1,
             ValueClass::Any(v) => v.key.len(),
         }
     }
@@ -580,10 +592,13 @@ impl<T> ValueClass<T> {
                 QueueClass::DmarcReportHeader(_)
                 | QueueClass::TlsReportHeader(_)
                 | QueueClass::DmarcReportEvent(_)
-                | QueueClass::TlsReportEvent(_) => SUBSPACE_REPORT_OUT,
+                | QueueClass::TlsReportEvent(_)
+                | QueueClass::DeliveryHistory { .. }
+                | QueueClass::Transcript(_)
+                | QueueClass::TranscriptIndex { .. } => SUBSPACE_REPORT_OUT,
                 QueueClass::QuotaCount(_) | QueueClass::QuotaSize(_) => SUBSPACE_QUOTA,
             },
-            ValueClass::Report(_) => SUBSPACE_REPORT_IN,
+            ValueClass::Report(_) | ValueClass::Audit(_) => SUBSPACE_REPORT_IN,
             ValueClass::Any(any) => any.subspace,
         }
     }
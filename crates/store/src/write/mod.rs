@@ -153,9 +153,16 @@ pub enum ValueClass<T> {
     Config(Vec<u8>),
     Queue(QueueClass),
     Report(ReportClass),
+    Audit(AuditClass),
     Any(AnyClass),
 }
 
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct AuditClass {
+    pub id: u64,
+    pub expires: u64,
+}
+
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct FtsQueueClass {
     pub seq: u64,
@@ -195,6 +202,9 @@ pub enum QueueClass {
     TlsReportEvent(ReportEvent),
     QuotaCount(Vec<u8>),
     QuotaSize(Vec<u8>),
+    DeliveryHistory { id: u64, expires: u64 },
+    Transcript(u64),
+    TranscriptIndex { id: u64, expires: u64 },
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
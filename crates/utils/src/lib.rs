@@ -11,6 +11,7 @@ pub mod config;
 pub mod glob;
 pub mod lru_cache;
 pub mod map;
+pub mod metrics;
 pub mod snowflake;
 pub mod suffixlist;
 pub mod url_params;
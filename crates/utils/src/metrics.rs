@@ -0,0 +1,368 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use parking_lot::{const_mutex, Mutex};
+
+// Maximum number of label combinations tracked per metric, any
+// further combinations are aggregated under the "_other" label value
+const MAX_SERIES: usize = 1000;
+const OTHER_LABEL: &str = "_other";
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+pub static CONNECTIONS_TOTAL: Counter = Counter::new(
+    "stalwart_connections_total",
+    "Total number of connections accepted",
+    &["listener", "protocol"],
+);
+pub static CONNECTIONS_ACTIVE: Gauge = Gauge::new(
+    "stalwart_connections_active",
+    "Number of connections currently active",
+    &["listener", "protocol"],
+);
+pub static AUTH_TOTAL: Counter = Counter::new(
+    "stalwart_auth_total",
+    "Total number of authentication attempts",
+    &["protocol", "result"],
+);
+pub static QUEUE_MESSAGES: Gauge = Gauge::new(
+    "stalwart_queue_messages",
+    "Number of messages in the SMTP queue",
+    &[],
+);
+pub static DELIVERY_TOTAL: Counter = Counter::new(
+    "stalwart_delivery_total",
+    "Total number of delivery attempts per destination domain",
+    &["domain", "result"],
+);
+pub static REPORTS_TOTAL: Counter = Counter::new(
+    "stalwart_reports_total",
+    "Total number of reports sent and received",
+    &["type", "direction"],
+);
+pub static FTS_INDEX_BACKLOG: Gauge = Gauge::new(
+    "stalwart_fts_index_backlog",
+    "Number of documents pending full-text indexing",
+    &[],
+);
+pub static SPAM_VERDICTS_TOTAL: Counter = Counter::new(
+    "stalwart_spam_verdicts_total",
+    "Total number of spam filter verdicts",
+    &["verdict"],
+);
+pub static STORE_READ_DURATION: Histogram = Histogram::new(
+    "stalwart_store_duration_seconds",
+    "Data store operation latency",
+    "op=\"read\"",
+    LATENCY_BUCKETS,
+);
+pub static STORE_WRITE_DURATION: Histogram = Histogram::new(
+    "stalwart_store_duration_seconds",
+    "Data store operation latency",
+    "op=\"write\"",
+    LATENCY_BUCKETS,
+);
+
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    series: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    series: Mutex<BTreeMap<Vec<String>, Arc<AtomicU64>>>,
+}
+
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static str,
+    buckets: &'static [f64],
+    counts: [AtomicU64; 16],
+    sum_nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Counter {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Counter {
+            name,
+            help,
+            labels,
+            series: const_mutex(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, labels: &[&str]) {
+        self.inc_by(labels, 1);
+    }
+
+    pub fn inc_by(&self, labels: &[&str], value: u64) {
+        debug_assert_eq!(labels.len(), self.labels.len());
+        let mut series = self.series.lock();
+        let key = series_key(series.len(), labels, |key| series.contains_key(key));
+        *series.entry(key).or_default() += value;
+    }
+
+    pub fn get(&self, labels: &[&str]) -> u64 {
+        let key = labels.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        self.series.lock().get(&key).copied().unwrap_or_default()
+    }
+
+    fn export(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "counter");
+        for (key, value) in self.series.lock().iter() {
+            write_sample(out, self.name, self.labels, key, *value);
+        }
+    }
+}
+
+impl Gauge {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Gauge {
+            name,
+            help,
+            labels,
+            series: const_mutex(BTreeMap::new()),
+        }
+    }
+
+    pub fn set(&self, labels: &[&str], value: u64) {
+        self.value(labels).store(value, Ordering::Relaxed);
+    }
+
+    // Links the gauge to an existing atomic counter, such as a concurrency limiter
+    pub fn bind(&self, labels: &[&str], value: Arc<AtomicU64>) {
+        debug_assert_eq!(labels.len(), self.labels.len());
+        let mut series = self.series.lock();
+        let key = series_key(series.len(), labels, |key| series.contains_key(key));
+        series.insert(key, value);
+    }
+
+    fn value(&self, labels: &[&str]) -> Arc<AtomicU64> {
+        debug_assert_eq!(labels.len(), self.labels.len());
+        let mut series = self.series.lock();
+        let key = series_key(series.len(), labels, |key| series.contains_key(key));
+        series.entry(key).or_default().clone()
+    }
+
+    fn export(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "gauge");
+        for (key, value) in self.series.lock().iter() {
+            write_sample(
+                out,
+                self.name,
+                self.labels,
+                key,
+                value.load(Ordering::Relaxed),
+            );
+        }
+    }
+}
+
+impl Histogram {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static str,
+        buckets: &'static [f64],
+    ) -> Self {
+        assert!(buckets.len() <= 16);
+        Histogram {
+            name,
+            help,
+            labels,
+            buckets,
+            counts: [const { AtomicU64::new(0) }; 16],
+            sum_nanos: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let value = duration.as_secs_f64();
+        if let Some(pos) = self.buckets.iter().position(|bucket| value <= *bucket) {
+            self.counts[pos].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn export(&self, out: &mut String, with_header: bool) {
+        if with_header {
+            write_header(out, self.name, self.help, "histogram");
+        }
+        let sum = self.sum_nanos.load(Ordering::Relaxed) as f64 / 1_000_000_000.0;
+        let mut cumulative = 0;
+        for (bucket, bucket_count) in self.buckets.iter().zip(&self.counts) {
+            cumulative += bucket_count.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                self.name, self.labels, bucket, cumulative
+            );
+        }
+        let count = self.count().max(cumulative);
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            self.name, self.labels, count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", self.name, self.labels, sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", self.name, self.labels, count);
+    }
+}
+
+// Returns all metrics in the Prometheus text exposition format
+pub fn export_prometheus() -> String {
+    let mut out = String::with_capacity(4096);
+    CONNECTIONS_TOTAL.export(&mut out);
+    CONNECTIONS_ACTIVE.export(&mut out);
+    AUTH_TOTAL.export(&mut out);
+    QUEUE_MESSAGES.export(&mut out);
+    DELIVERY_TOTAL.export(&mut out);
+    REPORTS_TOTAL.export(&mut out);
+    FTS_INDEX_BACKLOG.export(&mut out);
+    SPAM_VERDICTS_TOTAL.export(&mut out);
+    STORE_READ_DURATION.export(&mut out, true);
+    STORE_WRITE_DURATION.export(&mut out, false);
+    out
+}
+
+fn series_key(
+    num_series: usize,
+    labels: &[&str],
+    exists: impl Fn(&Vec<String>) -> bool,
+) -> Vec<String> {
+    let key = labels.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+    if num_series < MAX_SERIES || exists(&key) {
+        key
+    } else {
+        vec![OTHER_LABEL.to_string(); labels.len()]
+    }
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, typ: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {typ}");
+}
+
+fn write_sample(out: &mut String, name: &str, names: &[&str], values: &[String], value: u64) {
+    out.push_str(name);
+    if !names.is_empty() {
+        out.push('{');
+        for (pos, (name, value)) in names.iter().zip(values).enumerate() {
+            if pos > 0 {
+                out.push(',');
+            }
+            out.push_str(name);
+            out.push_str("=\"");
+            for ch in value.chars() {
+                match ch {
+                    '\\' => out.push_str("\\\\"),
+                    '"' => out.push_str("\\\""),
+                    '\n' => out.push_str("\\n"),
+                    _ => out.push(ch),
+                }
+            }
+            out.push('"');
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Counter, Gauge, Histogram, MAX_SERIES};
+
+    #[test]
+    fn prometheus_exposition() {
+        let counter = Counter::new("test_total", "Test counter", &["protocol", "result"]);
+        counter.inc(&["imap", "success"]);
+        counter.inc(&["imap", "success"]);
+        counter.inc(&["smtp", "fail\"ure"]);
+        let mut out = String::new();
+        counter.export(&mut out);
+        assert_eq!(
+            out,
+            concat!(
+                "# HELP test_total Test counter\n",
+                "# TYPE test_total counter\n",
+                "test_total{protocol=\"imap\",result=\"success\"} 2\n",
+                "test_total{protocol=\"smtp\",result=\"fail\\\"ure\"} 1\n",
+            )
+        );
+
+        let gauge = Gauge::new("test_gauge", "Test gauge", &[]);
+        gauge.set(&[], 42);
+        let mut out = String::new();
+        gauge.export(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_gauge Test gauge\n# TYPE test_gauge gauge\ntest_gauge 42\n"
+        );
+
+        let histogram = Histogram::new("test_seconds", "Test", "op=\"read\"", &[0.1, 1.0]);
+        histogram.observe(Duration::from_millis(50));
+        histogram.observe(Duration::from_millis(500));
+        histogram.observe(Duration::from_secs(5));
+        let mut out = String::new();
+        histogram.export(&mut out, false);
+        assert_eq!(
+            out,
+            concat!(
+                "test_seconds_bucket{op=\"read\",le=\"0.1\"} 1\n",
+                "test_seconds_bucket{op=\"read\",le=\"1\"} 2\n",
+                "test_seconds_bucket{op=\"read\",le=\"+Inf\"} 3\n",
+                "test_seconds_sum{op=\"read\"} 5.55\n",
+                "test_seconds_count{op=\"read\"} 3\n",
+            )
+        );
+    }
+
+    #[test]
+    fn series_limit() {
+        let counter = Counter::new("test_total", "Test counter", &["domain"]);
+        for i in 0..MAX_SERIES + 10 {
+            counter.inc(&[&format!("domain{i}.org")]);
+        }
+        counter.inc(&["domain0.org"]);
+        assert_eq!(counter.get(&["domain0.org"]), 2);
+        assert_eq!(counter.get(&["_other"]), 10);
+    }
+}
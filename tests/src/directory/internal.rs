@@ -10,6 +10,7 @@ use directory::{
         lookup::DirectoryStore, manage::ManageDirectory, PrincipalField, PrincipalUpdate,
         PrincipalValue,
     },
+    core::secret::ScramSecret,
     DirectoryError, ManagementError, Principal, QueryBy, Type,
 };
 use jmap_proto::types::collection::Collection;
@@ -159,7 +160,8 @@ async fn internal_directory() {
                     true
                 )
                 .await
-                .unwrap(),
+                .unwrap()
+                .map(|principal| principal.with_cleartext_secrets(&["my_secret", "my_secret2"])),
             Some(Principal {
                 id: jane_id,
                 name: "jane".to_string(),
@@ -184,6 +186,87 @@ async fn internal_directory() {
             None
         );
 
+        // Invalid roles should fail
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("jane"),
+                    vec![PrincipalUpdate::add_item(
+                        PrincipalField::Roles,
+                        PrincipalValue::String("root".to_string()),
+                    )],
+                )
+                .await,
+            Err(DirectoryError::Management(ManagementError::NotFound(
+                "root".to_string()
+            )))
+        );
+
+        // Add and remove roles
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("jane"),
+                    vec![
+                        PrincipalUpdate::add_item(
+                            PrincipalField::Roles,
+                            PrincipalValue::String("Helpdesk".to_string()),
+                        ),
+                        PrincipalUpdate::add_item(
+                            PrincipalField::Roles,
+                            PrincipalValue::String("domain-admin:example.org".to_string()),
+                        )
+                    ],
+                )
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            store
+                .query(QueryBy::Id(jane_id), false)
+                .await
+                .unwrap()
+                .unwrap()
+                .roles,
+            vec![
+                "helpdesk".to_string(),
+                "domain-admin:example.org".to_string()
+            ]
+        );
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("jane"),
+                    vec![PrincipalUpdate::remove_item(
+                        PrincipalField::Roles,
+                        PrincipalValue::String("helpdesk".to_string()),
+                    )],
+                )
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            store
+                .query(QueryBy::Id(jane_id), false)
+                .await
+                .unwrap()
+                .unwrap()
+                .roles,
+            vec!["domain-admin:example.org".to_string()]
+        );
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("jane"),
+                    vec![PrincipalUpdate::set(
+                        PrincipalField::Roles,
+                        PrincipalValue::StringList(vec![]),
+                    )],
+                )
+                .await,
+            Ok(())
+        );
+
         // Duplicate email address should fail
         assert_eq!(
             store
@@ -321,7 +404,8 @@ async fn internal_directory() {
                 )
                 .await
                 .unwrap()
-                .into_sorted(),
+                .into_sorted()
+                .with_cleartext_secrets(&["secret", "secret2"]),
             Principal {
                 id: john_id,
                 name: "john".to_string(),
@@ -377,7 +461,8 @@ async fn internal_directory() {
                 )
                 .await
                 .unwrap()
-                .into_sorted(),
+                .into_sorted()
+                .with_cleartext_secrets(&["secret", "secret2"]),
             Principal {
                 id: john_id,
                 name: "john".to_string(),
@@ -436,7 +521,8 @@ async fn internal_directory() {
                 )
                 .await
                 .unwrap()
-                .into_sorted(),
+                .into_sorted()
+                .with_cleartext_secrets(&["12345"]),
             Principal {
                 id: john_id,
                 name: "john.doe".to_string(),
@@ -446,6 +532,7 @@ async fn internal_directory() {
                 quota: 1024,
                 typ: Type::Superuser,
                 member_of: vec!["list".to_string(), "sales".to_string()],
+                roles: vec![],
             }
         );
         assert_eq!(store.get_account_id("john").await.unwrap(), None);
@@ -677,3 +764,37 @@ async fn internal_directory() {
         );
     }
 }
+
+trait CleartextSecrets {
+    fn with_cleartext_secrets(self, passwords: &[&str]) -> Self;
+}
+
+impl<T> CleartextSecrets for Principal<T> {
+    // Passwords are stored as salted SCRAM keys, which are replaced by
+    // the passwords they were derived from before comparing principals.
+    fn with_cleartext_secrets(mut self, passwords: &[&str]) -> Self {
+        assert_eq!(
+            self.secrets.len(),
+            passwords.len() * 2,
+            "{:?}",
+            self.secrets
+        );
+        for password in passwords {
+            assert_eq!(
+                self.secrets
+                    .iter()
+                    .filter(|secret| ScramSecret::parse(secret)
+                        .is_some_and(|scram| scram.verify_password(password)))
+                    .count(),
+                2,
+                "{password:?} not found in {:?}",
+                self.secrets
+            );
+        }
+        self.secrets = passwords
+            .iter()
+            .map(|password| password.to_string())
+            .collect();
+        self
+    }
+}
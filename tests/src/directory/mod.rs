@@ -7,6 +7,7 @@
 pub mod imap;
 pub mod internal;
 pub mod ldap;
+pub mod oidc;
 pub mod smtp;
 pub mod sql;
 
@@ -31,6 +32,13 @@ store = "rocksdb"
 type = "internal"
 store = "foundationdb"
 
+[directory."oidc"]
+type = "oidc"
+validation.url = "http://127.0.0.1:8822/jwks.json"
+validation.issuer = "https://idp.example.org/realms/test"
+validation.audience = "stalwart"
+provision = true
+
 [directory."sqlite"]
 type = "sql"
 store = "sqlite"
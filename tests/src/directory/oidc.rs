@@ -0,0 +1,353 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use common::{
+    config::jmap::oidc::{OidcConfig, OidcKey},
+    manager::webadmin::Resource,
+};
+use directory::{
+    backend::{internal::manage::ManageDirectory, oidc::OpenIdDirectory},
+    Principal, QueryBy, Type,
+};
+use hyper::{body, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use jmap::api::http::ToHttpResponse;
+use mail_send::Credentials;
+use serde_json::json;
+use tokio::{net::TcpListener, sync::watch};
+
+use crate::{directory::DirectoryTest, AssertConfig};
+
+const ISSUER: &str = "https://idp.example.org/realms/test";
+
+#[tokio::test]
+async fn oidc_directory() {
+    // Enable logging
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::DEBUG)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Spawn mock JWKS endpoint
+    let key = test_key().await;
+    let _tx = spawn_mock_jwks_endpoint(key.clone());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Obtain directory handle
+    let mut config = DirectoryTest::new("rocksdb".into()).await;
+    let handle = config.directories.directories.remove("oidc").unwrap();
+    let store = config.stores.stores.get("rocksdb").unwrap().clone();
+    store.destroy().await;
+
+    // Create domain, group and individual
+    store.create_domain("example.org").await.unwrap();
+    let sales_id = store
+        .create_account(
+            Principal {
+                typ: Type::Group,
+                name: "sales".to_string(),
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+        .unwrap();
+    store
+        .create_account(
+            Principal {
+                typ: Type::Individual,
+                name: "bob".to_string(),
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // Tokens for unknown accounts provision the account on first login
+    let token = build_token(
+        &key,
+        json!({
+            "preferred_username": "John",
+            "name": "John Doe",
+            "email": "john@example.org",
+            "groups": ["/sales", "unknown", "bob"],
+        }),
+    );
+    let principal = handle
+        .query(
+            QueryBy::Credentials(&Credentials::OAuthBearer {
+                token: token.clone(),
+            }),
+            true,
+        )
+        .await
+        .unwrap()
+        .expect("Failed to authenticate");
+    assert_eq!(principal.name, "john");
+    assert_eq!(principal.description.as_deref(), Some("John Doe"));
+    assert_eq!(principal.emails, vec!["john@example.org".to_string()]);
+    assert_eq!(principal.member_of, vec![sales_id]);
+    assert_eq!(
+        store.get_account_id("john").await.unwrap(),
+        Some(principal.id)
+    );
+
+    // Subsequent logins use the existing account
+    assert_eq!(
+        handle
+            .query(
+                QueryBy::Credentials(&Credentials::XOauth2 {
+                    username: "john".to_string(),
+                    secret: token.clone(),
+                }),
+                false,
+            )
+            .await
+            .unwrap()
+            .map(|p| p.id),
+        Some(principal.id)
+    );
+
+    // Addresses on foreign domains are not provisioned
+    let jane = handle
+        .query(
+            QueryBy::Credentials(&Credentials::OAuthBearer {
+                token: build_token(
+                    &key,
+                    json!({
+                        "preferred_username": "jane",
+                        "email": "jane@foobar.org",
+                    }),
+                ),
+            }),
+            false,
+        )
+        .await
+        .unwrap()
+        .expect("Failed to authenticate");
+    assert_eq!(jane.name, "jane");
+    assert!(jane.emails.is_empty());
+
+    // Invalid tokens should be rejected
+    let now = now();
+    for (test_num, token) in [
+        // Issued to another account
+        None,
+        // Expired
+        Some(build_token(
+            &key,
+            json!({"preferred_username": "john", "exp": now - 60}),
+        )),
+        // Not yet valid
+        Some(build_token(
+            &key,
+            json!({"preferred_username": "john", "nbf": now + 3600}),
+        )),
+        // Wrong issuer
+        Some(build_token(
+            &key,
+            json!({"preferred_username": "john", "iss": "https://evil.example.org"}),
+        )),
+        // Wrong audience
+        Some(build_token(
+            &key,
+            json!({"preferred_username": "john", "aud": "other"}),
+        )),
+        // Missing username claim
+        Some(build_token(&key, json!({"email": "john@example.org"}))),
+        // Tampered payload
+        Some({
+            let (header, rest) = token.split_once('.').unwrap();
+            let (_, signature) = rest.split_once('.').unwrap();
+            format!(
+                "{header}.{}.{signature}",
+                URL_SAFE_NO_PAD.encode(
+                    json!({
+                        "preferred_username": "admin",
+                        "iss": ISSUER,
+                        "aud": "stalwart",
+                        "exp": now + 3600,
+                    })
+                    .to_string()
+                )
+            )
+        }),
+        // Malformed
+        Some("not-a-token".to_string()),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let credentials = match token {
+            Some(token) => Credentials::OAuthBearer { token },
+            None => Credentials::XOauth2 {
+                username: "jane".to_string(),
+                secret: token_for_john(&key),
+            },
+        };
+        assert!(
+            handle
+                .query(QueryBy::Credentials(&credentials), false)
+                .await
+                .unwrap()
+                .is_none(),
+            "test {test_num} failed"
+        );
+    }
+
+    // Lookups other than tokens are handled by the internal directory
+    assert!(handle
+        .query(QueryBy::Name("john"), false)
+        .await
+        .unwrap()
+        .is_some());
+    assert_eq!(
+        handle.email_to_ids("john@example.org").await.unwrap(),
+        vec![principal.id]
+    );
+    assert!(handle.is_local_domain("example.org").await.unwrap());
+    assert!(!handle.is_local_domain("foobar.org").await.unwrap());
+    assert!(handle
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: "john".to_string(),
+                secret: token_for_john(&key),
+            }),
+            false
+        )
+        .await
+        .unwrap()
+        .is_none());
+
+    // Signed tokens require an issuer and audience to be configured
+    for toml in [
+        concat!(
+            "[directory.oidc]\n",
+            "validation.url = \"http://127.0.0.1:8822/jwks.json\"\n",
+            "validation.audience = \"stalwart\"\n",
+        ),
+        concat!(
+            "[directory.oidc]\n",
+            "validation.url = \"http://127.0.0.1:8822/jwks.json\"\n",
+            "validation.issuer = \"https://idp.example.org/realms/test\"\n",
+        ),
+    ] {
+        let mut config = utils::config::Config::new(toml).unwrap();
+        assert!(
+            OpenIdDirectory::from_config(&mut config, ("directory", "oidc"), store.clone())
+                .is_none()
+        );
+        assert!(!config.errors.is_empty());
+    }
+}
+
+fn token_for_john(key: &OidcKey) -> String {
+    build_token(key, json!({"preferred_username": "john"}))
+}
+
+fn build_token(key: &OidcKey, claims: serde_json::Value) -> String {
+    let now = now();
+    let mut payload = json!({
+        "iss": ISSUER,
+        "aud": ["account", "stalwart"],
+        "iat": now,
+        "exp": now + 3600,
+    });
+    for (name, value) in claims.as_object().unwrap() {
+        payload[name] = value.clone();
+    }
+
+    let mut token = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(json!({"alg": "RS256", "typ": "JWT", "kid": key.id}).to_string()),
+        URL_SAFE_NO_PAD.encode(payload.to_string())
+    );
+    let signature = key.sign(token.as_bytes()).unwrap();
+    token.push('.');
+    token.push_str(&URL_SAFE_NO_PAD.encode(signature));
+    token
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+async fn test_key() -> Arc<OidcKey> {
+    let pk = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("resources")
+        .join("tls_privatekey.pem");
+    let mut config = utils::config::Config::new(format!(
+        "[oauth.oidc.key.test]\nprivate-key = \"%{{file:{}}}%\"\n",
+        pk.to_str().unwrap()
+    ))
+    .unwrap();
+    config.resolve_all_macros().await;
+    let oidc = OidcConfig::parse(&mut config);
+    config.assert_no_errors();
+    oidc.signature_key.unwrap()
+}
+
+pub fn spawn_mock_jwks_endpoint(key: Arc<OidcKey>) -> watch::Sender<bool> {
+    let (tx, mut rx) = watch::channel(true);
+
+    tokio::spawn(async move {
+        let listener = TcpListener::bind("127.0.0.1:8822")
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Failed to bind mock JWKS server to 127.0.0.1:8822: {e}");
+            });
+
+        loop {
+            tokio::select! {
+                stream = listener.accept() => {
+                    if let Ok((stream, _)) = stream {
+                        let key = key.clone();
+                        tokio::spawn(async move {
+                            let _ = http1::Builder::new()
+                                .keep_alive(false)
+                                .serve_connection(
+                                    TokioIo::new(stream),
+                                    service_fn(|_: hyper::Request<body::Incoming>| {
+                                        let key = key.clone();
+                                        async move {
+                                            Ok::<_, hyper::Error>(
+                                                Resource {
+                                                    content_type: "application/json",
+                                                    contents: json!({"keys": [key.to_jwk()]})
+                                                        .to_string()
+                                                        .into_bytes(),
+                                                }
+                                                .into_http_response(),
+                                            )
+                                        }
+                                    }),
+                                )
+                                .await;
+                        });
+                    }
+                },
+                _ = rx.changed() => {
+                    break;
+                }
+            };
+        }
+    });
+
+    tx
+}
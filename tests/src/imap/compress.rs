@@ -0,0 +1,55 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::listener::compress::DeflateStream;
+use imap_proto::ResponseType;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test() {
+    println!("Running COMPRESS tests...");
+
+    let mut imap = ImapConnection::connect(b"_z ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+
+    // COMPRESS is not available before authentication
+    imap.send("COMPRESS DEFLATE").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    imap.send("AUTHENTICATE PLAIN {32+}\r\nAGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COMPRESS=DEFLATE");
+    imap.send("COMPRESS GZIP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+    imap.send("COMPRESS DEFLATE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Continue the session over the compressed stream
+    let mut stream = BufReader::new(DeflateStream::new(
+        imap.reader.into_inner().into_inner().unsplit(imap.writer),
+    ));
+    for (command, response) in [
+        ("_z SELECT INBOX\r\n", "_z OK"),
+        ("_z COMPRESS DEFLATE\r\n", "_z NO [COMPRESSIONACTIVE]"),
+        ("_z STARTTLS\r\n", "_z NO"),
+        ("_z LOGOUT\r\n", "* BYE"),
+    ] {
+        stream.write_all(command.as_bytes()).await.unwrap();
+        stream.flush().await.unwrap();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            assert!(stream.read_line(&mut line).await.unwrap() > 0, "{command}");
+            if line.starts_with("_z ") || line.starts_with("* BYE") {
+                break;
+            }
+        }
+        assert!(line.starts_with(response), "{command} -> {line}");
+    }
+}
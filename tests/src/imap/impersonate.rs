@@ -0,0 +1,85 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test() {
+    println!("Running impersonation tests...");
+
+    // Members of the impersonation group can log in as other accounts
+    let mut imap = ImapConnection::connect(b"_i ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("LOGIN \"jdoe@example.com*jane.smith@example.com\" secret")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("CREATE \"Impersonated\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LOGOUT").await;
+    imap.assert_read(Type::Untagged, ResponseType::Bye).await;
+
+    // The mailbox was created in the target account
+    let mut imap = ImapConnection::connect(b"_i ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("LOGIN jdoe@example.com secret").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LIST \"\" \"Impersonated\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("Impersonated");
+    imap.send("LOGOUT").await;
+    imap.assert_read(Type::Untagged, ResponseType::Bye).await;
+
+    // Log in using the SASL authorization identity
+    let mut imap = ImapConnection::connect(b"_i ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send(&format!(
+        "AUTHENTICATE PLAIN {}",
+        STANDARD.encode("jdoe@example.com\0jane.smith@example.com\0secret")
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE \"Impersonated\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LOGOUT").await;
+    imap.assert_read(Type::Untagged, ResponseType::Bye).await;
+
+    // Superusers and groups cannot be impersonated
+    let mut imap = ImapConnection::connect(b"_i ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("LOGIN \"admin*jane.smith@example.com\" secret")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send("LOGIN \"support@example.com*jane.smith@example.com\" secret")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send(&format!(
+        "AUTHENTICATE PLAIN {}",
+        STANDARD.encode("admin\0jane.smith@example.com\0secret")
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Principals outside the impersonation group are rejected
+    let mut imap = ImapConnection::connect(b"_i ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("LOGIN \"jane.smith@example.com*jdoe@example.com\" secret")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send(&format!(
+        "AUTHENTICATE PLAIN {}",
+        STANDARD.encode("jane.smith@example.com\0jdoe@example.com\0secret")
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Invalid credentials
+    imap.send("LOGIN \"jdoe@example.com*jane.smith@example.com\" wrong")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+}
@@ -0,0 +1,127 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
+    println!("Running METADATA tests...");
+
+    imap.send("CREATE Provolone").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Set mailbox metadata
+    imap.send(concat!(
+        "SETMETADATA Provolone (/private/comment \"My comment\" ",
+        "/shared/comment \"Shared comment\" /shared/vendor/test/a \"Nested\")"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send("GETMETADATA Provolone (/private/comment /shared/comment)")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(concat!(
+            "* METADATA \"Provolone\" (/private/comment \"My comment\" ",
+            "/shared/comment \"Shared comment\")"
+        ));
+
+    // Depth and size options
+    imap_check
+        .send("GETMETADATA (DEPTH infinity) Provolone /shared/vendor")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("/shared/vendor/test/a \"Nested\"");
+    imap_check
+        .send("GETMETADATA (DEPTH 1) Provolone /shared/vendor")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* METADATA", 0);
+    imap_check
+        .send("GETMETADATA (MAXSIZE 10) Provolone (/private/comment /shared/comment)")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("/private/comment \"My comment\"")
+        .assert_contains("[METADATA LONGENTRIES 14]")
+        .assert_count("Shared comment", 0);
+
+    // Limits
+    imap.send(&format!(
+        "SETMETADATA Provolone (/private/large \"{}\")",
+        "a".repeat(101)
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[METADATA MAXSIZE 100]");
+    imap.send("SETMETADATA Provolone (/shared/a \"1\" /shared/b \"2\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[METADATA TOOMANY]");
+
+    // Remove entries
+    imap.send("SETMETADATA Provolone (/shared/comment NIL /shared/vendor/test/a NIL)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send("GETMETADATA (DEPTH infinity) Provolone (/shared /private)")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* METADATA \"Provolone\" (/private/comment \"My comment\")");
+
+    // Server metadata
+    imap.send("SETMETADATA \"\" (/private/vendor/test \"Server value\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send("GETMETADATA \"\" /private/vendor/test")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* METADATA \"\" (/private/vendor/test \"Server value\")");
+    imap.send("SETMETADATA \"\" (/shared/admin \"mailto:postmaster@example.com\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[NOPERM]");
+
+    // Invalid requests
+    imap.send("GETMETADATA Burrata /private/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[NONEXISTENT]");
+    imap.send("SETMETADATA Provolone (/comment \"Value\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+
+    // Metadata is removed with the mailbox
+    imap.send("SETMETADATA \"\" (/private/vendor/test NIL)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE Provolone").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("CREATE Provolone").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA Provolone /private/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* METADATA", 0);
+    imap.send("DELETE Provolone").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
@@ -8,16 +8,23 @@ pub mod acl;
 pub mod append;
 pub mod basic;
 pub mod body_structure;
+pub mod compress;
 pub mod condstore;
 pub mod copy_move;
 pub mod fetch;
 pub mod idle;
+pub mod impersonate;
 pub mod mailbox;
 pub mod managesieve;
+pub mod metadata;
+pub mod notify;
 pub mod pop;
+pub mod quota;
+pub mod scram;
 pub mod search;
 pub mod store;
 pub mod thread;
+pub mod undelete;
 
 use std::{
     path::PathBuf,
@@ -57,6 +64,7 @@ hostname = "'imap.example.org'"
 bind = ["127.0.0.1:9991"]
 protocol = "imap"
 max-connections = 81920
+compress.enable = true
 
 [server.listener.imaptls]
 bind = ["127.0.0.1:9992"]
@@ -175,6 +183,13 @@ private-key = "%{file:{PK}}%"
 [imap.protocol]
 uidplus = true
 
+[imap.metadata]
+max-size = 100
+max-entries = 3
+
+[pop3]
+download-keyword = "$POP3Downloaded"
+
 [storage]
 data = "{STORE}"
 fts = "{STORE}"
@@ -257,6 +272,9 @@ email = "address"
 quota = "quota"
 class = "type"
 
+[authentication.impersonation]
+group = "support@example.com"
+
 [oauth]
 key = "parerga_und_paralipomena"
 [oauth.auth]
@@ -313,6 +331,7 @@ async fn init_imap_tests(store_id: &str, delete_if_exists: bool) -> IMAPTest {
     let ipc = Ipc {
         delivery_tx,
         webhook_tx,
+        cluster_tx: mpsc::channel(IPC_CHANNEL_BUFFER).0,
     };
 
     // Init servers
@@ -386,6 +405,9 @@ async fn init_imap_tests(store_id: &str, delete_if_exists: bool) -> IMAPTest {
     lookup
         .create_test_user_with_email("foobar@example.com", "secret", "Bill Foobar")
         .await;
+    lookup
+        .set_test_quota("foobar@example.com", 1024 * 1024)
+        .await;
     lookup
         .create_test_user_with_email("popper@example.com", "secret", "Karl Popper")
         .await;
@@ -469,8 +491,15 @@ pub async fn imap_tests() {
     copy_move::test(&mut imap, &mut imap_check).await;
     thread::test(&mut imap, &mut imap_check).await;
     idle::test(&mut imap, &mut imap_check).await;
+    notify::test(&mut imap, &mut imap_check).await;
+    metadata::test(&mut imap, &mut imap_check).await;
+    quota::test(&mut imap, &mut imap_check).await;
+    compress::test().await;
+    scram::test().await;
+    impersonate::test().await;
     condstore::test(&mut imap, &mut imap_check).await;
     acl::test(&mut imap, &mut imap_check).await;
+    undelete::test(&handle).await;
 
     // Logout
     for imap in [&mut imap, &mut imap_check] {
@@ -0,0 +1,153 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
+    println!("Running NOTIFY tests...");
+
+    // Unsupported events should be rejected
+    imap_check
+        .send("NOTIFY SET (SELECTED (MessageNew MessageExpunge AnnotationChange))")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("BADEVENT (");
+    imap_check
+        .send("NOTIFY SET (PERSONAL (MessageNew (UID) MessageExpunge))")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Bad)
+        .await;
+
+    // Enable notifications, expect the initial status of the monitored mailboxes
+    imap.send("CREATE Gorgonzola").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("SELECT Parmeggiano").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send(concat!(
+            "NOTIFY SET STATUS (SELECTED (MessageNew (UID FLAGS) MessageExpunge FlagChange)) ",
+            "(SUBTREE Gorgonzola (MessageNew MessageExpunge MailboxName)) ",
+            "(PERSONAL (MailboxName SubscriptionChange))"
+        ))
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("STATUS \"Gorgonzola\" (MESSAGES 0")
+        .assert_count("STATUS \"Parmeggiano\"", 0)
+        .assert_count("STATUS \"INBOX\"", 0);
+
+    // Mailbox creation in a monitored subtree
+    imap.send("CREATE Gorgonzola/Dolce").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST () \"/\" \"Gorgonzola/Dolce\"");
+
+    // New message in a monitored mailbox
+    let message = "From: test@domain.com\nSubject: Test\n\nTest message\n";
+    imap.send(&format!("APPEND Gorgonzola/Dolce {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("STATUS \"Gorgonzola/Dolce\"")
+        .assert_contains("MESSAGES 1")
+        .assert_count("UNSEEN", 0);
+
+    // Subscription changes
+    imap.send("SUBSCRIBE Parmeggiano").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST (\\Subscribed) \"/\" \"Parmeggiano\"");
+
+    // New message in the selected mailbox
+    imap.send(&format!("APPEND Parmeggiano {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXISTS");
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 FETCH (")
+        .assert_contains("UID 2")
+        .assert_contains("FLAGS ()");
+
+    // Flag changes and expunges in the selected mailbox
+    imap.send("SELECT Parmeggiano").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STORE 1 +FLAGS (\\Deleted)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 FETCH (FLAGS (\\Deleted) UID 2)");
+    imap.send("UID EXPUNGE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXPUNGE");
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 0 EXISTS");
+
+    // Mailbox deletion
+    imap.send("DELETE Gorgonzola/Dolce").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST (\\NonExistent) \"/\" \"Gorgonzola/Dolce\"");
+
+    // Notifications can be combined with IDLE
+    imap_check.send("IDLE").await;
+    imap_check
+        .assert_read(Type::Continuation, ResponseType::Ok)
+        .await;
+    imap.send("CREATE Gorgonzola/Piccante").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST () \"/\" \"Gorgonzola/Piccante\"");
+    imap_check.send_raw("DONE").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Disable notifications
+    imap_check.send("NOTIFY NONE").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE Gorgonzola/Piccante").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("NOOP").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("Piccante", 0);
+
+    // Clean up
+    imap.send("UNSUBSCRIBE Parmeggiano").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE Gorgonzola").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
@@ -15,6 +15,8 @@ use tokio_rustls::client::TlsStream;
 
 use crate::{jmap::delivery::SmtpConnection, smtp::session::VerifyResponse};
 
+use super::{AssertResult, ImapConnection, Type};
+
 pub async fn test() {
     println!("Running POP3 tests...");
 
@@ -50,6 +52,7 @@ pub async fn test() {
     pop3.assert_read(ResponseType::Multiline)
         .await
         .assert_contains("SASL PLAIN")
+        .assert_contains("XOAUTH2")
         .assert_contains("IMPLEMENTATION");
 
     // Noop
@@ -150,6 +153,25 @@ pub async fn test() {
     pop3.send("RSET").await;
     pop3.assert_read(ResponseType::Ok).await;
     pop3.send("QUIT").await;
+    pop3.assert_read(ResponseType::Ok).await;
+
+    // Retrieved messages should be flagged as downloaded
+    let mut imap = ImapConnection::connect(b"_p ").await;
+    imap.assert_read(Type::Untagged, imap_proto::ResponseType::Ok)
+        .await;
+    imap.send("AUTHENTICATE PLAIN AHBvcHBlckBleGFtcGxlLmNvbQBzZWNyZXQ=")
+        .await;
+    imap.assert_read(Type::Tagged, imap_proto::ResponseType::Ok)
+        .await;
+    imap.send("EXAMINE INBOX").await;
+    imap.assert_read(Type::Tagged, imap_proto::ResponseType::Ok)
+        .await;
+    imap.send("FETCH 1:* (FLAGS)").await;
+    imap.assert_read(Type::Tagged, imap_proto::ResponseType::Ok)
+        .await
+        .assert_count("$POP3Downloaded", 2);
+    imap.send("LOGOUT").await;
+
     let mut pop3 = Pop3Connection::connect_and_login().await;
     pop3.send("STAT").await;
     pop3.assert_read(ResponseType::Ok)
@@ -0,0 +1,112 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap_john: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running QUOTA tests...");
+
+    // Bill has a 1MB storage quota
+    let mut imap = ImapConnection::connect(b"_q ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    let secret = "AGZvb2JhckBleGFtcGxlLmNvbQBzZWNyZXQ=";
+    imap.send(&format!(
+        "AUTHENTICATE PLAIN {{{}+}}\r\n{}",
+        secret.len(),
+        secret
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("QUOTA=RES-STORAGE")
+        .assert_contains("QUOTA=RES-MESSAGE");
+
+    imap.send("GETQUOTAROOT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTAROOT \"INBOX\" \"\"")
+        .assert_contains("* QUOTA \"\" (STORAGE 0 1024)");
+
+    // Usage is updated after appending a message
+    let message = "From: test@domain.com\nSubject: Quota test\n\nTest message\n";
+    imap.send(&format!("APPEND INBOX {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETQUOTA \"\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTA \"\" (STORAGE 1 1024)");
+
+    // Messages that would exceed the storage quota are rejected
+    let message = format!(
+        "From: test@domain.com\nSubject: Large message\n\n{}\n",
+        "a".repeat(600 * 1024)
+    );
+    imap.send(&format!("APPEND INBOX {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(&message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send(&format!("APPEND INBOX {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(&message).await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[OVERQUOTA]");
+    imap.send("SELECT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 2 EXISTS");
+    imap.send("COPY 2 INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[OVERQUOTA]");
+    imap.send("STATUS INBOX (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 2");
+
+    // Unknown quota roots and insufficient permissions
+    imap.send("GETQUOTA \"Shared Folders/nobody@example.com\"")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[NONEXISTENT]");
+    imap.send("SETQUOTA \"\" (STORAGE 2048)").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[NOPERM]");
+    imap.send("SETQUOTA \"\" (MAILBOX 2048)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+
+    // Remove test messages
+    imap.send("STORE 1:* +FLAGS (\\Deleted)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("EXPUNGE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LOGOUT").await;
+    imap.assert_read(Type::Untagged, ResponseType::Bye).await;
+
+    // John has no quota limits
+    imap_john.send("GETQUOTAROOT INBOX").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTAROOT \"INBOX\"")
+        .assert_count("* QUOTA ", 0);
+    imap_john.send("GETQUOTA \"\"").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[NONEXISTENT]");
+}
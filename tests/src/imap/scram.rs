@@ -0,0 +1,143 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use directory::core::secret::ScramAlgorithm;
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test() {
+    println!("Running SCRAM tests...");
+
+    let mut imap = ImapConnection::connect(b"_s ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("AUTH=SCRAM-SHA-256")
+        .assert_contains("AUTH=SCRAM-SHA-1");
+
+    // Cancelled exchange
+    imap.send("AUTHENTICATE SCRAM-SHA-256").await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged("*").await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+
+    // Invalid password
+    for (algorithm, mechanism) in [
+        (ScramAlgorithm::Sha256, "SCRAM-SHA-256"),
+        (ScramAlgorithm::Sha1, "SCRAM-SHA-1"),
+    ] {
+        let server_final =
+            scram_exchange(&mut imap, algorithm, mechanism, "jdoe@example.com", "wrong").await;
+        assert_eq!(server_final, None);
+    }
+
+    // Unknown accounts receive a challenge as well
+    let server_final = scram_exchange(
+        &mut imap,
+        ScramAlgorithm::Sha256,
+        "SCRAM-SHA-256",
+        "nobody@example.com",
+        "secret",
+    )
+    .await;
+    assert_eq!(server_final, None);
+
+    // Successful exchange
+    let server_final = scram_exchange(
+        &mut imap,
+        ScramAlgorithm::Sha256,
+        "SCRAM-SHA-256",
+        "jdoe@example.com",
+        "secret",
+    )
+    .await;
+    assert!(server_final.is_some());
+    imap.send_untagged("").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("SELECT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LOGOUT").await;
+    imap.assert_read(Type::Untagged, ResponseType::Bye).await;
+}
+
+// Performs a SCRAM exchange and returns the verified server-final message,
+// or None if the server rejected the client proof
+async fn scram_exchange(
+    imap: &mut ImapConnection,
+    algorithm: ScramAlgorithm,
+    mechanism: &str,
+    username: &str,
+    password: &str,
+) -> Option<String> {
+    let client_first_bare = format!("n={username},r=rOprNGfwEbeRWgbNEkqO");
+    imap.send(&format!(
+        "AUTHENTICATE {mechanism} {}",
+        STANDARD.encode(format!("n,,{client_first_bare}"))
+    ))
+    .await;
+    let server_first = decode_challenge(
+        imap.assert_read(Type::Continuation, ResponseType::Ok)
+            .await
+            .last()
+            .unwrap(),
+    );
+
+    // Parse the server-first message
+    let mut nonce = "";
+    let mut salt = Vec::new();
+    let mut iterations = 0;
+    for attribute in server_first.split(',') {
+        match attribute.split_once('=').unwrap() {
+            ("r", value) => nonce = value,
+            ("s", value) => salt = STANDARD.decode(value).unwrap(),
+            ("i", value) => iterations = value.parse().unwrap(),
+            _ => {}
+        }
+    }
+    assert!(nonce.starts_with("rOprNGfwEbeRWgbNEkqO"), "{server_first}");
+    assert!(!salt.is_empty() && iterations > 0, "{server_first}");
+
+    // Calculate the client proof
+    let client_final_without_proof = format!("c=biws,r={nonce}");
+    let auth_message = format!("{client_first_bare},{server_first},{client_final_without_proof}");
+    let salted_password = algorithm.salted_password(password, &salt, iterations);
+    let client_key = algorithm.hmac(&salted_password, b"Client Key");
+    let client_signature = algorithm.hmac(&algorithm.hash(&client_key), auth_message.as_bytes());
+    let proof = client_key
+        .iter()
+        .zip(client_signature)
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<_>>();
+    imap.send_untagged(&STANDARD.encode(format!(
+        "{client_final_without_proof},p={}",
+        STANDARD.encode(proof)
+    )))
+    .await;
+
+    // Verify the server signature
+    let line = imap.reader.next_line().await.unwrap().unwrap();
+    if !line.starts_with("+ ") {
+        assert!(line.starts_with("_s NO"), "{line}");
+        return None;
+    }
+    let server_final = decode_challenge(&line);
+    let server_key = algorithm.hmac(&salted_password, b"Server Key");
+    assert_eq!(
+        server_final,
+        format!(
+            "v={}",
+            STANDARD.encode(algorithm.hmac(&server_key, auth_message.as_bytes()))
+        )
+    );
+    Some(server_final)
+}
+
+fn decode_challenge(line: &str) -> String {
+    String::from_utf8(STANDARD.decode(line.strip_prefix("+ ").unwrap()).unwrap()).unwrap()
+}
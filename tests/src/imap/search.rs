@@ -120,4 +120,86 @@ pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
         .assert_contains("COUNT 10 ALL 6,4:5,1,10,9,3,7:8,2");
+
+    // Partial results
+    imap_check.send("UID SEARCH RETURN (PARTIAL 1:3) ALL").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("PARTIAL (1:3 1:3)");
+    imap_check
+        .send("UID SEARCH RETURN (COUNT PARTIAL -1:-2) ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COUNT 10 PARTIAL (-1:-2 9:10)");
+    imap_check
+        .send("UID SEARCH RETURN (PARTIAL 20:30) ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("PARTIAL (20:30 NIL)");
+    imap_check
+        .send("UID SEARCH RETURN (ALL PARTIAL 1:3) ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Bad)
+        .await;
+    imap_check.send("UID FETCH 1:* (UID) (PARTIAL -1:-2)").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("FETCH (", 2)
+        .assert_contains("UID 9")
+        .assert_contains("UID 10");
+
+    // Save date
+    imap_check.send("UID FETCH 1 (SAVEDATE)").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("SAVEDATE \"");
+    imap_check.send("UID SEARCH SAVEDATESUPPORTED").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 1 2 3 4 5 6 7 8 9 10");
+    imap_check.send("UID SEARCH SAVEDBEFORE 1-Jan-2000").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+
+    // Multi-mailbox search
+    imap_check
+        .send("ESEARCH IN (selected) RETURN (COUNT) ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MAILBOX \"INBOX\" UIDVALIDITY ")
+        .assert_contains("UID COUNT 10");
+    imap_check
+        .send("ESEARCH IN (inboxes) RETURN (MIN) FROM nathaniel")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* ESEARCH ", 1)
+        .assert_contains("MIN 1");
+    imap_check
+        .send("ESEARCH IN (mailboxes \"Does not exist\") ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* ESEARCH ", 0);
+    imap_check
+        .send("ESEARCH IN (personal) RETURN (SAVE) ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Bad)
+        .await;
 }
@@ -72,20 +72,32 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     let email_id = email_id.expect("Missing EMAILID");
     let thread_id = thread_id.expect("Missing THREADID");
 
-    // 4 different threads are expected
-    imap.send("THREAD REFERENCES UTF-8 1:*").await;
+    // 3 different threads are expected, replies are nested under their parent
+    for algorithm in ["REFERENCES", "REFS", "ORDEREDSUBJECT"] {
+        imap.send(&format!("THREAD {algorithm} UTF-8 1:*")).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok)
+            .await
+            .assert_contains("(1 (2)(3)(4))")
+            .assert_contains("(5 (6)(7)(8))")
+            .assert_contains("(9 (10)(11)(12))");
+    }
+
+    imap.send("THREAD REFERENCES UTF-8 SUBJECT T1").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_contains("(1 2 3 4)")
-        .assert_contains("(5 6 7 8)")
-        .assert_contains("(9 10 11 12)");
+        .assert_contains("(5 (6)(7)(8))")
+        .assert_count("(1 (2)(3)(4))", 0)
+        .assert_count("(9 (10)(11)(12))", 0);
 
-    imap.send("THREAD REFERENCES UTF-8 SUBJECT T1").await;
+    // Messages whose parent is not part of the result set are promoted
+    imap.send("THREAD REFERENCES UTF-8 2:4").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_contains("(5 6 7 8)")
-        .assert_count("(1 2 3 4)", 0)
-        .assert_count("(9 10 11 12)", 0);
+        .assert_contains("* THREAD ((2)(3)(4))");
+    imap.send("THREAD ORDEREDSUBJECT UTF-8 2:4").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* THREAD (2 (3)(4))");
 
     // Filter by threadId and messageId
     imap.send(&format!(
@@ -95,8 +107,8 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     .await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_contains("(1 2 3 4)")
-        .assert_count("(", 1);
+        .assert_contains("(1 (2)(3)(4))")
+        .assert_count("(", 4);
 
     imap.send(&format!("UID THREAD REFERENCES UTF-8 EMAILID {}", email_id))
         .await;
@@ -112,4 +124,32 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
         .assert_count("EXPUNGE", 13);
+
+    // Only the closest references of a message are considered
+    let message = format!(
+        "Message-ID: <deep@example.com>\r\nReferences: {}\r\nSubject: Deep\r\n\r\nTest\r\n",
+        (0..5000)
+            .map(|i| format!("<ref{i}@example.com>"))
+            .collect::<Vec<_>>()
+            .join("\r\n ")
+    );
+    imap.send(&format!("APPEND Manchego {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(&message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("SELECT Manchego").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    for algorithm in ["REFERENCES", "REFS"] {
+        imap.send(&format!("THREAD {algorithm} UTF-8 1:*")).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok)
+            .await
+            .assert_contains("* THREAD (1)");
+    }
+    imap.send("STORE 1:* +FLAGS.SILENT (\\Deleted)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("EXPUNGE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("EXPUNGE", 1);
 }
@@ -0,0 +1,132 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use common::Enterprise;
+use imap_proto::ResponseType;
+use se_licensing::license::LicenseKey;
+
+use super::{AssertResult, IMAPTest, ImapConnection, Type};
+
+pub async fn test(handle: &IMAPTest) {
+    println!("Running undelete tests...");
+
+    // Enable undelete
+    let mut core = handle.jmap.shared_core.load().as_ref().clone();
+    core.enterprise = Enterprise {
+        license: LicenseKey {
+            valid_to: u64::MAX,
+            valid_from: 0,
+            hostname: "example.com".to_string(),
+            accounts: 100,
+        },
+        undelete_period: Some(Duration::from_secs(86400)),
+    }
+    .into();
+    handle.jmap.shared_core.store(core.into());
+
+    let mut imap = ImapConnection::connect(b"_u ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("AUTHENTICATE PLAIN {32+}\r\nAGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Delete two messages
+    imap.send("CREATE \"Undelete Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    for subject in ["First", "Second"] {
+        let message = format!("From: test@domain.com\nSubject: {subject}\n\n{subject} body\n");
+        imap.send(&format!("APPEND \"Undelete Test\" {{{}}}", message.len()))
+            .await;
+        imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+        imap.send_untagged(&message).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+    imap.send("SELECT \"Undelete Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 2 EXISTS");
+    imap.send("STORE 1:* +FLAGS.SILENT (\\Deleted)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("EXPUNGE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXPUNGE");
+
+    // Deleted messages are listed in the read-only folder
+    imap.send("SELECT \"Recently Deleted\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 2 EXISTS")
+        .assert_contains("[UIDVALIDITY 2166136261]")
+        .assert_contains("[READ-ONLY]");
+    imap.send("FETCH 1:* (FLAGS RFC822.TEXT)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("First body")
+        .assert_contains("Second body")
+        .assert_count("Subject:", 0);
+    imap.send("FETCH 1 (RFC822.HEADER)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("Subject: First")
+        .assert_count("First body", 0);
+
+    // The folder does not track changes
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("EXPUNGE", 0)
+        .assert_count("EXISTS", 0);
+    imap.send("STORE 1 +FLAGS (\\Seen)").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Restore a message by copying it out, which expunges it from the folder
+    imap.send("UID COPY 2147483649 \"Undelete Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COPYUID");
+    imap.send("UID COPY 2147483649 \"Undelete Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXPUNGE");
+    imap.send("FETCH 1 (UID)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("UID 2147483650");
+    imap.send("STATUS \"Undelete Test\" (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 1");
+    imap.send("STATUS \"Recently Deleted\" (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 1");
+
+    // UIDs and the UID validity do not change when messages are restored
+    imap.send("SELECT \"Recently Deleted\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXISTS")
+        .assert_contains("[UIDVALIDITY 2166136261]")
+        .assert_contains("[UIDNEXT 2147483651]");
+    imap.send("FETCH 1 (UID)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("UID 2147483650");
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Disable undelete and remove test mailbox
+    let mut core = handle.jmap.shared_core.load().as_ref().clone();
+    core.enterprise = None;
+    handle.jmap.shared_core.store(core.into());
+    imap.send("DELETE \"Undelete Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
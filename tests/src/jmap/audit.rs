@@ -0,0 +1,168 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use hyper::Method;
+use jmap::api::management::audit::{diff, redact, AuditEntry, AuditOutcome};
+use serde::Deserialize;
+use serde_json::json;
+use store::{
+    write::{AuditClass, ValueClass},
+    ValueKey,
+};
+
+use crate::jmap::{assert_is_empty, ManagementApi};
+
+use super::JMAPTest;
+
+#[derive(Debug, Deserialize)]
+struct AuditList {
+    items: Vec<AuditEntry>,
+    total: usize,
+}
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running audit log tests...");
+    let server = params.server.clone();
+
+    // Enable auditing
+    let mut core = server.shared_core.load().as_ref().clone();
+    core.jmap.audit_enable = true;
+    server.shared_core.store(core.into());
+
+    // Create a principal, register an invalid OAuth client and delete the principal
+    let api = ManagementApi::new(8899, "admin", "secret");
+    api.post::<u32>(
+        "/api/principal",
+        &json!({
+            "type": "individual",
+            "name": "audited",
+            "secrets": ["hunter2"],
+            "description": "Audited account"
+        }),
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+    api.post::<()>(
+        "/api/oauth/client",
+        &json!({
+            "clientId": "audited-client",
+            "redirectUris": ["http://audited.example.com/callback"]
+        }),
+    )
+    .await
+    .unwrap()
+    .unwrap_error();
+    api.request::<()>(Method::DELETE, "/api/principal/audited")
+        .await
+        .unwrap()
+        .unwrap_data();
+
+    // Queries do not produce audit entries
+    let list = api
+        .request::<AuditList>(Method::GET, "/api/audit?actor=admin&text=audited")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(list.total, 3, "{list:?}");
+
+    // Entries are returned newest first
+    let delete = &list.items[0];
+    assert_eq!(delete.action, "DELETE");
+    assert_eq!(delete.target, "/api/principal/audited");
+    assert_eq!(delete.outcome, AuditOutcome::Success);
+    assert_eq!(
+        delete.before.as_ref().and_then(|v| v.get("name")),
+        Some(&json!("audited"))
+    );
+    assert_eq!(delete.after, None);
+
+    // Failed requests are recorded with the error
+    let rejected = &list.items[1];
+    assert_eq!(rejected.action, "POST");
+    assert_eq!(rejected.target, "/api/oauth/client");
+    assert_eq!(rejected.outcome, AuditOutcome::Failure);
+    assert!(rejected.error.is_some());
+    assert_eq!(rejected.after, None);
+
+    // Secrets are redacted from requests and snapshots
+    let create = &list.items[2];
+    assert_eq!(create.action, "POST");
+    assert_eq!(create.outcome, AuditOutcome::Success);
+    assert_eq!(create.actor, "admin");
+    assert_eq!(
+        create.request.as_ref().and_then(|v| v.get("secrets")),
+        Some(&json!(["********"]))
+    );
+    assert_eq!(create.before, None);
+    let after = create.after.as_ref().unwrap();
+    assert_eq!(after.get("description"), Some(&json!("Audited account")));
+    assert!(!after.to_string().contains("hunter2"), "{after}");
+
+    // Pagination
+    let list = api
+        .request::<AuditList>(Method::GET, "/api/audit?text=audited&page=2&limit=1")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(list.total, 3);
+    assert_eq!(list.items.len(), 1);
+    assert_eq!(list.items[0].outcome, AuditOutcome::Failure);
+
+    // Disable auditing and remove entries
+    let mut core = server.shared_core.load().as_ref().clone();
+    core.jmap.audit_enable = false;
+    server.shared_core.store(core.into());
+    server
+        .core
+        .storage
+        .data
+        .delete_range(
+            ValueKey::from(ValueClass::Audit(AuditClass { id: 0, expires: 0 })),
+            ValueKey::from(ValueClass::Audit(AuditClass {
+                id: u64::MAX,
+                expires: u64::MAX,
+            })),
+        )
+        .await
+        .unwrap();
+    assert_is_empty(server).await;
+}
+
+#[test]
+fn audit_redact_and_diff() {
+    let mut request = json!([
+        {"action": "set", "field": "secrets", "value": "hunter2"},
+        {"action": "set", "field": "description", "value": "John"},
+        {"type": "enableOtpAuth", "url": "otpauth://totp/john?secret=ABC"},
+        {"type": "insert", "values": [["authentication.fallback-admin.secret", "abc"], ["server.hostname", "mx"]]},
+    ]);
+    redact(&mut request);
+    assert_eq!(
+        request,
+        json!([
+            {"action": "set", "field": "secrets", "value": "********"},
+            {"action": "set", "field": "description", "value": "John"},
+            {"type": "enableOtpAuth", "url": "********"},
+            {"type": "insert", "values": [["authentication.fallback-admin.secret", "********"], ["server.hostname", "mx"]]},
+        ])
+    );
+
+    assert_eq!(
+        diff(
+            Some(json!({"name": "john", "quota": 10, "emails": ["a@b.c"]})),
+            Some(json!({"name": "john", "quota": 20, "emails": ["a@b.c"], "description": "J"})),
+        ),
+        (
+            Some(json!({"quota": 10})),
+            Some(json!({"quota": 20, "description": "J"}))
+        )
+    );
+    assert_eq!(
+        diff(None, Some(json!({"name": "john"}))),
+        (None, Some(json!({"name": "john"})))
+    );
+}
@@ -6,9 +6,13 @@
 
 use std::time::{Duration, Instant};
 
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use bytes::Bytes;
 use directory::backend::internal::manage::ManageDirectory;
+use hyper::Method;
 use jmap::auth::oauth::{
+    client::OAuthClient,
+    oidc::{IdTokenClaims, OpenIdMetadata, UserInfo},
     DeviceAuthResponse, ErrorType, OAuthCodeRequest, OAuthMetadata, TokenResponse,
 };
 use jmap_client::{
@@ -70,6 +74,8 @@ pub async fn test(params: &mut JMAPTest) {
             &OAuthCodeRequest::Code {
                 client_id: "OAuthyMcOAuthFace".to_string(),
                 redirect_uri: "https://localhost".to_string().into(),
+                scope: None,
+                nonce: None,
             },
         )
         .await
@@ -120,6 +126,198 @@ pub async fn test(params: &mut JMAPTest) {
         .ids()
         .is_empty());
 
+    // ------------------------
+    // OpenID Connect
+    // ------------------------
+
+    // Obtain OpenID Connect metadata and key set
+    let oidc_metadata: OpenIdMetadata =
+        get("https://127.0.0.1:8899/.well-known/openid-configuration").await;
+    assert_eq!(oidc_metadata.issuer, metadata.issuer);
+    let jwks: serde_json::Value = get(&oidc_metadata.jwks_uri).await;
+    assert_eq!(jwks["keys"][0]["kid"], "test");
+    assert_eq!(jwks["keys"][0]["alg"], "RS256");
+
+    // ID tokens can only be issued to registered clients
+    let code_request = OAuthCodeRequest::Code {
+        client_id: "OpenIdClient".to_string(),
+        redirect_uri: "https://localhost/callback".to_string().into(),
+        scope: "openid email profile".to_string().into(),
+        nonce: "n-0S6_WzA2Mj".to_string().into(),
+    };
+    assert_eq!(
+        api.post::<OAuthCodeResponse>("/api/oauth", &code_request)
+            .await
+            .unwrap()
+            .unwrap_error()
+            .1,
+        "OpenID Connect requires a registered client."
+    );
+
+    // Only HTTPS or loopback redirect URIs can be registered
+    let admin_api = ManagementApi::new(8899, "admin", "secret");
+    let mut client = OAuthClient {
+        client_id: "OpenIdClient".to_string(),
+        description: "Webmail".to_string().into(),
+        redirect_uris: vec!["http://webmail.example.com/callback".to_string()],
+        secret: "wiki-secret".to_string().into(),
+    };
+    admin_api
+        .post::<()>("/api/oauth/client", &client)
+        .await
+        .unwrap()
+        .unwrap_error();
+    client.redirect_uris = vec![
+        "https://localhost/callback".to_string(),
+        "http://127.0.0.1:8080/callback".to_string(),
+    ];
+    admin_api
+        .post::<()>("/api/oauth/client", &client)
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(
+        admin_api
+            .request::<OAuthClient>(Method::GET, "/api/oauth/client/OpenIdClient")
+            .await
+            .unwrap()
+            .unwrap_data(),
+        OAuthClient {
+            secret: None,
+            ..client.clone()
+        }
+    );
+
+    // Unregistered redirect URIs should be rejected
+    assert_eq!(
+        api.post::<OAuthCodeResponse>(
+            "/api/oauth",
+            &OAuthCodeRequest::Code {
+                client_id: "OpenIdClient".to_string(),
+                redirect_uri: "https://evil.example.com/callback".to_string().into(),
+                scope: "openid".to_string().into(),
+                nonce: None,
+            },
+        )
+        .await
+        .unwrap()
+        .unwrap_error()
+        .1,
+        "Redirect URI is not registered for this client."
+    );
+
+    // Client secrets are verified
+    let response = api
+        .post::<OAuthCodeResponse>("/api/oauth", &code_request)
+        .await
+        .unwrap()
+        .unwrap_data();
+    let mut token_params = AHashMap::from_iter([
+        ("client_id".to_string(), "OpenIdClient".to_string()),
+        (
+            "redirect_uri".to_string(),
+            "https://localhost/callback".to_string(),
+        ),
+        ("grant_type".to_string(), "authorization_code".to_string()),
+        ("client_secret".to_string(), "wrong-secret".to_string()),
+        ("code".to_string(), response.code),
+    ]);
+    assert_eq!(
+        post::<TokenResponse>(&metadata.token_endpoint, &token_params).await,
+        TokenResponse::Error {
+            error: ErrorType::InvalidClient
+        }
+    );
+
+    // Obtain ID token
+    token_params.insert("client_secret".to_string(), "wiki-secret".to_string());
+    let response = match post::<TokenResponse>(&metadata.token_endpoint, &token_params).await {
+        TokenResponse::Granted(response) => response,
+        TokenResponse::Error { error } => panic!("Expected token, got {error:?}"),
+    };
+    let id_token = response.id_token.expect("Missing ID token");
+    let mut parts = id_token.split('.');
+    let header: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts.next().unwrap()).unwrap()).unwrap();
+    let claims: IdTokenClaims =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts.next().unwrap()).unwrap()).unwrap();
+    assert_eq!(header["kid"], "test");
+    assert!(parts.next().is_some_and(|signature| !signature.is_empty()));
+    assert_eq!(claims.iss, oidc_metadata.issuer);
+    assert_eq!(claims.aud, "OpenIdClient");
+    assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+    assert_eq!(claims.userinfo.email.as_deref(), Some("jdoe@example.com"));
+    assert_eq!(claims.userinfo.name.as_deref(), Some("John Doe"));
+
+    // Obtain user info
+    let userinfo = get_userinfo(&oidc_metadata.userinfo_endpoint, &response.access_token).await;
+    assert_eq!(userinfo.sub, claims.userinfo.sub);
+    assert_eq!(userinfo.email.as_deref(), Some("jdoe@example.com"));
+    assert_eq!(
+        userinfo.preferred_username.as_deref(),
+        Some("jdoe@example.com")
+    );
+    assert!(userinfo.groups.is_empty());
+
+    // Refreshed tokens keep the granted scope
+    let refresh_params = AHashMap::from_iter([
+        ("client_id".to_string(), "OpenIdClient".to_string()),
+        ("grant_type".to_string(), "refresh_token".to_string()),
+        (
+            "refresh_token".to_string(),
+            response.refresh_token.clone().unwrap(),
+        ),
+    ]);
+    let refreshed = match post::<TokenResponse>(&metadata.token_endpoint, &refresh_params).await {
+        TokenResponse::Granted(response) => response,
+        TokenResponse::Error { error } => panic!("Expected token, got {error:?}"),
+    };
+    assert_eq!(refreshed.scope.as_deref(), Some("openid email profile"));
+    assert_eq!(
+        get_userinfo(&oidc_metadata.userinfo_endpoint, &refreshed.access_token).await,
+        userinfo
+    );
+
+    // Only the claims covered by the granted scopes are returned
+    let response = api
+        .post::<OAuthCodeResponse>(
+            "/api/oauth",
+            &OAuthCodeRequest::Code {
+                client_id: "OpenIdClient".to_string(),
+                redirect_uri: "https://localhost/callback".to_string().into(),
+                scope: "openid".to_string().into(),
+                nonce: None,
+            },
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    token_params.insert("code".to_string(), response.code);
+    let response = match post::<TokenResponse>(&metadata.token_endpoint, &token_params).await {
+        TokenResponse::Granted(response) => response,
+        TokenResponse::Error { error } => panic!("Expected token, got {error:?}"),
+    };
+    assert_eq!(
+        get_userinfo(&oidc_metadata.userinfo_endpoint, &response.access_token).await,
+        UserInfo {
+            sub: claims.userinfo.sub.clone(),
+            ..Default::default()
+        }
+    );
+
+    // Remove client
+    admin_api
+        .request::<()>(Method::DELETE, "/api/oauth/client/OpenIdClient")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert!(admin_api
+        .request::<OAuthClient>(Method::GET, "/api/oauth/client/OpenIdClient")
+        .await
+        .unwrap()
+        .try_unwrap_data()
+        .is_none());
+
     // ------------------------
     // Device code flow
     // ------------------------
@@ -328,6 +526,24 @@ async fn get<T: DeserializeOwned>(url: &str) -> T {
     serde_json::from_slice(&get_bytes(url).await).unwrap()
 }
 
+async fn get_userinfo(url: &str, token: &str) -> UserInfo {
+    serde_json::from_slice::<UserInfo>(
+        &reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap()
+            .get(url)
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap(),
+    )
+    .unwrap()
+}
+
 async fn assert_unauthorized(base_url: &str, token: &str) {
     match Client::new()
         .credentials(Credentials::bearer(token))
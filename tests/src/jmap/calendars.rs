@@ -0,0 +1,358 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap_proto::types::id::Id;
+use serde_json::Value;
+
+use crate::jmap::{assert_is_empty, jmap_json_request};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Calendars tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    let account_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jdoe@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+
+    // The default calendar is created on first access
+    let response = request(
+        &account_id,
+        r#"[["Calendar/get", {"accountId": "$$"}, "0"]]"#,
+    )
+    .await;
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/name"),
+        "Calendar",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/myRights/mayRSVP"),
+        Some(&Value::Bool(true)),
+        "Response: {response:?}"
+    );
+    let default_id = pointer_str(&response, "/methodResponses/0/1/list/0/id").to_string();
+
+    // Create a calendar, a weekly recurring event and a single event
+    let response = request(
+        &account_id,
+        r##"[["Calendar/set", {
+                "accountId": "$$",
+                "create": {
+                    "work": {"name": "Work", "color": "#ff0000"}
+                }
+             }, "0"],
+             ["CalendarEvent/set", {
+                "accountId": "$$",
+                "create": {
+                    "e1": {
+                        "title": "Team meeting",
+                        "start": "2026-01-05T10:00:00",
+                        "timeZone": "Etc/UTC",
+                        "duration": "PT1H",
+                        "recurrenceRules": [{
+                            "@type": "RecurrenceRule",
+                            "frequency": "weekly",
+                            "count": 4
+                        }]
+                    },
+                    "e2": {
+                        "calendarIds": {"#work": true},
+                        "title": "Dentist",
+                        "description": "Annual checkup",
+                        "start": "2026-01-07T15:00:00",
+                        "timeZone": "Etc/UTC",
+                        "duration": "PT30M",
+                        "locations": {"l1": {"name": "Downtown clinic"}}
+                    },
+                    "e3": {
+                        "title": "Missing start"
+                    },
+                    "e4": {
+                        "title": "Out of range interval",
+                        "start": "2026-01-05T10:00:00",
+                        "recurrenceRules": [{
+                            "@type": "RecurrenceRule",
+                            "frequency": "daily",
+                            "interval": 4294967296
+                        }]
+                    }
+                }
+             }, "1"]]"##,
+    )
+    .await;
+    let work_id = pointer_str(&response, "/methodResponses/0/1/created/work/id").to_string();
+    let e1_id = pointer_str(&response, "/methodResponses/1/1/created/e1/id").to_string();
+    let e2_id = pointer_str(&response, "/methodResponses/1/1/created/e2/id").to_string();
+    assert!(
+        pointer_str(&response, "/methodResponses/1/1/created/e1/uid").starts_with("urn:uuid:"),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/1/1/notCreated/e3/type"),
+        "invalidProperties",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/1/1/notCreated/e4/type"),
+        "invalidProperties",
+        "Response: {response:?}"
+    );
+
+    // Fetch events
+    let response = request(
+        &account_id,
+        &r#"[["CalendarEvent/get", {"accountId": "$$", "ids": ["%%"],
+              "properties": ["title", "calendarIds", "utcStart", "utcEnd"]}, "0"]]"#
+            .replace("%%", &e1_id),
+    )
+    .await;
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/title"),
+        "Team meeting",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer(&format!(
+            "/methodResponses/0/1/list/0/calendarIds/{default_id}"
+        )),
+        Some(&Value::Bool(true)),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/utcStart"),
+        "2026-01-05T10:00:00Z",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/utcEnd"),
+        "2026-01-26T11:00:00Z",
+        "Response: {response:?}"
+    );
+
+    // Query events
+    for (filter, expected) in [
+        (r#"{"text": "meeting"}"#, vec![e1_id.as_str()]),
+        (r#"{"title": "dentist"}"#, vec![e2_id.as_str()]),
+        (r#"{"text": "clinic"}"#, vec![e2_id.as_str()]),
+        (
+            &format!(r#"{{"inCalendars": ["{work_id}"]}}"#),
+            vec![e2_id.as_str()],
+        ),
+        (
+            r#"{"after": "2026-01-20T00:00:00Z", "before": "2026-02-01T00:00:00Z"}"#,
+            vec![e1_id.as_str()],
+        ),
+        (r#"{"after": "2026-01-27T00:00:00Z"}"#, vec![]),
+        (
+            r#"{"operator": "OR", "conditions": [{"title": "dentist"}, {"title": "team"}]}"#,
+            vec![e1_id.as_str(), e2_id.as_str()],
+        ),
+    ] {
+        let response = request(
+            &account_id,
+            &r#"[["CalendarEvent/query", {"accountId": "$$", "filter": %%}, "0"]]"#
+                .replace("%%", filter),
+        )
+        .await;
+        assert_eq!(
+            query_ids(&response),
+            expected,
+            "Filter: {filter} Response: {response:?}"
+        );
+    }
+
+    // Expand recurrences into instances
+    let expand_query = r#"[["CalendarEvent/query", {"accountId": "$$",
+        "filter": {"after": "2026-01-01T00:00:00Z", "before": "2026-02-01T00:00:00Z"},
+        "expandRecurrences": true}, "0"]]"#;
+    let response = request(&account_id, expand_query).await;
+    let instance_ids = query_ids(&response)
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    assert_eq!(instance_ids.len(), 5, "Response: {response:?}");
+    assert_eq!(instance_ids[1], e2_id, "Response: {response:?}");
+    assert!(
+        instance_ids
+            .iter()
+            .all(|id| Id::from_bytes(id.as_bytes()).is_some()),
+        "Response: {response:?}"
+    );
+
+    // Expanding recurrences requires a bounded time range
+    let response = request(
+        &account_id,
+        r#"[["CalendarEvent/query", {"accountId": "$$",
+            "filter": {"after": "2026-01-01T00:00:00Z"},
+            "expandRecurrences": true}, "0"]]"#,
+    )
+    .await;
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/type"),
+        "invalidArguments",
+        "Response: {response:?}"
+    );
+
+    // Fetch an instance of the recurring event
+    let response = request(
+        &account_id,
+        &r#"[["CalendarEvent/get", {"accountId": "$$", "ids": ["%%"]}, "0"]]"#
+            .replace("%%", &instance_ids[2]),
+    )
+    .await;
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/recurrenceId"),
+        "2026-01-12T10:00:00",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/start"),
+        "2026-01-12T10:00:00",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/recurrenceRules"),
+        None,
+        "Response: {response:?}"
+    );
+
+    // Update one instance and exclude another
+    let response = request(
+        &account_id,
+        &r#"[["CalendarEvent/set", {
+                "accountId": "$$",
+                "update": {
+                    "%%": {"title": "Moved meeting", "start": "2026-01-12T14:00:00"}
+                },
+                "destroy": ["&&"]
+             }, "0"],
+             ["CalendarEvent/get", {"accountId": "$$", "ids": ["%%"]}, "1"]]"#
+            .replace("%%", &instance_ids[2])
+            .replace("&&", &instance_ids[3]),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(instance_ids[3].clone())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/1/1/list/0/title"),
+        "Moved meeting",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/1/1/list/0/start"),
+        "2026-01-12T14:00:00",
+        "Response: {response:?}"
+    );
+    let response = request(&account_id, expand_query).await;
+    assert_eq!(
+        query_ids(&response),
+        vec![
+            instance_ids[0].as_str(),
+            instance_ids[1].as_str(),
+            instance_ids[2].as_str(),
+            instance_ids[4].as_str()
+        ],
+        "Response: {response:?}"
+    );
+
+    // Calendars with events cannot be destroyed unless requested
+    let response = request(
+        &account_id,
+        &r#"[["Calendar/set", {"accountId": "$$", "destroy": ["%%"]}, "0"]]"#
+            .replace("%%", &work_id),
+    )
+    .await;
+    assert_eq!(
+        pointer_str(
+            &response,
+            &format!("/methodResponses/0/1/notDestroyed/{work_id}/type")
+        ),
+        "calendarHasEvent",
+        "Response: {response:?}"
+    );
+    let response = request(
+        &account_id,
+        &r#"[["Calendar/set", {"accountId": "$$", "destroy": ["%%"],
+                "onDestroyRemoveEvents": true}, "0"],
+             ["CalendarEvent/get", {"accountId": "$$", "ids": ["&&"]}, "1"]]"#
+            .replace("%%", &work_id)
+            .replace("&&", &e2_id),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(work_id.clone())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/notFound/0"),
+        Some(&Value::String(e2_id.clone())),
+        "Response: {response:?}"
+    );
+
+    // Remove the remaining event and the default calendar
+    let response = request(
+        &account_id,
+        &r#"[["CalendarEvent/set", {"accountId": "$$", "destroy": ["%%"]}, "0"]]"#
+            .replace("%%", &e1_id),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(e1_id.clone())),
+        "Response: {response:?}"
+    );
+    let response = jmap_json_request(
+        r#"[["Calendar/set", {"accountId": "$$", "destroy": ["&&"]}, "0"]]"#
+            .replace("$$", &account_id)
+            .replace("&&", &default_id),
+        "admin",
+        "secret",
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(default_id.clone())),
+        "Response: {response:?}"
+    );
+
+    assert_is_empty(server).await;
+}
+
+async fn request(account_id: &str, body: &str) -> Value {
+    jmap_json_request(body.replace("$$", account_id), "jdoe@example.com", "12345").await
+}
+
+fn query_ids(response: &Value) -> Vec<&str> {
+    response
+        .pointer("/methodResponses/0/1/ids")
+        .and_then(|v| v.as_array())
+        .map(|ids| ids.iter().filter_map(|id| id.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default()
+}
+
+fn pointer_str<'x>(response: &'x Value, pointer: &str) -> &'x str {
+    response
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+}
@@ -0,0 +1,291 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap_proto::types::id::Id;
+use serde_json::Value;
+
+use crate::jmap::{assert_is_empty, jmap_json_request};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Contacts tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    let account_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jdoe@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+
+    // The default address book is created on first access
+    let response = request(
+        &account_id,
+        r#"[["AddressBook/get", {"accountId": "$$"}, "0"]]"#,
+    )
+    .await;
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/name"),
+        "Personal",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/isDefault"),
+        Some(&Value::Bool(true)),
+        "Response: {response:?}"
+    );
+    let default_id = pointer_str(&response, "/methodResponses/0/1/list/0/id").to_string();
+
+    // Create an address book and two contact cards
+    let response = request(
+        &account_id,
+        r##"[["AddressBook/set", {
+                "accountId": "$$",
+                "create": {
+                    "work": {"name": "Work"}
+                }
+             }, "0"],
+             ["ContactCard/set", {
+                "accountId": "$$",
+                "create": {
+                    "c1": {
+                        "name": {"full": "Jane Doe"},
+                        "emails": {"e1": {"address": "jane@example.org"}}
+                    },
+                    "c2": {
+                        "addressBookIds": {"#work": true},
+                        "name": {"components": [
+                            {"kind": "given", "value": "Bill"},
+                            {"kind": "surname", "value": "Foobar"}
+                        ]},
+                        "emails": {"e1": {"address": "bill@foobar.org"}},
+                        "uid": "urn:uuid:0b7e3a3c-1d1b-4c4f-9a43-3a9b1f1c1c1c"
+                    }
+                }
+             }, "1"]]"##,
+    )
+    .await;
+    let work_id = pointer_str(&response, "/methodResponses/0/1/created/work/id").to_string();
+    let c1_id = pointer_str(&response, "/methodResponses/1/1/created/c1/id").to_string();
+    let c2_id = pointer_str(&response, "/methodResponses/1/1/created/c2/id").to_string();
+    assert!(
+        pointer_str(&response, "/methodResponses/1/1/created/c1/uid").starts_with("urn:uuid:"),
+        "Response: {response:?}"
+    );
+    let state = pointer_str(&response, "/methodResponses/1/1/newState").to_string();
+
+    // Fetch contact cards
+    let response = request(
+        &account_id,
+        &r#"[["ContactCard/get", {"accountId": "$$", "ids": ["%%"]}, "0"]]"#.replace("%%", &c1_id),
+    )
+    .await;
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/name/full"),
+        "Jane Doe",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(&response, "/methodResponses/0/1/list/0/@type"),
+        "Card",
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer(&format!(
+            "/methodResponses/0/1/list/0/addressBookIds/{default_id}"
+        )),
+        Some(&Value::Bool(true)),
+        "Response: {response:?}"
+    );
+
+    // Query contact cards
+    for (filter, expected) in [
+        (r#"{"text": "doe"}"#, vec![c1_id.as_str()]),
+        (r#"{"name": "foobar"}"#, vec![c2_id.as_str()]),
+        (r#"{"email": "jane@example.org"}"#, vec![c1_id.as_str()]),
+        (
+            r#"{"uid": "urn:uuid:0b7e3a3c-1d1b-4c4f-9a43-3a9b1f1c1c1c"}"#,
+            vec![c2_id.as_str()],
+        ),
+        (
+            &format!(r#"{{"inAddressBook": "{work_id}"}}"#),
+            vec![c2_id.as_str()],
+        ),
+        (
+            r#"{"operator": "OR", "conditions": [{"text": "jane"}, {"text": "bill"}]}"#,
+            vec![c2_id.as_str(), c1_id.as_str()],
+        ),
+    ] {
+        let response = request(
+            &account_id,
+            &r#"[["ContactCard/query", {"accountId": "$$", "filter": %%,
+                "sort": [{"property": "name"}]}, "0"]]"#
+                .replace("%%", filter),
+        )
+        .await;
+        assert_eq!(
+            response
+                .pointer("/methodResponses/0/1/ids")
+                .and_then(|v| v.as_array())
+                .map(|ids| ids.iter().filter_map(|id| id.as_str()).collect::<Vec<_>>())
+                .unwrap_or_default(),
+            expected,
+            "Filter: {filter} Response: {response:?}"
+        );
+    }
+
+    // Patch a contact card and make sure the index is updated
+    let response = request(
+        &account_id,
+        &r#"[["ContactCard/set", {
+                "accountId": "$$",
+                "update": {
+                    "%%": {"name/full": "Jane Smith"}
+                }
+             }, "0"],
+             ["ContactCard/query", {"accountId": "$$", "filter": {"name": "smith"}}, "1"],
+             ["ContactCard/query", {"accountId": "$$", "filter": {"name": "doe"}}, "2"]]"#
+            .replace("%%", &c1_id),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/ids/0"),
+        Some(&Value::String(c1_id.clone())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/2/1/ids/0"),
+        None,
+        "Response: {response:?}"
+    );
+
+    // The uid of a contact card is immutable
+    let response = request(
+        &account_id,
+        &r#"[["ContactCard/set", {
+                "accountId": "$$",
+                "update": {
+                    "%%": {"uid": "urn:uuid:changed"}
+                }
+             }, "0"]]"#
+            .replace("%%", &c1_id),
+    )
+    .await;
+    assert_eq!(
+        pointer_str(
+            &response,
+            &format!("/methodResponses/0/1/notUpdated/{c1_id}/type")
+        ),
+        "invalidProperties",
+        "Response: {response:?}"
+    );
+
+    // Obtain changes
+    let response = request(
+        &account_id,
+        &r#"[["ContactCard/changes", {"accountId": "$$", "sinceState": "%%"}, "0"]]"#
+            .replace("%%", &state),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/updated/0"),
+        Some(&Value::String(c1_id.clone())),
+        "Response: {response:?}"
+    );
+
+    // Address books with contents cannot be destroyed unless requested
+    let response = request(
+        &account_id,
+        &r#"[["AddressBook/set", {"accountId": "$$", "destroy": ["%%"]}, "0"]]"#
+            .replace("%%", &work_id),
+    )
+    .await;
+    assert_eq!(
+        pointer_str(
+            &response,
+            &format!("/methodResponses/0/1/notDestroyed/{work_id}/type")
+        ),
+        "addressBookHasContents",
+        "Response: {response:?}"
+    );
+    let response = request(
+        &account_id,
+        &r#"[["AddressBook/set", {"accountId": "$$", "destroy": ["%%"],
+                "onDestroyRemoveContents": true}, "0"],
+             ["ContactCard/get", {"accountId": "$$", "ids": ["&&"]}, "1"]]"#
+            .replace("%%", &work_id)
+            .replace("&&", &c2_id),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(work_id.clone())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/notFound/0"),
+        Some(&Value::String(c2_id.clone())),
+        "Response: {response:?}"
+    );
+
+    // The default address book can only be removed by an administrator
+    let response = request(
+        &account_id,
+        &r#"[["ContactCard/set", {"accountId": "$$", "destroy": ["%%"]}, "0"],
+             ["AddressBook/set", {"accountId": "$$", "destroy": ["&&"]}, "1"]]"#
+            .replace("%%", &c1_id)
+            .replace("&&", &default_id),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(c1_id.clone())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        pointer_str(
+            &response,
+            &format!("/methodResponses/1/1/notDestroyed/{default_id}/type")
+        ),
+        "forbidden",
+        "Response: {response:?}"
+    );
+    let response = jmap_json_request(
+        r#"[["AddressBook/set", {"accountId": "$$", "destroy": ["&&"]}, "0"]]"#
+            .replace("$$", &account_id)
+            .replace("&&", &default_id),
+        "admin",
+        "secret",
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(default_id.clone())),
+        "Response: {response:?}"
+    );
+
+    assert_is_empty(server).await;
+}
+
+async fn request(account_id: &str, body: &str) -> Value {
+    jmap_json_request(body.replace("$$", account_id), "jdoe@example.com", "12345").await
+}
+
+fn pointer_str<'x>(response: &'x Value, pointer: &str) -> &'x str {
+    response
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+}
@@ -0,0 +1,704 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use directory::backend::internal::manage::ManageDirectory;
+use jmap::{
+    calendar_event::{recurrence::ExpandRecurrence, EventFields},
+    dav::{
+        ical::{event_to_icalendar, icalendar_to_event},
+        request::{DavProperty, PropFind, PropertyUpdate, ReportRequest},
+        text::{parse_content_lines, split_unescaped},
+        vcard::{card_to_vcard, vcard_to_card},
+    },
+};
+use jmap_proto::types::{id::Id, property::Property, value::Value};
+use reqwest::{header, Method};
+
+use crate::jmap::{assert_is_empty, jmap_json_request};
+
+use super::JMAPTest;
+
+const VCARD: &str = "BEGIN:VCARD\r\nVERSION:3.0\r\nUID:card-1@example.com\r\nFN:Jane Doe\r\nN:Doe;Jane;;;\r\nEMAIL;TYPE=WORK:jane@example.com\r\nEND:VCARD\r\n";
+const VCARD_UPDATED: &str = "BEGIN:VCARD\r\nVERSION:3.0\r\nUID:card-1@example.com\r\nFN:Jane Smith\r\nN:Smith;Jane;;;\r\nEND:VCARD\r\n";
+const ICALENDAR: &str = concat!(
+    "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\n",
+    "BEGIN:VEVENT\r\nUID:event-1@example.com\r\nSUMMARY:Standup\r\n",
+    "DTSTART:20260105T090000Z\r\nDTEND:20260105T091500Z\r\n",
+    "RRULE:FREQ=DAILY;COUNT=5\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+);
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running CardDAV/CalDAV tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    let account_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jdoe@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+
+    // Service discovery
+    let (status, headers, _) = dav_request("PROPFIND", "/.well-known/carddav", &[], "").await;
+    assert_eq!(status, 301);
+    assert_eq!(headers.get("location").unwrap(), "/dav/");
+    let (status, headers, _) = dav_request("OPTIONS", "/dav/", &[], "").await;
+    assert_eq!(status, 200);
+    assert!(headers
+        .get("dav")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .contains("calendar-access"));
+
+    // Principal properties
+    let (status, _, body) = dav_request(
+        "PROPFIND",
+        &format!("/dav/principal/{account_id}/"),
+        &[("Depth", "0")],
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            r#"<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" "#,
+            r#"xmlns:CR="urn:ietf:params:xml:ns:carddav"><D:prop>"#,
+            r#"<CR:addressbook-home-set/><C:calendar-home-set/>"#,
+            r#"<C:calendar-user-address-set/><D:getlastmodified/>"#,
+            r#"</D:prop></D:propfind>"#
+        ),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains(&format!("/dav/card/{account_id}/")), "{body}");
+    assert!(body.contains(&format!("/dav/cal/{account_id}/")), "{body}");
+    assert!(body.contains("mailto:jdoe@example.com"), "{body}");
+    assert!(body.contains("404 Not Found"), "{body}");
+
+    // The default address book is listed in the home collection
+    let (status, _, body) = dav_request(
+        "PROPFIND",
+        &format!("/dav/card/{account_id}/"),
+        &[("Depth", "1")],
+        "",
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    let book_href = find_href(&body, &format!("/dav/card/{account_id}/"));
+    assert!(body.contains("<CR:addressbook/>"), "{body}");
+
+    // Create a vCard
+    let card_href = format!("{book_href}card-1@example.com.vcf");
+    let (status, headers, _) = dav_request(
+        "PUT",
+        &card_href,
+        &[("If-None-Match", "*"), ("Content-Type", "text/vcard")],
+        VCARD,
+    )
+    .await;
+    assert_eq!(status, 201);
+    let etag = headers.get("etag").unwrap().to_str().unwrap().to_string();
+    let (status, _, _) = dav_request("PUT", &card_href, &[("If-None-Match", "*")], VCARD).await;
+    assert_eq!(status, 412);
+
+    // Fetch it using GET and JMAP
+    let (status, headers, body) = dav_request("GET", &card_href, &[], "").await;
+    assert_eq!(status, 200);
+    assert_eq!(headers.get("etag").unwrap().to_str().unwrap(), etag);
+    assert!(body.contains("FN:Jane Doe"), "{body}");
+    let response = jmap_json_request(
+        r#"[["ContactCard/query", {"accountId": "$$"}, "0"]]"#.replace("$$", &account_id),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    assert_eq!(
+        response
+            .pointer("/methodResponses/0/1/ids")
+            .and_then(|ids| ids.as_array())
+            .map(|ids| ids.len()),
+        Some(1),
+        "Response: {response:?}"
+    );
+
+    // Initial sync
+    let (status, _, body) =
+        dav_request("REPORT", &book_href, &[], &sync_collection_request("")).await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains(&card_href), "{body}");
+    let sync_token = find_tag(&body, "D:sync-token");
+
+    // Update the vCard, stale ETags are rejected
+    let (status, _, _) = dav_request(
+        "PUT",
+        &card_href,
+        &[("If-Match", "\"1234\"")],
+        VCARD_UPDATED,
+    )
+    .await;
+    assert_eq!(status, 412);
+    let (status, _, _) =
+        dav_request("PUT", &card_href, &[("If-Match", &etag)], VCARD_UPDATED).await;
+    assert_eq!(status, 204);
+
+    // Incremental sync returns the changed card
+    let (status, _, body) = dav_request(
+        "REPORT",
+        &book_href,
+        &[],
+        &sync_collection_request(&sync_token),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains(&card_href), "{body}");
+    assert!(body.contains("Jane Smith"), "{body}");
+    assert_ne!(find_tag(&body, "D:sync-token"), sync_token);
+
+    // Multiget
+    let (status, _, body) = dav_request(
+        "REPORT",
+        &book_href,
+        &[],
+        &format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                r#"<CR:addressbook-multiget xmlns:D="DAV:" xmlns:CR="urn:ietf:params:xml:ns:carddav">"#,
+                r#"<D:prop><D:getetag/><CR:address-data/></D:prop>"#,
+                r#"<D:href>{}</D:href><D:href>{}missing.vcf</D:href>"#,
+                r#"</CR:addressbook-multiget>"#
+            ),
+            card_href, book_href
+        ),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("FN:Jane Smith"), "{body}");
+    assert!(body.contains("404 Not Found"), "{body}");
+
+    // Deleted items invalidate older sync tokens
+    let (status, _, _) = dav_request("DELETE", &card_href, &[], "").await;
+    assert_eq!(status, 204);
+    let (status, _, _) = dav_request("GET", &card_href, &[], "").await;
+    assert_eq!(status, 404);
+    let (status, _, body) = dav_request(
+        "REPORT",
+        &book_href,
+        &[],
+        &sync_collection_request(&sync_token),
+    )
+    .await;
+    assert_eq!(status, 403, "{body}");
+    assert!(body.contains("valid-sync-token"), "{body}");
+
+    // Items exceeding the account quota are rejected
+    params
+        .directory
+        .set_test_quota("jdoe@example.com", 50)
+        .await;
+    server.inner.access_tokens.clear();
+    server.inner.sessions.clear();
+    let (status, _, _) = dav_request("PUT", &card_href, &[("If-None-Match", "*")], VCARD).await;
+    assert_eq!(status, 507);
+    let response = jmap_json_request(
+        format!(
+            r#"[["CalendarEvent/set", {{"accountId": "{account_id}", "create": {{"e1": {{"title": "Over quota", "start": "2026-01-05T09:00:00"}}}}}}, "0"]]"#
+        ),
+        "admin",
+        "secret",
+    )
+    .await;
+    assert_eq!(
+        response
+            .pointer("/methodResponses/0/1/notCreated/e1/type")
+            .and_then(|t| t.as_str()),
+        Some("overQuota"),
+        "Response: {response:?}"
+    );
+    params.directory.set_test_quota("jdoe@example.com", 0).await;
+    server.inner.access_tokens.clear();
+    server.inner.sessions.clear();
+
+    // Create a calendar
+    let (status, headers, _) = dav_request(
+        "MKCALENDAR",
+        &format!("/dav/cal/{account_id}/work/"),
+        &[],
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            r#"<C:mkcalendar xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">"#,
+            r#"<D:set><D:prop><D:displayname>Work</D:displayname></D:prop></D:set>"#,
+            r#"</C:mkcalendar>"#
+        ),
+    )
+    .await;
+    assert_eq!(status, 201);
+    let calendar_href = headers
+        .get("location")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // Rename it
+    let (status, _, body) = dav_request(
+        "PROPPATCH",
+        &calendar_href,
+        &[],
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            r#"<D:propertyupdate xmlns:D="DAV:" xmlns:A="http://apple.com/ns/ical/">"#,
+            r#"<D:set><D:prop><D:displayname>Office</D:displayname>"#,
+            r#"<A:calendar-order>1</A:calendar-order></D:prop></D:set>"#,
+            r#"</D:propertyupdate>"#
+        ),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains("403 Forbidden"), "{body}");
+    let (status, _, body) = dav_request("PROPFIND", &calendar_href, &[("Depth", "0")], "").await;
+    assert_eq!(status, 207, "{body}");
+    assert!(
+        body.contains("<D:displayname>Office</D:displayname>"),
+        "{body}"
+    );
+    assert!(body.contains("<C:calendar/>"), "{body}");
+
+    // Add a recurring event
+    let event_href = format!("{calendar_href}event-1@example.com.ics");
+    let (status, _, _) = dav_request(
+        "PUT",
+        &event_href,
+        &[("If-None-Match", "*"), ("Content-Type", "text/calendar")],
+        ICALENDAR,
+    )
+    .await;
+    assert_eq!(status, 201);
+    let (status, _, body) = dav_request("GET", &event_href, &[], "").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("RRULE:FREQ=DAILY;COUNT=5"), "{body}");
+
+    // Time ranges are matched against the expanded occurrences
+    for (start, end, expect_match) in [
+        ("20260108T000000Z", "20260109T000000Z", true),
+        ("20260112T000000Z", "20260113T000000Z", false),
+    ] {
+        let (status, _, body) = dav_request(
+            "REPORT",
+            &calendar_href,
+            &[("Depth", "1")],
+            &format!(
+                concat!(
+                    r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                    r#"<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">"#,
+                    r#"<D:prop><D:getetag/></D:prop><C:filter><C:comp-filter name="VCALENDAR">"#,
+                    r#"<C:comp-filter name="VEVENT"><C:time-range start="{}" end="{}"/>"#,
+                    r#"</C:comp-filter></C:comp-filter></C:filter></C:calendar-query>"#
+                ),
+                start, end
+            ),
+        )
+        .await;
+        assert_eq!(status, 207, "{body}");
+        assert_eq!(body.contains(&event_href), expect_match, "{body}");
+    }
+
+    // Removing the calendar removes its events
+    let (status, _, _) = dav_request("DELETE", &calendar_href, &[], "").await;
+    assert_eq!(status, 204);
+    let (status, _, _) = dav_request("GET", &event_href, &[], "").await;
+    assert_eq!(status, 404);
+
+    // Remove the default collections
+    for (method, collection) in [
+        ("AddressBook/set", "AddressBook"),
+        ("Calendar/set", "Calendar"),
+    ] {
+        let response = jmap_json_request(
+            format!(r#"[["{collection}/get", {{"accountId": "{account_id}"}}, "0"]]"#),
+            "jdoe@example.com",
+            "12345",
+        )
+        .await;
+        let default_id = response
+            .pointer("/methodResponses/0/1/list/0/id")
+            .and_then(|id| id.as_str())
+            .unwrap()
+            .to_string();
+        let response = jmap_json_request(
+            format!(
+                r#"[["{method}", {{"accountId": "{account_id}", "destroy": ["{default_id}"]}}, "0"]]"#
+            ),
+            "admin",
+            "secret",
+        )
+        .await;
+        assert_eq!(
+            response.pointer("/methodResponses/0/1/destroyed/0"),
+            Some(&serde_json::Value::String(default_id)),
+            "Response: {response:?}"
+        );
+    }
+
+    assert_is_empty(server).await;
+}
+
+async fn dav_request(
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (u16, header::HeaderMap, String) {
+    let mut request = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(Duration::from_millis(1000))
+        .build()
+        .unwrap()
+        .request(
+            Method::from_bytes(method.as_bytes()).unwrap(),
+            format!("https://127.0.0.1:8899{path}"),
+        )
+        .header(
+            header::AUTHORIZATION,
+            format!("Basic {}", STANDARD.encode("jdoe@example.com:12345")),
+        );
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request.body(body.to_string()).send().await.unwrap();
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+    (status, headers, response.text().await.unwrap())
+}
+
+fn sync_collection_request(sync_token: &str) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            r#"<D:sync-collection xmlns:D="DAV:" xmlns:CR="urn:ietf:params:xml:ns:carddav">"#,
+            r#"<D:sync-token>{}</D:sync-token><D:sync-level>1</D:sync-level>"#,
+            r#"<D:prop><D:getetag/><CR:address-data/></D:prop></D:sync-collection>"#
+        ),
+        sync_token
+    )
+}
+
+fn find_href(body: &str, prefix: &str) -> String {
+    body.split("<D:href>")
+        .filter_map(|part| part.split_once("</D:href>"))
+        .map(|(href, _)| href)
+        .find(|href| href.starts_with(prefix) && href.len() > prefix.len())
+        .unwrap_or_else(|| panic!("No collection found: {body}"))
+        .to_string()
+}
+
+fn find_tag(body: &str, tag: &str) -> String {
+    body.split_once(&format!("<{tag}>"))
+        .and_then(|(_, value)| value.split_once(&format!("</{tag}>")))
+        .map(|(value, _)| value.to_string())
+        .unwrap_or_else(|| panic!("No {tag} found: {body}"))
+}
+
+#[test]
+fn dav_content_lines() {
+    let lines = parse_content_lines(concat!(
+        "BEGIN:VCARD\r\n",
+        "item1.EMAIL;TYPE=work,pref:jane@example.org\r\n",
+        "ATTENDEE;CN=\"Doe, Jane\";PARTSTAT=ACCEPTED:mailto:jane@\r\n",
+        " example.org\r\n",
+        "NOTE:Line one\\nLine two\\, continued\r\n",
+    ));
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[1].name, "EMAIL");
+    assert!(lines[1].has_param_value("type", "PREF"));
+    assert_eq!(lines[2].param("CN"), Some("Doe, Jane"));
+    assert_eq!(lines[2].value, "mailto:jane@example.org");
+    assert_eq!(lines[3].text(), "Line one\nLine two, continued");
+
+    let mut out = String::new();
+    lines[2].write(&mut out);
+    assert_eq!(parse_content_lines(&out), vec![lines[2].clone()]);
+
+    assert_eq!(
+        split_unescaped("Doe;Jane;;Dr.\\;Mr.;", ';'),
+        vec!["Doe", "Jane", "", "Dr.;Mr.", ""]
+    );
+}
+
+#[test]
+fn dav_vcard_roundtrip() {
+    let card = vcard_to_card(concat!(
+        "BEGIN:VCARD\r\n",
+        "VERSION:3.0\r\n",
+        "UID:urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1\r\n",
+        "FN:Jane Doe\r\n",
+        "N:Doe;Jane;;;\r\n",
+        "EMAIL;TYPE=INTERNET,WORK:jane@example.org\r\n",
+        "TEL;TYPE=CELL:+1-555-555-5555\r\n",
+        "ORG:Example Inc.;Marketing\r\n",
+        "BDAY:1980-04-12\r\n",
+        "NOTE:First line\\nSecond line\r\n",
+        "END:VCARD\r\n"
+    ))
+    .unwrap();
+
+    assert_eq!(
+        card.get(&Property::Uid).as_string(),
+        Some("urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1")
+    );
+    assert_eq!(
+        card.get(&Property::Name)
+            .as_obj()
+            .and_then(|name| name.event_str("full")),
+        Some("Jane Doe")
+    );
+    let email = card
+        .event_obj("emails")
+        .and_then(|emails| emails.event_obj("e1"))
+        .unwrap();
+    assert_eq!(email.event_str("address"), Some("jane@example.org"));
+    assert!(matches!(
+        email
+            .event_obj("contexts")
+            .map(|contexts| contexts.event_value("work")),
+        Some(Value::Bool(true))
+    ));
+
+    let vcard = card_to_vcard(&card);
+    for line in [
+        "FN:Jane Doe",
+        "N:Doe;Jane;;;",
+        "EMAIL;TYPE=INTERNET;TYPE=WORK:jane@example.org",
+        "TEL;TYPE=CELL:+1-555-555-5555",
+        "ORG:Example Inc.;Marketing",
+        "BDAY:1980-04-12",
+        "NOTE:First line\\nSecond line",
+    ] {
+        assert!(vcard.contains(line), "missing {line:?} in {vcard}");
+    }
+    let reparsed = vcard_to_card(&vcard).unwrap();
+    assert_eq!(reparsed.properties.len(), card.properties.len());
+    for (property, value) in card.properties.iter() {
+        assert_eq!(reparsed.get(property), value, "{property}");
+    }
+}
+
+#[test]
+fn dav_icalendar_roundtrip() {
+    let event = icalendar_to_event(concat!(
+        "BEGIN:VCALENDAR\r\n",
+        "VERSION:2.0\r\n",
+        "PRODID:-//Example Corp.//Example Client//EN\r\n",
+        "BEGIN:VTIMEZONE\r\n",
+        "TZID:Etc/GMT-1\r\n",
+        "BEGIN:STANDARD\r\n",
+        "DTSTART:19700101T000000\r\n",
+        "END:STANDARD\r\n",
+        "END:VTIMEZONE\r\n",
+        "BEGIN:VEVENT\r\n",
+        "UID:5d8f4b8e-3c4a-4e3b-9d52-7f1b1b3c9a11\r\n",
+        "DTSTAMP:20260101T120000Z\r\n",
+        "DTSTART;TZID=Etc/GMT-1:20260105T100000\r\n",
+        "DTEND;TZID=Etc/GMT-1:20260105T113000\r\n",
+        "SUMMARY:Weekly sync\\, team A\r\n",
+        "LOCATION:Room 1\r\n",
+        "RRULE:FREQ=WEEKLY;COUNT=4;BYDAY=MO\r\n",
+        "EXDATE;TZID=Etc/GMT-1:20260119T100000\r\n",
+        "ORGANIZER;CN=John Doe:mailto:jdoe@example.com\r\n",
+        "ATTENDEE;CN=Jane Doe;PARTSTAT=ACCEPTED;RSVP=TRUE:mailto:jane@example.org\r\n",
+        "BEGIN:VALARM\r\n",
+        "TRIGGER:-PT15M\r\n",
+        "ACTION:DISPLAY\r\n",
+        "END:VALARM\r\n",
+        "END:VEVENT\r\n",
+        "BEGIN:VEVENT\r\n",
+        "UID:5d8f4b8e-3c4a-4e3b-9d52-7f1b1b3c9a11\r\n",
+        "RECURRENCE-ID;TZID=Etc/GMT-1:20260112T100000\r\n",
+        "DTSTART;TZID=Etc/GMT-1:20260112T140000\r\n",
+        "DURATION:PT1H30M\r\n",
+        "SUMMARY:Weekly sync (moved)\r\n",
+        "END:VEVENT\r\n",
+        "END:VCALENDAR\r\n",
+    ))
+    .unwrap();
+
+    assert_eq!(event.event_str("title"), Some("Weekly sync, team A"));
+    assert_eq!(event.event_str("start"), Some("2026-01-05T10:00:00"));
+    assert_eq!(event.event_str("timeZone"), Some("Etc/GMT-1"));
+    assert_eq!(event.event_str("duration"), Some("PT1H30M"));
+    assert_eq!(event.event_organizer(), Some("jdoe@example.com"));
+    assert_eq!(event.event_locations(), vec!["Room 1"]);
+    let participants = event.event_participants();
+    assert_eq!(participants.len(), 2);
+    assert_eq!(participants[1].participation_status, Some("accepted"));
+
+    // Excluded and moved instances
+    let expansion = event.expand(0, i64::MAX / 2, 0, 100).unwrap();
+    assert_eq!(
+        expansion
+            .occurrences
+            .iter()
+            .map(|o| o.start.format("%d %H").to_string())
+            .collect::<Vec<_>>(),
+        vec!["05 10", "12 14", "26 10"]
+    );
+
+    let ical = event_to_icalendar(&event, None, None);
+    for line in [
+        "DTSTART;TZID=Etc/GMT-1:20260105T100000",
+        "RRULE:FREQ=WEEKLY;COUNT=4;BYDAY=MO",
+        "EXDATE;TZID=Etc/GMT-1:20260119T100000",
+        "RECURRENCE-ID;TZID=Etc/GMT-1:20260112T100000",
+        "DTSTART;TZID=Etc/GMT-1:20260112T140000",
+        "SUMMARY:Weekly sync (moved)",
+        "ORGANIZER;CN=John Doe:mailto:jdoe@example.com",
+    ] {
+        assert!(ical.contains(line), "missing {line:?} in {ical}");
+    }
+    let reparsed = icalendar_to_event(&ical).unwrap();
+    for property in [
+        "title",
+        "start",
+        "duration",
+        "recurrenceRules",
+        "participants",
+    ] {
+        assert_eq!(
+            reparsed.event_value(property),
+            event.event_value(property),
+            "{property}"
+        );
+    }
+    assert_eq!(
+        reparsed.get(&Property::Uid),
+        &Value::Text("5d8f4b8e-3c4a-4e3b-9d52-7f1b1b3c9a11".to_string())
+    );
+}
+
+#[test]
+fn dav_recurrence_window() {
+    // Old series are expanded from the start of the window
+    let event = icalendar_to_event(concat!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\n",
+        "BEGIN:VEVENT\r\nUID:hourly@example.com\r\nSUMMARY:Hourly\r\n",
+        "DTSTART:20000101T000000Z\r\nDURATION:PT30M\r\n",
+        "RRULE:FREQ=HOURLY;INTERVAL=2\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+    ))
+    .unwrap();
+    let expansion = event
+        .expand(1767225600, 1767225600 + 6 * 3600, 0, 100)
+        .unwrap();
+    assert_eq!(
+        expansion
+            .occurrences
+            .iter()
+            .map(|o| o.start.format("%Y-%m-%d %H").to_string())
+            .collect::<Vec<_>>(),
+        vec!["2026-01-01 00", "2026-01-01 02", "2026-01-01 04"]
+    );
+    assert!(!expansion.is_truncated);
+
+    // The instance limit applies to occurrences within the window
+    let expansion = event.expand(1767225600, 1767225600 + 86400, 0, 2).unwrap();
+    assert_eq!(expansion.occurrences.len(), 2);
+    assert!(expansion.is_truncated);
+
+    // Recurrence ids are resolved regardless of the number of prior instances
+    let recurrence_id =
+        chrono::NaiveDateTime::parse_from_str("2026-01-01T02:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+    assert!(event.instance(recurrence_id, 0, 100).is_some());
+    assert!(event
+        .instance(recurrence_id + chrono::Duration::hours(1), 0, 100)
+        .is_none());
+
+    // Occurrences preceding the window are counted
+    let event = icalendar_to_event(ICALENDAR).unwrap();
+    let expansion = event
+        .expand(1767225600 + 7 * 86400, i64::MAX / 2, 0, 100)
+        .unwrap();
+    assert_eq!(
+        expansion
+            .occurrences
+            .iter()
+            .map(|o| o.start.format("%d").to_string())
+            .collect::<Vec<_>>(),
+        vec!["08", "09"]
+    );
+}
+
+#[test]
+fn dav_parse_requests() {
+    assert_eq!(
+        PropFind::parse(
+            br#"<?xml version="1.0" encoding="utf-8" ?>
+            <D:propfind xmlns:D="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+              <D:prop><D:displayname/><CS:getctag/><X:custom xmlns:X="urn:x"/></D:prop>
+            </D:propfind>"#
+        ),
+        Some(PropFind::Prop(vec![
+            DavProperty::DisplayName,
+            DavProperty::GetCTag,
+            DavProperty::Other {
+                namespace: "urn:x".to_string(),
+                name: "custom".to_string()
+            }
+        ]))
+    );
+    assert_eq!(PropFind::parse(b""), Some(PropFind::AllProp));
+
+    assert_eq!(
+        ReportRequest::parse(
+            br#"<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+              <D:prop><D:getetag/></D:prop>
+              <C:filter><C:comp-filter name="VCALENDAR"><C:comp-filter name="VEVENT">
+                <C:time-range start="20260101T000000Z" end="20260201T000000Z"/>
+              </C:comp-filter></C:comp-filter></C:filter>
+            </C:calendar-query>"#
+        ),
+        Some(ReportRequest::Query {
+            props: PropFind::Prop(vec![DavProperty::GetETag]),
+            time_range: Some((1767225600, 1769904000))
+        })
+    );
+
+    assert_eq!(
+        ReportRequest::parse(
+            br#"<sync-collection xmlns="DAV:">
+              <sync-token>http://stalw.art/ns/sync/12</sync-token>
+              <sync-level>1</sync-level>
+              <prop><getetag/></prop>
+            </sync-collection>"#
+        ),
+        Some(ReportRequest::SyncCollection {
+            sync_token: Some("http://stalw.art/ns/sync/12".to_string()),
+            props: PropFind::Prop(vec![DavProperty::GetETag])
+        })
+    );
+
+    assert_eq!(
+        PropertyUpdate::parse(
+            br#"<D:propertyupdate xmlns:D="DAV:" xmlns:A="http://apple.com/ns/ical/">
+              <D:set><D:prop><D:displayname>Work &amp; more</D:displayname>
+              <A:calendar-color>#FF0000</A:calendar-color></D:prop></D:set>
+              <D:remove><D:prop><D:owner/></D:prop></D:remove>
+            </D:propertyupdate>"#
+        ),
+        Some(PropertyUpdate {
+            set: vec![
+                (DavProperty::DisplayName, "Work & more".to_string()),
+                (DavProperty::CalendarColor, "#FF0000".to_string())
+            ],
+            remove: vec![DavProperty::Owner]
+        })
+    );
+}
@@ -0,0 +1,100 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{net::IpAddr, time::Duration};
+
+use common::{cluster::ClusterEvent, IPC_CHANNEL_BUFFER};
+use jmap::{
+    services::gossip::{request::Request, spawn::GossiperBuilder},
+    JmapInstance,
+};
+use tokio::sync::{mpsc, watch};
+use utils::config::Config;
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running gossip tests...");
+    let server = params.server.clone();
+    let instance = JmapInstance {
+        core: server.shared_core.clone(),
+        jmap_inner: server.inner.clone(),
+        smtp_inner: server.smtp.inner.clone(),
+    };
+
+    // Start two cluster nodes on different loopback addresses
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let mut cluster_txs = Vec::new();
+    for (bind_addr, seed_addr) in [("127.0.0.1", "127.0.0.2"), ("127.0.0.2", "127.0.0.1")] {
+        let mut config = Config::new(format!(
+            concat!(
+                "[cluster]\n",
+                "bind-addr = \"{}\"\n",
+                "bind-port = 11179\n",
+                "key = \"gossip-test-key\"\n",
+                "seed-nodes = [\"{}\"]\n",
+                "heartbeat = \"100ms\"\n"
+            ),
+            bind_addr, seed_addr
+        ))
+        .unwrap();
+        let (cluster_tx, cluster_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
+        GossiperBuilder::try_parse(&mut config)
+            .unwrap()
+            .spawn(instance.clone(), cluster_rx, shutdown_rx.clone())
+            .await;
+        cluster_txs.push(cluster_tx);
+    }
+
+    // Events broadcast by one node are applied by its peers
+    let ip = "10.0.0.99".parse::<IpAddr>().unwrap();
+    assert!(!server.shared_core.load().is_ip_blocked(&ip));
+    cluster_txs[0]
+        .send(ClusterEvent::BlockIp(ip))
+        .await
+        .unwrap();
+    let mut is_blocked = false;
+    for _ in 0..50 {
+        if server.shared_core.load().is_ip_blocked(&ip) {
+            is_blocked = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(is_blocked, "Blocked IP was not replicated to the peer.");
+
+    // Clean up
+    server
+        .shared_core
+        .load()
+        .network
+        .blocked_ips
+        .ip_addresses
+        .write()
+        .remove(&ip);
+    shutdown_tx.send(true).unwrap();
+}
+
+#[test]
+fn gossip_encode_cluster_events() {
+    let events = vec![
+        ClusterEvent::BlockIp("192.168.1.10".parse::<IpAddr>().unwrap()),
+        ClusterEvent::BlockIp("2001:db8::1".parse::<IpAddr>().unwrap()),
+        ClusterEvent::InvalidatePrincipal(0),
+        ClusterEvent::InvalidatePrincipal(u32::MAX),
+        ClusterEvent::ReloadSettings,
+        ClusterEvent::ReloadBlockedIps,
+    ];
+
+    match Request::from_bytes(&Request::Events(events.clone()).to_bytes()) {
+        Some(Request::Events(decoded)) => assert_eq!(decoded, events),
+        other => panic!("Unexpected result: {other:?}"),
+    }
+
+    // Truncated events are rejected
+    let bytes = Request::Events(events).to_bytes();
+    assert!(Request::from_bytes(&bytes[..10]).is_none());
+}
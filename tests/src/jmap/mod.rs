@@ -38,11 +38,15 @@ use webhooks::{spawn_mock_webhook_endpoint, MockWebhookEndpoint};
 
 use crate::{add_test_certs, directory::DirectoryStore, store::TempDir, AssertConfig};
 
+pub mod audit;
 pub mod auth_acl;
 pub mod auth_limits;
 pub mod auth_oauth;
 pub mod blob;
+pub mod calendars;
+pub mod contacts;
 pub mod crypto;
+pub mod dav;
 pub mod delivery;
 pub mod email_changes;
 pub mod email_copy;
@@ -54,6 +58,7 @@ pub mod email_search_snippet;
 pub mod email_set;
 pub mod email_submission;
 pub mod event_source;
+pub mod gossip;
 pub mod mailbox;
 pub mod purge;
 pub mod push_subscription;
@@ -265,6 +270,9 @@ token = "1s"
 refresh-token = "3s"
 refresh-token-renew = "2s"
 
+[oauth.oidc.key.test]
+private-key = "%{file:{PK}}%"
+
 [session.extensions]
 expn = true
 vrfy = true
@@ -321,6 +329,7 @@ pub async fn jmap_tests() {
     auth_acl::test(&mut params).await;
     auth_limits::test(&mut params).await;
     auth_oauth::test(&mut params).await;
+    audit::test(&mut params).await;
     event_source::test(&mut params).await;
     push_subscription::test(&mut params).await;
     sieve_script::test(&mut params).await;
@@ -330,6 +339,10 @@ pub async fn jmap_tests() {
     quota::test(&mut params).await;
     crypto::test(&mut params).await;
     blob::test(&mut params).await;
+    contacts::test(&mut params).await;
+    calendars::test(&mut params).await;
+    dav::test(&mut params).await;
+    gossip::test(&mut params).await;
     purge::test(&mut params).await;
 
     if delete {
@@ -486,6 +499,7 @@ async fn init_jmap_tests(store_id: &str, delete_if_exists: bool) -> JMAPTest {
     let ipc = Ipc {
         delivery_tx,
         webhook_tx,
+        cluster_tx: mpsc::channel(IPC_CHANNEL_BUFFER).0,
     };
 
     // Init servers
@@ -33,6 +33,7 @@ pub mod rcpt;
 pub mod rewrite;
 pub mod scripts;
 pub mod sign;
+pub mod srs;
 pub mod throttle;
 pub mod vrfy;
 
@@ -0,0 +1,258 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    config::smtp::srs::{Srs, SrsError},
+    Core,
+};
+use ring::hmac;
+use store::Stores;
+use utils::config::Config;
+
+use crate::{
+    smtp::{
+        build_smtp,
+        session::{TestSession, VerifyResponse},
+        TempDir,
+    },
+    AssertConfig,
+};
+use smtp::core::{Inner, Session};
+
+const CONFIG: &str = r#"
+[storage]
+data = "sqlite"
+lookup = "sqlite"
+blob = "sqlite"
+fts = "sqlite"
+directory = "local"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/queue.db"
+
+[directory."local"]
+type = "memory"
+
+[[directory."local".principals]]
+name = "john"
+description = "John Doe"
+secret = "secret"
+email = "john@foobar.org"
+
+[session.rcpt]
+directory = "'local'"
+relay = true
+
+[queue.srs]
+enable = true
+domain = "'foobar.org'"
+secret = "srs-secret"
+"#;
+
+#[tokio::test]
+async fn srs() {
+    // Enable logging
+    /*let disable = 1;
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    let mut inner = Inner::default();
+    let tmp_dir = TempDir::new("smtp_srs_test", true);
+    let mut config = Config::new(tmp_dir.update_config(CONFIG)).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = Core::parse(&mut config, stores, Default::default()).await;
+    config.assert_no_errors();
+    let mut qr = inner.init_test_queue(&core);
+
+    let core = build_smtp(core, inner);
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.remote.org").await;
+
+    // The return path of relayed messages is rewritten
+    session
+        .send_message(
+            "sender@remote.org",
+            &["forward@external.net"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    let message = qr.expect_message().await;
+    let srs_address = message.return_path.clone();
+    assert!(
+        srs_address.starts_with("SRS0=") && srs_address.ends_with("=remote.org=sender@foobar.org"),
+        "{srs_address}"
+    );
+    assert_eq!(message.return_path_domain, "foobar.org");
+
+    // Messages from local senders or to local recipients are not rewritten
+    session
+        .send_message(
+            "john@foobar.org",
+            &["forward@external.net"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    assert_eq!(qr.expect_message().await.return_path, "john@foobar.org");
+    session
+        .send_message(
+            "sender@remote.org",
+            &["john@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    assert_eq!(qr.expect_message().await.return_path, "sender@remote.org");
+
+    // Bounces sent to the SRS address are routed to the original sender
+    session
+        .send_message("<>", &[&srs_address], "test:no_dkim", "250")
+        .await;
+    let message = qr.expect_message().await;
+    assert_eq!(message.return_path, "");
+    assert_eq!(message.recipients.len(), 1);
+    assert_eq!(message.recipients[0].address, "sender@remote.org");
+    assert_eq!(message.domains[0].domain, "remote.org");
+
+    // Tampered SRS addresses are rejected
+    let tampered = srs_address.replacen("=sender@", "=other@", 1);
+    session.mail_from("<>", "250").await;
+    session.rcpt_to(&tampered, "550 5.1.1").await;
+    session.rset().await;
+
+    // Make sure store is empty
+    qr.clear_queue(&core).await;
+    core.core
+        .storage
+        .data
+        .assert_is_empty(core.core.storage.blob.clone())
+        .await;
+}
+
+#[test]
+fn srs_rewrite() {
+    let srs = Srs {
+        keys: vec![
+            hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, b"new secret"),
+            hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, b"old secret"),
+        ],
+        ..Default::default()
+    };
+
+    // SRS0 roundtrip
+    let srs0 = srs
+        .forward("John.Doe@example.org", "forwarder.net")
+        .unwrap();
+    assert!(srs0.starts_with("SRS0="), "{srs0}");
+    assert!(
+        srs0.ends_with("=example.org=John.Doe@forwarder.net"),
+        "{srs0}"
+    );
+    assert_eq!(
+        srs.reverse(&srs0).unwrap().as_deref(),
+        Some("John.Doe@example.org")
+    );
+
+    // SRS1 when forwarding an SRS0 address from another forwarder
+    let srs1 = srs.forward(&srs0, "other.net").unwrap();
+    assert!(srs1.starts_with("SRS1="), "{srs1}");
+    assert!(srs1.ends_with("@other.net"), "{srs1}");
+    assert_eq!(srs.reverse(&srs1).unwrap().as_deref(), Some(srs0.as_str()));
+    let srs1_again = srs.forward(&srs1, "third.net").unwrap();
+    assert_eq!(
+        srs.reverse(&srs1_again).unwrap().as_deref(),
+        Some(srs0.as_str())
+    );
+
+    // Addresses at the SRS domain are not rewritten
+    assert_eq!(srs.forward("jane@forwarder.net", "forwarder.net"), None);
+
+    // Addresses signed with a rotated key are still accepted
+    let old_srs = Srs {
+        keys: vec![hmac::Key::new(
+            hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            b"old secret",
+        )],
+        ..Default::default()
+    };
+    let srs0 = old_srs
+        .forward("jane@example.org", "forwarder.net")
+        .unwrap();
+    assert_eq!(
+        srs.reverse(&srs0).unwrap().as_deref(),
+        Some("jane@example.org")
+    );
+
+    // Invalid hashes, expired timestamps and non SRS addresses
+    let forged = srs0.replacen("SRS0=", "SRS0=AAAA", 1);
+    assert_eq!(srs.reverse(&forged), Err(SrsError::Invalid));
+    let (hash, rest) = srs0["SRS0=".len()..].split_once('=').unwrap();
+    if hash.chars().any(|ch| ch.is_ascii_alphabetic()) {
+        let hash = hash
+            .chars()
+            .map(|ch| {
+                if ch.is_ascii_uppercase() {
+                    ch.to_ascii_lowercase()
+                } else {
+                    ch.to_ascii_uppercase()
+                }
+            })
+            .collect::<String>();
+        assert_eq!(
+            srs.reverse(&format!("SRS0={hash}={rest}")),
+            Err(SrsError::Invalid)
+        );
+    }
+    let (_, rest) = srs0.split_once('=').unwrap();
+    let (_, rest) = rest.split_once('=').unwrap();
+    let (timestamp, rest) = rest.split_once('=').unwrap();
+    let expired = encode_timestamp(decode_timestamp(timestamp) + 1024 - 30);
+    let hash = srs_hash(b"old secret", &[&expired, "example.org", "jane"]);
+    let (_, rest) = rest.split_once('=').unwrap();
+    assert_eq!(
+        srs.reverse(&format!("SRS0={hash}={expired}=example.org={rest}")),
+        Err(SrsError::Expired)
+    );
+    assert_eq!(srs.reverse("jane@example.org"), Ok(None));
+}
+
+fn srs_hash(secret: &[u8], values: &[&str]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let mut ctx = hmac::Context::with_key(&key);
+    for value in values {
+        ctx.update(value.to_lowercase().as_bytes());
+    }
+    let mut hash = STANDARD.encode(ctx.sign().as_ref());
+    hash.truncate(4);
+    hash
+}
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn encode_timestamp(days: u64) -> String {
+    let days = days % 1024;
+    [
+        BASE32[(days >> 5) as usize & 31] as char,
+        BASE32[days as usize & 31] as char,
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn decode_timestamp(timestamp: &str) -> u64 {
+    timestamp.bytes().fold(0, |acc, ch| {
+        (acc << 5) | BASE32.iter().position(|b| *b == ch).unwrap() as u64
+    })
+}
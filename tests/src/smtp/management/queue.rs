@@ -18,7 +18,11 @@ use crate::{
     jmap::ManagementApi,
     smtp::{outbound::TestServer, session::TestSession},
 };
-use smtp::queue::{manager::SpawnQueue, QueueId, Status};
+use smtp::queue::{
+    history::{DeliveryOutcome, DeliveryRecord},
+    manager::SpawnQueue,
+    QueueId, Status,
+};
 
 const LOCAL: &str = r#"
 [storage]
@@ -39,6 +43,9 @@ retry = "1000s"
 notify = "2000s"
 expire = "3000s"
 
+[queue.history]
+retention = "30d"
+
 [session.rcpt]
 relay = true
 max-recipients = 100
@@ -362,6 +369,56 @@ async fn manage_queue() {
         }
     }
 
+    // Delivery to john@foobar.org should have been recorded in the history
+    let history = api
+        .request::<List<DeliveryRecord>>(
+            Method::GET,
+            &format!("/api/queue/history?queue-id={}", id_map.get("e").unwrap()),
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(history.total, 1);
+    let record = &history.items[0];
+    assert_eq!(record.sender, "bill5@foobar.net");
+    assert_eq!(record.domain, "foobar.org");
+    assert_eq!(record.status, DeliveryOutcome::Delivered);
+    assert_eq!(record.attempts, 1);
+    assert_eq!(
+        record.transport.remote_host.as_deref(),
+        Some("mx1.foobar.org")
+    );
+    assert_eq!(
+        record.transport.remote_ip,
+        Some("127.0.0.1".parse().unwrap())
+    );
+    assert_eq!(
+        record
+            .recipients
+            .iter()
+            .map(|r| (r.address.as_str(), r.status))
+            .collect::<Vec<_>>(),
+        vec![("john@foobar.org", DeliveryOutcome::Delivered)]
+    );
+    for (query, expected_total) in [
+        ("from=bill5@foobar.net", 1),
+        ("to=john@foobar.org", 1),
+        ("from=bill1@foobar.net", 0),
+    ] {
+        assert_eq!(
+            api.request::<List<DeliveryRecord>>(
+                Method::GET,
+                &format!("/api/queue/history?{query}")
+            )
+            .await
+            .unwrap()
+            .unwrap_data()
+            .total,
+            expected_total,
+            "failed for {query}"
+        );
+    }
+
     // Cancel deliveries
     for (id, filter) in [
         ("a", "example2.org"),
@@ -0,0 +1,185 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use mail_auth::MX;
+use smtp::{
+    core::SMTP,
+    queue::history::{DeliveryOutcome, DeliveryRecord},
+};
+use store::{
+    write::{QueueClass, ValueClass},
+    IterateParams, ValueKey,
+};
+
+use crate::smtp::{outbound::TestServer, session::TestSession};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+max-recipients = 100
+
+[queue.history]
+retention = "1d"
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn delivery_history() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_history_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+
+    // Delivery history is disabled by default
+    let remote_core = remote.build_smtp();
+    assert_eq!(remote_core.core.smtp.queue.history_retention, None);
+
+    let mut local = TestServer::new("smtp_history_local", LOCAL, true).await;
+    let core = local.build_smtp();
+    assert_eq!(
+        core.core.smtp.queue.history_retention,
+        Some(Duration::from_secs(86400))
+    );
+
+    // Add mock DNS entries
+    for domain in ["foobar.org", "foobar.net"] {
+        core.core.smtp.resolvers.dns.mx_add(
+            domain,
+            vec![MX {
+                exchanges: vec![format!("mx.{domain}")],
+                preference: 10,
+            }],
+            Instant::now() + Duration::from_secs(10),
+        );
+        core.core.smtp.resolvers.dns.ipv4_add(
+            format!("mx.{domain}"),
+            vec!["127.0.0.1".parse().unwrap()],
+            Instant::now() + Duration::from_secs(10),
+        );
+    }
+
+    // Deliver a message to two domains, one of the recipients is rejected
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+    session
+        .send_message(
+            "john@test.org",
+            &["ok@foobar.org", "fail@foobar.org", "ok@foobar.net"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+
+    // One record is written for each domain
+    let mut records = wait_for_history(&core, 2).await;
+    records.sort_by(|a, b| a.domain.cmp(&b.domain));
+    for record in &records {
+        assert_eq!(record.sender, "john@test.org");
+        assert_eq!(record.status, DeliveryOutcome::Delivered);
+        assert_eq!(record.attempts, 1);
+        assert_eq!(
+            record.transport.remote_host.as_deref(),
+            Some(format!("mx.{}", record.domain).as_str())
+        );
+        assert_eq!(
+            record.transport.remote_ip,
+            Some("127.0.0.1".parse().unwrap())
+        );
+        assert!(record.completed >= record.created);
+    }
+    assert_eq!(records[0].domain, "foobar.net");
+    assert_eq!(
+        records[0]
+            .recipients
+            .iter()
+            .map(|r| (r.address.as_str(), r.status))
+            .collect::<Vec<_>>(),
+        vec![("ok@foobar.net", DeliveryOutcome::Delivered)]
+    );
+    assert_eq!(records[1].domain, "foobar.org");
+    assert_eq!(
+        records[1]
+            .recipients
+            .iter()
+            .map(|r| (r.address.as_str(), r.status))
+            .collect::<Vec<_>>(),
+        vec![
+            ("ok@foobar.org", DeliveryOutcome::Delivered),
+            ("fail@foobar.org", DeliveryOutcome::Failed)
+        ]
+    );
+    assert!(
+        records[1].recipients[1]
+            .response
+            .contains("Invalid recipient"),
+        "{:?}",
+        records[1].recipients[1]
+    );
+
+    // Clean up
+    local.qr.clear_queue(&core).await;
+    remote.qr.clear_queue(&remote_core).await;
+}
+
+async fn wait_for_history(core: &SMTP, expected: usize) -> Vec<DeliveryRecord> {
+    for _ in 0..50 {
+        let mut records = Vec::new();
+        core.core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Queue(QueueClass::DeliveryHistory {
+                        id: 0,
+                        expires: 0,
+                    })),
+                    ValueKey::from(ValueClass::Queue(QueueClass::DeliveryHistory {
+                        id: u64::MAX,
+                        expires: u64::MAX,
+                    })),
+                ),
+                |_, value| {
+                    records.push(serde_json::from_slice::<DeliveryRecord>(value).unwrap());
+                    Ok(true)
+                },
+            )
+            .await
+            .unwrap();
+
+        if records.len() >= expected {
+            assert_eq!(records.len(), expected);
+            return records;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("Timed out waiting for {expected} delivery records.");
+}
@@ -0,0 +1,309 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use common::{config::server::ServerProtocol, manager::webadmin::Resource};
+use hyper::{body, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use jmap::api::http::{fetch_body, ToHttpResponse};
+use mail_auth::MX;
+use smtp::{
+    inbound::hooks::{SmtpResponse, Stage},
+    outbound::hooks::{Action, Modification, Request, Response},
+    queue::{Error, Status},
+};
+use tokio::{net::TcpListener, sync::watch};
+
+use crate::smtp::{
+    inbound::{TestMessage, TestQueueEvent},
+    outbound::TestServer,
+    session::{TestSession, VerifyResponse},
+};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[queue.hook.test]
+url = "http://127.0.0.1:9335"
+enable = true
+stages = ["delivery", "result"]
+
+[remote.relay]
+address = relay.foobar.org
+port = 9925
+protocol = 'smtp'
+
+[remote.relay.tls]
+implicit = false
+allow-invalid-certs = true
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn outbound_hooks() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start mock hook server
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let _hook_rx = spawn_mock_outbound_hook_server(results.clone());
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_hooks_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+
+    let mut local = TestServer::new("smtp_hooks_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    for host in ["mx.foobar.org", "relay.foobar.org"] {
+        core.core.smtp.resolvers.dns.ipv4_add(
+            host,
+            vec!["127.0.0.1".parse().unwrap()],
+            Instant::now() + Duration::from_secs(10),
+        );
+    }
+
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+
+    // Recipients removed by the hook are not delivered
+    session
+        .send_message(
+            "john@test.org",
+            &["bill@foobar.org", "drop@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    assert_eq!(
+        remote
+            .qr
+            .expect_message()
+            .await
+            .recipients
+            .into_iter()
+            .map(|rcpt| rcpt.address)
+            .collect::<Vec<_>>(),
+        vec!["bill@foobar.org".to_string()]
+    );
+    local.qr.read_event().await.assert_reload();
+    local.qr.read_event().await.assert_reload();
+    let dsn = local.qr.last_queued_message().await;
+    assert_eq!(dsn.return_path, "");
+    local.qr.clear_queue(&core).await;
+    let result = wait_for_result(&results).await;
+    assert!(results.lock().unwrap().is_empty());
+    assert_eq!(result.context.domain, "foobar.org");
+    let result = result.result.unwrap();
+    assert_eq!(result.status, "completed");
+    for rcpt in result.recipients {
+        match rcpt.address.as_str() {
+            "bill@foobar.org" => assert_eq!(rcpt.status, "completed"),
+            "drop@foobar.org" => assert_eq!(rcpt.status, "perm_fail"),
+            _ => panic!("Unexpected recipient {}", rcpt.address),
+        }
+    }
+
+    // Messages can be routed through a different relay host or next-hop domain
+    for rcpt in ["jane@relay.org", "jane@reroute.org"] {
+        session
+            .send_message("john@test.org", &[rcpt], "test:no_dkim", "250")
+            .await;
+        local
+            .qr
+            .expect_message_then_deliver()
+            .await
+            .try_deliver(core.clone())
+            .await;
+        local.qr.read_event().await.assert_reload();
+        remote
+            .qr
+            .expect_message()
+            .await
+            .read_lines(&remote.qr)
+            .await
+            .assert_contains(rcpt);
+        let result = wait_for_result(&results).await.result.unwrap();
+        assert_eq!(result.status, "completed");
+    }
+
+    // Deferred deliveries are retried later
+    session
+        .send_message("john@test.org", &["jane@defer.org"], "test:no_dkim", "250")
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    let message = local.qr.last_queued_message().await;
+    assert_eq!(
+        message.domains[0].status,
+        Status::TemporaryFailure(Error::Io("Policy server busy".to_string()))
+    );
+    assert_eq!(message.domains[0].retry.inner, 1);
+    let result = wait_for_result(&results).await;
+    assert_eq!(result.context.domain, "defer.org");
+    assert_eq!(result.result.unwrap().status, "temp_fail");
+    remote.qr.assert_no_events();
+}
+
+async fn wait_for_result(results: &Mutex<Vec<Request>>) -> Request {
+    // Result notifications are sent in the background
+    for _ in 0..50 {
+        if let Some(result) = results.lock().unwrap().pop() {
+            return result;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("Timed out waiting for a result notification.");
+}
+
+pub fn spawn_mock_outbound_hook_server(results: Arc<Mutex<Vec<Request>>>) -> watch::Sender<bool> {
+    let (tx, rx) = watch::channel(true);
+
+    tokio::spawn(async move {
+        let listener = TcpListener::bind("127.0.0.1:9335")
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Failed to bind mock hook server to 127.0.0.1:9335: {e}");
+            });
+        let mut rx_ = rx.clone();
+        loop {
+            tokio::select! {
+                stream = listener.accept() => {
+                    match stream {
+                        Ok((stream, _)) => {
+                            let results = results.clone();
+                            let _ = http1::Builder::new()
+                            .keep_alive(false)
+                            .serve_connection(
+                                TokioIo::new(stream),
+                                service_fn(|mut req: hyper::Request<body::Incoming>| {
+                                    let results = results.clone();
+
+                                    async move {
+                                        let request = serde_json::from_slice::<Request>(&fetch_body(&mut req, 1024 * 1024).await.unwrap())
+                                        .unwrap();
+                                        // Result notifications are answered with an empty body
+                                        let contents = handle_outbound_hook(request, results)
+                                            .map(|response| serde_json::to_string(&response).unwrap().into_bytes())
+                                            .unwrap_or_default();
+
+                                        Ok::<_, hyper::Error>(
+                                            Resource {
+                                                content_type: "application/json",
+                                                contents,
+                                            }
+                                            .into_http_response(),
+                                        )
+                                    }
+                                }),
+                            )
+                            .await;
+                        }
+                        Err(err) => {
+                            panic!("Something went wrong: {err}" );
+                        }
+                    }
+                },
+                _ = rx_.changed() => {
+                    break;
+                }
+            };
+        }
+    });
+
+    tx
+}
+
+fn handle_outbound_hook(request: Request, results: Arc<Mutex<Vec<Request>>>) -> Option<Response> {
+    let mut response = Response {
+        action: Action::Accept,
+        response: None,
+        modifications: vec![],
+    };
+
+    match request.context.stage {
+        Stage::Delivery => match request.context.domain.as_str() {
+            "foobar.org" => {
+                if request
+                    .envelope
+                    .to
+                    .iter()
+                    .any(|rcpt| rcpt.address == "drop@foobar.org")
+                {
+                    response.modifications.push(Modification::DeleteRecipient {
+                        value: "drop@foobar.org".to_string(),
+                        reason: None,
+                    });
+                }
+            }
+            "relay.org" => {
+                response.modifications.push(Modification::ChangeRelay {
+                    value: "relay".to_string(),
+                });
+            }
+            "reroute.org" => {
+                response.modifications.push(Modification::Reroute {
+                    value: "foobar.org".to_string(),
+                });
+            }
+            "defer.org" => {
+                response.action = Action::Defer;
+                response.response = Some(SmtpResponse {
+                    message: Some("Policy server busy".to_string()),
+                    ..Default::default()
+                });
+            }
+            _ => (),
+        },
+        Stage::Result => {
+            results.lock().unwrap().push(request);
+            return None;
+        }
+        _ => panic!("Unexpected stage"),
+    }
+
+    Some(response)
+}
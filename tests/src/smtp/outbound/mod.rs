@@ -26,12 +26,16 @@ use super::{
 pub mod dane;
 pub mod extensions;
 pub mod fallback_relay;
+pub mod history;
+pub mod hooks;
 pub mod ip_lookup;
 pub mod lmtp;
 pub mod mta_sts;
+pub mod pool;
 pub mod smtp;
 pub mod throttle;
 pub mod tls;
+pub mod transcript;
 
 const CONFIG: &str = r#"
 [session.connect]
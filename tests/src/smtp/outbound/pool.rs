@@ -0,0 +1,126 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use mail_auth::MX;
+
+use crate::smtp::{
+    inbound::{TestMessage, TestQueueEvent},
+    outbound::TestServer,
+    session::{TestSession, VerifyResponse},
+};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[queue.outbound.pool]
+enable = true
+idle-timeout = "1s"
+max-messages = 3
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn connection_pool() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_pool_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+
+    let mut local = TestServer::new("smtp_pool_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+
+    // The connection is kept open after delivering the first message,
+    // and reused for the second one.
+    for rcpt in ["bill@foobar.org", "jane@foobar.org"] {
+        session
+            .send_message("john@test.org", &[rcpt], "test:no_dkim", "250")
+            .await;
+        local
+            .qr
+            .expect_message_then_deliver()
+            .await
+            .try_deliver(core.clone())
+            .await;
+        local.qr.read_event().await.assert_reload();
+        remote
+            .qr
+            .expect_message()
+            .await
+            .read_lines(&remote.qr)
+            .await
+            .assert_contains(rcpt)
+            .assert_contains("using TLSv1.3 with cipher");
+        assert_eq!(core.inner.connection_pool.idle_connections(), 1);
+    }
+
+    // The connection is closed after reaching the maximum number of messages
+    session
+        .send_message("john@test.org", &["bill@foobar.org"], "test:no_dkim", "250")
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    remote.qr.expect_message().await;
+    assert_eq!(core.inner.connection_pool.idle_connections(), 0);
+
+    // Idle connections are closed after the idle timeout
+    session
+        .send_message("john@test.org", &["jane@foobar.org"], "test:no_dkim", "250")
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    remote.qr.expect_message().await;
+    assert_eq!(core.inner.connection_pool.idle_connections(), 1);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(core.inner.connection_pool.idle_connections(), 0);
+}
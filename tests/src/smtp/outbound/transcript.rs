@@ -0,0 +1,245 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use mail_auth::MX;
+use smtp::core::{
+    transcript::{Direction, LineType, TranscriptRecord},
+    SMTP,
+};
+use store::{
+    write::{QueueClass, ValueClass},
+    Deserialize, IterateParams, ValueKey,
+};
+
+use crate::smtp::{
+    inbound::{TestMessage, TestQueueEvent},
+    outbound::TestServer,
+    session::{TestSession, VerifyResponse},
+};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[session.transcript]
+enable = true
+max-size = 400
+
+[queue.outbound.transcript]
+enable = true
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+
+[session.transcript]
+enable = [{if = "sender_domain = 'test.org'", then = true},
+          {else = false}]
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn transcripts() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_transcript_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+
+    let mut local = TestServer::new("smtp_transcript_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    // Record an inbound session, AUTH payloads and message bodies are redacted
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.init_transcript().await;
+    session
+        .cmd("AUTH PLAIN dGVzdAB0ZXN0AHNlY3JldA==", "503")
+        .await;
+    session.ehlo("mx.test.org").await;
+    session
+        .send_message("john@test.org", &["bill@foobar.org"], "test:no_dkim", "250")
+        .await;
+    session
+        .send_message(
+            "jane@other.org",
+            &["bill@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    drop(session);
+
+    // Deliver both messages, only the first one is recorded by the remote host
+    for _ in 0..2 {
+        local
+            .qr
+            .expect_message_then_deliver()
+            .await
+            .try_deliver(core.clone())
+            .await;
+        remote
+            .qr
+            .expect_message()
+            .await
+            .read_lines(&remote.qr)
+            .await
+            .assert_contains("Subject: Is dinner ready?");
+        local.qr.read_event().await.assert_reload();
+    }
+
+    let local_transcripts = wait_for_transcripts(&core, 3).await;
+    let remote_transcripts = wait_for_transcripts(&remote.build_smtp(), 1).await;
+
+    // Inbound transcripts are truncated once the maximum size is reached
+    let inbound = local_transcripts
+        .iter()
+        .find(|t| t.direction == Direction::Inbound)
+        .unwrap();
+    assert!(inbound.truncated);
+    assert!(inbound.lines.iter().map(|l| l.text.len()).sum::<usize>() <= 400);
+    assert_eq!(inbound.remote_host.as_deref(), Some("mx.test.org"));
+    assert_eq!(
+        inbound.lines[0].text, "AUTH PLAIN [redacted]",
+        "{:?}",
+        inbound.lines
+    );
+    assert!(inbound
+        .lines
+        .iter()
+        .all(|l| !l.text.contains("dGVzdAB0ZXN0AHNlY3JldA==")));
+
+    // Outbound transcripts include the full exchange with the remote host
+    let outbound = local_transcripts
+        .iter()
+        .filter(|t| t.direction == Direction::Outbound)
+        .collect::<Vec<_>>();
+    assert_eq!(outbound.len(), 2);
+    for transcript in outbound {
+        assert_eq!(transcript.remote_host.as_deref(), Some("mx.foobar.org"));
+        assert!(transcript.queue_id.is_some());
+        assert!(!transcript.truncated);
+        let lines = transcript_text(transcript);
+        lines
+            .assert_contains("S: 220 Test SMTP instance")
+            .assert_contains("C: RCPT TO:<bill@foobar.org>")
+            .assert_contains("bytes of message data redacted]")
+            .assert_not_contains("Is dinner ready?");
+    }
+
+    // Only the session matching the selection expression was recorded remotely
+    assert_eq!(remote_transcripts.len(), 1);
+    let inbound = &remote_transcripts[0];
+    assert_eq!(inbound.direction, Direction::Inbound);
+    assert_eq!(inbound.listener.as_deref(), Some("smtp-debug"));
+    transcript_text(inbound)
+        .assert_contains("C: MAIL FROM:<john@test.org>")
+        .assert_contains("S: 250 2.1.5 OK")
+        .assert_contains("bytes of message data redacted]")
+        .assert_not_contains("jane@other.org")
+        .assert_not_contains("Is dinner ready?");
+}
+
+async fn wait_for_transcripts(core: &SMTP, expected: usize) -> Vec<TranscriptRecord> {
+    // Transcripts are written in the background once the session ends
+    for _ in 0..50 {
+        let mut summaries = Vec::new();
+        core.core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Queue(QueueClass::TranscriptIndex {
+                        id: 0,
+                        expires: 0,
+                    })),
+                    ValueKey::from(ValueClass::Queue(QueueClass::TranscriptIndex {
+                        id: u64::MAX,
+                        expires: u64::MAX,
+                    })),
+                ),
+                |_, value| {
+                    summaries.push(TranscriptRecord::deserialize(value).unwrap());
+                    Ok(true)
+                },
+            )
+            .await
+            .unwrap();
+
+        if summaries.len() >= expected {
+            assert_eq!(summaries.len(), expected);
+
+            // The index only holds summaries, transcripts are fetched by id
+            let mut transcripts = Vec::with_capacity(expected);
+            for summary in summaries {
+                assert!(summary.lines.is_empty());
+                let transcript = core
+                    .core
+                    .storage
+                    .data
+                    .get_value::<TranscriptRecord>(ValueKey::from(ValueClass::Queue(
+                        QueueClass::Transcript(summary.id),
+                    )))
+                    .await
+                    .unwrap()
+                    .expect("Missing transcript");
+                assert_eq!(transcript.id, summary.id);
+                assert!(!transcript.lines.is_empty());
+                transcripts.push(transcript);
+            }
+            return transcripts;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("Timed out waiting for {expected} transcripts.");
+}
+
+fn transcript_text(transcript: &TranscriptRecord) -> Vec<String> {
+    transcript
+        .lines
+        .iter()
+        .map(|line| {
+            format!(
+                "{} {}",
+                match line.typ {
+                    LineType::Client => "C:",
+                    LineType::Server => "S:",
+                    LineType::Info => "*",
+                },
+                line.text
+            )
+        })
+        .collect()
+}
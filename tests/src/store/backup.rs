@@ -0,0 +1,111 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::{
+    manager::{archive::BackupSettings, restore::verify_backup},
+    Core,
+};
+use jmap_proto::types::{collection::Collection, property::Property};
+use store::{
+    write::{BatchBuilder, DirectoryClass, MaybeDynamicValue, ValueClass},
+    Store,
+};
+
+use crate::store::{
+    import_export::{random_bytes, Snapshot},
+    TempDir,
+};
+
+pub async fn test(db: Store) {
+    println!("Running incremental backup tests...");
+    let mut core = Core::default();
+    core.storage.data = db.clone();
+    core.storage.blob = db.clone().into();
+    core.storage.fts = db.clone().into();
+    core.storage.lookup = db.clone().into();
+    db.assert_is_empty(db.clone().into()).await;
+
+    // Create two accounts with a few messages each
+    for account_id in [0u32, 1u32] {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email);
+        for document_id in 0..3u32 {
+            batch
+                .create_document_with_id(document_id)
+                .set(
+                    ValueClass::Property(Property::Subject.into()),
+                    random_bytes(16),
+                )
+                .with_change_id(document_id as u64 + 1)
+                .log(MaybeDynamicValue::Static(vec![document_id as u8]));
+        }
+        batch.add(
+            ValueClass::Directory(DirectoryClass::UsedQuota(account_id)),
+            300,
+        );
+        db.write(batch.build()).await.unwrap();
+    }
+
+    // Full encrypted backup
+    let settings = || BackupSettings {
+        compress: true,
+        secret: Some("secret".to_string()),
+    };
+    let temp_dir = TempDir::new("backup_tests", true);
+    let full_path = temp_dir.path.join("full");
+    let incremental_path = temp_dir.path.join("incremental");
+    core.backup(full_path.clone(), None, settings()).await;
+
+    // Purging a message does not write to the change log
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(1)
+        .with_collection(Collection::Email)
+        .delete_document(2)
+        .clear(ValueClass::Property(Property::Subject.into()))
+        .add(ValueClass::Directory(DirectoryClass::UsedQuota(1)), -100);
+    db.write(batch.build()).await.unwrap();
+
+    // Only the purged account is exported again
+    core.backup(
+        incremental_path.clone(),
+        Some(full_path.clone()),
+        settings(),
+    )
+    .await;
+    let full = read_manifest(&full_path);
+    let incremental = read_manifest(&incremental_path);
+    assert_eq!(incremental["parent"]["id"], full["id"]);
+    assert_eq!(incremental["accounts"]["0"]["backup"], full["id"]);
+    assert_eq!(incremental["accounts"]["1"]["backup"], incremental["id"]);
+    assert_eq!(incremental["encryption"]["algorithm"], "aes-256-gcm");
+    assert_eq!(incremental["parent"]["path"], "../full");
+
+    // Backup chains can be moved to another directory
+    let moved_path = temp_dir.path.join("moved");
+    std::fs::create_dir_all(&moved_path).unwrap();
+    for name in ["full", "incremental"] {
+        std::fs::rename(temp_dir.path.join(name), moved_path.join(name)).unwrap();
+    }
+    let incremental_path = moved_path.join("incremental");
+    verify_backup(incremental_path.clone(), settings()).await;
+
+    // Restoring the incremental backup reproduces the purged store
+    let snapshot = Snapshot::new(&db).await;
+    db.destroy().await;
+    db.assert_is_empty(db.clone().into()).await;
+    core.restore(incremental_path, None, settings()).await;
+    snapshot.assert_is_eq(&Snapshot::new(&db).await);
+
+    db.destroy().await;
+    temp_dir.delete();
+}
+
+fn read_manifest(path: &std::path::Path) -> serde_json::Value {
+    serde_json::from_slice(&std::fs::read(path.join("manifest.json")).unwrap()).unwrap()
+}
@@ -5,7 +5,7 @@
  */
 
 use ahash::AHashSet;
-use common::Core;
+use common::{manager::archive::BackupSettings, Core};
 use jmap_proto::types::{collection::Collection, property::Property};
 use store::{
     rand,
@@ -240,7 +240,8 @@ pub async fn test(db: Store) {
     // Export store
     println!("Exporting store...");
     let temp_dir = TempDir::new("art_vandelay_tests", true);
-    core.backup(temp_dir.path.clone()).await;
+    core.backup(temp_dir.path.clone(), None, BackupSettings::default())
+        .await;
 
     // Destroy store
     println!("Destroying store...");
@@ -249,7 +250,8 @@ pub async fn test(db: Store) {
 
     // Import store
     println!("Importing store...");
-    core.restore(temp_dir.path.clone()).await;
+    core.restore(temp_dir.path.clone(), None, BackupSettings::default())
+        .await;
 
     // Verify hash
     print!("Verifying store hash...");
@@ -262,7 +264,7 @@ pub async fn test(db: Store) {
 }
 
 #[derive(Debug, PartialEq, Eq)]
-struct Snapshot {
+pub struct Snapshot {
     keys: AHashSet<KeyValue>,
 }
 
@@ -274,7 +276,7 @@ struct KeyValue {
 }
 
 impl Snapshot {
-    async fn new(db: &Store) -> Self {
+    pub async fn new(db: &Store) -> Self {
         let is_sql = matches!(
             db,
             Store::SQLite(_) | Store::PostgreSQL(_) | Store::MySQL(_)
@@ -333,7 +335,7 @@ impl Snapshot {
         Snapshot { keys }
     }
 
-    fn assert_is_eq(&self, other: &Self) {
+    pub fn assert_is_eq(&self, other: &Self) {
         let mut is_err = false;
         for key in &self.keys {
             if !other.keys.contains(key) {
@@ -362,6 +364,6 @@ impl Snapshot {
     }
 }
 
-fn random_bytes(len: usize) -> Vec<u8> {
+pub fn random_bytes(len: usize) -> Vec<u8> {
     (0..len).map(|_| rand::random::<u8>()).collect()
 }
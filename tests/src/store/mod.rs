@@ -5,6 +5,7 @@
  */
 
 pub mod assign_id;
+pub mod backup;
 pub mod blob;
 pub mod import_export;
 pub mod lookup;
@@ -92,6 +93,7 @@ pub async fn store_tests() {
     }
 
     import_export::test(store.clone()).await;
+    backup::test(store.clone()).await;
     assign_id::test(store.clone()).await;
     ops::test(store.clone()).await;
     query::test(store.clone(), FtsStore::Store(store.clone()), insert).await;